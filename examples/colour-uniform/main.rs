@@ -147,6 +147,7 @@ impl<B: Backend> RendererState<B> {
                     count: 1,
                     stage_flags: pso::ShaderStageFlags::FRAGMENT,
                     immutable_samplers: false,
+                    flags: pso::DescriptorBindingFlags::empty(),
                 },
                 pso::DescriptorSetLayoutBinding {
                     binding: 1,
@@ -154,6 +155,7 @@ impl<B: Backend> RendererState<B> {
                     count: 1,
                     stage_flags: pso::ShaderStageFlags::FRAGMENT,
                     immutable_samplers: false,
+                    flags: pso::DescriptorBindingFlags::empty(),
                 },
             ],
         );
@@ -171,6 +173,7 @@ impl<B: Backend> RendererState<B> {
                 count: 1,
                 stage_flags: pso::ShaderStageFlags::FRAGMENT,
                 immutable_samplers: false,
+                flags: pso::DescriptorBindingFlags::empty(),
             }],
         );
 
@@ -435,12 +438,14 @@ impl<B: Backend> RendererState<B> {
             cmd_buffer.insert_debug_marker("done", 0);
             cmd_buffer.finish();
 
-            self.device.borrow_mut().queues.queues[0].submit(
-                iter::once(&cmd_buffer),
-                iter::empty(),
-                iter::once(&*sem_image_present),
-                Some(&mut fence),
-            );
+            self.device.borrow_mut().queues.queues[0]
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::once(&*sem_image_present),
+                    Some(&mut fence),
+                )
+                .unwrap();
             command_buffers.push((cmd_buffer, fence));
 
             // present frame
@@ -668,6 +673,8 @@ impl<B: Backend> RenderPassState<B> {
                 inputs: &[],
                 resolves: &[],
                 preserves: &[],
+                view_mask: 0,
+                shading_rate: None,
             };
 
             device
@@ -1159,12 +1166,14 @@ impl<B: Backend> ImageState<B> {
 
             cmd_buffer.finish();
 
-            device_state.queues.queues[0].submit(
-                iter::once(&cmd_buffer),
-                iter::empty(),
-                iter::empty(),
-                Some(&mut transfered_image_fence),
-            );
+            device_state.queues.queues[0]
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::empty(),
+                    Some(&mut transfered_image_fence),
+                )
+                .unwrap();
         }
 
         ImageState {
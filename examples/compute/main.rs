@@ -94,6 +94,7 @@ fn main() {
                     count: 1,
                     stage_flags: pso::ShaderStageFlags::COMPUTE,
                     immutable_samplers: false,
+                    flags: pso::DescriptorBindingFlags::empty(),
                 }),
                 iter::empty(),
             )
@@ -240,12 +241,14 @@ fn main() {
         );
         command_buffer.finish();
 
-        queue_group.queues[0].submit(
-            iter::once(&command_buffer),
-            iter::empty(),
-            iter::empty(),
-            Some(&mut fence),
-        );
+        queue_group.queues[0]
+            .submit(
+                iter::once(&command_buffer),
+                iter::empty(),
+                iter::empty(),
+                Some(&mut fence),
+            )
+            .unwrap();
 
         device.wait_for_fence(&fence, !0).unwrap();
         command_pool.free(iter::once(command_buffer));
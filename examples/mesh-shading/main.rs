@@ -234,6 +234,7 @@ where
                         count: 1,
                         stage_flags: ShaderStageFlags::MESH,
                         immutable_samplers: false,
+                        flags: pso::DescriptorBindingFlags::empty(),
                     }),
                     iter::empty(),
                 )
@@ -377,6 +378,8 @@ where
                 inputs: &[],
                 resolves: &[],
                 preserves: &[],
+                view_mask: 0,
+                shading_rate: None,
             };
 
             ManuallyDrop::new(
@@ -648,12 +651,14 @@ where
             cmd_buffer.end_render_pass();
             cmd_buffer.finish();
 
-            self.queue_group.queues[0].submit(
-                iter::once(&*cmd_buffer),
-                iter::empty(),
-                iter::once(&self.submission_complete_semaphores[frame_idx]),
-                Some(&mut self.submission_complete_fences[frame_idx]),
-            );
+            self.queue_group.queues[0]
+                .submit(
+                    iter::once(&*cmd_buffer),
+                    iter::empty(),
+                    iter::once(&self.submission_complete_semaphores[frame_idx]),
+                    Some(&mut self.submission_complete_fences[frame_idx]),
+                )
+                .unwrap();
 
             // present frame
             let result = self.queue_group.queues[0].present(
@@ -29,7 +29,7 @@ pub fn wasm_main() {
 
 use hal::{
     buffer, command, display, format as f,
-    format::{AsFormat, ChannelType, Rgba8Srgb as ColorFormat, Swizzle},
+    format::{AsFormat, Rgba8Srgb as ColorFormat, Swizzle},
     image as i, memory as m, pass,
     pass::Subpass,
     pool,
@@ -356,6 +356,7 @@ where
                             count: 1,
                             stage_flags: ShaderStageFlags::FRAGMENT,
                             immutable_samplers: false,
+                            flags: pso::DescriptorBindingFlags::empty(),
                         },
                         pso::DescriptorSetLayoutBinding {
                             binding: 1,
@@ -363,6 +364,7 @@ where
                             count: 1,
                             stage_flags: ShaderStageFlags::FRAGMENT,
                             immutable_samplers: false,
+                            flags: pso::DescriptorBindingFlags::empty(),
                         },
                     ]
                     .into_iter(),
@@ -674,12 +676,14 @@ where
 
             cmd_buffer.finish();
 
-            queue_group.queues[0].submit(
-                iter::once(&cmd_buffer),
-                iter::empty(),
-                iter::empty(),
-                Some(&mut copy_fence),
-            );
+            queue_group.queues[0]
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::empty(),
+                    Some(&mut copy_fence),
+                )
+                .unwrap();
 
             device
                 .wait_for_fence(&copy_fence, !0)
@@ -691,17 +695,12 @@ where
         }
 
         let caps = surface.capabilities(&adapter.physical_device);
-        let formats = surface.supported_formats(&adapter.physical_device);
+        let formats = surface.ranked_formats(&adapter.physical_device);
         println!("formats: {:?}", formats);
-        let format = formats.map_or(f::Format::Rgba8Srgb, |formats| {
-            formats
-                .iter()
-                .find(|format| format.base_format().1 == ChannelType::Srgb)
-                .map(|format| *format)
-                .unwrap_or(formats[0])
-        });
+        let format = formats[0].format;
 
-        let swap_config = window::SwapchainConfig::from_caps(&caps, format, DIMS);
+        let swap_config = window::SwapchainConfig::from_caps(&caps, format, DIMS)
+            .with_color_space(formats[0].color_space);
         let fat = swap_config.framebuffer_attachment();
         println!("{:?}", swap_config);
         let extent = swap_config.extent;
@@ -729,6 +728,8 @@ where
                 inputs: &[],
                 resolves: &[],
                 preserves: &[],
+                view_mask: 0,
+                shading_rate: None,
             };
 
             ManuallyDrop::new(
@@ -1077,12 +1078,14 @@ where
             cmd_buffer.end_render_pass();
             cmd_buffer.finish();
 
-            self.queue_group.queues[0].submit(
-                iter::once(&*cmd_buffer),
-                iter::empty(),
-                iter::once(&self.submission_complete_semaphores[frame_idx]),
-                Some(&mut self.submission_complete_fences[frame_idx]),
-            );
+            self.queue_group.queues[0]
+                .submit(
+                    iter::once(&*cmd_buffer),
+                    iter::empty(),
+                    iter::once(&self.submission_complete_semaphores[frame_idx]),
+                    Some(&mut self.submission_complete_fences[frame_idx]),
+                )
+                .unwrap();
 
             // present frame
             let result = self.queue_group.queues[0].present(
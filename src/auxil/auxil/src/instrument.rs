@@ -0,0 +1,896 @@
+//! Instrumentation of SPIR-V shaders for GPU-assisted validation.
+//!
+//! Backends translating the shaders from SPIR-V instrument them before the
+//! translation. A check is added in front of each dynamic index into an array,
+//! a vector or a matrix, including the arrays of descriptors. When the index is
+//! out of range, the violation is written to a storage buffer along with the
+//! draw or dispatch that caused it, and the first element is accessed instead.
+//!
+//! The storage buffer is bound at `BINDING` of `DESCRIPTOR_SET`, and holds
+//! `RECORD_WORDS` 32-bit words. The backend writes an identifier of each draw
+//! or dispatch to the `CURRENT_DRAW` word before executing it, and reads the
+//! other words back to report the violations.
+
+use crate::{FastHashMap, FastHashSet};
+use std::fmt;
+
+/// Descriptor set of the storage buffer the violations are written to.
+pub const DESCRIPTOR_SET: u32 = !0;
+/// Binding of the storage buffer the violations are written to.
+pub const BINDING: u32 = 0;
+
+/// Word holding the identifier of the draw or dispatch being executed.
+pub const CURRENT_DRAW: usize = 0;
+/// Word receiving the identifier of the draw or dispatch of the violation.
+pub const DRAW: usize = 1;
+/// Word receiving the number of the failed check plus one, zero without violation.
+pub const CHECK: usize = 2;
+/// Word receiving the index that was out of range.
+pub const INDEX: usize = 3;
+/// Word receiving the length the index was checked against.
+pub const LENGTH: usize = 4;
+/// Number of words of the storage buffer.
+pub const RECORD_WORDS: usize = 5;
+
+const MAGIC_NUMBER: u32 = 0x07230203;
+const HEADER_WORDS: usize = 5;
+/// First version with the `StorageBuffer` storage class in the core.
+const VERSION_1_3: u32 = 0x0001_0300;
+/// First version listing all the global variables in the interface of the entry points.
+const VERSION_1_4: u32 = 0x0001_0400;
+
+mod op {
+    pub const NAME: u16 = 5;
+    pub const LINE: u16 = 8;
+    pub const ENTRY_POINT: u16 = 15;
+    pub const TYPE_BOOL: u16 = 20;
+    pub const TYPE_INT: u16 = 21;
+    pub const TYPE_VECTOR: u16 = 23;
+    pub const TYPE_MATRIX: u16 = 24;
+    pub const TYPE_ARRAY: u16 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u16 = 29;
+    pub const TYPE_STRUCT: u16 = 30;
+    pub const TYPE_POINTER: u16 = 32;
+    pub const CONSTANT: u16 = 43;
+    pub const FUNCTION: u16 = 54;
+    pub const FUNCTION_END: u16 = 56;
+    pub const VARIABLE: u16 = 59;
+    pub const LOAD: u16 = 61;
+    pub const STORE: u16 = 62;
+    pub const ACCESS_CHAIN: u16 = 65;
+    pub const IN_BOUNDS_ACCESS_CHAIN: u16 = 66;
+    pub const ARRAY_LENGTH: u16 = 68;
+    pub const DECORATE: u16 = 71;
+    pub const MEMBER_DECORATE: u16 = 72;
+    pub const BITCAST: u16 = 124;
+    pub const SELECT: u16 = 169;
+    pub const U_LESS_THAN: u16 = 176;
+    pub const PHI: u16 = 245;
+    pub const LOOP_MERGE: u16 = 246;
+    pub const SELECTION_MERGE: u16 = 247;
+    pub const LABEL: u16 = 248;
+    pub const BRANCH: u16 = 249;
+    pub const BRANCH_CONDITIONAL: u16 = 250;
+    pub const NO_LINE: u16 = 317;
+}
+
+mod decoration {
+    pub const BLOCK: u32 = 2;
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+mod storage_class {
+    pub const UNIFORM: u32 = 2;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+/// What the checked index selects.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckKind {
+    /// An element of an array, a vector or a matrix.
+    Element,
+    /// A descriptor of an array of descriptors.
+    Descriptor,
+}
+
+/// Index checked by the instrumentation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Check {
+    /// What the index selects.
+    pub kind: CheckKind,
+    /// Id of the access chain using the index.
+    pub access: u32,
+    /// Name of the accessed variable, if the module names it.
+    pub variable: Option<String>,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CheckKind::Element => write!(f, "element index")?,
+            CheckKind::Descriptor => write!(f, "descriptor index")?,
+        }
+        match self.variable {
+            Some(ref name) => write!(f, " into `{}`", name)?,
+            None => write!(f, " into an unnamed variable")?,
+        }
+        write!(f, " (access chain %{})", self.access)
+    }
+}
+
+/// Shader instrumented by `instrument`.
+#[derive(Clone, Debug)]
+pub struct Instrumented {
+    /// Words of the instrumented module.
+    pub words: Vec<u32>,
+    /// Checks added to the module, by their number minus `first_check`.
+    pub checks: Vec<Check>,
+}
+
+/// Instrument a SPIR-V module, numbering its checks from `first_check`.
+///
+/// The checks aren't added to the blocks declaring a loop, nor for the
+/// lengths set by specialization constants. A module without any dynamic
+/// index is returned unchanged.
+pub fn instrument(words: &[u32], first_check: u32) -> Result<Instrumented, String> {
+    if words.len() < HEADER_WORDS || words[0] != MAGIC_NUMBER {
+        return Err("Missing SPIR-V header".into());
+    }
+    let version = words[1];
+    let instructions = parse(&words[HEADER_WORDS..])?;
+    let module = Module::new(&instructions);
+    let mut builder = Builder::new(&module, version, words[3]);
+
+    let first_function = instructions
+        .iter()
+        .position(|inst| inst.opcode == op::FUNCTION)
+        .unwrap_or(instructions.len());
+    let mut functions = Vec::with_capacity(instructions.len() - first_function);
+    let mut checks = Vec::new();
+    let mut final_labels = FastHashMap::default();
+    let mut start = first_function;
+    while start < instructions.len() {
+        let inst = &instructions[start];
+        let end = instructions[start + 1..]
+            .iter()
+            .position(|inst| matches!(inst.opcode, op::LABEL | op::FUNCTION | op::FUNCTION_END))
+            .map_or(instructions.len(), |offset| start + 1 + offset);
+        if inst.opcode != op::LABEL {
+            // Function declaration, parameters and end.
+            if inst.opcode == op::FUNCTION {
+                final_labels.clear();
+            }
+            functions.extend(instructions[start..end].iter().cloned());
+            if inst.opcode == op::FUNCTION_END {
+                fix_phis(&mut functions, &final_labels);
+            }
+            start = end;
+            continue;
+        }
+        let block = &instructions[start..end];
+        let label = inst.words[1];
+        if block.iter().any(|inst| inst.opcode == op::LOOP_MERGE) {
+            functions.extend(block.iter().cloned());
+        } else {
+            let mut current_label = label;
+            for inst in block {
+                let is_access_chain =
+                    inst.opcode == op::ACCESS_CHAIN || inst.opcode == op::IN_BOUNDS_ACCESS_CHAIN;
+                let bounds = if is_access_chain {
+                    module.plan(inst)
+                } else {
+                    Vec::new()
+                };
+                if bounds.is_empty() {
+                    functions.push(inst.clone());
+                    continue;
+                }
+                let mut chain = inst.words.clone();
+                for bound in bounds {
+                    checks.push(Check {
+                        kind: bound.kind,
+                        access: inst.words[2],
+                        variable: module.name(module.root(inst.words[3])),
+                    });
+                    let number = first_check + checks.len() as u32;
+                    let index = chain[4 + bound.position];
+                    let (safe_index, merge) = builder.check(
+                        &mut functions,
+                        index,
+                        bound.index_type,
+                        bound.length,
+                        number,
+                    );
+                    chain[4 + bound.position] = safe_index;
+                    current_label = merge;
+                }
+                functions.push(Instruction {
+                    opcode: inst.opcode,
+                    words: chain,
+                });
+            }
+            if current_label != label {
+                final_labels.insert(label, current_label);
+            }
+        }
+        start = end;
+    }
+
+    if checks.is_empty() {
+        return Ok(Instrumented {
+            words: words.to_vec(),
+            checks,
+        });
+    }
+
+    let Builder {
+        bound,
+        record,
+        decorations,
+        globals,
+        ..
+    } = builder;
+    let first_declaration = instructions[..first_function]
+        .iter()
+        .position(|inst| !is_preamble(inst.opcode))
+        .unwrap_or(first_function);
+
+    let mut result = Vec::with_capacity(words.len() * 2);
+    result.extend_from_slice(&words[..HEADER_WORDS]);
+    result[3] = bound;
+    for inst in &instructions[..first_declaration] {
+        if inst.opcode == op::ENTRY_POINT && version >= VERSION_1_4 {
+            let mut inst = inst.clone();
+            inst.words.push(record);
+            inst.words[0] = (inst.words.len() as u32) << 16 | inst.opcode as u32;
+            result.extend_from_slice(&inst.words);
+        } else {
+            result.extend_from_slice(&inst.words);
+        }
+    }
+    for inst in decorations
+        .iter()
+        .chain(&instructions[first_declaration..first_function])
+        .chain(&globals)
+        .chain(&functions)
+    {
+        result.extend_from_slice(&inst.words);
+    }
+    Ok(Instrumented {
+        words: result,
+        checks,
+    })
+}
+
+#[derive(Clone, Debug)]
+struct Instruction {
+    opcode: u16,
+    /// All the words, starting with the word count and opcode.
+    words: Vec<u32>,
+}
+
+impl Instruction {
+    fn new(opcode: u16, operands: &[u32]) -> Self {
+        let mut words = Vec::with_capacity(1 + operands.len());
+        words.push(((1 + operands.len()) as u32) << 16 | opcode as u32);
+        words.extend_from_slice(operands);
+        Instruction { opcode, words }
+    }
+}
+
+fn parse(mut words: &[u32]) -> Result<Vec<Instruction>, String> {
+    let mut instructions = Vec::new();
+    while let Some(&first) = words.first() {
+        let count = (first >> 16) as usize;
+        if count == 0 || count > words.len() {
+            return Err(format!("Invalid SPIR-V instruction word count {}", count));
+        }
+        instructions.push(Instruction {
+            opcode: first as u16,
+            words: words[..count].to_vec(),
+        });
+        words = &words[count..];
+    }
+    Ok(instructions)
+}
+
+/// Check if the instruction comes before the types, constants and global variables.
+fn is_preamble(opcode: u16) -> bool {
+    matches!(
+        opcode,
+        // Debug instructions, except the line information.
+        2..=7 | 330
+        // Extensions, imports, memory model, entry points and execution modes.
+        | 10 | 11 | 14..=17 | 331
+        // Annotations.
+        | 71..=75 | 332 | 5632 | 5633
+    )
+}
+
+/// Check if the instruction has a result id, but no result type.
+fn has_untyped_result(opcode: u16) -> bool {
+    matches!(
+        opcode,
+        // Types, with the exception of forward pointers.
+        19..=38 | 322 | 327 | 4472 | 5341
+        // Strings, imports, labels and decoration groups.
+        | 7 | 11 | op::LABEL | 73
+    )
+}
+
+/// Check if the instruction doesn't have any result.
+fn has_no_result(opcode: u16) -> bool {
+    matches!(
+        opcode,
+        0 | op::LINE | op::NO_LINE | op::FUNCTION_END | op::STORE | 63 | 64 | 99
+        | 218..=221 | 224 | 225 | 228
+        | op::LOOP_MERGE | op::SELECTION_MERGE | op::BRANCH | op::BRANCH_CONDITIONAL
+        | 251..=257 | 4416 | 5364 | 5365 | 5380
+    )
+}
+
+/// Definitions of the module looked up by the instrumentation.
+struct Module<'a> {
+    instructions: &'a [Instruction],
+    /// Instruction defining each value, with a result type.
+    values: FastHashMap<u32, usize>,
+    /// Instruction declaring each type.
+    types: FastHashMap<u32, usize>,
+    /// Values of the 32-bit integer constants.
+    integers: FastHashMap<u32, u32>,
+    /// Constants, including the specialization constants.
+    constants: FastHashSet<u32>,
+    /// Variables bound to descriptors.
+    descriptors: FastHashSet<u32>,
+    names: FastHashMap<u32, &'a [u32]>,
+}
+
+impl<'a> Module<'a> {
+    fn new(instructions: &'a [Instruction]) -> Self {
+        let mut module = Module {
+            instructions,
+            values: FastHashMap::default(),
+            types: FastHashMap::default(),
+            integers: FastHashMap::default(),
+            constants: FastHashSet::default(),
+            descriptors: FastHashSet::default(),
+            names: FastHashMap::default(),
+        };
+        for (index, inst) in instructions.iter().enumerate() {
+            let words = &inst.words;
+            match inst.opcode {
+                op::NAME if words.len() > 2 => {
+                    module.names.insert(words[1], &words[2..]);
+                }
+                op::DECORATE if words.len() > 2 && words[2] == decoration::DESCRIPTOR_SET => {
+                    module.descriptors.insert(words[1]);
+                }
+                op::LABEL => {}
+                opcode if has_untyped_result(opcode) && words.len() > 1 => {
+                    module.types.insert(words[1], index);
+                }
+                opcode if has_no_result(opcode) || is_preamble(opcode) => {}
+                opcode if words.len() > 2 => {
+                    // Values are defined before their uses, but for the phis.
+                    module.values.entry(words[2]).or_insert(index);
+                    if let 41..=46 | 48..=52 = opcode {
+                        module.constants.insert(words[2]);
+                    }
+                    if opcode == op::CONSTANT && module.is_integer(words[1]) {
+                        module.integers.insert(words[2], words[3]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        module
+    }
+
+    fn def(&self, id: u32) -> Option<&'a Instruction> {
+        self.values.get(&id).map(|&index| &self.instructions[index])
+    }
+
+    fn ty(&self, id: u32) -> Option<&'a Instruction> {
+        self.types.get(&id).map(|&index| &self.instructions[index])
+    }
+
+    fn find_type(&self, operands: &[u32]) -> Option<u32> {
+        self.types.iter().find_map(|(&id, &index)| {
+            let words = &self.instructions[index].words;
+            if words[0] as u16 == operands[0] as u16 && words[2..] == operands[1..] {
+                Some(id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Check if the type is a 32-bit integer, of either signedness.
+    fn is_integer(&self, ty: u32) -> bool {
+        self.ty(ty)
+            .map_or(false, |ty| ty.opcode == op::TYPE_INT && ty.words[2] == 32)
+    }
+
+    /// Variable or parameter a pointer was derived from.
+    fn root(&self, mut pointer: u32) -> u32 {
+        while let Some(def) = self.def(pointer) {
+            match def.opcode {
+                op::ACCESS_CHAIN | op::IN_BOUNDS_ACCESS_CHAIN => pointer = def.words[3],
+                _ => break,
+            }
+        }
+        pointer
+    }
+
+    fn name(&self, id: u32) -> Option<String> {
+        let words = self.names.get(&id)?;
+        let bytes = words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .take_while(|&byte| byte != 0)
+            .collect::<Vec<_>>();
+        String::from_utf8(bytes).ok()
+    }
+
+    /// Find the dynamic indices of an access chain that need to be checked.
+    fn plan(&self, chain: &Instruction) -> Vec<Bound> {
+        let mut bounds = Vec::new();
+        let base = chain.words[3];
+        let pointer = match self.def(base).and_then(|def| self.ty(def.words[1])) {
+            Some(ty) if ty.opcode == op::TYPE_POINTER => ty,
+            _ => return bounds,
+        };
+        let is_descriptor = self.descriptors.contains(&base)
+            && self
+                .def(base)
+                .map_or(false, |def| def.opcode == op::VARIABLE);
+        let indices = &chain.words[4..];
+        let mut current = pointer.words[3];
+        for (position, &index) in indices.iter().enumerate() {
+            let ty = match self.ty(current) {
+                Some(ty) => ty,
+                None => break,
+            };
+            let index_type = match self.def(index) {
+                Some(def) if !self.constants.contains(&index) && self.is_integer(def.words[1]) => {
+                    Some(def.words[1])
+                }
+                _ => None,
+            };
+            let kind = if position == 0 && is_descriptor {
+                CheckKind::Descriptor
+            } else {
+                CheckKind::Element
+            };
+            let length = match ty.opcode {
+                op::TYPE_ARRAY => self
+                    .integers
+                    .get(&ty.words[3])
+                    .cloned()
+                    .map(Length::Constant),
+                op::TYPE_VECTOR | op::TYPE_MATRIX => Some(Length::Constant(ty.words[3])),
+                // The length of a runtime array is only known for the member
+                // of the structure the chain starts from.
+                op::TYPE_RUNTIME_ARRAY if position == 1 => {
+                    self.integers
+                        .get(&indices[0])
+                        .map(|&member| Length::Runtime {
+                            structure: base,
+                            member,
+                        })
+                }
+                op::TYPE_STRUCT => {
+                    match self
+                        .integers
+                        .get(&index)
+                        .and_then(|&member| ty.words.get(2 + member as usize))
+                    {
+                        Some(&member_type) => {
+                            current = member_type;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                op::TYPE_RUNTIME_ARRAY => None,
+                _ => break,
+            };
+            if let (Some(index_type), Some(length)) = (index_type, length) {
+                bounds.push(Bound {
+                    position,
+                    index_type,
+                    length,
+                    kind,
+                });
+            }
+            current = ty.words[2];
+        }
+        bounds
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Length {
+    Constant(u32),
+    /// Length of the runtime array ending the structure.
+    Runtime {
+        structure: u32,
+        member: u32,
+    },
+}
+
+/// Dynamic index of an access chain to check.
+#[derive(Debug)]
+struct Bound {
+    /// Position of the index in the chain.
+    position: usize,
+    index_type: u32,
+    length: Length,
+    kind: CheckKind,
+}
+
+/// Generator of the instructions of the checks.
+struct Builder {
+    bound: u32,
+    uint: u32,
+    boolean: u32,
+    uint_pointer: u32,
+    record: u32,
+    decorations: Vec<Instruction>,
+    /// Types, constants and variables added to the module.
+    globals: Vec<Instruction>,
+    constants: FastHashMap<(u32, u32), u32>,
+}
+
+impl Builder {
+    fn new(module: &Module, version: u32, bound: u32) -> Self {
+        let mut builder = Builder {
+            bound,
+            uint: 0,
+            boolean: 0,
+            uint_pointer: 0,
+            record: 0,
+            decorations: Vec::new(),
+            globals: Vec::new(),
+            constants: FastHashMap::default(),
+        };
+        let (class, block) = if version >= VERSION_1_3 {
+            (storage_class::STORAGE_BUFFER, decoration::BLOCK)
+        } else {
+            (storage_class::UNIFORM, decoration::BUFFER_BLOCK)
+        };
+        // Non-aggregate types can't be declared twice.
+        builder.uint = builder.declare(module, &[op::TYPE_INT as u32, 32, 0]);
+        builder.boolean = builder.declare(module, &[op::TYPE_BOOL as u32]);
+        builder.uint_pointer =
+            builder.declare(module, &[op::TYPE_POINTER as u32, class, builder.uint]);
+
+        let record_type = builder.id();
+        let mut members = vec![record_type];
+        members.extend((0..RECORD_WORDS).map(|_| builder.uint));
+        builder
+            .globals
+            .push(Instruction::new(op::TYPE_STRUCT, &members));
+        let record_pointer = builder.id();
+        builder.globals.push(Instruction::new(
+            op::TYPE_POINTER,
+            &[record_pointer, class, record_type],
+        ));
+        builder.record = builder.id();
+        builder.globals.push(Instruction::new(
+            op::VARIABLE,
+            &[record_pointer, builder.record, class],
+        ));
+
+        builder
+            .decorations
+            .push(Instruction::new(op::DECORATE, &[record_type, block]));
+        for member in 0..RECORD_WORDS as u32 {
+            builder.decorations.push(Instruction::new(
+                op::MEMBER_DECORATE,
+                &[record_type, member, decoration::OFFSET, member * 4],
+            ));
+        }
+        builder.decorations.push(Instruction::new(
+            op::DECORATE,
+            &[builder.record, decoration::DESCRIPTOR_SET, DESCRIPTOR_SET],
+        ));
+        builder.decorations.push(Instruction::new(
+            op::DECORATE,
+            &[builder.record, decoration::BINDING, BINDING],
+        ));
+        builder
+    }
+
+    fn id(&mut self) -> u32 {
+        let id = self.bound;
+        self.bound += 1;
+        id
+    }
+
+    /// Find a type of the module, or declare it.
+    fn declare(&mut self, module: &Module, operands: &[u32]) -> u32 {
+        if let Some(id) = module.find_type(operands) {
+            return id;
+        }
+        let id = self.id();
+        let mut words = vec![id];
+        words.extend_from_slice(&operands[1..]);
+        self.globals
+            .push(Instruction::new(operands[0] as u16, &words));
+        id
+    }
+
+    fn constant(&mut self, ty: u32, value: u32) -> u32 {
+        if let Some(&id) = self.constants.get(&(ty, value)) {
+            return id;
+        }
+        let id = self.id();
+        self.globals
+            .push(Instruction::new(op::CONSTANT, &[ty, id, value]));
+        self.constants.insert((ty, value), id);
+        id
+    }
+
+    /// Pointer to a word of the storage buffer receiving the violations.
+    fn word(&mut self, out: &mut Vec<Instruction>, word: usize) -> u32 {
+        let index = self.constant(self.uint, word as u32);
+        let id = self.id();
+        out.push(Instruction::new(
+            op::ACCESS_CHAIN,
+            &[self.uint_pointer, id, self.record, index],
+        ));
+        id
+    }
+
+    /// Check an index, splitting the block to report the violation.
+    ///
+    /// Returns the index to use instead, and the label of the block
+    /// continuing the original one.
+    fn check(
+        &mut self,
+        out: &mut Vec<Instruction>,
+        index: u32,
+        index_type: u32,
+        length: Length,
+        number: u32,
+    ) -> (u32, u32) {
+        let (length, uint_length) = match length {
+            Length::Constant(value) => (
+                self.constant(index_type, value),
+                self.constant(self.uint, value),
+            ),
+            Length::Runtime { structure, member } => {
+                let id = self.id();
+                out.push(Instruction::new(
+                    op::ARRAY_LENGTH,
+                    &[self.uint, id, structure, member],
+                ));
+                (id, id)
+            }
+        };
+        let in_bounds = self.id();
+        out.push(Instruction::new(
+            op::U_LESS_THAN,
+            &[self.boolean, in_bounds, index, length],
+        ));
+        let report = self.id();
+        let merge = self.id();
+        out.push(Instruction::new(op::SELECTION_MERGE, &[merge, 0]));
+        out.push(Instruction::new(
+            op::BRANCH_CONDITIONAL,
+            &[in_bounds, merge, report],
+        ));
+
+        out.push(Instruction::new(op::LABEL, &[report]));
+        let current_draw = self.word(out, CURRENT_DRAW);
+        let draw = self.id();
+        out.push(Instruction::new(op::LOAD, &[self.uint, draw, current_draw]));
+        let uint_index = if index_type == self.uint {
+            index
+        } else {
+            let id = self.id();
+            out.push(Instruction::new(op::BITCAST, &[self.uint, id, index]));
+            id
+        };
+        let number = self.constant(self.uint, number);
+        for &(word, value) in &[
+            (DRAW, draw),
+            (CHECK, number),
+            (INDEX, uint_index),
+            (LENGTH, uint_length),
+        ] {
+            let pointer = self.word(out, word);
+            out.push(Instruction::new(op::STORE, &[pointer, value]));
+        }
+        out.push(Instruction::new(op::BRANCH, &[merge]));
+
+        out.push(Instruction::new(op::LABEL, &[merge]));
+        let zero = self.constant(index_type, 0);
+        let safe_index = self.id();
+        out.push(Instruction::new(
+            op::SELECT,
+            &[index_type, safe_index, in_bounds, index, zero],
+        ));
+        (safe_index, merge)
+    }
+}
+
+/// Make the phis refer to the blocks ending the split blocks.
+fn fix_phis(instructions: &mut [Instruction], final_labels: &FastHashMap<u32, u32>) {
+    if final_labels.is_empty() {
+        return;
+    }
+    for inst in instructions.iter_mut().rev() {
+        match inst.opcode {
+            op::FUNCTION => break,
+            op::PHI => {
+                for parent in inst.words.iter_mut().skip(4).step_by(2) {
+                    if let Some(&label) = final_labels.get(parent) {
+                        *parent = label;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fragment shader indexing `uniform Block { vec4 colors[4]; } block` with a
+    // dynamic index, then picking the result with a phi.
+    fn module() -> Vec<u32> {
+        let mut words = vec![MAGIC_NUMBER, 0x0001_0000, 0, 30, 0];
+        let mut push = |opcode: u16, operands: &[u32]| {
+            words.extend_from_slice(&Instruction::new(opcode, operands).words)
+        };
+        push(17, &[1]); // OpCapability Shader
+        push(14, &[0, 1]); // OpMemoryModel Logical GLSL450
+        push(15, &[4, 1, u32::from_le_bytes(*b"main"), 0]); // OpEntryPoint Fragment %1 "main"
+        push(
+            op::NAME,
+            &[
+                10,
+                u32::from_le_bytes(*b"bloc"),
+                u32::from_le_bytes(*b"k\0\0\0"),
+            ],
+        );
+        push(op::DECORATE, &[10, decoration::DESCRIPTOR_SET, 0]);
+        push(op::DECORATE, &[10, decoration::BINDING, 0]);
+        push(19, &[2]); // %2 = OpTypeVoid
+        push(33, &[3, 2]); // %3 = OpTypeFunction %2
+        push(op::TYPE_INT, &[4, 32, 1]); // %4 = int
+        push(22, &[5, 32]); // %5 = float
+        push(op::TYPE_VECTOR, &[6, 5, 4]); // %6 = vec4
+        push(op::CONSTANT, &[4, 7, 4]); // %7 = 4
+        push(op::TYPE_ARRAY, &[8, 6, 7]); // %8 = vec4[4]
+        push(op::TYPE_STRUCT, &[9, 8]); // %9 = Block
+        push(op::TYPE_POINTER, &[11, 2, 9]); // %11 = Uniform Block*
+        push(op::VARIABLE, &[11, 10, 2]); // %10 = block
+        push(op::CONSTANT, &[4, 12, 0]); // %12 = 0
+        push(op::TYPE_POINTER, &[13, 2, 6]); // %13 = Uniform vec4*
+        push(op::TYPE_POINTER, &[14, 7, 4]); // %14 = Function int*
+        push(op::FUNCTION, &[2, 1, 0, 3]);
+        push(op::LABEL, &[15]);
+        push(op::VARIABLE, &[14, 16, 7]);
+        push(op::LOAD, &[4, 17, 16]);
+        push(op::ACCESS_CHAIN, &[13, 18, 10, 12, 17]);
+        push(op::LOAD, &[6, 19, 18]);
+        push(op::BRANCH, &[20]);
+        push(op::LABEL, &[20]);
+        push(op::PHI, &[6, 21, 19, 15]);
+        push(253, &[]); // OpReturn
+        push(op::FUNCTION_END, &[]);
+        words
+    }
+
+    #[test]
+    fn check_dynamic_index() {
+        let result = instrument(&module(), 7).unwrap();
+        assert_eq!(
+            result.checks,
+            vec![Check {
+                kind: CheckKind::Element,
+                access: 18,
+                variable: Some("block".to_string()),
+            }]
+        );
+        let instructions = parse(&result.words[HEADER_WORDS..]).unwrap();
+        let bound = result.words[3];
+        assert!(instructions
+            .iter()
+            .filter(|inst| {
+                !is_preamble(inst.opcode) && !has_no_result(inst.opcode) && inst.words.len() > 2
+            })
+            .all(|inst| inst.words[1] < bound && inst.words[2] < bound));
+
+        // The chain uses the clamped index.
+        let chain = instructions
+            .iter()
+            .find(|inst| inst.opcode == op::ACCESS_CHAIN && inst.words[2] == 18)
+            .unwrap();
+        let select = instructions
+            .iter()
+            .find(|inst| inst.opcode == op::SELECT)
+            .unwrap();
+        assert_eq!(chain.words[..5], [6 << 16 | 65, 13, 18, 10, 12]);
+        assert_eq!(chain.words[5], select.words[2]);
+        assert_eq!(select.words[4], 17);
+
+        // The check number is stored in the record.
+        let number = instructions
+            .iter()
+            .find(|inst| inst.opcode == op::CONSTANT && inst.words[3] == 8)
+            .unwrap();
+        assert!(instructions
+            .iter()
+            .any(|inst| inst.opcode == op::STORE && inst.words[2] == number.words[2]));
+
+        // The phi comes from the block continuing the split one.
+        let merge = instructions
+            .iter()
+            .find(|inst| inst.opcode == op::SELECTION_MERGE)
+            .unwrap();
+        let phi = instructions
+            .iter()
+            .find(|inst| inst.opcode == op::PHI)
+            .unwrap();
+        assert_eq!(phi.words[4], merge.words[1]);
+
+        // The record is bound to the reserved descriptor.
+        let record = instructions
+            .iter()
+            .find(|inst| {
+                inst.opcode == op::DECORATE
+                    && inst.words[2] == decoration::DESCRIPTOR_SET
+                    && inst.words[3] == DESCRIPTOR_SET
+            })
+            .unwrap();
+        let variable = instructions
+            .iter()
+            .find(|inst| inst.opcode == op::VARIABLE && inst.words[2] == record.words[1])
+            .unwrap();
+        assert_eq!(variable.words[3], storage_class::UNIFORM);
+        // The declarations precede the first function.
+        let function = instructions
+            .iter()
+            .position(|inst| inst.opcode == op::FUNCTION)
+            .unwrap();
+        let decoration = instructions
+            .iter()
+            .position(|inst| inst.words[..] == record.words[..])
+            .unwrap();
+        let declaration = instructions
+            .iter()
+            .position(|inst| inst.words[..] == variable.words[..])
+            .unwrap();
+        assert!(
+            decoration
+                < instructions
+                    .iter()
+                    .position(|inst| inst.opcode == 19)
+                    .unwrap()
+        );
+        assert!(declaration < function);
+    }
+
+    #[test]
+    fn keep_constant_indices() {
+        let mut words = module();
+        // Replace the dynamic index with the constant 0.
+        let chain = words
+            .windows(6)
+            .position(|w| w == [6 << 16 | 65, 13, 18, 10, 12, 17])
+            .unwrap();
+        words[chain + 5] = 12;
+        let result = instrument(&words, 0).unwrap();
+        assert!(result.checks.is_empty());
+        assert_eq!(result.words, words);
+    }
+}
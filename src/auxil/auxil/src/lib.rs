@@ -2,6 +2,8 @@
 use spirv_cross::spirv;
 use std::{io, slice};
 
+pub mod instrument;
+
 /// Fast hash map used internally.
 pub type FastHashMap<K, V> =
     std::collections::HashMap<K, V, std::hash::BuildHasherDefault<fxhash::FxHasher>>;
@@ -95,6 +97,27 @@ pub fn read_spirv<R: io::Read + io::Seek>(mut x: R) -> io::Result<Vec<u32>> {
     Ok(result)
 }
 
+/// Format the version of a Windows user mode driver, as returned by
+/// `IDXGIAdapter::CheckInterfaceSupport`, which packs it into four 16-bit words.
+pub fn umd_version_string(version: u64) -> String {
+    format!(
+        "{}.{}.{}.{}",
+        version >> 48,
+        (version >> 32) & 0xFFFF,
+        (version >> 16) & 0xFFFF,
+        version & 0xFFFF
+    )
+}
+
+/// Bytes of a Windows `LUID`, laid out as the structure in memory like the
+/// `deviceLUID` of Vulkan, from its low and high parts.
+pub fn luid_bytes(low_part: u32, high_part: i32) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes[..4].copy_from_slice(&low_part.to_ne_bytes());
+    bytes[4..].copy_from_slice(&high_part.to_ne_bytes());
+    bytes
+}
+
 #[cfg(feature = "spirv_cross")]
 pub fn spirv_cross_specialize_ast<T>(
     ast: &mut spirv::Ast<T>,
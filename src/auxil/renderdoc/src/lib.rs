@@ -100,6 +100,40 @@ impl Default for RenderDoc {
 /// A implementation specific handle
 pub type Handle = *mut ::std::os::raw::c_void;
 
+/// Option affecting how RenderDoc captures the frames.
+///
+/// The values match `RENDERDOC_CaptureOption`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CaptureOption {
+    /// Allow the application to enable vsync. Enabled by default.
+    AllowVSync = 0,
+    /// Allow the application to enable fullscreen. Enabled by default.
+    AllowFullscreen = 1,
+    /// Record API debugging events and messages, enabling the validation layers.
+    ApiValidation = 2,
+    /// Capture the callstacks of the API events.
+    CaptureCallstacks = 3,
+    /// Only capture the callstacks of the draw calls, requires `CaptureCallstacks`.
+    CaptureCallstacksOnlyDraws = 4,
+    /// Number of seconds to wait for a debugger to attach after launching.
+    DelayForDebugger = 5,
+    /// Verify the writes to mapped buffers and check for out of bounds accesses.
+    VerifyBufferAccess = 6,
+    /// Hook into the child processes launched by the application.
+    HookIntoChildren = 7,
+    /// Include all the live resources in the captures, not only the referenced ones.
+    RefAllResources = 8,
+    /// Save the initial contents of all the resources at the start of the captures.
+    SaveAllInitials = 9,
+    /// Capture all the command lists from the start of the application.
+    CaptureAllCmdLists = 10,
+    /// Mute the debug output of the API when `ApiValidation` is enabled.
+    DebugOutputMute = 11,
+    /// Allow the vendor extensions unsupported by RenderDoc.
+    AllowUnsupportedVendorExtensions = 12,
+}
+
 impl RenderDoc {
     /// Start a RenderDoc frame capture
     pub unsafe fn start_frame_capture(&self, device_handle: Handle, window_handle: Handle) {
@@ -124,4 +158,95 @@ impl RenderDoc {
             }
         };
     }
+
+    /// Set a capture option to an integer value, such as `1` or `0` for the
+    /// options that are toggles.
+    pub unsafe fn set_capture_option(&self, option: CaptureOption, value: u32) {
+        match self {
+            Self::Available { api: ref entry } => {
+                if entry.api.SetCaptureOptionU32.unwrap()(option as _, value) == 0 {
+                    log::warn!("Invalid RenderDoc capture option {:?} = {}", option, value)
+                }
+            }
+            Self::NotAvailable { ref reason } => {
+                log::warn!("Could not set RenderDoc capture option: {}", reason)
+            }
+        };
+    }
+
+    /// Set the template of the capture file paths, which get a suffix with the
+    /// frame number and the `.rdc` extension.
+    pub unsafe fn set_capture_file_path_template(&self, template: &str) {
+        match self {
+            Self::Available { api: ref entry } => match std::ffi::CString::new(template) {
+                Ok(template) => {
+                    entry
+                        .api
+                        .__bindgen_anon_2
+                        .SetCaptureFilePathTemplate
+                        .unwrap()(template.as_ptr());
+                }
+                Err(e) => log::warn!("Invalid RenderDoc capture file path template: {:?}", e),
+            },
+            Self::NotAvailable { ref reason } => {
+                log::warn!(
+                    "Could not set RenderDoc capture file path template: {}",
+                    reason
+                )
+            }
+        };
+    }
+}
+
+/// Capture of consecutive frames, each of them ended by a presentation.
+///
+/// The owner of the state, typically a queue, calls [`FrameCaptures::frame_presented`]
+/// after presenting, with the same handles as the ones used to start the capture.
+#[derive(Clone, Debug, Default)]
+pub struct FrameCaptures {
+    remaining: u32,
+}
+
+impl FrameCaptures {
+    /// Capture the next `frames` frames, starting now.
+    ///
+    /// If a capture is already in progress, it's extended instead, and counts as
+    /// the first of the frames. Passing `0` ends the capture in progress.
+    pub unsafe fn capture_next_frames(
+        &mut self,
+        render_doc: &RenderDoc,
+        device_handle: Handle,
+        window_handle: Handle,
+        frames: u32,
+    ) {
+        if self.remaining == 0 && frames != 0 {
+            render_doc.start_frame_capture(device_handle, window_handle);
+        } else if self.remaining != 0 && frames == 0 {
+            render_doc.end_frame_capture(device_handle, window_handle);
+        }
+        self.remaining = frames;
+    }
+
+    /// End the capture of the presented frame, and start the capture of the
+    /// next one if more frames are requested.
+    pub unsafe fn frame_presented(
+        &mut self,
+        render_doc: &RenderDoc,
+        device_handle: Handle,
+        window_handle: Handle,
+    ) {
+        if self.remaining == 0 {
+            return;
+        }
+        render_doc.end_frame_capture(device_handle, window_handle);
+        self.remaining -= 1;
+        if self.remaining != 0 {
+            render_doc.start_frame_capture(device_handle, window_handle);
+        }
+    }
+
+    /// Returns true if a capture is in progress.
+    pub fn is_capturing(&self) -> bool {
+        self.remaining != 0
+    }
 }
@@ -0,0 +1,130 @@
+#![warn(
+    trivial_casts,
+    trivial_numeric_casts,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_qualifications
+)]
+
+//! Creation of gfx-rs surfaces for [winit] windows.
+//!
+//! [`WindowSurface::new`] creates the instance of a backend and the surface of a
+//! window, along with the extent of the window in physical pixels. The window
+//! system handles are extracted from the window by the backends, and the GL
+//! backend sets up the EGL context of the window with the surface.
+//!
+//! The extent accounts for the scale factor of the display, so that the swapchain
+//! isn't stretched on high DPI displays. The sizes reported by the window events
+//! are converted the same way by [`physical_extent`].
+//!
+//! The backends are re-exported behind the features of the same name, on the
+//! platforms they support.
+//!
+//! ```no_run
+//! use gfx_window::WindowSurface;
+//! use hal::{format::Format, window::SwapchainConfig};
+//!
+//! fn init<B: hal::Backend>(window: &winit::window::Window) -> WindowSurface<B> {
+//!     let window_surface = WindowSurface::<B>::new(window, "gfx-rs app", 1).unwrap();
+//!     let config = SwapchainConfig::new(
+//!         window_surface.extent.width,
+//!         window_surface.extent.height,
+//!         Format::Bgra8Srgb,
+//!         3,
+//!     );
+//!     // Pick an adapter and configure the surface with `config`...
+//!     window_surface
+//! }
+//! # fn main() {}
+//! ```
+
+#[cfg(all(feature = "dx11", windows))]
+pub use gfx_backend_dx11 as dx11;
+#[cfg(all(feature = "dx12", windows))]
+pub use gfx_backend_dx12 as dx12;
+#[cfg(all(feature = "gl", not(any(target_os = "ios", target_os = "macos"))))]
+pub use gfx_backend_gl as gl;
+#[cfg(all(feature = "metal", any(target_os = "ios", target_os = "macos")))]
+pub use gfx_backend_metal as metal;
+#[cfg(feature = "vulkan")]
+pub use gfx_backend_vulkan as vulkan;
+
+use hal::{
+    window::{Extent2D, InitError},
+    Backend, Instance as _, UnsupportedBackend,
+};
+use winit::{dpi::PhysicalSize, window::Window};
+
+use std::fmt;
+
+/// Error on creating the instance and the surface of a window.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum Error {
+    /// The backend isn't supported on this platform.
+    #[error(transparent)]
+    UnsupportedBackend(#[from] UnsupportedBackend),
+    /// The surface can't be created for the window.
+    #[error(transparent)]
+    Surface(#[from] InitError),
+}
+
+/// Instance of a backend, with the surface of a window.
+pub struct WindowSurface<B: Backend> {
+    /// Instance the surface was created with.
+    pub instance: B::Instance,
+    /// Surface of the window.
+    pub surface: B::Surface,
+    /// Extent of the window when the surface was created, in physical pixels.
+    pub extent: Extent2D,
+}
+
+impl<B: Backend> fmt::Debug for WindowSurface<B> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WindowSurface")
+            .field("surface", &self.surface)
+            .field("extent", &self.extent)
+            .finish()
+    }
+}
+
+impl<B: Backend> WindowSurface<B> {
+    /// Create an instance of the backend, with the application `name` and
+    /// `version`, and the surface of `window`.
+    ///
+    /// The window has to outlive the surface.
+    pub fn new(window: &Window, name: &str, version: u32) -> Result<Self, Error> {
+        let instance = B::Instance::create(name, version)?;
+        let surface = unsafe { instance.create_surface(window)? };
+        Ok(WindowSurface {
+            instance,
+            surface,
+            extent: window_extent(window),
+        })
+    }
+
+    /// Destroy the surface, then the instance.
+    ///
+    /// # Safety
+    ///
+    /// The swapchain of the surface has to be unconfigured, and the devices
+    /// created from the instance have to be destroyed.
+    pub unsafe fn destroy(self) {
+        self.instance.destroy_surface(self.surface);
+    }
+}
+
+/// Extent of the window, in physical pixels.
+pub fn window_extent(window: &Window) -> Extent2D {
+    physical_extent(window.inner_size())
+}
+
+/// Convert a size reported by winit to an extent, such as the new size of a
+/// `WindowEvent::Resized` or `WindowEvent::ScaleFactorChanged` event.
+///
+/// Minimized windows have an empty extent, on which swapchains can't be configured.
+pub fn physical_extent(size: PhysicalSize<u32>) -> Extent2D {
+    Extent2D {
+        width: size.width,
+        height: size.height,
+    }
+}
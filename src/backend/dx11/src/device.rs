@@ -50,12 +50,13 @@ pub struct DepthStencilState {
 }
 
 pub struct Device {
-    raw: ComPtr<d3d11::ID3D11Device>,
+    pub(crate) raw: ComPtr<d3d11::ID3D11Device>,
     raw1: Option<ComPtr<d3d11_1::ID3D11Device1>>,
     pub(crate) context: ComPtr<d3d11::ID3D11DeviceContext>,
     features: hal::Features,
     memory_properties: MemoryProperties,
-    render_doc: gfx_renderdoc::RenderDoc,
+    format_properties: Arc<[format::Properties; format::NUM_FORMATS]>,
+    pub(crate) render_doc: Arc<gfx_renderdoc::RenderDoc>,
     pub(crate) internal: Arc<internal::Internal>,
 }
 
@@ -86,6 +87,7 @@ impl Device {
         features: hal::Features,
         downlevel: hal::DownlevelProperties,
         memory_properties: MemoryProperties,
+        format_properties: Arc<[format::Properties; format::NUM_FORMATS]>,
         feature_level: u32,
     ) -> Self {
         Device {
@@ -100,6 +102,7 @@ impl Device {
             context,
             features,
             memory_properties,
+            format_properties,
             render_doc: Default::default(),
         }
     }
@@ -108,6 +111,11 @@ impl Device {
         self.raw.as_raw()
     }
 
+    /// RenderDoc integration, to configure the captures.
+    pub fn render_doc(&self) -> &gfx_renderdoc::RenderDoc {
+        &self.render_doc
+    }
+
     fn create_rasterizer_state(
         &self,
         rasterizer_desc: &pso::Rasterizer,
@@ -1007,6 +1015,10 @@ impl device::Device<Backend> for Device {
         desc: &pso::GraphicsPipelineDesc<'a, Backend>,
         _cache: Option<&()>,
     ) -> Result<GraphicsPipeline, pso::CreationError> {
+        if desc.transform_feedback.is_some() {
+            // `Features::TRANSFORM_FEEDBACK` isn't exposed.
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
         let features = &self.features;
         let build_shader =
             |stage: ShaderStage, source: Option<&pso::EntryPoint<'a, Backend>>| match source {
@@ -1437,6 +1449,10 @@ impl device::Device<Backend> for Device {
         unimplemented!()
     }
 
+    fn format_properties(&self, format: format::Format) -> format::Properties {
+        self.format_properties[format as usize].clone()
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -1905,8 +1921,14 @@ impl device::Device<Backend> for Device {
     unsafe fn create_sampler(
         &self,
         info: &image::SamplerDesc,
-    ) -> Result<Sampler, device::AllocationError> {
-        assert!(info.normalized);
+    ) -> Result<Sampler, image::SamplerCreationError> {
+        let mut unsupported = info.unsupported_fields(self.features);
+        if !info.normalized {
+            unsupported |= image::SamplerFields::NORMALIZED;
+        }
+        if !unsupported.is_empty() {
+            return Err(image::SamplerCreationError::Unsupported(unsupported));
+        }
 
         let op = match info.comparison {
             Some(_) => d3d11::D3D11_FILTER_REDUCTION_TYPE_COMPARISON,
@@ -2083,6 +2105,9 @@ impl device::Device<Backend> for Device {
                     s: sampler.sampler_handle.as_raw() as *mut _,
                 },
                 pso::Descriptor::TexelBuffer(_buffer_view) => unimplemented!(),
+                pso::Descriptor::AccelerationStructure(_) => {
+                    unreachable!("Acceleration structures are never exposed")
+                }
             };
 
             let content = DescriptorContent::from(binding.ty);
@@ -2305,6 +2330,36 @@ impl device::Device<Backend> for Device {
         }
     }
 
+    fn memory_budget(&self) -> Vec<memory::HeapBudget> {
+        // The usage of the heaps isn't tracked.
+        self.memory_properties
+            .memory_heaps
+            .iter()
+            .map(|heap| memory::HeapBudget {
+                usage: 0,
+                budget: heap.size,
+            })
+            .collect()
+    }
+
+    unsafe fn set_memory_priority(&self, _memory: &Memory, _priority: memory::Priority) {
+        // The residency of the memory isn't managed by the backend.
+    }
+
+    unsafe fn make_resident<'a, I>(&self, _memories: I) -> Result<(), device::OutOfMemory>
+    where
+        I: Iterator<Item = &'a Memory>,
+    {
+        Ok(())
+    }
+
+    unsafe fn evict<'a, I>(&self, _memories: I)
+    where
+        I: Iterator<Item = &'a Memory>,
+    {
+        // The residency of the memory isn't managed by the backend.
+    }
+
     unsafe fn create_query_pool(
         &self,
         _query_ty: query::Type,
@@ -2317,6 +2372,29 @@ impl device::Device<Backend> for Device {
         unimplemented!()
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _geometry: &hal::acceleration_structure::GeometryInfo<Backend>,
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {}
+
     unsafe fn get_query_pool_results(
         &self,
         _pool: &QueryPool,
@@ -2342,6 +2420,26 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_compute_pipeline(&self, _pipeline: ComputePipeline) {}
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _cache: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {}
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+        _data: &mut [u8],
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
     unsafe fn destroy_framebuffer(&self, _fb: Framebuffer) {}
 
     unsafe fn destroy_buffer(&self, buffer: Buffer) {
@@ -2400,7 +2498,7 @@ impl device::Device<Backend> for Device {
         //unimplemented!()
     }
 
-    fn wait_idle(&self) -> Result<(), device::OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), device::OomOrDeviceLost> {
         Ok(())
         // unimplemented!()
     }
@@ -2461,6 +2559,35 @@ impl device::Device<Backend> for Device {
         // TODO
     }
 
+    unsafe fn set_graphics_pipeline_name(&self, pipeline: &mut GraphicsPipeline, name: &str) {
+        if !verify_debug_ascii(name) {
+            return;
+        }
+
+        set_debug_name(&pipeline.vs, &format!("{} -- Vertex Shader", name));
+        if let Some(ref gs) = pipeline.gs {
+            set_debug_name(gs, &format!("{} -- Geometry Shader", name));
+        }
+        if let Some(ref hs) = pipeline.hs {
+            set_debug_name(hs, &format!("{} -- Hull Shader", name));
+        }
+        if let Some(ref ds) = pipeline.ds {
+            set_debug_name(ds, &format!("{} -- Domain Shader", name));
+        }
+        if let Some(ref ps) = pipeline.ps {
+            set_debug_name(ps, &format!("{} -- Pixel Shader", name));
+        }
+        set_debug_name(&pipeline.input_layout, &format!("{} -- Input Layout", name));
+    }
+
+    unsafe fn set_compute_pipeline_name(&self, pipeline: &mut ComputePipeline, name: &str) {
+        if !verify_debug_ascii(name) {
+            return;
+        }
+
+        set_debug_name(&pipeline.cs, &format!("{} -- Compute Shader", name));
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &hal::display::Display<Backend>,
@@ -2534,6 +2661,7 @@ impl device::Device<Backend> for Device {
         _sparse: memory::SparseFlags,
         _view_caps: image::ViewCapabilities,
         _type_mask: u32,
+        __size: u64,
     ) -> Result<(Image, Memory), hal::external_memory::ExternalResourceError> {
         unimplemented!()
     }
@@ -6,7 +6,7 @@ use winapi::{
         guiddef::{GUID, REFIID},
         winerror,
     },
-    um::{d3d11, unknwnbase::IUnknown},
+    um::{d3d11, unknwnbase::IUnknown, winnt},
     Interface,
 };
 
@@ -140,12 +140,25 @@ fn enum_adapters1(
     }
 }
 
+/// Get the version of the user mode driver.
+fn driver_version(adapter: *mut dxgi::IDXGIAdapter) -> String {
+    let mut umd_version: winnt::LARGE_INTEGER = unsafe { mem::zeroed() };
+    let hr =
+        unsafe { (*adapter).CheckInterfaceSupport(&dxgi::IDXGIDevice::uuidof(), &mut umd_version) };
+    if !winerror::SUCCEEDED(hr) {
+        return String::new();
+    }
+    auxil::umd_version_string(unsafe { *umd_version.QuadPart() } as u64)
+}
+
 fn build_adapter_info(
     description: &[u16],
     vendor_id: u32,
     device_id: u32,
     flags: u32,
     video_mem: usize,
+    luid: winnt::LUID,
+    driver: String,
     device: &ComPtr<d3d11::ID3D11Device>,
 ) -> AdapterInfo {
     let mut features_options2: d3d11::D3D11_FEATURE_DATA_D3D11_OPTIONS2 = unsafe { mem::zeroed() };
@@ -163,23 +176,30 @@ fn build_adapter_info(
         name.to_string_lossy().into_owned()
     };
 
+    let device_type = if (flags & dxgi::DXGI_ADAPTER_FLAG_SOFTWARE) != 0 {
+        DeviceType::VirtualGpu
+    } else if discovered_gpu_type {
+        if features_options2.UnifiedMemoryArchitecture == 1 {
+            DeviceType::IntegratedGpu
+        } else {
+            DeviceType::DiscreteGpu
+        }
+    } else if video_mem <= 512_000_000 {
+        DeviceType::IntegratedGpu
+    } else {
+        DeviceType::DiscreteGpu
+    };
+    let unified_memory = device_type == DeviceType::IntegratedGpu;
+
     AdapterInfo {
         name: device_name,
         vendor: vendor_id as usize,
         device: device_id as usize,
-        device_type: if (flags & dxgi::DXGI_ADAPTER_FLAG_SOFTWARE) != 0 {
-            DeviceType::VirtualGpu
-        } else if discovered_gpu_type {
-            if features_options2.UnifiedMemoryArchitecture == 1 {
-                DeviceType::IntegratedGpu
-            } else {
-                DeviceType::DiscreteGpu
-            }
-        } else if video_mem <= 512_000_000 {
-            DeviceType::IntegratedGpu
-        } else {
-            DeviceType::DiscreteGpu
-        },
+        device_type,
+        driver,
+        unified_memory,
+        luid: Some(auxil::luid_bytes(luid.LowPart, luid.HighPart)),
+        uuid: None,
     }
 }
 
@@ -203,6 +223,8 @@ pub(crate) fn get_adapter_desc(
                 desc.DeviceId,
                 desc.Flags,
                 desc.DedicatedVideoMemory,
+                desc.AdapterLuid,
+                driver_version(adapter),
                 device,
             )
         }
@@ -221,6 +243,8 @@ pub(crate) fn get_adapter_desc(
                 desc.DeviceId,
                 desc.Flags,
                 desc.DedicatedVideoMemory,
+                desc.AdapterLuid,
+                driver_version(adapter),
                 device,
             )
         }
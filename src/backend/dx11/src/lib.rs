@@ -617,7 +617,7 @@ impl hal::Instance<Backend> for Instance {
             let limits = get_limits(feature_level);
             let (features, downlevel, performance_caveats) =
                 get_features(device.clone(), feature_level);
-            let format_properties = get_format_properties(device.clone());
+            let format_properties = Arc::new(get_format_properties(device.clone()));
 
             let physical_device = PhysicalDevice {
                 adapter,
@@ -655,6 +655,9 @@ impl hal::Instance<Backend> for Instance {
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
     ) -> Result<Surface, hal::window::InitError> {
         match has_handle.raw_window_handle() {
+            raw_window_handle::RawWindowHandle::Windows(handle) if handle.hwnd.is_null() => {
+                Err(hal::window::InitError::InvalidWindowHandle)
+            }
             raw_window_handle::RawWindowHandle::Windows(handle) => {
                 Ok(self.create_surface_from_hwnd(handle.hwnd))
             }
@@ -684,7 +687,7 @@ pub struct PhysicalDevice {
     features: hal::Features,
     properties: hal::PhysicalDeviceProperties,
     memory_properties: adapter::MemoryProperties,
-    format_properties: [format::Properties; format::NUM_FORMATS],
+    format_properties: Arc<[format::Properties; format::NUM_FORMATS]>,
 }
 
 impl fmt::Debug for PhysicalDevice {
@@ -848,6 +851,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             requested_features,
             self.properties.downlevel,
             self.memory_properties.clone(),
+            Arc::clone(&self.format_properties),
             feature_level,
         );
 
@@ -861,6 +865,9 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 // TODO: multiple queues?
                 let queue = Queue {
                     context: device.context.clone(),
+                    device: device.raw.clone(),
+                    render_doc: Arc::clone(&device.render_doc),
+                    captures: Default::default(),
                 };
                 group.add_queue(queue);
                 group
@@ -1142,6 +1149,10 @@ impl window::PresentationSurface<Backend> for Surface {
         config: window::SwapchainConfig,
     ) -> Result<(), window::SwapchainError> {
         assert!(image::Usage::COLOR_ATTACHMENT.contains(config.image_usage));
+        // The color space can only be set with `IDXGISwapChain3`, which isn't used.
+        if config.color_space != window::ColorSpace::SrgbNonlinear {
+            return Err(window::SwapchainError::UnsupportedColorSpace);
+        }
 
         let swapchain = match self.presentation.take() {
             Some(present) => {
@@ -1302,6 +1313,9 @@ impl queue::QueueFamily for QueueFamily {
 #[derive(Clone)]
 pub struct Queue {
     context: ComPtr<d3d11::ID3D11DeviceContext>,
+    device: ComPtr<d3d11::ID3D11Device>,
+    render_doc: Arc<gfx_renderdoc::RenderDoc>,
+    captures: gfx_renderdoc::FrameCaptures,
 }
 
 impl fmt::Debug for Queue {
@@ -1320,7 +1334,8 @@ impl queue::Queue<Backend> for Queue {
         _wait_semaphores: Iw,
         _signal_semaphores: Is,
         fence: Option<&mut Fence>,
-    ) where
+    ) -> Result<(), hal::device::OomOrDeviceLost>
+    where
         Ic: Iterator<Item = &'a CommandBuffer>,
     {
         let _scope = debug_scope!(&self.context, "Submit(fence={:?})", fence);
@@ -1348,10 +1363,18 @@ impl queue::Queue<Backend> for Queue {
             }
         }
 
+        // The commands don't report errors, the removal of the device is queried instead.
+        let reason = self.device.GetDeviceRemovedReason();
+        if reason != winerror::S_OK {
+            error!("Device removed with reason 0x{:x}", reason as u32);
+            return Err(hal::device::DeviceLost.into());
+        }
+
         if let Some(fence) = fence {
             *fence.mutex.lock() = true;
             fence.condvar.notify_all();
         }
+        Ok(())
     }
 
     unsafe fn present(
@@ -1370,10 +1393,15 @@ impl queue::Queue<Backend> for Queue {
         };
         presentation.is_init = false;
         presentation.swapchain.Present(interval, flags);
+        self.captures.frame_presented(
+            &self.render_doc,
+            self.device.as_raw() as *mut _,
+            ptr::null_mut(),
+        );
         Ok(None)
     }
 
-    fn wait_idle(&mut self) -> Result<(), hal::device::OutOfMemory> {
+    fn wait_idle(&mut self) -> Result<(), hal::device::OomOrDeviceLost> {
         // unimplemented!()
         Ok(())
     }
@@ -1381,6 +1409,17 @@ impl queue::Queue<Backend> for Queue {
     fn timestamp_period(&self) -> f32 {
         1.0
     }
+
+    fn capture_next_frames(&mut self, frames: u32) {
+        unsafe {
+            self.captures.capture_next_frames(
+                &self.render_doc,
+                self.device.as_raw() as *mut _,
+                ptr::null_mut(),
+                frames,
+            )
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -2532,6 +2571,14 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         // unimplemented!()
     }
 
+    unsafe fn set_shading_rate(
+        &mut self,
+        _rate: pso::ShadingRate,
+        _combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &GraphicsPipeline) {
         self.cache.set_graphics_pipeline(pipeline.clone());
         self.cache.bind(&self.context);
@@ -2865,6 +2912,47 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        _descs: &[hal::acceleration_structure::BuildDesc<Backend>],
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        _src: &(),
+        _dst: &(),
+        _mode: hal::acceleration_structure::CopyMode,
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a DescriptorSet>,
+        J: Iterator<Item = command::DescriptorSetOffset>,
+    {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _tables: &pso::ShaderBindingTables<Backend>,
+        _count: WorkGroupCount,
+    ) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
     unsafe fn fill_buffer(&mut self, buffer: &Buffer, sub: buffer::SubRange, data: u32) {
         let mut device: *mut d3d11::ID3D11Device = mem::zeroed();
         self.context.GetDevice(&mut device as *mut _);
@@ -3151,6 +3239,25 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         panic!("DX11 doesn't support DRAW_INDIRECT_COUNT")
     }
 
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, _: u32, _: I)
+    where
+        I: Iterator<Item = (&'a Buffer, buffer::SubRange)>,
+    {
+        error!("DX11 doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        error!("DX11 doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        error!("DX11 doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, _: Range<InstanceCount>) {
+        error!("DX11 doesn't support DRAW_TRANSFORM_FEEDBACK");
+    }
+
     unsafe fn draw_mesh_tasks(&mut self, _: TaskCount, _: TaskCount) {
         panic!("DX11 doesn't support MESH_SHADERS")
     }
@@ -4334,6 +4441,7 @@ impl From<pso::DescriptorType> for DescriptorContent {
                 ty: Bdt::Storage { read_only: false },
                 ..
             } => DescriptorContent::UAV,
+            Dt::AccelerationStructure => unreachable!("Acceleration structures are never exposed"),
         }
     }
 }
@@ -4505,6 +4613,7 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = ComputePipeline;
     type GraphicsPipeline = GraphicsPipeline;
+    type RayTracingPipeline = ();
     type PipelineLayout = PipelineLayout;
     type PipelineCache = ();
     type DescriptorSetLayout = DescriptorSetLayout;
@@ -4515,6 +4624,7 @@ impl hal::Backend for Backend {
     type Semaphore = Semaphore;
     type Event = ();
     type QueryPool = QueryPool;
+    type AccelerationStructure = ();
 
     type Display = ();
     type DisplayMode = ();
@@ -1,5 +1,7 @@
 
 Texture2DArray BlitSource : register(t0);
+// Chroma plane of multi-planar sources, `BlitSource` being the luma plane.
+Texture2DArray BlitChroma : register(t1);
 SamplerState BlitSampler : register(s0);
 
 cbuffer Region : register(b0) {
@@ -27,3 +29,20 @@ VsOutput vs_blit_2d(uint id: SV_VertexID) {
 float4 ps_blit_2d(VsOutput input) : SV_TARGET {
     return BlitSource.SampleLevel(BlitSampler, input.uv.xyz, input.uv.w);
 }
+
+// Rows of the matrix converting `(Y, Cb, Cr, 1)` to RGB.
+cbuffer Conversion : register(b1) {
+    float4 ycbcr_to_rgb[3];
+};
+
+float4 ps_blit_2d_ycbcr(VsOutput input) : SV_TARGET {
+    float luma = BlitSource.SampleLevel(BlitSampler, input.uv.xyz, input.uv.w).r;
+    float2 chroma = BlitChroma.SampleLevel(BlitSampler, input.uv.xyz, input.uv.w).rg;
+    float4 ycbcr = float4(luma, chroma, 1.0);
+    return float4(
+        dot(ycbcr_to_rgb[0], ycbcr),
+        dot(ycbcr_to_rgb[1], ycbcr),
+        dot(ycbcr_to_rgb[2], ycbcr),
+        1.0
+    );
+}
@@ -0,0 +1,543 @@
+//! Enhanced barriers (`ID3D12GraphicsCommandList7::Barrier`).
+//!
+//! These definitions are not part of `winapi` yet, so the small subset
+//! we need is declared here by hand, following `d3d12.h` of the Agility SDK.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use hal::{image, pso};
+use winapi::{
+    ctypes::c_void,
+    shared::{guiddef::GUID, minwindef::BOOL, winerror},
+    um::{d3d12, unknwnbase::IUnknown},
+    Interface,
+};
+
+use std::{mem, ops::Range, ptr};
+
+use crate::{conv, ray_tracing, resource as r, shading_rate};
+
+pub const D3D12_FEATURE_D3D12_OPTIONS12: d3d12::D3D12_FEATURE = 41;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct D3D12_FEATURE_DATA_D3D12_OPTIONS12 {
+    pub MSPrimitivesPipelineStatisticIncludesCulledPrimitives: i32,
+    pub EnhancedBarriersSupported: BOOL,
+    pub RelaxedFormatCastingSupported: BOOL,
+}
+
+type D3D12_BARRIER_TYPE = u32;
+const D3D12_BARRIER_TYPE_GLOBAL: D3D12_BARRIER_TYPE = 0;
+const D3D12_BARRIER_TYPE_TEXTURE: D3D12_BARRIER_TYPE = 1;
+const D3D12_BARRIER_TYPE_BUFFER: D3D12_BARRIER_TYPE = 2;
+
+type D3D12_BARRIER_SYNC = u32;
+const D3D12_BARRIER_SYNC_NONE: D3D12_BARRIER_SYNC = 0;
+const D3D12_BARRIER_SYNC_ALL: D3D12_BARRIER_SYNC = 0x1;
+const D3D12_BARRIER_SYNC_INDEX_INPUT: D3D12_BARRIER_SYNC = 0x4;
+const D3D12_BARRIER_SYNC_VERTEX_SHADING: D3D12_BARRIER_SYNC = 0x8;
+const D3D12_BARRIER_SYNC_PIXEL_SHADING: D3D12_BARRIER_SYNC = 0x10;
+const D3D12_BARRIER_SYNC_DEPTH_STENCIL: D3D12_BARRIER_SYNC = 0x20;
+const D3D12_BARRIER_SYNC_RENDER_TARGET: D3D12_BARRIER_SYNC = 0x40;
+const D3D12_BARRIER_SYNC_COMPUTE_SHADING: D3D12_BARRIER_SYNC = 0x80;
+const D3D12_BARRIER_SYNC_COPY: D3D12_BARRIER_SYNC = 0x200;
+const D3D12_BARRIER_SYNC_RESOLVE: D3D12_BARRIER_SYNC = 0x400;
+const D3D12_BARRIER_SYNC_EXECUTE_INDIRECT: D3D12_BARRIER_SYNC = 0x800;
+const D3D12_BARRIER_SYNC_ALL_SHADING: D3D12_BARRIER_SYNC = 0x1000;
+const D3D12_BARRIER_SYNC_RAYTRACING: D3D12_BARRIER_SYNC = 0x10_0000;
+const D3D12_BARRIER_SYNC_BUILD_RAYTRACING_ACCELERATION_STRUCTURE: D3D12_BARRIER_SYNC = 0x80_0000;
+const D3D12_BARRIER_SYNC_COPY_RAYTRACING_ACCELERATION_STRUCTURE: D3D12_BARRIER_SYNC = 0x100_0000;
+
+type D3D12_BARRIER_ACCESS = u32;
+const D3D12_BARRIER_ACCESS_COMMON: D3D12_BARRIER_ACCESS = 0;
+const D3D12_BARRIER_ACCESS_VERTEX_BUFFER: D3D12_BARRIER_ACCESS = 0x1;
+const D3D12_BARRIER_ACCESS_CONSTANT_BUFFER: D3D12_BARRIER_ACCESS = 0x2;
+const D3D12_BARRIER_ACCESS_INDEX_BUFFER: D3D12_BARRIER_ACCESS = 0x4;
+const D3D12_BARRIER_ACCESS_RENDER_TARGET: D3D12_BARRIER_ACCESS = 0x8;
+const D3D12_BARRIER_ACCESS_UNORDERED_ACCESS: D3D12_BARRIER_ACCESS = 0x10;
+const D3D12_BARRIER_ACCESS_DEPTH_STENCIL_WRITE: D3D12_BARRIER_ACCESS = 0x20;
+const D3D12_BARRIER_ACCESS_DEPTH_STENCIL_READ: D3D12_BARRIER_ACCESS = 0x40;
+const D3D12_BARRIER_ACCESS_SHADER_RESOURCE: D3D12_BARRIER_ACCESS = 0x80;
+const D3D12_BARRIER_ACCESS_STREAM_OUTPUT: D3D12_BARRIER_ACCESS = 0x100;
+const D3D12_BARRIER_ACCESS_INDIRECT_ARGUMENT: D3D12_BARRIER_ACCESS = 0x200;
+const D3D12_BARRIER_ACCESS_COPY_DEST: D3D12_BARRIER_ACCESS = 0x400;
+const D3D12_BARRIER_ACCESS_COPY_SOURCE: D3D12_BARRIER_ACCESS = 0x800;
+const D3D12_BARRIER_ACCESS_RESOLVE_DEST: D3D12_BARRIER_ACCESS = 0x1000;
+const D3D12_BARRIER_ACCESS_RESOLVE_SOURCE: D3D12_BARRIER_ACCESS = 0x2000;
+const D3D12_BARRIER_ACCESS_RAYTRACING_ACCELERATION_STRUCTURE_READ: D3D12_BARRIER_ACCESS = 0x4000;
+const D3D12_BARRIER_ACCESS_RAYTRACING_ACCELERATION_STRUCTURE_WRITE: D3D12_BARRIER_ACCESS = 0x8000;
+const D3D12_BARRIER_ACCESS_SHADING_RATE_SOURCE: D3D12_BARRIER_ACCESS = 0x10000;
+const D3D12_BARRIER_ACCESS_NO_ACCESS: D3D12_BARRIER_ACCESS = 0x8000_0000;
+
+type D3D12_BARRIER_LAYOUT = u32;
+const D3D12_BARRIER_LAYOUT_UNDEFINED: D3D12_BARRIER_LAYOUT = 0xFFFF_FFFF;
+const D3D12_BARRIER_LAYOUT_COMMON: D3D12_BARRIER_LAYOUT = 0;
+const D3D12_BARRIER_LAYOUT_GENERIC_READ: D3D12_BARRIER_LAYOUT = 1;
+const D3D12_BARRIER_LAYOUT_RENDER_TARGET: D3D12_BARRIER_LAYOUT = 2;
+const D3D12_BARRIER_LAYOUT_UNORDERED_ACCESS: D3D12_BARRIER_LAYOUT = 3;
+const D3D12_BARRIER_LAYOUT_DEPTH_STENCIL_WRITE: D3D12_BARRIER_LAYOUT = 4;
+const D3D12_BARRIER_LAYOUT_DEPTH_STENCIL_READ: D3D12_BARRIER_LAYOUT = 5;
+const D3D12_BARRIER_LAYOUT_SHADER_RESOURCE: D3D12_BARRIER_LAYOUT = 6;
+const D3D12_BARRIER_LAYOUT_COPY_SOURCE: D3D12_BARRIER_LAYOUT = 7;
+const D3D12_BARRIER_LAYOUT_COPY_DEST: D3D12_BARRIER_LAYOUT = 8;
+const D3D12_BARRIER_LAYOUT_RESOLVE_SOURCE: D3D12_BARRIER_LAYOUT = 9;
+const D3D12_BARRIER_LAYOUT_RESOLVE_DEST: D3D12_BARRIER_LAYOUT = 10;
+const D3D12_BARRIER_LAYOUT_SHADING_RATE_SOURCE: D3D12_BARRIER_LAYOUT = 11;
+
+const D3D12_TEXTURE_BARRIER_FLAG_NONE: u32 = 0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct D3D12_BARRIER_SUBRESOURCE_RANGE {
+    IndexOrFirstMipLevel: u32,
+    NumMipLevels: u32,
+    FirstArraySlice: u32,
+    NumArraySlices: u32,
+    FirstPlane: u32,
+    NumPlanes: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct D3D12_GLOBAL_BARRIER {
+    SyncBefore: D3D12_BARRIER_SYNC,
+    SyncAfter: D3D12_BARRIER_SYNC,
+    AccessBefore: D3D12_BARRIER_ACCESS,
+    AccessAfter: D3D12_BARRIER_ACCESS,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct D3D12_TEXTURE_BARRIER {
+    SyncBefore: D3D12_BARRIER_SYNC,
+    SyncAfter: D3D12_BARRIER_SYNC,
+    AccessBefore: D3D12_BARRIER_ACCESS,
+    AccessAfter: D3D12_BARRIER_ACCESS,
+    LayoutBefore: D3D12_BARRIER_LAYOUT,
+    LayoutAfter: D3D12_BARRIER_LAYOUT,
+    pResource: *mut d3d12::ID3D12Resource,
+    Subresources: D3D12_BARRIER_SUBRESOURCE_RANGE,
+    Flags: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct D3D12_BUFFER_BARRIER {
+    SyncBefore: D3D12_BARRIER_SYNC,
+    SyncAfter: D3D12_BARRIER_SYNC,
+    AccessBefore: D3D12_BARRIER_ACCESS,
+    AccessAfter: D3D12_BARRIER_ACCESS,
+    pResource: *mut d3d12::ID3D12Resource,
+    Offset: u64,
+    Size: u64,
+}
+
+#[repr(C)]
+struct D3D12_BARRIER_GROUP {
+    Type: D3D12_BARRIER_TYPE,
+    NumBarriers: u32,
+    // Union of pointers to the barrier arrays, selected by `Type`.
+    pBarriers: *const c_void,
+}
+
+/// Minimal view of `ID3D12GraphicsCommandList7`: we only ever call `Barrier`,
+/// so all the inherited methods are left opaque.
+#[repr(C)]
+struct ID3D12GraphicsCommandList7Vtbl {
+    // IUnknown (3), ID3D12Object (4), ID3D12DeviceChild (1), ID3D12CommandList (1),
+    // ID3D12GraphicsCommandList (51), ID3D12GraphicsCommandList1..6 (6 + 1 + 1 + 9 + 2 + 1).
+    parent: [*const c_void; 80],
+    Barrier: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList7,
+        NumBarrierGroups: u32,
+        pBarrierGroups: *const D3D12_BARRIER_GROUP,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12GraphicsCommandList7 {
+    lpVtbl: *const ID3D12GraphicsCommandList7Vtbl,
+}
+
+impl Interface for ID3D12GraphicsCommandList7 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0xdd171223,
+            Data2: 0x8b61,
+            Data3: 0x4769,
+            Data4: [0x90, 0xe3, 0x16, 0x0c, 0xcd, 0xe4, 0xe2, 0xc1],
+        }
+    }
+}
+
+/// Check if the device supports enhanced barriers.
+pub fn is_supported(device: native::Device) -> bool {
+    let mut options = D3D12_FEATURE_DATA_D3D12_OPTIONS12::default();
+    let hr = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_D3D12_OPTIONS12,
+            &mut options as *mut _ as *mut _,
+            mem::size_of::<D3D12_FEATURE_DATA_D3D12_OPTIONS12>() as _,
+        )
+    };
+    // Older runtimes don't know about the feature at all and fail the query.
+    winerror::SUCCEEDED(hr) && options.EnhancedBarriersSupported != 0
+}
+
+/// `ID3D12GraphicsCommandList7` interface of a command list.
+pub struct CommandList(*mut ID3D12GraphicsCommandList7);
+
+impl CommandList {
+    /// Query the interface of `list`, returning `None` if the runtime
+    /// doesn't expose it for this command list.
+    pub unsafe fn query(list: native::GraphicsCommandList) -> Option<Self> {
+        let mut list7: *mut ID3D12GraphicsCommandList7 = ptr::null_mut();
+        let hr = (*(list.as_mut_ptr() as *mut IUnknown)).QueryInterface(
+            &ID3D12GraphicsCommandList7::uuidof(),
+            &mut list7 as *mut *mut _ as *mut *mut c_void,
+        );
+        if winerror::SUCCEEDED(hr) {
+            Some(CommandList(list7))
+        } else {
+            warn!(
+                "Enhanced barriers were reported, but the command list doesn't support them: 0x{:X}",
+                hr
+            );
+            None
+        }
+    }
+}
+
+impl Drop for CommandList {
+    fn drop(&mut self) {
+        unsafe {
+            (*(self.0 as *mut IUnknown)).Release();
+        }
+    }
+}
+
+fn map_stages(stages: pso::PipelineStage) -> D3D12_BARRIER_SYNC {
+    use hal::pso::PipelineStage as Ps;
+
+    if stages.intersects(Ps::TOP_OF_PIPE | Ps::BOTTOM_OF_PIPE) {
+        return D3D12_BARRIER_SYNC_ALL;
+    }
+
+    let mut sync = D3D12_BARRIER_SYNC_NONE;
+    if stages.contains(Ps::DRAW_INDIRECT) {
+        sync |= D3D12_BARRIER_SYNC_EXECUTE_INDIRECT;
+    }
+    if stages.contains(Ps::VERTEX_INPUT) {
+        sync |= D3D12_BARRIER_SYNC_INDEX_INPUT | D3D12_BARRIER_SYNC_VERTEX_SHADING;
+    }
+    if stages
+        .intersects(Ps::VERTEX_SHADER | Ps::HULL_SHADER | Ps::DOMAIN_SHADER | Ps::GEOMETRY_SHADER)
+    {
+        sync |= D3D12_BARRIER_SYNC_VERTEX_SHADING;
+    }
+    if stages.intersects(Ps::TASK_SHADER | Ps::MESH_SHADER) {
+        sync |= D3D12_BARRIER_SYNC_ALL_SHADING;
+    }
+    // The shading rate image is read along with the pixel shading.
+    if stages.intersects(Ps::FRAGMENT_SHADER | Ps::SHADING_RATE_ATTACHMENT) {
+        sync |= D3D12_BARRIER_SYNC_PIXEL_SHADING;
+    }
+    if stages.intersects(Ps::EARLY_FRAGMENT_TESTS | Ps::LATE_FRAGMENT_TESTS) {
+        sync |= D3D12_BARRIER_SYNC_DEPTH_STENCIL;
+    }
+    if stages.contains(Ps::COLOR_ATTACHMENT_OUTPUT) {
+        sync |= D3D12_BARRIER_SYNC_RENDER_TARGET;
+    }
+    if stages.contains(Ps::COMPUTE_SHADER) {
+        sync |= D3D12_BARRIER_SYNC_COMPUTE_SHADING;
+    }
+    if stages.contains(Ps::TRANSFER) {
+        sync |= D3D12_BARRIER_SYNC_COPY | D3D12_BARRIER_SYNC_RESOLVE;
+    }
+    if stages.contains(Ps::RAY_TRACING_SHADER) {
+        sync |= D3D12_BARRIER_SYNC_RAYTRACING;
+    }
+    if stages.contains(Ps::ACCELERATION_STRUCTURE_BUILD) {
+        sync |= D3D12_BARRIER_SYNC_BUILD_RAYTRACING_ACCELERATION_STRUCTURE
+            | D3D12_BARRIER_SYNC_COPY_RAYTRACING_ACCELERATION_STRUCTURE;
+    }
+    sync
+}
+
+/// Translate legacy resource states into access bits.
+///
+/// We derive everything from the legacy states so that resources keep a consistent
+/// view for the parts of the backend that still issue `ResourceBarrier` transitions.
+fn map_access(state: d3d12::D3D12_RESOURCE_STATES) -> D3D12_BARRIER_ACCESS {
+    const MAPPING: &[(d3d12::D3D12_RESOURCE_STATES, D3D12_BARRIER_ACCESS)] = &[
+        (
+            d3d12::D3D12_RESOURCE_STATE_VERTEX_AND_CONSTANT_BUFFER,
+            D3D12_BARRIER_ACCESS_VERTEX_BUFFER | D3D12_BARRIER_ACCESS_CONSTANT_BUFFER,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_INDEX_BUFFER,
+            D3D12_BARRIER_ACCESS_INDEX_BUFFER,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_RENDER_TARGET,
+            D3D12_BARRIER_ACCESS_RENDER_TARGET,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            D3D12_BARRIER_ACCESS_UNORDERED_ACCESS,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_DEPTH_WRITE,
+            D3D12_BARRIER_ACCESS_DEPTH_STENCIL_WRITE,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_DEPTH_READ,
+            D3D12_BARRIER_ACCESS_DEPTH_STENCIL_READ,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE
+                | d3d12::D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE,
+            D3D12_BARRIER_ACCESS_SHADER_RESOURCE,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_STREAM_OUT,
+            D3D12_BARRIER_ACCESS_STREAM_OUTPUT,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_INDIRECT_ARGUMENT,
+            D3D12_BARRIER_ACCESS_INDIRECT_ARGUMENT,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_COPY_DEST,
+            D3D12_BARRIER_ACCESS_COPY_DEST,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_COPY_SOURCE,
+            D3D12_BARRIER_ACCESS_COPY_SOURCE,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_RESOLVE_DEST,
+            D3D12_BARRIER_ACCESS_RESOLVE_DEST,
+        ),
+        (
+            d3d12::D3D12_RESOURCE_STATE_RESOLVE_SOURCE,
+            D3D12_BARRIER_ACCESS_RESOLVE_SOURCE,
+        ),
+        (
+            ray_tracing::D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE,
+            D3D12_BARRIER_ACCESS_RAYTRACING_ACCELERATION_STRUCTURE_READ
+                | D3D12_BARRIER_ACCESS_RAYTRACING_ACCELERATION_STRUCTURE_WRITE,
+        ),
+        (
+            shading_rate::D3D12_RESOURCE_STATE_SHADING_RATE_SOURCE,
+            D3D12_BARRIER_ACCESS_SHADING_RATE_SOURCE,
+        ),
+    ];
+
+    MAPPING
+        .iter()
+        .filter(|&&(legacy, _)| state & legacy != 0)
+        .fold(D3D12_BARRIER_ACCESS_COMMON, |access, &(_, bits)| {
+            access | bits
+        })
+}
+
+/// Pick the texture layout matching a legacy resource state.
+fn map_layout(state: d3d12::D3D12_RESOURCE_STATES) -> D3D12_BARRIER_LAYOUT {
+    const SHADER_RESOURCE: d3d12::D3D12_RESOURCE_STATES =
+        d3d12::D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE
+            | d3d12::D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE;
+
+    match state {
+        d3d12::D3D12_RESOURCE_STATE_COMMON => D3D12_BARRIER_LAYOUT_COMMON,
+        d3d12::D3D12_RESOURCE_STATE_RENDER_TARGET => D3D12_BARRIER_LAYOUT_RENDER_TARGET,
+        d3d12::D3D12_RESOURCE_STATE_UNORDERED_ACCESS => D3D12_BARRIER_LAYOUT_UNORDERED_ACCESS,
+        d3d12::D3D12_RESOURCE_STATE_DEPTH_WRITE => D3D12_BARRIER_LAYOUT_DEPTH_STENCIL_WRITE,
+        d3d12::D3D12_RESOURCE_STATE_COPY_DEST => D3D12_BARRIER_LAYOUT_COPY_DEST,
+        d3d12::D3D12_RESOURCE_STATE_COPY_SOURCE => D3D12_BARRIER_LAYOUT_COPY_SOURCE,
+        d3d12::D3D12_RESOURCE_STATE_RESOLVE_DEST => D3D12_BARRIER_LAYOUT_RESOLVE_DEST,
+        d3d12::D3D12_RESOURCE_STATE_RESOLVE_SOURCE => D3D12_BARRIER_LAYOUT_RESOLVE_SOURCE,
+        shading_rate::D3D12_RESOURCE_STATE_SHADING_RATE_SOURCE => {
+            D3D12_BARRIER_LAYOUT_SHADING_RATE_SOURCE
+        }
+        _ if state & d3d12::D3D12_RESOURCE_STATE_DEPTH_READ != 0 => {
+            D3D12_BARRIER_LAYOUT_DEPTH_STENCIL_READ
+        }
+        _ if state & !SHADER_RESOURCE == 0 => D3D12_BARRIER_LAYOUT_SHADER_RESOURCE,
+        // Any other combination of read-only states.
+        _ => D3D12_BARRIER_LAYOUT_GENERIC_READ,
+    }
+}
+
+/// Reusable storage for the barrier groups of a single `pipeline_barrier` call.
+#[derive(Debug, Default)]
+pub struct BarrierGroups {
+    global: Vec<D3D12_GLOBAL_BARRIER>,
+    buffers: Vec<D3D12_BUFFER_BARRIER>,
+    textures: Vec<D3D12_TEXTURE_BARRIER>,
+}
+
+impl BarrierGroups {
+    pub fn clear(&mut self) {
+        self.global.clear();
+        self.buffers.clear();
+        self.textures.clear();
+    }
+
+    pub fn push_global(
+        &mut self,
+        stages: &Range<pso::PipelineStage>,
+        states: Range<d3d12::D3D12_RESOURCE_STATES>,
+    ) {
+        let (sync_before, access_before) = sync_access(stages.start, states.start);
+        let (sync_after, access_after) = sync_access(stages.end, states.end);
+        self.global.push(D3D12_GLOBAL_BARRIER {
+            SyncBefore: sync_before,
+            SyncAfter: sync_after,
+            AccessBefore: access_before,
+            AccessAfter: access_after,
+        });
+    }
+
+    /// The enhanced barriers alias resources with a global barrier, making the
+    /// writes visible, the next resource being initialized by its own barrier.
+    pub fn push_aliasing(&mut self, stages: &Range<pso::PipelineStage>) {
+        const WRITES: d3d12::D3D12_RESOURCE_STATES = d3d12::D3D12_RESOURCE_STATE_RENDER_TARGET
+            | d3d12::D3D12_RESOURCE_STATE_UNORDERED_ACCESS
+            | d3d12::D3D12_RESOURCE_STATE_DEPTH_WRITE
+            | d3d12::D3D12_RESOURCE_STATE_COPY_DEST
+            | d3d12::D3D12_RESOURCE_STATE_RESOLVE_DEST;
+        self.push_global(
+            stages,
+            WRITES..WRITES | d3d12::D3D12_RESOURCE_STATE_GENERIC_READ,
+        );
+    }
+
+    pub fn push_buffer(
+        &mut self,
+        stages: &Range<pso::PipelineStage>,
+        target: &r::BufferBound,
+        states: Range<d3d12::D3D12_RESOURCE_STATES>,
+    ) {
+        let (sync_before, access_before) = sync_access(stages.start, states.start);
+        let (sync_after, access_after) = sync_access(stages.end, states.end);
+        self.buffers.push(D3D12_BUFFER_BARRIER {
+            SyncBefore: sync_before,
+            SyncAfter: sync_after,
+            AccessBefore: access_before,
+            AccessAfter: access_after,
+            pResource: target.resource.as_mut_ptr(),
+            Offset: 0,
+            Size: !0,
+        });
+    }
+
+    /// Transition a texture, discarding its contents if `discard` is set,
+    /// i.e. when it comes from `Layout::Undefined`.
+    pub fn push_texture(
+        &mut self,
+        stages: &Range<pso::PipelineStage>,
+        target: &r::ImageBound,
+        states: Range<d3d12::D3D12_RESOURCE_STATES>,
+        discard: bool,
+        range: &image::SubresourceRange,
+    ) {
+        let (sync_before, access_before) = sync_access(stages.start, states.start);
+        let (sync_after, access_after) = sync_access(stages.end, states.end);
+        // Undefined contents can't be accessed before the barrier.
+        let (layout_before, access_before) = if discard {
+            (
+                D3D12_BARRIER_LAYOUT_UNDEFINED,
+                D3D12_BARRIER_ACCESS_NO_ACCESS,
+            )
+        } else {
+            (map_layout(states.start), access_before)
+        };
+
+        let full_range = image::SubresourceRange {
+            aspects: range.aspects,
+            ..Default::default()
+        };
+        let subresources = if *range == full_range {
+            // All subresources of the texture.
+            D3D12_BARRIER_SUBRESOURCE_RANGE {
+                IndexOrFirstMipLevel: !0,
+                NumMipLevels: 0,
+                FirstArraySlice: 0,
+                NumArraySlices: 0,
+                FirstPlane: 0,
+                NumPlanes: 0,
+            }
+        } else {
+            let planes = conv::map_planes(target.surface_type, range.aspects);
+            D3D12_BARRIER_SUBRESOURCE_RANGE {
+                IndexOrFirstMipLevel: target.relative_level(range.level_start as _),
+                NumMipLevels: range.resolve_level_count(target.mip_levels) as _,
+                FirstArraySlice: range.layer_start as _,
+                NumArraySlices: range.resolve_layer_count(target.kind.num_layers()) as _,
+                FirstPlane: planes.start,
+                NumPlanes: planes.end - planes.start,
+            }
+        };
+
+        self.textures.push(D3D12_TEXTURE_BARRIER {
+            SyncBefore: sync_before,
+            SyncAfter: sync_after,
+            AccessBefore: access_before,
+            AccessAfter: access_after,
+            LayoutBefore: layout_before,
+            LayoutAfter: map_layout(states.end),
+            pResource: target.resource.as_mut_ptr(),
+            Subresources: subresources,
+            Flags: D3D12_TEXTURE_BARRIER_FLAG_NONE,
+        });
+    }
+
+    /// Record all the pending barriers into the command list.
+    pub unsafe fn flush(&self, list: &CommandList) {
+        let groups = [
+            (
+                D3D12_BARRIER_TYPE_GLOBAL,
+                self.global.len(),
+                self.global.as_ptr() as *const c_void,
+            ),
+            (
+                D3D12_BARRIER_TYPE_BUFFER,
+                self.buffers.len(),
+                self.buffers.as_ptr() as *const c_void,
+            ),
+            (
+                D3D12_BARRIER_TYPE_TEXTURE,
+                self.textures.len(),
+                self.textures.as_ptr() as *const c_void,
+            ),
+        ]
+        .iter()
+        .filter(|&&(_, count, _)| count != 0)
+        .map(|&(ty, count, barriers)| D3D12_BARRIER_GROUP {
+            Type: ty,
+            NumBarriers: count as u32,
+            pBarriers: barriers,
+        })
+        .collect::<arrayvec::ArrayVec<[_; 3]>>();
+
+        if groups.is_empty() {
+            return;
+        }
+
+        ((*(*list.0).lpVtbl).Barrier)(list.0, groups.len() as u32, groups.as_ptr());
+    }
+}
+
+/// Resolve the synchronization scope and access of one side of a barrier.
+fn sync_access(
+    stages: pso::PipelineStage,
+    state: d3d12::D3D12_RESOURCE_STATES,
+) -> (D3D12_BARRIER_SYNC, D3D12_BARRIER_ACCESS) {
+    match map_stages(stages) {
+        // No synchronization implies no access on this side of the barrier.
+        D3D12_BARRIER_SYNC_NONE => (D3D12_BARRIER_SYNC_NONE, D3D12_BARRIER_ACCESS_NO_ACCESS),
+        sync => (sync, map_access(state)),
+    }
+}
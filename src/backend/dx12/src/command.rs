@@ -1,7 +1,8 @@
 use auxil::FastHashMap;
 use hal::{
-    buffer, command as com, format, format::Aspects, image, memory, pass, pso, query, DrawCount,
-    IndexCount, IndexType, InstanceCount, TaskCount, VertexCount, VertexOffset, WorkGroupCount,
+    acceleration_structure as accel, buffer, command as com, format, format::Aspects, image,
+    memory, pass, pso, query, queue::QueueFamilyId, DrawCount, IndexCount, IndexType,
+    InstanceCount, TaskCount, VertexCount, VertexOffset, WorkGroupCount,
 };
 
 use arrayvec::ArrayVec;
@@ -10,18 +11,26 @@ use winapi::{
     ctypes,
     shared::{dxgiformat, minwindef, winerror},
     um::{d3d12, d3dcommon},
-    Interface,
 };
 
 use std::{cmp, fmt, iter, mem, ops::Range, ptr, sync::Arc};
 
 use crate::{
-    conv, descriptors_cpu, device, internal,
+    barrier, conv, depth_bias, descriptors_cpu, device, heap, internal, mesh,
     pool::{CommandAllocatorIndex, PoolShared},
-    resource as r, validate_line_width, Backend, Device, Shared, MAX_DESCRIPTOR_SETS,
-    MAX_VERTEX_BUFFERS,
+    ray_tracing, resource as r, shading_rate, validate_line_width, Backend, Device, Shared,
+    MAX_DESCRIPTOR_SETS, MAX_VERTEX_BUFFERS,
 };
 
+const ALL_SHADER_STAGES: pso::PipelineStage = pso::PipelineStage::from_bits_truncate(
+    pso::PipelineStage::VERTEX_SHADER.bits()
+        | pso::PipelineStage::FRAGMENT_SHADER.bits()
+        | pso::PipelineStage::COMPUTE_SHADER.bits()
+        | pso::PipelineStage::GEOMETRY_SHADER.bits()
+        | pso::PipelineStage::HULL_SHADER.bits()
+        | pso::PipelineStage::DOMAIN_SHADER.bits(),
+);
+
 // Fixed size of the root signature.
 // Limited by D3D12.
 const ROOT_SIGNATURE_SIZE: usize = 64;
@@ -32,6 +41,15 @@ const NULL_VERTEX_BUFFER_VIEW: d3d12::D3D12_VERTEX_BUFFER_VIEW = d3d12::D3D12_VE
     StrideInBytes: 0,
 };
 
+const NULL_STREAM_OUTPUT_BUFFER_VIEW: d3d12::D3D12_STREAM_OUTPUT_BUFFER_VIEW =
+    d3d12::D3D12_STREAM_OUTPUT_BUFFER_VIEW {
+        BufferLocation: 0,
+        SizeInBytes: 0,
+        BufferFilledSizeLocation: 0,
+    };
+
+const SO_BUFFER_SLOT_COUNT: usize = d3d12::D3D12_SO_BUFFER_SLOT_COUNT as usize;
+
 fn get_rect(rect: &pso::Rect) -> d3d12::D3D12_RECT {
     d3d12::D3D12_RECT {
         left: rect.x as i32,
@@ -49,6 +67,13 @@ fn up_align(x: u32, alignment: u32) -> u32 {
     (x + alignment - 1) & !(alignment - 1)
 }
 
+fn aliased_resource(resource: memory::Aliased<Backend>) -> *mut d3d12::ID3D12Resource {
+    match resource {
+        memory::Aliased::Buffer(buffer) => buffer.expect_bound().resource.as_mut_ptr(),
+        memory::Aliased::Image(image) => image.expect_bound().resource.as_mut_ptr(),
+    }
+}
+
 #[derive(Clone, Debug)]
 struct AttachmentInfo {
     subpass_id: Option<pass::SubpassId>,
@@ -189,11 +214,11 @@ impl UserData {
     }
 }
 
-#[derive(Debug, Default)]
-struct PipelineCache {
+#[derive(Debug)]
+struct PipelineCache<P = native::PipelineState> {
     // Bound pipeline and layout info.
     // Changed on bind pipeline calls.
-    pipeline: Option<(native::PipelineState, Arc<r::PipelineShared>)>,
+    pipeline: Option<(P, Arc<r::PipelineShared>)>,
 
     // Virtualized root signature user data of the shaders
     user_data: UserData,
@@ -205,7 +230,19 @@ struct PipelineCache {
     sampler_start: u64,
 }
 
-impl PipelineCache {
+impl<P> Default for PipelineCache<P> {
+    fn default() -> Self {
+        PipelineCache {
+            pipeline: None,
+            user_data: UserData::default(),
+            temp_constants: Vec::new(),
+            srv_cbv_uav_start: 0,
+            sampler_start: 0,
+        }
+    }
+}
+
+impl<P> PipelineCache<P> {
     fn bind_descriptor_sets<'a, J>(
         &mut self,
         layout: &r::PipelineLayout,
@@ -369,6 +406,8 @@ impl PipelineCache {
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum BindPoint {
     Compute,
+    /// Ray tracing pipelines share the compute root signature.
+    RayTracing,
     Graphics {
         /// Internal pipelines used for blitting, copying, etc.
         internal: bool,
@@ -379,6 +418,7 @@ enum BindPoint {
 struct Copy {
     footprint_offset: u64,
     footprint: image::Extent,
+    format: dxgiformat::DXGI_FORMAT,
     row_pitch: u32,
     img_subresource: u32,
     img_offset: image::Offset,
@@ -417,8 +457,12 @@ pub struct CommandBuffer {
     /// Primitive topology of the currently bound graphics pipeline.
     /// Caching required for internal graphics pipelines.
     primitive_topology: d3d12::D3D12_PRIMITIVE_TOPOLOGY,
+    /// States that aren't baked into the currently bound graphics pipeline.
+    dynamic_states: hal::DynamicStates,
     /// Cache current compute root signature and pipeline.
     comp_pipeline: PipelineCache,
+    /// Cache current ray tracing root signature and state object.
+    rt_pipeline: PipelineCache<ray_tracing::StateObject>,
     /// D3D12 only has one slot for both bindpoints. Need to rebind everything if we want to switch
     /// between different bind points (ie. calling draw or dispatch).
     active_bindpoint: BindPoint,
@@ -441,6 +485,9 @@ pub struct CommandBuffer {
 
     vertex_buffer_views: [d3d12::D3D12_VERTEX_BUFFER_VIEW; MAX_VERTEX_BUFFERS],
 
+    /// Stream output targets, set when transform feedback begins.
+    so_buffer_views: [d3d12::D3D12_STREAM_OUTPUT_BUFFER_VIEW; SO_BUFFER_SLOT_COUNT],
+
     /// Re-using allocation for the image-buffer copies.
     copies: Vec<Copy>,
 
@@ -461,12 +508,19 @@ pub struct CommandBuffer {
     temporary_gpu_heaps: Vec<native::DescriptorHeap>,
     /// Resources that need to be alive till the end of the GPU execution.
     retained_resources: Vec<native::Resource>,
+    /// Filled sizes of the stream output buffers, created on first use.
+    ///
+    /// Each command buffer has its own, as lists executing concurrently
+    /// would otherwise write to the same counters.
+    so_counters: Option<internal::StreamOutputCounters>,
 
     /// Temporary wide string for the marker.
     temp_marker: Vec<u16>,
 
     /// Temporary transition barriers.
     barriers: Vec<d3d12::D3D12_RESOURCE_BARRIER>,
+    /// Temporary enhanced barriers, used instead of `barriers` when supported.
+    barrier_groups: barrier::BarrierGroups,
     /// Name of the underlying raw `GraphicsCommandList` object.
     pub(crate) raw_name: Vec<u16>,
 }
@@ -501,21 +555,26 @@ impl CommandBuffer {
             cur_subpass: !0,
             gr_pipeline: PipelineCache::default(),
             primitive_topology: d3dcommon::D3D_PRIMITIVE_TOPOLOGY_UNDEFINED,
+            dynamic_states: hal::DynamicStates::all(),
             comp_pipeline: PipelineCache::default(),
+            rt_pipeline: PipelineCache::default(),
             active_bindpoint: BindPoint::Graphics { internal: false },
             active_descriptor_heaps: [native::DescriptorHeap::null(); 2],
             occlusion_query: None,
             pipeline_stats_query: None,
             vertex_bindings_remap: [None; MAX_VERTEX_BUFFERS],
             vertex_buffer_views: [NULL_VERTEX_BUFFER_VIEW; MAX_VERTEX_BUFFERS],
+            so_buffer_views: [NULL_STREAM_OUTPUT_BUFFER_VIEW; SO_BUFFER_SLOT_COUNT],
             copies: Vec::new(),
             viewport_cache: ArrayVec::new(),
             scissor_cache: ArrayVec::new(),
             rtv_pools: Vec::new(),
             temporary_gpu_heaps: Vec::new(),
             retained_resources: Vec::new(),
+            so_counters: None,
             temp_marker: Vec::new(),
             barriers: Vec::new(),
+            barrier_groups: barrier::BarrierGroups::default(),
             raw_name: Vec::new(),
         }
     }
@@ -540,6 +599,9 @@ impl CommandBuffer {
         for resource in &self.retained_resources {
             resource.destroy();
         }
+        if let Some(ref counters) = self.so_counters {
+            counters.destroy();
+        }
         self.allocator_index.map(|index| (index, list))
     }
 
@@ -555,10 +617,23 @@ impl CommandBuffer {
     //
     // This only invalidates the slot and the user data!
     fn set_internal_graphics_pipeline(&mut self) {
+        if self.active_bindpoint == BindPoint::RayTracing {
+            self.restore_compute_root_signature();
+        }
         self.active_bindpoint = BindPoint::Graphics { internal: true };
         self.gr_pipeline.user_data.dirty_all();
     }
 
+    // Rebind the compute root signature when leaving the ray tracing bind point for
+    // the graphics one, so that switching to the compute bind point afterwards only
+    // needs to set the pipeline.
+    fn restore_compute_root_signature(&mut self) {
+        if let Some((_, ref shared)) = self.comp_pipeline.pipeline {
+            self.raw.set_compute_root_signature(shared.signature);
+            self.comp_pipeline.user_data.dirty_all();
+        }
+    }
+
     fn bind_descriptor_heaps(&mut self) {
         self.raw.set_descriptor_heaps(&self.active_descriptor_heaps);
     }
@@ -644,6 +719,27 @@ impl CommandBuffer {
             );
         }
 
+        // enable all the view instances of the pipelines of this subpass
+        if subpass.view_mask != 0 {
+            let (cmd_list1, hr) = unsafe { self.raw.cast::<d3d12::ID3D12GraphicsCommandList1>() };
+            if winerror::SUCCEEDED(hr) {
+                unsafe {
+                    cmd_list1.SetViewInstanceMask((1 << subpass.view_mask.count_ones()) - 1);
+                    cmd_list1.destroy();
+                }
+            } else {
+                warn!("View instancing is not supported");
+            }
+        }
+
+        // bind the shading rate image of this subpass, or unbind the one of the previous
+        if self.shared.shading_rate_tier == shading_rate::Tier::Image {
+            let image = subpass
+                .shading_rate_attachment
+                .map(|(id, _)| state.attachments[id].view.resource);
+            unsafe { shading_rate::set_image(self.raw, image) };
+        }
+
         // performs clears for all the attachments first used in this subpass
         for at in state.attachments.iter() {
             if at.subpass_id != Some(self.cur_subpass) {
@@ -762,6 +858,16 @@ impl CommandBuffer {
                     .expect("No graphics pipeline bound");
                 self.raw.set_pipeline_state(pipeline);
             }
+            BindPoint::RayTracing => {
+                // Switch to graphics bind point
+                let &(pipeline, _) = self
+                    .gr_pipeline
+                    .pipeline
+                    .as_ref()
+                    .expect("No graphics pipeline bound");
+                self.raw.set_pipeline_state(pipeline);
+                self.restore_compute_root_signature();
+            }
             BindPoint::Graphics { internal: true } => {
                 // Switch to graphics bind point
                 let &(pipeline, ref shared) = self
@@ -828,6 +934,21 @@ impl CommandBuffer {
                     }
                 }
             }
+            BindPoint::RayTracing => {
+                // Switch from the ray tracing bind point, which overwrote
+                // the compute root signature and its arguments.
+                let &(pipeline, ref shared) = self
+                    .comp_pipeline
+                    .pipeline
+                    .as_ref()
+                    .expect("No compute pipeline bound");
+
+                self.raw.set_pipeline_state(pipeline);
+                self.raw.set_compute_root_signature(shared.signature);
+                self.comp_pipeline.user_data.dirty_all();
+
+                self.active_bindpoint = BindPoint::Compute;
+            }
             BindPoint::Compute => {} // Nothing to do
         }
 
@@ -856,6 +977,57 @@ impl CommandBuffer {
         );
     }
 
+    fn set_ray_tracing_bind_point(&mut self) {
+        match self.active_bindpoint {
+            BindPoint::Graphics { .. } | BindPoint::Compute => {
+                // Switch to ray tracing bind point
+                let &(state_object, ref shared) = self
+                    .rt_pipeline
+                    .pipeline
+                    .as_ref()
+                    .expect("No ray tracing pipeline bound");
+
+                unsafe { ray_tracing::CommandList::new(self.raw).set_pipeline_state(state_object) };
+                self.raw.set_compute_root_signature(shared.signature);
+                self.rt_pipeline.user_data.dirty_all();
+
+                if self.active_bindpoint == (BindPoint::Graphics { internal: true }) {
+                    self.bind_descriptor_heaps();
+                    if let Some((_, ref shared)) = self.gr_pipeline.pipeline {
+                        self.raw.set_graphics_root_signature(shared.signature);
+                    }
+                }
+
+                self.active_bindpoint = BindPoint::RayTracing;
+            }
+            BindPoint::RayTracing => {} // Nothing to do
+        }
+
+        let cmd_buffer = &mut self.raw;
+        self.rt_pipeline.flush_user_data(
+            |slot, data| unsafe {
+                cmd_buffer.clone().SetComputeRoot32BitConstants(
+                    slot,
+                    data.len() as _,
+                    data.as_ptr() as *const _,
+                    0,
+                )
+            },
+            |slot, gpu| cmd_buffer.set_compute_root_descriptor_table(slot, gpu),
+            |slot, buffer, kind| match kind {
+                DynamicBuffer::Cbv => {
+                    cmd_buffer.set_compute_root_constant_buffer_view(slot, buffer)
+                }
+                DynamicBuffer::Srv => {
+                    cmd_buffer.set_compute_root_shader_resource_view(slot, buffer)
+                }
+                DynamicBuffer::Uav => {
+                    cmd_buffer.set_compute_root_unordered_access_view(slot, buffer)
+                }
+            },
+        );
+    }
+
     fn transition_barrier(
         transition: d3d12::D3D12_RESOURCE_TRANSITION_BARRIER,
     ) -> d3d12::D3D12_RESOURCE_BARRIER {
@@ -869,6 +1041,28 @@ impl CommandBuffer {
         barrier
     }
 
+    /// Adjust the states of a barrier transferring a resource between queue families.
+    ///
+    /// D3D12 has no ownership, but the resources decay to the common state between
+    /// queues of different types. The release half transitions into this state and
+    /// the acquire half out of it, on the queues recording them.
+    fn transfer_states(
+        &self,
+        families: &Option<Range<QueueFamilyId>>,
+        states: Range<d3d12::D3D12_RESOURCE_STATES>,
+    ) -> Range<d3d12::D3D12_RESOURCE_STATES> {
+        match *families {
+            Some(ref f) if f.start != f.end => {
+                if self.pool_shared.family == f.start {
+                    states.start..d3d12::D3D12_RESOURCE_STATE_COMMON
+                } else {
+                    d3d12::D3D12_RESOURCE_STATE_COMMON..states.end
+                }
+            }
+            _ => states,
+        }
+    }
+
     fn dual_transition_barriers(
         resource: native::Resource,
         sub: u32,
@@ -891,6 +1085,10 @@ impl CommandBuffer {
     }
 
     fn split_buffer_copy(copies: &mut Vec<Copy>, r: com::BufferImageCopy, image: &r::ImageBound) {
+        if !image.contains_level(r.image_layers.level) {
+            error!("Copy region {:?} is out of the levels of the image", r);
+            return;
+        }
         let buffer_width = if r.buffer_width == 0 {
             r.image_extent.width
         } else {
@@ -901,7 +1099,15 @@ impl CommandBuffer {
         } else {
             r.buffer_height
         };
-        let desc = image.surface_type.desc();
+        // Planes of multi-planar images are copied with the format of the plane.
+        let plane = conv::map_plane_slice(image.surface_type, r.image_layers.aspects);
+        let (desc, format) = match image.surface_type.planes().get(plane as usize) {
+            Some(plane) => (
+                plane.format.surface_desc(),
+                conv::map_format(plane.format).unwrap(),
+            ),
+            None => (image.surface_type.desc(), image.descriptor.Format),
+        };
         let bytes_per_block = desc.bits as u32 / 8;
         let image_extent_aligned = image::Extent {
             width: up_align(r.image_extent.width, desc.dim.0 as _),
@@ -913,7 +1119,8 @@ impl CommandBuffer {
         let is_pitch_aligned = row_pitch % d3d12::D3D12_TEXTURE_DATA_PITCH_ALIGNMENT == 0;
 
         for layer in r.image_layers.layers.clone() {
-            let img_subresource = image.calc_subresource(r.image_layers.level as _, layer as _, 0);
+            let img_subresource =
+                image.calc_subresource(r.image_layers.level as _, layer as _, plane);
             let layer_relative = (layer - r.image_layers.layers.start) as u32;
             let layer_offset = r.buffer_offset as u64
                 + (layer_relative * slice_pitch * r.image_extent.depth) as u64;
@@ -923,6 +1130,7 @@ impl CommandBuffer {
                 // trivial case: everything is aligned, ready for copying
                 copies.push(Copy {
                     footprint_offset: aligned_offset,
+                    format,
                     footprint: image_extent_aligned,
                     row_pitch,
                     img_subresource,
@@ -948,6 +1156,7 @@ impl CommandBuffer {
                     // we can map it to the aligned one and adjust the offsets accordingly
                     copies.push(Copy {
                         footprint_offset: aligned_offset,
+                        format,
                         footprint,
                         row_pitch,
                         img_subresource,
@@ -963,6 +1172,7 @@ impl CommandBuffer {
 
                     copies.push(Copy {
                         footprint_offset: aligned_offset,
+                        format,
                         footprint: image::Extent {
                             width: row_pitch_texels,
                             ..footprint
@@ -978,6 +1188,7 @@ impl CommandBuffer {
                     });
                     copies.push(Copy {
                         footprint_offset: aligned_offset,
+                        format,
                         footprint: image::Extent {
                             width: image_extent_aligned.width - half,
                             height: footprint.height + desc.dim.1 as u32,
@@ -1029,6 +1240,7 @@ impl CommandBuffer {
 
                         copies.push(Copy {
                             footprint_offset: aligned_offset,
+                            format,
                             footprint: image::Extent {
                                 width: cut_width + gap_texels,
                                 height: desc.dim.1 as _,
@@ -1061,6 +1273,7 @@ impl CommandBuffer {
 
                         copies.push(Copy {
                             footprint_offset: next_aligned_offset,
+                            format,
                             footprint: image::Extent {
                                 width: leftover,
                                 height: desc.dim.1 as _,
@@ -1086,6 +1299,15 @@ impl CommandBuffer {
         }
     }
 
+    fn is_dynamic_state(&self, state: hal::DynamicStates) -> bool {
+        if self.dynamic_states.contains(state) {
+            true
+        } else {
+            warn!("{:?} is baked into the bound pipeline, ignoring", state);
+            false
+        }
+    }
+
     fn set_vertex_buffers(&mut self) {
         let cmd_buffer = &mut self.raw;
         let vbs_remap = &self.vertex_bindings_remap;
@@ -1186,18 +1408,20 @@ impl CommandBuffer {
             // Only one barrier if it affects the whole image.
             self.barriers.push(bar);
         } else {
-            // Generate barrier for each layer/level combination.
-            for rel_level in 0..num_levels {
-                for rel_layer in 0..num_layers {
-                    unsafe {
-                        let transition_barrier = &mut *bar.u.Transition_mut();
-                        transition_barrier.Subresource = target.calc_subresource(
-                            (range.level_start + rel_level) as _,
-                            (range.layer_start + rel_layer) as _,
-                            0,
-                        );
+            // Generate barrier for each plane/layer/level combination.
+            for plane in conv::map_planes(target.surface_type, range.aspects) {
+                for rel_level in 0..num_levels {
+                    for rel_layer in 0..num_layers {
+                        unsafe {
+                            let transition_barrier = &mut *bar.u.Transition_mut();
+                            transition_barrier.Subresource = target.calc_subresource(
+                                (range.level_start + rel_level) as _,
+                                (range.layer_start + rel_layer) as _,
+                                plane,
+                            );
+                        }
+                        self.barriers.push(bar);
                     }
-                    self.barriers.push(bar);
                 }
             }
         }
@@ -1219,6 +1443,102 @@ impl CommandBuffer {
                 .ResourceBarrier(self.barriers.len() as _, self.barriers.as_ptr());
         }
     }
+
+    /// Record a pipeline barrier with the enhanced barrier API.
+    ///
+    /// Layouts and accesses are derived from the same legacy states as the
+    /// `ResourceBarrier` path, so both can be mixed within a command list.
+    unsafe fn enhanced_pipeline_barrier<'a, T>(
+        &mut self,
+        list: &barrier::CommandList,
+        stages: Range<pso::PipelineStage>,
+        barriers: T,
+    ) where
+        T: Iterator<Item = memory::Barrier<'a, Backend>>,
+    {
+        self.barrier_groups.clear();
+
+        for barrier in barriers {
+            match barrier {
+                memory::Barrier::AllBuffers(ref states) => {
+                    self.barrier_groups.push_global(
+                        &stages,
+                        conv::map_buffer_resource_state(states.start)
+                            ..conv::map_buffer_resource_state(states.end),
+                    );
+                }
+                memory::Barrier::AllImages(ref states) => {
+                    self.barrier_groups.push_global(
+                        &stages,
+                        conv::map_image_resource_state(states.start, image::Layout::General)
+                            ..conv::map_image_resource_state(states.end, image::Layout::General),
+                    );
+                }
+                memory::Barrier::Buffer {
+                    ref states,
+                    target,
+                    ref families,
+                    range: _,
+                } => {
+                    let target = target.expect_bound();
+                    let states = if target
+                        .usage
+                        .contains(buffer::Usage::ACCELERATION_STRUCTURE_STORAGE)
+                    {
+                        // Acceleration structures stay in their own state, only
+                        // their accesses need to be synchronized.
+                        ray_tracing::D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE
+                            ..ray_tracing::D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE
+                    } else {
+                        self.transfer_states(
+                            families,
+                            conv::map_buffer_resource_state(states.start)
+                                ..conv::map_buffer_resource_state(states.end),
+                        )
+                    };
+
+                    self.barrier_groups.push_buffer(&stages, target, states);
+                }
+                memory::Barrier::Image {
+                    ref states,
+                    target,
+                    ref families,
+                    ref range,
+                } => {
+                    let discard = states.start.1 == image::Layout::Undefined;
+                    let states = self.transfer_states(
+                        families,
+                        conv::map_image_resource_state(states.start.0, states.start.1)
+                            ..conv::map_image_resource_state(states.end.0, states.end.1),
+                    );
+
+                    let target = target.expect_bound();
+
+                    match target.place {
+                        r::Place::Heap { .. } | r::Place::Dedicated {} => {
+                            self.barrier_groups
+                                .push_texture(&stages, target, states, discard, range);
+                        }
+                        r::Place::Swapchain { .. } => {} //ignore
+                    }
+                }
+                memory::Barrier::Aliasing { .. } => {
+                    self.barrier_groups.push_aliasing(&stages);
+                }
+            }
+        }
+
+        // The global barrier replaces the UAV barrier of the legacy path.
+        if (stages.start & stages.end).intersects(ALL_SHADER_STAGES) {
+            self.barrier_groups.push_global(
+                &stages,
+                d3d12::D3D12_RESOURCE_STATE_UNORDERED_ACCESS
+                    ..d3d12::D3D12_RESOURCE_STATE_UNORDERED_ACCESS,
+            );
+        }
+
+        self.barrier_groups.flush(list);
+    }
 }
 
 impl com::CommandBuffer<Backend> for CommandBuffer {
@@ -1274,13 +1594,16 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.cur_subpass = !0;
         self.gr_pipeline = PipelineCache::default();
         self.primitive_topology = d3dcommon::D3D_PRIMITIVE_TOPOLOGY_UNDEFINED;
+        self.dynamic_states = hal::DynamicStates::all();
         self.comp_pipeline = PipelineCache::default();
+        self.rt_pipeline = PipelineCache::default();
         self.active_bindpoint = BindPoint::Graphics { internal: false };
         self.active_descriptor_heaps = [native::DescriptorHeap::null(); 2];
         self.occlusion_query = None;
         self.pipeline_stats_query = None;
         self.vertex_bindings_remap = [None; MAX_VERTEX_BUFFERS];
         self.vertex_buffer_views = [NULL_VERTEX_BUFFER_VIEW; MAX_VERTEX_BUFFERS];
+        self.so_buffer_views = [NULL_STREAM_OUTPUT_BUFFER_VIEW; SO_BUFFER_SLOT_COUNT];
         for heap in self.rtv_pools.drain(..) {
             heap.destroy();
         }
@@ -1419,6 +1742,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     ) where
         T: Iterator<Item = memory::Barrier<'a, Backend>>,
     {
+        if self.shared.enhanced_barriers {
+            // Falls back to legacy barriers if the list doesn't expose the interface.
+            if let Some(list) = barrier::CommandList::query(self.raw) {
+                self.enhanced_pipeline_barrier(&list, stages, barriers);
+                return;
+            }
+        }
+
         self.barriers.clear();
 
         // transition barriers
@@ -1444,21 +1775,36 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     ref families,
                     range: _,
                 } => {
-                    // TODO: Implement queue family ownership transitions for dx12
-                    if let Some(f) = families {
-                        if f.start.0 != f.end.0 {
-                            unimplemented!("Queue family resource ownership transitions are not implemented for DX12 (attempted transition from queue family {} to {}", f.start.0, f.end.0);
-                        }
+                    let target = target.expect_bound();
+                    if target
+                        .usage
+                        .contains(buffer::Usage::ACCELERATION_STRUCTURE_STORAGE)
+                    {
+                        // Acceleration structures stay in their own state, only
+                        // their accesses need to be synchronized.
+                        let mut bar = d3d12::D3D12_RESOURCE_BARRIER {
+                            Type: d3d12::D3D12_RESOURCE_BARRIER_TYPE_UAV,
+                            Flags: d3d12::D3D12_RESOURCE_BARRIER_FLAG_NONE,
+                            u: mem::zeroed(),
+                        };
+                        *bar.u.UAV_mut() = d3d12::D3D12_RESOURCE_UAV_BARRIER {
+                            pResource: target.resource.as_mut_ptr(),
+                        };
+                        self.barriers.push(bar);
+                        continue;
                     }
 
-                    let state_src = conv::map_buffer_resource_state(states.start);
-                    let state_dst = conv::map_buffer_resource_state(states.end);
+                    let states = self.transfer_states(
+                        families,
+                        conv::map_buffer_resource_state(states.start)
+                            ..conv::map_buffer_resource_state(states.end),
+                    );
+                    let (state_src, state_dst) = (states.start, states.end);
 
                     if state_src == state_dst {
                         continue;
                     }
 
-                    let target = target.expect_bound();
                     let bar = Self::transition_barrier(d3d12::D3D12_RESOURCE_TRANSITION_BARRIER {
                         pResource: target.resource.as_mut_ptr(),
                         Subresource: d3d12::D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
@@ -1474,41 +1820,42 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     ref families,
                     ref range,
                 } => {
-                    // TODO: Implement queue family ownership transitions for dx12
-                    if let Some(f) = families {
-                        if f.start.0 != f.end.0 {
-                            unimplemented!("Queue family resource ownership transitions are not implemented for DX12 (attempted transition from queue family {} to {}", f.start.0, f.end.0);
-                        }
-                    }
-
-                    let state_src = conv::map_image_resource_state(states.start.0, states.start.1);
-                    let state_dst = conv::map_image_resource_state(states.end.0, states.end.1);
+                    let states = self.transfer_states(
+                        families,
+                        conv::map_image_resource_state(states.start.0, states.start.1)
+                            ..conv::map_image_resource_state(states.end.0, states.end.1),
+                    );
 
                     let target = target.expect_bound();
 
                     match target.place {
-                        r::Place::Heap { .. } => {
-                            self.fill_texture_barries(target, state_src..state_dst, range);
+                        r::Place::Heap { .. } | r::Place::Dedicated {} => {
+                            self.fill_texture_barries(target, states, range);
                         }
                         r::Place::Swapchain { .. } => {} //ignore
                     }
                 }
+                memory::Barrier::Aliasing { before, after } => {
+                    let mut bar = d3d12::D3D12_RESOURCE_BARRIER {
+                        Type: d3d12::D3D12_RESOURCE_BARRIER_TYPE_ALIASING,
+                        Flags: d3d12::D3D12_RESOURCE_BARRIER_FLAG_NONE,
+                        u: mem::zeroed(),
+                    };
+                    *bar.u.Aliasing_mut() = d3d12::D3D12_RESOURCE_ALIASING_BARRIER {
+                        pResourceBefore: before.map_or(ptr::null_mut(), aliased_resource),
+                        pResourceAfter: after.map_or(ptr::null_mut(), aliased_resource),
+                    };
+                    self.barriers.push(bar);
+                }
             }
         }
 
-        let all_shader_stages = pso::PipelineStage::VERTEX_SHADER
-            | pso::PipelineStage::FRAGMENT_SHADER
-            | pso::PipelineStage::COMPUTE_SHADER
-            | pso::PipelineStage::GEOMETRY_SHADER
-            | pso::PipelineStage::HULL_SHADER
-            | pso::PipelineStage::DOMAIN_SHADER;
-
         // UAV barriers
         //
         // TODO: Currently always add a global UAV barrier.
         //       WAR only requires an execution barrier but D3D12 seems to need
         //       a UAV barrier for this according to docs. Can we make this better?
-        if (stages.start & stages.end).intersects(all_shader_stages) {
+        if (stages.start & stages.end).intersects(ALL_SHADER_STAGES) {
             let mut barrier = d3d12::D3D12_RESOURCE_BARRIER {
                 Type: d3d12::D3D12_RESOURCE_BARRIER_TYPE_UAV,
                 Flags: d3d12::D3D12_RESOURCE_BARRIER_FLAG_NONE,
@@ -1552,7 +1899,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         let base_state = conv::map_image_resource_state(image::Access::TRANSFER_WRITE, layout);
 
         for sub in subresource_ranges {
-            if sub.level_start != 0 || image.mip_levels != 1 {
+            if sub.level_start != image.base_level || image.mip_levels != image.base_level + 1 {
                 warn!("Clearing non-zero mipmap levels is not supported yet");
             }
             let target_state = if sub.aspects.contains(Aspects::COLOR) {
@@ -1634,6 +1981,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                             levels: attachment.mip_levels.0..attachment.mip_levels.1,
                             layers: attachment.layers.0 + clear_rect.layers.start
                                 ..attachment.layers.0 + clear_rect.layers.end,
+                            plane: 0,
                         };
                         let rtv = rtv_pool.alloc_handle();
                         Device::view_image_as_render_target_impl(device, rtv, &view_info).unwrap();
@@ -1668,6 +2016,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                             levels: attachment.mip_levels.0..attachment.mip_levels.1,
                             layers: attachment.layers.0 + clear_rect.layers.start
                                 ..attachment.layers.0 + clear_rect.layers.end,
+                            plane: 0,
                         };
                         let dsv = dsv_pool.alloc_handle();
                         Device::view_image_as_depth_stencil_impl(device, dsv, &view_info).unwrap();
@@ -1708,6 +2057,12 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         }
 
         for r in regions {
+            if !src.contains_level(r.src_subresource.level)
+                || !dst.contains_level(r.dst_subresource.level)
+            {
+                error!("Resolve region {:?} is out of the levels of the images", r);
+                continue;
+            }
             for layer in 0..r.extent.depth as u32 {
                 self.raw.ResolveSubresource(
                     src.resource.as_mut_ptr(),
@@ -1764,9 +2119,13 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             _ => unimplemented!(),
         }
 
+        // Multi-planar sources are converted to RGB, sampling each of their planes.
+        let planes = src.surface_type.planes();
+        let ycbcr = !planes.is_empty();
+
         // Descriptor heap for the current blit, only storing the src image
         let (srv_heap, _) = device.create_descriptor_heap(
-            1,
+            cmp::max(1, planes.len()) as _,
             native::DescriptorHeapType::CbvSrvUav,
             native::DescriptorHeapFlags::SHADER_VISIBLE,
             0,
@@ -1774,7 +2133,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.raw.set_descriptor_heaps(&[srv_heap]);
         self.temporary_gpu_heaps.push(srv_heap);
 
-        let srv_desc = Device::build_image_as_shader_resource_desc(&device::ViewInfo {
+        let srv_info = device::ViewInfo {
             resource: src.resource,
             kind: src.kind,
             caps: src.view_caps,
@@ -1783,13 +2142,35 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             component_mapping: device::IDENTITY_MAPPING,
             levels: 0..src.descriptor.MipLevels as _,
             layers: 0..src.kind.num_layers(),
-        })
-        .unwrap();
-        device.CreateShaderResourceView(
-            src.resource.as_mut_ptr(),
-            &srv_desc,
-            srv_heap.start_cpu_descriptor(),
-        );
+            plane: 0,
+        };
+        if ycbcr {
+            let handle_size =
+                device.get_descriptor_increment_size(native::DescriptorHeapType::CbvSrvUav);
+            let start = srv_heap.start_cpu_descriptor();
+            for (index, plane) in planes.iter().enumerate() {
+                let srv_desc = Device::build_image_as_shader_resource_desc(&device::ViewInfo {
+                    format: conv::map_format(plane.format).unwrap(),
+                    plane: index as _,
+                    ..srv_info.clone()
+                })
+                .unwrap();
+                device.CreateShaderResourceView(
+                    src.resource.as_mut_ptr(),
+                    &srv_desc,
+                    d3d12::D3D12_CPU_DESCRIPTOR_HANDLE {
+                        ptr: start.ptr + index * handle_size as usize,
+                    },
+                );
+            }
+        } else {
+            let srv_desc = Device::build_image_as_shader_resource_desc(&srv_info).unwrap();
+            device.CreateShaderResourceView(
+                src.resource.as_mut_ptr(),
+                &srv_desc,
+                srv_heap.start_cpu_descriptor(),
+            );
+        }
 
         let filter = match filter {
             image::Filter::Nearest => d3d12::D3D12_FILTER_MIN_MAG_MIP_POINT,
@@ -1800,11 +2181,33 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             rtv: d3d12::D3D12_CPU_DESCRIPTOR_HANDLE,
             viewport: d3d12::D3D12_VIEWPORT,
             data: internal::BlitData,
+            ycbcr_to_rgb: Option<internal::YcbcrToRgb>,
         }
         let mut instances = FastHashMap::<internal::BlitKey, Vec<Instance>>::default();
         let mut barriers = Vec::new();
 
         for r in regions {
+            if !src.contains_level(r.src_subresource.level)
+                || !dst.contains_level(r.dst_subresource.level)
+            {
+                error!("Blit region {:?} is out of the levels of the images", r);
+                continue;
+            }
+            // The planes of 10-bit formats store the values in their high bits,
+            // so the bits of the plane formats give the normalized values.
+            let ycbcr_to_rgb = match (planes.first(), r.ycbcr_conversion) {
+                (Some(plane), Some(conversion)) => {
+                    Some(conversion.to_rgb_matrix(plane.format.surface_desc().bits as u8))
+                }
+                (Some(_), None) => {
+                    error!(
+                        "Blit region {:?} of a multi-planar image has no YCbCr conversion",
+                        r
+                    );
+                    continue;
+                }
+                (None, _) => None,
+            };
             let first_layer = r.dst_subresource.layers.start;
             let num_layers = r.dst_subresource.layers.end - first_layer;
 
@@ -1829,7 +2232,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                         };
 
                         *desc.u.Texture2DArray_mut() = d3d12::D3D12_TEX2D_ARRAY_RTV {
-                            MipSlice: r.dst_subresource.level as _,
+                            MipSlice: dst.relative_level(r.dst_subresource.level as _),
                             FirstArraySlice: (i + first_layer) as u32,
                             ArraySize: 1,
                             PlaneSlice: 0, // TODO
@@ -1890,7 +2293,8 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                         src_offset: [sx as f32 / width as f32, sy as f32 / height as f32],
                         src_extent: [dx as f32 / width as f32, dy as f32 / height as f32],
                         layer: src_layer as f32,
-                        level: r.src_subresource.level as _,
+                        // The SRV starts at the first level backed by the resource.
+                        level: src.relative_level(r.src_subresource.level as _) as _,
                     }
                 };
 
@@ -1898,6 +2302,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     rtv: rtv_pool.at(i as _, 0).cpu,
                     viewport,
                     data,
+                    ycbcr_to_rgb,
                 });
 
                 barriers.push(Self::transition_barrier(
@@ -1921,7 +2326,11 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         // execute blits
         self.set_internal_graphics_pipeline();
         for (key, list) in instances {
-            let blit = self.shared.service_pipes.get_blit_2d_color(key);
+            let blit = if ycbcr {
+                self.shared.service_pipes.get_blit_2d_ycbcr(key)
+            } else {
+                self.shared.service_pipes.get_blit_2d_color(key)
+            };
             self.raw
                 .IASetPrimitiveTopology(d3dcommon::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
             self.raw.set_pipeline_state(blit.pipeline);
@@ -1943,6 +2352,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                     &inst.data as *const _ as *const _,
                     0,
                 );
+                if let Some(ref ycbcr_to_rgb) = inst.ycbcr_to_rgb {
+                    self.raw.SetGraphicsRoot32BitConstants(
+                        2,
+                        (mem::size_of::<internal::YcbcrToRgb>() / 4) as _,
+                        ycbcr_to_rgb.as_ptr() as *const _,
+                        0,
+                    );
+                }
                 self.raw
                     .OMSetRenderTargets(1, &inst.rtv, minwindef::TRUE, ptr::null());
                 self.raw.draw(3, 1, 0, 0);
@@ -1977,12 +2394,16 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             IndexType::U16 => dxgiformat::DXGI_FORMAT_R16_UINT,
             IndexType::U32 => dxgiformat::DXGI_FORMAT_R32_UINT,
         };
+        let size = match buffer::cast(sub.size_to(buffer.requirements.size)) {
+            Ok(size) => size,
+            Err(err) => {
+                error!("Unable to bind index buffer: {}", err);
+                return;
+            }
+        };
         let location = buffer.resource.gpu_virtual_address();
-        self.raw.set_index_buffer(
-            location + sub.offset,
-            sub.size_to(buffer.requirements.size) as u32,
-            format,
-        );
+        self.raw
+            .set_index_buffer(location + sub.offset, size, format);
     }
 
     unsafe fn bind_vertex_buffers<'a, T>(&mut self, first_binding: pso::BufferIndex, buffers: T)
@@ -1996,9 +2417,16 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             .zip(buffers)
         {
             let b = buffer.expect_bound();
+            let size = match buffer::cast(sub.size_to(b.requirements.size)) {
+                Ok(size) => size,
+                Err(err) => {
+                    error!("Unable to bind vertex buffer: {}", err);
+                    continue;
+                }
+            };
             let base = (*b.resource).GetGPUVirtualAddress();
             view.BufferLocation = base + sub.offset;
-            view.SizeInBytes = sub.size_to(b.requirements.size) as u32;
+            view.SizeInBytes = size;
         }
         self.set_vertex_buffers();
     }
@@ -2007,6 +2435,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     where
         T: Iterator<Item = pso::Viewport>,
     {
+        if !self.is_dynamic_state(hal::DynamicStates::VIEWPORT) {
+            return;
+        }
         for (i, vp) in viewports.enumerate() {
             let viewport = d3d12::D3D12_VIEWPORT {
                 TopLeftX: vp.rect.x as _,
@@ -2031,6 +2462,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     where
         T: Iterator<Item = pso::Rect>,
     {
+        if !self.is_dynamic_state(hal::DynamicStates::SCISSOR) {
+            return;
+        }
         for (i, r) in scissors.enumerate() {
             let rect = get_rect(&r);
             if i + first_scissor as usize >= self.scissor_cache.len() {
@@ -2045,11 +2479,17 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        if !self.is_dynamic_state(hal::DynamicStates::BLEND_CONSTANTS) {
+            return;
+        }
         self.raw.set_blend_factor(color);
     }
 
     unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
         assert!(!faces.is_empty());
+        if !self.is_dynamic_state(hal::DynamicStates::STENCIL_REFERENCE) {
+            return;
+        }
 
         if !faces.is_all() {
             warn!(
@@ -2072,6 +2512,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        if !self.is_dynamic_state(hal::DynamicStates::DEPTH_BOUNDS) {
+            return;
+        }
         let (cmd_list1, hr) = self.raw.cast::<d3d12::ID3D12GraphicsCommandList1>();
         if winerror::SUCCEEDED(hr) {
             cmd_list1.OMSetDepthBounds(bounds.start, bounds.end);
@@ -2085,9 +2528,27 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         validate_line_width(width);
     }
 
-    unsafe fn set_depth_bias(&mut self, _depth_bias: pso::DepthBias) {
-        //TODO:
-        // unimplemented!()
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        if !self.is_dynamic_state(hal::DynamicStates::DEPTH_BIAS) {
+            return;
+        }
+        if self.shared.dynamic_depth_bias {
+            depth_bias::set(self.raw, depth_bias);
+        } else {
+            warn!("Dynamic depth bias is not supported");
+        }
+    }
+
+    unsafe fn set_shading_rate(
+        &mut self,
+        rate: pso::ShadingRate,
+        combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        if self.shared.shading_rate_tier != shading_rate::Tier::NotSupported {
+            shading_rate::set_rate(self.raw, rate, combiners);
+        } else {
+            warn!("Variable rate shading is not supported");
+        }
     }
 
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &r::GraphicsPipeline) {
@@ -2106,12 +2567,18 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.raw.IASetPrimitiveTopology(pipeline.topology);
         self.primitive_topology = pipeline.topology;
 
+        if self.active_bindpoint == BindPoint::RayTracing {
+            self.restore_compute_root_signature();
+        }
         self.active_bindpoint = BindPoint::Graphics { internal: false };
         self.gr_pipeline.pipeline = Some((pipeline.raw, Arc::clone(&pipeline.shared)));
         self.vertex_bindings_remap = pipeline.vertex_bindings;
 
         self.set_vertex_buffers();
 
+        // Baked states are applied through the same paths as the dynamic ones,
+        // but can't be overwritten until another pipeline is bound.
+        self.dynamic_states = hal::DynamicStates::all();
         if let Some(ref vp) = pipeline.baked_states.viewport {
             self.set_viewports(0, iter::once(vp.clone()));
         }
@@ -2124,6 +2591,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         if let Some(ref bounds) = pipeline.baked_states.depth_bounds {
             self.set_depth_bounds(bounds.clone());
         }
+        self.dynamic_states = pipeline.dynamic_states;
     }
 
     unsafe fn bind_graphics_descriptor_sets<'a, I, J>(
@@ -2149,7 +2617,10 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn bind_compute_pipeline(&mut self, pipeline: &r::ComputePipeline) {
         match self.comp_pipeline.pipeline {
-            Some((_, ref shared)) if Arc::ptr_eq(shared, &pipeline.shared) => {
+            Some((_, ref shared))
+                if Arc::ptr_eq(shared, &pipeline.shared)
+                    && self.active_bindpoint != BindPoint::RayTracing =>
+            {
                 // Same root signature, nothing to do
             }
             _ => {
@@ -2207,17 +2678,28 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
     unsafe fn fill_buffer(&mut self, buffer: &r::Buffer, range: buffer::SubRange, data: u32) {
         let buffer = buffer.expect_bound();
         let bytes_per_unit = 4;
-        let start = range.offset as i32;
+        let start = range.offset;
         let end = range
             .size
-            .map_or(buffer.requirements.size, |s| range.offset + s) as i32;
+            .map_or(buffer.requirements.size, |s| range.offset + s);
         if start % 4 != 0 || end % 4 != 0 {
             warn!("Fill buffer bounds have to be multiples of 4");
         }
+        let (left, right, num_elements) = match (
+            buffer::cast(start / bytes_per_unit),
+            buffer::cast(end / bytes_per_unit),
+            buffer::cast(buffer.requirements.size / bytes_per_unit),
+        ) {
+            (Ok(left), Ok(right), Ok(num_elements)) => (left, right, num_elements),
+            (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => {
+                error!("Unable to fill buffer: {}", err);
+                return;
+            }
+        };
         let rect = d3d12::D3D12_RECT {
-            left: start / bytes_per_unit,
+            left,
             top: 0,
-            right: end / bytes_per_unit,
+            right,
             bottom: 1,
         };
 
@@ -2243,7 +2725,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         };
         *uav_desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
             FirstElement: 0,
-            NumElements: (buffer.requirements.size / bytes_per_unit as u64) as u32,
+            NumElements: num_elements,
             StructureByteStride: 0,
             CounterOffsetInBytes: 0,
             Flags: d3d12::D3D12_BUFFER_UAV_FLAG_RAW,
@@ -2277,6 +2759,81 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn build_acceleration_structures(&mut self, descs: &[accel::BuildDesc<Backend>]) {
+        let list = ray_tracing::CommandList::new(self.raw);
+        for desc in descs {
+            let geometries = ray_tracing::map_geometries(&desc.geometry);
+            let build = ray_tracing::D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_DESC {
+                DestAccelerationStructureData: desc.dst.location,
+                Inputs: ray_tracing::map_inputs(&desc.geometry, &geometries, desc.src.is_some()),
+                SourceAccelerationStructureData: desc.src.map_or(0, |src| src.location),
+                ScratchAccelerationStructureData: ray_tracing::address(&desc.scratch),
+            };
+            list.build(&build);
+        }
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        src: &r::AccelerationStructure,
+        dst: &r::AccelerationStructure,
+        mode: accel::CopyMode,
+    ) {
+        ray_tracing::CommandList::new(self.raw).copy(dst.location, src.location, mode);
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, pipeline: &r::RayTracingPipeline) {
+        match self.rt_pipeline.pipeline {
+            Some((_, ref shared))
+                if Arc::ptr_eq(shared, &pipeline.shared)
+                    && self.active_bindpoint == BindPoint::RayTracing =>
+            {
+                // Same root signature, nothing to do
+            }
+            _ => {
+                self.raw
+                    .set_compute_root_signature(pipeline.shared.signature);
+                // All slots need to be rebound internally on signature change.
+                self.rt_pipeline.user_data.dirty_all();
+            }
+        }
+        ray_tracing::CommandList::new(self.raw).set_pipeline_state(pipeline.raw);
+
+        self.active_bindpoint = BindPoint::RayTracing;
+        self.rt_pipeline.pipeline = Some((pipeline.raw, Arc::clone(&pipeline.shared)));
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &r::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a r::DescriptorSet>,
+        J: Iterator<Item = com::DescriptorSetOffset>,
+    {
+        let set_array = sets.collect::<ArrayVec<[_; MAX_DESCRIPTOR_SETS]>>();
+        self.active_descriptor_heaps = self
+            .rt_pipeline
+            .bind_descriptor_sets(layout, first_set, &set_array, offsets);
+        self.bind_descriptor_heaps();
+
+        for (i, set) in set_array.into_iter().enumerate() {
+            self.mark_bound_descriptor(first_set + i, set);
+        }
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        tables: &pso::ShaderBindingTables<Backend>,
+        count: WorkGroupCount,
+    ) {
+        self.set_ray_tracing_bind_point();
+        ray_tracing::CommandList::new(self.raw)
+            .dispatch_rays(&ray_tracing::map_dispatch(tables, count));
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &r::Buffer, dst: &r::Buffer, regions: T)
     where
         T: Iterator<Item = com::BufferCopy>,
@@ -2334,10 +2891,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         let device = self.shared.service_pipes.device.clone();
         let src_desc = src.surface_type.desc();
         let dst_desc = dst.surface_type.desc();
-        assert_eq!(src_desc.bits, dst_desc.bits);
+        // Planes are copied from or to images of the plane format directly.
+        let is_planar =
+            !src.surface_type.planes().is_empty() || !dst.surface_type.planes().is_empty();
+        assert!(is_planar || src_desc.bits == dst_desc.bits);
         //Note: Direct3D 10.1 enables copies between prestructured-typed textures
         // and block-compressed textures of the same bit widths.
-        let do_alias = src.surface_type != dst.surface_type
+        let do_alias = !is_planar
+            && src.surface_type != dst.surface_type
             && src_desc.is_compressed() == dst_desc.is_compressed();
 
         if do_alias {
@@ -2345,30 +2906,30 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             // similarly to how it allows the views to be created.
 
             // create an aliased resource to the source
-            let mut alias = native::Resource::null();
             let desc = d3d12::D3D12_RESOURCE_DESC {
                 Format: dst.descriptor.Format,
                 ..src.descriptor.clone()
             };
-            let (heap_ptr, heap_offset) = match src.place {
-                r::Place::Heap { raw, offset } => (raw.as_mut_ptr(), offset),
+            let (heap, heap_offset) = match src.place {
+                r::Place::Heap { raw, offset } => (raw, offset),
                 r::Place::Swapchain {} => {
                     error!("Unable to copy swapchain image, skipping");
                     return;
                 }
+                r::Place::Dedicated {} => {
+                    error!("Unable to reinterpret the format of an imported image, skipping");
+                    return;
+                }
             };
-            assert_eq!(
-                winerror::S_OK,
-                device.CreatePlacedResource(
-                    heap_ptr,
-                    heap_offset,
-                    &desc,
-                    d3d12::D3D12_RESOURCE_STATE_COMMON,
-                    ptr::null(),
-                    &d3d12::ID3D12Resource::uuidof(),
-                    alias.mut_void(),
-                )
+            let (alias, hr) = heap::create_placed_resource(
+                device,
+                heap,
+                heap_offset,
+                &desc,
+                d3d12::D3D12_RESOURCE_STATE_COMMON,
+                None,
             );
+            assert_eq!(winerror::S_OK, hr);
             src_image.pResource = alias.as_mut_ptr();
             self.retained_resources.push(alias);
 
@@ -2391,6 +2952,12 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 r.src_subresource.layers.len(),
                 r.dst_subresource.layers.len()
             );
+            if !src.contains_level(r.src_subresource.level)
+                || !dst.contains_level(r.dst_subresource.level)
+            {
+                error!("Copy region {:?} is out of the levels of the images", r);
+                continue;
+            }
             let src_box = d3d12::D3D12_BOX {
                 left: r.src_offset.x as _,
                 top: r.src_offset.y as _,
@@ -2399,6 +2966,8 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 front: r.src_offset.z as _,
                 back: (r.src_offset.z + r.extent.depth as i32) as _,
             };
+            let src_plane = conv::map_plane_slice(src.surface_type, r.src_subresource.aspects);
+            let dst_plane = conv::map_plane_slice(dst.surface_type, r.dst_subresource.aspects);
 
             for (src_layer, dst_layer) in r
                 .src_subresource
@@ -2407,9 +2976,9 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
                 .zip(r.dst_subresource.layers.clone())
             {
                 *src_image.u.SubresourceIndex_mut() =
-                    src.calc_subresource(r.src_subresource.level as _, src_layer as _, 0);
+                    src.calc_subresource(r.src_subresource.level as _, src_layer as _, src_plane);
                 *dst_image.u.SubresourceIndex_mut() =
-                    dst.calc_subresource(r.dst_subresource.level as _, dst_layer as _, 0);
+                    dst.calc_subresource(r.dst_subresource.level as _, dst_layer as _, dst_plane);
                 self.raw.CopyTextureRegion(
                     &dst_image,
                     r.dst_offset.x as _,
@@ -2481,7 +3050,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             let footprint = d3d12::D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
                 Offset: c.footprint_offset,
                 Footprint: d3d12::D3D12_SUBRESOURCE_FOOTPRINT {
-                    Format: image.descriptor.Format,
+                    Format: c.format,
                     Width: c.footprint.width,
                     Height: c.footprint.height,
                     Depth: c.footprint.depth,
@@ -2545,7 +3114,7 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             let footprint = d3d12::D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
                 Offset: c.footprint_offset,
                 Footprint: d3d12::D3D12_SUBRESOURCE_FOOTPRINT {
-                    Format: image.descriptor.Format,
+                    Format: c.format,
                     Width: c.footprint.width,
                     Height: c.footprint.height,
                     Depth: c.footprint.depth,
@@ -2631,10 +3200,82 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         );
     }
 
-    unsafe fn draw_mesh_tasks(&mut self, _: TaskCount, _: TaskCount) {
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: Iterator<Item = (&'a r::Buffer, buffer::SubRange)>,
+    {
+        assert!(first_binding as usize <= SO_BUFFER_SLOT_COUNT);
+
+        let device = self.pool_shared.device;
+        let counters = self
+            .so_counters
+            .get_or_insert_with(|| internal::StreamOutputCounters::new(device));
+        for (slot, (view, (buffer, sub))) in self.so_buffer_views[first_binding as _..]
+            .iter_mut()
+            .zip(buffers)
+            .enumerate()
+        {
+            let b = buffer.expect_bound();
+            let base = (*b.resource).GetGPUVirtualAddress();
+            view.BufferLocation = base + sub.offset;
+            view.SizeInBytes = sub.size_to(b.requirements.size);
+            view.BufferFilledSizeLocation = counters.location(first_binding as usize + slot);
+        }
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        // Capturing starts from the beginning of the buffers, so reset the filled sizes.
+        if let Some(ref counters) = self.so_counters {
+            self.raw.CopyBufferRegion(
+                counters.counters.as_mut_ptr(),
+                0,
+                counters.zeros.as_mut_ptr(),
+                0,
+                internal::StreamOutputCounters::SIZE,
+            );
+            let (barrier, _) = Self::dual_transition_barriers(
+                counters.counters,
+                d3d12::D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+                d3d12::D3D12_RESOURCE_STATE_COPY_DEST..d3d12::D3D12_RESOURCE_STATE_STREAM_OUT,
+            );
+            self.raw.ResourceBarrier(1, &barrier);
+        }
+
+        self.raw
+            .SOSetTargets(0, SO_BUFFER_SLOT_COUNT as _, self.so_buffer_views.as_ptr());
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        self.raw
+            .SOSetTargets(0, SO_BUFFER_SLOT_COUNT as _, ptr::null());
+
+        if let Some(ref counters) = self.so_counters {
+            let (_, barrier) = Self::dual_transition_barriers(
+                counters.counters,
+                d3d12::D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+                d3d12::D3D12_RESOURCE_STATE_COMMON..d3d12::D3D12_RESOURCE_STATE_STREAM_OUT,
+            );
+            self.raw.ResourceBarrier(1, &barrier);
+        }
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, _: Range<InstanceCount>) {
+        // D3D12 has no equivalent of `DrawAuto`.
         unimplemented!()
     }
 
+    unsafe fn draw_mesh_tasks(&mut self, task_count: TaskCount, first_task: TaskCount) {
+        // `DispatchMesh` always starts from the first group.
+        if first_task != 0 {
+            error!("Mesh tasks can't start from the task {}", first_task);
+            return;
+        }
+        self.set_graphics_bind_point();
+        mesh::dispatch(self.raw, task_count);
+    }
+
+    // The indirect arguments of `DispatchMesh` are group counts in 3 dimensions,
+    // which don't match the `task_count, first_task` pair of the other backends.
     unsafe fn draw_mesh_tasks_indirect(
         &mut self,
         _: &r::Buffer,
@@ -1,12 +1,13 @@
-use crate::validate_line_width;
+use crate::{shading_rate, validate_line_width};
 
 use spirv_cross::spirv;
-use std::mem;
+use std::{mem, ops::Range};
 
 use winapi::{
     shared::{
         basetsd::UINT8,
         dxgiformat::*,
+        dxgitype,
         minwindef::{FALSE, INT, TRUE, UINT},
     },
     um::{d3d12::*, d3dcommon::*},
@@ -15,11 +16,11 @@ use winapi::{
 use auxil::ShaderStage;
 use hal::{
     buffer,
-    format::{Format, ImageFeature, SurfaceType, Swizzle},
-    image, pso,
+    format::{Aspects, Format, ImageFeature, PlaneDesc, SurfaceType, Swizzle},
+    image, pso, window as w,
 };
 
-use native::ShaderVisibility;
+use native::{RootSignatureFlags, ShaderVisibility};
 
 fn is_little_endinan() -> bool {
     unsafe { 1 == *(&1u32 as *const _ as *const u8) }
@@ -106,6 +107,8 @@ pub fn map_format(format: Format) -> Option<DXGI_FORMAT> {
         Bc6hSfloat => DXGI_FORMAT_BC6H_SF16,
         Bc7Unorm => DXGI_FORMAT_BC7_UNORM,
         Bc7Srgb => DXGI_FORMAT_BC7_UNORM_SRGB,
+        Nv12Unorm => DXGI_FORMAT_NV12,
+        P010Unorm => DXGI_FORMAT_P010,
 
         _ => return None,
     };
@@ -131,6 +134,39 @@ pub fn map_format_shader_stencil(surface: SurfaceType) -> Option<DXGI_FORMAT> {
     }
 }
 
+/// Returns the plane slice of a multi-planar image selected by the aspects.
+///
+/// Other images use plane 0, which is also the one viewed for depth and stencil.
+pub fn map_plane_slice(surface: SurfaceType, aspects: Aspects) -> UINT {
+    surface
+        .planes()
+        .iter()
+        .position(|plane| plane.aspect == aspects)
+        .unwrap_or(0) as UINT
+}
+
+/// Returns the range of planes of an image covered by the aspects.
+pub fn map_planes(surface: SurfaceType, aspects: Aspects) -> Range<UINT> {
+    let planes = surface.planes();
+    if !planes.is_empty() {
+        // The color aspect covers all the planes.
+        let covered = |plane: &PlaneDesc| aspects.intersects(Aspects::COLOR | plane.aspect);
+        let first = planes.iter().position(covered).unwrap_or(planes.len());
+        let end = planes
+            .iter()
+            .rposition(covered)
+            .map_or(first, |last| last + 1);
+        return first as UINT..end as UINT;
+    }
+    if aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+        0..2
+    } else if aspects == Aspects::STENCIL {
+        1..2
+    } else {
+        0..1
+    }
+}
+
 pub fn map_format_dsv(surface: SurfaceType) -> Option<DXGI_FORMAT> {
     match surface {
         SurfaceType::D16 => Some(DXGI_FORMAT_D16_UNORM),
@@ -151,6 +187,26 @@ pub fn map_format_nosrgb(format: Format) -> Option<DXGI_FORMAT> {
     }
 }
 
+pub fn map_color_space(color_space: w::ColorSpace) -> dxgitype::DXGI_COLOR_SPACE_TYPE {
+    match color_space {
+        // sRGB formats are encoded by the render target views.
+        w::ColorSpace::SrgbNonlinear | w::ColorSpace::Linear => {
+            dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709
+        }
+        w::ColorSpace::ExtendedSrgbLinear => dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+        w::ColorSpace::Hdr10St2084 => dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+    }
+}
+
+/// Refresh rate of a display mode, in millihertz.
+pub fn map_refresh_rate(rate: dxgitype::DXGI_RATIONAL) -> u32 {
+    if rate.Denominator == 0 {
+        0
+    } else {
+        (rate.Numerator as u64 * 1000 / rate.Denominator as u64) as u32
+    }
+}
+
 pub fn map_swizzle(swizzle: Swizzle) -> UINT {
     use hal::format::Component::*;
 
@@ -257,8 +313,8 @@ pub fn map_rasterizer(rasterizer: &pso::Rasterizer, multisample: bool) -> D3D12_
     use hal::pso::PolygonMode::*;
 
     let bias = match rasterizer.depth_bias {
-        //TODO: support dynamic depth bias
         Some(pso::State::Static(db)) => db,
+        // Dynamic bias is recorded with `RSSetDepthBias`.
         Some(_) | None => pso::DepthBias::default(),
     };
 
@@ -511,13 +567,22 @@ pub fn map_filter(
 pub fn map_buffer_resource_state(access: buffer::Access) -> D3D12_RESOURCE_STATES {
     use self::buffer::Access;
     // Mutable states
-    if access.contains(Access::SHADER_WRITE) {
+    // Acceleration structure accesses only concern the scratch buffers here, the
+    // storage of the acceleration structures never leaves its dedicated state.
+    if access.intersects(
+        Access::SHADER_WRITE
+            | Access::ACCELERATION_STRUCTURE_READ
+            | Access::ACCELERATION_STRUCTURE_WRITE,
+    ) {
         return D3D12_RESOURCE_STATE_UNORDERED_ACCESS;
     }
     if access.contains(Access::TRANSFER_WRITE) {
         // Resolve not relevant for buffers.
         return D3D12_RESOURCE_STATE_COPY_DEST;
     }
+    if access.contains(Access::TRANSFORM_FEEDBACK_WRITE) {
+        return D3D12_RESOURCE_STATE_STREAM_OUT;
+    }
 
     // Read-only states
     let mut state = D3D12_RESOURCE_STATE_COMMON;
@@ -602,6 +667,9 @@ pub fn map_image_resource_state(
         image::Layout::ShaderReadOnlyOptimal | image::Layout::DepthStencilReadOnlyOptimal => {
             derive_immutable_image_states(access)
         }
+        image::Layout::ShadingRateAttachmentOptimal => {
+            shading_rate::D3D12_RESOURCE_STATE_SHADING_RATE_SOURCE
+        }
         image::Layout::Undefined | image::Layout::Preinitialized => D3D12_RESOURCE_STATE_COMMON,
     }
 }
@@ -619,6 +687,25 @@ pub fn map_shader_visibility(flags: pso::ShaderStageFlags) -> ShaderVisibility {
     }
 }
 
+/// Deny the access to the root signature from the graphics stages no parameter is visible to.
+pub fn map_root_signature_flags(stages: pso::ShaderStageFlags) -> RootSignatureFlags {
+    use hal::pso::ShaderStageFlags as Ssf;
+
+    let mut flags = RootSignatureFlags::ALLOW_IA_INPUT_LAYOUT;
+    for &(stage, deny) in &[
+        (Ssf::VERTEX, RootSignatureFlags::DENY_VS_ROOT_ACCESS),
+        (Ssf::HULL, RootSignatureFlags::DENY_HS_ROOT_ACCESS),
+        (Ssf::DOMAIN, RootSignatureFlags::DENY_DS_ROOT_ACCESS),
+        (Ssf::GEOMETRY, RootSignatureFlags::DENY_GS_ROOT_ACCESS),
+        (Ssf::FRAGMENT, RootSignatureFlags::DENY_PS_ROOT_ACCESS),
+    ] {
+        if !stages.contains(stage) {
+            flags |= deny;
+        }
+    }
+    flags
+}
+
 pub fn map_buffer_flags(usage: buffer::Usage) -> D3D12_RESOURCE_FLAGS {
     let mut flags = D3D12_RESOURCE_FLAG_NONE;
 
@@ -626,6 +713,10 @@ pub fn map_buffer_flags(usage: buffer::Usage) -> D3D12_RESOURCE_FLAGS {
     if usage.contains(buffer::Usage::STORAGE) || usage.contains(buffer::Usage::TRANSFER_DST) {
         flags |= D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS;
     }
+    // Acceleration structure builds write to their storage and scratch memory as UAVs.
+    if usage.contains(buffer::Usage::ACCELERATION_STRUCTURE_STORAGE) {
+        flags |= D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS;
+    }
 
     flags
 }
@@ -0,0 +1,393 @@
+//! Diagnostics of the debug layer: validation setup, forwarding of the
+//! `ID3D12InfoQueue` messages to `log`, and decoding of the Device Removed
+//! Extended Data (DRED) when the device is lost.
+//!
+//! The DRED interfaces are newer than what `winapi` exposes, so they are
+//! declared here by hand from `d3d12.h`.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use winapi::{
+    ctypes::{c_char, c_void},
+    shared::{
+        basetsd::SIZE_T,
+        guiddef::{GUID, REFIID},
+        minwindef::{TRUE, UINT},
+        winerror,
+    },
+    um::{d3d12, d3d12sdklayers, libloaderapi, unknwnbase::IUnknown, winnt::HRESULT},
+    Interface,
+};
+
+use std::{ffi::CStr, mem, ptr, slice};
+
+type D3D12_DRED_ENABLEMENT = u32;
+type D3D12_AUTO_BREADCRUMB_OP = u32;
+type D3D12_DRED_ALLOCATION_TYPE = u32;
+
+const D3D12_DRED_ENABLEMENT_FORCED_ON: D3D12_DRED_ENABLEMENT = 2;
+
+type D3D12GetDebugInterface =
+    unsafe extern "system" fn(riid: REFIID, ppvDebug: *mut *mut c_void) -> HRESULT;
+
+#[repr(C)]
+struct D3D12_AUTO_BREADCRUMB_NODE {
+    pCommandListDebugNameA: *const c_char,
+    pCommandListDebugNameW: *const u16,
+    pCommandQueueDebugNameA: *const c_char,
+    pCommandQueueDebugNameW: *const u16,
+    pCommandList: *mut d3d12::ID3D12GraphicsCommandList,
+    pCommandQueue: *mut d3d12::ID3D12CommandQueue,
+    BreadcrumbCount: u32,
+    pLastBreadcrumbValue: *const u32,
+    pCommandHistory: *const D3D12_AUTO_BREADCRUMB_OP,
+    pNext: *const D3D12_AUTO_BREADCRUMB_NODE,
+}
+
+#[repr(C)]
+struct D3D12_DRED_AUTO_BREADCRUMBS_OUTPUT {
+    pHeadAutoBreadcrumbNode: *const D3D12_AUTO_BREADCRUMB_NODE,
+}
+
+#[repr(C)]
+struct D3D12_DRED_ALLOCATION_NODE {
+    ObjectNameA: *const c_char,
+    ObjectNameW: *const u16,
+    AllocationType: D3D12_DRED_ALLOCATION_TYPE,
+    pNext: *const D3D12_DRED_ALLOCATION_NODE,
+}
+
+#[repr(C)]
+struct D3D12_DRED_PAGE_FAULT_OUTPUT {
+    PageFaultVA: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    pHeadExistingAllocationNode: *const D3D12_DRED_ALLOCATION_NODE,
+    pHeadRecentFreedAllocationNode: *const D3D12_DRED_ALLOCATION_NODE,
+}
+
+#[repr(C)]
+struct ID3D12DeviceRemovedExtendedDataSettingsVtbl {
+    // IUnknown (3).
+    parent: [*const c_void; 3],
+    SetAutoBreadcrumbsEnablement: unsafe extern "system" fn(
+        This: *mut ID3D12DeviceRemovedExtendedDataSettings,
+        Enablement: D3D12_DRED_ENABLEMENT,
+    ),
+    SetPageFaultEnablement: unsafe extern "system" fn(
+        This: *mut ID3D12DeviceRemovedExtendedDataSettings,
+        Enablement: D3D12_DRED_ENABLEMENT,
+    ),
+    SetWatsonDumpEnablement: unsafe extern "system" fn(
+        This: *mut ID3D12DeviceRemovedExtendedDataSettings,
+        Enablement: D3D12_DRED_ENABLEMENT,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12DeviceRemovedExtendedDataSettings {
+    lpVtbl: *const ID3D12DeviceRemovedExtendedDataSettingsVtbl,
+}
+
+impl Interface for ID3D12DeviceRemovedExtendedDataSettings {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x82bc481c,
+            Data2: 0x6b9b,
+            Data3: 0x4030,
+            Data4: [0xae, 0xdb, 0x7e, 0xe3, 0xd1, 0xdf, 0x1e, 0x63],
+        }
+    }
+}
+
+#[repr(C)]
+struct ID3D12DeviceRemovedExtendedDataVtbl {
+    // IUnknown (3).
+    parent: [*const c_void; 3],
+    GetAutoBreadcrumbsOutput: unsafe extern "system" fn(
+        This: *mut ID3D12DeviceRemovedExtendedData,
+        pOutput: *mut D3D12_DRED_AUTO_BREADCRUMBS_OUTPUT,
+    ) -> HRESULT,
+    GetPageFaultAllocationOutput: unsafe extern "system" fn(
+        This: *mut ID3D12DeviceRemovedExtendedData,
+        pOutput: *mut D3D12_DRED_PAGE_FAULT_OUTPUT,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct ID3D12DeviceRemovedExtendedData {
+    lpVtbl: *const ID3D12DeviceRemovedExtendedDataVtbl,
+}
+
+impl Interface for ID3D12DeviceRemovedExtendedData {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x98931d33,
+            Data2: 0x5ae8,
+            Data3: 0x4791,
+            Data4: [0xaa, 0x3c, 0x1a, 0x73, 0xa2, 0x93, 0x4e, 0x71],
+        }
+    }
+}
+
+/// Names of `D3D12_AUTO_BREADCRUMB_OP`, indexed by value.
+const BREADCRUMB_OP_NAMES: &[&str] = &[
+    "SetMarker",
+    "BeginEvent",
+    "EndEvent",
+    "DrawInstanced",
+    "DrawIndexedInstanced",
+    "ExecuteIndirect",
+    "Dispatch",
+    "CopyBufferRegion",
+    "CopyTextureRegion",
+    "CopyResource",
+    "CopyTiles",
+    "ResolveSubresource",
+    "ClearRenderTargetView",
+    "ClearUnorderedAccessView",
+    "ClearDepthStencilView",
+    "ResourceBarrier",
+    "ExecuteBundle",
+    "Present",
+    "ResolveQueryData",
+    "BeginSubmission",
+    "EndSubmission",
+    "DecodeFrame",
+    "ProcessFrames",
+    "AtomicCopyBufferUint",
+    "AtomicCopyBufferUint64",
+    "ResolveSubresourceRegion",
+    "WriteBufferImmediate",
+    "DecodeFrame1",
+    "SetProtectedResourceSession",
+    "DecodeFrame2",
+    "ProcessFrames1",
+    "BuildRaytracingAccelerationStructure",
+    "EmitRaytracingAccelerationStructurePostbuildInfo",
+    "CopyRaytracingAccelerationStructure",
+    "DispatchRays",
+    "InitializeMetaCommand",
+    "ExecuteMetaCommand",
+    "EstimateMotion",
+    "ResolveMotionVectorHeap",
+    "SetPipelineState1",
+    "InitializeExtensionCommand",
+    "ExecuteExtensionCommand",
+    "DispatchMesh",
+];
+
+/// Messages of the debug layer that are expected, and not worth reporting.
+const IGNORED_MESSAGES: &[d3d12sdklayers::D3D12_MESSAGE_ID] = &[
+    // The clear values are only a hint for the optimized clears.
+    d3d12sdklayers::D3D12_MESSAGE_ID_CLEARRENDERTARGETVIEW_MISMATCHINGCLEARVALUE,
+    d3d12sdklayers::D3D12_MESSAGE_ID_CLEARDEPTHSTENCILVIEW_MISMATCHINGCLEARVALUE,
+];
+
+/// Enable the debug layer, along with GPU-based validation with `gpu_validation`.
+///
+/// Has to be called before creating the devices.
+pub fn enable_debug_layer(library: &native::D3D12Lib, gpu_validation: bool) {
+    match library.get_debug_interface() {
+        Ok((debug_controller, hr)) if winerror::SUCCEEDED(hr) => {
+            debug_controller.enable_layer();
+            // GPU-based validation instruments the shaders with bounds and
+            // descriptor checks, reporting the violations with the offending
+            // draw or dispatch.
+            if gpu_validation {
+                let (debug1, hr) = debug_controller.cast::<d3d12sdklayers::ID3D12Debug1>();
+                if winerror::SUCCEEDED(hr) {
+                    unsafe {
+                        debug1.SetEnableGPUBasedValidation(TRUE);
+                        debug1.destroy();
+                    }
+                } else {
+                    warn!("GPU-based validation is not supported");
+                }
+            }
+            unsafe {
+                debug_controller.Release();
+            }
+        }
+        _ => {
+            warn!("Unable to get D3D12 debug interface");
+        }
+    }
+}
+
+/// Record the breadcrumbs of the command lists and the page faults, reported by
+/// [`report_device_removed`] on device loss.
+///
+/// Has to be called before creating the devices, after loading `d3d12.dll`.
+pub fn enable_dred() {
+    unsafe {
+        let module = libloaderapi::GetModuleHandleA(b"d3d12.dll\0".as_ptr() as *const _);
+        if module.is_null() {
+            return;
+        }
+        let proc =
+            libloaderapi::GetProcAddress(module, b"D3D12GetDebugInterface\0".as_ptr() as *const _);
+        if proc.is_null() {
+            return;
+        }
+        let get_debug_interface: D3D12GetDebugInterface = mem::transmute(proc);
+
+        let mut settings: *mut ID3D12DeviceRemovedExtendedDataSettings = ptr::null_mut();
+        let hr = get_debug_interface(
+            &ID3D12DeviceRemovedExtendedDataSettings::uuidof(),
+            &mut settings as *mut *mut _ as *mut *mut c_void,
+        );
+        if !winerror::SUCCEEDED(hr) {
+            info!("Device removed extended data is not supported");
+            return;
+        }
+        let vtbl = &*(*settings).lpVtbl;
+        (vtbl.SetAutoBreadcrumbsEnablement)(settings, D3D12_DRED_ENABLEMENT_FORCED_ON);
+        (vtbl.SetPageFaultEnablement)(settings, D3D12_DRED_ENABLEMENT_FORCED_ON);
+        (*(settings as *mut IUnknown)).Release();
+    }
+}
+
+/// Filter out the messages of the info queue that aren't worth reporting.
+///
+/// The info queue is only available with the debug layer enabled.
+pub unsafe fn setup_info_queue(device: native::Device) {
+    let (info_queue, hr) = device.cast::<d3d12sdklayers::ID3D12InfoQueue>();
+    if !winerror::SUCCEEDED(hr) {
+        return;
+    }
+
+    let mut severities = [d3d12sdklayers::D3D12_MESSAGE_SEVERITY_INFO];
+    let mut ids = IGNORED_MESSAGES.to_vec();
+    let mut filter: d3d12sdklayers::D3D12_INFO_QUEUE_FILTER = mem::zeroed();
+    filter.DenyList.NumSeverities = severities.len() as UINT;
+    filter.DenyList.pSeverityList = severities.as_mut_ptr();
+    filter.DenyList.NumIDs = ids.len() as UINT;
+    filter.DenyList.pIDList = ids.as_mut_ptr();
+    let hr = info_queue.PushStorageFilter(&mut filter);
+    if !winerror::SUCCEEDED(hr) {
+        warn!("Error in PushStorageFilter: 0x{:X}", hr);
+    }
+    info_queue.destroy();
+}
+
+/// Forward the messages stored in the info queue to `log`, and clear them.
+pub unsafe fn flush_messages(device: native::Device) {
+    let (info_queue, hr) = device.cast::<d3d12sdklayers::ID3D12InfoQueue>();
+    if !winerror::SUCCEEDED(hr) {
+        return;
+    }
+
+    let mut storage = Vec::<u64>::new();
+    for index in 0..info_queue.GetNumStoredMessages() {
+        let mut size: SIZE_T = 0;
+        if !winerror::SUCCEEDED(info_queue.GetMessage(index, ptr::null_mut(), &mut size)) {
+            continue;
+        }
+        // The description is stored right after the message, keep it aligned.
+        storage.clear();
+        storage.resize((size as usize + 7) / 8, 0);
+        let message = storage.as_mut_ptr() as *mut d3d12sdklayers::D3D12_MESSAGE;
+        if !winerror::SUCCEEDED(info_queue.GetMessage(index, message, &mut size)) {
+            continue;
+        }
+
+        let message = &*message;
+        let description = CStr::from_ptr(message.pDescription).to_string_lossy();
+        let level = match message.Severity {
+            d3d12sdklayers::D3D12_MESSAGE_SEVERITY_CORRUPTION
+            | d3d12sdklayers::D3D12_MESSAGE_SEVERITY_ERROR => log::Level::Error,
+            d3d12sdklayers::D3D12_MESSAGE_SEVERITY_WARNING => log::Level::Warn,
+            d3d12sdklayers::D3D12_MESSAGE_SEVERITY_INFO => log::Level::Info,
+            _ => log::Level::Debug,
+        };
+        log!(level, "D3D12 [{}]: {}", message.ID, description);
+    }
+
+    info_queue.ClearStoredMessages();
+    info_queue.destroy();
+}
+
+/// Debug name of an object, from either its wide or narrow string.
+unsafe fn debug_name(narrow: *const c_char, wide: *const u16) -> String {
+    if !wide.is_null() {
+        let len = (0..).take_while(|&i| *wide.offset(i) != 0).count();
+        String::from_utf16_lossy(slice::from_raw_parts(wide, len))
+    } else if !narrow.is_null() {
+        CStr::from_ptr(narrow).to_string_lossy().into_owned()
+    } else {
+        String::from("<unnamed>")
+    }
+}
+
+unsafe fn report_allocations(label: &str, mut node: *const D3D12_DRED_ALLOCATION_NODE) {
+    while let Some(allocation) = node.as_ref() {
+        error!(
+            "\t{} allocation {} (type {})",
+            label,
+            debug_name(allocation.ObjectNameA, allocation.ObjectNameW),
+            allocation.AllocationType,
+        );
+        node = allocation.pNext;
+    }
+}
+
+/// Report the breadcrumbs of the command lists in flight and the page fault
+/// that led to the removal of the device, as recorded by DRED.
+pub unsafe fn report_device_removed(device: native::Device) {
+    let mut dred: *mut ID3D12DeviceRemovedExtendedData = ptr::null_mut();
+    let hr = (*(device.as_mut_ptr() as *mut IUnknown)).QueryInterface(
+        &ID3D12DeviceRemovedExtendedData::uuidof(),
+        &mut dred as *mut *mut _ as *mut *mut c_void,
+    );
+    if !winerror::SUCCEEDED(hr) {
+        return;
+    }
+    let vtbl = &*(*dred).lpVtbl;
+
+    let mut breadcrumbs: D3D12_DRED_AUTO_BREADCRUMBS_OUTPUT = mem::zeroed();
+    if winerror::SUCCEEDED((vtbl.GetAutoBreadcrumbsOutput)(dred, &mut breadcrumbs)) {
+        let mut node = breadcrumbs.pHeadAutoBreadcrumbNode;
+        while let Some(breadcrumb) = node.as_ref() {
+            // The last value is the number of operations completed by the GPU.
+            let completed = breadcrumb
+                .pLastBreadcrumbValue
+                .as_ref()
+                .map_or(0, |&value| value);
+            if completed < breadcrumb.BreadcrumbCount {
+                error!(
+                    "Command list {} on queue {} completed {} of {} operations:",
+                    debug_name(
+                        breadcrumb.pCommandListDebugNameA,
+                        breadcrumb.pCommandListDebugNameW
+                    ),
+                    debug_name(
+                        breadcrumb.pCommandQueueDebugNameA,
+                        breadcrumb.pCommandQueueDebugNameW
+                    ),
+                    completed,
+                    breadcrumb.BreadcrumbCount,
+                );
+                for i in 0..breadcrumb.BreadcrumbCount {
+                    let op = *breadcrumb.pCommandHistory.offset(i as isize);
+                    let name = BREADCRUMB_OP_NAMES
+                        .get(op as usize)
+                        .cloned()
+                        .unwrap_or("Unknown");
+                    let marker = if i == completed { " <- fault" } else { "" };
+                    error!("\t{}{}", name, marker);
+                }
+            }
+            node = breadcrumb.pNext;
+        }
+    }
+
+    let mut page_fault: D3D12_DRED_PAGE_FAULT_OUTPUT = mem::zeroed();
+    if winerror::SUCCEEDED((vtbl.GetPageFaultAllocationOutput)(dred, &mut page_fault))
+        && page_fault.PageFaultVA != 0
+    {
+        error!("Page fault at GPU address 0x{:X}", page_fault.PageFaultVA);
+        report_allocations("Existing", page_fault.pHeadExistingAllocationNode);
+        report_allocations("Freed", page_fault.pHeadRecentFreedAllocationNode);
+    }
+
+    (*(dred as *mut IUnknown)).Release();
+}
@@ -0,0 +1,89 @@
+//! Dynamic depth bias (`ID3D12GraphicsCommandList9::RSSetDepthBias`).
+//!
+//! Like the enhanced barriers, these definitions are missing from `winapi`
+//! and are declared here by hand, following `d3d12.h` of the Agility SDK.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use hal::pso;
+use winapi::{
+    ctypes::c_void,
+    shared::{guiddef::GUID, minwindef::BOOL, winerror},
+    um::{d3d12, unknwnbase::IUnknown},
+    Interface,
+};
+
+use std::{mem, ptr};
+
+const D3D12_FEATURE_D3D12_OPTIONS16: d3d12::D3D12_FEATURE = 45;
+
+/// Allows the depth bias of the pipeline to be changed with `RSSetDepthBias`.
+pub const D3D12_PIPELINE_STATE_FLAG_DYNAMIC_DEPTH_BIAS: d3d12::D3D12_PIPELINE_STATE_FLAGS = 0x4;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct D3D12_FEATURE_DATA_D3D12_OPTIONS16 {
+    DynamicDepthBiasSupported: BOOL,
+    GPUUploadHeapSupported: BOOL,
+}
+
+/// Minimal view of `ID3D12GraphicsCommandList9`: we only ever call `RSSetDepthBias`,
+/// so all the inherited methods are left opaque.
+#[repr(C)]
+struct ID3D12GraphicsCommandList9Vtbl {
+    // ID3D12GraphicsCommandList7 (81), ID3D12GraphicsCommandList8 (1).
+    parent: [*const c_void; 82],
+    RSSetDepthBias: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList9,
+        DepthBias: f32,
+        DepthBiasClamp: f32,
+        SlopeScaledDepthBias: f32,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12GraphicsCommandList9 {
+    lpVtbl: *const ID3D12GraphicsCommandList9Vtbl,
+}
+
+impl Interface for ID3D12GraphicsCommandList9 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x34ed2808,
+            Data2: 0xffe6,
+            Data3: 0x4c2b,
+            Data4: [0xb1, 0x1a, 0xca, 0xbd, 0x2b, 0x0c, 0x59, 0xe1],
+        }
+    }
+}
+
+/// Check if the device allows changing the depth bias without switching pipelines.
+pub fn is_supported(device: native::Device) -> bool {
+    let mut options = D3D12_FEATURE_DATA_D3D12_OPTIONS16::default();
+    let hr = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_D3D12_OPTIONS16,
+            &mut options as *mut _ as *mut _,
+            mem::size_of::<D3D12_FEATURE_DATA_D3D12_OPTIONS16>() as _,
+        )
+    };
+    // Older runtimes don't know about the feature at all and fail the query.
+    winerror::SUCCEEDED(hr) && options.DynamicDepthBiasSupported != 0
+}
+
+/// Record a depth bias change, for pipelines created with
+/// `D3D12_PIPELINE_STATE_FLAG_DYNAMIC_DEPTH_BIAS`.
+pub unsafe fn set(list: native::GraphicsCommandList, bias: pso::DepthBias) {
+    let mut list9: *mut ID3D12GraphicsCommandList9 = ptr::null_mut();
+    let hr = (*(list.as_mut_ptr() as *mut IUnknown)).QueryInterface(
+        &ID3D12GraphicsCommandList9::uuidof(),
+        &mut list9 as *mut *mut _ as *mut *mut c_void,
+    );
+    assert_eq!(
+        winerror::S_OK,
+        hr,
+        "Dynamic depth bias was reported, but the command list doesn't support it"
+    );
+    ((*(*list9).lpVtbl).RSSetDepthBias)(list9, bias.const_factor, bias.clamp, bias.slope_factor);
+    (*(list9 as *mut IUnknown)).Release();
+}
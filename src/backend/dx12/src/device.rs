@@ -15,16 +15,21 @@ use winapi::{
 };
 
 use auxil::{spirv_cross_specialize_ast, ShaderStage};
+use hal::external_memory::{
+    ExternalBufferMemory, ExternalImageMemory, ExternalMemoryExportError, ExternalMemoryType,
+    ExternalResourceError, Handle, PlatformMemory,
+};
 use hal::{
-    buffer, device as d, display, format, format::Aspects, image, memory, memory::Requirements,
-    pass, pool::CommandPoolCreateFlags, pso, pso::VertexInputRate, query, queue::QueueFamilyId,
-    window as w,
+    acceleration_structure as accel, buffer, device as d, display, format, format::Aspects, image,
+    memory, memory::Requirements, pass, pool::CommandPoolCreateFlags, pso, pso::VertexInputRate,
+    query, queue::QueueFamilyId, window as w,
 };
 
 use crate::{
-    command as cmd, conv, descriptors_cpu, pool::CommandPool, resource as r, root_constants,
-    root_constants::RootConstant, window::Swapchain, Backend as B, Device, MemoryGroup,
-    MAX_VERTEX_BUFFERS, NUM_HEAP_PROPERTIES, QUEUE_FAMILIES,
+    command as cmd, conv, depth_bias, descriptors_cpu, heap, mesh, pool::CommandPool, ray_tracing,
+    residency, resource as r, retire::Retired, root_constants, root_constants::RootConstant,
+    root_signature, shading_rate, window::Swapchain, Backend as B, Device, MemoryGroup,
+    MAX_VERTEX_BUFFERS, MAX_VIEW_INSTANCES, NUM_HEAP_PROPERTIES, QUEUE_FAMILIES,
 };
 use native::{PipelineStateSubobject, Subobject};
 
@@ -45,6 +50,20 @@ fn wide_cstr(name: &str) -> Vec<u16> {
     name.encode_utf16().chain(iter::once(0)).collect()
 }
 
+fn map_buffer_creation_error(err: buffer::CreationError) -> ExternalResourceError {
+    match err {
+        buffer::CreationError::OutOfMemory(oom) => oom.into(),
+        buffer::CreationError::UnsupportedUsage(_) => ExternalResourceError::InvalidExternalHandle,
+    }
+}
+
+fn map_image_creation_error(err: image::CreationError) -> ExternalResourceError {
+    match err {
+        image::CreationError::OutOfMemory(oom) => oom.into(),
+        _ => ExternalResourceError::InvalidExternalHandle,
+    }
+}
+
 /// Emit error during shader module creation. Used if we don't expect an error
 /// but might panic due to an exception in SPIRV-Cross.
 fn gen_unexpected_error(stage: ShaderStage, err: SpirvErrorCode) -> pso::CreationError {
@@ -76,6 +95,8 @@ pub(crate) struct ViewInfo {
     pub(crate) component_mapping: UINT,
     pub(crate) levels: Range<image::Level>,
     pub(crate) layers: Range<image::Layer>,
+    // Plane of a multi-planar image.
+    pub(crate) plane: UINT,
 }
 
 pub(crate) enum CommandSignature {
@@ -173,12 +194,14 @@ struct GraphicsPipelineStateSubobjectStream {
     node_mask: PipelineStateSubobject<UINT>,
     cached_pso: PipelineStateSubobject<d3d12::D3D12_CACHED_PIPELINE_STATE>,
     flags: PipelineStateSubobject<d3d12::D3D12_PIPELINE_STATE_FLAGS>,
+    view_instancing: PipelineStateSubobject<d3d12::D3D12_VIEW_INSTANCING_DESC>,
 }
 
 impl GraphicsPipelineStateSubobjectStream {
     fn new(
         pso_desc: &d3d12::D3D12_GRAPHICS_PIPELINE_STATE_DESC,
         depth_bounds_test_enable: bool,
+        view_instancing: d3d12::D3D12_VIEW_INSTANCING_DESC,
     ) -> Self {
         GraphicsPipelineStateSubobjectStream {
             root_signature: PipelineStateSubobject::new(
@@ -238,6 +261,10 @@ impl GraphicsPipelineStateSubobjectStream {
             node_mask: PipelineStateSubobject::new(Subobject::NodeMask, pso_desc.NodeMask),
             cached_pso: PipelineStateSubobject::new(Subobject::CachedPSO, pso_desc.CachedPSO),
             flags: PipelineStateSubobject::new(Subobject::Flags, pso_desc.Flags),
+            view_instancing: PipelineStateSubobject::new(
+                Subobject::ViewInstancing,
+                view_instancing,
+            ),
         }
     }
 }
@@ -537,6 +564,17 @@ impl Device {
                     .map(|src| (*src, false))
                     .ok_or(pso::CreationError::MissingEntryPoint(source.entry.into()))
             }
+            r::ShaderModule::Spirv(_)
+                if stage == ShaderStage::Task || stage == ShaderStage::Mesh =>
+            {
+                // SPIRV-Cross can't generate amplification and mesh shaders,
+                // these have to be provided as precompiled DXIL.
+                let error = format!("{:?} shaders must be precompiled", stage);
+                Err(pso::CreationError::ShaderCreationError(
+                    stage.to_flag(),
+                    error,
+                ))
+            }
             r::ShaderModule::Spirv(ref raw_data) => {
                 let mut ast = Self::parse_spirv(stage, raw_data)?;
                 spirv_cross_specialize_ast(&mut ast, &source.specialization)
@@ -574,6 +612,7 @@ impl Device {
     pub(crate) fn create_command_signature(
         device: native::Device,
         ty: CommandSignature,
+        nodes: d::NodeMask,
     ) -> native::CommandSignature {
         let (arg, stride) = match ty {
             CommandSignature::Draw => (native::IndirectArgument::draw(), 16),
@@ -582,7 +621,7 @@ impl Device {
         };
 
         let (signature, hr) =
-            device.create_command_signature(native::RootSignature::null(), &[arg], stride, 0);
+            device.create_command_signature(native::RootSignature::null(), &[arg], stride, nodes);
 
         if !winerror::SUCCEEDED(hr) {
             error!("error on command signature creation: {:x}", hr);
@@ -590,6 +629,40 @@ impl Device {
         signature
     }
 
+    /// Create the root signature described by `builder`, or share the identical
+    /// one already created, holding a new reference to it.
+    pub(crate) fn create_root_signature(
+        &self,
+        builder: &root_signature::RootSignatureBuilder,
+    ) -> Result<native::RootSignature, root_signature::CreationError> {
+        let signature_raw = builder.serialize(&self.signature_serializer)?;
+        let serialized = unsafe {
+            slice::from_raw_parts(
+                signature_raw.GetBufferPointer() as *const u8,
+                signature_raw.GetBufferSize(),
+            )
+        };
+        let mut root_signatures = self.root_signatures.lock();
+        let signature = match root_signatures.get(serialized) {
+            Some(&signature) => signature,
+            None => {
+                let (signature, hr) = self.raw.create_root_signature(signature_raw, self.nodes);
+                if !winerror::SUCCEEDED(hr) {
+                    error!("Error in CreateRootSignature: 0x{:X}", hr);
+                    unsafe { signature_raw.destroy() };
+                    return Err(d::OutOfMemory::Device.into());
+                }
+                root_signatures.insert(serialized.to_vec(), signature);
+                signature
+            }
+        };
+        unsafe {
+            signature.AddRef();
+            signature_raw.destroy();
+        }
+        Ok(signature)
+    }
+
     pub(crate) fn create_descriptor_heap_impl(
         device: native::Device,
         heap_type: native::DescriptorHeapType,
@@ -677,7 +750,7 @@ impl Device {
                 desc.ViewDimension = d3d12::D3D12_RTV_DIMENSION_TEXTURE2D;
                 *unsafe { desc.u.Texture2D_mut() } = d3d12::D3D12_TEX2D_RTV {
                     MipSlice,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                 }
             }
             image::ViewKind::D2Array if is_msaa => {
@@ -693,7 +766,7 @@ impl Device {
                     MipSlice,
                     FirstArraySlice,
                     ArraySize,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                 }
             }
             image::ViewKind::D3 => {
@@ -712,7 +785,7 @@ impl Device {
                     MipSlice,
                     FirstArraySlice,
                     ArraySize,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                 }
             }
         };
@@ -882,7 +955,7 @@ impl Device {
                 *unsafe { desc.u.Texture2D_mut() } = d3d12::D3D12_TEX2D_SRV {
                     MostDetailedMip,
                     MipLevels,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                     ResourceMinLODClamp: 0.0,
                 }
             }
@@ -900,7 +973,7 @@ impl Device {
                     MipLevels,
                     FirstArraySlice,
                     ArraySize,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                     ResourceMinLODClamp: 0.0,
                 }
             }
@@ -1008,7 +1081,7 @@ impl Device {
                 desc.ViewDimension = d3d12::D3D12_UAV_DIMENSION_TEXTURE2D;
                 *unsafe { desc.u.Texture2D_mut() } = d3d12::D3D12_TEX2D_UAV {
                     MipSlice,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                 }
             }
             image::ViewKind::D2Array => {
@@ -1017,7 +1090,7 @@ impl Device {
                     MipSlice,
                     FirstArraySlice,
                     ArraySize,
-                    PlaneSlice: 0, //TODO
+                    PlaneSlice: info.plane,
                 }
             }
             image::ViewKind::D3 => {
@@ -1132,7 +1205,7 @@ impl Device {
         config: &w::SwapchainConfig,
     ) -> Swapchain {
         let waitable = unsafe {
-            inner.SetMaximumFrameLatency(config.image_count);
+            inner.SetMaximumFrameLatency(config.frame_latency.unwrap_or(config.image_count));
             inner.GetFrameLatencyWaitableObject()
         };
 
@@ -1165,6 +1238,7 @@ impl Device {
             waitable,
             usage: config.image_usage,
             acquired_count: 0,
+            frame_waited: false,
         }
     }
 
@@ -1193,6 +1267,7 @@ impl Device {
             component_mapping: IDENTITY_MAPPING,
             levels: 0..1,
             layers: 0..0,
+            plane: 0,
         };
         let format_properties = self
             .format_properties
@@ -1217,6 +1292,7 @@ impl Device {
             place,
             surface_type: image_unbound.format.base_format().0,
             kind: image_unbound.kind,
+            base_level: image_unbound.base_level,
             mip_levels: image_unbound.mip_levels,
             default_view_format: image_unbound.view_format,
             view_caps: image_unbound.view_caps,
@@ -1269,15 +1345,262 @@ impl Device {
             requirements: image_unbound.requirements,
         });
     }
-}
 
-impl d::Device<B> for Device {
-    unsafe fn allocate_memory(
+    /// Record the copies writing the descriptors of `op` into the accumulator.
+    unsafe fn accumulate_descriptor_write<'a, I>(
         &self,
-        mem_type: hal::MemoryTypeId,
+        updater: &mut descriptors_cpu::DescriptorUpdater,
+        accum: &mut descriptors_cpu::MultiCopyAccumulator,
+        op: pso::DescriptorSetWrite<'a, B, I>,
+    ) where
+        I: Iterator<Item = pso::Descriptor<'a, B>>,
+    {
+        debug!("write_descriptor_set");
+
+        let mut offset = op.array_offset as u64;
+        let mut target_binding = op.binding as usize;
+        let base_sampler_offset = op.set.sampler_offset(op.binding, op.array_offset);
+        trace!("\tsampler offset {}", base_sampler_offset);
+        let mut sampler_offset = base_sampler_offset;
+        debug!("\tbinding {} array offset {}", target_binding, offset);
+
+        for descriptor in op.descriptors {
+            // spill over the writes onto the next binding
+            while offset >= op.set.binding_infos[target_binding].count {
+                target_binding += 1;
+                offset = 0;
+            }
+            let bind_info = &mut op.set.binding_infos[target_binding];
+            let mut src_cbv = None;
+            let mut src_srv = None;
+            let mut src_uav = None;
+
+            match descriptor {
+                pso::Descriptor::Buffer(buffer, ref sub) => {
+                    let buffer = buffer.expect_bound();
+
+                    if bind_info.content.is_dynamic() {
+                        // Root Descriptor
+                        let buffer_address = (*buffer.resource).GetGPUVirtualAddress();
+                        // Descriptor sets need to be externally synchronized according to specification
+                        bind_info.dynamic_descriptors[offset as usize].gpu_buffer_location =
+                            buffer_address + sub.offset;
+                    } else if let Err(err) = buffer::cast::<u32>(
+                        sub.size_to(buffer.requirements.size)
+                            + d3d12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT as u64,
+                    ) {
+                        // View sizes are UINTs, including the aligned size of the CBV.
+                        error!("Unable to write buffer descriptor: {}", err);
+                    } else {
+                        // Descriptor table
+                        let size = sub.size_to(buffer.requirements.size);
+
+                        if bind_info.content.contains(r::DescriptorContent::CBV) {
+                            // Making the size field of buffer requirements for uniform
+                            // buffers a multiple of 256 and setting the required offset
+                            // alignment to 256 allows us to patch the size here.
+                            // We can always enforce the size to be aligned to 256 for
+                            // CBVs without going out-of-bounds.
+                            let mask = d3d12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT - 1;
+                            let desc = d3d12::D3D12_CONSTANT_BUFFER_VIEW_DESC {
+                                BufferLocation: (*buffer.resource).GetGPUVirtualAddress()
+                                    + sub.offset,
+                                SizeInBytes: (size as u32 + mask) as u32 & !mask,
+                            };
+                            let handle = updater.alloc_handle(self.raw);
+                            self.raw.CreateConstantBufferView(&desc, handle);
+                            src_cbv = Some(handle);
+                        }
+                        if bind_info.content.contains(r::DescriptorContent::SRV) {
+                            assert_eq!(size % 4, 0);
+                            let mut desc = d3d12::D3D12_SHADER_RESOURCE_VIEW_DESC {
+                                Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
+                                Shader4ComponentMapping: IDENTITY_MAPPING,
+                                ViewDimension: d3d12::D3D12_SRV_DIMENSION_BUFFER,
+                                u: mem::zeroed(),
+                            };
+                            *desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_SRV {
+                                FirstElement: sub.offset as _,
+                                NumElements: (size / 4) as _,
+                                StructureByteStride: 0,
+                                Flags: d3d12::D3D12_BUFFER_SRV_FLAG_RAW,
+                            };
+                            let handle = updater.alloc_handle(self.raw);
+                            self.raw.CreateShaderResourceView(
+                                buffer.resource.as_mut_ptr(),
+                                &desc,
+                                handle,
+                            );
+                            src_srv = Some(handle);
+                        }
+                        if bind_info.content.contains(r::DescriptorContent::UAV) {
+                            assert_eq!(size % 4, 0);
+                            let mut desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
+                                Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
+                                ViewDimension: d3d12::D3D12_UAV_DIMENSION_BUFFER,
+                                u: mem::zeroed(),
+                            };
+                            *desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
+                                FirstElement: sub.offset as _,
+                                NumElements: (size / 4) as _,
+                                StructureByteStride: 0,
+                                CounterOffsetInBytes: 0,
+                                Flags: d3d12::D3D12_BUFFER_UAV_FLAG_RAW,
+                            };
+                            let handle = updater.alloc_handle(self.raw);
+                            self.raw.CreateUnorderedAccessView(
+                                buffer.resource.as_mut_ptr(),
+                                ptr::null_mut(),
+                                &desc,
+                                handle,
+                            );
+                            src_uav = Some(handle);
+                        }
+                    }
+                }
+                pso::Descriptor::Image(image, _layout) => {
+                    if bind_info.content.contains(r::DescriptorContent::SRV) {
+                        src_srv = image.handle_srv.map(|h| h.raw);
+                    }
+                    if bind_info.content.contains(r::DescriptorContent::UAV) {
+                        src_uav = image.handle_uav.map(|h| h.raw);
+                    }
+                }
+                pso::Descriptor::CombinedImageSampler(image, _layout, sampler) => {
+                    src_srv = image.handle_srv.map(|h| h.raw);
+                    op.set.sampler_origins[sampler_offset] = sampler.handle.raw;
+                    sampler_offset += 1;
+                }
+                pso::Descriptor::Sampler(sampler) => {
+                    op.set.sampler_origins[sampler_offset] = sampler.handle.raw;
+                    sampler_offset += 1;
+                }
+                pso::Descriptor::TexelBuffer(buffer_view) => {
+                    if bind_info.content.contains(r::DescriptorContent::SRV) {
+                        let handle = buffer_view.handle_srv
+                            .expect("SRV handle of the storage texel buffer is zero (not supported by specified format)");
+                        src_srv = Some(handle.raw);
+                    }
+                    if bind_info.content.contains(r::DescriptorContent::UAV) {
+                        let handle = buffer_view.handle_uav
+                            .expect("UAV handle of the storage texel buffer is zero (not supported by specified format)");
+                        src_uav = Some(handle.raw);
+                    }
+                }
+                pso::Descriptor::AccelerationStructure(accel_struct) => {
+                    if bind_info.content.contains(r::DescriptorContent::SRV) {
+                        let handle = updater.alloc_handle(self.raw);
+                        ray_tracing::create_view(self.raw, accel_struct.location, handle);
+                        src_srv = Some(handle);
+                    }
+                }
+            }
+
+            if let Some(handle) = src_cbv {
+                trace!("\tcbv offset {}", offset);
+                accum.src_views.add(handle, 1);
+                accum
+                    .dst_views
+                    .add(bind_info.view_range.as_ref().unwrap().at(offset), 1);
+            }
+            if let Some(handle) = src_srv {
+                trace!("\tsrv offset {}", offset);
+                accum.src_views.add(handle, 1);
+                accum
+                    .dst_views
+                    .add(bind_info.view_range.as_ref().unwrap().at(offset), 1);
+            }
+            if let Some(handle) = src_uav {
+                let uav_offset = if bind_info.content.contains(r::DescriptorContent::SRV) {
+                    bind_info.count + offset
+                } else {
+                    offset
+                };
+                trace!("\tuav offset {}", uav_offset);
+                accum.src_views.add(handle, 1);
+                accum
+                    .dst_views
+                    .add(bind_info.view_range.as_ref().unwrap().at(uav_offset), 1);
+            }
+
+            offset += 1;
+        }
+
+        if sampler_offset != base_sampler_offset {
+            op.set
+                .update_samplers(&self.samplers.heap, &self.samplers.origins, accum);
+        }
+    }
+
+    /// Record the copies of the descriptors of `op` into the accumulator.
+    unsafe fn accumulate_descriptor_copy(
+        &self,
+        accum: &mut descriptors_cpu::MultiCopyAccumulator,
+        op: pso::DescriptorSetCopy<B>,
+    ) {
+        let src_info = &op.src_set.binding_infos[op.src_binding as usize];
+        let dst_info = &op.dst_set.binding_infos[op.dst_binding as usize];
+
+        if let (Some(src_range), Some(dst_range)) =
+            (src_info.view_range.as_ref(), dst_info.view_range.as_ref())
+        {
+            assert!(op.src_array_offset + op.count <= src_range.handle.size as usize);
+            assert!(op.dst_array_offset + op.count <= dst_range.handle.size as usize);
+            let count = op.count as u32;
+            accum
+                .src_views
+                .add(src_range.at(op.src_array_offset as _), count);
+            accum
+                .dst_views
+                .add(dst_range.at(op.dst_array_offset as _), count);
+
+            if (src_info.content & dst_info.content)
+                .contains(r::DescriptorContent::SRV | r::DescriptorContent::UAV)
+            {
+                assert!(
+                    src_info.count as usize + op.src_array_offset + op.count
+                        <= src_range.handle.size as usize
+                );
+                assert!(
+                    dst_info.count as usize + op.dst_array_offset + op.count
+                        <= dst_range.handle.size as usize
+                );
+                accum.src_views.add(
+                    src_range.at(src_info.count + op.src_array_offset as u64),
+                    count,
+                );
+                accum.dst_views.add(
+                    dst_range.at(dst_info.count + op.dst_array_offset as u64),
+                    count,
+                );
+            }
+        }
+
+        if dst_info.content.contains(r::DescriptorContent::SAMPLER) {
+            let src_offset = op
+                .src_set
+                .sampler_offset(op.src_binding, op.src_array_offset);
+            let dst_offset = op
+                .dst_set
+                .sampler_offset(op.dst_binding, op.dst_array_offset);
+            op.dst_set.sampler_origins[dst_offset..dst_offset + op.count]
+                .copy_from_slice(&op.src_set.sampler_origins[src_offset..src_offset + op.count]);
+
+            op.dst_set
+                .update_samplers(&self.samplers.heap, &self.samplers.origins, accum);
+        }
+    }
+
+    /// Allocate a heap of the memory type `mem_type`, created on `node` and visible from
+    /// the `visible_nodes`, with `extra_flags` added to the flags of the memory group.
+    unsafe fn allocate_heap(
+        &self,
+        mem_type: usize,
         size: u64,
+        node: u32,
+        visible_nodes: d::NodeMask,
+        extra_flags: d3d12::D3D12_HEAP_FLAGS,
     ) -> Result<r::Memory, d::AllocationError> {
-        let mem_type = mem_type.0;
         let mem_base_id = mem_type % NUM_HEAP_PROPERTIES;
         let heap_property = &self.heap_properties[mem_base_id];
 
@@ -1285,8 +1608,8 @@ impl d::Device<B> for Device {
             Type: d3d12::D3D12_HEAP_TYPE_CUSTOM,
             CPUPageProperty: heap_property.page_property,
             MemoryPoolPreference: heap_property.memory_pool,
-            CreationNodeMask: 0,
-            VisibleNodeMask: 0,
+            CreationNodeMask: 1 << node,
+            VisibleNodeMask: visible_nodes,
         };
 
         // Exposed memory types are grouped according to their capabilities.
@@ -1303,14 +1626,10 @@ impl d::Device<B> for Device {
                 2 => d3d12::D3D12_HEAP_FLAG_ALLOW_ONLY_NON_RT_DS_TEXTURES,
                 3 => d3d12::D3D12_HEAP_FLAG_ALLOW_ONLY_RT_DS_TEXTURES,
                 _ => unreachable!(),
-            },
+            } | extra_flags,
         };
 
-        let mut heap = native::Heap::null();
-        let hr = self
-            .raw
-            .clone()
-            .CreateHeap(&desc, &d3d12::ID3D12Heap::uuidof(), heap.mut_void());
+        let (heap, hr) = heap::create_heap(self.raw, &desc);
         if hr != winerror::S_OK {
             if hr != winerror::E_OUTOFMEMORY {
                 error!("Error in CreateHeap: 0x{:X}", hr);
@@ -1328,35 +1647,22 @@ impl d::Device<B> for Device {
 
         // Create a buffer resource covering the whole memory slice to be able to map the whole memory.
         let resource = if is_mapable {
-            let mut resource = native::Resource::null();
-            let desc = d3d12::D3D12_RESOURCE_DESC {
-                Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
-                Alignment: 0,
-                Width: size,
-                Height: 1,
-                DepthOrArraySize: 1,
-                MipLevels: 1,
-                Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
-                SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
-                    Count: 1,
-                    Quality: 0,
-                },
-                Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-                Flags: d3d12::D3D12_RESOURCE_FLAG_NONE,
-            };
-
-            assert_eq!(
-                winerror::S_OK,
-                self.raw.clone().CreatePlacedResource(
-                    heap.as_mut_ptr(),
-                    0,
-                    &desc,
-                    d3d12::D3D12_RESOURCE_STATE_COMMON,
-                    ptr::null(),
-                    &d3d12::ID3D12Resource::uuidof(),
-                    resource.mut_void(),
-                )
-            );
+            let desc = heap::buffer_desc(size, d3d12::D3D12_RESOURCE_FLAG_NONE);
+            let (resource, hr) = heap::create_placed_resource(
+                self.raw,
+                heap,
+                0,
+                &desc,
+                d3d12::D3D12_RESOURCE_STATE_COMMON,
+                None,
+            );
+            if hr != winerror::S_OK {
+                if hr != winerror::E_OUTOFMEMORY {
+                    error!("Error in CreatePlacedResource: 0x{:X}", hr);
+                }
+                heap.destroy();
+                return Err(d::OutOfMemory::Device.into());
+            }
 
             Some(resource)
         } else {
@@ -1371,15 +1677,112 @@ impl d::Device<B> for Device {
         })
     }
 
+    /// Pick a memory type for a shared heap among the ones of `type_mask`.
+    ///
+    /// Shared heaps can't be accessed by the host, so only the default heap of
+    /// each memory group can be exported or imported.
+    fn shared_heap_type(type_mask: u32) -> Result<usize, ExternalResourceError> {
+        (0..32)
+            .find(|&id| type_mask & (1 << id) != 0 && id % NUM_HEAP_PROPERTIES == 0)
+            .ok_or(ExternalResourceError::NoValidMemoryTypeId)
+    }
+
+    /// Allocate a heap which can be exported with `CreateSharedHandle`.
+    unsafe fn allocate_shared_heap(
+        &self,
+        external_memory_type: ExternalMemoryType,
+        type_id: usize,
+        size: u64,
+    ) -> Result<r::Memory, ExternalResourceError> {
+        match external_memory_type {
+            ExternalMemoryType::OpaqueWin32 | ExternalMemoryType::D3D12Heap => {}
+            _ => return Err(ExternalResourceError::InvalidExternalHandle),
+        }
+        self.allocate_heap(type_id, size, 0, 1, d3d12::D3D12_HEAP_FLAG_SHARED)
+            .map_err(|err| match err {
+                d::AllocationError::OutOfMemory(oom) => oom.into(),
+                d::AllocationError::TooManyObjects => ExternalResourceError::TooManyObjects,
+            })
+    }
+
+    /// Open an object shared by another device or API.
+    ///
+    /// Only NT handles can be opened, the handle stays owned by the caller.
+    unsafe fn open_shared_handle<T: Interface>(
+        &self,
+        handle: &Handle,
+    ) -> Result<native::WeakPtr<T>, ExternalResourceError> {
+        let mut object = native::WeakPtr::<T>::null();
+        let hr =
+            self.raw
+                .OpenSharedHandle(**handle as winnt::HANDLE, &T::uuidof(), object.mut_void());
+        match hr {
+            winerror::S_OK => Ok(object),
+            winerror::E_OUTOFMEMORY => Err(d::OutOfMemory::Host.into()),
+            _ => {
+                error!("Error in OpenSharedHandle: 0x{:X}", hr);
+                Err(ExternalResourceError::InvalidExternalHandle)
+            }
+        }
+    }
+
+    /// Open a heap shared by another device or API.
+    unsafe fn open_shared_heap(
+        &self,
+        handle: &Handle,
+        type_id: usize,
+        size: u64,
+    ) -> Result<r::Memory, ExternalResourceError> {
+        // The size of the heap isn't queried, the resource placed at its start
+        // is trusted to fit in it.
+        Ok(r::Memory {
+            heap: self.open_shared_handle(handle)?,
+            type_id,
+            size,
+            resource: None,
+        })
+    }
+}
+
+impl d::Device<B> for Device {
+    unsafe fn allocate_memory(
+        &self,
+        mem_type: hal::MemoryTypeId,
+        size: u64,
+    ) -> Result<r::Memory, d::AllocationError> {
+        self.allocate_node_memory(mem_type, size, 0, 1)
+    }
+
+    unsafe fn allocate_node_memory(
+        &self,
+        mem_type: hal::MemoryTypeId,
+        size: u64,
+        node: u32,
+        visible_nodes: d::NodeMask,
+    ) -> Result<r::Memory, d::AllocationError> {
+        self.allocate_heap(mem_type.0, size, node, visible_nodes, 0)
+    }
+
     unsafe fn create_command_pool(
         &self,
         family: QueueFamilyId,
         create_flags: CommandPoolCreateFlags,
+    ) -> Result<CommandPool, d::OutOfMemory> {
+        self.create_node_command_pool(family, create_flags, 0)
+    }
+
+    unsafe fn create_node_command_pool(
+        &self,
+        family: QueueFamilyId,
+        create_flags: CommandPoolCreateFlags,
+        node: u32,
     ) -> Result<CommandPool, d::OutOfMemory> {
         let list_type = QUEUE_FAMILIES[family.0].native_type();
         Ok(CommandPool::new(
             self.raw,
             list_type,
+            family,
+            1 << node,
             &self.shared,
             create_flags,
         ))
@@ -1511,6 +1914,11 @@ impl d::Device<B> for Device {
                 let old = mem::replace(&mut att_infos[id].sub_states[sid], state);
                 debug_assert_eq!(SubState::Undefined, old);
             }
+            for &(id, _layout) in sub.shading_rate {
+                let state = SubState::New(shading_rate::D3D12_RESOURCE_STATE_SHADING_RATE_SOURCE);
+                let old = mem::replace(&mut att_infos[id].sub_states[sid], state);
+                debug_assert_eq!(SubState::Undefined, old);
+            }
             for &id in sub.preserves {
                 let old = mem::replace(&mut att_infos[id].sub_states[sid], SubState::Preserve);
                 debug_assert_eq!(SubState::Undefined, old);
@@ -1604,8 +2012,10 @@ impl d::Device<B> for Device {
                 depth_stencil_attachment: si.desc.depth_stencil.cloned(),
                 input_attachments: si.desc.inputs.iter().cloned().collect(),
                 resolve_attachments: si.desc.resolves.iter().cloned().collect(),
+                shading_rate_attachment: si.desc.shading_rate.cloned(),
                 pre_barriers,
                 post_barriers,
+                view_mask: si.desc.view_mask,
             });
         }
         // if this fails, our graph has cycles
@@ -1693,10 +2103,15 @@ impl d::Device<B> for Device {
             root_constants.len()
         );
 
-        // Number of elements in the root signature.
-        // Guarantees that no re-allocation is done, and our pointers are valid
-        let mut parameters = Vec::with_capacity(root_constants.len() + sets.len() * 2);
-        let mut parameter_offsets = Vec::with_capacity(parameters.capacity());
+        let visible_stages = sets
+            .iter()
+            .flat_map(|set| set.bindings.iter().map(|bind| bind.stage_flags))
+            .chain(root_constants.iter().map(|constant| constant.stages))
+            .fold(pso::ShaderStageFlags::empty(), |u, stages| u | stages);
+        let mut builder = root_signature::RootSignatureBuilder::new(
+            conv::map_root_signature_flags(visible_stages),
+        );
+        let mut parameter_offsets = Vec::with_capacity(root_constants.len() + sets.len() * 2);
 
         // Convert root signature descriptions into root signature parameters.
         for root_constant in root_constants.iter() {
@@ -1705,14 +2120,14 @@ impl d::Device<B> for Device {
                 ROOT_CONSTANT_SPACE, root_constant.range
             );
             parameter_offsets.push(root_constant.range.start);
-            parameters.push(native::RootParameter::constants(
+            builder.constants(
                 conv::map_shader_visibility(root_constant.stages),
                 native::Binding {
                     register: root_constant.range.start as _,
                     space: ROOT_CONSTANT_SPACE,
                 },
                 (root_constant.range.end - root_constant.range.start) as _,
-            ));
+            );
         }
 
         // Offest of `spaceN` for descriptor tables. Root constants will be in
@@ -1720,24 +2135,6 @@ impl d::Device<B> for Device {
         // This has to match `patch_spirv_resources` logic.
         let root_space_offset = if !root_constants.is_empty() { 1 } else { 0 };
 
-        // Collect the whole number of bindings we will create upfront.
-        // It allows us to preallocate enough storage to avoid reallocation,
-        // which could cause invalid pointers.
-        let total = sets
-            .iter()
-            .map(|desc_set| {
-                let mut sum = 0;
-                for binding in desc_set.bindings.iter() {
-                    let content = r::DescriptorContent::from(binding.ty);
-                    if !content.is_dynamic() {
-                        sum += content.bits().count_ones() as usize;
-                    }
-                }
-                sum
-            })
-            .sum();
-        let mut ranges = Vec::with_capacity(total);
-
         let elements = sets
             .iter()
             .enumerate()
@@ -1747,35 +2144,37 @@ impl d::Device<B> for Device {
                 let root_table_offset = root_offset;
                 log::debug!("\tSet {} space={}, root offset={}", i, space, root_offset);
 
-                //TODO: split between sampler and non-sampler tables
-                let visibility = conv::map_shader_visibility(
-                    set.bindings
-                        .iter()
-                        .fold(pso::ShaderStageFlags::empty(), |u, bind| {
-                            u | bind.stage_flags
-                        }),
-                );
+                // Tables are only visible to the stages of the bindings they contain.
+                let table_visibility = |filter: fn(r::DescriptorContent) -> bool| {
+                    conv::map_shader_visibility(
+                        set.bindings
+                            .iter()
+                            .filter(|bind| filter(r::DescriptorContent::from(bind.ty)))
+                            .fold(pso::ShaderStageFlags::empty(), |u, bind| {
+                                u | bind.stage_flags
+                            }),
+                    )
+                };
 
                 for bind in set.bindings.iter() {
                     debug!("\tRange {:?} at space={}", bind, space);
                 }
 
-                let describe = |bind: &pso::DescriptorSetLayoutBinding, ty| {
-                    native::DescriptorRange::new(
+                let describe =
+                    |bind: &pso::DescriptorSetLayoutBinding, ty| root_signature::DescriptorRange {
                         ty,
-                        bind.count as _,
-                        native::Binding {
+                        count: bind.count as _,
+                        binding: native::Binding {
                             register: bind.binding as _,
                             space,
                         },
-                        d3d12::D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
-                    )
-                };
+                        offset: d3d12::D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+                    };
 
                 let mut mutable_bindings = auxil::FastHashSet::default();
 
                 // SRV/CBV/UAV descriptor tables
-                let mut range_base = ranges.len();
+                let mut ranges = Vec::new();
                 for bind in set.bindings.iter() {
                     let content = r::DescriptorContent::from(bind.ty);
                     if !content.is_dynamic() {
@@ -1792,40 +2191,39 @@ impl d::Device<B> for Device {
                         }
                     }
                 }
-                if ranges.len() > range_base {
+                if !ranges.is_empty() {
                     parameter_offsets.push(root_offset);
                     log::debug!(
                         "\tView table with {} views at root offset {}",
-                        ranges.len() - range_base,
+                        ranges.len(),
                         root_offset
                     );
-                    parameters.push(native::RootParameter::descriptor_table(
-                        visibility,
-                        &ranges[range_base..],
-                    ));
+                    let visibility = table_visibility(|content| {
+                        !content.is_dynamic() && content.intersects(r::DescriptorContent::VIEW)
+                    });
+                    builder.descriptor_table(visibility, ranges);
                     table_type |= r::SRV_CBV_UAV;
                     root_offset += 1;
                 }
 
                 // Sampler descriptor tables
-                range_base = ranges.len();
+                let mut ranges = Vec::new();
                 for bind in set.bindings.iter() {
                     let content = r::DescriptorContent::from(bind.ty);
                     if content.contains(r::DescriptorContent::SAMPLER) {
                         ranges.push(describe(bind, native::DescriptorRangeType::Sampler));
                     }
                 }
-                if ranges.len() > range_base {
+                if !ranges.is_empty() {
                     log::debug!(
                         "\tSampler table with {} samplers at root offset {}",
-                        ranges.len() - range_base,
+                        ranges.len(),
                         root_offset
                     );
                     parameter_offsets.push(root_offset);
-                    parameters.push(native::RootParameter::descriptor_table(
-                        visibility,
-                        &ranges[range_base..],
-                    ));
+                    let visibility =
+                        table_visibility(|content| content.contains(r::DescriptorContent::SAMPLER));
+                    builder.descriptor_table(visibility, ranges);
                     table_type |= r::SAMPLERS;
                     root_offset += 1;
                 }
@@ -1838,23 +2236,33 @@ impl d::Device<B> for Device {
                             register: bind.binding as _,
                             space,
                         };
+                        let visibility = conv::map_shader_visibility(bind.stage_flags);
 
                         if content.contains(r::DescriptorContent::CBV) {
                             parameter_offsets.push(root_offset);
-                            parameters
-                                .push(native::RootParameter::cbv_descriptor(visibility, binding));
+                            builder.descriptor(
+                                visibility,
+                                native::DescriptorRangeType::CBV,
+                                binding,
+                            );
                             root_offset += 2; // root CBV costs 2 words
                         }
                         if content.contains(r::DescriptorContent::SRV) {
                             parameter_offsets.push(root_offset);
-                            parameters
-                                .push(native::RootParameter::srv_descriptor(visibility, binding));
+                            builder.descriptor(
+                                visibility,
+                                native::DescriptorRangeType::SRV,
+                                binding,
+                            );
                             root_offset += 2; // root SRV costs 2 words
                         }
                         if content.contains(r::DescriptorContent::UAV) {
                             parameter_offsets.push(root_offset);
-                            parameters
-                                .push(native::RootParameter::uav_descriptor(visibility, binding));
+                            builder.descriptor(
+                                visibility,
+                                native::DescriptorRangeType::UAV,
+                                binding,
+                            );
                             root_offset += 2; // root UAV costs 2 words
                         }
                     }
@@ -1870,34 +2278,18 @@ impl d::Device<B> for Device {
             })
             .collect();
 
-        // Ensure that we didn't reallocate!
-        debug_assert_eq!(ranges.len(), total);
-        assert_eq!(parameters.len(), parameter_offsets.len());
+        assert_eq!(builder.parameter_count(), parameter_offsets.len());
 
-        // TODO: error handling
-        let (signature_raw, error) = match self.library.serialize_root_signature(
-            native::RootSignatureVersion::V1_0,
-            &parameters,
-            &[],
-            native::RootSignatureFlags::ALLOW_IA_INPUT_LAYOUT,
-        ) {
-            Ok((pair, hr)) if winerror::SUCCEEDED(hr) => pair,
-            Ok((_, hr)) => panic!("Can't serialize root signature: {:?}", hr),
-            Err(e) => panic!("Can't find serialization function: {:?}", e),
+        let signature = match self.create_root_signature(&builder) {
+            Ok(signature) => signature,
+            Err(root_signature::CreationError::OutOfMemory(oom)) => return Err(oom),
+            // The layouts are translated by the backend, so this is a bug of ours.
+            Err(root_signature::CreationError::Invalid(message)) => {
+                error!("Invalid root signature of the pipeline layout: {}", message);
+                return Err(d::OutOfMemory::Device);
+            }
         };
 
-        if !error.is_null() {
-            error!(
-                "Root signature serialization error: {:?}",
-                error.as_c_str().to_str().unwrap()
-            );
-            error.destroy();
-        }
-
-        // TODO: error handling
-        let (signature, _hr) = self.raw.create_root_signature(signature_raw, 0);
-        signature_raw.destroy();
-
         Ok(r::PipelineLayout {
             shared: Arc::new(r::PipelineShared {
                 signature,
@@ -1935,6 +2327,12 @@ impl d::Device<B> for Device {
         desc: &pso::GraphicsPipelineDesc<'a, B>,
         _cache: Option<&()>,
     ) -> Result<r::GraphicsPipeline, pso::CreationError> {
+        let dynamic_states = desc.dynamic_states();
+        let unsupported = dynamic_states - self.private_caps.dynamic_pipeline_states();
+        if !unsupported.is_empty() {
+            return Err(pso::CreationError::UnsupportedDynamicStates(unsupported));
+        }
+
         enum ShaderBc {
             Owned(native::Blob),
             Borrowed(native::Blob),
@@ -1971,7 +2369,7 @@ impl d::Device<B> for Device {
         let vertex_buffers: Vec<pso::VertexBufferDesc> = Vec::new();
         let attributes: Vec<pso::AttributeDesc> = Vec::new();
         let mesh_input_assembler = pso::InputAssemblerDesc::new(pso::Primitive::TriangleList);
-        let (vertex_buffers, attributes, input_assembler, vs, gs, hs, ds, _, _) =
+        let (vertex_buffers, attributes, input_assembler, vs, gs, hs, ds, ts, ms) =
             match desc.primitive_assembler {
                 pso::PrimitiveAssemblerDesc::Vertex {
                     buffers,
@@ -1999,6 +2397,11 @@ impl d::Device<B> for Device {
                         None,
                     )
                 }
+                pso::PrimitiveAssemblerDesc::Mesh { .. }
+                    if !self.features.contains(hal::Features::MESH_SHADER) =>
+                {
+                    return Err(pso::CreationError::UnsupportedPipeline);
+                }
                 pso::PrimitiveAssemblerDesc::Mesh { ref task, ref mesh } => (
                     &vertex_buffers[..],
                     &attributes[..],
@@ -2027,9 +2430,11 @@ impl d::Device<B> for Device {
 
         let vs = build_shader(ShaderStage::Vertex, vs)?;
         let gs = build_shader(ShaderStage::Geometry, gs)?;
-        let hs = build_shader(ShaderStage::Domain, hs)?;
-        let ds = build_shader(ShaderStage::Hull, ds)?;
+        let hs = build_shader(ShaderStage::Hull, hs)?;
+        let ds = build_shader(ShaderStage::Domain, ds)?;
         let ps = build_shader(ShaderStage::Fragment, desc.fragment.as_ref())?;
+        let ts = build_shader(ShaderStage::Task, ts)?;
+        let ms = build_shader(ShaderStage::Mesh, ms)?;
 
         // Rebind vertex buffers, see native.rs for more details.
         let mut vertex_bindings = [None; MAX_VERTEX_BUFFERS];
@@ -2141,6 +2546,31 @@ impl d::Device<B> for Device {
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        // Captured outputs use the semantics SPIRV-Cross assigns to them.
+        let (so_entries, so_strides) = match desc.transform_feedback {
+            Some(ref tf) => {
+                let strides = tf.buffer_strides();
+                if strides.len() > d3d12::D3D12_SO_BUFFER_SLOT_COUNT as usize {
+                    error!("Too many transform feedback buffers: {}", strides.len());
+                    return Err(pso::CreationError::UnsupportedPipeline);
+                }
+                let entries = tf
+                    .varyings
+                    .iter()
+                    .map(|varying| d3d12::D3D12_SO_DECLARATION_ENTRY {
+                        Stream: 0,
+                        SemanticName: "TEXCOORD\0".as_ptr() as *const _,
+                        SemanticIndex: varying.location,
+                        StartComponent: 0,
+                        ComponentCount: varying.components,
+                        OutputSlot: varying.buffer as _,
+                    })
+                    .collect::<Vec<_>>();
+                (entries, strides)
+            }
+            None => (Vec::new(), Vec::new()),
+        };
+
         // TODO: check maximum number of rtvs
         // Get associated subpass information
         let pass = {
@@ -2151,6 +2581,15 @@ impl d::Device<B> for Device {
             }
         };
 
+        let color_count = pass.color_attachments.len().max(desc.blender.targets.len());
+        let color_limit = d3d12::D3D12_SIMULTANEOUS_RENDER_TARGET_COUNT as usize;
+        if color_count > color_limit {
+            return Err(pso::CreationError::TooManyColorAttachments {
+                count: color_count,
+                limit: color_limit,
+            });
+        }
+
         // Get color attachment formats from subpass
         let (rtvs, num_rtvs) = {
             let mut rtvs = [dxgiformat::DXGI_FORMAT_UNKNOWN;
@@ -2183,11 +2622,22 @@ impl d::Device<B> for Device {
             DS: *ds.shader(),
             HS: *hs.shader(),
             StreamOutput: d3d12::D3D12_STREAM_OUTPUT_DESC {
-                pSODeclaration: ptr::null(),
-                NumEntries: 0,
-                pBufferStrides: ptr::null(),
-                NumStrides: 0,
-                RasterizedStream: 0,
+                pSODeclaration: if so_entries.is_empty() {
+                    ptr::null()
+                } else {
+                    so_entries.as_ptr()
+                },
+                NumEntries: so_entries.len() as u32,
+                pBufferStrides: if so_strides.is_empty() {
+                    ptr::null()
+                } else {
+                    so_strides.as_ptr()
+                },
+                NumStrides: so_strides.len() as u32,
+                RasterizedStream: match desc.transform_feedback {
+                    Some(ref tf) if !tf.rasterize => d3d12::D3D12_SO_NO_RASTERIZED_STREAM,
+                    _ => 0,
+                },
             },
             BlendState: d3d12::D3D12_BLEND_DESC {
                 AlphaToCoverageEnable: desc.multisampling.as_ref().map_or(FALSE, |ms| {
@@ -2231,23 +2681,76 @@ impl d::Device<B> for Device {
                 })
                 .unwrap_or(dxgiformat::DXGI_FORMAT_UNKNOWN),
             SampleDesc: sample_desc,
-            NodeMask: 0,
+            NodeMask: self.nodes,
             CachedPSO: d3d12::D3D12_CACHED_PIPELINE_STATE {
                 pCachedBlob: ptr::null(),
                 CachedBlobSizeInBytes: 0,
             },
-            Flags: d3d12::D3D12_PIPELINE_STATE_FLAG_NONE,
+            Flags: if dynamic_states.contains(hal::DynamicStates::DEPTH_BIAS) {
+                depth_bias::D3D12_PIPELINE_STATE_FLAG_DYNAMIC_DEPTH_BIAS
+            } else {
+                d3d12::D3D12_PIPELINE_STATE_FLAG_NONE
+            },
         };
         let topology = conv::map_topology(input_assembler);
 
+        // Each view instance renders to the array layer of its bit in the view mask.
+        // `Features::MULTIVIEW` isn't reported, as shaders translated from SPIR-V target
+        // shader model 5.1 and can't read `SV_ViewID`, which requires shader model 6.1.
+        let view_mask = pass.view_mask;
+        let view_locations = (0..32)
+            .filter(|bit| view_mask & (1 << bit) != 0)
+            .map(|bit| d3d12::D3D12_VIEW_INSTANCE_LOCATION {
+                ViewportArrayIndex: 0,
+                RenderTargetArrayIndex: bit,
+            })
+            .collect::<SmallVec<[_; MAX_VIEW_INSTANCES]>>();
+        let view_instancing = d3d12::D3D12_VIEW_INSTANCING_DESC {
+            ViewInstanceCount: view_locations.len() as _,
+            pViewInstanceLocations: if view_locations.is_empty() {
+                ptr::null()
+            } else {
+                view_locations.as_ptr()
+            },
+            Flags: d3d12::D3D12_VIEW_INSTANCING_FLAG_NONE,
+        };
+
         // Create PSO
         let mut pipeline = native::PipelineState::null();
-        let hr = if desc.depth_stencil.depth_bounds {
-            // The DepthBoundsTestEnable option isn't available in the original D3D12_GRAPHICS_PIPELINE_STATE_DESC struct.
+        let hr = if let pso::PrimitiveAssemblerDesc::Mesh { .. } = desc.primitive_assembler {
+            // Mesh shading pipelines can only be described with a subobject stream.
+            let (device2, hr) = self.raw.cast::<d3d12::ID3D12Device2>();
+            if winerror::SUCCEEDED(hr) {
+                let mut pss_stream = mesh::MeshPipelineStateSubobjectStream::new(
+                    &pso_desc,
+                    *ts.shader(),
+                    *ms.shader(),
+                    desc.depth_stencil.depth_bounds,
+                    view_instancing,
+                );
+                let pss_desc = d3d12::D3D12_PIPELINE_STATE_STREAM_DESC {
+                    SizeInBytes: mem::size_of_val(&pss_stream),
+                    pPipelineStateSubobjectStream: &mut pss_stream as *mut _ as _,
+                };
+                device2.CreatePipelineState(
+                    &pss_desc,
+                    &d3d12::ID3D12PipelineState::uuidof(),
+                    pipeline.mut_void(),
+                )
+            } else {
+                hr
+            }
+        } else if desc.depth_stencil.depth_bounds || view_mask != 0 {
+            // The DepthBoundsTestEnable option and view instancing aren't available in the original
+            // D3D12_GRAPHICS_PIPELINE_STATE_DESC struct.
             // Instead, we must use the newer subobject stream method.
             let (device2, hr) = self.raw.cast::<d3d12::ID3D12Device2>();
             if winerror::SUCCEEDED(hr) {
-                let mut pss_stream = GraphicsPipelineStateSubobjectStream::new(&pso_desc, true);
+                let mut pss_stream = GraphicsPipelineStateSubobjectStream::new(
+                    &pso_desc,
+                    desc.depth_stencil.depth_bounds,
+                    view_instancing,
+                );
                 let pss_desc = d3d12::D3D12_PIPELINE_STATE_STREAM_DESC {
                     SizeInBytes: mem::size_of_val(&pss_stream),
                     pPipelineStateSubobjectStream: &mut pss_stream as *mut _ as _,
@@ -2279,6 +2782,8 @@ impl d::Device<B> for Device {
         destroy_shader(gs);
         destroy_shader(hs);
         destroy_shader(ds);
+        destroy_shader(ts);
+        destroy_shader(ms);
 
         if winerror::SUCCEEDED(hr) {
             let mut baked_states = desc.baked_states.clone();
@@ -2296,6 +2801,7 @@ impl d::Device<B> for Device {
                 topology,
                 vertex_bindings,
                 baked_states,
+                dynamic_states,
             })
         } else {
             let error = format!("Failed to build shader: {:x}", hr);
@@ -2321,7 +2827,7 @@ impl d::Device<B> for Device {
         let (pipeline, hr) = self.raw.create_compute_pipeline_state(
             desc.layout.shared.signature,
             native::Shader::from_blob(cs),
-            0,
+            self.nodes,
             native::CachedPSO::null(),
             native::PipelineStateFlags::empty(),
         );
@@ -2429,41 +2935,40 @@ impl d::Device<B> for Device {
             return Err(d::BindError::OutOfBounds);
         }
 
-        let mut resource = native::Resource::null();
-        let desc = d3d12::D3D12_RESOURCE_DESC {
-            Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
-            Alignment: 0,
-            Width: buffer_unbound.requirements.size,
-            Height: 1,
-            DepthOrArraySize: 1,
-            MipLevels: 1,
-            Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
-            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-            Flags: conv::map_buffer_flags(buffer_unbound.usage),
+        // Acceleration structures must be created in, and can never leave,
+        // their dedicated state.
+        let initial_state = if buffer_unbound
+            .usage
+            .contains(buffer::Usage::ACCELERATION_STRUCTURE_STORAGE)
+        {
+            ray_tracing::D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE
+        } else {
+            d3d12::D3D12_RESOURCE_STATE_COMMON
         };
-
-        assert_eq!(
-            winerror::S_OK,
-            self.raw.clone().CreatePlacedResource(
-                memory.heap.as_mut_ptr(),
-                offset,
-                &desc,
-                d3d12::D3D12_RESOURCE_STATE_COMMON,
-                ptr::null(),
-                &d3d12::ID3D12Resource::uuidof(),
-                resource.mut_void(),
-            )
+        let desc = heap::buffer_desc(
+            buffer_unbound.requirements.size,
+            conv::map_buffer_flags(buffer_unbound.usage),
         );
 
+        let (resource, hr) =
+            heap::create_placed_resource(self.raw, memory.heap, offset, &desc, initial_state, None);
+        if hr != winerror::S_OK {
+            if hr != winerror::E_OUTOFMEMORY {
+                error!("Error in CreatePlacedResource: 0x{:X}", hr);
+            }
+            return Err(d::OutOfMemory::Device.into());
+        }
+
         if let Some(ref name) = buffer_unbound.name {
             resource.SetName(name.as_ptr());
         }
 
         let clear_uav = if buffer_unbound.usage.contains(buffer::Usage::TRANSFER_DST) {
+            let num_elements =
+                buffer::cast(buffer_unbound.requirements.size / 4).map_err(|err| {
+                    error!("Unable to create the clear view: {}", err);
+                    d::OutOfMemory::Device
+                })?;
             let handle = self.srv_uav_pool.lock().alloc_handle();
             let mut view_desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
                 Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
@@ -2473,7 +2978,7 @@ impl d::Device<B> for Device {
 
             *view_desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
                 FirstElement: 0,
-                NumElements: (buffer_unbound.requirements.size / 4) as _,
+                NumElements: num_elements,
                 StructureByteStride: 0,
                 CounterOffsetInBytes: 0,
                 Flags: d3d12::D3D12_BUFFER_UAV_FLAG_RAW,
@@ -2493,6 +2998,7 @@ impl d::Device<B> for Device {
         *buffer = r::Buffer::Bound(r::BufferBound {
             resource,
             requirements: buffer_unbound.requirements,
+            usage: buffer_unbound.usage,
             clear_uav,
         });
 
@@ -2525,7 +3031,11 @@ impl d::Device<B> for Device {
         // Check if it adheres to the texel buffer offset limit
         assert_eq!(start % bytes_per_texel, 0);
         let first_element = start / bytes_per_texel;
-        let num_elements = size / bytes_per_texel; // rounds down to next smaller size
+        // rounds down to next smaller size
+        let num_elements: u32 = buffer::cast(size / bytes_per_texel).map_err(|err| {
+            error!("Unable to create buffer view: {}", err);
+            d::OutOfMemory::Device
+        })?;
 
         let handle_srv = if buffer_features.contains(format::BufferFeature::UNIFORM_TEXEL) {
             let mut desc = d3d12::D3D12_SHADER_RESOURCE_VIEW_DESC {
@@ -2537,7 +3047,7 @@ impl d::Device<B> for Device {
 
             *desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_SRV {
                 FirstElement: first_element,
-                NumElements: num_elements as _,
+                NumElements: num_elements,
                 StructureByteStride: bytes_per_texel as _,
                 Flags: d3d12::D3D12_BUFFER_SRV_FLAG_NONE,
             };
@@ -2564,7 +3074,7 @@ impl d::Device<B> for Device {
 
             *desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
                 FirstElement: first_element,
-                NumElements: num_elements as _,
+                NumElements: num_elements,
                 StructureByteStride: bytes_per_texel as _,
                 Flags: d3d12::D3D12_BUFFER_UAV_FLAG_NONE,
                 CounterOffsetInBytes: 0,
@@ -2588,6 +3098,10 @@ impl d::Device<B> for Device {
         });
     }
 
+    fn format_properties(&self, format: format::Format) -> format::Properties {
+        self.format_properties.resolve(format as usize).properties
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -2598,14 +3112,44 @@ impl d::Device<B> for Device {
         sparse: memory::SparseFlags,
         view_caps: image::ViewCapabilities,
     ) -> Result<r::Image, image::CreationError> {
-        assert!(mip_levels <= kind.compute_num_levels());
+        self.create_partial_image(
+            kind,
+            0..mip_levels,
+            format,
+            tiling,
+            usage,
+            sparse,
+            view_caps,
+        )
+    }
+
+    unsafe fn create_partial_image(
+        &self,
+        kind: image::Kind,
+        levels: Range<image::Level>,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+    ) -> Result<r::Image, image::CreationError> {
+        assert!(levels.start < levels.end && levels.end <= kind.compute_num_levels());
+        match kind {
+            // 3D views address the depth slices of the full extent, which
+            // a truncated resource can't provide.
+            image::Kind::D3(..) if levels.start != 0 => {
+                return Err(image::CreationError::Levels(levels));
+            }
+            _ => {}
+        }
 
         let base_format = format.base_format();
         let format_desc = base_format.0.desc();
         let bytes_per_block = (format_desc.bits / 8) as _;
         let block_dim = format_desc.dim;
         let view_format = conv::map_format(format);
-        let extent = kind.extent();
+        // The resource only holds the levels starting at `levels.start`.
+        let extent = kind.level_extent(levels.start);
 
         let format_info = self.format_properties.resolve(format as usize);
         let (layout, features) = if sparse.contains(memory::SparseFlags::SPARSE_BINDING) {
@@ -2629,36 +3173,22 @@ impl d::Device<B> for Device {
             return Err(image::CreationError::Samples(kind.num_samples()));
         }
 
-        let desc = d3d12::D3D12_RESOURCE_DESC {
-            Dimension: match kind {
-                image::Kind::D1(..) => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE1D,
-                image::Kind::D2(..) => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE2D,
-                image::Kind::D3(..) => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE3D,
-            },
-            Alignment: 0,
-            Width: extent.width as _,
-            Height: extent.height as _,
-            DepthOrArraySize: if extent.depth > 1 {
-                extent.depth as _
-            } else {
-                kind.num_layers() as _
-            },
-            MipLevels: mip_levels as _,
-            Format: if format_desc.is_compressed() {
-                view_format.unwrap()
-            } else {
-                match conv::map_surface_type(base_format.0) {
-                    Some(format) => format,
-                    None => return Err(image::CreationError::Format(format)),
-                }
-            },
-            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
-                Count: kind.num_samples() as _,
-                Quality: 0,
-            },
-            Layout: layout,
-            Flags: conv::map_image_flags(usage, features),
+        let surface_format = if format_desc.is_compressed() {
+            view_format.unwrap()
+        } else {
+            match conv::map_surface_type(base_format.0) {
+                Some(format) => format,
+                None => return Err(image::CreationError::Format(format)),
+            }
         };
+        let desc = heap::texture_desc(
+            kind,
+            extent,
+            levels.end - levels.start,
+            surface_format,
+            layout,
+            conv::map_image_flags(usage, features),
+        );
 
         let alloc_info = self.raw.clone().GetResourceAllocationInfo(0, 1, &desc);
 
@@ -2684,7 +3214,8 @@ impl d::Device<B> for Device {
             },
             format,
             kind,
-            mip_levels,
+            base_level: levels.start,
+            mip_levels: levels.end,
             usage,
             tiling,
             view_caps,
@@ -2708,15 +3239,16 @@ impl d::Device<B> for Device {
     ) -> image::SubresourceFootprint {
         let mut num_rows = 0;
         let mut total_bytes = 0;
-        let _desc = match image {
-            r::Image::Bound(i) => i.descriptor,
-            r::Image::Unbound(i) => i.desc,
+        let surface_type = match image {
+            r::Image::Bound(i) => i.surface_type,
+            r::Image::Unbound(i) => i.format.base_format().0,
         };
+        let plane = conv::map_plane_slice(surface_type, sub.aspects);
         let footprint = {
             let mut footprint = mem::zeroed();
             self.raw.GetCopyableFootprints(
                 image.get_desc(),
-                image.calc_subresource(sub.level as _, sub.layer as _, 0),
+                image.calc_subresource(sub.level as _, sub.layer as _, plane),
                 1,
                 0,
                 &mut footprint,
@@ -2755,20 +3287,20 @@ impl d::Device<B> for Device {
             return Err(d::BindError::OutOfBounds);
         }
 
-        let mut resource = native::Resource::null();
-
-        assert_eq!(
-            winerror::S_OK,
-            self.raw.clone().CreatePlacedResource(
-                memory.heap.as_mut_ptr(),
-                offset,
-                &image_unbound.desc,
-                d3d12::D3D12_RESOURCE_STATE_COMMON,
-                ptr::null(),
-                &d3d12::ID3D12Resource::uuidof(),
-                resource.mut_void(),
-            )
+        let (resource, hr) = heap::create_placed_resource(
+            self.raw,
+            memory.heap,
+            offset,
+            &image_unbound.desc,
+            d3d12::D3D12_RESOURCE_STATE_COMMON,
+            None,
         );
+        if hr != winerror::S_OK {
+            if hr != winerror::E_OUTOFMEMORY {
+                error!("Error in CreatePlacedResource: 0x{:X}", hr);
+            }
+            return Err(d::OutOfMemory::Device.into());
+        }
 
         if let Some(ref name) = image_unbound.name {
             resource.SetName(name.as_ptr());
@@ -2797,9 +3329,14 @@ impl d::Device<B> for Device {
     ) -> Result<r::ImageView, image::ViewCreationError> {
         let image = image.expect_bound();
         let is_array = image.kind.num_layers() > 1;
+        let level_end = range.level_start + range.resolve_level_count(image.mip_levels);
+        if range.level_start < image.base_level || level_end > image.mip_levels {
+            return Err(image::ViewCreationError::Level(range.level_start));
+        }
+        // Views index the levels backed by the resource.
         let mip_levels = (
-            range.level_start,
-            range.level_start + range.resolve_level_count(image.mip_levels),
+            range.level_start - image.base_level,
+            level_end - image.base_level,
         );
         let layers = (
             range.layer_start,
@@ -2823,6 +3360,7 @@ impl d::Device<B> for Device {
             component_mapping: conv::map_swizzle(swizzle),
             levels: mip_levels.0..mip_levels.1,
             layers: layers.0..layers.1,
+            plane: conv::map_plane_slice(image.surface_type, range.aspects),
         };
 
         //Note: we allow RTV/DSV/SRV/UAV views to fail to be created here,
@@ -2832,8 +3370,10 @@ impl d::Device<B> for Device {
 
         Ok(r::ImageView {
             resource: image.resource,
-            handle_srv: if usage.intersects(image::Usage::SAMPLED | image::Usage::INPUT_ATTACHMENT)
-            {
+            // Read-only storage images are bound as SRVs as well.
+            handle_srv: if usage.intersects(
+                image::Usage::SAMPLED | image::Usage::INPUT_ATTACHMENT | image::Usage::STORAGE,
+            ) {
                 let info = if range.aspects.contains(format::Aspects::DEPTH) {
                     conv::map_format_shader_depth(surface_format).map(|format| ViewInfo {
                         format,
@@ -2898,18 +3438,43 @@ impl d::Device<B> for Device {
     unsafe fn create_sampler(
         &self,
         info: &image::SamplerDesc,
-    ) -> Result<r::Sampler, d::AllocationError> {
+    ) -> Result<r::Sampler, image::SamplerCreationError> {
+        let mut unsupported = info.unsupported_fields(self.features);
         if !info.normalized {
-            warn!("Sampler with unnormalized coordinates is not supported!");
+            unsupported |= image::SamplerFields::NORMALIZED;
+        }
+        if info
+            .anisotropy_clamp
+            .map_or(false, |aniso| aniso as u32 > d3d12::D3D12_MAX_MAXANISOTROPY)
+        {
+            unsupported |= image::SamplerFields::ANISOTROPY;
+        }
+        if info.lod_bias.0 < d3d12::D3D12_MIP_LOD_BIAS_MIN
+            || info.lod_bias.0 > d3d12::D3D12_MIP_LOD_BIAS_MAX
+        {
+            unsupported |= image::SamplerFields::LOD_BIAS;
+        }
+        // Samplers are heap descriptors, even when immutable, so the border color isn't
+        // restricted to the `D3D12_STATIC_BORDER_COLOR` values of static samplers.
+        if !unsupported.is_empty() {
+            return Err(image::SamplerCreationError::Unsupported(unsupported));
         }
         let handle = match self.samplers.map.lock().entry(info.clone()) {
             Entry::Occupied(e) => *e.get(),
             Entry::Vacant(e) => {
                 let handle = self.samplers.pool.lock().alloc_handle();
                 let info = e.key();
-                let op = match info.comparison {
-                    Some(_) => d3d12::D3D12_FILTER_REDUCTION_TYPE_COMPARISON,
-                    None => d3d12::D3D12_FILTER_REDUCTION_TYPE_STANDARD,
+                let op = match (info.comparison, info.reduction_mode) {
+                    (Some(_), _) => d3d12::D3D12_FILTER_REDUCTION_TYPE_COMPARISON,
+                    (None, image::ReductionMode::WeightedAverage) => {
+                        d3d12::D3D12_FILTER_REDUCTION_TYPE_STANDARD
+                    }
+                    (None, image::ReductionMode::Minimum) => {
+                        d3d12::D3D12_FILTER_REDUCTION_TYPE_MINIMUM
+                    }
+                    (None, image::ReductionMode::Maximum) => {
+                        d3d12::D3D12_FILTER_REDUCTION_TYPE_MAXIMUM
+                    }
                 };
                 self.raw.create_sampler(
                     handle.raw,
@@ -3033,229 +3598,39 @@ impl d::Device<B> for Device {
         descriptor_updater.reset();
 
         let mut accum = descriptors_cpu::MultiCopyAccumulator::default();
-        debug!("write_descriptor_set");
+        self.accumulate_descriptor_write(&mut descriptor_updater, &mut accum, op);
+        accum.flush(self.raw);
+    }
 
-        let mut offset = op.array_offset as u64;
-        let mut target_binding = op.binding as usize;
-        let base_sampler_offset = op.set.sampler_offset(op.binding, op.array_offset);
-        trace!("\tsampler offset {}", base_sampler_offset);
-        let mut sampler_offset = base_sampler_offset;
-        debug!("\tbinding {} array offset {}", target_binding, offset);
+    unsafe fn copy_descriptor_set<'a>(&self, op: pso::DescriptorSetCopy<'a, B>) {
+        let mut accum = descriptors_cpu::MultiCopyAccumulator::default();
+        self.accumulate_descriptor_copy(&mut accum, op);
+        accum.flush(self.raw);
+    }
 
-        for descriptor in op.descriptors {
-            // spill over the writes onto the next binding
-            while offset >= op.set.binding_infos[target_binding].count {
-                target_binding += 1;
-                offset = 0;
-            }
-            let bind_info = &mut op.set.binding_infos[target_binding];
-            let mut src_cbv = None;
-            let mut src_srv = None;
-            let mut src_uav = None;
+    unsafe fn update_descriptor_sets<'a, I, J, K>(&self, writes: I, copies: K)
+    where
+        I: Iterator<Item = pso::DescriptorSetWrite<'a, B, J>>,
+        J: Iterator<Item = pso::Descriptor<'a, B>>,
+        K: Iterator<Item = pso::DescriptorSetCopy<'a, B>>,
+    {
+        let mut descriptor_updater = self.descriptor_updater.lock();
+        descriptor_updater.reset();
 
-            match descriptor {
-                pso::Descriptor::Buffer(buffer, ref sub) => {
-                    let buffer = buffer.expect_bound();
+        // All the writes end up in a single `CopyDescriptors` call per heap type.
+        let mut accum = descriptors_cpu::MultiCopyAccumulator::default();
+        for op in writes {
+            self.accumulate_descriptor_write(&mut descriptor_updater, &mut accum, op);
+        }
+        accum.flush(self.raw);
 
-                    if bind_info.content.is_dynamic() {
-                        // Root Descriptor
-                        let buffer_address = (*buffer.resource).GetGPUVirtualAddress();
-                        // Descriptor sets need to be externally synchronized according to specification
-                        bind_info.dynamic_descriptors[offset as usize].gpu_buffer_location =
-                            buffer_address + sub.offset;
-                    } else {
-                        // Descriptor table
-                        let size = sub.size_to(buffer.requirements.size);
-
-                        if bind_info.content.contains(r::DescriptorContent::CBV) {
-                            // Making the size field of buffer requirements for uniform
-                            // buffers a multiple of 256 and setting the required offset
-                            // alignment to 256 allows us to patch the size here.
-                            // We can always enforce the size to be aligned to 256 for
-                            // CBVs without going out-of-bounds.
-                            let mask = d3d12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT - 1;
-                            let desc = d3d12::D3D12_CONSTANT_BUFFER_VIEW_DESC {
-                                BufferLocation: (*buffer.resource).GetGPUVirtualAddress()
-                                    + sub.offset,
-                                SizeInBytes: (size as u32 + mask) as u32 & !mask,
-                            };
-                            let handle = descriptor_updater.alloc_handle(self.raw);
-                            self.raw.CreateConstantBufferView(&desc, handle);
-                            src_cbv = Some(handle);
-                        }
-                        if bind_info.content.contains(r::DescriptorContent::SRV) {
-                            assert_eq!(size % 4, 0);
-                            let mut desc = d3d12::D3D12_SHADER_RESOURCE_VIEW_DESC {
-                                Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
-                                Shader4ComponentMapping: IDENTITY_MAPPING,
-                                ViewDimension: d3d12::D3D12_SRV_DIMENSION_BUFFER,
-                                u: mem::zeroed(),
-                            };
-                            *desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_SRV {
-                                FirstElement: sub.offset as _,
-                                NumElements: (size / 4) as _,
-                                StructureByteStride: 0,
-                                Flags: d3d12::D3D12_BUFFER_SRV_FLAG_RAW,
-                            };
-                            let handle = descriptor_updater.alloc_handle(self.raw);
-                            self.raw.CreateShaderResourceView(
-                                buffer.resource.as_mut_ptr(),
-                                &desc,
-                                handle,
-                            );
-                            src_srv = Some(handle);
-                        }
-                        if bind_info.content.contains(r::DescriptorContent::UAV) {
-                            assert_eq!(size % 4, 0);
-                            let mut desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
-                                Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
-                                ViewDimension: d3d12::D3D12_UAV_DIMENSION_BUFFER,
-                                u: mem::zeroed(),
-                            };
-                            *desc.u.Buffer_mut() = d3d12::D3D12_BUFFER_UAV {
-                                FirstElement: sub.offset as _,
-                                NumElements: (size / 4) as _,
-                                StructureByteStride: 0,
-                                CounterOffsetInBytes: 0,
-                                Flags: d3d12::D3D12_BUFFER_UAV_FLAG_RAW,
-                            };
-                            let handle = descriptor_updater.alloc_handle(self.raw);
-                            self.raw.CreateUnorderedAccessView(
-                                buffer.resource.as_mut_ptr(),
-                                ptr::null_mut(),
-                                &desc,
-                                handle,
-                            );
-                            src_uav = Some(handle);
-                        }
-                    }
-                }
-                pso::Descriptor::Image(image, _layout) => {
-                    if bind_info.content.contains(r::DescriptorContent::SRV) {
-                        src_srv = image.handle_srv.map(|h| h.raw);
-                    }
-                    if bind_info.content.contains(r::DescriptorContent::UAV) {
-                        src_uav = image.handle_uav.map(|h| h.raw);
-                    }
-                }
-                pso::Descriptor::CombinedImageSampler(image, _layout, sampler) => {
-                    src_srv = image.handle_srv.map(|h| h.raw);
-                    op.set.sampler_origins[sampler_offset] = sampler.handle.raw;
-                    sampler_offset += 1;
-                }
-                pso::Descriptor::Sampler(sampler) => {
-                    op.set.sampler_origins[sampler_offset] = sampler.handle.raw;
-                    sampler_offset += 1;
-                }
-                pso::Descriptor::TexelBuffer(buffer_view) => {
-                    if bind_info.content.contains(r::DescriptorContent::SRV) {
-                        let handle = buffer_view.handle_srv
-                            .expect("SRV handle of the storage texel buffer is zero (not supported by specified format)");
-                        src_srv = Some(handle.raw);
-                    }
-                    if bind_info.content.contains(r::DescriptorContent::UAV) {
-                        let handle = buffer_view.handle_uav
-                            .expect("UAV handle of the storage texel buffer is zero (not supported by specified format)");
-                        src_uav = Some(handle.raw);
-                    }
-                }
-            }
-
-            if let Some(handle) = src_cbv {
-                trace!("\tcbv offset {}", offset);
-                accum.src_views.add(handle, 1);
-                accum
-                    .dst_views
-                    .add(bind_info.view_range.as_ref().unwrap().at(offset), 1);
-            }
-            if let Some(handle) = src_srv {
-                trace!("\tsrv offset {}", offset);
-                accum.src_views.add(handle, 1);
-                accum
-                    .dst_views
-                    .add(bind_info.view_range.as_ref().unwrap().at(offset), 1);
-            }
-            if let Some(handle) = src_uav {
-                let uav_offset = if bind_info.content.contains(r::DescriptorContent::SRV) {
-                    bind_info.count + offset
-                } else {
-                    offset
-                };
-                trace!("\tuav offset {}", uav_offset);
-                accum.src_views.add(handle, 1);
-                accum
-                    .dst_views
-                    .add(bind_info.view_range.as_ref().unwrap().at(uav_offset), 1);
-            }
-
-            offset += 1;
-        }
-
-        if sampler_offset != base_sampler_offset {
-            op.set
-                .update_samplers(&self.samplers.heap, &self.samplers.origins, &mut accum);
-        }
-
-        accum.flush(self.raw);
-    }
-
-    unsafe fn copy_descriptor_set<'a>(&self, op: pso::DescriptorSetCopy<'a, B>) {
-        let mut accum = descriptors_cpu::MultiCopyAccumulator::default();
-
-        let src_info = &op.src_set.binding_infos[op.src_binding as usize];
-        let dst_info = &op.dst_set.binding_infos[op.dst_binding as usize];
-
-        if let (Some(src_range), Some(dst_range)) =
-            (src_info.view_range.as_ref(), dst_info.view_range.as_ref())
-        {
-            assert!(op.src_array_offset + op.count <= src_range.handle.size as usize);
-            assert!(op.dst_array_offset + op.count <= dst_range.handle.size as usize);
-            let count = op.count as u32;
-            accum
-                .src_views
-                .add(src_range.at(op.src_array_offset as _), count);
-            accum
-                .dst_views
-                .add(dst_range.at(op.dst_array_offset as _), count);
-
-            if (src_info.content & dst_info.content)
-                .contains(r::DescriptorContent::SRV | r::DescriptorContent::UAV)
-            {
-                assert!(
-                    src_info.count as usize + op.src_array_offset + op.count
-                        <= src_range.handle.size as usize
-                );
-                assert!(
-                    dst_info.count as usize + op.dst_array_offset + op.count
-                        <= dst_range.handle.size as usize
-                );
-                accum.src_views.add(
-                    src_range.at(src_info.count + op.src_array_offset as u64),
-                    count,
-                );
-                accum.dst_views.add(
-                    dst_range.at(dst_info.count + op.dst_array_offset as u64),
-                    count,
-                );
-            }
-        }
-
-        if dst_info.content.contains(r::DescriptorContent::SAMPLER) {
-            let src_offset = op
-                .src_set
-                .sampler_offset(op.src_binding, op.src_array_offset);
-            let dst_offset = op
-                .dst_set
-                .sampler_offset(op.dst_binding, op.dst_array_offset);
-            op.dst_set.sampler_origins[dst_offset..dst_offset + op.count]
-                .copy_from_slice(&op.src_set.sampler_origins[src_offset..src_offset + op.count]);
-
-            op.dst_set
-                .update_samplers(&self.samplers.heap, &self.samplers.origins, &mut accum);
-        }
-
-        accum.flush(self.raw.clone());
-    }
+        // The copies are flushed separately, since they may read the written descriptors.
+        let mut accum = descriptors_cpu::MultiCopyAccumulator::default();
+        for op in copies {
+            self.accumulate_descriptor_copy(&mut accum, op);
+        }
+        accum.flush(self.raw);
+    }
 
     unsafe fn map_memory(
         &self,
@@ -3437,9 +3812,87 @@ impl d::Device<B> for Device {
     }
 
     unsafe fn free_memory(&self, memory: r::Memory) {
-        memory.heap.destroy();
         if let Some(buffer) = memory.resource {
-            buffer.destroy();
+            self.retirement.retire(Retired::Resource(buffer));
+        }
+        // Imported resources own their storage.
+        if !memory.heap.is_null() {
+            self.retirement.retire(Retired::Heap(memory.heap));
+        }
+    }
+
+    fn memory_budget(&self) -> Vec<memory::HeapBudget> {
+        // Same heaps as the ones reported by the physical device.
+        let segments = [
+            dxgi1_4::DXGI_MEMORY_SEGMENT_GROUP_LOCAL,
+            dxgi1_4::DXGI_MEMORY_SEGMENT_GROUP_NON_LOCAL,
+        ];
+        self.memory_heaps
+            .iter()
+            .zip(segments.iter())
+            .map(|(heap, &segment)| unsafe {
+                let mut info: dxgi1_4::DXGI_QUERY_VIDEO_MEMORY_INFO = mem::zeroed();
+                let hr = self.adapter.QueryVideoMemoryInfo(0, segment, &mut info);
+                if winerror::SUCCEEDED(hr) {
+                    memory::HeapBudget {
+                        usage: info.CurrentUsage,
+                        budget: info.Budget,
+                    }
+                } else {
+                    warn!("Unable to query the video memory: 0x{:x}", hr);
+                    memory::HeapBudget {
+                        usage: 0,
+                        budget: heap.size,
+                    }
+                }
+            })
+            .collect()
+    }
+
+    unsafe fn set_memory_priority(&self, memory: &r::Memory, priority: memory::Priority) {
+        if !memory.heap.is_null() {
+            residency::set_priority(self.raw, memory.heap, priority);
+        }
+    }
+
+    unsafe fn make_resident<'a, I>(&self, memories: I) -> Result<(), d::OutOfMemory>
+    where
+        I: Iterator<Item = &'a r::Memory>,
+    {
+        let mut objects = memories
+            .filter(|memory| !memory.heap.is_null())
+            .map(|memory| memory.heap.as_mut_ptr() as *mut d3d12::ID3D12Pageable)
+            .collect::<SmallVec<[_; 4]>>();
+        if objects.is_empty() {
+            return Ok(());
+        }
+        match self
+            .raw
+            .MakeResident(objects.len() as _, objects.as_mut_ptr())
+        {
+            winerror::S_OK => Ok(()),
+            winerror::E_OUTOFMEMORY => Err(d::OutOfMemory::Device),
+            hr => {
+                error!("Failed to make memory resident: 0x{:X}", hr);
+                Err(d::OutOfMemory::Device)
+            }
+        }
+    }
+
+    unsafe fn evict<'a, I>(&self, memories: I)
+    where
+        I: Iterator<Item = &'a r::Memory>,
+    {
+        let mut objects = memories
+            .filter(|memory| !memory.heap.is_null())
+            .map(|memory| memory.heap.as_mut_ptr() as *mut d3d12::ID3D12Pageable)
+            .collect::<SmallVec<[_; 4]>>();
+        if objects.is_empty() {
+            return;
+        }
+        let hr = self.raw.Evict(objects.len() as _, objects.as_mut_ptr());
+        if !winerror::SUCCEEDED(hr) {
+            error!("Failed to evict memory: 0x{:X}", hr);
         }
     }
 
@@ -3464,7 +3917,38 @@ impl d::Device<B> for Device {
     }
 
     unsafe fn destroy_query_pool(&self, pool: r::QueryPool) {
-        pool.raw.destroy();
+        self.retirement.retire(Retired::QueryHeap(pool.raw));
+    }
+
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        geometry: &accel::GeometryInfo<B>,
+    ) -> accel::SizeRequirements {
+        let geometries = ray_tracing::map_geometries(geometry);
+        let inputs = ray_tracing::map_inputs(geometry, &geometries, false);
+        ray_tracing::build_requirements(self.raw, &inputs)
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        desc: &accel::CreateDesc<B>,
+    ) -> Result<r::AccelerationStructure, d::OutOfMemory> {
+        // Acceleration structures aren't objects on their own in D3D12,
+        // they are only referenced by their location.
+        Ok(r::AccelerationStructure {
+            location: ray_tracing::address(&(desc.buffer, desc.offset)),
+        })
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        accel_struct: &r::AccelerationStructure,
+    ) -> accel::DeviceAddress {
+        accel_struct.location
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: r::AccelerationStructure) {
+        // Just drop
     }
 
     unsafe fn get_query_pool_results(
@@ -3477,21 +3961,7 @@ impl d::Device<B> for Device {
     ) -> Result<bool, d::WaitError> {
         let num_queries = queries.end - queries.start;
         let size = 8 * num_queries as u64;
-        let buffer_desc = d3d12::D3D12_RESOURCE_DESC {
-            Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
-            Alignment: 0,
-            Width: size,
-            Height: 1,
-            DepthOrArraySize: 1,
-            MipLevels: 1,
-            Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
-            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-            Flags: d3d12::D3D12_RESOURCE_FLAG_NONE,
-        };
+        let buffer_desc = heap::buffer_desc(size, d3d12::D3D12_RESOURCE_FLAG_NONE);
 
         let properties = d3d12::D3D12_HEAP_PROPERTIES {
             Type: d3d12::D3D12_HEAP_TYPE_READBACK,
@@ -3508,27 +3978,18 @@ impl d::Device<B> for Device {
             Flags: d3d12::D3D12_HEAP_FLAG_ALLOW_ONLY_BUFFERS,
         };
 
-        let mut heap = native::Heap::null();
-        assert_eq!(
-            self.raw
-                .clone()
-                .CreateHeap(&heap_desc, &d3d12::ID3D12Heap::uuidof(), heap.mut_void()),
-            winerror::S_OK
-        );
+        let (heap, hr) = heap::create_heap(self.raw, &heap_desc);
+        assert_eq!(winerror::S_OK, hr);
 
-        let mut temp_buffer = native::Resource::null();
-        assert_eq!(
-            winerror::S_OK,
-            self.raw.clone().CreatePlacedResource(
-                heap.as_mut_ptr(),
-                0,
-                &buffer_desc,
-                d3d12::D3D12_RESOURCE_STATE_COPY_DEST,
-                ptr::null(),
-                &d3d12::ID3D12Resource::uuidof(),
-                temp_buffer.mut_void(),
-            )
+        let (temp_buffer, hr) = heap::create_placed_resource(
+            self.raw,
+            heap,
+            0,
+            &buffer_desc,
+            d3d12::D3D12_RESOURCE_STATE_COPY_DEST,
+            None,
         );
+        assert_eq!(winerror::S_OK, hr);
 
         let list_type = native::CmdListType::Direct;
         let (com_allocator, hr_alloc) = self.raw.create_command_allocator(list_type);
@@ -3616,15 +4077,182 @@ impl d::Device<B> for Device {
     }
 
     unsafe fn destroy_pipeline_layout(&self, layout: r::PipelineLayout) {
-        layout.shared.signature.destroy();
+        self.retirement
+            .retire(Retired::RootSignature(layout.shared.signature));
     }
 
     unsafe fn destroy_graphics_pipeline(&self, pipeline: r::GraphicsPipeline) {
-        pipeline.raw.destroy();
+        self.retirement.retire(Retired::PipelineState(pipeline.raw));
     }
 
     unsafe fn destroy_compute_pipeline(&self, pipeline: r::ComputePipeline) {
-        pipeline.raw.destroy();
+        self.retirement.retire(Retired::PipelineState(pipeline.raw));
+    }
+
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        desc: &pso::RayTracingPipelineDesc<'a, B>,
+        _cache: Option<&()>,
+    ) -> Result<r::RayTracingPipeline, pso::CreationError> {
+        let mut libraries = Vec::new();
+        let mut hit_groups = Vec::new();
+        // Name of the export of each group, to query its identifier.
+        let mut group_exports = Vec::with_capacity(desc.groups.len());
+
+        fn add_library(
+            libraries: &mut Vec<ray_tracing::Library>,
+            stage: pso::ShaderStageFlags,
+            source: &pso::EntryPoint<B>,
+        ) -> Result<usize, pso::CreationError> {
+            let blob = match *source.module {
+                r::ShaderModule::Compiled(ref shaders) => *shaders
+                    .get(source.entry)
+                    .ok_or(pso::CreationError::MissingEntryPoint(source.entry.into()))?,
+                r::ShaderModule::Spirv(_) => {
+                    // SPIRV-Cross can't generate DXIL libraries, ray tracing
+                    // shaders have to be provided precompiled.
+                    let error = "Ray tracing shaders must be precompiled".to_string();
+                    return Err(pso::CreationError::ShaderCreationError(stage, error));
+                }
+            };
+            let index = libraries.len();
+            libraries.push(ray_tracing::Library {
+                bytecode: *native::Shader::from_blob(blob),
+                entry: wide_cstr(source.entry),
+                export: wide_cstr(&format!("shader{}", index)),
+            });
+            Ok(index)
+        }
+
+        for (i, group) in desc.groups.iter().enumerate() {
+            let export = match *group {
+                pso::ShaderGroup::RayGeneration(ref entry) => {
+                    let index = add_library(&mut libraries, pso::ShaderStageFlags::RAYGEN, entry)?;
+                    libraries[index].export.clone()
+                }
+                pso::ShaderGroup::Miss(ref entry) => {
+                    let index = add_library(&mut libraries, pso::ShaderStageFlags::MISS, entry)?;
+                    libraries[index].export.clone()
+                }
+                pso::ShaderGroup::Callable(ref entry) => {
+                    let index =
+                        add_library(&mut libraries, pso::ShaderStageFlags::CALLABLE, entry)?;
+                    libraries[index].export.clone()
+                }
+                pso::ShaderGroup::TrianglesHitGroup {
+                    ref closest_hit,
+                    ref any_hit,
+                } => {
+                    let hit_group = ray_tracing::HitGroup {
+                        export: wide_cstr(&format!("group{}", i)),
+                        procedural: false,
+                        closest_hit: closest_hit
+                            .as_ref()
+                            .map(|entry| {
+                                add_library(
+                                    &mut libraries,
+                                    pso::ShaderStageFlags::CLOSEST_HIT,
+                                    entry,
+                                )
+                            })
+                            .transpose()?,
+                        any_hit: any_hit
+                            .as_ref()
+                            .map(|entry| {
+                                add_library(&mut libraries, pso::ShaderStageFlags::ANY_HIT, entry)
+                            })
+                            .transpose()?,
+                        intersection: None,
+                    };
+                    let export = hit_group.export.clone();
+                    hit_groups.push(hit_group);
+                    export
+                }
+                pso::ShaderGroup::ProceduralHitGroup {
+                    ref intersection,
+                    ref closest_hit,
+                    ref any_hit,
+                } => {
+                    let hit_group = ray_tracing::HitGroup {
+                        export: wide_cstr(&format!("group{}", i)),
+                        procedural: true,
+                        closest_hit: closest_hit
+                            .as_ref()
+                            .map(|entry| {
+                                add_library(
+                                    &mut libraries,
+                                    pso::ShaderStageFlags::CLOSEST_HIT,
+                                    entry,
+                                )
+                            })
+                            .transpose()?,
+                        any_hit: any_hit
+                            .as_ref()
+                            .map(|entry| {
+                                add_library(&mut libraries, pso::ShaderStageFlags::ANY_HIT, entry)
+                            })
+                            .transpose()?,
+                        intersection: Some(add_library(
+                            &mut libraries,
+                            pso::ShaderStageFlags::INTERSECTION,
+                            intersection,
+                        )?),
+                    };
+                    let export = hit_group.export.clone();
+                    hit_groups.push(hit_group);
+                    export
+                }
+            };
+            group_exports.push(export);
+        }
+
+        let (state_object, hr) = ray_tracing::create_pipeline(
+            self.raw,
+            desc.layout.shared.signature,
+            &libraries,
+            &hit_groups,
+            desc,
+        );
+
+        if winerror::SUCCEEDED(hr) {
+            let group_handles = group_exports
+                .iter()
+                .map(|export| ray_tracing::shader_identifier(state_object, export))
+                .collect();
+
+            Ok(r::RayTracingPipeline {
+                raw: state_object,
+                shared: Arc::clone(&desc.layout.shared),
+                group_handles,
+            })
+        } else {
+            let error = format!("Failed to build shader: {:x}", hr);
+            Err(pso::CreationError::ShaderCreationError(
+                pso::ShaderStageFlags::RAY_TRACING,
+                error,
+            ))
+        }
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, pipeline: r::RayTracingPipeline) {
+        self.retirement.retire(Retired::StateObject(pipeline.raw));
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        pipeline: &r::RayTracingPipeline,
+        first_group: u32,
+        group_count: u32,
+        data: &mut [u8],
+    ) -> Result<(), d::OutOfMemory> {
+        let groups = first_group as usize..(first_group + group_count) as usize;
+        for (chunk, handle) in data
+            .chunks_exact_mut(ray_tracing::D3D12_SHADER_IDENTIFIER_SIZE_IN_BYTES)
+            .zip(pipeline.group_handles[groups].iter())
+        {
+            chunk.copy_from_slice(handle);
+        }
+        Ok(())
     }
 
     unsafe fn destroy_framebuffer(&self, _fb: r::Framebuffer) {
@@ -3637,7 +4265,7 @@ impl d::Device<B> for Device {
                 if let Some(handle) = buffer.clear_uav {
                     self.srv_uav_pool.lock().free_handle(handle);
                 }
-                buffer.resource.destroy();
+                self.retirement.retire(Retired::Resource(buffer.resource));
             }
             r::Buffer::Unbound(_) => {}
         }
@@ -3666,7 +4294,7 @@ impl d::Device<B> for Device {
                 for handle in image.clear_sv {
                     dsv_pool.free_handle(handle);
                 }
-                image.resource.destroy();
+                self.retirement.retire(Retired::Resource(image.resource));
             }
             r::Image::Unbound(_) => {}
         }
@@ -3717,10 +4345,12 @@ impl d::Device<B> for Device {
         unimplemented!()
     }
 
-    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), d::OomOrDeviceLost> {
         for queue in &self.queues {
             queue.wait_idle_impl()?;
         }
+        // Nothing references the retired objects anymore.
+        unsafe { self.retirement.release_all() };
         Ok(())
     }
 
@@ -3787,6 +4417,16 @@ impl d::Device<B> for Device {
         pipeline_layout.shared.signature.SetName(cwstr.as_ptr());
     }
 
+    unsafe fn set_graphics_pipeline_name(&self, pipeline: &mut r::GraphicsPipeline, name: &str) {
+        let cwstr = wide_cstr(name);
+        pipeline.raw.SetName(cwstr.as_ptr());
+    }
+
+    unsafe fn set_compute_pipeline_name(&self, pipeline: &mut r::ComputePipeline, name: &str) {
+        let cwstr = wide_cstr(name);
+        pipeline.raw.SetName(cwstr.as_ptr());
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &display::Display<B>,
@@ -3814,63 +4454,150 @@ impl d::Device<B> for Device {
 
     unsafe fn create_allocate_external_buffer(
         &self,
-        _external_memory_type: hal::external_memory::ExternalBufferMemoryType,
-        _usage: hal::buffer::Usage,
-        _sparse: hal::memory::SparseFlags,
-        _type_mask: u32,
-        _size: u64,
-    ) -> Result<(r::Buffer, r::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        external_memory_type: hal::external_memory::ExternalBufferMemoryType,
+        usage: hal::buffer::Usage,
+        sparse: hal::memory::SparseFlags,
+        type_mask: u32,
+        size: u64,
+    ) -> Result<(r::Buffer, r::Memory), ExternalResourceError> {
+        let mut buffer = self
+            .create_buffer(size, usage, sparse)
+            .map_err(map_buffer_creation_error)?;
+        let requirements = self.get_buffer_requirements(&buffer);
+        let type_id = Self::shared_heap_type(type_mask & requirements.type_mask)?;
+        let memory = self.allocate_shared_heap(external_memory_type, type_id, requirements.size)?;
+        // The memory type and the size are taken from the requirements of the buffer.
+        self.bind_buffer_memory(&memory, 0, &mut buffer).unwrap();
+        Ok((buffer, memory))
     }
 
     unsafe fn import_external_buffer(
         &self,
-        _external_memory: hal::external_memory::ExternalBufferMemory,
-        _usage: hal::buffer::Usage,
-        _sparse: hal::memory::SparseFlags,
-        _type_mask: u32,
-        _size: u64,
-    ) -> Result<(r::Buffer, r::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        external_memory: ExternalBufferMemory,
+        usage: hal::buffer::Usage,
+        sparse: hal::memory::SparseFlags,
+        type_mask: u32,
+        size: u64,
+    ) -> Result<(r::Buffer, r::Memory), ExternalResourceError> {
+        let handle = match external_memory {
+            ExternalBufferMemory::OpaqueWin32(ref handle)
+            | ExternalBufferMemory::D3D12Heap(ref handle) => handle,
+            _ => return Err(ExternalResourceError::InvalidExternalHandle),
+        };
+        let mut buffer = self
+            .create_buffer(size, usage, sparse)
+            .map_err(map_buffer_creation_error)?;
+        let requirements = self.get_buffer_requirements(&buffer);
+        let type_id = Self::shared_heap_type(type_mask & requirements.type_mask)?;
+        let memory = self.open_shared_heap(handle, type_id, requirements.size)?;
+        self.bind_buffer_memory(&memory, 0, &mut buffer).unwrap();
+        Ok((buffer, memory))
     }
 
     unsafe fn create_allocate_external_image(
         &self,
-        _external_memory_type: hal::external_memory::ExternalImageMemoryType,
-        _kind: hal::image::Kind,
-        _mip_levels: hal::image::Level,
-        _format: hal::format::Format,
-        _tiling: hal::image::Tiling,
-        _usage: hal::image::Usage,
-        _sparse: hal::memory::SparseFlags,
-        _view_caps: hal::image::ViewCapabilities,
-        _type_mask: u32,
-    ) -> Result<(r::Image, r::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        external_memory_type: hal::external_memory::ExternalImageMemoryType,
+        kind: hal::image::Kind,
+        mip_levels: hal::image::Level,
+        format: hal::format::Format,
+        tiling: hal::image::Tiling,
+        usage: hal::image::Usage,
+        sparse: hal::memory::SparseFlags,
+        view_caps: hal::image::ViewCapabilities,
+        type_mask: u32,
+    ) -> Result<(r::Image, r::Memory), ExternalResourceError> {
+        if tiling == image::Tiling::Linear {
+            return Err(ExternalResourceError::InvalidExternalHandle);
+        }
+        let mut image = self
+            .create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)
+            .map_err(map_image_creation_error)?;
+        let requirements = self.get_image_requirements(&image);
+        let type_id = Self::shared_heap_type(type_mask & requirements.type_mask)?;
+        let memory = self.allocate_shared_heap(
+            external_memory_type.external_memory_type(),
+            type_id,
+            requirements.size,
+        )?;
+        self.bind_image_memory(&memory, 0, &mut image).unwrap();
+        Ok((image, memory))
     }
 
     unsafe fn import_external_image(
         &self,
-        _external_memory: hal::external_memory::ExternalImageMemory,
-        _kind: hal::image::Kind,
-        _mip_levels: hal::image::Level,
-        _format: hal::format::Format,
-        _tiling: hal::image::Tiling,
-        _usage: hal::image::Usage,
-        _sparse: hal::memory::SparseFlags,
-        _view_caps: hal::image::ViewCapabilities,
-        _type_mask: u32,
-    ) -> Result<(r::Image, r::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        external_memory: ExternalImageMemory,
+        kind: hal::image::Kind,
+        mip_levels: hal::image::Level,
+        format: hal::format::Format,
+        tiling: hal::image::Tiling,
+        usage: hal::image::Usage,
+        sparse: hal::memory::SparseFlags,
+        view_caps: hal::image::ViewCapabilities,
+        type_mask: u32,
+        _size: u64,
+    ) -> Result<(r::Image, r::Memory), ExternalResourceError> {
+        if tiling == image::Tiling::Linear {
+            return Err(ExternalResourceError::InvalidExternalHandle);
+        }
+        let mut image = self
+            .create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)
+            .map_err(map_image_creation_error)?;
+        let requirements = self.get_image_requirements(&image);
+        let type_id = Self::shared_heap_type(type_mask & requirements.type_mask)?;
+        let memory = match external_memory {
+            ExternalImageMemory::OpaqueWin32(ref handle)
+            | ExternalImageMemory::D3D12Heap(ref handle) => {
+                let memory = self.open_shared_heap(handle, type_id, requirements.size)?;
+                self.bind_image_memory(&memory, 0, &mut image).unwrap();
+                memory
+            }
+            // Textures shared by Direct3D 11, such as the frames of a hardware video
+            // decoder, are committed resources with no heap to place the image in.
+            ExternalImageMemory::D3D11Texture(ref handle)
+            | ExternalImageMemory::D3D12Resource(ref handle) => {
+                let resource = self.open_shared_handle(handle)?;
+                self.bind_image_resource(resource, &mut image, r::Place::Dedicated {});
+                r::Memory {
+                    heap: native::Heap::null(),
+                    type_id,
+                    size: requirements.size,
+                    resource: None,
+                }
+            }
+            _ => return Err(ExternalResourceError::InvalidExternalHandle),
+        };
+        Ok((image, memory))
     }
 
     unsafe fn export_memory(
         &self,
-        _external_memory_type: hal::external_memory::ExternalMemoryType,
-        _memory: &r::Memory,
-    ) -> Result<hal::external_memory::PlatformMemory, hal::external_memory::ExternalMemoryExportError>
-    {
-        unimplemented!()
+        external_memory_type: ExternalMemoryType,
+        memory: &r::Memory,
+    ) -> Result<PlatformMemory, ExternalMemoryExportError> {
+        match external_memory_type {
+            ExternalMemoryType::OpaqueWin32 | ExternalMemoryType::D3D12Heap => {}
+            _ => return Err(ExternalMemoryExportError::InvalidExternalHandle),
+        }
+        if memory.heap.is_null() {
+            return Err(ExternalMemoryExportError::InvalidExternalHandle);
+        }
+        // Fails on the heaps allocated without `D3D12_HEAP_FLAG_SHARED`.
+        let mut handle = ptr::null_mut();
+        let hr = self.raw.CreateSharedHandle(
+            memory.heap.as_mut_ptr() as *mut d3d12::ID3D12DeviceChild,
+            ptr::null(),
+            winnt::GENERIC_ALL,
+            ptr::null(),
+            &mut handle,
+        );
+        match hr {
+            winerror::S_OK => Ok(Handle::from(handle as *mut ffi::c_void).into()),
+            winerror::E_OUTOFMEMORY => Err(ExternalMemoryExportError::OutOfHostMemory),
+            _ => {
+                error!("Error in CreateSharedHandle: 0x{:X}", hr);
+                Err(ExternalMemoryExportError::InvalidExternalHandle)
+            }
+        }
     }
 
     unsafe fn drm_format_modifier(&self, _image: &r::Image) -> Option<hal::format::DrmModifier> {
@@ -0,0 +1,141 @@
+//! Descriptions of the resources, and creation of the heaps and of the
+//! committed, placed and reserved resources.
+
+use hal::image;
+use winapi::{
+    shared::{dxgiformat, dxgitype},
+    um::d3d12,
+    Interface,
+};
+
+use std::ptr;
+
+/// Description of a buffer resource of `size` bytes.
+pub fn buffer_desc(size: u64, flags: d3d12::D3D12_RESOURCE_FLAGS) -> d3d12::D3D12_RESOURCE_DESC {
+    d3d12::D3D12_RESOURCE_DESC {
+        Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
+        Alignment: 0,
+        Width: size,
+        Height: 1,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
+        SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        Flags: flags,
+    }
+}
+
+/// Description of a texture resource of `kind`, with `mip_levels` levels
+/// starting at `extent`.
+///
+/// The layers of `kind` are only used by the textures without depth.
+pub fn texture_desc(
+    kind: image::Kind,
+    extent: image::Extent,
+    mip_levels: image::Level,
+    format: dxgiformat::DXGI_FORMAT,
+    layout: d3d12::D3D12_TEXTURE_LAYOUT,
+    flags: d3d12::D3D12_RESOURCE_FLAGS,
+) -> d3d12::D3D12_RESOURCE_DESC {
+    d3d12::D3D12_RESOURCE_DESC {
+        Dimension: match kind {
+            image::Kind::D1(..) => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE1D,
+            image::Kind::D2(..) => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            image::Kind::D3(..) => d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE3D,
+        },
+        Alignment: 0,
+        Width: extent.width as _,
+        Height: extent.height as _,
+        DepthOrArraySize: if extent.depth > 1 {
+            extent.depth as _
+        } else {
+            kind.num_layers() as _
+        },
+        MipLevels: mip_levels as _,
+        Format: format,
+        SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
+            Count: kind.num_samples() as _,
+            Quality: 0,
+        },
+        Layout: layout,
+        Flags: flags,
+    }
+}
+
+pub unsafe fn create_heap(
+    device: native::Device,
+    desc: &d3d12::D3D12_HEAP_DESC,
+) -> native::D3DResult<native::Heap> {
+    let mut heap = native::Heap::null();
+    let hr = device.CreateHeap(desc, &d3d12::ID3D12Heap::uuidof(), heap.mut_void());
+    (heap, hr)
+}
+
+/// Create a resource with a heap of its own.
+///
+/// The memory of committed resources is zero-initialized.
+pub unsafe fn create_committed_resource(
+    device: native::Device,
+    heap_properties: &d3d12::D3D12_HEAP_PROPERTIES,
+    heap_flags: d3d12::D3D12_HEAP_FLAGS,
+    desc: &d3d12::D3D12_RESOURCE_DESC,
+    initial_state: d3d12::D3D12_RESOURCE_STATES,
+    clear_value: Option<&d3d12::D3D12_CLEAR_VALUE>,
+) -> native::D3DResult<native::Resource> {
+    let mut resource = native::Resource::null();
+    let hr = device.CreateCommittedResource(
+        heap_properties,
+        heap_flags,
+        desc,
+        initial_state,
+        clear_value.map_or(ptr::null(), |value| value),
+        &d3d12::ID3D12Resource::uuidof(),
+        resource.mut_void(),
+    );
+    (resource, hr)
+}
+
+/// Create a resource at `offset` in the memory of `heap`.
+pub unsafe fn create_placed_resource(
+    device: native::Device,
+    heap: native::Heap,
+    offset: u64,
+    desc: &d3d12::D3D12_RESOURCE_DESC,
+    initial_state: d3d12::D3D12_RESOURCE_STATES,
+    clear_value: Option<&d3d12::D3D12_CLEAR_VALUE>,
+) -> native::D3DResult<native::Resource> {
+    let mut resource = native::Resource::null();
+    let hr = device.CreatePlacedResource(
+        heap.as_mut_ptr(),
+        offset,
+        desc,
+        initial_state,
+        clear_value.map_or(ptr::null(), |value| value),
+        &d3d12::ID3D12Resource::uuidof(),
+        resource.mut_void(),
+    );
+    (resource, hr)
+}
+
+/// Create a resource without memory, bound to the tiles of heaps with
+/// `UpdateTileMappings`.
+pub unsafe fn create_reserved_resource(
+    device: native::Device,
+    desc: &d3d12::D3D12_RESOURCE_DESC,
+    initial_state: d3d12::D3D12_RESOURCE_STATES,
+    clear_value: Option<&d3d12::D3D12_CLEAR_VALUE>,
+) -> native::D3DResult<native::Resource> {
+    let mut resource = native::Resource::null();
+    let hr = device.CreateReservedResource(
+        desc,
+        initial_state,
+        clear_value.map_or(ptr::null(), |value| value),
+        &d3d12::ID3D12Resource::uuidof(),
+        resource.mut_void(),
+    );
+    (resource, hr)
+}
@@ -1,5 +1,5 @@
 use auxil::FastHashMap;
-use std::{ffi::CStr, mem, ptr, sync::Arc};
+use std::{ffi::CStr, iter, mem, ptr};
 
 use parking_lot::Mutex;
 use winapi::{
@@ -14,6 +14,11 @@ use winapi::{
 
 use native;
 
+use crate::{
+    heap,
+    root_signature::{self, DescriptorRange, RootSignatureBuilder},
+};
+
 #[derive(Clone, Debug)]
 pub struct BlitPipe {
     pub pipeline: native::PipelineState,
@@ -37,29 +42,36 @@ pub struct BlitData {
     pub level: f32,
 }
 
+/// Rows of the matrix converting YCbCr to RGB, passed to the blits of
+/// multi-planar sources.
+pub type YcbcrToRgb = [[f32; 4]; 3];
+
 pub type BlitKey = (dxgiformat::DXGI_FORMAT, d3d12::D3D12_FILTER);
 type BlitMap = FastHashMap<BlitKey, BlitPipe>;
 
 #[derive(Debug)]
 pub(crate) struct ServicePipes {
     pub(crate) device: native::Device,
-    library: Arc<native::D3D12Lib>,
+    signature_serializer: root_signature::Serializer,
     blits_2d_color: Mutex<BlitMap>,
+    blits_2d_ycbcr: Mutex<BlitMap>,
 }
 
 impl ServicePipes {
-    pub fn new(device: native::Device, library: Arc<native::D3D12Lib>) -> Self {
+    pub fn new(device: native::Device, signature_serializer: root_signature::Serializer) -> Self {
         ServicePipes {
             device,
-            library,
+            signature_serializer,
             blits_2d_color: Mutex::new(FastHashMap::default()),
+            blits_2d_ycbcr: Mutex::new(FastHashMap::default()),
         }
     }
 
     pub unsafe fn destroy(&self) {
-        let blits = self.blits_2d_color.lock();
-        for (_, pipe) in &*blits {
-            pipe.destroy();
+        for blits in &[&self.blits_2d_color, &self.blits_2d_ycbcr] {
+            for (_, pipe) in &*blits.lock() {
+                pipe.destroy();
+            }
         }
     }
 
@@ -67,37 +79,54 @@ impl ServicePipes {
         let mut blits = self.blits_2d_color.lock();
         blits
             .entry(key)
-            .or_insert_with(|| self.create_blit_2d_color(key))
+            .or_insert_with(|| self.create_blit_2d(key, false))
             .clone()
     }
 
-    fn create_blit_2d_color(&self, (dst_format, filter): BlitKey) -> BlitPipe {
-        let descriptor_range = [native::DescriptorRange::new(
-            native::DescriptorRangeType::SRV,
-            1,
-            native::Binding {
-                register: 0,
-                space: 0,
-            },
-            0,
-        )];
+    /// Get the pipeline blitting a multi-planar source with a conversion
+    /// from YCbCr to RGB, sampling the luma and chroma planes.
+    pub fn get_blit_2d_ycbcr(&self, key: BlitKey) -> BlitPipe {
+        let mut blits = self.blits_2d_ycbcr.lock();
+        blits
+            .entry(key)
+            .or_insert_with(|| self.create_blit_2d(key, true))
+            .clone()
+    }
 
-        let root_parameters = [
-            native::RootParameter::descriptor_table(
+    fn create_blit_2d(&self, (dst_format, filter): BlitKey, ycbcr: bool) -> BlitPipe {
+        let mut builder = RootSignatureBuilder::new(native::RootSignatureFlags::empty());
+        builder
+            .descriptor_table(
                 native::ShaderVisibility::All,
-                &descriptor_range,
-            ),
-            native::RootParameter::constants(
+                iter::once(DescriptorRange {
+                    ty: native::DescriptorRangeType::SRV,
+                    count: if ycbcr { 2 } else { 1 },
+                    binding: native::Binding {
+                        register: 0,
+                        space: 0,
+                    },
+                    offset: 0,
+                }),
+            )
+            .constants(
                 native::ShaderVisibility::All,
                 native::Binding {
                     register: 0,
                     space: 0,
                 },
                 (mem::size_of::<BlitData>() / 4) as _,
-            ),
-        ];
-
-        let static_samplers = [native::StaticSampler::new(
+            );
+        if ycbcr {
+            builder.constants(
+                native::ShaderVisibility::PS,
+                native::Binding {
+                    register: 1,
+                    space: 0,
+                },
+                (mem::size_of::<YcbcrToRgb>() / 4) as _,
+            );
+        }
+        builder.static_sampler(native::StaticSampler::new(
             native::ShaderVisibility::PS,
             native::Binding {
                 register: 0,
@@ -114,26 +143,13 @@ impl ServicePipes {
             d3d12::D3D12_COMPARISON_FUNC_ALWAYS,
             native::StaticBorderColor::TransparentBlack,
             0.0..d3d12::D3D12_FLOAT32_MAX,
-        )];
-
-        let (signature_raw, error) = match self.library.serialize_root_signature(
-            native::RootSignatureVersion::V1_0,
-            &root_parameters,
-            &static_samplers,
-            native::RootSignatureFlags::empty(),
-        ) {
-            Ok((pair, hr)) if winerror::SUCCEEDED(hr) => pair,
-            Ok((_, hr)) => panic!("Can't serialize internal root signature: {:?}", hr),
-            Err(e) => panic!("Can't find serialization function: {:?}", e),
-        };
-
-        if !error.is_null() {
-            error!("D3D12SerializeRootSignature error: {:?}", unsafe {
-                error.as_c_str().to_str().unwrap()
-            });
-            unsafe { error.destroy() };
-        }
+        ));
 
+        // The blits can't report errors, and their signature is fixed.
+        let signature_raw = match builder.serialize(&self.signature_serializer) {
+            Ok(blob) => blob,
+            Err(e) => panic!("Can't serialize internal root signature: {:?}", e),
+        };
         let (signature, _hr) = self.device.create_root_signature(signature_raw, 0);
         unsafe {
             signature_raw.destroy();
@@ -147,10 +163,15 @@ impl ServicePipes {
             unsafe { CStr::from_bytes_with_nul_unchecked(b"vs_blit_2d\0") },
             native::ShaderCompileFlags::empty(),
         );
+        let ps_entry: &[u8] = if ycbcr {
+            b"ps_blit_2d_ycbcr\0"
+        } else {
+            b"ps_blit_2d\0"
+        };
         let ((ps, _), _hr_ps) = native::Shader::compile(
             shader_src,
             unsafe { CStr::from_bytes_with_nul_unchecked(b"ps_5_0\0") },
-            unsafe { CStr::from_bytes_with_nul_unchecked(b"ps_blit_2d\0") },
+            unsafe { CStr::from_bytes_with_nul_unchecked(ps_entry) },
             native::ShaderCompileFlags::empty(),
         );
 
@@ -242,3 +263,58 @@ impl ServicePipes {
         }
     }
 }
+
+/// Filled size counters of the stream output buffer slots.
+///
+/// Stream output appends after the size stored in the counter of each slot,
+/// so the counters are cleared from a zero-initialized twin when capturing starts.
+#[derive(Debug)]
+pub(crate) struct StreamOutputCounters {
+    pub(crate) counters: native::Resource,
+    pub(crate) zeros: native::Resource,
+}
+
+impl StreamOutputCounters {
+    /// Size of the counters, one `u64` per buffer slot.
+    pub const SIZE: u64 = d3d12::D3D12_SO_BUFFER_SLOT_COUNT as u64 * 8;
+
+    pub fn new(device: native::Device) -> Self {
+        let create = || {
+            let heap_properties = d3d12::D3D12_HEAP_PROPERTIES {
+                Type: d3d12::D3D12_HEAP_TYPE_DEFAULT,
+                CPUPageProperty: d3d12::D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+                MemoryPoolPreference: d3d12::D3D12_MEMORY_POOL_UNKNOWN,
+                CreationNodeMask: 0,
+                VisibleNodeMask: 0,
+            };
+            let desc = heap::buffer_desc(Self::SIZE, d3d12::D3D12_RESOURCE_FLAG_NONE);
+            // Committed resources are zero-initialized.
+            let (resource, hr) = unsafe {
+                heap::create_committed_resource(
+                    device,
+                    &heap_properties,
+                    d3d12::D3D12_HEAP_FLAG_NONE,
+                    &desc,
+                    d3d12::D3D12_RESOURCE_STATE_COMMON,
+                    None,
+                )
+            };
+            assert_eq!(winerror::S_OK, hr);
+            resource
+        };
+
+        StreamOutputCounters {
+            counters: create(),
+            zeros: create(),
+        }
+    }
+
+    pub unsafe fn location(&self, slot: usize) -> d3d12::D3D12_GPU_VIRTUAL_ADDRESS {
+        self.counters.GetGPUVirtualAddress() + slot as u64 * 8
+    }
+
+    pub unsafe fn destroy(&self) {
+        self.counters.destroy();
+        self.zeros.destroy();
+    }
+}
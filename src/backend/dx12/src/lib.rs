@@ -23,20 +23,33 @@ extern crate bitflags;
 #[macro_use]
 extern crate log;
 
+mod barrier;
 mod command;
 mod conv;
+mod debug;
+mod depth_bias;
 mod descriptors_cpu;
 mod device;
+mod heap;
 mod internal;
+mod mesh;
 mod pool;
+mod ray_tracing;
+mod residency;
 mod resource;
+mod retire;
 mod root_constants;
+mod root_signature;
+mod shading_rate;
 mod window;
 
 use auxil::FastHashMap;
 use hal::{
-    adapter, display, format as f, image, memory, pso::PipelineStage, queue as q, Features, Limits,
-    PhysicalDeviceProperties,
+    adapter,
+    device::{DeviceLost, OomOrDeviceLost, OutOfMemory},
+    display, format as f, image, memory,
+    pso::PipelineStage,
+    queue as q, Features, Limits, PhysicalDeviceProperties,
 };
 use range_alloc::RangeAllocator;
 
@@ -44,7 +57,7 @@ use parking_lot::{Mutex, RwLock};
 use smallvec::SmallVec;
 use winapi::{
     shared::{dxgi, dxgi1_2, dxgi1_4, dxgi1_6, minwindef::TRUE, winerror},
-    um::{d3d12, d3d12sdklayers, handleapi, synchapi, winbase},
+    um::{d3d12, d3d12sdklayers, handleapi, profileapi, synchapi, winbase, winnt},
     Interface,
 };
 
@@ -54,8 +67,10 @@ use std::{
     fmt,
     mem,
     os::windows::ffi::OsStringExt,
+    ptr,
     //TODO: use parking_lot
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use self::descriptors_cpu::DescriptorCpuPool;
@@ -71,6 +86,8 @@ pub(crate) struct HeapProperties {
 // Only 16 input slots allowed.
 const MAX_VERTEX_BUFFERS: usize = 16;
 const MAX_DESCRIPTOR_SETS: usize = 8;
+// `D3D12_MAX_VIEW_INSTANCE_COUNT`
+const MAX_VIEW_INSTANCES: usize = 4;
 
 const NUM_HEAP_PROPERTIES: usize = 3;
 
@@ -225,6 +242,9 @@ pub struct PhysicalDevice {
     // Opening the same adapter multiple times will return the same D3D12Device again.
     is_open: Arc<Mutex<bool>>,
     adapter: native::WeakPtr<dxgi1_2::IDXGIAdapter2>,
+    // Number of GPUs linked together in the adapter, exposed as nodes.
+    node_count: u32,
+    instance_flags: hal::InstanceFlags,
     library: Arc<native::D3D12Lib>,
 }
 
@@ -243,6 +263,27 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         families: &[(&QueueFamily, &[q::QueuePriority])],
         requested_features: Features,
     ) -> Result<adapter::Gpu<Backend>, hal::device::CreationError> {
+        self.open_group(families, requested_features, 1)
+    }
+
+    fn node_count(&self) -> u32 {
+        self.node_count
+    }
+
+    unsafe fn open_group(
+        &self,
+        families: &[(&QueueFamily, &[q::QueuePriority])],
+        requested_features: Features,
+        nodes: hal::device::NodeMask,
+    ) -> Result<adapter::Gpu<Backend>, hal::device::CreationError> {
+        if nodes == 0 || nodes.checked_shr(self.node_count).unwrap_or(0) != 0 {
+            error!(
+                "Nodes {:#b} out of the {} nodes of the device",
+                nodes, self.node_count
+            );
+            return Err(hal::device::CreationError::InitializationFailed);
+        }
+
         let mut open_guard = match self.is_open.try_lock() {
             Some(inner) => inner,
             None => return Err(hal::device::CreationError::TooManyObjects),
@@ -263,6 +304,9 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             }
             Err(e) => panic!("device creation failed with {:?}", e),
         };
+        if self.instance_flags.contains(hal::InstanceFlags::VALIDATION) {
+            debug::setup_info_queue(device_raw);
+        }
 
         // Always create the presentation queue in case we want to build a swapchain.
         let (present_queue, hr_queue) = device_raw.create_command_queue(
@@ -275,14 +319,21 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             error!("error on queue creation: {:x}", hr_queue);
         }
 
-        let mut device = Device::new(device_raw, &self, present_queue);
+        let mut device = Device::new(device_raw, &self, present_queue, nodes);
         device.features = requested_features;
 
-        let queue_groups = families
-            .iter()
-            .map(|&(&family, priorities)| {
+        // One group per node and family, the present queue being on the first node.
+        let queue_groups = (0..self.node_count)
+            .filter(|node| nodes & (1 << node) != 0)
+            .flat_map(|node| families.iter().map(move |&family| (node, family)))
+            .filter(|&(node, (family, _))| match family {
+                QueueFamily::Present => node == 0,
+                QueueFamily::Normal(_) => true,
+            })
+            .map(|(node, (&family, priorities))| {
                 use hal::queue::QueueFamily as _;
                 let mut group = q::QueueGroup::new(family.id());
+                group.node = node;
 
                 let create_idle_event = || native::Event::create(true, false);
 
@@ -295,6 +346,12 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                             raw: device.present_queue.clone(),
                             idle_fence: device.create_raw_fence(false),
                             idle_event: create_idle_event(),
+                            retirement: Arc::clone(&device.retirement),
+                            timeline: device.retirement.add_queue(device.create_raw_fence(false)),
+                            device: device.raw,
+                            render_doc: Arc::clone(&device.render_doc),
+                            captures: Default::default(),
+                            instance_flags: self.instance_flags,
                         };
                         device.append_queue(queue.clone());
                         group.add_queue(queue);
@@ -306,7 +363,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                                 list_type,
                                 native::Priority::Normal,
                                 native::CommandQueueFlags::empty(),
-                                0,
+                                1 << node,
                             );
 
                             if winerror::SUCCEEDED(hr_queue) {
@@ -314,6 +371,14 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                                     raw: queue,
                                     idle_fence: device.create_raw_fence(false),
                                     idle_event: create_idle_event(),
+                                    retirement: Arc::clone(&device.retirement),
+                                    timeline: device
+                                        .retirement
+                                        .add_queue(device.create_raw_fence(false)),
+                                    device: device.raw,
+                                    render_doc: Arc::clone(&device.render_doc),
+                                    captures: Default::default(),
+                                    instance_flags: self.instance_flags,
                                 };
                                 device.append_queue(queue.clone());
                                 group.add_queue(queue);
@@ -446,24 +511,35 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         &self,
         _usage: hal::buffer::Usage,
         _sparse: hal::memory::SparseFlags,
-        _memory_type: hal::external_memory::ExternalMemoryType,
+        memory_type: hal::external_memory::ExternalMemoryType,
     ) -> hal::external_memory::ExternalMemoryProperties {
-        unimplemented!()
+        shared_heap_properties(memory_type)
     }
 
     fn external_image_properties(
         &self,
-        _format: hal::format::Format,
+        format: hal::format::Format,
         _dimensions: u8,
-        _tiling: hal::image::Tiling,
+        tiling: hal::image::Tiling,
         _usage: hal::image::Usage,
         _view_caps: hal::image::ViewCapabilities,
-        _memory_type: hal::external_memory::ExternalMemoryType,
+        memory_type: hal::external_memory::ExternalMemoryType,
     ) -> Result<
         hal::external_memory::ExternalMemoryProperties,
         hal::external_memory::ExternalImagePropertiesError,
     > {
-        unimplemented!()
+        if conv::map_format(format).is_none() {
+            return Err(hal::external_memory::ExternalImagePropertiesError::FormatNotSupported);
+        }
+        use hal::external_memory::{ExternalMemoryProperties as Emp, ExternalMemoryType as Emt};
+        // Row major textures can only be placed in heaps shared across adapters.
+        Ok(match (tiling, memory_type) {
+            (image::Tiling::Linear, _) => Emp::empty(),
+            // Textures shared by other devices or APIs, such as the frames of a video decoder.
+            (image::Tiling::Optimal, Emt::D3D11Texture)
+            | (image::Tiling::Optimal, Emt::D3D12Resource) => Emp::IMPORTABLE,
+            (image::Tiling::Optimal, _) => shared_heap_properties(memory_type),
+        })
     }
 
     fn features(&self) -> Features {
@@ -508,6 +584,13 @@ pub struct Queue {
     pub(crate) raw: native::CommandQueue,
     idle_fence: native::Fence,
     idle_event: native::Event,
+    retirement: Arc<retire::Retirement>,
+    // Index of the timeline of the queue in the retirement list.
+    timeline: usize,
+    device: native::Device,
+    render_doc: Arc<gfx_renderdoc::RenderDoc>,
+    captures: gfx_renderdoc::FrameCaptures,
+    instance_flags: hal::InstanceFlags,
 }
 
 impl fmt::Debug for Queue {
@@ -523,18 +606,42 @@ impl Queue {
         self.raw.destroy();
     }
 
-    fn wait_idle_impl(&self) -> Result<(), hal::device::OutOfMemory> {
-        self.raw.signal(self.idle_fence, 1);
-        assert_eq!(
-            winerror::S_OK,
-            self.idle_fence.set_event_on_completion(self.idle_event, 1)
-        );
+    fn wait_idle_impl(&self) -> Result<(), OomOrDeviceLost> {
+        self.check_result(self.raw.signal(self.idle_fence, 1))?;
+        self.check_result(self.idle_fence.set_event_on_completion(self.idle_event, 1))?;
 
-        unsafe {
-            synchapi::WaitForSingleObject(self.idle_event.0, winbase::INFINITE);
+        match unsafe { synchapi::WaitForSingleObject(self.idle_event.0, winbase::INFINITE) } {
+            winbase::WAIT_FAILED => Err(DeviceLost.into()),
+            _ => self.check_result(unsafe { self.device.GetDeviceRemovedReason() }),
         }
+    }
 
-        Ok(())
+    /// Forward the messages of the debug layer to `log`.
+    fn flush_messages(&self) {
+        if self.instance_flags.contains(hal::InstanceFlags::VALIDATION) {
+            unsafe { debug::flush_messages(self.device) };
+        }
+    }
+
+    /// Map the result of a queue operation, reporting the removal of the device.
+    fn check_result(&self, hr: winnt::HRESULT) -> Result<(), OomOrDeviceLost> {
+        match hr {
+            winerror::S_OK => Ok(()),
+            winerror::E_OUTOFMEMORY => Err(OutOfMemory::Host.into()),
+            winerror::DXGI_ERROR_DEVICE_REMOVED
+            | winerror::DXGI_ERROR_DEVICE_RESET
+            | winerror::DXGI_ERROR_DEVICE_HUNG => {
+                let reason = unsafe { self.device.GetDeviceRemovedReason() };
+                error!("Device removed with reason {:x}", reason);
+                self.flush_messages();
+                unsafe { debug::report_device_removed(self.device) };
+                Err(DeviceLost.into())
+            }
+            other => {
+                error!("Unexpected queue error {:x}", other);
+                Ok(())
+            }
+        }
     }
 }
 
@@ -548,7 +655,8 @@ impl q::Queue<Backend> for Queue {
         _wait_semaphores: Iw,
         _signal_semaphores: Is,
         fence: Option<&mut resource::Fence>,
-    ) where
+    ) -> Result<(), OomOrDeviceLost>
+    where
         Ic: Iterator<Item = &'a command::CommandBuffer>,
         Iw: Iterator<Item = (&'a resource::Semaphore, PipelineStage)>,
         Is: Iterator<Item = &'a resource::Semaphore>,
@@ -564,10 +672,15 @@ impl q::Queue<Backend> for Queue {
             .collect::<SmallVec<[_; 4]>>();
         self.raw
             .ExecuteCommandLists(lists.len() as _, lists.as_ptr());
+        self.check_result(self.retirement.signal(self.timeline, self.raw))?;
 
         if let Some(fence) = fence {
-            assert_eq!(winerror::S_OK, self.raw.Signal(fence.raw.as_mut_ptr(), 1));
+            self.check_result(self.raw.Signal(fence.raw.as_mut_ptr(), 1))?;
         }
+        // Execution doesn't return errors, the removal of the device is queried instead.
+        self.check_result(self.device.GetDeviceRemovedReason())?;
+        self.flush_messages();
+        Ok(())
     }
 
     unsafe fn bind_sparse<'a, Iw, Is, Ibi, Ib, Iii, Io, Ii>(
@@ -683,17 +796,13 @@ impl q::Queue<Backend> for Queue {
                     );
                 }
                 Image::Unbound(image_unbound) => {
-                    let mut resource = native::Resource::null();
-                    assert_eq!(
-                        winerror::S_OK,
-                        device.raw.clone().CreateReservedResource(
-                            &image_unbound.desc,
-                            d3d12::D3D12_RESOURCE_STATE_COMMON,
-                            std::ptr::null(),
-                            &d3d12::ID3D12Resource::uuidof(),
-                            resource.mut_void(),
-                        )
+                    let (resource, hr) = heap::create_reserved_resource(
+                        device.raw,
+                        &image_unbound.desc,
+                        d3d12::D3D12_RESOURCE_STATE_COMMON,
+                        None,
                     );
+                    assert_eq!(winerror::S_OK, hr);
 
                     self.raw.UpdateTileMappings(
                         resource.as_mut_ptr(),
@@ -725,11 +834,19 @@ impl q::Queue<Backend> for Queue {
         image: window::SwapchainImage,
         _wait_semaphore: Option<&mut resource::Semaphore>,
     ) -> Result<Option<hal::window::Suboptimal>, hal::window::PresentError> {
-        surface.present(image).map(|()| None)
+        let result = surface.present(image).map(|()| None);
+        self.captures.frame_presented(
+            &self.render_doc,
+            self.device.as_mut_ptr() as *mut _,
+            ptr::null_mut(),
+        );
+        result
     }
 
-    fn wait_idle(&mut self) -> Result<(), hal::device::OutOfMemory> {
-        self.wait_idle_impl()
+    fn wait_idle(&mut self) -> Result<(), OomOrDeviceLost> {
+        self.wait_idle_impl()?;
+        self.flush_messages();
+        Ok(())
     }
 
     fn timestamp_period(&self) -> f32 {
@@ -739,6 +856,50 @@ impl q::Queue<Backend> for Queue {
         }
         (1_000_000_000.0 / frequency as f64) as f32
     }
+
+    fn timestamp_calibration(&self) -> Option<q::TimestampCalibration> {
+        let mut gpu_timestamp = 0u64;
+        let mut cpu_timestamp = 0u64;
+        let hr = unsafe {
+            self.raw
+                .GetClockCalibration(&mut gpu_timestamp, &mut cpu_timestamp)
+        };
+        if !winerror::SUCCEEDED(hr) {
+            return None;
+        }
+
+        Some(q::TimestampCalibration {
+            gpu_timestamp,
+            cpu_instant: performance_counter_instant(cpu_timestamp)?,
+        })
+    }
+
+    fn capture_next_frames(&mut self, frames: u32) {
+        unsafe {
+            self.captures.capture_next_frames(
+                &self.render_doc,
+                self.device.as_mut_ptr() as *mut _,
+                ptr::null_mut(),
+                frames,
+            )
+        }
+    }
+}
+
+/// Convert a `QueryPerformanceCounter` value to an `Instant`.
+///
+/// `Instant` doesn't expose the counter, so the value is related to the current time instead.
+pub(crate) fn performance_counter_instant(counter: u64) -> Option<Instant> {
+    let now = Instant::now();
+    let (current, frequency) = unsafe {
+        let mut current = mem::zeroed::<winnt::LARGE_INTEGER>();
+        let mut frequency = mem::zeroed::<winnt::LARGE_INTEGER>();
+        profileapi::QueryPerformanceCounter(&mut current);
+        profileapi::QueryPerformanceFrequency(&mut frequency);
+        (*current.QuadPart() as u64, *frequency.QuadPart() as u64)
+    };
+    let elapsed = current.saturating_sub(counter) as f64 / frequency as f64;
+    now.checked_sub(Duration::from_secs_f64(elapsed))
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -752,6 +913,28 @@ enum MemoryArchitecture {
 pub struct PrivateCapabilities {
     heterogeneous_resource_heaps: bool,
     memory_architecture: MemoryArchitecture,
+    enhanced_barriers: bool,
+    dynamic_depth_bias: bool,
+    depth_bounds_test: bool,
+    shading_rate_tier: shading_rate::Tier,
+}
+
+impl PrivateCapabilities {
+    /// Pipeline states that can be left out of the PSO and set on the command list.
+    fn dynamic_pipeline_states(&self) -> hal::DynamicStates {
+        let mut states = hal::DynamicStates::VIEWPORT
+            | hal::DynamicStates::SCISSOR
+            | hal::DynamicStates::BLEND_CONSTANTS
+            | hal::DynamicStates::STENCIL_REFERENCE;
+        if self.dynamic_depth_bias {
+            states |= hal::DynamicStates::DEPTH_BIAS;
+        }
+        // Depth bounds are never part of the PSO, only the test enable is.
+        if self.depth_bounds_test {
+            states |= hal::DynamicStates::DEPTH_BOUNDS;
+        }
+        states
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -774,6 +957,12 @@ impl CmdSignatures {
 struct Shared {
     pub signatures: CmdSignatures,
     pub service_pipes: internal::ServicePipes,
+    /// Record pipeline barriers with `ID3D12GraphicsCommandList7::Barrier`.
+    pub enhanced_barriers: bool,
+    /// Record depth bias changes with `ID3D12GraphicsCommandList9::RSSetDepthBias`.
+    pub dynamic_depth_bias: bool,
+    /// Variable rate shading support, recorded with `ID3D12GraphicsCommandList5`.
+    pub shading_rate_tier: shading_rate::Tier,
 }
 
 impl Shared {
@@ -800,6 +989,12 @@ impl SamplerStorage {
 
 pub struct Device {
     raw: native::Device,
+    // Adapter of the device, queried for the memory budget.
+    adapter: native::WeakPtr<dxgi1_4::IDXGIAdapter3>,
+    // Heaps of the physical device, the budgets when the adapter can't be queried.
+    memory_heaps: Vec<adapter::MemoryHeap>,
+    // Nodes the device is opened with, sharing the pipelines and the signatures.
+    nodes: hal::device::NodeMask,
     private_caps: PrivateCapabilities,
     features: Features,
     format_properties: Arc<FormatProperties>,
@@ -815,7 +1010,12 @@ pub struct Device {
         Mutex<RangeAllocator<DescriptorIndex>>,
     ),
     samplers: SamplerStorage,
+    // Root signatures by their serialized blob, shared by the identical pipeline layouts.
+    root_signatures: Mutex<FastHashMap<Vec<u8>, native::RootSignature>>,
+    signature_serializer: root_signature::Serializer,
     events: Mutex<Vec<native::Event>>,
+    // Native objects destroyed by the user, released once the queues are done with them.
+    retirement: Arc<retire::Retirement>,
     shared: Arc<Shared>,
     // Present queue exposed by the `Present` queue family.
     // Required for swapchain creation. Only a single queue supports presentation.
@@ -825,8 +1025,7 @@ pub struct Device {
     queues: Vec<Queue>,
     // Indicates that there is currently an active device.
     open: Arc<Mutex<bool>>,
-    library: Arc<native::D3D12Lib>,
-    render_doc: gfx_renderdoc::RenderDoc,
+    render_doc: Arc<gfx_renderdoc::RenderDoc>,
 }
 
 impl fmt::Debug for Device {
@@ -843,6 +1042,7 @@ impl Device {
         device: native::Device,
         physical_device: &PhysicalDevice,
         present_queue: native::CommandQueue,
+        nodes: hal::device::NodeMask,
     ) -> Self {
         // Allocate descriptor heaps
         let rtv_pool = DescriptorCpuPool::new(device, native::DescriptorHeapType::Rtv);
@@ -872,27 +1072,37 @@ impl Device {
             physical_device.workarounds.avoid_cpu_descriptor_overwrites,
         );
 
-        let draw_signature = Self::create_command_signature(device, device::CommandSignature::Draw);
+        let draw_signature =
+            Self::create_command_signature(device, device::CommandSignature::Draw, nodes);
         let draw_indexed_signature =
-            Self::create_command_signature(device, device::CommandSignature::DrawIndexed);
+            Self::create_command_signature(device, device::CommandSignature::DrawIndexed, nodes);
         let dispatch_signature =
-            Self::create_command_signature(device, device::CommandSignature::Dispatch);
+            Self::create_command_signature(device, device::CommandSignature::Dispatch, nodes);
 
         let signatures = CmdSignatures {
             draw: draw_signature,
             draw_indexed: draw_indexed_signature,
             dispatch: dispatch_signature,
         };
-        let service_pipes =
-            internal::ServicePipes::new(device, Arc::clone(&physical_device.library));
+        let signature_serializer =
+            root_signature::Serializer::new(Arc::clone(&physical_device.library));
+        let service_pipes = internal::ServicePipes::new(device, signature_serializer.clone());
+        let (adapter, hr_adapter) = physical_device.adapter.cast::<dxgi1_4::IDXGIAdapter3>();
+        assert_eq!(winerror::S_OK, hr_adapter);
+
         let shared = Shared {
             signatures,
             service_pipes,
+            enhanced_barriers: physical_device.private_caps.enhanced_barriers,
+            dynamic_depth_bias: physical_device.private_caps.dynamic_depth_bias,
+            shading_rate_tier: physical_device.private_caps.shading_rate_tier,
         };
 
         Device {
             raw: device,
-            library: Arc::clone(&physical_device.library),
+            adapter,
+            memory_heaps: physical_device.memory_properties.memory_heaps.clone(),
+            nodes,
             private_caps: physical_device.private_caps,
             features: Features::empty(),
             format_properties: physical_device.format_properties.clone(),
@@ -908,7 +1118,10 @@ impl Device {
                 heap: heap_sampler,
                 origins: RwLock::default(),
             },
+            root_signatures: Mutex::default(),
+            signature_serializer,
             events: Mutex::new(Vec::new()),
+            retirement: Arc::default(),
             shared: Arc::new(shared),
             present_queue,
             queues: Vec::new(),
@@ -927,6 +1140,11 @@ impl Device {
     pub unsafe fn as_raw(&self) -> *mut d3d12::ID3D12Device {
         self.raw.as_mut_ptr()
     }
+
+    /// RenderDoc integration, to configure the captures.
+    pub fn render_doc(&self) -> &gfx_renderdoc::RenderDoc {
+        &self.render_doc
+    }
 }
 
 impl Drop for Device {
@@ -938,15 +1156,20 @@ impl Drop for Device {
                 let _ = q::Queue::wait_idle(queue);
                 queue.destroy();
             }
+            self.retirement.destroy();
 
             self.shared.destroy();
             self.heap_srv_cbv_uav.0.destroy();
             self.samplers.destroy();
+            for (_, signature) in self.root_signatures.get_mut().drain() {
+                signature.destroy();
+            }
             self.rtv_pool.lock().destroy();
             self.dsv_pool.lock().destroy();
             self.srv_uav_pool.lock().destroy();
 
             self.descriptor_updater.lock().destroy();
+            self.adapter.destroy();
 
             // Debug tracking alive objects
             let (debug_device, hr_debug) = self.raw.cast::<d3d12sdklayers::ID3D12DebugDevice>();
@@ -965,6 +1188,7 @@ pub struct Instance {
     pub(crate) factory: native::Factory4,
     library: Arc<native::D3D12Lib>,
     lib_dxgi: native::DxgiLib,
+    flags: hal::InstanceFlags,
 }
 
 impl Drop for Instance {
@@ -979,26 +1203,29 @@ unsafe impl Send for Instance {}
 unsafe impl Sync for Instance {}
 
 impl hal::Instance<Backend> for Instance {
-    fn create(_: &str, _: u32) -> Result<Self, hal::UnsupportedBackend> {
+    fn create(name: &str, version: u32) -> Result<Self, hal::UnsupportedBackend> {
+        Self::create_with_flags(name, version, hal::InstanceFlags::default())
+    }
+
+    fn create_with_flags(
+        _: &str,
+        _: u32,
+        flags: hal::InstanceFlags,
+    ) -> Result<Self, hal::UnsupportedBackend> {
+        let flags = flags.with_env();
         let lib_main = match native::D3D12Lib::new() {
             Ok(lib) => lib,
             Err(_) => return Err(hal::UnsupportedBackend),
         };
 
-        #[cfg(debug_assertions)]
-        {
-            // Enable debug layer
-            match lib_main.get_debug_interface() {
-                Ok((debug_controller, hr)) if winerror::SUCCEEDED(hr) => {
-                    debug_controller.enable_layer();
-                    unsafe {
-                        debug_controller.Release();
-                    }
-                }
-                _ => {
-                    warn!("Unable to get D3D12 debug interface");
-                }
-            }
+        if flags.contains(hal::InstanceFlags::VALIDATION) {
+            debug::enable_debug_layer(
+                &lib_main,
+                flags.contains(hal::InstanceFlags::GPU_VALIDATION),
+            );
+        }
+        if flags.contains(hal::InstanceFlags::DEBUG) {
+            debug::enable_dred();
         }
 
         let lib_dxgi = native::DxgiLib::new().unwrap();
@@ -1006,12 +1233,16 @@ impl hal::Instance<Backend> for Instance {
         // The `DXGI_CREATE_FACTORY_DEBUG` flag is only allowed to be passed to
         // `CreateDXGIFactory2` if the debug interface is actually available. So
         // we check for whether it exists first.
-        let factory_flags = match lib_dxgi.get_debug_interface1() {
-            Ok((queue, hr)) if winerror::SUCCEEDED(hr) => {
-                unsafe { queue.destroy() };
-                native::FactoryCreationFlags::DEBUG
+        let factory_flags = if !flags.contains(hal::InstanceFlags::VALIDATION) {
+            native::FactoryCreationFlags::empty()
+        } else {
+            match lib_dxgi.get_debug_interface1() {
+                Ok((queue, hr)) if winerror::SUCCEEDED(hr) => {
+                    unsafe { queue.destroy() };
+                    native::FactoryCreationFlags::DEBUG
+                }
+                _ => native::FactoryCreationFlags::empty(),
             }
-            _ => native::FactoryCreationFlags::empty(),
         };
 
         // Create DXGI factory
@@ -1028,6 +1259,7 @@ impl hal::Instance<Backend> for Instance {
             factory,
             library: Arc::new(lib_main),
             lib_dxgi,
+            flags,
         })
     }
 
@@ -1129,6 +1361,15 @@ impl hal::Instance<Backend> for Instance {
                 )
             });
 
+            let mut umd_version: winnt::LARGE_INTEGER = unsafe { mem::zeroed() };
+            let driver = if winerror::SUCCEEDED(unsafe {
+                adapter.CheckInterfaceSupport(&dxgi::IDXGIDevice::uuidof(), &mut umd_version)
+            }) {
+                auxil::umd_version_string(unsafe { *umd_version.QuadPart() } as u64)
+            } else {
+                String::new()
+            };
+
             let mut workarounds = Workarounds::default();
 
             let info = adapter::AdapterInfo {
@@ -1143,6 +1384,13 @@ impl hal::Instance<Backend> for Instance {
                 } else {
                     adapter::DeviceType::DiscreteGpu
                 },
+                driver,
+                unified_memory: features_architecture.UMA == TRUE,
+                luid: Some(auxil::luid_bytes(
+                    desc.AdapterLuid.LowPart,
+                    desc.AdapterLuid.HighPart,
+                )),
+                uuid: None,
             };
 
             let mut features: d3d12::D3D12_FEATURE_DATA_D3D12_OPTIONS = unsafe { mem::zeroed() };
@@ -1174,6 +1422,12 @@ impl hal::Instance<Backend> for Instance {
             let heterogeneous_resource_heaps =
                 features.ResourceHeapTier != d3d12::D3D12_RESOURCE_HEAP_TIER_1;
 
+            let enhanced_barriers = barrier::is_supported(device);
+            let dynamic_depth_bias = depth_bias::is_supported(device);
+            let mesh_shader_supported = mesh::is_supported(device);
+            let ray_tracing_supported = ray_tracing::is_supported(device);
+            let (shading_rate_tier, shading_rate_properties) = shading_rate::query(device);
+
             let uma = features_architecture.UMA == TRUE;
             let cc_uma = features_architecture.CacheCoherentUMA == TRUE;
 
@@ -1184,7 +1438,7 @@ impl hal::Instance<Backend> for Instance {
             };
 
             // https://msdn.microsoft.com/en-us/library/windows/desktop/dn788678(v=vs.85).aspx
-            let base_memory_types: [adapter::MemoryType; NUM_HEAP_PROPERTIES] =
+            let mut base_memory_types: [adapter::MemoryType; NUM_HEAP_PROPERTIES] =
                 match memory_architecture {
                     MemoryArchitecture::NUMA => [
                         // DEFAULT
@@ -1251,6 +1505,10 @@ impl hal::Instance<Backend> for Instance {
                         },
                     ],
                 };
+            // All the resources are placed in heaps, sharing the memory they're bound to.
+            for memory_type in base_memory_types.iter_mut() {
+                memory_type.properties |= Properties::ALIASABLE;
+            }
 
             let memory_types = if heterogeneous_resource_heaps {
                 base_memory_types.to_vec()
@@ -1372,9 +1630,23 @@ impl hal::Instance<Backend> for Instance {
                 Features::CONSERVATIVE_RASTERIZATION
             };
 
+            let private_caps = PrivateCapabilities {
+                heterogeneous_resource_heaps,
+                memory_architecture,
+                enhanced_barriers,
+                dynamic_depth_bias,
+                depth_bounds_test: depth_bounds_test_supported,
+                shading_rate_tier,
+            };
+
             let physical_device = PhysicalDevice {
                 library: Arc::clone(&self.library),
                 adapter,
+                // The shader visible descriptor heaps, the query heaps and the blit
+                // pipelines can only live on a single node, so the linked GPUs of the
+                // adapter (`GetNodeCount`) aren't exposed until they're created per node.
+                node_count: 1,
+                instance_flags: self.flags,
                 features:
                     // TODO: add more features, based on
                     // https://msdn.microsoft.com/de-de/library/windows/desktop/mt186615(v=vs.85).aspx
@@ -1393,6 +1665,12 @@ impl hal::Instance<Backend> for Instance {
                     Features::SAMPLER_BORDER_COLOR |
                     Features::MUTABLE_COMPARISON_SAMPLER |
                     Features::SAMPLER_ANISOTROPY |
+                    // Min/max filtering comes with the second tier of tiled resources.
+                    if features.TiledResourcesTier >= d3d12::D3D12_TILED_RESOURCES_TIER_2 {
+                        Features::SAMPLER_REDUCTION
+                    } else {
+                        Features::empty()
+                    } |
                     Features::TEXTURE_DESCRIPTOR_ARRAY |
                     Features::BUFFER_DESCRIPTOR_ARRAY |
                     Features::SAMPLER_MIRROR_CLAMP_EDGE |
@@ -1406,10 +1684,28 @@ impl hal::Instance<Backend> for Instance {
                     Features::STORAGE_BUFFER_DESCRIPTOR_INDEXING |
                     Features::UNIFORM_BUFFER_DESCRIPTOR_INDEXING |
                     Features::UNSIZED_DESCRIPTOR_ARRAY |
+                    // The views are written straight into the shader visible heap, and
+                    // only read by the GPU when the command lists execute.
+                    Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND |
+                    Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND |
+                    Features::DESCRIPTOR_BINDING_VARIABLE_COUNT |
+                    if features.VPAndRTArrayIndexFromAnyShaderFeedingRasterizerSupportedWithoutGSEmulation == TRUE {
+                        Features::SHADER_OUTPUT_VIEWPORT_INDEX | Features::SHADER_OUTPUT_LAYER
+                    } else {
+                        Features::empty()
+                    } |
                     Features::DRAW_INDIRECT_COUNT |
                     Features::INDEPENDENT_BLENDING |
                     Features::SAMPLE_RATE_SHADING |
                     Features::FRAGMENT_STORES_AND_ATOMICS |
+                    if enhanced_barriers { Features::ENHANCED_BARRIERS } else { Features::empty() } |
+                    Features::TRANSFORM_FEEDBACK |
+                    Features::MULTI_PLANAR_FORMATS |
+                    Features::EXTERNAL_MEMORY |
+                    if mesh_shader_supported { Features::MESH_SHADER_MASK } else { Features::empty() } |
+                    if ray_tracing_supported { Features::ACCELERATION_STRUCTURE | Features::RAY_TRACING_PIPELINE } else { Features::empty() } |
+                    if shading_rate_tier >= shading_rate::Tier::PerDraw { Features::SHADING_RATE } else { Features::empty() } |
+                    if shading_rate_tier >= shading_rate::Tier::Image { Features::SHADING_RATE_ATTACHMENT } else { Features::empty() } |
                     tiled_resource_features |
                     conservative_faster_features,
                 properties: PhysicalDeviceProperties {
@@ -1459,7 +1755,7 @@ impl hal::Instance<Backend> for Instance {
                         max_image_cube_size: d3d12::D3D12_REQ_TEXTURECUBE_DIMENSION as _,
                         max_image_array_layers: d3d12::D3D12_REQ_TEXTURE2D_ARRAY_AXIS_DIMENSION as _,
                         max_texel_elements: 0,
-                        max_patch_size: 0,
+                        max_patch_size: d3d12::D3D12_IA_PATCH_MAX_CONTROL_POINT_COUNT as _,
                         max_viewports: d3d12::D3D12_VIEWPORT_AND_SCISSORRECT_OBJECT_COUNT_PER_PIPELINE as _,
                         max_viewport_dimensions: [d3d12::D3D12_VIEWPORT_BOUNDS_MAX as _; 2],
                         max_framebuffer_extent: hal::image::Extent {
@@ -1495,6 +1791,7 @@ impl hal::Instance<Backend> for Instance {
                         framebuffer_depth_sample_counts: sample_count_mask,
                         framebuffer_stencil_sample_counts: sample_count_mask,
                         max_color_attachments: d3d12::D3D12_SIMULTANEOUS_RENDER_TARGET_COUNT as _,
+                        timestamp_compute_and_graphics: true,
                         buffer_image_granularity: 1,
                         non_coherent_atom_size: 1, //TODO: confirm
                         max_sampler_anisotropy: 16.,
@@ -1503,18 +1800,23 @@ impl hal::Instance<Backend> for Instance {
                         min_vertex_input_binding_stride_alignment: 1,
                         ..Limits::default() //TODO
                     },
-                    dynamic_pipeline_states: hal::DynamicStates::VIEWPORT
-                        | hal::DynamicStates::SCISSOR
-                        | hal::DynamicStates::BLEND_CONSTANTS
-                        | hal::DynamicStates::STENCIL_REFERENCE,
+                    dynamic_pipeline_states: private_caps.dynamic_pipeline_states(),
+                    mesh_shader: if mesh_shader_supported {
+                        mesh::PROPERTIES
+                    } else {
+                        hal::MeshShaderProperties::default()
+                    },
+                    ray_tracing: if ray_tracing_supported {
+                        ray_tracing::PROPERTIES
+                    } else {
+                        hal::RayTracingProperties::default()
+                    },
+                    shading_rate: shading_rate_properties,
                     downlevel: hal::DownlevelProperties::all_enabled(),
                     ..PhysicalDeviceProperties::default()
                 },
                 format_properties: Arc::new(FormatProperties::new(device)),
-                private_caps: PrivateCapabilities {
-                    heterogeneous_resource_heaps,
-                    memory_architecture,
-                },
+                private_caps,
                 workarounds,
                 heap_properties,
                 memory_properties: adapter::MemoryProperties {
@@ -1540,6 +1842,9 @@ impl hal::Instance<Backend> for Instance {
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
     ) -> Result<window::Surface, hal::window::InitError> {
         match has_handle.raw_window_handle() {
+            raw_window_handle::RawWindowHandle::Windows(handle) if handle.hwnd.is_null() => {
+                Err(hal::window::InitError::InvalidWindowHandle)
+            }
             raw_window_handle::RawWindowHandle::Windows(handle) => {
                 Ok(self.create_surface_from_hwnd(handle.hwnd))
             }
@@ -1590,6 +1895,7 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = resource::ComputePipeline;
     type GraphicsPipeline = resource::GraphicsPipeline;
+    type RayTracingPipeline = resource::RayTracingPipeline;
     type PipelineLayout = resource::PipelineLayout;
     type PipelineCache = ();
     type DescriptorSetLayout = resource::DescriptorSetLayout;
@@ -1600,6 +1906,7 @@ impl hal::Backend for Backend {
     type Semaphore = resource::Semaphore;
     type Event = ();
     type QueryPool = resource::QueryPool;
+    type AccelerationStructure = resource::AccelerationStructure;
 
     type Display = ();
     type DisplayMode = ();
@@ -1612,6 +1919,19 @@ fn validate_line_width(width: f32) {
     assert_eq!(width, 1.0);
 }
 
+/// Shared heaps are exchanged as NT handles, opened with `OpenSharedHandle`.
+fn shared_heap_properties(
+    memory_type: hal::external_memory::ExternalMemoryType,
+) -> hal::external_memory::ExternalMemoryProperties {
+    use hal::external_memory::{ExternalMemoryProperties as Emp, ExternalMemoryType as Emt};
+    match memory_type {
+        Emt::OpaqueWin32 | Emt::D3D12Heap => {
+            Emp::EXPORTABLE | Emp::IMPORTABLE | Emp::EXPORTABLE_FROM_IMPORTED
+        }
+        _ => Emp::empty(),
+    }
+}
+
 #[derive(Clone, Debug, Default)]
 struct FormatInfo {
     properties: f::Properties,
@@ -1740,6 +2060,16 @@ impl FormatProperties {
                     }
                 }
             }
+            if format.is_planar() {
+                // Planes are viewed and copied individually, the whole image can only be
+                // converted to RGB by blitting it.
+                props.linear_tiling = f::ImageFeature::empty();
+                props.optimal_tiling &=
+                    f::ImageFeature::TRANSFER_SRC | f::ImageFeature::TRANSFER_DST;
+                if can_image {
+                    props.optimal_tiling |= f::ImageFeature::BLIT_SRC;
+                }
+            }
             //TODO: blits, linear tiling
             props
         };
@@ -0,0 +1,231 @@
+//! Mesh shading (`ID3D12GraphicsCommandList6::DispatchMesh`).
+//!
+//! Like the enhanced barriers, these definitions are missing from `winapi`
+//! and are declared here by hand, following `d3d12.h` of the Agility SDK.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use winapi::{
+    ctypes::c_void,
+    shared::{dxgiformat, dxgitype, guiddef::GUID, minwindef::UINT, winerror},
+    um::{d3d12, unknwnbase::IUnknown},
+    Interface,
+};
+
+use std::{mem, ptr};
+
+const D3D12_FEATURE_D3D12_OPTIONS7: d3d12::D3D12_FEATURE = 32;
+
+type D3D12_MESH_SHADER_TIER = u32;
+const D3D12_MESH_SHADER_TIER_1: D3D12_MESH_SHADER_TIER = 10;
+
+const D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_AS: d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE = 24;
+const D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_MS: d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE = 25;
+
+/// Limits of the first mesh shader tier, which are fixed by the D3D12 specification.
+pub const PROPERTIES: hal::MeshShaderProperties = hal::MeshShaderProperties {
+    // We only ever dispatch along X, see `dispatch`.
+    max_draw_mesh_tasks_count: 65535,
+    max_task_work_group_invocations: 128,
+    max_task_work_group_size: [128, 128, 128],
+    max_task_total_memory_size: 32 * 1024,
+    max_task_output_count: 65535,
+    max_mesh_work_group_invocations: 128,
+    max_mesh_work_group_size: [128, 128, 128],
+    max_mesh_total_memory_size: 32 * 1024,
+    max_mesh_output_vertices: 256,
+    max_mesh_output_primitives: 256,
+    max_mesh_multiview_view_count: 0,
+    mesh_output_per_vertex_granularity: 1,
+    mesh_output_per_primitive_granularity: 1,
+    // `DispatchMesh` always starts from the first group.
+    first_task: false,
+};
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct D3D12_FEATURE_DATA_D3D12_OPTIONS7 {
+    MeshShaderTier: D3D12_MESH_SHADER_TIER,
+    SamplerFeedbackTier: u32,
+}
+
+/// Minimal view of `ID3D12GraphicsCommandList6`: we only ever call `DispatchMesh`,
+/// so all the inherited methods are left opaque.
+#[repr(C)]
+struct ID3D12GraphicsCommandList6Vtbl {
+    // IUnknown (3), ID3D12Object (4), ID3D12DeviceChild (1), ID3D12CommandList (1),
+    // ID3D12GraphicsCommandList (51), ID3D12GraphicsCommandList1..5 (6 + 1 + 1 + 9 + 2).
+    parent: [*const c_void; 79],
+    DispatchMesh: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList6,
+        ThreadGroupCountX: UINT,
+        ThreadGroupCountY: UINT,
+        ThreadGroupCountZ: UINT,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12GraphicsCommandList6 {
+    lpVtbl: *const ID3D12GraphicsCommandList6Vtbl,
+}
+
+impl Interface for ID3D12GraphicsCommandList6 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0xc3827890,
+            Data2: 0xe548,
+            Data3: 0x4cfa,
+            Data4: [0x96, 0xcf, 0x56, 0x89, 0xa9, 0x37, 0x0f, 0x80],
+        }
+    }
+}
+
+/// Check if the device supports amplification and mesh shaders.
+pub fn is_supported(device: native::Device) -> bool {
+    let mut options = D3D12_FEATURE_DATA_D3D12_OPTIONS7::default();
+    let hr = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_D3D12_OPTIONS7,
+            &mut options as *mut _ as *mut _,
+            mem::size_of::<D3D12_FEATURE_DATA_D3D12_OPTIONS7>() as _,
+        )
+    };
+    // Older runtimes don't know about the feature at all and fail the query.
+    winerror::SUCCEEDED(hr) && options.MeshShaderTier >= D3D12_MESH_SHADER_TIER_1
+}
+
+/// Record a mesh shading draw of `task_count` amplification (or mesh, if the
+/// pipeline has no amplification shader) thread groups.
+pub unsafe fn dispatch(list: native::GraphicsCommandList, task_count: u32) {
+    let mut list6: *mut ID3D12GraphicsCommandList6 = ptr::null_mut();
+    let hr = (*(list.as_mut_ptr() as *mut IUnknown)).QueryInterface(
+        &ID3D12GraphicsCommandList6::uuidof(),
+        &mut list6 as *mut *mut _ as *mut *mut c_void,
+    );
+    assert_eq!(
+        winerror::S_OK,
+        hr,
+        "Mesh shaders were reported, but the command list doesn't support them"
+    );
+    ((*(*list6).lpVtbl).DispatchMesh)(list6, task_count, 1, 1);
+    (*(list6 as *mut IUnknown)).Release();
+}
+
+/// Subobject of a pipeline state stream, laid out like the `alignas(void*)`
+/// wrappers of `d3dx12.h`. Unlike `native::PipelineStateSubobject`, the type is
+/// a raw value, as the amplification and mesh shader types have no `Subobject`.
+#[repr(C)]
+struct StreamSubobject<T> {
+    align: [usize; 0],
+    ty: d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE,
+    desc: T,
+}
+
+impl<T> StreamSubobject<T> {
+    fn new(ty: d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE, desc: T) -> Self {
+        StreamSubobject {
+            align: [],
+            ty,
+            desc,
+        }
+    }
+}
+
+/// Pipeline state stream of a mesh shading pipeline. These can't be described by
+/// `D3D12_GRAPHICS_PIPELINE_STATE_DESC`, which has no slots for the shaders and
+/// always implies an input assembler.
+#[repr(C)]
+pub struct MeshPipelineStateSubobjectStream {
+    root_signature: StreamSubobject<*mut d3d12::ID3D12RootSignature>,
+    amplification: StreamSubobject<d3d12::D3D12_SHADER_BYTECODE>,
+    mesh: StreamSubobject<d3d12::D3D12_SHADER_BYTECODE>,
+    ps: StreamSubobject<d3d12::D3D12_SHADER_BYTECODE>,
+    blend: StreamSubobject<d3d12::D3D12_BLEND_DESC>,
+    sample_mask: StreamSubobject<UINT>,
+    rasterizer: StreamSubobject<d3d12::D3D12_RASTERIZER_DESC>,
+    depth_stencil: StreamSubobject<d3d12::D3D12_DEPTH_STENCIL_DESC1>,
+    render_target_formats: StreamSubobject<d3d12::D3D12_RT_FORMAT_ARRAY>,
+    depth_stencil_format: StreamSubobject<dxgiformat::DXGI_FORMAT>,
+    sample_desc: StreamSubobject<dxgitype::DXGI_SAMPLE_DESC>,
+    node_mask: StreamSubobject<UINT>,
+    flags: StreamSubobject<d3d12::D3D12_PIPELINE_STATE_FLAGS>,
+    view_instancing: StreamSubobject<d3d12::D3D12_VIEW_INSTANCING_DESC>,
+}
+
+impl MeshPipelineStateSubobjectStream {
+    /// Take the fixed function state from `pso_desc`, which must have been built
+    /// without any of the vertex processing shaders.
+    pub fn new(
+        pso_desc: &d3d12::D3D12_GRAPHICS_PIPELINE_STATE_DESC,
+        amplification: d3d12::D3D12_SHADER_BYTECODE,
+        mesh: d3d12::D3D12_SHADER_BYTECODE,
+        depth_bounds_test_enable: bool,
+        view_instancing: d3d12::D3D12_VIEW_INSTANCING_DESC,
+    ) -> Self {
+        MeshPipelineStateSubobjectStream {
+            root_signature: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_ROOT_SIGNATURE,
+                pso_desc.pRootSignature,
+            ),
+            amplification: StreamSubobject::new(
+                D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_AS,
+                amplification,
+            ),
+            mesh: StreamSubobject::new(D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_MS, mesh),
+            ps: StreamSubobject::new(d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_PS, pso_desc.PS),
+            blend: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_BLEND,
+                pso_desc.BlendState,
+            ),
+            sample_mask: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_SAMPLE_MASK,
+                pso_desc.SampleMask,
+            ),
+            rasterizer: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_RASTERIZER,
+                pso_desc.RasterizerState,
+            ),
+            depth_stencil: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_DEPTH_STENCIL1,
+                d3d12::D3D12_DEPTH_STENCIL_DESC1 {
+                    DepthEnable: pso_desc.DepthStencilState.DepthEnable,
+                    DepthWriteMask: pso_desc.DepthStencilState.DepthWriteMask,
+                    DepthFunc: pso_desc.DepthStencilState.DepthFunc,
+                    StencilEnable: pso_desc.DepthStencilState.StencilEnable,
+                    StencilReadMask: pso_desc.DepthStencilState.StencilReadMask,
+                    StencilWriteMask: pso_desc.DepthStencilState.StencilWriteMask,
+                    FrontFace: pso_desc.DepthStencilState.FrontFace,
+                    BackFace: pso_desc.DepthStencilState.BackFace,
+                    DepthBoundsTestEnable: depth_bounds_test_enable as _,
+                },
+            ),
+            render_target_formats: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_RENDER_TARGET_FORMATS,
+                d3d12::D3D12_RT_FORMAT_ARRAY {
+                    RTFormats: pso_desc.RTVFormats,
+                    NumRenderTargets: pso_desc.NumRenderTargets,
+                },
+            ),
+            depth_stencil_format: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_DEPTH_STENCIL_FORMAT,
+                pso_desc.DSVFormat,
+            ),
+            sample_desc: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_SAMPLE_DESC,
+                pso_desc.SampleDesc,
+            ),
+            node_mask: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_NODE_MASK,
+                pso_desc.NodeMask,
+            ),
+            flags: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_FLAGS,
+                pso_desc.Flags,
+            ),
+            view_instancing: StreamSubobject::new(
+                d3d12::D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_VIEW_INSTANCING,
+                view_instancing,
+            ),
+        }
+    }
+}
@@ -4,7 +4,7 @@ use parking_lot::Mutex;
 use winapi::shared::winerror;
 
 use crate::{command::CommandBuffer, Backend, Shared};
-use hal::{command, pool};
+use hal::{command, pool, queue::QueueFamilyId};
 
 const REUSE_COUNT: usize = 64;
 
@@ -56,8 +56,12 @@ impl CommandManager {
 }
 
 pub struct PoolShared {
-    device: native::Device,
+    pub(crate) device: native::Device,
     list_type: native::CmdListType,
+    // Queue family executing the command lists, for the ownership transfers.
+    pub(crate) family: QueueFamilyId,
+    // Node executing the command lists.
+    node_mask: u32,
     manager: Mutex<CommandManager>,
 }
 
@@ -100,7 +104,7 @@ impl PoolShared {
                     self.list_type,
                     raw,
                     native::PipelineState::null(),
-                    0,
+                    self.node_mask,
                 );
                 assert_eq!(
                     hr,
@@ -140,12 +144,16 @@ impl CommandPool {
     pub(crate) fn new(
         device: native::Device,
         list_type: native::CmdListType,
+        family: QueueFamilyId,
+        node_mask: u32,
         shared: &Arc<Shared>,
         _create_flags: pool::CommandPoolCreateFlags,
     ) -> Self {
         let pool_shared = Arc::new(PoolShared {
             device,
             list_type,
+            family,
+            node_mask,
             manager: Mutex::default(),
         });
         CommandPool {
@@ -0,0 +1,758 @@
+//! Ray tracing (DXR), built on `ID3D12Device5` and `ID3D12GraphicsCommandList4`.
+//!
+//! `winapi` predates DXR, so the interfaces and structures we use are declared
+//! here by hand from `d3d12.h`, with the inherited methods left opaque.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use hal::{acceleration_structure as accel, pso, IndexType, WorkGroupCount};
+use winapi::{
+    ctypes::c_void,
+    shared::{
+        dxgiformat,
+        guiddef::{GUID, REFIID},
+        minwindef::{BOOL, UINT},
+        winerror::{self, HRESULT},
+    },
+    um::{d3d12, unknwnbase::IUnknown, winnt::LPCWSTR},
+    Interface,
+};
+
+use std::{iter, mem, ptr};
+
+use crate::{conv, device, resource as r, Backend};
+
+const D3D12_FEATURE_D3D12_OPTIONS5: d3d12::D3D12_FEATURE = 27;
+
+type D3D12_RAYTRACING_TIER = u32;
+const D3D12_RAYTRACING_TIER_1_0: D3D12_RAYTRACING_TIER = 10;
+
+/// State in which the buffers holding acceleration structures are created, and stay.
+pub const D3D12_RESOURCE_STATE_RAYTRACING_ACCELERATION_STRUCTURE: d3d12::D3D12_RESOURCE_STATES =
+    0x40_0000;
+const D3D12_SRV_DIMENSION_RAYTRACING_ACCELERATION_STRUCTURE: d3d12::D3D12_SRV_DIMENSION = 11;
+
+/// Size of the shader identifiers, which are the handles of the shader groups.
+pub const D3D12_SHADER_IDENTIFIER_SIZE_IN_BYTES: usize = 32;
+
+/// Limits of the first ray tracing tier, which are fixed by the D3D12 specification.
+pub const PROPERTIES: hal::RayTracingProperties = hal::RayTracingProperties {
+    shader_group_handle_size: D3D12_SHADER_IDENTIFIER_SIZE_IN_BYTES as u32,
+    shader_group_handle_alignment: 32,
+    shader_group_base_alignment: 64,
+    max_shader_group_stride: 4096,
+    max_ray_recursion_depth: 31,
+    max_primitive_count: 1 << 29,
+    max_instance_count: 1 << 24,
+};
+
+type D3D12_RAYTRACING_GEOMETRY_TYPE = u32;
+const D3D12_RAYTRACING_GEOMETRY_TYPE_TRIANGLES: D3D12_RAYTRACING_GEOMETRY_TYPE = 0;
+const D3D12_RAYTRACING_GEOMETRY_TYPE_PROCEDURAL_PRIMITIVE_AABBS: D3D12_RAYTRACING_GEOMETRY_TYPE = 1;
+
+type D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE = u32;
+const D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE_TOP_LEVEL:
+    D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE = 0;
+const D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE_BOTTOM_LEVEL:
+    D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE = 1;
+
+// The other build flags have the same values as `accel::BuildFlags`.
+const D3D12_RAYTRACING_ACCELERATION_STRUCTURE_BUILD_FLAG_PERFORM_UPDATE: u32 = 0x20;
+
+const D3D12_ELEMENTS_LAYOUT_ARRAY: u32 = 0;
+
+type D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE = u32;
+const D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE_CLONE:
+    D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE = 0;
+const D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE_COMPACT:
+    D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE = 1;
+
+type D3D12_STATE_OBJECT_TYPE = u32;
+const D3D12_STATE_OBJECT_TYPE_RAYTRACING_PIPELINE: D3D12_STATE_OBJECT_TYPE = 3;
+
+type D3D12_STATE_SUBOBJECT_TYPE = u32;
+const D3D12_STATE_SUBOBJECT_TYPE_GLOBAL_ROOT_SIGNATURE: D3D12_STATE_SUBOBJECT_TYPE = 1;
+const D3D12_STATE_SUBOBJECT_TYPE_DXIL_LIBRARY: D3D12_STATE_SUBOBJECT_TYPE = 5;
+const D3D12_STATE_SUBOBJECT_TYPE_RAYTRACING_SHADER_CONFIG: D3D12_STATE_SUBOBJECT_TYPE = 9;
+const D3D12_STATE_SUBOBJECT_TYPE_RAYTRACING_PIPELINE_CONFIG: D3D12_STATE_SUBOBJECT_TYPE = 10;
+const D3D12_STATE_SUBOBJECT_TYPE_HIT_GROUP: D3D12_STATE_SUBOBJECT_TYPE = 11;
+
+type D3D12_HIT_GROUP_TYPE = u32;
+const D3D12_HIT_GROUP_TYPE_TRIANGLES: D3D12_HIT_GROUP_TYPE = 0;
+const D3D12_HIT_GROUP_TYPE_PROCEDURAL_PRIMITIVE: D3D12_HIT_GROUP_TYPE = 1;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct D3D12_FEATURE_DATA_D3D12_OPTIONS5 {
+    SRVOnlyTiledResourceTier3: BOOL,
+    RenderPassesTier: u32,
+    RaytracingTier: D3D12_RAYTRACING_TIER,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct D3D12_GPU_VIRTUAL_ADDRESS_AND_STRIDE {
+    StartAddress: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    StrideInBytes: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct D3D12_GPU_VIRTUAL_ADDRESS_RANGE {
+    StartAddress: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    SizeInBytes: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct D3D12_GPU_VIRTUAL_ADDRESS_RANGE_AND_STRIDE {
+    StartAddress: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    SizeInBytes: u64,
+    StrideInBytes: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct D3D12_RAYTRACING_GEOMETRY_TRIANGLES_DESC {
+    Transform3x4: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    IndexFormat: dxgiformat::DXGI_FORMAT,
+    VertexFormat: dxgiformat::DXGI_FORMAT,
+    IndexCount: UINT,
+    VertexCount: UINT,
+    IndexBuffer: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    VertexBuffer: D3D12_GPU_VIRTUAL_ADDRESS_AND_STRIDE,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct D3D12_RAYTRACING_GEOMETRY_AABBS_DESC {
+    AABBCount: u64,
+    AABBs: D3D12_GPU_VIRTUAL_ADDRESS_AND_STRIDE,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union D3D12_RAYTRACING_GEOMETRY_DESC_u {
+    Triangles: D3D12_RAYTRACING_GEOMETRY_TRIANGLES_DESC,
+    AABBs: D3D12_RAYTRACING_GEOMETRY_AABBS_DESC,
+}
+
+#[repr(C)]
+pub struct D3D12_RAYTRACING_GEOMETRY_DESC {
+    Type: D3D12_RAYTRACING_GEOMETRY_TYPE,
+    Flags: u32,
+    u: D3D12_RAYTRACING_GEOMETRY_DESC_u,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS_u {
+    InstanceDescs: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    pGeometryDescs: *const D3D12_RAYTRACING_GEOMETRY_DESC,
+}
+
+#[repr(C)]
+pub struct D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS {
+    Type: D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE,
+    Flags: u32,
+    NumDescs: UINT,
+    DescsLayout: u32,
+    u: D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS_u,
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct D3D12_RAYTRACING_ACCELERATION_STRUCTURE_PREBUILD_INFO {
+    ResultDataMaxSizeInBytes: u64,
+    ScratchDataSizeInBytes: u64,
+    UpdateScratchDataSizeInBytes: u64,
+}
+
+#[repr(C)]
+pub struct D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_DESC {
+    pub DestAccelerationStructureData: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    pub Inputs: D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS,
+    pub SourceAccelerationStructureData: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    pub ScratchAccelerationStructureData: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+}
+
+#[repr(C)]
+pub struct D3D12_DISPATCH_RAYS_DESC {
+    RayGenerationShaderRecord: D3D12_GPU_VIRTUAL_ADDRESS_RANGE,
+    MissShaderTable: D3D12_GPU_VIRTUAL_ADDRESS_RANGE_AND_STRIDE,
+    HitGroupTable: D3D12_GPU_VIRTUAL_ADDRESS_RANGE_AND_STRIDE,
+    CallableShaderTable: D3D12_GPU_VIRTUAL_ADDRESS_RANGE_AND_STRIDE,
+    Width: UINT,
+    Height: UINT,
+    Depth: UINT,
+}
+
+#[repr(C)]
+struct D3D12_STATE_SUBOBJECT {
+    Type: D3D12_STATE_SUBOBJECT_TYPE,
+    pDesc: *const c_void,
+}
+
+#[repr(C)]
+struct D3D12_STATE_OBJECT_DESC {
+    Type: D3D12_STATE_OBJECT_TYPE,
+    NumSubobjects: UINT,
+    pSubobjects: *const D3D12_STATE_SUBOBJECT,
+}
+
+#[repr(C)]
+struct D3D12_EXPORT_DESC {
+    Name: LPCWSTR,
+    ExportToRename: LPCWSTR,
+    Flags: u32,
+}
+
+#[repr(C)]
+struct D3D12_DXIL_LIBRARY_DESC {
+    DXILLibrary: d3d12::D3D12_SHADER_BYTECODE,
+    NumExports: UINT,
+    pExports: *const D3D12_EXPORT_DESC,
+}
+
+#[repr(C)]
+struct D3D12_HIT_GROUP_DESC {
+    HitGroupExport: LPCWSTR,
+    Type: D3D12_HIT_GROUP_TYPE,
+    AnyHitShaderImport: LPCWSTR,
+    ClosestHitShaderImport: LPCWSTR,
+    IntersectionShaderImport: LPCWSTR,
+}
+
+#[repr(C)]
+struct D3D12_RAYTRACING_SHADER_CONFIG {
+    MaxPayloadSizeInBytes: UINT,
+    MaxAttributeSizeInBytes: UINT,
+}
+
+#[repr(C)]
+struct D3D12_RAYTRACING_PIPELINE_CONFIG {
+    MaxTraceRecursionDepth: UINT,
+}
+
+#[repr(C)]
+struct D3D12_GLOBAL_ROOT_SIGNATURE {
+    pGlobalRootSignature: *mut d3d12::ID3D12RootSignature,
+}
+
+/// Minimal view of `ID3D12Device5`, with the methods creating the ray tracing
+/// pipelines and sizing the acceleration structures.
+#[repr(C)]
+struct ID3D12Device5Vtbl {
+    // IUnknown (3), ID3D12Object (4), ID3D12Device (37), ID3D12Device1..4 (3 + 1 + 3 + 6),
+    // and the lifetime tracker and meta command methods of ID3D12Device5 (5).
+    parent: [*const c_void; 62],
+    CreateStateObject: unsafe extern "system" fn(
+        This: *mut ID3D12Device5,
+        pDesc: *const D3D12_STATE_OBJECT_DESC,
+        riid: REFIID,
+        ppStateObject: *mut *mut c_void,
+    ) -> HRESULT,
+    GetRaytracingAccelerationStructurePrebuildInfo: unsafe extern "system" fn(
+        This: *mut ID3D12Device5,
+        pDesc: *const D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS,
+        pInfo: *mut D3D12_RAYTRACING_ACCELERATION_STRUCTURE_PREBUILD_INFO,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12Device5 {
+    lpVtbl: *const ID3D12Device5Vtbl,
+}
+
+impl Interface for ID3D12Device5 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x8b4f173b,
+            Data2: 0x2fea,
+            Data3: 0x4b80,
+            Data4: [0x8f, 0x58, 0x43, 0x07, 0x19, 0x1a, 0xb9, 0x5d],
+        }
+    }
+}
+
+/// Minimal view of `ID3D12GraphicsCommandList4`, with the ray tracing commands.
+#[repr(C)]
+struct ID3D12GraphicsCommandList4Vtbl {
+    // IUnknown (3), ID3D12Object (4), ID3D12DeviceChild (1), ID3D12CommandList (1),
+    // ID3D12GraphicsCommandList (51), ID3D12GraphicsCommandList1..3 (6 + 1 + 1),
+    // and the render pass and meta command methods of ID3D12GraphicsCommandList4 (4).
+    parent: [*const c_void; 72],
+    BuildRaytracingAccelerationStructure: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList4,
+        pDesc: *const D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_DESC,
+        NumPostbuildInfoDescs: UINT,
+        pPostbuildInfoDescs: *const c_void,
+    ),
+    EmitRaytracingAccelerationStructurePostbuildInfo: *const c_void,
+    CopyRaytracingAccelerationStructure: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList4,
+        DestAccelerationStructureData: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+        SourceAccelerationStructureData: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+        Mode: D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE,
+    ),
+    SetPipelineState1: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList4,
+        pStateObject: *mut ID3D12StateObject,
+    ),
+    DispatchRays: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList4,
+        pDesc: *const D3D12_DISPATCH_RAYS_DESC,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12GraphicsCommandList4 {
+    lpVtbl: *const ID3D12GraphicsCommandList4Vtbl,
+}
+
+impl Interface for ID3D12GraphicsCommandList4 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x8754318e,
+            Data2: 0xd3a9,
+            Data3: 0x4541,
+            Data4: [0x98, 0xcf, 0x64, 0x5b, 0x50, 0xdc, 0x48, 0x74],
+        }
+    }
+}
+
+/// Ray tracing pipeline. We never call any of its methods, its shader
+/// identifiers are queried through `ID3D12StateObjectProperties`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ID3D12StateObject {
+    lpVtbl: *const c_void,
+}
+
+impl Interface for ID3D12StateObject {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x47016943,
+            Data2: 0xfca8,
+            Data3: 0x4594,
+            Data4: [0x93, 0xea, 0xaf, 0x25, 0x8b, 0x55, 0x34, 0x6d],
+        }
+    }
+}
+
+pub type StateObject = native::WeakPtr<ID3D12StateObject>;
+
+#[repr(C)]
+struct ID3D12StateObjectPropertiesVtbl {
+    // IUnknown (3).
+    parent: [*const c_void; 3],
+    GetShaderIdentifier: unsafe extern "system" fn(
+        This: *mut ID3D12StateObjectProperties,
+        pExportName: LPCWSTR,
+    ) -> *mut c_void,
+}
+
+#[repr(C)]
+struct ID3D12StateObjectProperties {
+    lpVtbl: *const ID3D12StateObjectPropertiesVtbl,
+}
+
+impl Interface for ID3D12StateObjectProperties {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0xde5fa827,
+            Data2: 0x9bf9,
+            Data3: 0x4f26,
+            Data4: [0x89, 0xff, 0xd7, 0xf5, 0x6f, 0xde, 0x38, 0x60],
+        }
+    }
+}
+
+/// Query the interface `T` of `object`, which is known to be supported.
+unsafe fn query<T: Interface>(object: *mut IUnknown) -> *mut T {
+    let mut raw: *mut T = ptr::null_mut();
+    let hr = (*object).QueryInterface(&T::uuidof(), &mut raw as *mut *mut _ as *mut *mut c_void);
+    assert_eq!(
+        winerror::S_OK,
+        hr,
+        "Ray tracing was reported, but the interface isn't supported"
+    );
+    raw
+}
+
+unsafe fn release<T>(object: *mut T) {
+    (*(object as *mut IUnknown)).Release();
+}
+
+/// Check if the device supports acceleration structures and ray tracing pipelines.
+pub fn is_supported(device: native::Device) -> bool {
+    let mut options = D3D12_FEATURE_DATA_D3D12_OPTIONS5::default();
+    let hr = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_D3D12_OPTIONS5,
+            &mut options as *mut _ as *mut _,
+            mem::size_of::<D3D12_FEATURE_DATA_D3D12_OPTIONS5>() as _,
+        )
+    };
+    winerror::SUCCEEDED(hr) && options.RaytracingTier >= D3D12_RAYTRACING_TIER_1_0
+}
+
+/// GPU virtual address of a location in a buffer.
+pub fn address(
+    (buffer, offset): &(&r::Buffer, hal::buffer::Offset),
+) -> d3d12::D3D12_GPU_VIRTUAL_ADDRESS {
+    buffer.expect_bound().resource.gpu_virtual_address() + offset
+}
+
+/// Translate the geometries of a bottom level acceleration structure.
+/// Top level ones have no geometry descriptions, see `map_inputs`.
+pub fn map_geometries(info: &accel::GeometryInfo<Backend>) -> Vec<D3D12_RAYTRACING_GEOMETRY_DESC> {
+    info.geometries
+        .iter()
+        .filter_map(|desc| {
+            let (ty, u) = match desc.geometry {
+                accel::Geometry::Triangles(ref triangles) => {
+                    let (index_format, index_count, index_buffer) = match triangles.index_buffer {
+                        Some((buffer, offset, ty)) => (
+                            match ty {
+                                IndexType::U16 => dxgiformat::DXGI_FORMAT_R16_UINT,
+                                IndexType::U32 => dxgiformat::DXGI_FORMAT_R32_UINT,
+                            },
+                            desc.primitive_count * 3,
+                            address(&(buffer, offset)),
+                        ),
+                        None => (dxgiformat::DXGI_FORMAT_UNKNOWN, 0, 0),
+                    };
+                    let raw = D3D12_RAYTRACING_GEOMETRY_TRIANGLES_DESC {
+                        Transform3x4: triangles.transform.as_ref().map_or(0, address),
+                        IndexFormat: index_format,
+                        VertexFormat: conv::map_format(triangles.vertex_format)
+                            .expect("Unsupported vertex format"),
+                        IndexCount: index_count,
+                        // Without indices, the triangles are made of consecutive vertices.
+                        VertexCount: if index_count == 0 {
+                            desc.primitive_count * 3
+                        } else {
+                            triangles.vertex_count
+                        },
+                        IndexBuffer: index_buffer,
+                        VertexBuffer: D3D12_GPU_VIRTUAL_ADDRESS_AND_STRIDE {
+                            StartAddress: address(&triangles.vertex_buffer),
+                            StrideInBytes: triangles.vertex_stride as u64,
+                        },
+                    };
+                    (
+                        D3D12_RAYTRACING_GEOMETRY_TYPE_TRIANGLES,
+                        D3D12_RAYTRACING_GEOMETRY_DESC_u { Triangles: raw },
+                    )
+                }
+                accel::Geometry::Aabbs { ref buffer, stride } => (
+                    D3D12_RAYTRACING_GEOMETRY_TYPE_PROCEDURAL_PRIMITIVE_AABBS,
+                    D3D12_RAYTRACING_GEOMETRY_DESC_u {
+                        AABBs: D3D12_RAYTRACING_GEOMETRY_AABBS_DESC {
+                            AABBCount: desc.primitive_count as u64,
+                            AABBs: D3D12_GPU_VIRTUAL_ADDRESS_AND_STRIDE {
+                                StartAddress: address(buffer),
+                                StrideInBytes: stride as u64,
+                            },
+                        },
+                    },
+                ),
+                accel::Geometry::Instances { .. } => return None,
+            };
+            Some(D3D12_RAYTRACING_GEOMETRY_DESC {
+                Type: ty,
+                // Same values as `accel::GeometryFlags`.
+                Flags: desc.flags.bits(),
+                u,
+            })
+        })
+        .collect()
+}
+
+/// Describe the inputs of an acceleration structure build, referencing
+/// `geometries`, as returned by `map_geometries`.
+pub fn map_inputs(
+    info: &accel::GeometryInfo<Backend>,
+    geometries: &[D3D12_RAYTRACING_GEOMETRY_DESC],
+    update: bool,
+) -> D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS {
+    let flags = if update {
+        info.flags.bits() | D3D12_RAYTRACING_ACCELERATION_STRUCTURE_BUILD_FLAG_PERFORM_UPDATE
+    } else {
+        info.flags.bits()
+    };
+    match info.ty {
+        accel::Type::TopLevel => {
+            let (num_descs, instances) = match info.geometries {
+                [accel::GeometryDesc {
+                    geometry: accel::Geometry::Instances { buffer },
+                    primitive_count,
+                    ..
+                }] => (*primitive_count, address(buffer)),
+                _ => panic!(
+                    "Top level acceleration structures must have a single geometry of instances"
+                ),
+            };
+            D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS {
+                Type: D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE_TOP_LEVEL,
+                Flags: flags,
+                NumDescs: num_descs,
+                DescsLayout: D3D12_ELEMENTS_LAYOUT_ARRAY,
+                u: D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS_u {
+                    InstanceDescs: instances,
+                },
+            }
+        }
+        accel::Type::BottomLevel => D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS {
+            Type: D3D12_RAYTRACING_ACCELERATION_STRUCTURE_TYPE_BOTTOM_LEVEL,
+            Flags: flags,
+            NumDescs: geometries.len() as _,
+            DescsLayout: D3D12_ELEMENTS_LAYOUT_ARRAY,
+            u: D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS_u {
+                pGeometryDescs: geometries.as_ptr(),
+            },
+        },
+    }
+}
+
+/// Get the sizes of the buffers needed to build an acceleration structure.
+pub unsafe fn build_requirements(
+    device: native::Device,
+    inputs: &D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_INPUTS,
+) -> accel::SizeRequirements {
+    let device5 = query::<ID3D12Device5>(device.as_mut_ptr() as *mut IUnknown);
+    let mut info = D3D12_RAYTRACING_ACCELERATION_STRUCTURE_PREBUILD_INFO::default();
+    ((*(*device5).lpVtbl).GetRaytracingAccelerationStructurePrebuildInfo)(
+        device5, inputs, &mut info,
+    );
+    release(device5);
+    accel::SizeRequirements {
+        acceleration_structure_size: info.ResultDataMaxSizeInBytes,
+        build_scratch_size: info.ScratchDataSizeInBytes,
+        update_scratch_size: info.UpdateScratchDataSizeInBytes,
+    }
+}
+
+/// Write a shader resource view of the acceleration structure at `location`.
+pub unsafe fn create_view(
+    device: native::Device,
+    location: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+    handle: d3d12::D3D12_CPU_DESCRIPTOR_HANDLE,
+) {
+    let mut desc = d3d12::D3D12_SHADER_RESOURCE_VIEW_DESC {
+        Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
+        Shader4ComponentMapping: device::IDENTITY_MAPPING,
+        ViewDimension: D3D12_SRV_DIMENSION_RAYTRACING_ACCELERATION_STRUCTURE,
+        u: mem::zeroed(),
+    };
+    // `D3D12_RAYTRACING_ACCELERATION_STRUCTURE_SRV` only holds the location,
+    // which overlaps the first element of the buffer view.
+    desc.u.Buffer_mut().FirstElement = location;
+    // The view isn't backed by a resource, the location is enough.
+    device.CreateShaderResourceView(ptr::null_mut(), &desc, handle);
+}
+
+/// DXIL library exporting a single shader of a ray tracing pipeline. Entry points
+/// of different libraries may have the same name, so each one is exported under
+/// a name unique to the pipeline.
+pub struct Library {
+    pub bytecode: d3d12::D3D12_SHADER_BYTECODE,
+    pub entry: Vec<u16>,
+    pub export: Vec<u16>,
+}
+
+/// Hit group of a ray tracing pipeline, with the indices of the libraries of its shaders.
+pub struct HitGroup {
+    pub export: Vec<u16>,
+    pub procedural: bool,
+    pub closest_hit: Option<usize>,
+    pub any_hit: Option<usize>,
+    pub intersection: Option<usize>,
+}
+
+/// Create a ray tracing pipeline out of its libraries and hit groups.
+pub unsafe fn create_pipeline(
+    device: native::Device,
+    signature: native::RootSignature,
+    libraries: &[Library],
+    hit_groups: &[HitGroup],
+    desc: &pso::RayTracingPipelineDesc<Backend>,
+) -> (StateObject, HRESULT) {
+    let exports = libraries
+        .iter()
+        .map(|library| D3D12_EXPORT_DESC {
+            Name: library.export.as_ptr(),
+            ExportToRename: library.entry.as_ptr(),
+            Flags: 0,
+        })
+        .collect::<Vec<_>>();
+    let library_descs = libraries
+        .iter()
+        .zip(exports.iter())
+        .map(|(library, export)| D3D12_DXIL_LIBRARY_DESC {
+            DXILLibrary: library.bytecode,
+            NumExports: 1,
+            pExports: export,
+        })
+        .collect::<Vec<_>>();
+    let import =
+        |library: Option<usize>| library.map_or(ptr::null(), |i| libraries[i].export.as_ptr());
+    let hit_group_descs = hit_groups
+        .iter()
+        .map(|group| D3D12_HIT_GROUP_DESC {
+            HitGroupExport: group.export.as_ptr(),
+            Type: if group.procedural {
+                D3D12_HIT_GROUP_TYPE_PROCEDURAL_PRIMITIVE
+            } else {
+                D3D12_HIT_GROUP_TYPE_TRIANGLES
+            },
+            AnyHitShaderImport: import(group.any_hit),
+            ClosestHitShaderImport: import(group.closest_hit),
+            IntersectionShaderImport: import(group.intersection),
+        })
+        .collect::<Vec<_>>();
+    // The configurations aren't associated to any export, making them
+    // the default of all the shaders of the pipeline.
+    let shader_config = D3D12_RAYTRACING_SHADER_CONFIG {
+        MaxPayloadSizeInBytes: desc.max_payload_size,
+        MaxAttributeSizeInBytes: desc.max_attribute_size,
+    };
+    let pipeline_config = D3D12_RAYTRACING_PIPELINE_CONFIG {
+        MaxTraceRecursionDepth: desc.max_recursion_depth,
+    };
+    let root_signature = D3D12_GLOBAL_ROOT_SIGNATURE {
+        pGlobalRootSignature: signature.as_mut_ptr(),
+    };
+
+    let subobject = |ty, data: *const c_void| D3D12_STATE_SUBOBJECT {
+        Type: ty,
+        pDesc: data,
+    };
+    let subobjects = library_descs
+        .iter()
+        .map(|desc| {
+            subobject(
+                D3D12_STATE_SUBOBJECT_TYPE_DXIL_LIBRARY,
+                desc as *const _ as _,
+            )
+        })
+        .chain(
+            hit_group_descs
+                .iter()
+                .map(|desc| subobject(D3D12_STATE_SUBOBJECT_TYPE_HIT_GROUP, desc as *const _ as _)),
+        )
+        .chain(iter::once(subobject(
+            D3D12_STATE_SUBOBJECT_TYPE_RAYTRACING_SHADER_CONFIG,
+            &shader_config as *const _ as _,
+        )))
+        .chain(iter::once(subobject(
+            D3D12_STATE_SUBOBJECT_TYPE_RAYTRACING_PIPELINE_CONFIG,
+            &pipeline_config as *const _ as _,
+        )))
+        .chain(iter::once(subobject(
+            D3D12_STATE_SUBOBJECT_TYPE_GLOBAL_ROOT_SIGNATURE,
+            &root_signature as *const _ as _,
+        )))
+        .collect::<Vec<_>>();
+    let state_object_desc = D3D12_STATE_OBJECT_DESC {
+        Type: D3D12_STATE_OBJECT_TYPE_RAYTRACING_PIPELINE,
+        NumSubobjects: subobjects.len() as _,
+        pSubobjects: subobjects.as_ptr(),
+    };
+
+    let device5 = query::<ID3D12Device5>(device.as_mut_ptr() as *mut IUnknown);
+    let mut state_object = StateObject::null();
+    let hr = ((*(*device5).lpVtbl).CreateStateObject)(
+        device5,
+        &state_object_desc,
+        &ID3D12StateObject::uuidof(),
+        state_object.mut_void(),
+    );
+    release(device5);
+    (state_object, hr)
+}
+
+/// Get the identifier of the shader or hit group exported as `export`.
+pub unsafe fn shader_identifier(
+    state_object: StateObject,
+    export: &[u16],
+) -> [u8; D3D12_SHADER_IDENTIFIER_SIZE_IN_BYTES] {
+    let properties =
+        query::<ID3D12StateObjectProperties>(state_object.as_mut_ptr() as *mut IUnknown);
+    let identifier = ((*(*properties).lpVtbl).GetShaderIdentifier)(properties, export.as_ptr());
+    release(properties);
+    assert!(!identifier.is_null(), "Missing shader export");
+    *(identifier as *const [u8; D3D12_SHADER_IDENTIFIER_SIZE_IN_BYTES])
+}
+
+/// Describe a ray dispatch of `count` rays, with the given shader binding tables.
+pub fn map_dispatch(
+    tables: &pso::ShaderBindingTables<Backend>,
+    count: WorkGroupCount,
+) -> D3D12_DISPATCH_RAYS_DESC {
+    let table = |table: &Option<pso::ShaderBindingTable<Backend>>| match *table {
+        Some(ref table) => D3D12_GPU_VIRTUAL_ADDRESS_RANGE_AND_STRIDE {
+            StartAddress: address(&(table.buffer, table.offset)),
+            SizeInBytes: table.size,
+            StrideInBytes: table.stride as u64,
+        },
+        None => D3D12_GPU_VIRTUAL_ADDRESS_RANGE_AND_STRIDE {
+            StartAddress: 0,
+            SizeInBytes: 0,
+            StrideInBytes: 0,
+        },
+    };
+    D3D12_DISPATCH_RAYS_DESC {
+        RayGenerationShaderRecord: D3D12_GPU_VIRTUAL_ADDRESS_RANGE {
+            StartAddress: address(&(tables.ray_generation.buffer, tables.ray_generation.offset)),
+            SizeInBytes: tables.ray_generation.size,
+        },
+        MissShaderTable: table(&tables.miss),
+        HitGroupTable: table(&tables.hit),
+        CallableShaderTable: table(&tables.callable),
+        Width: count[0],
+        Height: count[1],
+        Depth: count[2],
+    }
+}
+
+/// Ray tracing commands of a command list.
+pub struct CommandList(*mut ID3D12GraphicsCommandList4);
+
+impl CommandList {
+    pub unsafe fn new(list: native::GraphicsCommandList) -> Self {
+        CommandList(query(list.as_mut_ptr() as *mut IUnknown))
+    }
+
+    pub unsafe fn build(&self, desc: &D3D12_BUILD_RAYTRACING_ACCELERATION_STRUCTURE_DESC) {
+        ((*(*self.0).lpVtbl).BuildRaytracingAccelerationStructure)(self.0, desc, 0, ptr::null());
+    }
+
+    pub unsafe fn copy(
+        &self,
+        dst: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+        src: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+        mode: accel::CopyMode,
+    ) {
+        let mode = match mode {
+            accel::CopyMode::Clone => D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE_CLONE,
+            accel::CopyMode::Compact => D3D12_RAYTRACING_ACCELERATION_STRUCTURE_COPY_MODE_COMPACT,
+        };
+        ((*(*self.0).lpVtbl).CopyRaytracingAccelerationStructure)(self.0, dst, src, mode);
+    }
+
+    pub unsafe fn set_pipeline_state(&self, state_object: StateObject) {
+        ((*(*self.0).lpVtbl).SetPipelineState1)(self.0, state_object.as_mut_ptr());
+    }
+
+    pub unsafe fn dispatch_rays(&self, desc: &D3D12_DISPATCH_RAYS_DESC) {
+        ((*(*self.0).lpVtbl).DispatchRays)(self.0, desc);
+    }
+}
+
+impl Drop for CommandList {
+    fn drop(&mut self) {
+        unsafe { release(self.0) }
+    }
+}
@@ -0,0 +1,86 @@
+//! Residency priorities of the heaps (`ID3D12Device1`).
+//!
+//! `SetResidencyPriority` is newer than what `winapi` exposes, so the interface
+//! is declared here by hand from `d3d12.h`.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use hal::memory::Priority;
+use winapi::{
+    ctypes::c_void,
+    shared::{guiddef::GUID, minwindef::UINT, winerror},
+    um::{d3d12, unknwnbase::IUnknown, winnt::HRESULT},
+    Interface,
+};
+
+use std::ptr;
+
+type D3D12_RESIDENCY_PRIORITY = u32;
+
+const D3D12_RESIDENCY_PRIORITY_MINIMUM: D3D12_RESIDENCY_PRIORITY = 0x2800_0000;
+const D3D12_RESIDENCY_PRIORITY_LOW: D3D12_RESIDENCY_PRIORITY = 0x5000_0000;
+const D3D12_RESIDENCY_PRIORITY_NORMAL: D3D12_RESIDENCY_PRIORITY = 0x7800_0000;
+const D3D12_RESIDENCY_PRIORITY_HIGH: D3D12_RESIDENCY_PRIORITY = 0xa001_0000;
+const D3D12_RESIDENCY_PRIORITY_MAXIMUM: D3D12_RESIDENCY_PRIORITY = 0xc800_0000;
+
+/// Minimal view of `ID3D12Device1`, with only the residency priority method.
+#[repr(C)]
+struct ID3D12Device1Vtbl {
+    // IUnknown (3), ID3D12Object (4), ID3D12Device (37), and the pipeline library
+    // and fence methods of ID3D12Device1 (2).
+    parent: [*const c_void; 46],
+    SetResidencyPriority: unsafe extern "system" fn(
+        This: *mut ID3D12Device1,
+        NumObjects: UINT,
+        ppObjects: *const *mut d3d12::ID3D12Pageable,
+        pPriorities: *const D3D12_RESIDENCY_PRIORITY,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct ID3D12Device1 {
+    lpVtbl: *const ID3D12Device1Vtbl,
+}
+
+impl Interface for ID3D12Device1 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x77acce80,
+            Data2: 0x638e,
+            Data3: 0x4e65,
+            Data4: [0x88, 0x95, 0xc1, 0xf2, 0x33, 0x86, 0x86, 0x3e],
+        }
+    }
+}
+
+fn map_priority(priority: Priority) -> D3D12_RESIDENCY_PRIORITY {
+    match priority {
+        Priority::Minimum => D3D12_RESIDENCY_PRIORITY_MINIMUM,
+        Priority::Low => D3D12_RESIDENCY_PRIORITY_LOW,
+        Priority::Normal => D3D12_RESIDENCY_PRIORITY_NORMAL,
+        Priority::High => D3D12_RESIDENCY_PRIORITY_HIGH,
+        Priority::Maximum => D3D12_RESIDENCY_PRIORITY_MAXIMUM,
+    }
+}
+
+/// Set the residency priority of a heap.
+///
+/// Runtimes predating `ID3D12Device1` have no priorities, and the hint is dropped.
+pub unsafe fn set_priority(device: native::Device, heap: native::Heap, priority: Priority) {
+    let mut device1: *mut ID3D12Device1 = ptr::null_mut();
+    let hr = (*(device.as_mut_ptr() as *mut IUnknown)).QueryInterface(
+        &ID3D12Device1::uuidof(),
+        &mut device1 as *mut *mut _ as *mut *mut c_void,
+    );
+    if !winerror::SUCCEEDED(hr) {
+        return;
+    }
+
+    let object = heap.as_mut_ptr() as *mut d3d12::ID3D12Pageable;
+    let priority = map_priority(priority);
+    let hr = ((*(*device1).lpVtbl).SetResidencyPriority)(device1, 1, &object, &priority);
+    if !winerror::SUCCEEDED(hr) {
+        warn!("Failed to set the residency priority of a heap: 0x{:X}", hr);
+    }
+    (*(device1 as *mut IUnknown)).Release();
+}
@@ -11,6 +11,7 @@ use std::{collections::BTreeMap, fmt, ops::Range, slice, sync::Arc};
 
 use crate::{
     descriptors_cpu::{Handle, MultiCopyAccumulator},
+    ray_tracing,
     root_constants::RootConstant,
     Backend, DescriptorIndex, MAX_VERTEX_BUFFERS,
 };
@@ -62,8 +63,11 @@ pub struct SubpassDesc {
     pub(crate) depth_stencil_attachment: Option<pass::AttachmentRef>,
     pub(crate) input_attachments: Vec<pass::AttachmentRef>,
     pub(crate) resolve_attachments: Vec<pass::AttachmentRef>,
+    pub(crate) shading_rate_attachment: Option<pass::AttachmentRef>,
     pub(crate) pre_barriers: Vec<BarrierDesc>,
     pub(crate) post_barriers: Vec<BarrierDesc>,
+    // Array layers rendered by view instancing, zero without it.
+    pub(crate) view_mask: u32,
 }
 
 impl SubpassDesc {
@@ -75,6 +79,7 @@ impl SubpassDesc {
             .chain(self.depth_stencil_attachment.iter())
             .chain(self.input_attachments.iter())
             .chain(self.resolve_attachments.iter())
+            .chain(self.shading_rate_attachment.iter())
             .any(|&(id, _)| id == at_id)
     }
 }
@@ -107,6 +112,7 @@ pub struct GraphicsPipeline {
     pub(crate) topology: d3d12::D3D12_PRIMITIVE_TOPOLOGY,
     pub(crate) vertex_bindings: [Option<VertexBinding>; MAX_VERTEX_BUFFERS],
     pub(crate) baked_states: pso::BakedStates,
+    pub(crate) dynamic_states: hal::DynamicStates,
 }
 unsafe impl Send for GraphicsPipeline {}
 unsafe impl Sync for GraphicsPipeline {}
@@ -120,6 +126,17 @@ pub struct ComputePipeline {
 unsafe impl Send for ComputePipeline {}
 unsafe impl Sync for ComputePipeline {}
 
+#[derive(Debug)]
+pub struct RayTracingPipeline {
+    pub(crate) raw: ray_tracing::StateObject,
+    pub(crate) shared: Arc<PipelineShared>,
+    /// Shader identifiers of the groups, in order.
+    pub(crate) group_handles: Vec<[u8; ray_tracing::D3D12_SHADER_IDENTIFIER_SIZE_IN_BYTES]>,
+}
+
+unsafe impl Send for RayTracingPipeline {}
+unsafe impl Sync for RayTracingPipeline {}
+
 bitflags! {
     pub struct SetTableTypes: u8 {
         const SRV_CBV_UAV = 0x1;
@@ -182,6 +199,7 @@ pub struct BufferUnbound {
 pub struct BufferBound {
     pub(crate) resource: native::Resource,
     pub(crate) requirements: memory::Requirements,
+    pub(crate) usage: buffer::Usage,
     pub(crate) clear_uav: Option<Handle>,
 }
 
@@ -242,6 +260,8 @@ unsafe impl Sync for BufferView {}
 pub enum Place {
     Heap { raw: native::Heap, offset: u64 },
     Swapchain {},
+    // Resource opened from a handle shared by another device or API.
+    Dedicated {},
 }
 
 #[derive(Clone)]
@@ -250,6 +270,9 @@ pub struct ImageBound {
     pub(crate) place: Place,
     pub(crate) surface_type: format::SurfaceType,
     pub(crate) kind: image::Kind,
+    // First mip level backed by the resource, subresource indices are relative to it.
+    pub(crate) base_level: image::Level,
+    // One past the last mip level backed by the resource.
     pub(crate) mip_levels: image::Level,
     pub(crate) default_view_format: Option<DXGI_FORMAT>,
     pub(crate) view_caps: image::ViewCapabilities,
@@ -269,9 +292,34 @@ impl fmt::Debug for ImageBound {
 unsafe impl Send for ImageBound {}
 unsafe impl Sync for ImageBound {}
 
+/// Index a mip level among the levels backed by the resource.
+///
+/// Commands reject levels outside of the image with `ImageBound::contains_level`,
+/// so this only guards against wrapping around on the remaining paths.
+fn relative_level(mip_level: UINT, base_level: image::Level) -> UINT {
+    mip_level
+        .checked_sub(base_level as UINT)
+        .unwrap_or_else(|| {
+            error!(
+                "Mip level {} is below the base level {} of the image",
+                mip_level, base_level
+            );
+            0
+        })
+}
+
 impl ImageBound {
+    /// Check if a mip level is backed by the resource.
+    pub fn contains_level(&self, mip_level: image::Level) -> bool {
+        self.base_level <= mip_level && mip_level < self.mip_levels
+    }
+
+    pub fn relative_level(&self, mip_level: UINT) -> UINT {
+        relative_level(mip_level, self.base_level)
+    }
+
     pub fn calc_subresource(&self, mip_level: UINT, layer: UINT, plane: UINT) -> UINT {
-        mip_level
+        self.relative_level(mip_level)
             + (layer * self.descriptor.MipLevels as UINT)
             + (plane * self.descriptor.MipLevels as UINT * self.kind.num_layers() as UINT)
     }
@@ -285,6 +333,7 @@ pub struct ImageUnbound {
     pub(crate) requirements: memory::Requirements,
     pub(crate) format: format::Format,
     pub(crate) kind: image::Kind,
+    pub(crate) base_level: image::Level,
     pub(crate) mip_levels: image::Level,
     pub(crate) usage: image::Usage,
     pub(crate) tiling: image::Tiling,
@@ -304,7 +353,7 @@ impl fmt::Debug for ImageUnbound {
 
 impl ImageUnbound {
     pub fn calc_subresource(&self, mip_level: UINT, layer: UINT, plane: UINT) -> UINT {
-        mip_level
+        relative_level(mip_level, self.base_level)
             + (layer * self.desc.MipLevels as UINT)
             + (plane * self.desc.MipLevels as UINT * self.kind.num_layers() as UINT)
     }
@@ -379,6 +428,7 @@ pub struct ImageView {
     // Required for attachment resolves.
     pub(crate) dxgi_format: DXGI_FORMAT,
     pub(crate) num_levels: image::Level,
+    // Relative to the first mip level backed by the image resource.
     pub(crate) mip_levels: (image::Level, image::Level),
     pub(crate) layers: (image::Layer, image::Layer),
     pub(crate) kind: image::Kind,
@@ -520,7 +570,7 @@ impl From<pso::DescriptorType> for DescriptorContent {
                     Bdf::Texel => Dc::SRV,
                 },
             },
-            Dt::InputAttachment => Dc::SRV,
+            Dt::InputAttachment | Dt::AccelerationStructure => Dc::SRV,
         }
     }
 }
@@ -766,10 +816,12 @@ pub struct DescriptorPool {
 unsafe impl Send for DescriptorPool {}
 unsafe impl Sync for DescriptorPool {}
 
-impl pso::DescriptorPool<Backend> for DescriptorPool {
-    unsafe fn allocate_one(
+impl DescriptorPool {
+    /// Allocate a set, with `variable_count` descriptors in its variable count binding.
+    unsafe fn allocate_set(
         &mut self,
         layout: &DescriptorSetLayout,
+        variable_count: Option<pso::DescriptorArrayIndex>,
     ) -> Result<DescriptorSet, pso::AllocationError> {
         let mut binding_infos = Vec::new();
         let mut first_gpu_view = None;
@@ -781,6 +833,14 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
             while binding_infos.len() <= binding.binding as usize {
                 binding_infos.push(DescriptorBindingInfo::default());
             }
+            let count = if binding
+                .flags
+                .contains(pso::DescriptorBindingFlags::VARIABLE_COUNT)
+            {
+                variable_count.unwrap_or(binding.count)
+            } else {
+                binding.count
+            };
             let content = DescriptorContent::from(binding.ty);
             debug!("\tbinding {:?} with content {:?}", binding, content);
 
@@ -789,18 +849,18 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
                     content: content ^ DescriptorContent::DYNAMIC,
                     gpu_buffer_location: 0,
                 };
-                (None, vec![descriptor; binding.count])
+                (None, vec![descriptor; count])
             } else {
                 if content.contains(DescriptorContent::SAMPLER) {
-                    num_samplers += binding.count;
+                    num_samplers += count;
                 }
 
                 let view_range = if content.intersects(DescriptorContent::VIEW) {
                     let count = if content.contains(DescriptorContent::SRV | DescriptorContent::UAV)
                     {
-                        2 * binding.count as u64
+                        2 * count as u64
                     } else {
-                        binding.count as u64
+                        count as u64
                     };
                     debug!("\tview handles: {}", count);
                     let handle = self
@@ -823,7 +883,7 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
             };
 
             binding_infos[binding.binding as usize] = DescriptorBindingInfo {
-                count: binding.count as _,
+                count: count as _,
                 view_range,
                 dynamic_descriptors,
                 content,
@@ -841,6 +901,23 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
             raw_name: Vec::new(),
         })
     }
+}
+
+impl pso::DescriptorPool<Backend> for DescriptorPool {
+    unsafe fn allocate_one(
+        &mut self,
+        layout: &DescriptorSetLayout,
+    ) -> Result<DescriptorSet, pso::AllocationError> {
+        self.allocate_set(layout, None)
+    }
+
+    unsafe fn allocate_variable(
+        &mut self,
+        layout: &DescriptorSetLayout,
+        count: pso::DescriptorArrayIndex,
+    ) -> Result<DescriptorSet, pso::AllocationError> {
+        self.allocate_set(layout, Some(count))
+    }
 
     unsafe fn free<I>(&mut self, descriptor_sets: I)
     where
@@ -870,3 +947,9 @@ pub struct QueryPool {
 
 unsafe impl Send for QueryPool {}
 unsafe impl Sync for QueryPool {}
+
+#[derive(Debug)]
+pub struct AccelerationStructure {
+    /// GPU virtual address of the acceleration structure in its buffer.
+    pub(crate) location: d3d12::D3D12_GPU_VIRTUAL_ADDRESS,
+}
@@ -0,0 +1,145 @@
+//! Deferred release of the native objects.
+//!
+//! Releasing a `WeakPtr` destroys the object immediately, while the command lists
+//! executing on the queues may still reference it. Instead of releasing them when
+//! the user destroys a resource, the objects are retired together with the last
+//! value signaled on the timeline fence of every queue, and are only released once
+//! all the queues have completed these submissions.
+//!
+//! This complements the frame based destruction of `hal::frame`: resources retired
+//! by the user at the end of a frame may still be destroyed before all the queues
+//! are done with them, e.g. if they are used by an async compute queue.
+
+use parking_lot::Mutex;
+use smallvec::SmallVec;
+use winapi::shared::winerror;
+
+use std::{collections::VecDeque, fmt};
+
+use crate::ray_tracing;
+
+/// Native object waiting to be released.
+pub(crate) enum Retired {
+    Resource(native::Resource),
+    Heap(native::Heap),
+    PipelineState(native::PipelineState),
+    RootSignature(native::RootSignature),
+    QueryHeap(native::QueryHeap),
+    StateObject(ray_tracing::StateObject),
+}
+
+impl Retired {
+    unsafe fn release(self) {
+        match self {
+            Retired::Resource(raw) => raw.destroy(),
+            Retired::Heap(raw) => raw.destroy(),
+            Retired::PipelineState(raw) => raw.destroy(),
+            Retired::RootSignature(raw) => raw.destroy(),
+            Retired::QueryHeap(raw) => raw.destroy(),
+            Retired::StateObject(raw) => raw.destroy(),
+        }
+    }
+}
+
+struct Timeline {
+    fence: native::Fence,
+    /// Value signaled after the last submission of the queue.
+    submitted: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    timelines: Vec<Timeline>,
+    /// Retired objects, with the values of the timelines to wait for.
+    pending: VecDeque<(SmallVec<[u64; 4]>, Retired)>,
+}
+
+/// Retirement list shared by the device and its queues.
+#[derive(Default)]
+pub(crate) struct Retirement {
+    inner: Mutex<Inner>,
+}
+
+impl fmt::Debug for Retirement {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("Retirement")
+    }
+}
+
+unsafe impl Send for Retirement {}
+unsafe impl Sync for Retirement {}
+
+impl Retirement {
+    /// Register the timeline fence of a new queue, returning its index.
+    pub(crate) fn add_queue(&self, fence: native::Fence) -> usize {
+        let mut inner = self.inner.lock();
+        inner.timelines.push(Timeline {
+            fence,
+            submitted: 0,
+        });
+        inner.timelines.len() - 1
+    }
+
+    /// Advance the timeline of a queue after a submission, and release the
+    /// objects that aren't used anymore.
+    pub(crate) unsafe fn signal(
+        &self,
+        timeline: usize,
+        queue: native::CommandQueue,
+    ) -> winerror::HRESULT {
+        let mut inner = self.inner.lock();
+        let hr = {
+            let timeline = &mut inner.timelines[timeline];
+            timeline.submitted += 1;
+            queue.signal(timeline.fence, timeline.submitted)
+        };
+        inner.release_completed();
+        hr
+    }
+
+    /// Retire an object, released once all the queues are done with the
+    /// submissions made so far.
+    pub(crate) unsafe fn retire(&self, object: Retired) {
+        let mut inner = self.inner.lock();
+        let values = inner.timelines.iter().map(|t| t.submitted).collect();
+        inner.pending.push_back((values, object));
+        inner.release_completed();
+    }
+
+    /// Release all the retired objects, the queues having to be idle.
+    pub(crate) unsafe fn release_all(&self) {
+        let mut inner = self.inner.lock();
+        for (_, object) in inner.pending.drain(..) {
+            object.release();
+        }
+    }
+
+    /// Release all the retired objects and the timeline fences.
+    pub(crate) unsafe fn destroy(&self) {
+        self.release_all();
+        for timeline in self.inner.lock().timelines.drain(..) {
+            timeline.fence.destroy();
+        }
+    }
+}
+
+impl Inner {
+    unsafe fn release_completed(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let completed = self
+            .timelines
+            .iter()
+            .map(|t| t.fence.GetCompletedValue())
+            .collect::<SmallVec<[u64; 4]>>();
+        // The objects are retired in order, so the values only increase.
+        while let Some((values, _)) = self.pending.front() {
+            if values.iter().zip(&completed).any(|(&v, &c)| v > c) {
+                break;
+            }
+            let (_, object) = self.pending.pop_front().unwrap();
+            object.release();
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Typed description of the root signatures, serialized with
+//! `D3D12SerializeVersionedRootSignature`.
+//!
+//! The versioned serialization is only exported by the runtimes supporting
+//! root signatures 1.1, so it's looked up in `d3d12.dll` by hand. The older
+//! runtimes fall back to `D3D12SerializeRootSignature` and version 1.0.
+
+use hal::device as d;
+use winapi::{
+    shared::winerror,
+    um::{d3d12, d3dcommon::ID3DBlob, libloaderapi, winnt::HRESULT},
+};
+
+use std::{mem, ops::Range, ptr, sync::Arc};
+
+type D3D12SerializeVersionedRootSignature = unsafe extern "system" fn(
+    pRootSignature: *const d3d12::D3D12_VERSIONED_ROOT_SIGNATURE_DESC,
+    ppBlob: *mut *mut ID3DBlob,
+    ppErrorBlob: *mut *mut ID3DBlob,
+) -> HRESULT;
+
+/// Error of the creation of a root signature.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CreationError {
+    /// Out of either host or device memory.
+    OutOfMemory(d::OutOfMemory),
+    /// The signature was rejected, with the message of the error blob.
+    Invalid(String),
+}
+
+impl From<d::OutOfMemory> for CreationError {
+    fn from(error: d::OutOfMemory) -> Self {
+        CreationError::OutOfMemory(error)
+    }
+}
+
+/// Range of descriptors of a table.
+#[derive(Clone, Copy, Debug)]
+pub struct DescriptorRange {
+    pub ty: native::DescriptorRangeType,
+    pub count: u32,
+    pub binding: native::Binding,
+    /// Offset from the start of the table, or `D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND`.
+    pub offset: u32,
+}
+
+#[derive(Clone, Debug)]
+enum Parameter {
+    DescriptorTable {
+        visibility: native::ShaderVisibility,
+        ranges: Range<usize>,
+    },
+    Constants {
+        visibility: native::ShaderVisibility,
+        binding: native::Binding,
+        count: u32,
+    },
+    Descriptor {
+        visibility: native::ShaderVisibility,
+        ty: native::DescriptorRangeType,
+        binding: native::Binding,
+    },
+}
+
+/// Description of a root signature, built parameter by parameter.
+pub struct RootSignatureBuilder {
+    parameters: Vec<Parameter>,
+    ranges: Vec<DescriptorRange>,
+    static_samplers: Vec<native::StaticSampler>,
+    flags: native::RootSignatureFlags,
+}
+
+impl RootSignatureBuilder {
+    pub fn new(flags: native::RootSignatureFlags) -> Self {
+        RootSignatureBuilder {
+            parameters: Vec::new(),
+            ranges: Vec::new(),
+            static_samplers: Vec::new(),
+            flags,
+        }
+    }
+
+    /// Number of the parameters added so far, which is the index of the next one.
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// Add a table of descriptor ranges, set with a descriptor handle.
+    pub fn descriptor_table<I>(
+        &mut self,
+        visibility: native::ShaderVisibility,
+        ranges: I,
+    ) -> &mut Self
+    where
+        I: IntoIterator<Item = DescriptorRange>,
+    {
+        let start = self.ranges.len();
+        self.ranges.extend(ranges);
+        self.parameters.push(Parameter::DescriptorTable {
+            visibility,
+            ranges: start..self.ranges.len(),
+        });
+        self
+    }
+
+    /// Add `count` 32-bit constants, set inline.
+    pub fn constants(
+        &mut self,
+        visibility: native::ShaderVisibility,
+        binding: native::Binding,
+        count: u32,
+    ) -> &mut Self {
+        self.parameters.push(Parameter::Constants {
+            visibility,
+            binding,
+            count,
+        });
+        self
+    }
+
+    /// Add a root descriptor of a buffer, set with its GPU address.
+    ///
+    /// Samplers can't be root descriptors.
+    pub fn descriptor(
+        &mut self,
+        visibility: native::ShaderVisibility,
+        ty: native::DescriptorRangeType,
+        binding: native::Binding,
+    ) -> &mut Self {
+        debug_assert!(!matches!(ty, native::DescriptorRangeType::Sampler));
+        self.parameters.push(Parameter::Descriptor {
+            visibility,
+            ty,
+            binding,
+        });
+        self
+    }
+
+    pub fn static_sampler(&mut self, sampler: native::StaticSampler) -> &mut Self {
+        self.static_samplers.push(sampler);
+        self
+    }
+
+    /// Serialize the signature, with the highest version of the runtime.
+    pub fn serialize(&self, serializer: &Serializer) -> Result<native::Blob, CreationError> {
+        let ((blob, error), hr) = match serializer.versioned {
+            Some(serialize) => unsafe { self.serialize_1_1(serialize) },
+            None => self.serialize_1_0(&serializer.library),
+        };
+
+        // The error blob explains why the signature was rejected.
+        let message = if error.is_null() {
+            None
+        } else {
+            let message = unsafe { error.as_c_str() }.to_string_lossy().into_owned();
+            unsafe { error.destroy() };
+            Some(message)
+        };
+        match hr {
+            _ if winerror::SUCCEEDED(hr) => {
+                if let Some(message) = message {
+                    warn!("Root signature serialization: {}", message);
+                }
+                Ok(blob)
+            }
+            winerror::E_OUTOFMEMORY => Err(d::OutOfMemory::Host.into()),
+            _ => Err(CreationError::Invalid(message.unwrap_or_else(|| {
+                format!("Serialization failed with 0x{:X}", hr)
+            }))),
+        }
+    }
+
+    unsafe fn serialize_1_1(
+        &self,
+        serialize: D3D12SerializeVersionedRootSignature,
+    ) -> ((native::Blob, native::Error), HRESULT) {
+        // The descriptors and their data are volatile, as with version 1.0:
+        // the descriptor sets may be updated and the buffers written after binding.
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|range| d3d12::D3D12_DESCRIPTOR_RANGE1 {
+                RangeType: range.ty as _,
+                NumDescriptors: range.count,
+                BaseShaderRegister: range.binding.register,
+                RegisterSpace: range.binding.space,
+                Flags: match range.ty {
+                    native::DescriptorRangeType::Sampler => {
+                        d3d12::D3D12_DESCRIPTOR_RANGE_FLAG_DESCRIPTORS_VOLATILE
+                    }
+                    _ => {
+                        d3d12::D3D12_DESCRIPTOR_RANGE_FLAG_DESCRIPTORS_VOLATILE
+                            | d3d12::D3D12_DESCRIPTOR_RANGE_FLAG_DATA_VOLATILE
+                    }
+                },
+                OffsetInDescriptorsFromTableStart: range.offset,
+            })
+            .collect::<Vec<_>>();
+
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let mut raw: d3d12::D3D12_ROOT_PARAMETER1 = mem::zeroed();
+                match *parameter {
+                    Parameter::DescriptorTable {
+                        visibility,
+                        ranges: ref range,
+                    } => {
+                        raw.ParameterType = d3d12::D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE;
+                        raw.ShaderVisibility = visibility as _;
+                        *raw.u.DescriptorTable_mut() = d3d12::D3D12_ROOT_DESCRIPTOR_TABLE1 {
+                            NumDescriptorRanges: range.len() as _,
+                            pDescriptorRanges: ranges[range.clone()].as_ptr(),
+                        };
+                    }
+                    Parameter::Constants {
+                        visibility,
+                        binding,
+                        count,
+                    } => {
+                        raw.ParameterType = d3d12::D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS;
+                        raw.ShaderVisibility = visibility as _;
+                        *raw.u.Constants_mut() = d3d12::D3D12_ROOT_CONSTANTS {
+                            ShaderRegister: binding.register,
+                            RegisterSpace: binding.space,
+                            Num32BitValues: count,
+                        };
+                    }
+                    Parameter::Descriptor {
+                        visibility,
+                        ty,
+                        binding,
+                    } => {
+                        raw.ParameterType = match ty {
+                            native::DescriptorRangeType::CBV => {
+                                d3d12::D3D12_ROOT_PARAMETER_TYPE_CBV
+                            }
+                            native::DescriptorRangeType::SRV => {
+                                d3d12::D3D12_ROOT_PARAMETER_TYPE_SRV
+                            }
+                            native::DescriptorRangeType::UAV => {
+                                d3d12::D3D12_ROOT_PARAMETER_TYPE_UAV
+                            }
+                            native::DescriptorRangeType::Sampler => unreachable!(),
+                        };
+                        raw.ShaderVisibility = visibility as _;
+                        *raw.u.Descriptor_mut() = d3d12::D3D12_ROOT_DESCRIPTOR1 {
+                            ShaderRegister: binding.register,
+                            RegisterSpace: binding.space,
+                            Flags: d3d12::D3D12_ROOT_DESCRIPTOR_FLAG_DATA_VOLATILE,
+                        };
+                    }
+                }
+                raw
+            })
+            .collect::<Vec<_>>();
+
+        let mut desc = d3d12::D3D12_VERSIONED_ROOT_SIGNATURE_DESC {
+            Version: d3d12::D3D_ROOT_SIGNATURE_VERSION_1_1,
+            u: mem::zeroed(),
+        };
+        *desc.u.Desc_1_1_mut() = d3d12::D3D12_ROOT_SIGNATURE_DESC1 {
+            NumParameters: parameters.len() as _,
+            pParameters: parameters.as_ptr(),
+            NumStaticSamplers: self.static_samplers.len() as _,
+            // `StaticSampler` is a transparent wrapper of `D3D12_STATIC_SAMPLER_DESC`.
+            pStaticSamplers: self.static_samplers.as_ptr() as *const _,
+            Flags: self.flags.bits(),
+        };
+
+        let mut blob = native::Blob::null();
+        let mut error = native::Error::null();
+        let hr = serialize(
+            &desc,
+            blob.mut_void() as *mut *mut _,
+            error.mut_void() as *mut *mut _,
+        );
+        ((blob, error), hr)
+    }
+
+    fn serialize_1_0(
+        &self,
+        library: &native::D3D12Lib,
+    ) -> ((native::Blob, native::Error), HRESULT) {
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|range| {
+                native::DescriptorRange::new(range.ty, range.count, range.binding, range.offset)
+            })
+            .collect::<Vec<_>>();
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| match *parameter {
+                Parameter::DescriptorTable {
+                    visibility,
+                    ranges: ref range,
+                } => native::RootParameter::descriptor_table(visibility, &ranges[range.clone()]),
+                Parameter::Constants {
+                    visibility,
+                    binding,
+                    count,
+                } => native::RootParameter::constants(visibility, binding, count),
+                Parameter::Descriptor {
+                    visibility,
+                    ty,
+                    binding,
+                } => match ty {
+                    native::DescriptorRangeType::CBV => {
+                        native::RootParameter::cbv_descriptor(visibility, binding)
+                    }
+                    native::DescriptorRangeType::SRV => {
+                        native::RootParameter::srv_descriptor(visibility, binding)
+                    }
+                    native::DescriptorRangeType::UAV => {
+                        native::RootParameter::uav_descriptor(visibility, binding)
+                    }
+                    native::DescriptorRangeType::Sampler => unreachable!(),
+                },
+            })
+            .collect::<Vec<_>>();
+
+        match library.serialize_root_signature(
+            native::RootSignatureVersion::V1_0,
+            &parameters,
+            &self.static_samplers,
+            self.flags,
+        ) {
+            Ok(result) => result,
+            Err(e) => panic!("Can't find serialization function: {:?}", e),
+        }
+    }
+}
+
+/// Serialization entry point of the runtime.
+#[derive(Clone, Debug)]
+pub struct Serializer {
+    // Keeps `d3d12.dll` loaded for the entry point.
+    library: Arc<native::D3D12Lib>,
+    versioned: Option<D3D12SerializeVersionedRootSignature>,
+}
+
+impl Serializer {
+    /// Look up the versioned serialization, once `d3d12.dll` is loaded by `library`.
+    pub fn new(library: Arc<native::D3D12Lib>) -> Self {
+        let versioned = unsafe {
+            let module = libloaderapi::GetModuleHandleA(b"d3d12.dll\0".as_ptr() as *const _);
+            let proc = if module.is_null() {
+                ptr::null_mut()
+            } else {
+                libloaderapi::GetProcAddress(
+                    module,
+                    b"D3D12SerializeVersionedRootSignature\0".as_ptr() as *const _,
+                )
+            };
+            if proc.is_null() {
+                info!("Root signatures 1.1 are not supported");
+                None
+            } else {
+                Some(mem::transmute::<_, D3D12SerializeVersionedRootSignature>(
+                    proc,
+                ))
+            }
+        };
+        Serializer { library, versioned }
+    }
+}
@@ -0,0 +1,178 @@
+//! Variable rate shading (`ID3D12GraphicsCommandList5`).
+//!
+//! The shading rate commands and the `OPTIONS6` feature query are newer than
+//! what `winapi` exposes, so they are declared here by hand from `d3d12.h`.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use hal::pso;
+use winapi::{
+    ctypes::c_void,
+    shared::{
+        guiddef::GUID,
+        minwindef::{BOOL, UINT},
+        winerror,
+    },
+    um::{d3d12, unknwnbase::IUnknown},
+    Interface,
+};
+
+use std::{mem, ptr};
+
+const D3D12_FEATURE_D3D12_OPTIONS6: d3d12::D3D12_FEATURE = 30;
+
+const D3D12_VARIABLE_SHADING_RATE_TIER_1: u32 = 1;
+const D3D12_VARIABLE_SHADING_RATE_TIER_2: u32 = 2;
+
+/// State of an image read as the shading rate attachment of a subpass.
+pub const D3D12_RESOURCE_STATE_SHADING_RATE_SOURCE: d3d12::D3D12_RESOURCE_STATES = 0x100_0000;
+
+type D3D12_SHADING_RATE = u32;
+type D3D12_SHADING_RATE_COMBINER = u32;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct D3D12_FEATURE_DATA_D3D12_OPTIONS6 {
+    AdditionalShadingRatesSupported: BOOL,
+    PerPrimitiveShadingRateSupportedWithViewportIndexing: BOOL,
+    VariableShadingRateTier: u32,
+    ShadingRateImageTileSize: UINT,
+    BackgroundProcessingSupported: BOOL,
+}
+
+/// Minimal view of `ID3D12GraphicsCommandList5`, with only the shading rate methods.
+#[repr(C)]
+struct ID3D12GraphicsCommandList5Vtbl {
+    // ID3D12GraphicsCommandList4 (77).
+    parent: [*const c_void; 77],
+    RSSetShadingRate: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList5,
+        BaseShadingRate: D3D12_SHADING_RATE,
+        pCombiners: *const D3D12_SHADING_RATE_COMBINER,
+    ),
+    RSSetShadingRateImage: unsafe extern "system" fn(
+        This: *mut ID3D12GraphicsCommandList5,
+        shadingRateImage: *mut d3d12::ID3D12Resource,
+    ),
+}
+
+#[repr(C)]
+struct ID3D12GraphicsCommandList5 {
+    lpVtbl: *const ID3D12GraphicsCommandList5Vtbl,
+}
+
+impl Interface for ID3D12GraphicsCommandList5 {
+    fn uuidof() -> GUID {
+        GUID {
+            Data1: 0x55050859,
+            Data2: 0x4024,
+            Data3: 0x474c,
+            Data4: [0x87, 0xf5, 0x64, 0x72, 0xea, 0xee, 0x44, 0xea],
+        }
+    }
+}
+
+/// Level of variable rate shading support of a device.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Tier {
+    NotSupported,
+    /// Per-draw shading rates.
+    PerDraw,
+    /// Per-draw rates, combined with the rates of a shading rate image.
+    Image,
+}
+
+/// Query the variable rate shading support of the device.
+pub fn query(device: native::Device) -> (Tier, hal::ShadingRateProperties) {
+    let mut options = D3D12_FEATURE_DATA_D3D12_OPTIONS6::default();
+    let hr = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_D3D12_OPTIONS6,
+            &mut options as *mut _ as *mut _,
+            mem::size_of::<D3D12_FEATURE_DATA_D3D12_OPTIONS6>() as _,
+        )
+    };
+    // Runtimes predating the feature fail the query.
+    if !winerror::SUCCEEDED(hr) {
+        return (Tier::NotSupported, hal::ShadingRateProperties::default());
+    }
+
+    let tier = match options.VariableShadingRateTier {
+        D3D12_VARIABLE_SHADING_RATE_TIER_1 => Tier::PerDraw,
+        D3D12_VARIABLE_SHADING_RATE_TIER_2 => Tier::Image,
+        _ => Tier::NotSupported,
+    };
+    let properties = hal::ShadingRateProperties {
+        attachment_tile_size: if tier == Tier::Image {
+            options.ShadingRateImageTileSize
+        } else {
+            0
+        },
+        large_rates: tier != Tier::NotSupported && options.AdditionalShadingRatesSupported != 0,
+    };
+    (tier, properties)
+}
+
+fn map_rate(rate: pso::ShadingRate) -> D3D12_SHADING_RATE {
+    use hal::pso::ShadingRate as Sr;
+    // The rate packs the log2 of the width in bits 2..4 and the one of the height in bits 0..2.
+    match rate {
+        Sr::Rate1x1 => 0x0,
+        Sr::Rate1x2 => 0x1,
+        Sr::Rate2x1 => 0x4,
+        Sr::Rate2x2 => 0x5,
+        Sr::Rate2x4 => 0x6,
+        Sr::Rate4x2 => 0x9,
+        Sr::Rate4x4 => 0xa,
+    }
+}
+
+fn map_combiner(combiner: pso::ShadingRateCombiner) -> D3D12_SHADING_RATE_COMBINER {
+    use hal::pso::ShadingRateCombiner as Src;
+    match combiner {
+        Src::Keep => 0,
+        Src::Replace => 1,
+        Src::Min => 2,
+        Src::Max => 3,
+        Src::Sum => 4,
+    }
+}
+
+unsafe fn with_list5(
+    list: native::GraphicsCommandList,
+    fun: impl FnOnce(*mut ID3D12GraphicsCommandList5),
+) {
+    let mut list5: *mut ID3D12GraphicsCommandList5 = ptr::null_mut();
+    let hr = (*(list.as_mut_ptr() as *mut IUnknown)).QueryInterface(
+        &ID3D12GraphicsCommandList5::uuidof(),
+        &mut list5 as *mut *mut _ as *mut *mut c_void,
+    );
+    assert_eq!(
+        winerror::S_OK,
+        hr,
+        "Variable rate shading was reported, but the command list doesn't support it"
+    );
+    fun(list5);
+    (*(list5 as *mut IUnknown)).Release();
+}
+
+/// Record a change of the per-draw shading rate, and of the way it's combined
+/// with the per-primitive rate and the rate of the shading rate image.
+pub unsafe fn set_rate(
+    list: native::GraphicsCommandList,
+    rate: pso::ShadingRate,
+    combiners: [pso::ShadingRateCombiner; 2],
+) {
+    let combiners = [map_combiner(combiners[0]), map_combiner(combiners[1])];
+    with_list5(list, |list5| {
+        ((*(*list5).lpVtbl).RSSetShadingRate)(list5, map_rate(rate), combiners.as_ptr())
+    });
+}
+
+/// Bind the shading rate image, or unbind it with `None`.
+pub unsafe fn set_image(list: native::GraphicsCommandList, image: Option<native::Resource>) {
+    let raw = image.map_or(ptr::null_mut(), |resource| resource.as_mut_ptr());
+    with_list5(list, |list5| {
+        ((*(*list5).lpVtbl).RSSetShadingRateImage)(list5, raw)
+    });
+}
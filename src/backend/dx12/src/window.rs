@@ -1,16 +1,20 @@
-use std::{borrow::Borrow, fmt, mem, os::raw::c_void};
+use std::{borrow::Borrow, fmt, mem, os::raw::c_void, ptr};
 
 use winapi::{
     shared::{
-        dxgi, dxgi1_4, dxgi1_5, dxgitype,
+        dxgi, dxgi1_4, dxgi1_5, dxgi1_6, dxgitype,
         minwindef::{BOOL, FALSE, TRUE},
         windef::{HWND, RECT},
         winerror,
     },
-    um::{d3d12, synchapi, winbase, winnt::HANDLE, winuser::GetClientRect},
+    um::{
+        d3d12, synchapi, winbase,
+        winnt::HANDLE,
+        winuser::{GetClientRect, MonitorFromWindow, MONITOR_DEFAULTTONEAREST},
+    },
 };
 
-use crate::{conv, resource as r, Backend, Device, Instance, PhysicalDevice, QueueFamily};
+use crate::{conv, heap, resource as r, Backend, Device, Instance, PhysicalDevice, QueueFamily};
 use hal::{device::Device as _, format as f, image as i, window as w};
 
 impl Instance {
@@ -19,6 +23,7 @@ impl Instance {
             factory: self.factory,
             wnd_handle: hwnd as *mut _,
             presentation: None,
+            frame_latency: None,
         }
     }
 }
@@ -29,12 +34,21 @@ struct Presentation {
     format: f::Format,
     size: w::Extent2D,
     mode: w::PresentMode,
+    color_space: w::ColorSpace,
+    fullscreen: bool,
+    /// Set when the fullscreen state changed, until the buffers are resized.
+    resize_pending: bool,
+    /// Present and refresh counts of the last frame statistics.
+    last_statistics: Option<(u32, u32)>,
+    dropped_frames: u64,
 }
 
 pub struct Surface {
     pub(crate) factory: native::WeakPtr<dxgi1_4::IDXGIFactory4>,
     pub(crate) wnd_handle: HWND,
     presentation: Option<Presentation>,
+    /// Latency set without a swapchain, replacing the one of the next configuration.
+    frame_latency: Option<u32>,
 }
 
 impl fmt::Debug for Surface {
@@ -63,6 +77,8 @@ impl Surface {
         }
 
         let (interval, flags) = match present.mode {
+            // Exclusive fullscreen always tears, and rejects the flag.
+            w::PresentMode::IMMEDIATE if present.fullscreen => (0, 0),
             w::PresentMode::IMMEDIATE => (0, dxgi::DXGI_PRESENT_ALLOW_TEARING),
             w::PresentMode::FIFO => (1, 0),
             _ => (1, 0), // Surface was created with an unsupported present mode, fall back to FIFO
@@ -71,6 +87,52 @@ impl Surface {
         sc.inner.Present(interval, flags);
         Ok(())
     }
+
+    /// Output showing the window, if it's connected to the adapter.
+    unsafe fn output(
+        &self,
+        physical_device: &PhysicalDevice,
+    ) -> Option<native::WeakPtr<dxgi::IDXGIOutput>> {
+        let monitor = MonitorFromWindow(self.wnd_handle, MONITOR_DEFAULTTONEAREST);
+        for index in 0.. {
+            let mut output = native::WeakPtr::<dxgi::IDXGIOutput>::null();
+            let hr = physical_device
+                .adapter
+                .EnumOutputs(index, output.mut_void() as *mut *mut _);
+            if !winerror::SUCCEEDED(hr) {
+                break;
+            }
+            let mut desc = mem::zeroed::<dxgi::DXGI_OUTPUT_DESC>();
+            let hr = output.GetDesc(&mut desc);
+            if winerror::SUCCEEDED(hr) && desc.Monitor == monitor {
+                return Some(output);
+            }
+            output.destroy();
+        }
+        None
+    }
+
+    /// Description of the output showing the window, if it's connected to the adapter.
+    unsafe fn output_desc(
+        &self,
+        physical_device: &PhysicalDevice,
+    ) -> Option<dxgi1_6::DXGI_OUTPUT_DESC1> {
+        let output = self.output(physical_device)?;
+        let (output6, hr) = output.cast::<dxgi1_6::IDXGIOutput6>();
+        output.destroy();
+        if !winerror::SUCCEEDED(hr) {
+            // Runtimes predating `IDXGIOutput6` don't describe the HDR support.
+            return None;
+        }
+        let mut desc = mem::zeroed::<dxgi1_6::DXGI_OUTPUT_DESC1>();
+        let hr = output6.GetDesc1(&mut desc);
+        output6.destroy();
+        if winerror::SUCCEEDED(hr) {
+            Some(desc)
+        } else {
+            None
+        }
+    }
 }
 
 impl w::Surface<Backend> for Surface {
@@ -143,6 +205,84 @@ impl w::Surface<Backend> for Surface {
             f::Format::Rgba16Sfloat,
         ])
     }
+
+    fn ranked_formats(&self, physical_device: &PhysicalDevice) -> Vec<w::SurfaceFormat> {
+        let mut formats = w::rank_surface_formats(self.supported_formats(physical_device));
+        // scRGB is composited on any display, while HDR10 is only presented
+        // as such by displays in an HDR mode.
+        formats.push(w::SurfaceFormat {
+            format: f::Format::Rgba16Sfloat,
+            color_space: w::ColorSpace::ExtendedSrgbLinear,
+        });
+        if self.hdr_capabilities(physical_device).is_some() {
+            formats.push(w::SurfaceFormat {
+                format: f::Format::A2b10g10r10Unorm,
+                color_space: w::ColorSpace::Hdr10St2084,
+            });
+        }
+        formats
+    }
+
+    fn hdr_capabilities(&self, physical_device: &PhysicalDevice) -> Option<w::HdrCapabilities> {
+        let desc = unsafe { self.output_desc(physical_device) }?;
+        if desc.ColorSpace != dxgitype::DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020 {
+            return None;
+        }
+        Some(w::HdrCapabilities {
+            red_primary: desc.RedPrimary,
+            green_primary: desc.GreenPrimary,
+            blue_primary: desc.BluePrimary,
+            white_point: desc.WhitePoint,
+            min_luminance: desc.MinLuminance,
+            max_luminance: desc.MaxLuminance,
+            max_full_frame_luminance: desc.MaxFullFrameLuminance,
+        })
+    }
+
+    fn video_modes(
+        &self,
+        physical_device: &PhysicalDevice,
+        format: f::Format,
+    ) -> Vec<w::VideoMode> {
+        let dxgi_format = match conv::map_format_nosrgb(format) {
+            Some(format) => format,
+            None => return Vec::new(),
+        };
+        let output = match unsafe { self.output(physical_device) } {
+            Some(output) => output,
+            None => return Vec::new(),
+        };
+
+        let mut descs = Vec::new();
+        unsafe {
+            let mut count = 0;
+            let hr = output.GetDisplayModeList(dxgi_format, 0, &mut count, ptr::null_mut());
+            if winerror::SUCCEEDED(hr) {
+                descs.reserve(count as usize);
+                let hr = output.GetDisplayModeList(dxgi_format, 0, &mut count, descs.as_mut_ptr());
+                if winerror::SUCCEEDED(hr) {
+                    descs.set_len(count as usize);
+                } else {
+                    error!("Error in GetDisplayModeList: 0x{:X}", hr);
+                }
+            }
+            output.destroy();
+        }
+
+        let mut modes = descs
+            .iter()
+            .map(|desc| w::VideoMode {
+                extent: w::Extent2D {
+                    width: desc.Width,
+                    height: desc.Height,
+                },
+                refresh_rate: conv::map_refresh_rate(desc.RefreshRate),
+            })
+            .collect::<Vec<_>>();
+        // The modes only differing by their scaling are listed next to each other.
+        modes.dedup();
+        modes
+    }
 }
 
 #[derive(Debug)]
@@ -170,13 +310,26 @@ impl w::PresentationSurface<Backend> for Surface {
     unsafe fn configure_swapchain(
         &mut self,
         device: &Device,
-        config: w::SwapchainConfig,
+        mut config: w::SwapchainConfig,
     ) -> Result<(), w::SwapchainError> {
         assert!(i::Usage::COLOR_ATTACHMENT.contains(config.image_usage));
+        if let Some(latency) = self.frame_latency.take() {
+            config.frame_latency = Some(latency);
+        }
 
         let swapchain = match self.presentation.take() {
             Some(present) => {
-                if present.format == config.format && present.size == config.extent {
+                if present.format == config.format
+                    && present.size == config.extent
+                    && present.color_space == config.color_space
+                    && !present.resize_pending
+                {
+                    if let Some(latency) = config.frame_latency {
+                        present
+                            .swapchain
+                            .inner
+                            .SetMaximumFrameLatency(latency.max(1));
+                    }
                     self.presentation = Some(present);
                     return Ok(());
                 }
@@ -200,6 +353,9 @@ impl w::PresentationSurface<Backend> for Surface {
                     error!("ResizeBuffers failed with 0x{:x}", result as u32);
                     return Err(w::SwapchainError::WindowInUse);
                 }
+                inner.SetMaximumFrameLatency(
+                    config.frame_latency.unwrap_or(config.image_count).max(1),
+                );
                 inner
             }
             None => {
@@ -209,6 +365,17 @@ impl w::PresentationSurface<Backend> for Surface {
             }
         };
 
+        let mut support = 0;
+        let color_space = conv::map_color_space(config.color_space);
+        let hr = swapchain.CheckColorSpaceSupport(color_space, &mut support);
+        if !winerror::SUCCEEDED(hr)
+            || support & dxgi1_4::DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT == 0
+        {
+            swapchain.destroy();
+            return Err(w::SwapchainError::UnsupportedColorSpace);
+        }
+        swapchain.SetColorSpace1(color_space);
+
         // Disable automatic Alt+Enter handling by DXGI.
         const DXGI_MWA_NO_WINDOW_CHANGES: u32 = 1;
         const DXGI_MWA_NO_ALT_ENTER: u32 = 2;
@@ -217,21 +384,35 @@ impl w::PresentationSurface<Backend> for Surface {
             DXGI_MWA_NO_WINDOW_CHANGES | DXGI_MWA_NO_ALT_ENTER,
         );
 
+        let mut fullscreen = FALSE;
+        swapchain.GetFullscreenState(&mut fullscreen, ptr::null_mut());
+
         self.presentation = Some(Presentation {
             swapchain: device.wrap_swapchain(swapchain, &config),
             format: config.format,
             size: config.extent,
             mode: config.present_mode,
+            color_space: config.color_space,
+            fullscreen: fullscreen != FALSE,
+            resize_pending: false,
+            last_statistics: None,
+            dropped_frames: 0,
         });
         Ok(())
     }
 
     unsafe fn unconfigure_swapchain(&mut self, device: &Device) {
         if let Some(mut present) = self.presentation.take() {
-            let _ = present.swapchain.wait(winbase::INFINITE);
+            if !present.swapchain.frame_waited {
+                let _ = present.swapchain.wait(winbase::INFINITE);
+            }
             let _ = device.wait_idle(); //TODO: this shouldn't be needed,
                                         // but it complains that the queue is still used otherwise
             let inner = present.swapchain.release_resources();
+            // Swapchains can't be released in exclusive fullscreen.
+            if present.fullscreen {
+                inner.SetFullscreenState(FALSE, ptr::null_mut());
+            }
             inner.destroy();
         }
     }
@@ -243,39 +424,42 @@ impl w::PresentationSurface<Backend> for Surface {
         let present = self.presentation.as_mut().unwrap();
         let sc = &mut present.swapchain;
 
-        sc.wait((timeout_ns / 1_000_000) as u32)?;
+        if sc.frame_waited {
+            sc.frame_waited = false;
+        } else {
+            sc.wait((timeout_ns / 1_000_000) as u32)?;
+        }
 
         let base_index = sc.inner.GetCurrentBackBufferIndex() as usize;
         let index = (base_index + sc.acquired_count) % sc.resources.len();
         sc.acquired_count += 1;
         let resource = sc.resources[index];
 
+        let suboptimal = if present.resize_pending {
+            Some(w::Suboptimal)
+        } else {
+            None
+        };
         let kind = i::Kind::D2(present.size.width, present.size.height, 1, 1);
         let base_format = present.format.base_format();
         let dxgi_format = conv::map_format(present.format).unwrap();
         let rtv = sc.rtv_heap.at(index as _, 0).cpu;
 
-        let descriptor = d3d12::D3D12_RESOURCE_DESC {
-            Dimension: d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE2D,
-            Alignment: 0,
-            Width: present.size.width as _,
-            Height: present.size.height as _,
-            DepthOrArraySize: 1,
-            MipLevels: 1,
-            Format: dxgi_format,
-            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
-            Layout: d3d12::D3D12_TEXTURE_LAYOUT_UNKNOWN,
-            Flags: d3d12::D3D12_RESOURCE_FLAG_NONE, //TODO?
-        };
+        let descriptor = heap::texture_desc(
+            kind,
+            kind.extent(),
+            1,
+            dxgi_format,
+            d3d12::D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            d3d12::D3D12_RESOURCE_FLAG_NONE, //TODO?
+        );
 
         let image = r::ImageBound {
             resource,
             place: r::Place::Swapchain {},
             surface_type: base_format.0,
             kind,
+            base_level: 0,
             mip_levels: 1,
             default_view_format: None,
             view_caps: i::ViewCapabilities::empty(),
@@ -307,7 +491,164 @@ impl w::PresentationSurface<Backend> for Surface {
             },
         };
 
-        Ok((swapchain_image, None))
+        Ok((swapchain_image, suboptimal))
+    }
+
+    unsafe fn wait_for_available_frame(&mut self, timeout_ns: u64) -> Result<(), w::AcquireError> {
+        let sc = match self.presentation {
+            Some(ref mut present) => &mut present.swapchain,
+            None => return Err(w::OutOfDate.into()),
+        };
+        if !sc.frame_waited {
+            sc.wait((timeout_ns / 1_000_000) as u32)?;
+            sc.frame_waited = true;
+        }
+        Ok(())
+    }
+
+    unsafe fn set_frame_latency(&mut self, latency: u32) {
+        let sc = match self.presentation {
+            Some(ref present) => &present.swapchain,
+            None => {
+                self.frame_latency = Some(latency);
+                return;
+            }
+        };
+        let hr = sc.inner.SetMaximumFrameLatency(latency.max(1));
+        if !winerror::SUCCEEDED(hr) {
+            error!("Error in SetMaximumFrameLatency: 0x{:X}", hr);
+        }
+    }
+
+    fn present_statistics(&mut self) -> Option<w::PresentStatistics> {
+        let present = self.presentation.as_mut()?;
+        let mut stats = unsafe { mem::zeroed::<dxgi::DXGI_FRAME_STATISTICS>() };
+        let hr = unsafe { present.swapchain.inner.GetFrameStatistics(&mut stats) };
+        if !winerror::SUCCEEDED(hr) {
+            // Nothing was displayed yet, or the statistics are disjoint after a mode change.
+            present.last_statistics = None;
+            return None;
+        }
+
+        if let Some((present_count, refresh_count)) = present.last_statistics {
+            let presents = stats.PresentCount.wrapping_sub(present_count);
+            let refreshes = stats.PresentRefreshCount.wrapping_sub(refresh_count);
+            // With vsync, each frame is expected to be displayed for a single refresh.
+            if present.mode != w::PresentMode::IMMEDIATE && presents != 0 && refreshes > presents {
+                present.dropped_frames += (refreshes - presents) as u64;
+            }
+        }
+        present.last_statistics = Some((stats.PresentCount, stats.PresentRefreshCount));
+
+        // The time is sampled at the vertical blank of `SyncRefreshCount`, which
+        // may come after the one that displayed the last frame.
+        let present_time = if stats.SyncRefreshCount == stats.PresentRefreshCount {
+            crate::performance_counter_instant(unsafe { *stats.SyncQPCTime.QuadPart() } as u64)
+        } else {
+            None
+        };
+        Some(w::PresentStatistics {
+            present_count: stats.PresentCount as u64,
+            present_time,
+            vblank_count: Some(stats.PresentRefreshCount as u64),
+            dropped_frames: Some(present.dropped_frames),
+        })
+    }
+
+    unsafe fn set_hdr_metadata(&mut self, metadata: Option<&w::HdrMetadata>) {
+        let present = match self.presentation {
+            Some(ref present) => present,
+            None => {
+                warn!("HDR metadata can only be set on a configured swapchain");
+                return;
+            }
+        };
+        let (swapchain4, hr) = present.swapchain.inner.cast::<dxgi1_5::IDXGISwapChain4>();
+        if !winerror::SUCCEEDED(hr) {
+            warn!("HDR metadata isn't supported by the runtime");
+            return;
+        }
+
+        // Chromaticities are in units of 0.00002, the minimum luminance in units of 0.0001 nits.
+        let chromaticity = |xy: [f32; 2]| [(xy[0] * 50000.0) as u16, (xy[1] * 50000.0) as u16];
+        let hr = match metadata {
+            Some(metadata) => {
+                let mut hdr10 = dxgi1_5::DXGI_HDR_METADATA_HDR10 {
+                    RedPrimary: chromaticity(metadata.red_primary),
+                    GreenPrimary: chromaticity(metadata.green_primary),
+                    BluePrimary: chromaticity(metadata.blue_primary),
+                    WhitePoint: chromaticity(metadata.white_point),
+                    MaxMasteringLuminance: metadata.max_mastering_luminance as u32,
+                    MinMasteringLuminance: (metadata.min_mastering_luminance * 10000.0) as u32,
+                    MaxContentLightLevel: metadata.max_content_light_level as u16,
+                    MaxFrameAverageLightLevel: metadata.max_frame_average_light_level as u16,
+                };
+                swapchain4.SetHDRMetaData(
+                    dxgi1_5::DXGI_HDR_METADATA_TYPE_HDR10,
+                    mem::size_of::<dxgi1_5::DXGI_HDR_METADATA_HDR10>() as _,
+                    &mut hdr10 as *mut _ as *mut _,
+                )
+            }
+            None => {
+                swapchain4.SetHDRMetaData(dxgi1_5::DXGI_HDR_METADATA_TYPE_NONE, 0, ptr::null_mut())
+            }
+        };
+        swapchain4.destroy();
+        if !winerror::SUCCEEDED(hr) {
+            error!("Error in SetHDRMetaData: 0x{:X}", hr);
+        }
+    }
+
+    unsafe fn set_fullscreen(&mut self, mode: w::FullscreenMode) -> Result<(), w::FullscreenError> {
+        let present = match self.presentation.as_mut() {
+            Some(present) => present,
+            None => return Err(w::FullscreenError::NotConfigured),
+        };
+        let swapchain = present.swapchain.inner;
+        let hr = match mode {
+            w::FullscreenMode::Windowed => swapchain.SetFullscreenState(FALSE, ptr::null_mut()),
+            w::FullscreenMode::Exclusive(video_mode) => {
+                let hr = swapchain.SetFullscreenState(TRUE, ptr::null_mut());
+                match video_mode {
+                    // In exclusive fullscreen, resizing the target switches the
+                    // output to the closest video mode.
+                    Some(video_mode) if winerror::SUCCEEDED(hr) => {
+                        let desc = dxgitype::DXGI_MODE_DESC {
+                            Width: video_mode.extent.width,
+                            Height: video_mode.extent.height,
+                            RefreshRate: dxgitype::DXGI_RATIONAL {
+                                Numerator: video_mode.refresh_rate,
+                                Denominator: 1000,
+                            },
+                            Format: conv::map_format_nosrgb(present.format).unwrap(),
+                            ScanlineOrdering: dxgitype::DXGI_MODE_SCANLINE_ORDER_UNSPECIFIED,
+                            Scaling: dxgitype::DXGI_MODE_SCALING_UNSPECIFIED,
+                        };
+                        let hr = swapchain.ResizeTarget(&desc);
+                        if !winerror::SUCCEEDED(hr) {
+                            error!("Error in ResizeTarget: 0x{:X}", hr);
+                        }
+                        winerror::S_OK
+                    }
+                    _ => hr,
+                }
+            }
+        };
+
+        match hr {
+            winerror::S_OK => {
+                present.fullscreen = mode != w::FullscreenMode::Windowed;
+                present.resize_pending = true;
+                Ok(())
+            }
+            winerror::E_OUTOFMEMORY => Err(hal::device::OutOfMemory::Host.into()),
+            winerror::DXGI_ERROR_NOT_CURRENTLY_AVAILABLE
+            | winerror::DXGI_STATUS_MODE_CHANGE_IN_PROGRESS => Err(w::FullscreenError::Unavailable),
+            hr => {
+                error!("Error in SetFullscreenState: 0x{:X}", hr);
+                Err(w::FullscreenError::Unsupported)
+            }
+        }
     }
 }
 
@@ -322,6 +663,9 @@ pub struct Swapchain {
     pub(crate) waitable: HANDLE,
     pub(crate) usage: i::Usage,
     pub(crate) acquired_count: usize,
+    /// Set if the waitable object has been waited for by `wait_for_available_frame`,
+    /// so that the next acquisition doesn't wait again.
+    pub(crate) frame_waited: bool,
 }
 
 impl Swapchain {
@@ -2,10 +2,15 @@
 pub struct Buffer {
     /// Size of this buffer
     pub(crate) size: u64,
+    /// User-defined name for this buffer
+    pub(crate) name: String,
 }
 
 impl Buffer {
     pub fn new(size: u64) -> Self {
-        Buffer { size }
+        Buffer {
+            size,
+            name: String::new(),
+        }
     }
 }
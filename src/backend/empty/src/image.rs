@@ -1,30 +1,75 @@
-use hal::image::Kind;
+use hal::format::Format;
+use hal::image::{Kind, Level, Subresource, SubresourceFootprint};
 use hal::memory::Requirements as MemoryRequirements;
 
 #[derive(Debug)]
 pub struct Image {
     /// What type of image this is, as well as its extent.
     kind: Kind,
+    /// Number of mip levels of the image.
+    num_levels: Level,
+    /// Format of the texels.
+    format: Format,
+    /// User-defined name for this image
+    pub(crate) name: String,
 }
 
 impl Image {
-    pub fn new(kind: Kind) -> Self {
-        Image { kind }
+    pub fn new(kind: Kind, num_levels: Level, format: Format) -> Self {
+        Image {
+            kind,
+            num_levels,
+            format,
+            name: String::new(),
+        }
+    }
+
+    /// Row and depth pitches of a mip level, with the size of the level
+    /// for each array layer.
+    fn level_pitches(&self, level: Level) -> (u64, u64, u64) {
+        let desc = self.format.surface_desc();
+        let extent = self.kind.level_extent(level);
+        let blocks_x = (extent.width + desc.dim.0 as u32 - 1) / desc.dim.0 as u32;
+        let blocks_y = (extent.height + desc.dim.1 as u32 - 1) / desc.dim.1 as u32;
+        let samples = u64::from(self.kind.num_samples());
+        let row_pitch = (u64::from(blocks_x) * u64::from(desc.bits) * samples + 7) / 8;
+        let depth_pitch = row_pitch * u64::from(blocks_y);
+        (
+            row_pitch,
+            depth_pitch,
+            depth_pitch * u64::from(extent.depth),
+        )
+    }
+
+    /// Size of all the mip levels of an array layer.
+    fn layer_size(&self) -> u64 {
+        (0..self.num_levels)
+            .map(|level| self.level_pitches(level).2)
+            .sum()
     }
 
     pub fn get_requirements(&self) -> MemoryRequirements {
-        let size = match self.kind {
-            Kind::D2(width, height, layers, samples) => {
-                assert_eq!(layers, 1, "Multi-layer images are not supported");
-                assert_eq!(samples, 1, "Multisampled images are not supported");
-                u64::from(width) * u64::from(height)
-            }
-            _ => unimplemented!("Unsupported image kind"),
-        };
         MemoryRequirements {
-            size,
+            size: self.layer_size() * u64::from(self.kind.num_layers()),
             alignment: 1,
             type_mask: !0,
         }
     }
+
+    /// Footprint of a subresource, the layers being stored one after the other
+    /// with all their mip levels.
+    pub fn get_footprint(&self, sub: Subresource) -> SubresourceFootprint {
+        let array_pitch = self.layer_size();
+        let level_offset: u64 = (0..sub.level)
+            .map(|level| self.level_pitches(level).2)
+            .sum();
+        let (row_pitch, depth_pitch, level_size) = self.level_pitches(sub.level);
+        let start = u64::from(sub.layer) * array_pitch + level_offset;
+        SubresourceFootprint {
+            slice: start..start + level_size,
+            row_pitch,
+            array_pitch,
+            depth_pitch,
+        }
+    }
 }
@@ -1,5 +1,11 @@
 //! Mock backend implementation to test the code for compile errors
 //! outside of the graphics development environment.
+//!
+//! It can also be used to unit test the logic of a renderer without a GPU or
+//! a window: all the calls succeed without doing any work, the submissions
+//! completing immediately, while the created objects keep track of their state,
+//! e.g. fences are signaled by the submissions. The capabilities of the adapters
+//! can be configured with [`AdapterConfig`] to simulate various hardware.
 
 extern crate gfx_hal as hal;
 
@@ -8,9 +14,14 @@ use crate::{
     descriptor::{DescriptorPool, DescriptorSet, DescriptorSetLayout},
     image::Image,
     memory::Memory,
+    query_pool::QueryPool,
+    sync::{Event, Fence},
 };
 
-use hal::{adapter, command, device, display, format, pass, pool, pso, query, queue, window};
+use hal::{
+    acceleration_structure, adapter, command, device, display, format, pass, pool, pso, query,
+    queue, window,
+};
 use log::debug;
 
 use std::{borrow::Borrow, ops::Range};
@@ -19,8 +30,10 @@ mod buffer;
 mod descriptor;
 mod image;
 mod memory;
+mod query_pool;
+mod sync;
 
-const NOT_SUPPORTED_MESSAGE: &str = "This function is not currently mocked by the empty backend";
+const NO_DISPLAY_MESSAGE: &str = "The empty backend doesn't expose any display";
 
 /// Dummy backend.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -50,30 +63,86 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = ();
     type GraphicsPipeline = ();
+    type RayTracingPipeline = ();
     type PipelineCache = ();
     type PipelineLayout = ();
     type DescriptorSetLayout = DescriptorSetLayout;
     type DescriptorPool = DescriptorPool;
     type DescriptorSet = DescriptorSet;
 
-    type Fence = ();
+    type Fence = Fence;
     type Semaphore = ();
-    type Event = ();
-    type QueryPool = ();
+    type Event = Event;
+    type QueryPool = QueryPool;
+    type AccelerationStructure = ();
 
     type Display = ();
     type DisplayMode = ();
 }
 
+/// Capabilities of a mock adapter, e.g. to simulate weak hardware.
+#[derive(Clone, Debug)]
+pub struct AdapterConfig {
+    /// Information reported by the adapter.
+    pub info: adapter::AdapterInfo,
+    /// Features supported by the physical device.
+    pub features: hal::Features,
+    /// Properties of the physical device, including its limits.
+    pub properties: hal::PhysicalDeviceProperties,
+}
+
+impl Default for AdapterConfig {
+    fn default() -> Self {
+        AdapterConfig {
+            info: adapter::AdapterInfo {
+                name: "Mock Device".to_string(),
+                vendor: 0,
+                device: 1234,
+                device_type: adapter::DeviceType::Other,
+                driver: String::new(),
+                // The only memory type is both device local and CPU visible
+                unified_memory: true,
+                luid: None,
+                uuid: None,
+            },
+            features: hal::Features::empty(),
+            properties: hal::PhysicalDeviceProperties {
+                limits: hal::Limits {
+                    non_coherent_atom_size: 1,
+                    optimal_buffer_copy_pitch_alignment: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// All the formats support all the features.
+fn format_properties() -> format::Properties {
+    format::Properties {
+        linear_tiling: format::ImageFeature::all(),
+        optimal_tiling: format::ImageFeature::all(),
+        buffer_features: format::BufferFeature::all(),
+        drm_format_properties: Vec::new(),
+    }
+}
+
 /// Dummy physical device.
 #[derive(Debug)]
-pub struct PhysicalDevice;
+pub struct PhysicalDevice {
+    config: AdapterConfig,
+}
 impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
     unsafe fn open(
         &self,
         families: &[(&QueueFamily, &[queue::QueuePriority])],
-        _requested_features: hal::Features,
+        requested_features: hal::Features,
     ) -> Result<adapter::Gpu<Backend>, device::CreationError> {
+        if !self.config.features.contains(requested_features) {
+            return Err(device::CreationError::MissingFeature);
+        }
+
         // Validate the arguments
         assert_eq!(
             families.len(),
@@ -106,18 +175,51 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
     }
 
     fn format_properties(&self, _: Option<format::Format>) -> format::Properties {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        format_properties()
     }
 
     fn image_format_properties(
         &self,
         _: format::Format,
-        _dim: u8,
+        dim: u8,
         _: hal::image::Tiling,
         _: hal::image::Usage,
         _: hal::image::ViewCapabilities,
     ) -> Option<hal::image::FormatProperties> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        let limits = &self.config.properties.limits;
+        let max_extent = match dim {
+            1 => hal::image::Extent {
+                width: limits.max_image_1d_size,
+                height: 1,
+                depth: 1,
+            },
+            2 => hal::image::Extent {
+                width: limits.max_image_2d_size,
+                height: limits.max_image_2d_size,
+                depth: 1,
+            },
+            3 => hal::image::Extent {
+                width: limits.max_image_3d_size,
+                height: limits.max_image_3d_size,
+                depth: limits.max_image_3d_size,
+            },
+            _ => return None,
+        };
+        Some(hal::image::FormatProperties {
+            max_extent,
+            max_levels: 32 - max_extent.width.max(1).leading_zeros() as hal::image::Level,
+            max_layers: if dim == 3 {
+                1
+            } else {
+                limits.max_image_array_layers
+            },
+            sample_count_mask: if dim == 2 {
+                limits.framebuffer_color_sample_counts
+            } else {
+                1
+            },
+            max_resource_size: !0,
+        })
     }
 
     fn memory_properties(&self) -> adapter::MemoryProperties {
@@ -150,7 +252,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         _sparse: hal::memory::SparseFlags,
         _memory_type: hal::external_memory::ExternalMemoryType,
     ) -> hal::external_memory::ExternalMemoryProperties {
-        unimplemented!()
+        hal::external_memory::ExternalMemoryProperties::empty()
     }
 
     fn external_image_properties(
@@ -165,33 +267,26 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         hal::external_memory::ExternalMemoryProperties,
         hal::external_memory::ExternalImagePropertiesError,
     > {
-        unimplemented!()
+        Ok(hal::external_memory::ExternalMemoryProperties::empty())
     }
 
     fn features(&self) -> hal::Features {
-        hal::Features::empty()
+        self.config.features
     }
 
     fn properties(&self) -> hal::PhysicalDeviceProperties {
-        hal::PhysicalDeviceProperties {
-            limits: hal::Limits {
-                non_coherent_atom_size: 1,
-                optimal_buffer_copy_pitch_alignment: 1,
-                ..Default::default()
-            },
-            ..Default::default()
-        }
+        self.config.properties
     }
 
     unsafe fn enumerate_displays(&self) -> Vec<display::Display<Backend>> {
-        unimplemented!();
+        Vec::new()
     }
 
     unsafe fn enumerate_compatible_planes(
         &self,
         _display: &display::Display<Backend>,
     ) -> Vec<display::Plane> {
-        unimplemented!();
+        Vec::new()
     }
 
     unsafe fn create_display_mode(
@@ -200,7 +295,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         _resolution: (u32, u32),
         _refresh_rate: u32,
     ) -> Result<display::DisplayMode<Backend>, display::DisplayModeError> {
-        unimplemented!();
+        Err(display::DisplayModeError::UnsupportedDisplayMode)
     }
 
     unsafe fn create_display_plane<'a>(
@@ -208,7 +303,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         _display: &'a display::DisplayMode<Backend>,
         _plane: &'a display::Plane,
     ) -> Result<display::DisplayPlane<'a, Backend>, device::OutOfMemory> {
-        unimplemented!();
+        unreachable!("{}", NO_DISPLAY_MESSAGE)
     }
 }
 
@@ -216,10 +311,20 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
 #[derive(Debug)]
 pub struct Queue;
 impl queue::Queue<Backend> for Queue {
-    unsafe fn submit<'a, Ic, Iw, Is>(&mut self, _: Ic, _: Iw, _: Is, _: Option<&mut ()>)
+    unsafe fn submit<'a, Ic, Iw, Is>(
+        &mut self,
+        _: Ic,
+        _: Iw,
+        _: Is,
+        fence: Option<&mut Fence>,
+    ) -> Result<(), device::OomOrDeviceLost>
     where
         Ic: Iterator<Item = &'a CommandBuffer>,
     {
+        if let Some(fence) = fence {
+            fence.set_signaled(true);
+        }
+        Ok(())
     }
 
     unsafe fn present(
@@ -231,8 +336,8 @@ impl queue::Queue<Backend> for Queue {
         Ok(None)
     }
 
-    fn wait_idle(&mut self) -> Result<(), device::OutOfMemory> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    fn wait_idle(&mut self) -> Result<(), device::OomOrDeviceLost> {
+        Ok(())
     }
 
     fn timestamp_period(&self) -> f32 {
@@ -289,16 +394,14 @@ impl device::Device<Backend> for Device {
         &self,
         _data: Option<&[u8]>,
     ) -> Result<(), device::OutOfMemory> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Ok(())
     }
 
     unsafe fn get_pipeline_cache_data(&self, _cache: &()) -> Result<Vec<u8>, device::OutOfMemory> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Ok(Vec::new())
     }
 
-    unsafe fn destroy_pipeline_cache(&self, _: ()) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn destroy_pipeline_cache(&self, _: ()) {}
 
     unsafe fn create_graphics_pipeline<'a>(
         &self,
@@ -313,7 +416,25 @@ impl device::Device<Backend> for Device {
         _: &pso::ComputePipelineDesc<'a, Backend>,
         _: Option<&()>,
     ) -> Result<(), pso::CreationError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Ok(())
+    }
+
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        Ok(())
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _: &(),
+        _: u32,
+        _: u32,
+        _: &mut [u8],
+    ) -> Result<(), device::OutOfMemory> {
+        Ok(())
     }
 
     unsafe fn merge_pipeline_caches<'a, I>(
@@ -343,7 +464,7 @@ impl device::Device<Backend> for Device {
     unsafe fn create_sampler(
         &self,
         _: &hal::image::SamplerDesc,
-    ) -> Result<(), device::AllocationError> {
+    ) -> Result<(), hal::image::SamplerCreationError> {
         Ok(())
     }
 
@@ -380,20 +501,24 @@ impl device::Device<Backend> for Device {
         _: Option<format::Format>,
         _: hal::buffer::SubRange,
     ) -> Result<(), hal::buffer::ViewCreationError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Ok(())
+    }
+
+    fn format_properties(&self, _: format::Format) -> format::Properties {
+        format_properties()
     }
 
     unsafe fn create_image(
         &self,
         kind: hal::image::Kind,
-        _: hal::image::Level,
-        _: format::Format,
+        num_levels: hal::image::Level,
+        format: format::Format,
         _: hal::image::Tiling,
         _: hal::image::Usage,
         _: hal::memory::SparseFlags,
         _: hal::image::ViewCapabilities,
     ) -> Result<Image, hal::image::CreationError> {
-        Ok(Image::new(kind))
+        Ok(Image::new(kind, num_levels, format))
     }
 
     unsafe fn get_image_requirements(&self, image: &Image) -> hal::memory::Requirements {
@@ -402,10 +527,10 @@ impl device::Device<Backend> for Device {
 
     unsafe fn get_image_subresource_footprint(
         &self,
-        _: &Image,
-        _: hal::image::Subresource,
+        image: &Image,
+        sub: hal::image::Subresource,
     ) -> hal::image::SubresourceFootprint {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        image.get_footprint(sub)
     }
 
     unsafe fn bind_image_memory(
@@ -458,55 +583,81 @@ impl device::Device<Backend> for Device {
     {
     }
 
-    unsafe fn copy_descriptor_set<'a>(&self, _: pso::DescriptorSetCopy<'a, Backend>) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn copy_descriptor_set<'a>(&self, _: pso::DescriptorSetCopy<'a, Backend>) {}
 
     fn create_semaphore(&self) -> Result<(), device::OutOfMemory> {
         Ok(())
     }
 
-    fn create_fence(&self, _: bool) -> Result<(), device::OutOfMemory> {
-        Ok(())
+    fn create_fence(&self, signaled: bool) -> Result<Fence, device::OutOfMemory> {
+        Ok(Fence::new(signaled))
     }
 
-    unsafe fn get_fence_status(&self, _: &()) -> Result<bool, device::DeviceLost> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn get_fence_status(&self, fence: &Fence) -> Result<bool, device::DeviceLost> {
+        Ok(fence.is_signaled())
     }
 
-    fn create_event(&self) -> Result<(), device::OutOfMemory> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    fn create_event(&self) -> Result<Event, device::OutOfMemory> {
+        Ok(Event::new())
     }
 
-    unsafe fn get_event_status(&self, _: &()) -> Result<bool, device::WaitError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn get_event_status(&self, event: &Event) -> Result<bool, device::WaitError> {
+        Ok(event.is_set())
     }
 
-    unsafe fn set_event(&self, _: &mut ()) -> Result<(), device::OutOfMemory> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn set_event(&self, event: &mut Event) -> Result<(), device::OutOfMemory> {
+        event.set(true);
+        Ok(())
     }
 
-    unsafe fn reset_event(&self, _: &mut ()) -> Result<(), device::OutOfMemory> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn reset_event(&self, event: &mut Event) -> Result<(), device::OutOfMemory> {
+        event.set(false);
+        Ok(())
     }
 
-    unsafe fn create_query_pool(&self, _: query::Type, _: u32) -> Result<(), query::CreationError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn create_query_pool(
+        &self,
+        ty: query::Type,
+        count: u32,
+    ) -> Result<QueryPool, query::CreationError> {
+        Ok(QueryPool::new(ty, count))
     }
 
-    unsafe fn destroy_query_pool(&self, _: ()) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn destroy_query_pool(&self, _: QueryPool) {}
+
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _: &acceleration_structure::GeometryInfo<Backend>,
+    ) -> acceleration_structure::SizeRequirements {
+        acceleration_structure::SizeRequirements::default()
     }
 
-    unsafe fn get_query_pool_results(
+    unsafe fn create_acceleration_structure(
+        &self,
+        _: &acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), device::OutOfMemory> {
+        Ok(())
+    }
+
+    unsafe fn get_acceleration_structure_address(
         &self,
         _: &(),
-        _: Range<query::Id>,
-        _: &mut [u8],
-        _: hal::buffer::Stride,
-        _: query::ResultFlags,
+    ) -> acceleration_structure::DeviceAddress {
+        0
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _: ()) {}
+
+    unsafe fn get_query_pool_results(
+        &self,
+        pool: &QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: hal::buffer::Stride,
+        flags: query::ResultFlags,
     ) -> Result<bool, device::WaitError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        pool.get_results(queries, data, stride, flags);
+        Ok(true)
     }
 
     unsafe fn map_memory(
@@ -530,13 +681,35 @@ impl device::Device<Backend> for Device {
     where
         I: Iterator<Item = (&'a Memory, hal::memory::Segment)>,
     {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Ok(())
     }
 
     unsafe fn free_memory(&self, _memory: Memory) {
         // Let memory drop
     }
 
+    fn memory_budget(&self) -> Vec<hal::memory::HeapBudget> {
+        vec![hal::memory::HeapBudget {
+            usage: 0,
+            budget: 64 * 1024,
+        }]
+    }
+
+    unsafe fn set_memory_priority(&self, _memory: &Memory, _priority: hal::memory::Priority) {}
+
+    unsafe fn make_resident<'a, I>(&self, _memories: I) -> Result<(), device::OutOfMemory>
+    where
+        I: Iterator<Item = &'a Memory>,
+    {
+        Ok(())
+    }
+
+    unsafe fn evict<'a, I>(&self, _memories: I)
+    where
+        I: Iterator<Item = &'a Memory>,
+    {
+    }
+
     unsafe fn destroy_shader_module(&self, _: ()) {}
 
     unsafe fn destroy_render_pass(&self, _: ()) {}
@@ -545,16 +718,15 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_graphics_pipeline(&self, _: ()) {}
 
-    unsafe fn destroy_compute_pipeline(&self, _: ()) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn destroy_compute_pipeline(&self, _: ()) {}
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _: ()) {}
+
     unsafe fn destroy_framebuffer(&self, _: ()) {}
 
     unsafe fn destroy_buffer(&self, _: Buffer) {}
 
-    unsafe fn destroy_buffer_view(&self, _: ()) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn destroy_buffer_view(&self, _: ()) {}
 
     unsafe fn destroy_image(&self, _: Image) {}
 
@@ -566,45 +738,35 @@ impl device::Device<Backend> for Device {
 
     unsafe fn destroy_descriptor_set_layout(&self, _: DescriptorSetLayout) {}
 
-    unsafe fn destroy_fence(&self, _: ()) {}
+    unsafe fn destroy_fence(&self, _: Fence) {}
 
     unsafe fn destroy_semaphore(&self, _: ()) {}
 
-    unsafe fn destroy_event(&self, _: ()) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn destroy_event(&self, _: Event) {}
 
-    fn wait_idle(&self) -> Result<(), device::OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), device::OomOrDeviceLost> {
         Ok(())
     }
 
-    unsafe fn set_image_name(&self, _: &mut Image, _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn set_image_name(&self, image: &mut Image, name: &str) {
+        image.name = name.to_string();
     }
 
-    unsafe fn set_buffer_name(&self, _: &mut Buffer, _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn set_buffer_name(&self, buffer: &mut Buffer, name: &str) {
+        buffer.name = name.to_string();
     }
 
-    unsafe fn set_command_buffer_name(&self, _: &mut CommandBuffer, _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn set_command_buffer_name(&self, command_buffer: &mut CommandBuffer, name: &str) {
+        command_buffer.name = name.to_string();
     }
 
-    unsafe fn set_semaphore_name(&self, _: &mut (), _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_semaphore_name(&self, _: &mut (), _: &str) {}
 
-    unsafe fn set_fence_name(&self, _: &mut (), _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_fence_name(&self, _: &mut Fence, _: &str) {}
 
-    unsafe fn set_framebuffer_name(&self, _: &mut (), _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_framebuffer_name(&self, _: &mut (), _: &str) {}
 
-    unsafe fn set_render_pass_name(&self, _: &mut (), _: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_render_pass_name(&self, _: &mut (), _: &str) {}
 
     unsafe fn set_descriptor_set_name(&self, set: &mut DescriptorSet, name: &str) {
         set.name = name.to_string();
@@ -614,9 +776,11 @@ impl device::Device<Backend> for Device {
         layout.name = name.to_string();
     }
 
-    unsafe fn set_pipeline_layout_name(&self, _pipeline_layout: &mut (), _name: &str) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_pipeline_layout_name(&self, _pipeline_layout: &mut (), _name: &str) {}
+
+    unsafe fn set_graphics_pipeline_name(&self, _pipeline: &mut (), _name: &str) {}
+
+    unsafe fn set_compute_pipeline_name(&self, _pipeline: &mut (), _name: &str) {}
 
     unsafe fn create_allocate_external_buffer(
         &self,
@@ -632,7 +796,7 @@ impl device::Device<Backend> for Device {
         ),
         hal::external_memory::ExternalResourceError,
     > {
-        unimplemented!()
+        Err(hal::external_memory::ExternalResourceError::InvalidExternalHandle)
     }
 
     unsafe fn import_external_buffer(
@@ -649,7 +813,7 @@ impl device::Device<Backend> for Device {
         ),
         hal::external_memory::ExternalResourceError,
     > {
-        unimplemented!()
+        Err(hal::external_memory::ExternalResourceError::InvalidExternalHandle)
     }
 
     unsafe fn create_allocate_external_image(
@@ -670,7 +834,7 @@ impl device::Device<Backend> for Device {
         ),
         hal::external_memory::ExternalResourceError,
     > {
-        unimplemented!()
+        Err(hal::external_memory::ExternalResourceError::InvalidExternalHandle)
     }
 
     unsafe fn import_external_image(
@@ -684,6 +848,7 @@ impl device::Device<Backend> for Device {
         _sparse: hal::memory::SparseFlags,
         _view_caps: hal::image::ViewCapabilities,
         _type_mask: u32,
+        __size: u64,
     ) -> Result<
         (
             <Backend as gfx_hal::Backend>::Image,
@@ -691,7 +856,7 @@ impl device::Device<Backend> for Device {
         ),
         hal::external_memory::ExternalResourceError,
     > {
-        unimplemented!()
+        Err(hal::external_memory::ExternalResourceError::InvalidExternalHandle)
     }
 
     unsafe fn export_memory(
@@ -700,7 +865,7 @@ impl device::Device<Backend> for Device {
         _memory: &<Backend as gfx_hal::Backend>::Memory,
     ) -> Result<hal::external_memory::PlatformMemory, hal::external_memory::ExternalMemoryExportError>
     {
-        unimplemented!()
+        Err(hal::external_memory::ExternalMemoryExportError::InvalidExternalHandle)
     }
 
     unsafe fn drm_format_modifier(
@@ -710,12 +875,14 @@ impl device::Device<Backend> for Device {
         None
     }
 
-    unsafe fn reset_fence(&self, _: &mut ()) -> Result<(), device::OutOfMemory> {
+    unsafe fn reset_fence(&self, fence: &mut Fence) -> Result<(), device::OutOfMemory> {
+        fence.set_signaled(false);
         Ok(())
     }
 
-    unsafe fn wait_for_fence(&self, _: &(), _: u64) -> Result<bool, device::WaitError> {
-        Ok(true)
+    unsafe fn wait_for_fence(&self, fence: &Fence, _: u64) -> Result<bool, device::WaitError> {
+        // Nothing can signal the fence while waiting, so the wait times out.
+        Ok(fence.is_signaled())
     }
 
     unsafe fn set_display_power_state(
@@ -723,7 +890,7 @@ impl device::Device<Backend> for Device {
         _display: &display::Display<Backend>,
         _power_state: &display::control::PowerState,
     ) -> Result<(), display::control::DisplayControlError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Err(display::control::DisplayControlError::UnsupportedFeature)
     }
 
     unsafe fn register_device_event(
@@ -731,7 +898,7 @@ impl device::Device<Backend> for Device {
         _device_event: &display::control::DeviceEvent,
         _fence: &mut <Backend as hal::Backend>::Fence,
     ) -> Result<(), display::control::DisplayControlError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Err(display::control::DisplayControlError::UnsupportedFeature)
     }
 
     unsafe fn register_display_event(
@@ -740,16 +907,12 @@ impl device::Device<Backend> for Device {
         _display_event: &display::control::DisplayEvent,
         _fence: &mut <Backend as hal::Backend>::Fence,
     ) -> Result<(), display::control::DisplayControlError> {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        Err(display::control::DisplayControlError::UnsupportedFeature)
     }
 
-    fn start_capture(&self) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    fn start_capture(&self) {}
 
-    fn stop_capture(&self) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    fn stop_capture(&self) {}
 }
 
 #[derive(Debug)]
@@ -775,25 +938,25 @@ const QUEUE_FAMILY_ID: queue::QueueFamilyId = queue::QueueFamilyId(0);
 #[derive(Debug)]
 pub struct CommandPool;
 impl pool::CommandPool<Backend> for CommandPool {
-    unsafe fn allocate_one(&mut self, level: command::Level) -> CommandBuffer {
-        assert_eq!(
-            level,
-            command::Level::Primary,
-            "Only primary command buffers are supported"
-        );
-        CommandBuffer
+    unsafe fn allocate_one(&mut self, _: command::Level) -> CommandBuffer {
+        CommandBuffer {
+            name: String::new(),
+        }
     }
 
     unsafe fn reset(&mut self, _: bool) {}
 
     unsafe fn free<I>(&mut self, _: I) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+        // Let the command buffers drop
     }
 }
 
 /// Dummy command buffer, which ignores all the calls.
 #[derive(Debug)]
-pub struct CommandBuffer;
+pub struct CommandBuffer {
+    /// User-defined name for this command buffer
+    name: String,
+}
 impl command::CommandBuffer<Backend> for CommandBuffer {
     unsafe fn begin(
         &mut self,
@@ -804,9 +967,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn finish(&mut self) {}
 
-    unsafe fn reset(&mut self, _: bool) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn reset(&mut self, _: bool) {}
 
     unsafe fn pipeline_barrier<'a, T>(
         &mut self,
@@ -818,13 +979,9 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     {
     }
 
-    unsafe fn fill_buffer(&mut self, _: &Buffer, _: hal::buffer::SubRange, _: u32) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn fill_buffer(&mut self, _: &Buffer, _: hal::buffer::SubRange, _: u32) {}
 
-    unsafe fn update_buffer(&mut self, _: &Buffer, _: hal::buffer::Offset, _: &[u8]) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn update_buffer(&mut self, _: &Buffer, _: hal::buffer::Offset, _: &[u8]) {}
 
     unsafe fn clear_image<T>(
         &mut self,
@@ -833,12 +990,9 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: command::ClearValue,
         _: T,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn clear_attachments<T, U>(&mut self, _: T, _: U) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn clear_attachments<T, U>(&mut self, _: T, _: U) {}
 
     unsafe fn resolve_image<T>(
         &mut self,
@@ -848,7 +1002,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: hal::image::Layout,
         _: T,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
     unsafe fn blit_image<T>(
@@ -860,7 +1013,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: hal::image::Filter,
         _: T,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
     unsafe fn bind_index_buffer(
@@ -869,7 +1021,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: hal::buffer::SubRange,
         _: hal::IndexType,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
     unsafe fn bind_vertex_buffers<'a, T>(&mut self, _: u32, _: T)
@@ -882,33 +1033,21 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn set_scissors<T>(&mut self, _: u32, _: T) {}
 
-    unsafe fn set_stencil_reference(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_stencil_reference(&mut self, _: pso::Face, _: pso::StencilValue) {}
 
-    unsafe fn set_stencil_read_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_stencil_read_mask(&mut self, _: pso::Face, _: pso::StencilValue) {}
 
-    unsafe fn set_stencil_write_mask(&mut self, _: pso::Face, _: pso::StencilValue) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_stencil_write_mask(&mut self, _: pso::Face, _: pso::StencilValue) {}
 
-    unsafe fn set_blend_constants(&mut self, _: pso::ColorValue) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_blend_constants(&mut self, _: pso::ColorValue) {}
 
-    unsafe fn set_depth_bounds(&mut self, _: Range<f32>) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_depth_bounds(&mut self, _: Range<f32>) {}
 
-    unsafe fn set_line_width(&mut self, _: f32) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_line_width(&mut self, _: f32) {}
 
-    unsafe fn set_depth_bias(&mut self, _: pso::DepthBias) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_depth_bias(&mut self, _: pso::DepthBias) {}
+
+    unsafe fn set_shading_rate(&mut self, _: pso::ShadingRate, _: [pso::ShadingRateCombiner; 2]) {}
 
     unsafe fn begin_render_pass<'a, T>(
         &mut self,
@@ -922,9 +1061,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     {
     }
 
-    unsafe fn next_subpass(&mut self, _: command::SubpassContents) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn next_subpass(&mut self, _: command::SubpassContents) {}
 
     unsafe fn end_render_pass(&mut self) {}
 
@@ -937,9 +1074,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         // Do nothing
     }
 
-    unsafe fn bind_compute_pipeline(&mut self, _: &()) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn bind_compute_pipeline(&mut self, _: &()) {}
 
     unsafe fn bind_compute_descriptor_sets<'a, I, J>(&mut self, _: &(), _: usize, _: I, _: J)
     where
@@ -948,18 +1083,38 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         // Do nothing
     }
 
-    unsafe fn dispatch(&mut self, _: hal::WorkGroupCount) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn dispatch(&mut self, _: hal::WorkGroupCount) {}
+
+    unsafe fn dispatch_indirect(&mut self, _: &Buffer, _: hal::buffer::Offset) {}
+
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        _: &[acceleration_structure::BuildDesc<Backend>],
+    ) {
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        _: &(),
+        _: &(),
+        _: acceleration_structure::CopyMode,
+    ) {
     }
 
-    unsafe fn dispatch_indirect(&mut self, _: &Buffer, _: hal::buffer::Offset) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _: &()) {}
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(&mut self, _: &(), _: usize, _: I, _: J)
+    where
+        I: Iterator<Item = &'a DescriptorSet>,
+    {
+        // Do nothing
     }
 
-    unsafe fn copy_buffer<T>(&mut self, _: &Buffer, _: &Buffer, _: T) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn trace_rays(&mut self, _: &pso::ShaderBindingTables<Backend>, _: hal::WorkGroupCount) {
     }
 
+    unsafe fn copy_buffer<T>(&mut self, _: &Buffer, _: &Buffer, _: T) {}
+
     unsafe fn copy_image<T>(
         &mut self,
         _: &Image,
@@ -968,7 +1123,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: hal::image::Layout,
         _: T,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
     unsafe fn copy_buffer_to_image<T>(
@@ -987,7 +1141,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: &Buffer,
         _: T,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
     unsafe fn draw(&mut self, _: Range<hal::VertexCount>, _: Range<hal::InstanceCount>) {}
@@ -1040,10 +1193,20 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     ) {
     }
 
-    unsafe fn draw_mesh_tasks(&mut self, _: hal::TaskCount, _: hal::TaskCount) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, _: u32, _: I)
+    where
+        I: Iterator<Item = (&'a Buffer, hal::buffer::SubRange)>,
+    {
     }
 
+    unsafe fn begin_transform_feedback(&mut self) {}
+
+    unsafe fn end_transform_feedback(&mut self) {}
+
+    unsafe fn draw_transform_feedback(&mut self, _: Range<hal::InstanceCount>) {}
+
+    unsafe fn draw_mesh_tasks(&mut self, _: hal::TaskCount, _: hal::TaskCount) {}
+
     unsafe fn draw_mesh_tasks_indirect(
         &mut self,
         _: &Buffer,
@@ -1051,7 +1214,6 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: hal::DrawCount,
         _: hal::buffer::Stride,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
     unsafe fn draw_mesh_tasks_indirect_count(
@@ -1063,51 +1225,36 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: u32,
         _: hal::buffer::Stride,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn set_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn set_event(&mut self, _: &Event, _: pso::PipelineStage) {}
 
-    unsafe fn reset_event(&mut self, _: &(), _: pso::PipelineStage) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn reset_event(&mut self, _: &Event, _: pso::PipelineStage) {}
 
     unsafe fn wait_events<'a, I, J>(&mut self, _: I, _: Range<pso::PipelineStage>, _: J)
     where
         J: Iterator<Item = hal::memory::Barrier<'a, Backend>>,
     {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn begin_query(&mut self, _: query::Query<Backend>, _: query::ControlFlags) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn begin_query(&mut self, _: query::Query<Backend>, _: query::ControlFlags) {}
 
-    unsafe fn end_query(&mut self, _: query::Query<Backend>) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn end_query(&mut self, _: query::Query<Backend>) {}
 
-    unsafe fn reset_query_pool(&mut self, _: &(), _: Range<query::Id>) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn reset_query_pool(&mut self, _: &QueryPool, _: Range<query::Id>) {}
 
     unsafe fn copy_query_pool_results(
         &mut self,
-        _: &(),
+        _: &QueryPool,
         _: Range<query::Id>,
         _: &Buffer,
         _: hal::buffer::Offset,
         _: hal::buffer::Stride,
         _: query::ResultFlags,
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, _: query::Query<Backend>) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, _: query::Query<Backend>) {}
 
     unsafe fn push_graphics_constants(
         &mut self,
@@ -1116,34 +1263,26 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         _: u32,
         _: &[u32],
     ) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn push_compute_constants(&mut self, _: &(), _: u32, _: &[u32]) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn push_compute_constants(&mut self, _: &(), _: u32, _: &[u32]) {}
 
     unsafe fn execute_commands<'a, T>(&mut self, _: T)
     where
         T: Iterator<Item = &'a CommandBuffer>,
     {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
     }
 
-    unsafe fn insert_debug_marker(&mut self, _: &str, _: u32) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
-    unsafe fn begin_debug_marker(&mut self, _: &str, _: u32) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
-    unsafe fn end_debug_marker(&mut self) {
-        unimplemented!("{}", NOT_SUPPORTED_MESSAGE)
-    }
+    unsafe fn insert_debug_marker(&mut self, _: &str, _: u32) {}
+    unsafe fn begin_debug_marker(&mut self, _: &str, _: u32) {}
+    unsafe fn end_debug_marker(&mut self) {}
 }
 
 /// Dummy surface.
 #[derive(Debug)]
-pub struct Surface;
+pub struct Surface {
+    swapchain: Option<window::SwapchainConfig>,
+}
 impl window::Surface<Backend> for Surface {
     fn supports_queue_family(&self, _: &QueueFamily) -> bool {
         true
@@ -1181,15 +1320,17 @@ impl window::Surface<Backend> for Surface {
 }
 
 #[derive(Debug)]
-pub struct SwapchainImage;
+pub struct SwapchainImage {
+    image: Image,
+}
 impl Borrow<Image> for SwapchainImage {
     fn borrow(&self) -> &Image {
-        unimplemented!()
+        &self.image
     }
 }
 impl Borrow<()> for SwapchainImage {
     fn borrow(&self) -> &() {
-        unimplemented!()
+        &()
     }
 }
 
@@ -1199,23 +1340,43 @@ impl window::PresentationSurface<Backend> for Surface {
     unsafe fn configure_swapchain(
         &mut self,
         _: &Device,
-        _: window::SwapchainConfig,
+        config: window::SwapchainConfig,
     ) -> Result<(), window::SwapchainError> {
+        self.swapchain = Some(config);
         Ok(())
     }
 
-    unsafe fn unconfigure_swapchain(&mut self, _: &Device) {}
+    unsafe fn unconfigure_swapchain(&mut self, _: &Device) {
+        self.swapchain = None;
+    }
 
     unsafe fn acquire_image(
         &mut self,
         _: u64,
     ) -> Result<(SwapchainImage, Option<window::Suboptimal>), window::AcquireError> {
-        Ok((SwapchainImage, None))
+        let config = self.swapchain.as_ref().ok_or(window::OutOfDate)?;
+        let kind = hal::image::Kind::D2(
+            config.extent.width,
+            config.extent.height,
+            config.image_layers,
+            1,
+        );
+        let image = Image::new(kind, 1, config.format);
+        Ok((SwapchainImage { image }, None))
     }
 }
 
 #[derive(Debug)]
-pub struct Instance;
+pub struct Instance {
+    adapters: Vec<AdapterConfig>,
+}
+
+impl Instance {
+    /// Create an instance exposing an adapter for each of the given configurations.
+    pub fn with_adapters(adapters: Vec<AdapterConfig>) -> Self {
+        Instance { adapters }
+    }
+}
 
 impl hal::Instance<Backend> for Instance {
     fn create(name: &str, version: u32) -> Result<Self, hal::UnsupportedBackend> {
@@ -1223,24 +1384,21 @@ impl hal::Instance<Backend> for Instance {
             "Creating empty backend instance with name '{}' and version {}",
             name, version
         );
-        Ok(Instance)
+        Ok(Instance::with_adapters(vec![AdapterConfig::default()]))
     }
 
     fn enumerate_adapters(&self) -> Vec<adapter::Adapter<Backend>> {
-        // TODO: provide more mock adapters, with various qualities
-        let info = adapter::AdapterInfo {
-            name: "Mock Device".to_string(),
-            vendor: 0,
-            device: 1234,
-            device_type: adapter::DeviceType::Other,
-        };
-        let adapter = adapter::Adapter {
-            info,
-            physical_device: PhysicalDevice,
-            // TODO: multiple queue families
-            queue_families: vec![QueueFamily],
-        };
-        vec![adapter]
+        self.adapters
+            .iter()
+            .map(|config| adapter::Adapter {
+                info: config.info.clone(),
+                physical_device: PhysicalDevice {
+                    config: config.clone(),
+                },
+                // TODO: multiple queue families
+                queue_families: vec![QueueFamily],
+            })
+            .collect()
     }
 
     unsafe fn create_surface(
@@ -1249,7 +1407,7 @@ impl hal::Instance<Backend> for Instance {
     ) -> Result<Surface, hal::window::InitError> {
         // TODO: maybe check somehow that the given handle is valid?
         let _handle = raw_window_handle.raw_window_handle();
-        Ok(Surface)
+        Ok(Surface { swapchain: None })
     }
 
     unsafe fn destroy_surface(&self, _surface: Surface) {}
@@ -1262,6 +1420,6 @@ impl hal::Instance<Backend> for Instance {
         _alpha: display::DisplayPlaneAlpha,
         _image_extent: window::Extent2D,
     ) -> Result<Surface, display::DisplayPlaneSurfaceError> {
-        unimplemented!();
+        Err(display::DisplayPlaneSurfaceError::UnsupportedFeature)
     }
 }
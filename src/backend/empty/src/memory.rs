@@ -51,7 +51,7 @@ impl Memory {
         }
 
         let data = unsafe { &mut *self.data.get() };
-        Ok(data.as_mut_ptr())
+        Ok(data[segment.offset as usize..].as_mut_ptr())
     }
 }
 
@@ -0,0 +1,51 @@
+use hal::{buffer, query};
+
+use std::ops::Range;
+
+/// A query pool, whose queries are always available and report zeros.
+#[derive(Debug)]
+pub struct QueryPool {
+    /// Type of the queries of this pool.
+    ty: query::Type,
+    /// Number of queries in this pool.
+    count: query::Id,
+}
+
+impl QueryPool {
+    pub fn new(ty: query::Type, count: query::Id) -> Self {
+        QueryPool { ty, count }
+    }
+
+    pub fn get_results(
+        &self,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
+    ) {
+        assert!(queries.end <= self.count, "Query is out of the pool");
+        let num_values = match self.ty {
+            query::Type::PipelineStatistics(statistics) => statistics.bits().count_ones() as usize,
+            query::Type::Occlusion | query::Type::Timestamp => 1,
+        };
+        let value_size = if flags.contains(query::ResultFlags::BITS_64) {
+            8
+        } else {
+            4
+        };
+        let with_availability = flags.contains(query::ResultFlags::WITH_AVAILABILITY);
+
+        for index in 0..(queries.end - queries.start) as usize {
+            let start = index * stride as usize;
+            let end = start + (num_values + with_availability as usize) * value_size;
+            let result = &mut data[start..end];
+            for byte in result.iter_mut() {
+                *byte = 0;
+            }
+            // The availability follows the values of the query.
+            if with_availability {
+                result[num_values * value_size] = 1;
+            }
+        }
+    }
+}
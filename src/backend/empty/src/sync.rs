@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A fence, signaled by the submission it's attached to, as the submissions
+/// complete immediately.
+#[derive(Debug)]
+pub struct Fence {
+    signaled: AtomicBool,
+}
+
+impl Fence {
+    pub fn new(signaled: bool) -> Self {
+        Fence {
+            signaled: AtomicBool::new(signaled),
+        }
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        self.signaled.load(Ordering::Acquire)
+    }
+
+    pub fn set_signaled(&self, signaled: bool) {
+        self.signaled.store(signaled, Ordering::Release);
+    }
+}
+
+/// An event, only set and reset by the host.
+#[derive(Debug)]
+pub struct Event {
+    set: AtomicBool,
+}
+
+impl Event {
+    pub fn new() -> Self {
+        Event {
+            set: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.set.load(Ordering::Acquire)
+    }
+
+    pub fn set(&self, set: bool) {
+        self.set.store(set, Ordering::Release);
+    }
+}
@@ -9,9 +9,10 @@ use hal::{
 };
 
 use crate::{
-    info, native as n,
+    conv, info, native as n,
     pool::{self, BufferMemory},
-    Backend, ColorSlot,
+    translate::{Translation, TranslationPool},
+    Backend, BaseInstanceStats, ColorSlot,
 };
 
 use arrayvec::ArrayVec;
@@ -65,6 +66,10 @@ pub enum Command {
         vertices: Range<hal::VertexCount>,
         instances: Range<hal::InstanceCount>,
     },
+    DrawTransformFeedback {
+        primitive: u32,
+        instances: Range<hal::InstanceCount>,
+    },
     DrawIndexed {
         primitive: u32,
         index_type: u32,
@@ -79,6 +84,12 @@ pub enum Command {
         uniform: n::UniformDesc,
         buffer: BufferSlice,
     },
+    /// Set a member of an emulated uniform block from the host copy of the
+    /// bound buffer range, read at submission.
+    BindBlockUniform {
+        uniform: n::UniformDesc,
+        shadow: n::HostShadow,
+    },
     BindRasterizer {
         rasterizer: pso::Rasterizer,
     },
@@ -89,6 +100,7 @@ pub enum Command {
         depth_range_ptr: BufferSlice,
     },
     SetScissors(u32, BufferSlice),
+    SetScissorTest(bool),
     SetBlendColor(pso::ColorValue),
 
     /// Clear floating-point color drawbuffer of bound framebuffer.
@@ -101,14 +113,33 @@ pub enum Command {
     ClearBufferDepthStencil(Option<pso::DepthValue>, Option<pso::StencilValue>),
     /// Clear the currently bound texture with the given color.
     ClearTexture([f32; 4]),
-    FillBuffer(n::RawBuffer, Range<buffer::Offset>, u32),
+    /// Fill a range of a buffer, and of the host copy of its memory if any.
+    FillBuffer(
+        n::RawBuffer,
+        Range<buffer::Offset>,
+        u32,
+        Option<n::HostShadow>,
+    ),
+    /// Upload data to a buffer bound to its target, and to the host copy of its
+    /// memory if any.
+    UpdateBuffer {
+        buffer: n::RawBuffer,
+        target: u32,
+        offset: buffer::Offset,
+        data: BufferSlice,
+        shadow: Option<n::HostShadow>,
+    },
     BindFramebuffer {
         target: FrameBufferTarget,
         framebuffer: n::RawFramebuffer,
         colors: ArrayVec<[n::ImageView; MAX_COLOR_ATTACHMENTS]>,
         depth_stencil: Option<n::ImageView>,
+        // Layers of the attachments rendered with multiview, zero for a single layer.
+        view_mask: u32,
     },
     SetDrawColorBuffers(ArrayVec<[u8; MAX_COLOR_ATTACHMENTS]>),
+    /// Discard the contents of the attachments of the draw framebuffer.
+    InvalidateFramebuffer(ArrayVec<[u32; MAX_COLOR_ATTACHMENTS + 2]>),
     SetPatchSize(i32),
     BindProgram(<GlContext as glow::HasContext>::Program),
     SetBlend(Option<pso::BlendState>),
@@ -120,6 +151,8 @@ pub enum Command {
         dst_buffer: n::RawBuffer,
         src_target: u32,
         dst_target: u32,
+        /// Host copy of the destination memory, read back after the copy.
+        dst_shadow: Option<n::HostShadow>,
         data: command::BufferCopy,
     },
     CopyBufferToTexture {
@@ -128,6 +161,8 @@ pub enum Command {
         texture_target: n::TextureTarget,
         texture_format: n::TextureFormat,
         pixel_type: n::DataType,
+        /// Block description of compressed textures.
+        compressed: Option<hal::format::FormatDesc>,
         data: command::BufferImageCopy,
     },
     CopyBufferToRenderbuffer(n::RawBuffer, n::Renderbuffer, command::BufferImageCopy),
@@ -152,15 +187,40 @@ pub enum Command {
         dst_format: n::TextureFormat,
         data: command::ImageCopy,
     },
+    BlitImage {
+        src_image: n::ImageType,
+        dst_image: n::ImageType,
+        filter: u32,
+        data: command::ImageBlit,
+    },
     BindBufferRange(u32, u32, n::RawBuffer, i32, i32),
     BindTexture(u32, n::Texture, n::TextureTarget),
+    BindImage {
+        unit: u32,
+        texture: n::Texture,
+        level: i32,
+        layer: Option<i32>,
+        access: u32,
+        format: n::TextureFormat,
+    },
+    SetTextureSrgbDecode(u32, n::TextureTarget, bool),
     BindSampler(u32, n::Sampler),
     SetTextureSamplerSettings(u32, n::TextureTarget, image::SamplerDesc),
     SetColorMask(Option<DrawBuffer>, pso::ColorMask),
+    SetFramebufferSrgb(bool),
+    SetDepthBias(pso::DepthBias),
+    SetLineWidth(f32),
+    BeginTransformFeedback(n::TransformFeedback),
+    EndTransformFeedback,
     SetDepthMask(bool),
     SetStencilMask(pso::StencilValue),
     SetStencilMaskSeparate(pso::Sided<pso::StencilValue>),
     MemoryBarrier(u32),
+    TextureBarrier,
+    InsertDebugMarker(BufferSlice),
+    PushDebugGroup(BufferSlice),
+    PopDebugGroup,
+    WriteTimestamp(n::Query),
 }
 
 pub type FrameBufferTarget = u32;
@@ -215,14 +275,30 @@ struct Cache {
     attributes: Vec<n::AttributeDesc>,
     // Active uniforms
     uniforms: Vec<n::UniformDesc>,
+    // Shadow copy of the push constant data, re-applied when the program changes.
+    push_constants: Vec<u32>,
+    // Members of the emulated uniform blocks of the current program.
+    block_uniforms: Vec<n::BlockUniformDesc>,
+    // Host copies of the buffer ranges bound to the emulated uniform blocks, by slot.
+    uniform_blocks: Vec<Option<n::HostShadow>>,
     // Current depth mask
     depth_mask: Option<bool>,
     // Current stencil mask
     stencil_mask: Option<pso::Sided<pso::StencilValue>>,
+    // Last scissors set, with the index of the first one.
+    scissors: Option<(u32, BufferSlice)>,
     /// Currently bound samplers.
     samplers: Vec<Option<n::FatSampler>>,
     /// Current sampler redirection map.
     texture_slots: [TextureSlotInfo; MAX_TEXTURE_SLOTS],
+    // States that aren't baked into the current pipeline.
+    dynamic_states: hal::DynamicStates,
+    // Transform feedback setup of the current pipeline.
+    transform_feedback: Option<n::TransformFeedback>,
+    // Base instance uniform of the current pipeline, if its shaders were patched.
+    base_instance_uniform: Option<n::UniformDesc>,
+    // Value last written to the base instance uniform of the current program.
+    base_instance: Option<hal::InstanceCount>,
 }
 
 impl Cache {
@@ -241,10 +317,27 @@ impl Cache {
             vertex_buffer_descs: Vec::new(),
             attributes: Vec::new(),
             uniforms: Vec::new(),
+            push_constants: Vec::new(),
+            block_uniforms: Vec::new(),
+            uniform_blocks: Vec::new(),
             depth_mask: None,
             stencil_mask: None,
+            scissors: None,
             samplers: (0..MAX_SAMPLERS).map(|_| None).collect(),
             texture_slots: [TextureSlotInfo::default(); MAX_TEXTURE_SLOTS],
+            dynamic_states: hal::DynamicStates::all(),
+            transform_feedback: None,
+            base_instance_uniform: None,
+            base_instance: None,
+        }
+    }
+
+    fn is_dynamic_state(&self, state: hal::DynamicStates) -> bool {
+        if self.dynamic_states.contains(state) {
+            true
+        } else {
+            log::warn!("{:?} is baked into the bound pipeline, ignoring", state);
+            false
         }
     }
 }
@@ -264,6 +357,19 @@ impl From<hal::Limits> for Limits {
     }
 }
 
+/// Size and capacity of the storage of recorded commands.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CommandStorageStats {
+    /// Number of recorded commands.
+    pub commands: usize,
+    /// Number of commands that fit without reallocating.
+    pub command_capacity: usize,
+    /// Size of the recorded inline data, in bytes.
+    pub data_bytes: usize,
+    /// Size of the inline data that fits without reallocating, in bytes.
+    pub data_capacity: usize,
+}
+
 #[derive(Debug)]
 pub struct CommandStorage {
     pub(crate) memory: Arc<Mutex<BufferMemory>>,
@@ -325,6 +431,21 @@ impl CommandStorage {
         slice
     }
 
+    /// Run `fun` on the storage of the commands and data.
+    fn with_buffer<R>(&self, fun: impl FnOnce(&mut pool::OwnedBuffer) -> R) -> R {
+        let mut memory = self
+            .memory
+            .try_lock()
+            .expect("Trying to access a command buffer, while memory is in-use.");
+
+        fun(match *memory {
+            BufferMemory::Linear(ref mut buffer) => buffer,
+            BufferMemory::Individual {
+                ref mut storage, ..
+            } => storage.get_mut(&self.id).unwrap(),
+        })
+    }
+
     fn reset(&mut self) {
         let mut memory = self
             .memory
@@ -375,6 +496,10 @@ pub struct CommandBuffer {
     limits: Limits,
     legacy_featues: info::LegacyFeatures,
     active_attribs: usize,
+    pub(crate) base_instance_stats: BaseInstanceStats,
+    translation_pool: Option<Arc<TranslationPool>>,
+    /// Commands translated when finishing the command buffer, if any.
+    pub(crate) translation: Option<Translation>,
 }
 
 impl CommandBuffer {
@@ -383,6 +508,7 @@ impl CommandBuffer {
         limits: Limits,
         memory: Arc<Mutex<BufferMemory>>,
         legacy_featues: info::LegacyFeatures,
+        translation_pool: Option<Arc<TranslationPool>>,
     ) -> Self {
         let (id, individual_reset) = {
             let mut memory = memory
@@ -419,9 +545,39 @@ impl CommandBuffer {
             limits,
             active_attribs: 0,
             legacy_featues,
+            base_instance_stats: BaseInstanceStats::default(),
+            translation_pool,
+            translation: None,
         }
     }
 
+    /// Reserve capacity for at least `commands` more commands and `data_bytes` more
+    /// bytes of inline data, such as push constants and buffer updates, so that
+    /// recording them doesn't reallocate.
+    ///
+    /// Unless the pool was created with `RESET_INDIVIDUAL`, the storage is shared
+    /// by all the command buffers of the pool.
+    pub fn reserve(&mut self, commands: usize, data_bytes: usize) {
+        self.data.with_buffer(|buffer| {
+            buffer.commands.reserve(commands);
+            buffer.data.reserve(data_bytes);
+        })
+    }
+
+    /// Returns the size and capacity of the storage backing this command buffer.
+    ///
+    /// Unless the pool was created with `RESET_INDIVIDUAL`, the storage is shared
+    /// by all the command buffers of the pool. Finishing a command buffer translated
+    /// on worker threads moves its commands out of the storage.
+    pub fn storage_stats(&self) -> CommandStorageStats {
+        self.data.with_buffer(|buffer| CommandStorageStats {
+            commands: buffer.commands.len(),
+            command_capacity: buffer.commands.capacity(),
+            data_bytes: buffer.data.len(),
+            data_capacity: buffer.data.capacity(),
+        })
+    }
+
     // Soft reset only the buffers, but doesn't free any memory or clears memory
     // of the owning pool.
     pub(crate) fn soft_reset(&mut self) {
@@ -429,6 +585,8 @@ impl CommandBuffer {
         self.cache = Cache::new();
         self.pass_cache = None;
         self.cur_subpass = !0;
+        self.base_instance_stats = BaseInstanceStats::default();
+        self.translation = None;
     }
 
     fn update_blend_targets(&mut self, blend_targets: &[pso::ColorBlendDesc]) {
@@ -499,7 +657,14 @@ impl CommandBuffer {
             let (handle, range) = vertex_buffers[binding].as_ref().unwrap();
 
             let mut attribute = attribute.clone();
-            attribute.offset += range.start as u32;
+            match buffer::cast::<u32>(range.start) {
+                Ok(offset) => attribute.offset += offset,
+                Err(err) => {
+                    log::error!("Unable to bind vertex attribute: {}", err);
+                    self.cache.error_state = true;
+                    continue;
+                }
+            }
 
             match vertex_buffer_descs.get(binding) {
                 Some(&Some(desc)) => {
@@ -520,7 +685,8 @@ impl CommandBuffer {
     }
 
     fn begin_subpass(&mut self) {
-        let state = self.pass_cache.as_ref().unwrap();
+        // The cache is moved out while the clears are recorded, and restored after.
+        let state = self.pass_cache.take().unwrap();
         let subpass = &state.render_pass.subpasses[self.cur_subpass as usize];
 
         // See `begin_renderpass_cache` for clearing strategy
@@ -530,7 +696,7 @@ impl CommandBuffer {
         let attachment_indices = subpass
             .color_attachments
             .iter()
-            .map(|&index| state.attachments[index].color_index.unwrap())
+            .filter_map(|&index| state.attachments[index].color_index)
             .collect();
         self.data
             .push_cmd(Command::SetDrawColorBuffers(attachment_indices));
@@ -557,27 +723,13 @@ impl CommandBuffer {
                         continue;
                     }
                     let channel = view_format.base_format().1;
-                    let draw_color_index = draw_color_index as u32;
-
-                    self.data.push_cmd(match channel {
-                        ChannelType::Unorm
-                        | ChannelType::Snorm
-                        | ChannelType::Ufloat
-                        | ChannelType::Sfloat
-                        | ChannelType::Srgb
-                        | ChannelType::Uscaled
-                        | ChannelType::Sscaled => {
-                            Command::ClearBufferColorF(draw_color_index, unsafe {
-                                info.clear_value.color.float32
-                            })
-                        }
-                        ChannelType::Uint => Command::ClearBufferColorU(draw_color_index, unsafe {
-                            info.clear_value.color.uint32
-                        }),
-                        ChannelType::Sint => Command::ClearBufferColorI(draw_color_index, unsafe {
-                            info.clear_value.color.sint32
-                        }),
-                    });
+                    if let Some(command) =
+                        self.clear_color_command(draw_color_index as u32, channel, unsafe {
+                            info.clear_value.color
+                        })
+                    {
+                        self.data.push_cmd(command);
+                    }
                 }
                 // Clear depth-stencil target
                 None => {
@@ -589,9 +741,6 @@ impl CommandBuffer {
                         None
                     };
 
-                    // Only reset depth mask if it was non writable
-                    let depth_mask = self.cache.depth_mask.filter(|mask| !mask);
-
                     let stencil = if view_format.is_stencil()
                         && rat.stencil_ops.load == pass::AttachmentLoadOp::Clear
                     {
@@ -600,34 +749,117 @@ impl CommandBuffer {
                         None
                     };
 
-                    let stencil_mask = self
-                        .cache
-                        .stencil_mask
-                        .filter(|mask| mask.front != !0 || mask.back != !0);
+                    self.clear_depth_stencil(depth, stencil);
+                }
+            }
+        }
 
-                    // Temporarily reset masks as they may prevent buffer clear in gl
-                    if depth_mask.is_some() || self.cache.depth_mask.is_none() {
-                        self.data.push_cmd(Command::SetDepthMask(true));
-                    }
-                    if stencil_mask.is_some() || self.cache.stencil_mask.is_none() {
-                        self.data.push_cmd(Command::SetStencilMask(!0));
-                    }
+        self.pass_cache = Some(state);
+    }
 
-                    if depth.is_some() || stencil.is_some() {
-                        self.data
-                            .push_cmd(Command::ClearBufferDepthStencil(depth, stencil));
-                    }
+    /// Clear the depth-stencil attachment, resetting the write masks
+    /// which would prevent the clear, and restoring them afterwards.
+    fn clear_depth_stencil(
+        &mut self,
+        depth: Option<pso::DepthValue>,
+        stencil: Option<pso::StencilValue>,
+    ) {
+        if depth.is_none() && stencil.is_none() {
+            return;
+        }
 
-                    // Restore masks if they were reset
-                    if let Some(mask) = depth_mask {
-                        self.data.push_cmd(Command::SetDepthMask(mask));
-                    }
-                    if let Some(mask) = stencil_mask {
-                        self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
-                    }
+        // Only reset depth mask if it was non writable
+        let depth_mask = self.cache.depth_mask.filter(|mask| !mask);
+        let stencil_mask = self
+            .cache
+            .stencil_mask
+            .filter(|mask| mask.front != !0 || mask.back != !0);
+
+        // Temporarily reset masks as they may prevent buffer clear in gl
+        if depth.is_some() && (depth_mask.is_some() || self.cache.depth_mask.is_none()) {
+            self.data.push_cmd(Command::SetDepthMask(true));
+        }
+        if stencil.is_some() && (stencil_mask.is_some() || self.cache.stencil_mask.is_none()) {
+            self.data.push_cmd(Command::SetStencilMask(!0));
+        }
+
+        self.data
+            .push_cmd(Command::ClearBufferDepthStencil(depth, stencil));
+
+        // Restore masks if they were reset
+        if let (Some(mask), Some(_)) = (depth_mask, depth) {
+            self.data.push_cmd(Command::SetDepthMask(mask));
+        }
+        if let (Some(mask), Some(_)) = (stencil_mask, stencil) {
+            self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
+        }
+    }
+
+    /// Clear a color draw buffer of the current subpass, resetting its write mask
+    /// and restoring the one of the bound pipeline afterwards.
+    fn clear_color(
+        &mut self,
+        draw_buffer: DrawBuffer,
+        channel: ChannelType,
+        value: command::ClearColor,
+    ) {
+        let command = match self.clear_color_command(draw_buffer, channel, value) {
+            Some(command) => command,
+            None => return,
+        };
+        self.data.push_cmd(Command::SetColorMask(
+            Some(draw_buffer),
+            pso::ColorMask::ALL,
+        ));
+        self.data.push_cmd(command);
+        if let Some(Some(target)) = self.cache.blend_targets.get(draw_buffer as usize) {
+            self.data
+                .push_cmd(Command::SetColorMask(Some(draw_buffer), target.mask));
+        }
+    }
+
+    /// Command clearing a color draw buffer with the value matching the channel
+    /// type of its format.
+    ///
+    /// Without `glClearBuffer`, only the first draw buffer can be cleared, and only
+    /// with float values. Other clears are skipped and put the buffer in error.
+    fn clear_color_command(
+        &mut self,
+        draw_buffer: DrawBuffer,
+        channel: ChannelType,
+        value: command::ClearColor,
+    ) -> Option<Command> {
+        let command = match channel {
+            ChannelType::Unorm
+            | ChannelType::Snorm
+            | ChannelType::Ufloat
+            | ChannelType::Sfloat
+            | ChannelType::Srgb
+            | ChannelType::Uscaled
+            | ChannelType::Sscaled => {
+                Command::ClearBufferColorF(draw_buffer, unsafe { value.float32 })
+            }
+            ChannelType::Uint => Command::ClearBufferColorU(draw_buffer, unsafe { value.uint32 }),
+            ChannelType::Sint => Command::ClearBufferColorI(draw_buffer, unsafe { value.sint32 }),
+        };
+        if !self
+            .legacy_featues
+            .contains(info::LegacyFeatures::CLEAR_BUFFER)
+        {
+            match command {
+                Command::ClearBufferColorF(0, _) => {}
+                _ => {
+                    log::error!(
+                        "Clearing draw buffer {} of {:?} format is not supported without `glClearBuffer`",
+                        draw_buffer,
+                        channel
+                    );
+                    self.cache.error_state = true;
+                    return None;
                 }
             }
         }
+        Some(command)
     }
 
     fn update_sampler_states(&mut self, dirty_textures: u32, dirty_samplers: u32) {
@@ -654,53 +886,201 @@ impl CommandBuffer {
         }
     }
 
+    /// Update the push constant data in the given range, in bytes,
+    /// and emit the uniforms fully covered by it.
+    fn update_push_constants(&mut self, offset: u32, constants: &[u32]) {
+        let start = offset as usize / 4;
+        let end = start + constants.len();
+        if self.cache.push_constants.len() < end {
+            self.cache.push_constants.resize(end, 0);
+        }
+        self.cache.push_constants[start..end].copy_from_slice(constants);
+        self.emit_push_constants(offset..end as u32 * 4);
+    }
+
+    // Bind the vertex attributes and the base instance uniform for a draw,
+    // returning the instances to pass to the draw call.
+    fn bind_instances(
+        &mut self,
+        mut instances: Range<hal::InstanceCount>,
+    ) -> Range<hal::InstanceCount> {
+        if instances.start != 0 {
+            self.base_instance_stats.draws += 1;
+        }
+
+        if let Some(ref uniform) = self.cache.base_instance_uniform {
+            if self.cache.base_instance != Some(instances.start) {
+                self.cache.base_instance = Some(instances.start);
+                let buffer = self.data.add(&[instances.start as i32]);
+                self.data.push_cmd(Command::BindUniform {
+                    uniform: uniform.clone(),
+                    buffer,
+                });
+            }
+            if instances.start != 0 {
+                self.base_instance_stats.patched_draws += 1;
+            }
+        }
+
+        if !self
+            .legacy_featues
+            .contains(info::LegacyFeatures::DRAW_INSTANCED_BASE)
+        {
+            if instances.start != 0 {
+                self.base_instance_stats.emulated_draws += 1;
+            }
+            instances.end -= instances.start;
+            self.bind_attributes(instances.start);
+            instances.start = 0;
+        } else {
+            self.bind_attributes(0);
+        }
+
+        instances
+    }
+
+    /// Emit the uniforms of the current program that lie within the given
+    /// range of the push constant data.
+    fn emit_push_constants(&mut self, range: Range<u32>) {
+        for uniform in self.cache.uniforms.iter() {
+            if uniform.offset < range.start || uniform.offset + uniform.size > range.end {
+                continue;
+            }
+            let start = uniform.offset as usize / 4;
+            let end = start + uniform.size as usize / 4;
+            if end > self.cache.push_constants.len() {
+                continue;
+            }
+            let buffer = self.data.add(&self.cache.push_constants[start..end]);
+            self.data.push_cmd(Command::BindUniform {
+                uniform: uniform.clone(),
+                buffer,
+            });
+        }
+    }
+
+    /// Emit the members of the emulated uniform blocks of the current program,
+    /// only for the given slot if any.
+    fn emit_block_uniforms(&mut self, slot: Option<u8>) {
+        for block_uniform in self.cache.block_uniforms.iter() {
+            if slot.map_or(false, |slot| slot != block_uniform.slot) {
+                continue;
+            }
+            let shadow = match self.cache.uniform_blocks.get(block_uniform.slot as usize) {
+                Some(&Some(shadow)) => shadow,
+                _ => continue,
+            };
+            self.data.push_cmd(Command::BindBlockUniform {
+                uniform: block_uniform.uniform.clone(),
+                shadow,
+            });
+        }
+    }
+
     fn bind_descriptor_sets<'a, I, J>(
         &mut self,
         layout: &n::PipelineLayout,
         first_set: usize,
         sets: I,
-        mut offsets: J,
+        offsets: J,
     ) where
         I: Iterator<Item = &'a n::DescriptorSet>,
         J: Iterator<Item = command::DescriptorSetOffset>,
     {
-        if let Some(_) = offsets.next() {
-            log::warn!("Dynamic offsets are not supported yet");
-        }
+        let offsets = offsets.collect::<Vec<_>>();
+        // Index of the first dynamic offset of the current set.
+        let mut first_offset = 0;
 
         let mut dirty_textures = 0u32;
         let mut dirty_samplers = 0u32;
         let mut set = first_set as usize;
         for desc_set in sets {
-            for (binding_layout, new_binding) in
-                desc_set.layout.iter().zip(desc_set.bindings.iter())
-            {
-                let binding = layout.sets[set].bindings[binding_layout.binding as usize] as u32;
+            let dynamic_offset =
+                |slot: n::DescriptorSlot| match dynamic_offset_index(&desc_set.layout, slot) {
+                    Some(index) => match offsets.get(first_offset + index) {
+                        Some(&offset) => offset,
+                        None => {
+                            log::error!("Missing dynamic offset of binding {}", slot.binding);
+                            0
+                        }
+                    },
+                    None => 0,
+                };
+
+            for &(slot, ref new_binding) in desc_set.bindings.iter() {
+                let binding = layout.sets[set].bindings[slot.binding as usize] as u32
+                    + slot.array_index as u32;
                 match *new_binding {
+                    n::DescSetBindings::Buffer {
+                        register: n::BindingRegister::UniformBuffers,
+                        shadow,
+                        ..
+                    } if !self
+                        .legacy_featues
+                        .contains(info::LegacyFeatures::CONSTANT_BUFFER) =>
+                    {
+                        let offset = dynamic_offset(slot) as usize;
+                        let slot = binding as usize;
+                        if self.cache.uniform_blocks.len() <= slot {
+                            self.cache.uniform_blocks.resize(slot + 1, None);
+                        }
+                        self.cache.uniform_blocks[slot] =
+                            shadow.map(|shadow| n::HostShadow(unsafe { shadow.0.add(offset) }));
+                        self.emit_block_uniforms(Some(binding as u8));
+                    }
                     n::DescSetBindings::Buffer {
                         register,
                         buffer,
                         offset,
                         size,
+                        ..
                     } => {
+                        let offset = offset + dynamic_offset(slot) as buffer::Offset;
+                        let (offset, size) = match (buffer::cast(offset), buffer::cast(size)) {
+                            (Ok(offset), Ok(size)) => (offset, size),
+                            (Err(err), _) | (_, Err(err)) => {
+                                log::error!("Unable to bind buffer descriptor: {}", err);
+                                self.cache.error_state = true;
+                                continue;
+                            }
+                        };
                         let bind_point = match register {
                             n::BindingRegister::UniformBuffers => glow::UNIFORM_BUFFER,
                             n::BindingRegister::StorageBuffers => glow::SHADER_STORAGE_BUFFER,
-                            n::BindingRegister::Textures => panic!("Wrong desc set binding"),
+                            n::BindingRegister::Textures | n::BindingRegister::Images => {
+                                panic!("Wrong desc set binding")
+                            }
                         };
                         self.data.push_cmd(Command::BindBufferRange(
-                            bind_point,
-                            binding,
-                            buffer,
-                            offset as i32,
-                            size as i32,
+                            bind_point, binding, buffer, offset, size,
                         ));
                     }
-                    n::DescSetBindings::Texture(texture, textype) => {
+                    n::DescSetBindings::Texture(texture, textype, srgb) => {
                         dirty_textures |= 1 << binding;
                         self.cache.texture_slots[binding as usize].tex_target = textype;
                         self.data
                             .push_cmd(Command::BindTexture(binding, texture, textype));
+                        // Views of sRGB images share the decoding state of the texture.
+                        if let Some(decode) = srgb {
+                            self.data
+                                .push_cmd(Command::SetTextureSrgbDecode(binding, textype, decode));
+                        }
+                    }
+                    n::DescSetBindings::Image {
+                        texture,
+                        level,
+                        layer,
+                        access,
+                        format,
+                    } => {
+                        self.data.push_cmd(Command::BindImage {
+                            unit: binding,
+                            texture,
+                            level,
+                            layer,
+                            access,
+                            format,
+                        });
                     }
                     n::DescSetBindings::Sampler(sampler) => {
                         dirty_samplers |= 1 << binding;
@@ -715,6 +1095,12 @@ impl CommandBuffer {
                 }
             }
 
+            first_offset += desc_set
+                .layout
+                .iter()
+                .filter(|binding| is_dynamic(binding))
+                .map(|binding| binding.count)
+                .sum::<usize>();
             set += 1;
         }
 
@@ -722,6 +1108,32 @@ impl CommandBuffer {
     }
 }
 
+fn is_dynamic(binding: &pso::DescriptorSetLayoutBinding) -> bool {
+    match binding.ty {
+        pso::DescriptorType::Buffer {
+            format: pso::BufferDescriptorFormat::Structured { dynamic_offset },
+            ..
+        } => dynamic_offset,
+        _ => false,
+    }
+}
+
+/// Index of the dynamic offset of a descriptor among the ones of its set,
+/// which are ordered by binding, then by array element.
+fn dynamic_offset_index(
+    layout: &[pso::DescriptorSetLayoutBinding],
+    slot: n::DescriptorSlot,
+) -> Option<usize> {
+    let mut index = 0;
+    for binding in layout.iter().filter(|binding| is_dynamic(binding)) {
+        if binding.binding == slot.binding {
+            return Some(index + slot.array_index as usize);
+        }
+        index += binding.count;
+    }
+    None
+}
+
 impl command::CommandBuffer<Backend> for CommandBuffer {
     unsafe fn begin(
         &mut self,
@@ -738,7 +1150,20 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn finish(&mut self) {
-        // no-op
+        // The commands of linear pools are shared by all their command buffers,
+        // so only the ones of individual command buffers can be taken out.
+        let pool = match self.translation_pool {
+            Some(ref pool) if self.individual_reset => pool,
+            _ => return,
+        };
+        let range =
+            self.data.buf.offset as usize..(self.data.buf.offset + self.data.buf.size) as usize;
+        let (commands, data_len) = self.data.with_buffer(|buffer| {
+            let commands = buffer.commands.drain(range).collect::<Vec<_>>();
+            (commands, buffer.data.len())
+        });
+        self.data.buf = BufferSlice::new();
+        self.translation = Some(pool.translate(commands, data_len));
     }
 
     unsafe fn reset(&mut self, _release_resources: bool) {
@@ -764,46 +1189,74 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         // doing a `set_graphics_pipeline` followed by `pipeline_barrier` may need
         // the vertex bindings to be reinstated.
         let mut mask = 0;
+        let mut feedback = false;
 
         for barrier in barriers {
             match barrier {
                 memory::Barrier::AllBuffers(access) => {
                     if access.start.contains(buffer::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_STORAGE_BARRIER_BIT;
+                        mask |= conv::buffer_barrier_bits(access.end);
                     }
                 }
                 memory::Barrier::Buffer { states, .. } => {
                     if states.start.contains(buffer::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_STORAGE_BARRIER_BIT;
+                        mask |= conv::buffer_barrier_bits(states.end);
                     }
                 }
                 memory::Barrier::AllImages(access) => {
                     if access.start.contains(image::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+                        mask |= conv::image_barrier_bits(access.end);
                     }
+                    feedback |= conv::is_feedback_barrier(access);
                 }
                 memory::Barrier::Image { states, .. } => {
                     if states.start.0.contains(image::Access::SHADER_WRITE) {
-                        mask |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+                        mask |= conv::image_barrier_bits(states.end.0);
                     }
+                    feedback |= conv::is_feedback_barrier(states.start.0..states.end.0);
                 }
+                // Every GL resource has its own storage, nothing is aliased.
+                memory::Barrier::Aliasing { .. } => {}
             }
         }
 
         if mask != 0 {
             self.data.push_cmd(Command::MemoryBarrier(mask));
         }
+        // Only the attachments of the bound framebuffer can be sampled while
+        // being rendered to, which is the case of subpass self-dependencies.
+        if feedback && self.pass_cache.is_some() {
+            self.data.push_cmd(Command::TextureBarrier);
+        }
     }
 
     unsafe fn fill_buffer(&mut self, buffer: &n::Buffer, sub: buffer::SubRange, data: u32) {
         let bounded_buffer = buffer.as_bound();
         let range = crate::resolve_sub_range(&sub, bounded_buffer.range);
-        self.data
-            .push_cmd(Command::FillBuffer(bounded_buffer.raw, range, data));
+        self.data.push_cmd(Command::FillBuffer(
+            bounded_buffer.raw,
+            range,
+            data,
+            bounded_buffer.shadow,
+        ));
+        if bounded_buffer.shadow.is_some() {
+            self.emit_block_uniforms(None);
+        }
     }
 
-    unsafe fn update_buffer(&mut self, _buffer: &n::Buffer, _offset: buffer::Offset, _data: &[u8]) {
-        unimplemented!()
+    unsafe fn update_buffer(&mut self, buffer: &n::Buffer, offset: buffer::Offset, data: &[u8]) {
+        let bounded_buffer = buffer.as_bound();
+        let data = self.data.add_raw(data);
+        self.data.push_cmd(Command::UpdateBuffer {
+            buffer: bounded_buffer.raw,
+            target: bounded_buffer.target,
+            offset: bounded_buffer.range.start + offset,
+            data,
+            shadow: bounded_buffer.shadow,
+        });
+        if bounded_buffer.shadow.is_some() {
+            self.emit_block_uniforms(None);
+        }
     }
 
     unsafe fn begin_render_pass<'a, T>(
@@ -826,9 +1279,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         //      select correct ClearBuffer variant.
         //    * Check for attachment loading clearing strategy
 
-        // TODO: store ops:
-        //   < GL 4.5: Ignore
-        //  >= GL 4.5: Invalidate framebuffer attachment when store op is `DONT_CARE`.
+        // The store ops are applied in `end_render_pass`.
 
         // 2./3.
         let mut attachments = Vec::new();
@@ -839,8 +1290,16 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
             let view = info.image_view.clone();
             let aspects = view.aspects();
             let color_index = if aspects.contains(Aspects::COLOR) {
-                colors.push(view);
-                Some(colors.len() as u8 - 1)
+                match colors.try_push(view) {
+                    Ok(()) => Some(colors.len() as u8 - 1),
+                    Err(_) => {
+                        log::error!(
+                            "Render pass has more than {} color attachments",
+                            colors.len()
+                        );
+                        None
+                    }
+                }
             } else {
                 depth_stencil = Some(view);
                 None
@@ -858,12 +1317,21 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         //Note: we currently only expect one sub-pass.
 
+        // GL has a single switch for the sRGB conversion of all attachments,
+        // which only affects the ones with sRGB storage.
+        let srgb = colors.iter().any(|view| view.srgb() == Some(true));
+        if srgb && colors.iter().any(|view| view.srgb() == Some(false)) {
+            log::warn!("Render pass mixes sRGB and linear views of sRGB images");
+        }
+
         self.data.push_cmd(Command::BindFramebuffer {
             target: glow::DRAW_FRAMEBUFFER,
             framebuffer: framebuffer.raw,
             colors,
             depth_stencil,
+            view_mask: render_pass.subpasses[0].view_mask,
         });
+        self.data.push_cmd(Command::SetFramebufferSrgb(srgb));
 
         // Enter first subpass
         self.cur_subpass = 0;
@@ -875,7 +1343,46 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     }
 
     unsafe fn end_render_pass(&mut self) {
-        // TODO
+        // Discard the contents that aren't stored, sparing tile-based GPUs from
+        // writing them back to memory, e.g. for the transient attachments.
+        let state = match self.pass_cache {
+            Some(ref state) => state,
+            None => return,
+        };
+        let mut discarded = ArrayVec::new();
+        for (rat, info) in state
+            .render_pass
+            .attachments
+            .iter()
+            .zip(state.attachments.iter())
+        {
+            match info.color_index {
+                Some(index) => {
+                    if rat.ops.store == pass::AttachmentStoreOp::DontCare {
+                        discarded.push(glow::COLOR_ATTACHMENT0 + index as u32);
+                    }
+                }
+                None => {
+                    let aspects = rat
+                        .format
+                        .map_or(Aspects::empty(), |format| format.surface_desc().aspects);
+                    if aspects.contains(Aspects::DEPTH)
+                        && rat.ops.store == pass::AttachmentStoreOp::DontCare
+                    {
+                        discarded.push(glow::DEPTH_ATTACHMENT);
+                    }
+                    if aspects.contains(Aspects::STENCIL)
+                        && rat.stencil_ops.store == pass::AttachmentStoreOp::DontCare
+                    {
+                        discarded.push(glow::STENCIL_ATTACHMENT);
+                    }
+                }
+            }
+        }
+        if !discarded.is_empty() {
+            self.data
+                .push_cmd(Command::InvalidateFramebuffer(discarded));
+        }
     }
 
     unsafe fn clear_image<T>(
@@ -897,10 +1404,16 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
             Some(fbo) => {
                 // TODO: reset color mask
                 // 2. ClearBuffer
+                let srgb = if image.srgb_storage {
+                    Some(image.channel == ChannelType::Srgb)
+                } else {
+                    None
+                };
                 let view = match image.object_type {
                     n::ImageType::Renderbuffer { raw, .. } => n::ImageView::Renderbuffer {
                         raw,
                         aspects: image.format_desc.aspects,
+                        srgb,
                     },
                     n::ImageType::Texture {
                         target,
@@ -918,9 +1431,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                                 aspects: Aspects::COLOR,
                                 layer_start: 0,
                                 layer_count: Some(layer_count),
-                                level_start: 0,
+                                level_start: image.base_level,
                                 level_count: Some(level_count),
                             },
+                            srgb,
+                            internal_format: None,
+                            owned: false,
                         }
                     }
                 };
@@ -929,23 +1445,18 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                     framebuffer: fbo,
                     colors: iter::once(view).collect(),
                     depth_stencil: None,
+                    view_mask: 0,
                 });
+                self.data
+                    .push_cmd(Command::SetFramebufferSrgb(srgb == Some(true)));
                 self.data
                     .push_cmd(Command::SetDrawColorBuffers(iter::once(0).collect()));
                 self.data
                     .push_cmd(Command::SetColorMask(None, pso::ColorMask::ALL));
 
-                self.data.push_cmd(match image.channel {
-                    ChannelType::Unorm
-                    | ChannelType::Snorm
-                    | ChannelType::Ufloat
-                    | ChannelType::Sfloat
-                    | ChannelType::Srgb
-                    | ChannelType::Uscaled
-                    | ChannelType::Sscaled => Command::ClearBufferColorF(0, color.float32),
-                    ChannelType::Uint => Command::ClearBufferColorU(0, color.uint32),
-                    ChannelType::Sint => Command::ClearBufferColorI(0, color.sint32),
-                });
+                if let Some(command) = self.clear_color_command(0, image.channel, color) {
+                    self.data.push_cmd(command);
+                }
 
                 //Note: color mask is not restored: we are outside of a render pass,
                 // and whatever needs to have the mask, including the pass, should set it.
@@ -963,39 +1474,128 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         }
     }
 
-    unsafe fn clear_attachments<T, U>(&mut self, _: T, _: U)
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
     where
         T: Iterator<Item = command::AttachmentClear>,
         U: Iterator<Item = pso::ClearRect>,
     {
-        unimplemented!()
+        let state = match self.pass_cache {
+            Some(ref state) => state,
+            None => panic!("`clear_attachments` can only be called inside a renderpass"),
+        };
+        let subpass = &state.render_pass.subpasses[self.cur_subpass as usize];
+        let clears = clears
+            .filter_map(|clear| match clear {
+                command::AttachmentClear::Color { index, value } => {
+                    let attachment =
+                        &state.render_pass.attachments[subpass.color_attachments[index]];
+                    let channel = attachment.format.unwrap().base_format().1;
+                    Some((Some((index as DrawBuffer, channel, value)), None, None))
+                }
+                command::AttachmentClear::DepthStencil { depth, stencil } => {
+                    if subpass.depth_stencil.is_none() {
+                        log::warn!("Subpass has no depth-stencil attachment to clear");
+                        return None;
+                    }
+                    Some((None, depth, stencil))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Clear rects are applied with the scissor test, which is
+        // otherwise left disabled, and the scissors are restored afterwards.
+        self.data.push_cmd(Command::SetScissorTest(true));
+        for rect in rects {
+            if rect.layers != (0..1) {
+                log::warn!(
+                    "Clearing a subset of the layers is not supported, clearing all of them"
+                );
+            }
+            let scissor = &[
+                rect.rect.x as i32,
+                rect.rect.y as i32,
+                rect.rect.w as i32,
+                rect.rect.h as i32,
+            ];
+            let scissor_ptr = self.data.add::<i32>(scissor);
+            self.data.push_cmd(Command::SetScissors(0, scissor_ptr));
+
+            for &(color, depth, stencil) in &clears {
+                match color {
+                    Some((draw_buffer, channel, value)) => {
+                        self.clear_color(draw_buffer, channel, value)
+                    }
+                    None => self.clear_depth_stencil(depth, stencil),
+                }
+            }
+        }
+        self.data.push_cmd(Command::SetScissorTest(false));
+        if let Some((first_scissor, scissors_ptr)) = self.cache.scissors {
+            self.data
+                .push_cmd(Command::SetScissors(first_scissor, scissors_ptr));
+        }
     }
 
     unsafe fn resolve_image<T>(
         &mut self,
-        _src: &n::Image,
+        src: &n::Image,
         _src_layout: image::Layout,
-        _dst: &n::Image,
+        dst: &n::Image,
         _dst_layout: image::Layout,
-        _regions: T,
+        regions: T,
     ) where
         T: Iterator<Item = command::ImageResolve>,
     {
-        unimplemented!()
+        // Blitting from a multisampled framebuffer resolves it,
+        // as long as the source and destination rectangles match.
+        for region in regions {
+            self.data.push_cmd(Command::BlitImage {
+                src_image: src.object_type,
+                dst_image: dst.object_type,
+                filter: glow::NEAREST,
+                data: command::ImageBlit {
+                    src_bounds: region.src_offset.into_bounds(&region.extent),
+                    src_subresource: region.src_subresource,
+                    dst_bounds: region.dst_offset.into_bounds(&region.extent),
+                    dst_subresource: region.dst_subresource,
+                    ycbcr_conversion: None,
+                },
+            });
+        }
     }
 
     unsafe fn blit_image<T>(
         &mut self,
-        _src: &n::Image,
+        src: &n::Image,
         _src_layout: image::Layout,
-        _dst: &n::Image,
+        dst: &n::Image,
         _dst_layout: image::Layout,
-        _filter: image::Filter,
-        _regions: T,
+        filter: image::Filter,
+        regions: T,
     ) where
         T: Iterator<Item = command::ImageBlit>,
     {
-        log::error!("Blit is not implemented");
+        let filter = match filter {
+            image::Filter::Nearest => glow::NEAREST,
+            image::Filter::Linear => glow::LINEAR,
+        };
+        for region in regions {
+            if !src.levels().contains(&region.src_subresource.level)
+                || !dst.levels().contains(&region.dst_subresource.level)
+            {
+                log::error!(
+                    "Blit region {:?} is out of the levels of the images",
+                    region
+                );
+                continue;
+            }
+            self.data.push_cmd(Command::BlitImage {
+                src_image: src.object_type,
+                dst_image: dst.object_type,
+                filter,
+                data: region,
+            });
+        }
     }
 
     unsafe fn bind_index_buffer(
@@ -1004,6 +1604,16 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         sub: buffer::SubRange,
         ty: hal::IndexType,
     ) {
+        if ty == hal::IndexType::U32
+            && !self
+                .legacy_featues
+                .contains(info::LegacyFeatures::INDEX_U32)
+        {
+            log::error!("32-bit indices are not supported");
+            self.cache.index_type_range = None;
+            self.cache.error_state = true;
+            return;
+        }
         let bounded_buffer = buffer.as_bound();
 
         self.cache.index_type_range =
@@ -1034,6 +1644,10 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     where
         T: Iterator<Item = pso::Viewport>,
     {
+        if !self.cache.is_dynamic_state(hal::DynamicStates::VIEWPORT) {
+            return;
+        }
+
         // OpenGL has two functions for setting the viewports.
         // Configuring the rectangle area and setting the depth bounds are separated.
         //
@@ -1079,6 +1693,10 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     where
         T: Iterator<Item = pso::Rect>,
     {
+        if !self.cache.is_dynamic_state(hal::DynamicStates::SCISSOR) {
+            return;
+        }
+
         let mut scissors_ptr = BufferSlice { offset: 0, size: 0 };
         let mut len = 0;
         for scissor in scissors {
@@ -1098,6 +1716,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                 self.cache.error_state = true;
             }
             n if n + first_scissor as usize <= self.limits.max_viewports => {
+                self.cache.scissors = Some((first_scissor, scissors_ptr));
                 self.data
                     .push_cmd(Command::SetScissors(first_scissor, scissors_ptr));
             }
@@ -1110,6 +1729,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
         assert!(!faces.is_empty());
+        if !self
+            .cache
+            .is_dynamic_state(hal::DynamicStates::STENCIL_REFERENCE)
+        {
+            return;
+        }
 
         let mut front = 0;
         let mut back = 0;
@@ -1137,12 +1762,36 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!();
     }
 
-    unsafe fn set_stencil_write_mask(&mut self, _faces: pso::Face, _value: pso::StencilValue) {
-        // set self.cache.stencil_mask once implemented
-        unimplemented!();
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        assert!(!faces.is_empty());
+        if !self
+            .cache
+            .is_dynamic_state(hal::DynamicStates::STENCIL_WRITE_MASK)
+        {
+            return;
+        }
+
+        let mut mask = self
+            .cache
+            .stencil_mask
+            .unwrap_or_else(|| pso::Sided::new(!0));
+        if faces.contains(pso::Face::FRONT) {
+            mask.front = value;
+        }
+        if faces.contains(pso::Face::BACK) {
+            mask.back = value;
+        }
+        self.cache.stencil_mask = Some(mask);
+        self.data.push_cmd(Command::SetStencilMaskSeparate(mask));
     }
 
     unsafe fn set_blend_constants(&mut self, cv: pso::ColorValue) {
+        if !self
+            .cache
+            .is_dynamic_state(hal::DynamicStates::BLEND_CONSTANTS)
+        {
+            return;
+        }
         if self.cache.blend_color != Some(cv) {
             self.cache.blend_color = Some(cv);
             self.data.push_cmd(Command::SetBlendColor(cv));
@@ -1153,12 +1802,26 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         log::warn!("Depth bounds test is not supported");
     }
 
-    unsafe fn set_line_width(&mut self, _width: f32) {
-        unimplemented!()
+    unsafe fn set_line_width(&mut self, width: f32) {
+        if self.cache.is_dynamic_state(hal::DynamicStates::LINE_WIDTH) {
+            self.data.push_cmd(Command::SetLineWidth(width));
+        }
     }
 
-    unsafe fn set_depth_bias(&mut self, _depth_bias: pso::DepthBias) {
-        unimplemented!()
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        if !self.cache.is_dynamic_state(hal::DynamicStates::DEPTH_BIAS) {
+            return;
+        }
+        self.data.push_cmd(Command::SetDepthBias(depth_bias));
+    }
+
+    unsafe fn set_shading_rate(
+        &mut self,
+        _rate: pso::ShadingRate,
+        _combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        // GL has no variable rate shading, every pixel is shaded
+        // as if the rate was 1x1.
     }
 
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline) {
@@ -1176,12 +1839,17 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         if self.cache.program != Some(pipeline.program) {
             self.cache.program = Some(pipeline.program);
             self.data.push_cmd(Command::BindProgram(pipeline.program));
+            self.cache.uniforms = pipeline.uniforms.clone();
+            self.cache.block_uniforms = pipeline.block_uniforms.clone();
+            self.cache.base_instance = None;
+            self.emit_push_constants(0..!0);
+            self.emit_block_uniforms(None);
         }
 
         self.cache.attributes = pipeline.attributes.clone();
+        self.cache.base_instance_uniform = pipeline.base_instance_uniform.clone();
         self.cache.vertex_buffer_descs = pipeline.vertex_buffers.clone();
-
-        self.cache.uniforms = pipeline.uniforms.clone();
+        self.cache.transform_feedback = pipeline.transform_feedback;
 
         self.update_blend_targets(&pipeline.blend_targets);
 
@@ -1195,6 +1863,9 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         ));
         self.cache.depth_mask = pipeline.depth.map(|d| d.write);
 
+        // Baked states are recorded like the dynamic ones,
+        // but can't be overwritten until another pipeline is bound.
+        self.cache.dynamic_states = hal::DynamicStates::all();
         if let Some(ref vp) = pipeline.baked_states.viewport {
             self.set_viewports(0, iter::once(vp.clone()));
         }
@@ -1207,6 +1878,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         if let Some(ref bounds) = pipeline.baked_states.depth_bounds {
             self.set_depth_bounds(bounds.clone());
         }
+        self.cache.dynamic_states = pipeline.dynamic_states;
 
         let mut dirty_textures = 0u32;
         for (texture_index, (slot, &sampler_index)) in self
@@ -1243,6 +1915,10 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         if self.cache.program != Some(pipeline.program) {
             self.cache.program = Some(pipeline.program);
             self.data.push_cmd(Command::BindProgram(pipeline.program));
+            self.cache.uniforms = pipeline.uniforms.clone();
+            self.cache.block_uniforms = pipeline.block_uniforms.clone();
+            self.emit_push_constants(0..!0);
+            self.emit_block_uniforms(None);
         }
     }
 
@@ -1271,6 +1947,47 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         ));
     }
 
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        _descs: &[hal::acceleration_structure::BuildDesc<Backend>],
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        _src: &(),
+        _dst: &(),
+        _mode: hal::acceleration_structure::CopyMode,
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a n::DescriptorSet>,
+        J: Iterator<Item = command::DescriptorSetOffset>,
+    {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _tables: &pso::ShaderBindingTables<Backend>,
+        _count: hal::WorkGroupCount,
+    ) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &n::Buffer, dst: &n::Buffer, regions: T)
     where
         T: Iterator<Item = command::BufferCopy>,
@@ -1287,6 +2004,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                 dst_buffer: dst_bounded_buffer.raw,
                 src_target: src_bounded_buffer.target,
                 dst_target: dst_bounded_buffer.target,
+                dst_shadow: dst_bounded_buffer.shadow,
                 data: r,
             };
             self.data.push_cmd(cmd);
@@ -1294,6 +2012,9 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         if self.data.buf.size == old_size {
             log::error!("At least one region must be specified");
+        } else if dst_bounded_buffer.shadow.is_some() {
+            // The emulated uniform blocks read the host copy when they're bound.
+            self.emit_block_uniforms(None);
         }
     }
 
@@ -1310,15 +2031,22 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         let old_size = self.data.buf.size;
 
         for r in regions {
-            let cmd = match dst.object_type {
+            if !src.levels().contains(&r.src_subresource.level)
+                || !dst.levels().contains(&r.dst_subresource.level)
+            {
+                log::error!("Copy region {:?} is out of the levels of the images", r);
+                continue;
+            }
+            let src_image = src.plane(r.src_subresource.aspects);
+            let cmd = match dst.plane(r.dst_subresource.aspects) {
                 n::ImageType::Renderbuffer { raw, format } => Command::CopyImageToRenderbuffer {
-                    src_image: src.object_type,
+                    src_image,
                     dst_renderbuffer: raw,
                     dst_format: format,
                     data: r,
                 },
                 n::ImageType::Texture { raw, target, .. } => {
-                    Command::CopyImageToTexture(src.object_type, raw, target, r)
+                    Command::CopyImageToTexture(src_image, raw, target, r)
                 }
             };
             self.data.push_cmd(cmd);
@@ -1342,8 +2070,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
         let src_bounded_buffer = src.as_bound();
         for mut r in regions {
+            if !dst.levels().contains(&r.image_layers.level) {
+                log::error!("Copy region {:?} is out of the levels of the image", r);
+                continue;
+            }
             r.buffer_offset += src_bounded_buffer.range.start;
-            let cmd = match dst.object_type {
+            let cmd = match dst.plane(r.image_layers.aspects) {
                 n::ImageType::Renderbuffer { raw, .. } => {
                     Command::CopyBufferToRenderbuffer(src_bounded_buffer.raw, raw, r)
                 }
@@ -1359,6 +2091,11 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
                     texture_target: target,
                     texture_format: format,
                     pixel_type,
+                    compressed: if dst.format_desc.is_compressed() {
+                        Some(dst.format_desc)
+                    } else {
+                        None
+                    },
                     data: r,
                 },
             };
@@ -1383,8 +2120,12 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         let dst_bounded_buffer = dst.as_bound();
 
         for mut r in regions {
+            if !src.levels().contains(&r.image_layers.level) {
+                log::error!("Copy region {:?} is out of the levels of the image", r);
+                continue;
+            }
             r.buffer_offset += dst_bounded_buffer.range.start;
-            let cmd = match src.object_type {
+            let cmd = match src.plane(r.image_layers.aspects) {
                 n::ImageType::Renderbuffer { raw, .. } => {
                     Command::CopyRenderbufferToBuffer(raw, dst_bounded_buffer.raw, r)
                 }
@@ -1414,18 +2155,9 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
     unsafe fn draw(
         &mut self,
         vertices: Range<hal::VertexCount>,
-        mut instances: Range<hal::InstanceCount>,
+        instances: Range<hal::InstanceCount>,
     ) {
-        if !self
-            .legacy_featues
-            .contains(info::LegacyFeatures::DRAW_INSTANCED_BASE)
-        {
-            instances.end -= instances.start;
-            self.bind_attributes(instances.start);
-            instances.start = 0;
-        } else {
-            self.bind_attributes(0);
-        }
+        let instances = self.bind_instances(instances);
 
         match self.cache.primitive {
             Some(primitive) => {
@@ -1446,18 +2178,9 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         &mut self,
         indices: Range<hal::IndexCount>,
         base_vertex: hal::VertexOffset,
-        mut instances: Range<hal::InstanceCount>,
+        instances: Range<hal::InstanceCount>,
     ) {
-        if !self
-            .legacy_featues
-            .contains(info::LegacyFeatures::DRAW_INSTANCED_BASE)
-        {
-            instances.end -= instances.start;
-            self.bind_attributes(instances.start);
-            instances.start = 0;
-        } else {
-            self.bind_attributes(0);
-        }
+        let instances = self.bind_instances(instances);
 
         let (index_type, buffer_range) = match &self.cache.index_type_range {
             Some((index_type, buffer_range)) => (index_type, buffer_range),
@@ -1541,6 +2264,67 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: Iterator<Item = (&'a n::Buffer, buffer::SubRange)>,
+    {
+        for (i, (buffer, sub)) in buffers.enumerate() {
+            let bounded_buffer = buffer.as_bound();
+            let range = crate::resolve_sub_range(&sub, bounded_buffer.range);
+            let (offset, size) = match (
+                buffer::cast(range.start),
+                buffer::cast(range.end - range.start),
+            ) {
+                (Ok(offset), Ok(size)) => (offset, size),
+                (Err(err), _) | (_, Err(err)) => {
+                    log::error!("Unable to bind transform feedback buffer: {}", err);
+                    self.cache.error_state = true;
+                    continue;
+                }
+            };
+            self.data.push_cmd(Command::BindBufferRange(
+                glow::TRANSFORM_FEEDBACK_BUFFER,
+                first_binding + i as u32,
+                bounded_buffer.raw,
+                offset,
+                size,
+            ));
+        }
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        match self.cache.transform_feedback {
+            Some(tf) => self.data.push_cmd(Command::BeginTransformFeedback(tf)),
+            None => {
+                log::warn!(
+                    "The bound pipeline doesn't capture any outputs with transform feedback"
+                );
+                self.cache.error_state = true;
+            }
+        }
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        self.data.push_cmd(Command::EndTransformFeedback);
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, instances: Range<hal::InstanceCount>) {
+        let instances = self.bind_instances(instances);
+
+        match self.cache.primitive {
+            Some(primitive) => {
+                self.data.push_cmd(Command::DrawTransformFeedback {
+                    primitive,
+                    instances,
+                });
+            }
+            None => {
+                log::warn!("No primitive bound. An active pipeline needs to be bound before calling `draw_transform_feedback`.");
+                self.cache.error_state = true;
+            }
+        }
+    }
+
     unsafe fn draw_mesh_tasks(&mut self, _: u32, _: u32) {
         unimplemented!()
     }
@@ -1588,7 +2372,7 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
 
     unsafe fn copy_query_pool_results(
         &mut self,
-        _pool: &(),
+        _pool: &n::QueryPool,
         _queries: Range<query::Id>,
         _buffer: &n::Buffer,
         _offset: buffer::Offset,
@@ -1602,50 +2386,50 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
-    unsafe fn reset_query_pool(&mut self, _pool: &(), _queries: Range<query::Id>) {
-        unimplemented!()
+    unsafe fn reset_query_pool(&mut self, _pool: &n::QueryPool, _queries: Range<query::Id>) {
+        // Writing a query overwrites its previous result, there is nothing to reset.
     }
 
-    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, _: query::Query<Backend>) {
-        unimplemented!()
+    unsafe fn write_timestamp(&mut self, _: pso::PipelineStage, query: query::Query<Backend>) {
+        // The timestamp is always taken once all the previous commands have completed.
+        self.data.push_cmd(Command::WriteTimestamp(
+            query.pool.queries[query.id as usize],
+        ));
     }
 
     unsafe fn push_graphics_constants(
         &mut self,
-        _layout: &n::PipelineLayout,
-        _stages: pso::ShaderStageFlags,
+        layout: &n::PipelineLayout,
+        stages: pso::ShaderStageFlags,
         offset: u32,
         constants: &[u32],
     ) {
-        let buffer = self.data.add(constants);
-
-        let uniforms = &self.cache.uniforms;
-        if uniforms.is_empty() {
-            unimplemented!()
-        }
-
-        let uniform = if offset == 0 {
-            // If offset is zero, we can just return the first item
-            // in our uniform list
-            uniforms.get(0).unwrap()
-        } else {
-            match uniforms.binary_search_by(|uniform| uniform.offset.cmp(&offset as _)) {
-                Ok(index) => uniforms.get(index).unwrap(),
-                Err(_) => panic!("No uniform found at offset: {}", offset),
-            }
-        }
-        .clone();
-
-        self.data.push_cmd(Command::BindUniform { uniform, buffer });
+        let end = offset + constants.len() as u32 * 4;
+        debug_assert!(layout
+            .push_constants
+            .iter()
+            .any(|(range_stages, range)| range_stages.contains(stages)
+                && range.start <= offset
+                && end <= range.end));
+        self.update_push_constants(offset, constants);
     }
 
     unsafe fn push_compute_constants(
         &mut self,
-        _layout: &n::PipelineLayout,
-        _offset: u32,
-        _constants: &[u32],
+        layout: &n::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
     ) {
-        unimplemented!()
+        let end = offset + constants.len() as u32 * 4;
+        debug_assert!(layout
+            .push_constants
+            .iter()
+            .any(
+                |(range_stages, range)| range_stages.contains(pso::ShaderStageFlags::COMPUTE)
+                    && range.start <= offset
+                    && end <= range.end
+            ));
+        self.update_push_constants(offset, constants);
     }
 
     unsafe fn execute_commands<'a, T>(&mut self, _buffers: T)
@@ -1655,13 +2439,15 @@ impl command::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
-    unsafe fn insert_debug_marker(&mut self, _name: &str, _color: u32) {
-        //TODO
+    unsafe fn insert_debug_marker(&mut self, name: &str, _color: u32) {
+        let name = self.data.add_raw(name.as_bytes());
+        self.data.push_cmd(Command::InsertDebugMarker(name));
     }
-    unsafe fn begin_debug_marker(&mut self, _name: &str, _color: u32) {
-        //TODO
+    unsafe fn begin_debug_marker(&mut self, name: &str, _color: u32) {
+        let name = self.data.add_raw(name.as_bytes());
+        self.data.push_cmd(Command::PushDebugGroup(name));
     }
     unsafe fn end_debug_marker(&mut self) {
-        //TODO
+        self.data.push_cmd(Command::PopDebugGroup);
     }
 }
@@ -1,5 +1,9 @@
 use crate::native::VertexAttribFunction;
-use hal::{format::Format, image as i, pso};
+use hal::{
+    buffer,
+    format::{Component, Format, SurfaceType},
+    image as i, pso,
+};
 
 /*
 pub fn _image_kind_to_gl(kind: i::Kind) -> t::GLenum {
@@ -34,6 +38,30 @@ pub fn filter_to_gl(mag: i::Filter, min: i::Filter, mip: i::Filter) -> (u32, u32
     (min_filter, mag_filter)
 }
 
+pub fn component_to_gl(component: Component) -> u32 {
+    match component {
+        Component::Zero => glow::ZERO,
+        Component::One => glow::ONE,
+        Component::R => glow::RED,
+        Component::G => glow::GREEN,
+        Component::B => glow::BLUE,
+        Component::A => glow::ALPHA,
+    }
+}
+
+/// `TEXTURE_REDUCTION_MODE_ARB` of `ARB_texture_filter_minmax`.
+pub const TEXTURE_REDUCTION_MODE: u32 = 0x9366;
+/// `WEIGHTED_AVERAGE_ARB` of `ARB_texture_filter_minmax`.
+const WEIGHTED_AVERAGE: u32 = 0x9367;
+
+pub fn reduction_mode_to_gl(mode: i::ReductionMode) -> u32 {
+    match mode {
+        i::ReductionMode::WeightedAverage => WEIGHTED_AVERAGE,
+        i::ReductionMode::Minimum => glow::MIN,
+        i::ReductionMode::Maximum => glow::MAX,
+    }
+}
+
 pub fn wrap_to_gl(w: i::WrapMode) -> u32 {
     match w {
         i::WrapMode::Tile => glow::REPEAT,
@@ -44,6 +72,64 @@ pub fn wrap_to_gl(w: i::WrapMode) -> u32 {
     }
 }
 
+/// Size in bytes of a single element of the given uniform type, tightly packed
+/// as expected by `glUniform*`, or `None` if the type isn't supported.
+pub fn uniform_type_size(utype: u32) -> Option<u32> {
+    Some(match utype {
+        glow::FLOAT | glow::INT => 4,
+        glow::FLOAT_VEC2 | glow::INT_VEC2 => 8,
+        glow::FLOAT_VEC3 | glow::INT_VEC3 => 12,
+        glow::FLOAT_VEC4 | glow::INT_VEC4 | glow::FLOAT_MAT2 => 16,
+        glow::FLOAT_MAT3 => 36,
+        glow::FLOAT_MAT4 => 64,
+        _ => return None,
+    })
+}
+
+/// Size and array stride in bytes of the given uniform type in a push constant
+/// block, following the std430 rules, or `None` if the type can't be used for
+/// push constants.
+pub fn push_constant_type_layout(utype: u32) -> Option<(u32, u32)> {
+    Some(match utype {
+        glow::FLOAT | glow::INT => (4, 4),
+        glow::FLOAT_VEC2 | glow::INT_VEC2 => (8, 8),
+        glow::FLOAT_VEC3 | glow::INT_VEC3 => (12, 16),
+        glow::FLOAT_VEC4 | glow::INT_VEC4 | glow::FLOAT_MAT2 => (16, 16),
+        // The columns are aligned to 16 bytes.
+        glow::FLOAT_MAT3 => (48, 48),
+        glow::FLOAT_MAT4 => (64, 64),
+        _ => return None,
+    })
+}
+
+/// Copy the elements of a push constant of the given uniform type out of the
+/// std430 layout of the push constant block, tightly packed as expected by
+/// `glUniform*`.
+///
+/// The elements of the arrays are `stride` bytes apart, and the columns of the
+/// matrices 16 bytes apart, the padding being stripped from both.
+pub fn pack_push_constant(utype: u32, data: &[u8]) -> Vec<u32> {
+    let (element_size, stride) = push_constant_type_layout(utype).unwrap();
+    let packed_size = uniform_type_size(utype).unwrap() as usize;
+    let (columns, column_size) = match utype {
+        glow::FLOAT_MAT3 => (3, 12),
+        _ => (1, packed_size),
+    };
+    let count = (data.len() - element_size as usize) / stride as usize + 1;
+    let mut packed = Vec::with_capacity(count * packed_size / 4);
+    for element in 0..count {
+        for column in 0..columns {
+            let start = element * stride as usize + column * 16;
+            packed.extend(
+                data[start..start + column_size]
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            );
+        }
+    }
+    packed
+}
+
 pub fn input_assember_to_gl_primitive(ia: &pso::InputAssemblerDesc) -> u32 {
     match (ia.primitive, ia.with_adjacency) {
         (pso::Primitive::PointList, false) => glow::POINTS,
@@ -61,6 +147,97 @@ pub fn input_assember_to_gl_primitive(ia: &pso::InputAssemblerDesc) -> u32 {
     }
 }
 
+/// Returns the primitive mode to capture with transform feedback while drawing
+/// the given primitive topology.
+pub fn transform_feedback_primitive(primitive: u32) -> Option<u32> {
+    match primitive {
+        glow::POINTS => Some(glow::POINTS),
+        glow::LINES | glow::LINE_STRIP => Some(glow::LINES),
+        glow::TRIANGLES | glow::TRIANGLE_STRIP => Some(glow::TRIANGLES),
+        _ => None,
+    }
+}
+
+/// Returns the `glMemoryBarrier` bits making shader writes visible to the
+/// given accesses of a buffer.
+pub fn buffer_barrier_bits(access: buffer::Access) -> u32 {
+    if access.contains(buffer::Access::MEMORY_READ) {
+        return glow::ALL_BARRIER_BITS;
+    }
+    let mut bits = 0;
+    if access.contains(buffer::Access::INDIRECT_COMMAND_READ) {
+        bits |= glow::COMMAND_BARRIER_BIT;
+    }
+    if access.contains(buffer::Access::INDEX_BUFFER_READ) {
+        bits |= glow::ELEMENT_ARRAY_BARRIER_BIT;
+    }
+    if access.contains(buffer::Access::VERTEX_BUFFER_READ) {
+        bits |= glow::VERTEX_ATTRIB_ARRAY_BARRIER_BIT;
+    }
+    if access.contains(buffer::Access::UNIFORM_READ) {
+        bits |= glow::UNIFORM_BARRIER_BIT;
+    }
+    // Texel buffers are read through buffer textures and images.
+    if access.intersects(buffer::Access::SHADER_READ | buffer::Access::SHADER_WRITE) {
+        bits |= glow::SHADER_STORAGE_BARRIER_BIT
+            | glow::TEXTURE_FETCH_BARRIER_BIT
+            | glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+    }
+    if access.intersects(
+        buffer::Access::TRANSFER_READ
+            | buffer::Access::TRANSFER_WRITE
+            | buffer::Access::HOST_READ
+            | buffer::Access::HOST_WRITE,
+    ) {
+        bits |= glow::BUFFER_UPDATE_BARRIER_BIT | glow::PIXEL_BUFFER_BARRIER_BIT;
+    }
+    if access.contains(buffer::Access::TRANSFORM_FEEDBACK_WRITE) {
+        bits |= glow::TRANSFORM_FEEDBACK_BARRIER_BIT;
+    }
+    bits
+}
+
+/// Returns the `glMemoryBarrier` bits making shader writes visible to the
+/// given accesses of an image.
+pub fn image_barrier_bits(access: i::Access) -> u32 {
+    if access.contains(i::Access::MEMORY_READ) {
+        return glow::ALL_BARRIER_BITS;
+    }
+    let mut bits = 0;
+    if access.intersects(i::Access::SHADER_READ | i::Access::INPUT_ATTACHMENT_READ) {
+        bits |= glow::TEXTURE_FETCH_BARRIER_BIT | glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+    }
+    if access.contains(i::Access::SHADER_WRITE) {
+        bits |= glow::SHADER_IMAGE_ACCESS_BARRIER_BIT;
+    }
+    if access.intersects(
+        i::Access::COLOR_ATTACHMENT_READ
+            | i::Access::COLOR_ATTACHMENT_WRITE
+            | i::Access::DEPTH_STENCIL_ATTACHMENT_READ
+            | i::Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+    ) {
+        bits |= glow::FRAMEBUFFER_BARRIER_BIT;
+    }
+    if access.intersects(
+        i::Access::TRANSFER_READ
+            | i::Access::TRANSFER_WRITE
+            | i::Access::HOST_READ
+            | i::Access::HOST_WRITE,
+    ) {
+        bits |= glow::TEXTURE_UPDATE_BARRIER_BIT | glow::PIXEL_BUFFER_BARRIER_BIT;
+    }
+    bits
+}
+
+/// Returns true if the barrier makes attachment writes visible to shader reads
+/// of the same image, which requires a `glTextureBarrier` inside a render pass.
+pub fn is_feedback_barrier(access: std::ops::Range<i::Access>) -> bool {
+    let attachment_writes =
+        i::Access::COLOR_ATTACHMENT_WRITE | i::Access::DEPTH_STENCIL_ATTACHMENT_WRITE;
+    let shader_reads = i::Access::SHADER_READ | i::Access::INPUT_ATTACHMENT_READ;
+    access.start.intersects(attachment_writes) && access.end.intersects(shader_reads)
+}
+
 pub struct FormatDescription {
     pub tex_internal: u32,
     pub tex_external: u32,
@@ -87,6 +264,78 @@ impl FormatDescription {
     }
 }
 
+impl FormatDescription {
+    /// Describe a block-compressed format, which is uploaded with its internal format.
+    fn compressed(tex_internal: u32, num_components: u8) -> Self {
+        FormatDescription::new(
+            tex_internal,
+            tex_internal,
+            glow::UNSIGNED_BYTE,
+            num_components,
+            VertexAttribFunction::Float,
+        )
+    }
+}
+
+/// Return the feature required by a block-compressed surface type.
+pub fn compression_feature(surface: SurfaceType) -> Option<hal::Features> {
+    use hal::format::SurfaceType::*;
+    Some(match surface {
+        BC1_RGB | BC1_RGBA | BC2 | BC3 | BC4 | BC5 | BC6 | BC7 => hal::Features::FORMAT_BC,
+        ETC2_R8_G8_B8 | ETC2_R8_G8_B8_A1 | ETC2_R8_G8_B8_A8 | EAC_R11 | EAC_R11_G11 => {
+            hal::Features::FORMAT_ETC2
+        }
+        ASTC_4x4 | ASTC_5x4 | ASTC_5x5 | ASTC_6x5 | ASTC_6x6 | ASTC_8x5 | ASTC_8x6 | ASTC_8x8
+        | ASTC_10x5 | ASTC_10x6 | ASTC_10x8 | ASTC_10x10 | ASTC_12x10 | ASTC_12x12 => {
+            hal::Features::FORMAT_ASTC_LDR
+        }
+        _ => return None,
+    })
+}
+
+/// Return the sRGB format sharing the texel layout of a linear format.
+pub fn srgb_format(format: Format) -> Option<Format> {
+    match format {
+        Format::Rgba8Unorm => Some(Format::Rgba8Srgb),
+        Format::Bgra8Unorm => Some(Format::Bgra8Srgb),
+        _ => None,
+    }
+}
+
+/// Check if the format is renderable on all the supported GL and GLES versions.
+///
+/// This is needed by `glBlitFramebuffer`, which attaches both images to framebuffers,
+/// when the driver can't be asked with `glGetInternalformativ`.
+pub fn is_renderable(format: Format) -> bool {
+    use hal::format::Format::*;
+    match format {
+        R8Unorm | Rg8Unorm | Rgba8Unorm | Rgba8Srgb | Bgra8Unorm | Bgra8Srgb => true,
+        R8Uint | R8Sint | Rg8Uint | Rg8Sint | Rgba8Uint | Rgba8Sint => true,
+        R16Uint | R16Sint | Rg16Uint | Rg16Sint | Rgba16Uint | Rgba16Sint => true,
+        R32Uint | R32Sint | Rg32Uint | Rg32Sint | Rgba32Uint | Rgba32Sint => true,
+        D16Unorm | D24UnormS8Uint | D32Sfloat | D32SfloatS8Uint => true,
+        _ => false,
+    }
+}
+
+/// Check if the format can back a texel buffer on all the supported GL and GLES versions.
+///
+/// Texture buffers only accept the formats listed by `glTexBufferRange`, the
+/// 16-bit normalized ones being missing from GLES.
+pub fn is_texel_buffer_format(format: Format) -> bool {
+    use hal::format::Format::*;
+    match format {
+        R8Unorm | Rg8Unorm | Rgba8Unorm => true,
+        R8Uint | R8Sint | Rg8Uint | Rg8Sint | Rgba8Uint | Rgba8Sint => true,
+        R16Uint | R16Sint | Rg16Uint | Rg16Sint | Rgba16Uint | Rgba16Sint => true,
+        R16Sfloat | Rg16Sfloat | Rgba16Sfloat => true,
+        R32Uint | R32Sint | Rg32Uint | Rg32Sint | Rgb32Uint | Rgb32Sint => true,
+        Rgba32Uint | Rgba32Sint => true,
+        R32Sfloat | Rg32Sfloat | Rgb32Sfloat | Rgba32Sfloat => true,
+        _ => false,
+    }
+}
+
 pub fn describe_format(format: Format) -> Option<FormatDescription> {
     use crate::native::VertexAttribFunction::*;
     use hal::format::Format::*;
@@ -250,17 +499,134 @@ pub fn describe_format(format: Format) -> Option<FormatDescription> {
             2,
             Float,
         ),
+        Bc1RgbUnorm => FormatDescription::compressed(glow::COMPRESSED_RGB_S3TC_DXT1_EXT, 3),
+        Bc1RgbSrgb => FormatDescription::compressed(glow::COMPRESSED_SRGB_S3TC_DXT1_EXT, 3),
+        Bc1RgbaUnorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_S3TC_DXT1_EXT, 4),
+        Bc1RgbaSrgb => FormatDescription::compressed(glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT, 4),
+        Bc2Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_S3TC_DXT3_EXT, 4),
+        Bc2Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT, 4),
+        Bc3Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_S3TC_DXT5_EXT, 4),
+        Bc3Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT, 4),
+        Bc4Unorm => FormatDescription::compressed(glow::COMPRESSED_RED_RGTC1, 1),
+        Bc4Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_RED_RGTC1, 1),
+        Bc5Unorm => FormatDescription::compressed(glow::COMPRESSED_RG_RGTC2, 2),
+        Bc5Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_RG_RGTC2, 2),
+        Bc6hUfloat => FormatDescription::compressed(glow::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT, 3),
+        Bc6hSfloat => FormatDescription::compressed(glow::COMPRESSED_RGB_BPTC_SIGNED_FLOAT, 3),
+        Bc7Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_BPTC_UNORM, 4),
+        Bc7Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB_ALPHA_BPTC_UNORM, 4),
+        Etc2R8g8b8Unorm => FormatDescription::compressed(glow::COMPRESSED_RGB8_ETC2, 3),
+        Etc2R8g8b8Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ETC2, 3),
+        Etc2R8g8b8a1Unorm => {
+            FormatDescription::compressed(glow::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2, 4)
+        }
+        Etc2R8g8b8a1Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2, 4)
+        }
+        Etc2R8g8b8a8Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA8_ETC2_EAC, 4),
+        Etc2R8g8b8a8Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC, 4)
+        }
+        EacR11Unorm => FormatDescription::compressed(glow::COMPRESSED_R11_EAC, 1),
+        EacR11Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_R11_EAC, 1),
+        EacR11g11Unorm => FormatDescription::compressed(glow::COMPRESSED_RG11_EAC, 2),
+        EacR11g11Snorm => FormatDescription::compressed(glow::COMPRESSED_SIGNED_RG11_EAC, 2),
+        Astc4x4Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_4x4_KHR, 4),
+        Astc4x4Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_4x4_KHR, 4),
+        Astc5x4Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_5x4_KHR, 4),
+        Astc5x4Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_5x4_KHR, 4),
+        Astc5x5Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_5x5_KHR, 4),
+        Astc5x5Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_5x5_KHR, 4),
+        Astc6x5Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_6x5_KHR, 4),
+        Astc6x5Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_6x5_KHR, 4),
+        Astc6x6Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_6x6_KHR, 4),
+        Astc6x6Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_6x6_KHR, 4),
+        Astc8x5Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_8x5_KHR, 4),
+        Astc8x5Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_8x5_KHR, 4),
+        Astc8x6Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_8x6_KHR, 4),
+        Astc8x6Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_8x6_KHR, 4),
+        Astc8x8Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_8x8_KHR, 4),
+        Astc8x8Srgb => FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_8x8_KHR, 4),
+        Astc10x5Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_10x5_KHR, 4),
+        Astc10x5Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x5_KHR, 4)
+        }
+        Astc10x6Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_10x6_KHR, 4),
+        Astc10x6Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x6_KHR, 4)
+        }
+        Astc10x8Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_10x8_KHR, 4),
+        Astc10x8Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x8_KHR, 4)
+        }
+        Astc10x10Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_10x10_KHR, 4),
+        Astc10x10Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_10x10_KHR, 4)
+        }
+        Astc12x10Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_12x10_KHR, 4),
+        Astc12x10Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_12x10_KHR, 4)
+        }
+        Astc12x12Unorm => FormatDescription::compressed(glow::COMPRESSED_RGBA_ASTC_12x12_KHR, 4),
+        Astc12x12Srgb => {
+            FormatDescription::compressed(glow::COMPRESSED_SRGB8_ALPHA8_ASTC_12x12_KHR, 4)
+        }
 
         _ => return None,
     })
 }
 
 #[cfg(feature = "cross")]
-pub fn map_naga_stage_to_cross(stage: naga::ShaderStage) -> spirv_cross::spirv::ExecutionModel {
+pub fn map_stage_to_cross(stage: crate::native::ShaderStage) -> spirv_cross::spirv::ExecutionModel {
+    use crate::native::ShaderStage as Ss;
     use spirv_cross::spirv::ExecutionModel as Em;
     match stage {
-        naga::ShaderStage::Vertex => Em::Vertex,
-        naga::ShaderStage::Fragment => Em::Fragment,
-        naga::ShaderStage::Compute => Em::GlCompute,
+        Ss::Vertex => Em::Vertex,
+        Ss::Hull => Em::TessellationControl,
+        Ss::Domain => Em::TessellationEvaluation,
+        Ss::Geometry => Em::Geometry,
+        Ss::Fragment => Em::Fragment,
+        Ss::Compute => Em::GlCompute,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn std430(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_ne_bytes()).collect()
+    }
+
+    #[test]
+    fn push_constant_arrays_upload_all_elements() {
+        // vec4[2]
+        let data = std430(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(
+            pack_push_constant(glow::FLOAT_VEC4, &data),
+            [1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        // float[3]
+        let data = std430(&[1, 2, 3]);
+        assert_eq!(pack_push_constant(glow::FLOAT, &data), [1, 2, 3]);
+    }
+
+    #[test]
+    fn push_constant_arrays_strip_the_padding() {
+        // vec3[2], the last element without its padding.
+        let data = std430(&[1, 2, 3, 0, 4, 5, 6]);
+        assert_eq!(
+            pack_push_constant(glow::FLOAT_VEC3, &data),
+            [1, 2, 3, 4, 5, 6]
+        );
+        // mat3[2]
+        let data = std430(&[
+            1, 2, 3, 0, 4, 5, 6, 0, 7, 8, 9, 0, //
+            10, 11, 12, 0, 13, 14, 15, 0, 16, 17, 18, 0,
+        ]);
+        assert_eq!(
+            pack_push_constant(glow::FLOAT_MAT3, &data),
+            (1..=18).collect::<Vec<_>>()
+        );
     }
 }
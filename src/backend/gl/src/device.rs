@@ -1,12 +1,17 @@
 use crate::{
     command as cmd, conv,
-    info::LegacyFeatures,
+    info::{LegacyFeatures, MemoryInfoExtension},
+    memory_heaps,
+    memory_object::{self, EglImageFns, ImportHandle},
     native as n,
     pool::{BufferMemory, CommandPool, OwnedBuffer},
-    state, Backend as B, FastHashMap, GlContainer, GlContext, MemoryUsage, Share, Starc,
-    MAX_TEXTURE_SLOTS,
+    state,
+    translate::TranslationPool,
+    Backend as B, FastHashMap, GlContainer, GlContext, MemoryUsage, Share, Starc,
+    DEVICE_LOCAL_HEAP, MAX_COLOR_ATTACHMENTS, MAX_SAMPLERS, MAX_TEXTURE_SLOTS,
 };
 
+use auxil::instrument;
 use hal::{
     buffer, device as d,
     format::{ChannelType, Format, Swizzle},
@@ -18,7 +23,7 @@ use hal::{
 use glow::HasContext;
 use parking_lot::Mutex;
 
-use std::{ops::Range, slice, sync::Arc};
+use std::{ops::Range, os::raw::c_void, slice, sync::Arc};
 
 #[cfg(feature = "cross")]
 type CrossAst = spirv_cross::spirv::Ast<spirv_cross::glsl::Target>;
@@ -40,6 +45,17 @@ struct CompilationContext<'a> {
     layout: &'a n::PipelineLayout,
     sampler_map: &'a mut n::SamplerBindMap,
     name_binding_map: &'a mut FastHashMap<String, (n::BindingRegister, u8)>,
+    // Names of the vertex shader outputs, used for transform feedback.
+    output_names: &'a mut FastHashMap<pso::Location, String>,
+    // Number of views rendered at once with multiview, zero without it.
+    view_count: u32,
+    // Members of the emulated uniform blocks, by their uniform name, with
+    // the slot of their block and their offset in it.
+    block_members: &'a mut FastHashMap<String, (u8, u32)>,
+    // Offsets of the members of the push constant block, by their name.
+    push_constant_members: &'a mut FastHashMap<String, u32>,
+    // Storage buffer slot receiving the violations of the instrumented shaders.
+    violation_slot: Option<u8>,
 }
 
 impl<'a> CompilationContext<'a> {
@@ -48,6 +64,11 @@ impl<'a> CompilationContext<'a> {
             layout: self.layout,
             sampler_map: self.sampler_map,
             name_binding_map: self.name_binding_map,
+            output_names: self.output_names,
+            view_count: self.view_count,
+            block_members: self.block_members,
+            push_constant_members: self.push_constant_members,
+            violation_slot: self.violation_slot,
         }
     }
 }
@@ -59,8 +80,21 @@ pub struct Device {
     features: hal::Features,
     #[cfg(feature = "cross")]
     spv_options: naga::back::spv::Options,
+    base_instance_patching: bool,
+    translation_pool: Option<Arc<TranslationPool>>,
 }
 
+/// Uniform holding the base instance in vertex shaders patched by SPIRV-Cross.
+const BASE_INSTANCE_UNIFORM: &str = "SPIRV_Cross_BaseInstance";
+/// Prefix of the plain uniforms emulating uniform blocks, followed by the slot
+/// of the block.
+const UNIFORM_BUFFER_PREFIX: &str = "_gfx_ubo";
+
+// Queries of `GL_NVX_gpu_memory_info` and `GL_ATI_meminfo`.
+const GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX: u32 = 0x9048;
+const GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX: u32 = 0x9049;
+const TEXTURE_FREE_MEMORY_ATI: u32 = 0x87FC;
+
 impl Drop for Device {
     fn drop(&mut self) {
         self.share.open.set(false);
@@ -89,18 +123,248 @@ impl Device {
                     capabilities: None,
                 }
             },
+            base_instance_patching: false,
+            translation_pool: None,
+        }
+    }
+
+    /// Patch vertex shaders of pipelines created afterwards, so that `gl_InstanceIndex`
+    /// includes the first instance of the draw.
+    ///
+    /// GLSL's `gl_InstanceID` always starts at zero, so the first instance is passed
+    /// in a uniform instead, updated by the command buffer on each draw. This requires
+    /// the `cross` feature, as the patched vertex shaders are generated by SPIRV-Cross.
+    ///
+    /// The cost of this can be observed with `Queue::base_instance_stats`.
+    pub fn set_base_instance_patching(&mut self, enabled: bool) {
+        if enabled && !cfg!(feature = "cross") {
+            log::warn!("Base instance patching requires the `cross` feature");
+        }
+        self.base_instance_patching = enabled;
+    }
+
+    /// Translate the command buffers on `threads` worker threads when they are finished,
+    /// so that submitting them only executes the GL calls. Zero disables the translation.
+    ///
+    /// The translation drops the commands setting a state that is already set, and
+    /// checks that the commands only read their recorded data. It only applies to
+    /// the pools created afterwards with `RESET_INDIVIDUAL`, since the command
+    /// buffers of the other pools share their storage.
+    /// Threads are not available on the web, where this has no effect.
+    pub fn set_command_translation_threads(&mut self, threads: usize) {
+        if cfg!(target_arch = "wasm32") {
+            log::warn!("Command translation threads are not available on the web");
+            return;
+        }
+        self.translation_pool = if threads == 0 {
+            None
+        } else {
+            Some(Arc::new(TranslationPool::new(threads)))
+        };
+    }
+
+    /// Create an image from EGL images, such as the frames of a hardware video decoder,
+    /// without copying them. This requires `GL_OES_EGL_image`.
+    ///
+    /// Multi-planar formats take an EGL image per plane, e.g. the `R8` and `GR88`
+    /// DMA-BUF images of the luma and chroma planes of a `Nv12Unorm` frame. The EGL
+    /// images can be destroyed once the image is created, which keeps their storage.
+    /// The image doesn't need any memory to be bound.
+    pub unsafe fn image_from_egl_images(
+        &self,
+        egl_images: &[*const c_void],
+        kind: i::Kind,
+        format: Format,
+    ) -> Result<n::Image, i::CreationError> {
+        let fns = match self.share.egl_image {
+            Some(ref fns) => fns,
+            None => {
+                log::error!("EGL images are not supported");
+                return Err(i::CreationError::Format(format));
+            }
+        };
+        let (width, height) = match kind {
+            i::Kind::D2(w, h, 1, 1) => (w, h),
+            _ => return Err(i::CreationError::Kind),
+        };
+        let planes = format.planes();
+        if egl_images.len() != planes.len().max(1) {
+            return Err(i::CreationError::Format(format));
+        }
+
+        if let Some((first, others)) = planes.split_first() {
+            let mut image =
+                self.egl_image_texture(fns, egl_images[0], width, height, first.format)?;
+            for (index, (plane, &egl_image)) in others.iter().zip(&egl_images[1..]).enumerate() {
+                let (w, h) = plane.extent(width, height);
+                match self.egl_image_texture(fns, egl_image, w, h, plane.format) {
+                    Ok(plane_image) => {
+                        image.extra_planes[index] = Some(plane_image.object_type);
+                        image.requirements.size += plane_image.requirements.size;
+                    }
+                    Err(err) => {
+                        d::Device::destroy_image(self, image);
+                        return Err(err);
+                    }
+                }
+            }
+            return Ok(image);
+        }
+        self.egl_image_texture(fns, egl_images[0], width, height, format)
+    }
+
+    /// Import a single plane DMA-BUF as an EGL image. The file descriptor stays
+    /// owned by the caller, and the texture keeps the storage of the EGL image.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    unsafe fn import_dma_buf_image(
+        &self,
+        fd: i32,
+        properties: Option<&hal::external_memory::DrmFormatImageProperties>,
+        kind: i::Kind,
+        num_levels: i::Level,
+        format: Format,
+        type_mask: u32,
+        size: u64,
+    ) -> Result<(n::Image, n::Memory), hal::external_memory::ExternalResourceError> {
+        use hal::external_memory::{DrmModifier, ExternalResourceError as Ere};
+        use std::convert::TryInto;
+
+        let (dma_buf, egl_image) = match (&self.share.dma_buf, &self.share.egl_image) {
+            (Some(dma_buf), Some(egl_image)) => (dma_buf, egl_image),
+            _ => return Err(Ere::InvalidExternalHandle),
+        };
+        let fourcc = memory_object::drm_fourcc(format).ok_or(Ere::InvalidExternalHandle)?;
+        let (width, height) = match kind {
+            i::Kind::D2(w, h, 1, 1) if num_levels == 1 => (w, h),
+            _ => return Err(Ere::InvalidExternalHandle),
+        };
+        let type_mask = type_mask & self.share.image_memory_type_mask();
+        if type_mask == 0 {
+            return Err(Ere::NoValidMemoryTypeId);
+        }
+        let (memory_type, _) = self.share.memory_types[type_mask.trailing_zeros() as usize];
+
+        // Without the layout of the exporter, the rows are assumed to be packed.
+        let (offset, pitch, modifier) = match properties {
+            Some(properties) => {
+                let plane = properties
+                    .plane_layouts
+                    .first()
+                    .ok_or(Ere::InvalidExternalHandle)?;
+                let modifier = match properties.drm_modifier {
+                    DrmModifier::Invalid => None,
+                    modifier => Some(
+                        modifier
+                            .try_into()
+                            .map_err(|_| Ere::InvalidExternalHandle)?,
+                    ),
+                };
+                (plane.slice.start as u32, plane.row_pitch as u32, modifier)
+            }
+            None => (0, width * (format.surface_desc().bits / 8) as u32, None),
+        };
+        let raw = dma_buf
+            .create_image(fd, width, height, fourcc, offset, pitch, modifier)
+            .ok_or_else(|| {
+                log::error!("Error creating an EGL image from a DMA-BUF");
+                Ere::InvalidExternalHandle
+            })?;
+        let result = self.egl_image_texture(egl_image, raw, width, height, format);
+        dma_buf.destroy_image(raw);
+        let mut image = result.map_err(|_| Ere::InvalidExternalHandle)?;
+        image.requirements.size = size;
+
+        self.share
+            .memory_usage
+            .allocate(memory_type.heap_index, size);
+        let memory = n::Memory {
+            properties: memory_type.properties,
+            heap_index: memory_type.heap_index,
+            buffer: None,
+            size,
+            map_flags: 0,
+            emulate_map_allocation: None,
+            memory_object: None,
+        };
+        Ok((image, memory))
+    }
+
+    unsafe fn egl_image_texture(
+        &self,
+        fns: &EglImageFns,
+        egl_image: *const c_void,
+        width: i::Size,
+        height: i::Size,
+        format: Format,
+    ) -> Result<n::Image, i::CreationError> {
+        let desc = conv::describe_format(format).ok_or(i::CreationError::Format(format))?;
+        let gl = &self.share.context;
+        let name = gl.create_texture().unwrap();
+        gl.bind_texture(glow::TEXTURE_2D, Some(name));
+        fns.image_target_texture_2d(glow::TEXTURE_2D, egl_image);
+        let channel = format.base_format().1;
+        if let ChannelType::Uint | ChannelType::Sint = channel {
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as _,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as _,
+            );
+        }
+        gl.bind_texture(glow::TEXTURE_2D, None);
+
+        let error = gl.get_error();
+        if error != glow::NO_ERROR {
+            log::error!("Error creating a texture from an EGL image: 0x{:X}", error);
+            gl.delete_texture(name);
+            return Err(i::CreationError::Format(format));
         }
+
+        let format_desc = format.base_format().0.desc();
+        let texels_per_block = format_desc.dim.0 as u64 * format_desc.dim.1 as u64;
+        Ok(n::Image {
+            object_type: n::ImageType::Texture {
+                target: glow::TEXTURE_2D,
+                raw: name,
+                format: desc.tex_external,
+                pixel_type: desc.data_type,
+                layer_count: 1,
+                level_count: 1,
+            },
+            extra_planes: [None; 2],
+            kind: i::Kind::D2(width, height, 1, 1),
+            format_desc,
+            channel,
+            srgb_storage: channel == ChannelType::Srgb,
+            immutable_storage: false,
+            requirements: memory::Requirements {
+                size: width as u64 * height as u64 * (format_desc.bits / 8) as u64
+                    / texels_per_block,
+                alignment: 1,
+                type_mask: self.share.image_memory_type_mask(),
+            },
+            base_level: 0,
+            num_levels: 1,
+            num_layers: 1,
+        })
     }
 
     fn create_shader_module_raw(
         gl: &GlContainer,
         shader: &str,
-        stage: naga::ShaderStage,
+        stage: n::ShaderStage,
     ) -> Result<n::Shader, d::ShaderError> {
         let target = match stage {
-            naga::ShaderStage::Vertex => glow::VERTEX_SHADER,
-            naga::ShaderStage::Fragment => glow::FRAGMENT_SHADER,
-            naga::ShaderStage::Compute => glow::COMPUTE_SHADER,
+            n::ShaderStage::Vertex => glow::VERTEX_SHADER,
+            n::ShaderStage::Hull => glow::TESS_CONTROL_SHADER,
+            n::ShaderStage::Domain => glow::TESS_EVALUATION_SHADER,
+            n::ShaderStage::Geometry => glow::GEOMETRY_SHADER,
+            n::ShaderStage::Fragment => glow::FRAGMENT_SHADER,
+            n::ShaderStage::Compute => glow::COMPUTE_SHADER,
         };
 
         let name = unsafe { gl.create_shader(target) }.unwrap();
@@ -128,14 +392,51 @@ impl Device {
 
     fn create_shader_program(
         &self,
-        shaders: &[(naga::ShaderStage, Option<&pso::EntryPoint<B>>)],
+        shaders: &[(n::ShaderStage, Option<&pso::EntryPoint<B>>)],
         layout: &n::PipelineLayout,
-    ) -> Result<(glow::Program, n::SamplerBindMap), pso::CreationError> {
+        transform_feedback: Option<&pso::TransformFeedbackDesc>,
+        view_count: u32,
+    ) -> Result<
+        (
+            glow::Program,
+            n::SamplerBindMap,
+            Vec<n::UniformDesc>,
+            Vec<n::BlockUniformDesc>,
+        ),
+        pso::CreationError,
+    > {
+        // Texture units and samplers are shared by all the stages of the program.
+        let limits = &self.share.public_caps.limits.descriptor_limits;
+        let texture_limit =
+            (limits.max_descriptor_set_sampled_images as usize).min(MAX_TEXTURE_SLOTS);
+        if layout.num_textures > texture_limit {
+            return Err(pso::CreationError::TooManyDescriptors {
+                ty: pso::DescriptorType::Image {
+                    ty: pso::ImageDescriptorType::Sampled {
+                        with_sampler: false,
+                    },
+                },
+                count: layout.num_textures,
+                limit: texture_limit,
+            });
+        }
+        let sampler_limit = (limits.max_descriptor_set_samplers as usize).min(MAX_SAMPLERS);
+        if layout.num_samplers > sampler_limit {
+            return Err(pso::CreationError::TooManyDescriptors {
+                ty: pso::DescriptorType::Sampler,
+                count: layout.num_samplers,
+                limit: sampler_limit,
+            });
+        }
+
         let gl = &self.share.context;
         let program = unsafe { gl.create_program().unwrap() };
 
         let mut name_binding_map = FastHashMap::<String, (n::BindingRegister, u8)>::default();
         let mut sampler_map = [None; MAX_TEXTURE_SLOTS];
+        let mut output_names = FastHashMap::<pso::Location, String>::default();
+        let mut block_members = FastHashMap::<String, (u8, u32)>::default();
+        let mut push_constant_members = FastHashMap::<String, u32>::default();
 
         let mut has_vertex_stage = false;
         let mut has_fragment_stage = false;
@@ -143,23 +444,31 @@ impl Device {
             layout,
             sampler_map: &mut sampler_map,
             name_binding_map: &mut name_binding_map,
+            output_names: &mut output_names,
+            view_count,
+            block_members: &mut block_members,
+            push_constant_members: &mut push_constant_members,
+            violation_slot: self.share.gpu_validation.as_ref().map(|v| v.binding),
         };
 
-        let mut shaders_to_delete = arrayvec::ArrayVec::<[_; 3]>::new();
+        let mut shaders_to_delete = arrayvec::ArrayVec::<[_; 6]>::new();
 
         for &(stage, point_maybe) in shaders {
             if let Some(point) = point_maybe {
                 match stage {
-                    naga::ShaderStage::Vertex => has_vertex_stage = true,
-                    naga::ShaderStage::Fragment => has_fragment_stage = true,
-                    naga::ShaderStage::Compute => (),
+                    n::ShaderStage::Vertex => has_vertex_stage = true,
+                    n::ShaderStage::Fragment => has_fragment_stage = true,
+                    n::ShaderStage::Hull
+                    | n::ShaderStage::Domain
+                    | n::ShaderStage::Geometry
+                    | n::ShaderStage::Compute => (),
                 }
 
                 let shader = self
                     .compile_shader(point, stage, context.reborrow())
                     .map_err(|err| {
                         let error = format!("{} shader compilation failed: {:?}", err, stage);
-                        pso::CreationError::ShaderCreationError(stage.into(), error)
+                        pso::CreationError::ShaderCreationError(stage.to_flag(), error)
                     })?;
                 unsafe {
                     gl.attach_shader(program, shader);
@@ -172,7 +481,12 @@ impl Device {
         if has_vertex_stage && !has_fragment_stage {
             let sl = &self.share.info.shading_language;
             let version = (sl.major * 100 + sl.minor * 10) as u16;
-            let shader_type = if sl.is_embedded { "es" } else { "" };
+            // GLSL ES 1.00 has no profile in its version directive.
+            let shader_type = if sl.is_embedded && version >= 300 {
+                "es"
+            } else {
+                ""
+            };
             let shader_src = format!(
                 "#version {version} {shader_type} \n void main(void) {{}}",
                 version = version,
@@ -185,7 +499,7 @@ impl Device {
             let shader = Self::create_shader_module_raw(
                 &self.share.context,
                 &shader_src,
-                naga::ShaderStage::Fragment,
+                n::ShaderStage::Fragment,
             )
             .unwrap();
             unsafe {
@@ -194,6 +508,43 @@ impl Device {
             }
         }
 
+        if let Some(tf) = transform_feedback {
+            let mut names = Vec::with_capacity(tf.varyings.len());
+            for varying in tf.varyings.iter() {
+                match output_names.get(&varying.location) {
+                    Some(name) => names.push(name.as_str()),
+                    None => {
+                        let error = format!(
+                            "Captured output at location {} is not written by the vertex shader",
+                            varying.location
+                        );
+                        return Err(pso::CreationError::ShaderCreationError(
+                            pso::ShaderStageFlags::VERTEX,
+                            error,
+                        ));
+                    }
+                }
+            }
+            // Without `gl_NextBuffer`, either all the outputs are interleaved
+            // in the first buffer, or each one is written to its own buffer.
+            let buffer_mode = if tf.varyings.iter().all(|v| v.buffer == 0) {
+                glow::INTERLEAVED_ATTRIBS
+            } else if tf
+                .varyings
+                .iter()
+                .enumerate()
+                .all(|(i, v)| v.buffer as usize == i)
+            {
+                glow::SEPARATE_ATTRIBS
+            } else {
+                log::error!("Several outputs can only be captured into the first buffer");
+                return Err(pso::CreationError::UnsupportedPipeline);
+            };
+            unsafe {
+                gl.transform_feedback_varyings(program, &names, buffer_mode);
+            }
+        }
+
         unsafe {
             gl.link_program(program);
         }
@@ -233,9 +584,17 @@ impl Device {
             for (name, &(register, slot)) in name_binding_map.iter() {
                 log::trace!("Get binding {:?} from program {:?}", name, program);
                 match register {
-                    n::BindingRegister::Textures => unsafe {
+                    n::BindingRegister::Textures | n::BindingRegister::Images => unsafe {
                         let loc = gl.get_uniform_location(program, name).unwrap();
                         gl.uniform_1_i32(Some(&loc), slot as _);
+                        // The elements of arrays are bound to consecutive units.
+                        for index in 1.. {
+                            let element = format!("{}[{}]", name, index);
+                            match gl.get_uniform_location(program, &element) {
+                                Some(loc) => gl.uniform_1_i32(Some(&loc), slot as i32 + index),
+                                None => break,
+                            }
+                        }
                     },
                     n::BindingRegister::UniformBuffers => unsafe {
                         let index = gl.get_uniform_block_index(program, name).unwrap();
@@ -249,7 +608,121 @@ impl Device {
             }
         }
 
-        Ok((program, sampler_map))
+        let uniforms = self.reflect_push_constants(program, &push_constant_members);
+        let block_uniforms = self.reflect_block_uniforms(program, &block_members);
+
+        Ok((program, sampler_map, uniforms, block_uniforms))
+    }
+
+    /// Collect the plain uniforms of a linked program, which back the push constants.
+    ///
+    /// The uniforms are placed in the push constant block at the offsets of the
+    /// block members of the same name, as declared by the shaders.
+    fn reflect_push_constants(
+        &self,
+        program: glow::Program,
+        members: &FastHashMap<String, u32>,
+    ) -> Vec<n::UniformDesc> {
+        let gl = &self.share.context;
+        let count = unsafe { gl.get_active_uniforms(program) };
+
+        let mut uniforms = Vec::new();
+        for index in 0..count {
+            let glow::ActiveUniform { size, utype, name } =
+                unsafe { gl.get_active_uniform(program, index) }.unwrap();
+            if name == BASE_INSTANCE_UNIFORM || name.starts_with(UNIFORM_BUFFER_PREFIX) {
+                continue;
+            }
+
+            // Samplers and uniform block members don't have a location,
+            // so the only remaining uniforms should be push constants.
+            let location = match unsafe { gl.get_uniform_location(program, &name) } {
+                Some(location) => location,
+                None => continue,
+            };
+            // The members are reported with the name of the block as prefix,
+            // and the arrays with the name of their first element.
+            let member = name.rsplit('.').next().unwrap().trim_end_matches("[0]");
+            let offset = match members.get(member) {
+                Some(&offset) => offset,
+                None => {
+                    log::warn!("Push constant {} isn't a member of the block", name);
+                    continue;
+                }
+            };
+            let (element_size, stride) = match conv::push_constant_type_layout(utype) {
+                Some(layout) => layout,
+                None => {
+                    log::warn!("Unsupported push constant type {:#x} of {}", utype, name);
+                    continue;
+                }
+            };
+            uniforms.push(n::UniformDesc {
+                location: Starc::new(location),
+                offset,
+                size: stride * (size as u32 - 1) + element_size,
+                utype,
+            });
+        }
+
+        uniforms
+    }
+
+    /// Collect the members of the uniform blocks emulated with plain uniforms,
+    /// see `PrivateCaps::emulate_uniform_buffers`.
+    fn reflect_block_uniforms(
+        &self,
+        program: glow::Program,
+        block_members: &FastHashMap<String, (u8, u32)>,
+    ) -> Vec<n::BlockUniformDesc> {
+        if block_members.is_empty() {
+            return Vec::new();
+        }
+        let gl = &self.share.context;
+        let count = unsafe { gl.get_active_uniforms(program) };
+
+        let mut uniforms = Vec::new();
+        for index in 0..count {
+            let glow::ActiveUniform { size, utype, name } =
+                unsafe { gl.get_active_uniform(program, index) }.unwrap();
+            if !name.starts_with(UNIFORM_BUFFER_PREFIX) {
+                continue;
+            }
+            // Arrays are reported with the name of their first element.
+            let &(slot, offset) = match block_members.get(name.trim_end_matches("[0]")) {
+                Some(member) if size == 1 => member,
+                _ => {
+                    log::warn!("Unsupported member {} of an emulated uniform block", name);
+                    continue;
+                }
+            };
+            let location = match unsafe { gl.get_uniform_location(program, &name) } {
+                Some(location) => location,
+                None => continue,
+            };
+            let size = match conv::uniform_type_size(utype) {
+                Some(size) => size,
+                None => {
+                    log::warn!(
+                        "Unsupported uniform block member type {:#x} of {}",
+                        utype,
+                        name
+                    );
+                    continue;
+                }
+            };
+            uniforms.push(n::BlockUniformDesc {
+                slot,
+                uniform: n::UniformDesc {
+                    location: Starc::new(location),
+                    offset,
+                    size,
+                    utype,
+                },
+            });
+        }
+
+        uniforms
     }
 
     fn _bind_target_compat(gl: &GlContainer, point: u32, attachment: u32, view: &n::ImageView) {
@@ -262,6 +735,7 @@ impl Device {
                 raw,
                 ref sub,
                 is_3d: false,
+                ..
             } => unsafe {
                 gl.bind_texture(target, Some(raw));
                 gl.framebuffer_texture_2d(
@@ -277,6 +751,7 @@ impl Device {
                 raw,
                 ref sub,
                 is_3d: true,
+                ..
             } => unsafe {
                 gl.bind_texture(target, Some(raw));
                 gl.framebuffer_texture_3d(
@@ -297,15 +772,23 @@ impl Device {
                 gl.framebuffer_renderbuffer(point, attachment, glow::RENDERBUFFER, Some(rb));
             },
             n::ImageView::Texture {
-                target: _,
+                target,
                 raw,
                 ref sub,
                 is_3d: false,
+                ..
             } => unsafe {
+                let target = match target {
+                    glow::TEXTURE_2D_MULTISAMPLE => target,
+                    glow::TEXTURE_CUBE_MAP => {
+                        glow::TEXTURE_CUBE_MAP_POSITIVE_X + sub.layer_start as u32
+                    }
+                    _ => glow::TEXTURE_2D,
+                };
                 gl.framebuffer_texture_2d(
                     point,
                     attachment,
-                    glow::TEXTURE_2D,
+                    target,
                     Some(raw),
                     sub.level_start as _,
                 );
@@ -315,6 +798,7 @@ impl Device {
                 raw,
                 ref sub,
                 is_3d: true,
+                ..
             } => unsafe {
                 gl.framebuffer_texture_layer(
                     point,
@@ -327,6 +811,38 @@ impl Device {
         }
     }
 
+    /// Attach the layers of an array texture view selected by a contiguous
+    /// multiview mask, the first one being rendered by view 0.
+    pub(crate) fn bind_target_multiview(
+        gl: &GlContainer,
+        point: u32,
+        attachment: u32,
+        view: &n::ImageView,
+        view_mask: u32,
+    ) {
+        match *view {
+            n::ImageView::Texture {
+                raw,
+                ref sub,
+                is_3d: true,
+                ..
+            } => unsafe {
+                gl.framebuffer_texture_multiview_ovr(
+                    point,
+                    attachment,
+                    Some(raw),
+                    sub.level_start as _,
+                    (sub.layer_start as u32 + view_mask.trailing_zeros()) as _,
+                    view_mask.count_ones() as _,
+                );
+            },
+            _ => panic!(
+                "Multiview attachment {:?} isn't an array texture view",
+                view
+            ),
+        }
+    }
+
     #[cfg(feature = "cross")]
     fn parse_spirv_cross(&self, raw_data: &[u32]) -> Result<CrossAst, d::ShaderError> {
         use spirv_cross::{spirv, ErrorCode as Ec};
@@ -340,16 +856,41 @@ impl Device {
         })
     }
 
+    /// Collect the offsets of the members of the push constant block.
     #[cfg(feature = "cross")]
-    fn set_push_const_layout(&self, _ast: &mut CrossAst) -> Result<(), d::ShaderError> {
-        Ok(())
+    fn reflect_push_constant_members(
+        &self,
+        ast: &CrossAst,
+        members: &mut FastHashMap<String, u32>,
+    ) {
+        use spirv_cross::spirv::{Decoration, Type};
+
+        for res in ast.get_shader_resources().unwrap().push_constant_buffers {
+            let member_count = match ast.get_type(res.base_type_id) {
+                Ok(Type::Struct { member_types, .. }) => member_types.len() as u32,
+                _ => 0,
+            };
+            for index in 0..member_count {
+                let member = ast.get_member_name(res.base_type_id, index).unwrap();
+                // SPIRV-Cross names the anonymous members after their index.
+                let member = if member.is_empty() {
+                    format!("_m{}", index)
+                } else {
+                    member
+                };
+                let offset = ast
+                    .get_member_decoration(res.base_type_id, index, Decoration::Offset)
+                    .unwrap();
+                members.insert(member, offset);
+            }
+        }
     }
 
     #[cfg(feature = "cross")]
     fn translate_spirv_cross(
         &self,
         ast: &mut CrossAst,
-        stage: naga::ShaderStage,
+        stage: n::ShaderStage,
         entry_point: &str,
     ) -> Result<String, d::ShaderError> {
         use spirv_cross::{glsl, ErrorCode as Ec};
@@ -387,11 +928,12 @@ impl Device {
             }
         };
         compile_options.vertex.invert_y = !self.features.contains(hal::Features::NDC_Y_UP);
+        compile_options.vertex.support_nonzero_base_instance = self.base_instance_patching;
         compile_options.force_zero_initialized_variables = true;
-        compile_options.entry_point = Some((
-            entry_point.to_string(),
-            conv::map_naga_stage_to_cross(stage),
-        ));
+        compile_options.emit_uniform_buffer_as_plain_uniforms =
+            self.share.private_caps.emulate_uniform_buffers;
+        compile_options.entry_point =
+            Some((entry_point.to_string(), conv::map_stage_to_cross(stage)));
         log::debug!("SPIR-V options {:?}", compile_options);
 
         ast.set_compiler_options(&compile_options).map_err(|err| {
@@ -425,11 +967,20 @@ impl Device {
         );
         self.remap_binding(
             ast,
-            &res.uniform_buffers,
-            n::BindingRegister::UniformBuffers,
+            &res.storage_images,
+            n::BindingRegister::Images,
             layout,
             nb_map,
         );
+        if !self.share.private_caps.emulate_uniform_buffers {
+            self.remap_binding(
+                ast,
+                &res.uniform_buffers,
+                n::BindingRegister::UniformBuffers,
+                layout,
+                nb_map,
+            );
+        }
         self.remap_binding(
             ast,
             &res.storage_buffers,
@@ -439,6 +990,50 @@ impl Device {
         );
     }
 
+    /// Turn the uniform blocks into plain uniforms, named after their slot so that
+    /// the blocks match across the stages, and collect their members.
+    #[cfg(feature = "cross")]
+    fn flatten_uniform_buffers(
+        &self,
+        ast: &mut CrossAst,
+        layout: &n::PipelineLayout,
+        block_members: &mut FastHashMap<String, (u8, u32)>,
+    ) {
+        use spirv_cross::spirv::{Decoration, Type};
+
+        for res in ast.get_shader_resources().unwrap().uniform_buffers {
+            let set = ast
+                .get_decoration(res.id, Decoration::DescriptorSet)
+                .unwrap();
+            let binding = ast.get_decoration(res.id, Decoration::Binding).unwrap();
+            let slot = layout.sets[set as usize].bindings[binding as usize];
+            let name = format!("{}{}", UNIFORM_BUFFER_PREFIX, slot);
+            ast.set_name(res.id, &name).unwrap();
+
+            let member_count = match ast.get_type(res.base_type_id) {
+                Ok(Type::Struct { member_types, .. }) => member_types.len() as u32,
+                _ => 0,
+            };
+            for index in 0..member_count {
+                let member = ast.get_member_name(res.base_type_id, index).unwrap();
+                // SPIRV-Cross names the anonymous members after their index.
+                let member = if member.is_empty() {
+                    format!("_m{}", index)
+                } else {
+                    member
+                };
+                let offset = ast
+                    .get_member_decoration(res.base_type_id, index, Decoration::Offset)
+                    .unwrap();
+                block_members.insert(format!("{}.{}", name, member), (slot, offset));
+            }
+
+            ast.unset_decoration(res.id, Decoration::Binding).unwrap();
+            ast.unset_decoration(res.id, Decoration::DescriptorSet)
+                .unwrap();
+        }
+    }
+
     #[cfg(feature = "cross")]
     fn remap_binding(
         &self,
@@ -455,9 +1050,21 @@ impl Device {
                 .get_decoration(res.id, Decoration::DescriptorSet)
                 .unwrap();
             let binding = ast.get_decoration(res.id, Decoration::Binding).unwrap();
-            let set_info = &layout.sets[set as usize];
-            let slot = set_info.bindings[binding as usize];
-            assert!((slot as usize) < MAX_TEXTURE_SLOTS);
+            let slot = match self.share.gpu_validation {
+                Some(ref validation) if set == instrument::DESCRIPTOR_SET => validation.binding,
+                _ => {
+                    let set_info = &layout.sets[set as usize];
+                    let slot = set_info.bindings[binding as usize];
+                    let length = Self::array_length(ast, res.type_id);
+                    assert!(
+                        slot as usize + length as usize <= MAX_TEXTURE_SLOTS,
+                        "Resource {} doesn't fit in the {} available units",
+                        res.name,
+                        MAX_TEXTURE_SLOTS
+                    );
+                    slot
+                }
+            };
 
             if self
                 .share
@@ -484,21 +1091,33 @@ impl Device {
         use spirv_cross::spirv::Decoration;
 
         let mut id_map =
-            FastHashMap::<u32, (pso::DescriptorSetIndex, pso::DescriptorBinding)>::default();
+            FastHashMap::<u32, (pso::DescriptorSetIndex, pso::DescriptorBinding, u32)>::default();
         let res = ast.get_shader_resources().unwrap();
         self.populate_id_map(ast, &mut id_map, &res.separate_images);
         self.populate_id_map(ast, &mut id_map, &res.separate_samplers);
 
         for cis in ast.get_combined_image_samplers().unwrap() {
-            let texture_slot = {
-                let &(set, binding) = id_map.get(&cis.image_id).unwrap();
-                context.layout.sets[set as usize].bindings[binding as usize]
+            let (texture_slot, texture_length) = {
+                let &(set, binding, length) = id_map.get(&cis.image_id).unwrap();
+                (
+                    context.layout.sets[set as usize].bindings[binding as usize],
+                    length,
+                )
             };
-            let sampler_slot = {
-                let &(set, binding) = id_map.get(&cis.sampler_id).unwrap();
-                context.layout.sets[set as usize].bindings[binding as usize]
+            let (sampler_slot, sampler_length) = {
+                let &(set, binding, length) = id_map.get(&cis.sampler_id).unwrap();
+                (
+                    context.layout.sets[set as usize].bindings[binding as usize],
+                    length,
+                )
             };
-            context.sampler_map[texture_slot as usize] = Some(sampler_slot);
+            // Each texture of an array is combined with the sampler of the same
+            // index, or with the only sampler.
+            for index in 0..texture_length as u8 {
+                let sampler_index = if sampler_length > 1 { index } else { 0 };
+                context.sampler_map[(texture_slot + index) as usize] =
+                    Some(sampler_slot + sampler_index);
+            }
 
             if self
                 .share
@@ -529,23 +1148,46 @@ impl Device {
         module: &naga::Module,
         ep_info: &naga::valid::FunctionInfo,
         reflection_info: naga::back::glsl::ReflectionInfo,
+        stage: n::ShaderStage,
         context: CompilationContext,
     ) {
         for (handle, var) in module.global_variables.iter() {
             if ep_info[handle].is_empty() {
                 continue;
             }
+            let violation_slot = match var.binding {
+                Some(ref br) if br.group == instrument::DESCRIPTOR_SET => context.violation_slot,
+                Some(ref br) => {
+                    context.layout.check_visibility(br.group, br.binding, stage);
+                    None
+                }
+                None => None,
+            };
             let register = match var.class {
                 naga::StorageClass::Uniform => n::BindingRegister::UniformBuffers,
                 naga::StorageClass::Storage => n::BindingRegister::StorageBuffers,
+                naga::StorageClass::PushConstant => {
+                    if let naga::TypeInner::Struct { ref members, .. } = module.types[var.ty].inner
+                    {
+                        for member in members.iter() {
+                            if let Some(ref name) = member.name {
+                                context
+                                    .push_constant_members
+                                    .insert(name.clone(), member.offset);
+                            }
+                        }
+                    }
+                    continue;
+                }
                 _ => continue,
             };
             //TODO: make Naga reflect all the names, not just textures
-            let slot = match var.binding {
-                Some(ref br) => {
+            let slot = match (violation_slot, &var.binding) {
+                (Some(slot), _) => slot,
+                (None, &Some(ref br)) => {
                     context.layout.sets[br.group as usize].bindings[br.binding as usize]
                 }
-                ref other => panic!("Unexpected resource binding {:?}", other),
+                (None, other) => panic!("Unexpected resource binding {:?}", other),
             };
 
             let name = reflection_info.uniforms[&handle].clone();
@@ -554,15 +1196,23 @@ impl Device {
         }
 
         for (name, mapping) in reflection_info.texture_mapping {
-            let texture_linear_index = match module.global_variables[mapping.texture].binding {
+            let texture = &module.global_variables[mapping.texture];
+            let texture_linear_index = match texture.binding {
                 Some(ref br) => {
                     context.layout.sets[br.group as usize].bindings[br.binding as usize]
                 }
                 ref other => panic!("Unexpected texture binding {:?}", other),
             };
+            let register = match module.types[texture.ty].inner {
+                naga::TypeInner::Image {
+                    class: naga::ImageClass::Storage { .. },
+                    ..
+                } => n::BindingRegister::Images,
+                _ => n::BindingRegister::Textures,
+            };
             context
                 .name_binding_map
-                .insert(name, (n::BindingRegister::Textures, texture_linear_index));
+                .insert(name, (register, texture_linear_index));
             if let Some(sampler_handle) = mapping.sampler {
                 let sampler_linear_index = match module.global_variables[sampler_handle].binding {
                     Some(ref br) => {
@@ -575,11 +1225,37 @@ impl Device {
         }
     }
 
+    /// Collect the names Naga gives to the outputs of a vertex entry point.
+    fn reflect_outputs(
+        module: &naga::Module,
+        entry_point: &naga::EntryPoint,
+        output_names: &mut FastHashMap<pso::Location, String>,
+    ) {
+        output_names.clear();
+        let result = match entry_point.function.result {
+            Some(ref result) => result,
+            None => return,
+        };
+        let mut add_output = |binding: &Option<naga::Binding>| {
+            if let Some(naga::Binding::Location { location, .. }) = *binding {
+                output_names.insert(location, format!("_vs2fs_location{}", location));
+            }
+        };
+        match module.types[result.ty].inner {
+            naga::TypeInner::Struct { ref members, .. } => {
+                for member in members.iter() {
+                    add_output(&member.binding);
+                }
+            }
+            _ => add_output(&result.binding),
+        }
+    }
+
     #[cfg(feature = "cross")]
     fn populate_id_map(
         &self,
         ast: &CrossAst,
-        id_map: &mut FastHashMap<u32, (pso::DescriptorSetIndex, pso::DescriptorBinding)>,
+        id_map: &mut FastHashMap<u32, (pso::DescriptorSetIndex, pso::DescriptorBinding, u32)>,
         all_res: &[spirv_cross::spirv::Resource],
     ) {
         use spirv_cross::spirv::Decoration;
@@ -588,13 +1264,27 @@ impl Device {
                 .get_decoration(res.id, Decoration::DescriptorSet)
                 .unwrap();
             let binding = ast.get_decoration(res.id, Decoration::Binding).unwrap();
-            assert!(id_map.insert(res.id, (set as _, binding)).is_none())
+            let length = Self::array_length(ast, res.type_id);
+            assert!(id_map.insert(res.id, (set as _, binding, length)).is_none())
+        }
+    }
+
+    /// Number of elements of an array of images or samplers, or 1 for a single one.
+    #[cfg(feature = "cross")]
+    fn array_length(ast: &CrossAst, type_id: u32) -> u32 {
+        use spirv_cross::spirv::Type;
+        match ast.get_type(type_id) {
+            Ok(Type::Image { ref array, .. })
+            | Ok(Type::SampledImage { ref array, .. })
+            | Ok(Type::Sampler { ref array, .. }) => array.iter().product::<u32>().max(1),
+            _ => 1,
         }
     }
 
     fn compile_shader_library_naga(
         gl: &GlContainer,
         shader: &d::NagaShader,
+        stage: n::ShaderStage,
         options: &naga::back::glsl::Options,
         context: CompilationContext,
     ) -> Result<n::Shader, d::ShaderError> {
@@ -614,16 +1304,25 @@ impl Device {
                 options.entry_point
             )))?;
 
+        if stage == n::ShaderStage::Vertex {
+            Self::reflect_outputs(
+                &shader.module,
+                &shader.module.entry_points[entry_point_index],
+                context.output_names,
+            );
+        }
+
         match writer.write() {
             Ok(reflection_info) => {
                 Self::reflect_shader(
                     &shader.module,
                     shader.info.get_entry_point(entry_point_index),
                     reflection_info,
+                    stage,
                     context,
                 );
                 log::debug!("Naga generated shader:\n{}", output);
-                Self::create_shader_module_raw(gl, &output, options.shader_stage)
+                Self::create_shader_module_raw(gl, &output, stage)
             }
             Err(e) => {
                 log::warn!("Naga GLSL write: {}", e);
@@ -635,50 +1334,132 @@ impl Device {
     fn compile_shader(
         &self,
         ep: &pso::EntryPoint<B>,
-        stage: naga::ShaderStage,
+        stage: n::ShaderStage,
         mut context: CompilationContext,
     ) -> Result<n::Shader, d::ShaderError> {
-        let naga_options = naga::back::glsl::Options {
-            version: {
-                use naga::back::glsl::Version;
-                let sl = &self.share.info.shading_language;
-                let value = (sl.major * 100 + sl.minor * 10) as u16;
-                if sl.is_embedded {
-                    Version::Embedded(value)
-                } else {
-                    Version::Desktop(value)
-                }
-            },
-            shader_stage: stage,
-            entry_point: ep.entry.to_string(),
+        let naga_stage = match stage.to_naga() {
+            // Naga can't patch the base instance nor declare the view count,
+            // leave it to SPIRV-Cross.
+            Some(_)
+                if cfg!(feature = "cross")
+                    && (self.base_instance_patching || context.view_count != 0)
+                    && stage == n::ShaderStage::Vertex =>
+            {
+                None
+            }
+            // Nor can it flatten the uniform blocks.
+            Some(_) if self.share.private_caps.emulate_uniform_buffers => None,
+            other => other,
         };
-
         #[cfg_attr(not(feature = "cross"), allow(unused_mut))]
-        let mut result = match ep.module.naga {
-            Ok(ref shader) => Self::compile_shader_library_naga(
-                &self.share.context,
-                shader,
-                &naga_options,
-                context.reborrow(),
-            ),
-            Err(ref e) => Err(d::ShaderError::CompilationFailed(e.clone())),
+        let mut result = match (&ep.module.naga, naga_stage) {
+            (&Ok(ref shader), Some(naga_stage)) => {
+                let naga_options = naga::back::glsl::Options {
+                    version: {
+                        use naga::back::glsl::Version;
+                        let sl = &self.share.info.shading_language;
+                        let value = (sl.major * 100 + sl.minor * 10) as u16;
+                        if sl.is_embedded {
+                            Version::Embedded(value)
+                        } else {
+                            Version::Desktop(value)
+                        }
+                    },
+                    shader_stage: naga_stage,
+                    entry_point: ep.entry.to_string(),
+                };
+                Self::compile_shader_library_naga(
+                    &self.share.context,
+                    shader,
+                    stage,
+                    &naga_options,
+                    context.reborrow(),
+                )
+            }
+            (&Ok(_), None) => Err(d::ShaderError::CompilationFailed(format!(
+                "{:?} shaders can't be generated by Naga",
+                stage
+            ))),
+            (&Err(ref e), _) => Err(d::ShaderError::CompilationFailed(e.clone())),
         };
         #[cfg(feature = "cross")]
         if result.is_err() {
             let mut ast = self.parse_spirv_cross(&ep.module.spv).unwrap();
             auxil::spirv_cross_specialize_ast(&mut ast, &ep.specialization).unwrap();
+            if self.share.private_caps.emulate_uniform_buffers {
+                self.flatten_uniform_buffers(&mut ast, context.layout, context.block_members);
+            }
             self.remap_bindings(&mut ast, context.layout, context.name_binding_map);
             self.combine_separate_images_and_samplers(&mut ast, context.reborrow());
-            self.set_push_const_layout(&mut ast).unwrap();
+            self.reflect_push_constant_members(&ast, context.push_constant_members);
+
+            if stage == n::ShaderStage::Vertex {
+                use spirv_cross::spirv::Decoration;
 
-            let glsl = self
+                context.output_names.clear();
+                for res in ast.get_shader_resources().unwrap().stage_outputs {
+                    if let Ok(location) = ast.get_decoration(res.id, Decoration::Location) {
+                        let name = if res.name.is_empty() {
+                            format!("_{}", res.id)
+                        } else {
+                            res.name
+                        };
+                        context.output_names.insert(location, name);
+                    }
+                }
+            }
+
+            let mut glsl = self
                 .translate_spirv_cross(&mut ast, stage, ep.entry)
                 .unwrap();
-            log::debug!("SPIRV-Cross generated shader:\n{}", glsl);
-            result = Self::create_shader_module_raw(&self.share.context, &glsl, stage);
+            result = if stage == n::ShaderStage::Vertex && context.view_count != 0 {
+                declare_view_count(&mut glsl, context.view_count)
+            } else {
+                Ok(())
+            }
+            .and_then(|()| {
+                log::debug!("SPIRV-Cross generated shader:\n{}", glsl);
+                Self::create_shader_module_raw(&self.share.context, &glsl, stage)
+            });
         }
         result
     }
+    /// Label an object with `KHR_debug`, so that it's shown by debugging tools.
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe fn label_object(&self, identifier: u32, name: u32, label: &str) {
+        if self.share.private_caps.debug_markers {
+            self.share
+                .context
+                .object_label(identifier, name, Some(label));
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    unsafe fn label_object<T>(&self, _identifier: u32, _name: T, _label: &str) {}
+}
+
+/// Count the blocks covering a level of an image, rounding partial blocks up.
+fn block_count(desc: &hal::format::FormatDesc, width: u32, height: u32) -> u64 {
+    let (block_width, block_height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+    let columns = (width + block_width - 1) / block_width;
+    let rows = (height + block_height - 1) / block_height;
+    columns as u64 * rows as u64
+}
+
+/// Declare the number of views rendered by a vertex shader with `OVR_multiview2`,
+/// which SPIRV-Cross only does with an option its bindings don't expose.
+#[cfg(feature = "cross")]
+fn declare_view_count(glsl: &mut String, view_count: u32) -> Result<(), d::ShaderError> {
+    let main = glsl.find("void main()").ok_or_else(|| {
+        d::ShaderError::CompilationFailed("SPIRV-Cross entry point is missing".to_string())
+    })?;
+    glsl.insert_str(main, &format!("layout(num_views = {}) in;\n", view_count));
+    // Shaders not reading the view index don't enable the extension yet.
+    if !glsl.contains("GL_OVR_multiview2") {
+        let version_end = glsl.find('\n').map_or(glsl.len(), |i| i + 1);
+        glsl.insert_str(version_end, "#extension GL_OVR_multiview2 : require\n");
+    }
+    Ok(())
 }
 
 pub(crate) unsafe fn set_sampler_info<SetParamFloat, SetParamFloatVec, SetParamInt>(
@@ -694,14 +1475,21 @@ pub(crate) unsafe fn set_sampler_info<SetParamFloat, SetParamFloatVec, SetParamI
     SetParamInt: FnMut(u32, i32),
 {
     let (min, mag) = conv::filter_to_gl(info.mag_filter, info.min_filter, info.mip_filter);
-    if let Some(fac) = info.anisotropy_clamp {
-        if features.contains(hal::Features::SAMPLER_ANISOTROPY) {
-            set_param_float(glow::TEXTURE_MAX_ANISOTROPY, fac as f32);
-        }
+    // Without sampler objects, the parameters are set on the textures, so the ones
+    // left over by a previous sampler have to be reset as well.
+    if features.contains(hal::Features::SAMPLER_ANISOTROPY) {
+        let fac = info.anisotropy_clamp.map_or(1.0, |fac| fac as f32);
+        set_param_float(glow::TEXTURE_MAX_ANISOTROPY, fac);
     }
 
     set_param_int(glow::TEXTURE_MIN_FILTER, min as i32);
     set_param_int(glow::TEXTURE_MAG_FILTER, mag as i32);
+    if features.contains(hal::Features::SAMPLER_REDUCTION) {
+        set_param_int(
+            conv::TEXTURE_REDUCTION_MODE,
+            conv::reduction_mode_to_gl(info.reduction_mode) as i32,
+        );
+    }
 
     let (s, t, r) = info.wrap_mode;
     set_param_int(glow::TEXTURE_WRAP_S, conv::wrap_to_gl(s) as i32);
@@ -758,6 +1546,11 @@ impl d::Device<B> for Device {
         match memory_role {
             MemoryUsage::Buffer(buffer_usage) => {
                 let gl = &self.share.context;
+                // GLsizeiptr is 32-bit on some platforms, refuse sizes that do not fit.
+                let gl_size: i32 = buffer::cast(size).map_err(|err| {
+                    log::error!("Unable to allocate buffer memory: {}", err);
+                    d::OutOfMemory::Device
+                })?;
                 let target = if buffer_usage.contains(buffer::Usage::INDEX)
                     && !self.share.private_caps.index_buffer_role_change
                 {
@@ -798,7 +1591,7 @@ impl d::Device<B> for Device {
                         }
                     }
 
-                    gl.buffer_storage(target, size as i32, None, storage_flags);
+                    gl.buffer_storage(target, gl_size, None, storage_flags);
                 } else {
                     assert!(!is_coherent_memory);
                     let usage = if is_cpu_visible_memory {
@@ -810,7 +1603,7 @@ impl d::Device<B> for Device {
                     } else {
                         glow::STATIC_DRAW
                     };
-                    gl.buffer_data_size(target, size as i32, usage);
+                    gl.buffer_data_size(target, gl_size, usage);
                 }
 
                 gl.bind_buffer(target, None);
@@ -819,23 +1612,42 @@ impl d::Device<B> for Device {
                     panic!("Error allocating memory buffer {:?}", err);
                 }
 
+                // The emulated uniform buffers are read from the host allocation,
+                // which is kept for the lifetime of the memory.
+                let emulate_map_allocation =
+                    if is_cpu_visible_memory && self.share.private_caps.emulate_uniform_buffers {
+                        Some(Box::into_raw(vec![0; size as usize].into_boxed_slice()) as *mut u8)
+                    } else {
+                        None
+                    };
+
+                self.share
+                    .memory_usage
+                    .allocate(memory_type.heap_index, size);
                 Ok(n::Memory {
                     properties: memory_type.properties,
+                    heap_index: memory_type.heap_index,
                     buffer: Some((raw, target)),
                     size,
                     map_flags,
-                    emulate_map_allocation: None,
+                    emulate_map_allocation,
+                    memory_object: None,
                 })
             }
 
             MemoryUsage::Image => {
                 assert!(is_device_local_memory);
+                self.share
+                    .memory_usage
+                    .allocate(memory_type.heap_index, size);
                 Ok(n::Memory {
                     properties: memory::Properties::DEVICE_LOCAL,
+                    heap_index: memory_type.heap_index,
                     buffer: None,
                     size,
                     map_flags: 0,
                     emulate_map_allocation: None,
+                    memory_object: None,
                 })
             }
         }
@@ -864,6 +1676,7 @@ impl d::Device<B> for Device {
             limits,
             memory: Arc::new(Mutex::new(memory)),
             legacy_features: self.share.legacy_features,
+            translation_pool: self.translation_pool.clone(),
         })
     }
 
@@ -886,9 +1699,13 @@ impl d::Device<B> for Device {
     {
         let subpasses = subpasses
             .map(|subpass| {
+                // Subpasses with more attachments than supported by the device fail
+                // when creating their pipelines, but are still stored.
                 assert!(
-                    subpass.colors.len() <= self.share.public_caps.limits.max_color_attachments,
-                    "Color attachment limit exceeded"
+                    subpass.colors.len() <= MAX_COLOR_ATTACHMENTS,
+                    "Subpass has {} color attachments, only {} are supported",
+                    subpass.colors.len(),
+                    MAX_COLOR_ATTACHMENTS
                 );
                 let color_attachments = subpass.colors.iter().map(|&(index, _)| index).collect();
 
@@ -897,6 +1714,7 @@ impl d::Device<B> for Device {
                 n::SubpassDesc {
                     color_attachments,
                     depth_stencil,
+                    view_mask: subpass.view_mask,
                 }
             })
             .collect();
@@ -910,15 +1728,17 @@ impl d::Device<B> for Device {
     unsafe fn create_pipeline_layout<'a, Is, Ic>(
         &self,
         layouts: Is,
-        _: Ic,
+        push_constants: Ic,
     ) -> Result<n::PipelineLayout, d::OutOfMemory>
     where
         Is: Iterator<Item = &'a n::DescriptorSetLayout>,
+        Ic: Iterator<Item = (pso::ShaderStageFlags, Range<u32>)>,
     {
         use std::convert::TryInto;
         let mut sets = Vec::new();
         let mut num_samplers = 0usize;
         let mut num_textures = 0usize;
+        let mut num_images = 0usize;
         let mut num_uniform_buffers = 0usize;
         let mut num_storage_buffers = 0usize;
 
@@ -931,16 +1751,27 @@ impl d::Device<B> for Device {
                 assert!(!binding.immutable_samplers); //TODO
                 let counter = match binding.ty {
                     pso::DescriptorType::Sampler => &mut num_samplers,
+                    pso::DescriptorType::Image {
+                        ty: pso::ImageDescriptorType::Storage { .. },
+                    } => &mut num_images,
                     pso::DescriptorType::InputAttachment | pso::DescriptorType::Image { .. } => {
                         &mut num_textures
                     }
+                    // Texel buffers are bound as buffer textures and images.
                     pso::DescriptorType::Buffer {
                         ty,
-                        format: _, //TODO
+                        format: pso::BufferDescriptorFormat::Texel,
                     } => match ty {
+                        pso::BufferDescriptorType::Uniform => &mut num_textures,
+                        pso::BufferDescriptorType::Storage { .. } => &mut num_images,
+                    },
+                    pso::DescriptorType::Buffer { ty, .. } => match ty {
                         pso::BufferDescriptorType::Uniform => &mut num_uniform_buffers,
                         pso::BufferDescriptorType::Storage { .. } => &mut num_storage_buffers,
                     },
+                    pso::DescriptorType::AccelerationStructure => {
+                        unreachable!("Acceleration structures are never exposed")
+                    }
                 };
 
                 bindings[binding.binding as usize] = (*counter).try_into().unwrap();
@@ -953,7 +1784,12 @@ impl d::Device<B> for Device {
             });
         }
 
-        Ok(n::PipelineLayout { sets })
+        Ok(n::PipelineLayout {
+            sets,
+            push_constants: push_constants.collect(),
+            num_textures,
+            num_samplers,
+        })
     }
 
     unsafe fn create_pipeline_cache(&self, _data: Option<&[u8]>) -> Result<(), d::OutOfMemory> {
@@ -982,7 +1818,28 @@ impl d::Device<B> for Device {
         desc: &pso::GraphicsPipelineDesc<'a, B>,
         _cache: Option<&()>,
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
-        let (vertex_buffers, desc_attributes, input_assembler, vs) = match desc.primitive_assembler
+        let dynamic_states = desc.dynamic_states();
+        let unsupported = dynamic_states - self.share.public_caps.dynamic_pipeline_states;
+        if !unsupported.is_empty() {
+            return Err(pso::CreationError::UnsupportedDynamicStates(unsupported));
+        }
+
+        // Extra attachments would be left out of the draw buffers.
+        let subpass = &desc.subpass.main_pass.subpasses[desc.subpass.index as usize];
+        let color_count = subpass
+            .color_attachments
+            .len()
+            .max(desc.blender.targets.len());
+        let color_limit = self.share.public_caps.limits.max_color_attachments;
+        if color_count > color_limit {
+            return Err(pso::CreationError::TooManyColorAttachments {
+                count: color_count,
+                limit: color_limit,
+            });
+        }
+
+        let (vertex_buffers, desc_attributes, input_assembler, vs, ts, gs) = match desc
+            .primitive_assembler
         {
             pso::PrimitiveAssemblerDesc::Vertex {
                 buffers,
@@ -992,9 +1849,30 @@ impl d::Device<B> for Device {
                 ref tessellation,
                 ref geometry,
             } => {
-                if tessellation.is_some() || geometry.is_some() {
+                if geometry.is_some() && !self.features.contains(hal::Features::GEOMETRY_SHADER) {
                     return Err(pso::CreationError::UnsupportedPipeline);
                 }
+                if desc.transform_feedback.is_some() {
+                    if !self.features.contains(hal::Features::TRANSFORM_FEEDBACK) {
+                        return Err(pso::CreationError::UnsupportedPipeline);
+                    }
+                    if tessellation.is_some() || geometry.is_some() {
+                        log::error!("Only the outputs of vertex shaders can be captured");
+                        return Err(pso::CreationError::UnsupportedPipeline);
+                    }
+                }
+                if tessellation.is_some() {
+                    if !self.features.contains(hal::Features::TESSELLATION_SHADER) {
+                        return Err(pso::CreationError::UnsupportedPipeline);
+                    }
+                    match input_assembler.primitive {
+                        pso::Primitive::PatchList(_) => {}
+                        _ => {
+                            log::error!("Tessellation requires a patch list primitive");
+                            return Err(pso::CreationError::UnsupportedPipeline);
+                        }
+                    }
+                }
 
                 let mut vertex_buffers = Vec::new();
                 for vb in buffers {
@@ -1004,74 +1882,106 @@ impl d::Device<B> for Device {
                     vertex_buffers[vb.binding as usize] = Some(*vb);
                 }
 
-                (vertex_buffers, attributes, input_assembler, vertex)
+                (
+                    vertex_buffers,
+                    attributes,
+                    input_assembler,
+                    vertex,
+                    tessellation.as_ref(),
+                    geometry.as_ref(),
+                )
             }
             pso::PrimitiveAssemblerDesc::Mesh { .. } => {
                 return Err(pso::CreationError::UnsupportedPipeline);
             }
         };
 
+        let attributes = desc_attributes
+            .iter()
+            .map(|a| match conv::describe_format(a.element.format) {
+                Some(fd) => Ok(n::AttributeDesc {
+                    location: a.location,
+                    offset: a.element.offset,
+                    binding: a.binding,
+                    size: fd.num_components as _,
+                    format: fd.data_type,
+                    vertex_attrib_fn: fd.va_fun,
+                }),
+                None => {
+                    log::error!("Unsupported vertex attribute format {:?}", a.element.format);
+                    Err(pso::CreationError::UnsupportedPipeline)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
         let shaders = [
-            (naga::ShaderStage::Vertex, Some(vs)),
-            (naga::ShaderStage::Fragment, desc.fragment.as_ref()),
+            (n::ShaderStage::Vertex, Some(vs)),
+            (n::ShaderStage::Hull, ts.map(|ts| &ts.0)),
+            (n::ShaderStage::Domain, ts.map(|ts| &ts.1)),
+            (n::ShaderStage::Geometry, gs),
+            (n::ShaderStage::Fragment, desc.fragment.as_ref()),
         ];
-        let (program, sampler_map) = self.create_shader_program(&shaders[..], &desc.layout)?;
+        let primitive = conv::input_assember_to_gl_primitive(input_assembler);
+        let transform_feedback = match desc.transform_feedback {
+            Some(ref tf) => match conv::transform_feedback_primitive(primitive) {
+                Some(primitive) => Some(n::TransformFeedback {
+                    primitive,
+                    rasterizer_discard: !tf.rasterize,
+                }),
+                None => {
+                    log::error!("Can't capture {:?} primitives", input_assembler.primitive);
+                    return Err(pso::CreationError::UnsupportedPipeline);
+                }
+            },
+            None => None,
+        };
+
+        let view_mask = desc.subpass.main_pass.subpasses[desc.subpass.index as usize].view_mask;
+        // `OVR_multiview2` attaches a range of layers, not arbitrary ones.
+        let views = view_mask >> view_mask.trailing_zeros().min(31);
+        if views & views.wrapping_add(1) != 0 {
+            log::error!("Multiview mask {:#b} isn't contiguous", view_mask);
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
+        let (program, sampler_map, uniforms, block_uniforms) = self.create_shader_program(
+            &shaders[..],
+            &desc.layout,
+            desc.transform_feedback.as_ref(),
+            view_mask.count_ones(),
+        )?;
 
         let patch_size = match input_assembler.primitive {
             pso::Primitive::PatchList(size) => Some(size as _),
             _ => None,
         };
 
-        let mut uniforms = Vec::new();
-        {
-            let gl = &self.share.context;
-            let count = gl.get_active_uniforms(program);
-
-            let mut offset = 0;
-
-            for uniform in 0..count {
-                let glow::ActiveUniform { size, utype, name } =
-                    gl.get_active_uniform(program, uniform).unwrap();
-
-                if let Some(location) = gl.get_uniform_location(program, &name) {
-                    // Sampler2D won't show up in UniformLocation and the only other uniforms
-                    // should be push constants
-                    uniforms.push(n::UniformDesc {
-                        location: Starc::new(location),
-                        offset,
-                        utype,
-                    });
-
-                    offset += size as u32;
-                }
-            }
+        let gl = &self.share.context;
+        let base_instance_uniform = unsafe {
+            gl.get_uniform_location(program, BASE_INSTANCE_UNIFORM)
         }
+        .map(|location| n::UniformDesc {
+            location: Starc::new(location),
+            offset: 0,
+            size: 4,
+            utype: glow::INT,
+        });
 
         Ok(n::GraphicsPipeline {
             program,
-            primitive: conv::input_assember_to_gl_primitive(input_assembler),
+            primitive,
             patch_size,
             blend_targets: desc.blender.targets.clone(),
             vertex_buffers,
-            attributes: desc_attributes
-                .iter()
-                .map(|&a| {
-                    let fd = conv::describe_format(a.element.format).unwrap();
-                    n::AttributeDesc {
-                        location: a.location,
-                        offset: a.element.offset,
-                        binding: a.binding,
-                        size: fd.num_components as _,
-                        format: fd.data_type,
-                        vertex_attrib_fn: fd.va_fun,
-                    }
-                })
-                .collect(),
+            attributes,
             uniforms,
+            block_uniforms,
             rasterizer: desc.rasterizer,
             depth: desc.depth_stencil.depth,
             baked_states: desc.baked_states.clone(),
+            dynamic_states,
             sampler_map,
+            transform_feedback,
+            base_instance_uniform,
         })
     }
 
@@ -1083,10 +1993,13 @@ impl d::Device<B> for Device {
         if self.share.public_caps.limits.max_compute_work_group_count[0] == 0 {
             return Err(pso::CreationError::UnsupportedPipeline);
         }
-        let shader = (naga::ShaderStage::Compute, Some(&desc.shader));
-        let (program, sampler_map) = self.create_shader_program(&[shader], &desc.layout)?;
+        let shader = (n::ShaderStage::Compute, Some(&desc.shader));
+        let (program, sampler_map, uniforms, block_uniforms) =
+            self.create_shader_program(&[shader], &desc.layout, None, 0)?;
         Ok(n::ComputePipeline {
             program,
+            uniforms,
+            block_uniforms,
             sampler_map,
         })
     }
@@ -1094,13 +2007,30 @@ impl d::Device<B> for Device {
     unsafe fn create_framebuffer<I>(
         &self,
         _render_pass: &n::RenderPass,
-        _attachments: I,
+        attachments: I,
         _extent: i::Extent,
-    ) -> Result<n::Framebuffer, d::OutOfMemory> {
+    ) -> Result<n::Framebuffer, d::OutOfMemory>
+    where
+        I: Iterator<Item = i::FramebufferAttachment>,
+    {
         if !self.share.private_caps.framebuffer {
             return Err(d::OutOfMemory::Host);
         }
 
+        // All the color attachments are bound at once in `begin_render_pass`.
+        let colors = attachments
+            .filter(|attachment| attachment.format.is_color())
+            .count();
+        let color_limit = self.share.public_caps.limits.max_color_attachments;
+        if colors > color_limit {
+            log::error!(
+                "Framebuffer has {} color attachments, only {} are supported",
+                colors,
+                color_limit
+            );
+            return Err(d::OutOfMemory::Host);
+        }
+
         let gl = &self.share.context;
         let raw = gl.create_framebuffer().unwrap();
 
@@ -1180,6 +2110,17 @@ impl d::Device<B> for Device {
         &self,
         raw_data: &[u32],
     ) -> Result<n::ShaderModule, d::ShaderError> {
+        let instrumented = match self.share.gpu_validation {
+            Some(ref validation) => match validation.instrument(raw_data) {
+                Ok(words) => Some(words),
+                Err(e) => {
+                    log::warn!("Unable to instrument the shader: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let raw_data = instrumented.as_ref().map_or(raw_data, |words| &words[..]);
         Ok(n::ShaderModule {
             #[cfg(feature = "cross")]
             spv: raw_data.to_vec(),
@@ -1230,8 +2171,30 @@ impl d::Device<B> for Device {
     unsafe fn create_sampler(
         &self,
         info: &i::SamplerDesc,
-    ) -> Result<n::FatSampler, d::AllocationError> {
-        assert!(info.normalized);
+    ) -> Result<n::FatSampler, i::SamplerCreationError> {
+        let mut unsupported = info.unsupported_fields(self.features);
+        // Only rectangle textures can be sampled with unnormalized coordinates.
+        if !info.normalized {
+            unsupported |= i::SamplerFields::NORMALIZED;
+        }
+        let max_anisotropy = self.share.public_caps.limits.max_sampler_anisotropy;
+        if info
+            .anisotropy_clamp
+            .map_or(false, |fac| fac as f32 > max_anisotropy)
+        {
+            unsupported |= i::SamplerFields::ANISOTROPY;
+        }
+        // ES 2 only wraps non-power-of-two textures with `CLAMP_TO_EDGE`, and the
+        // sampler may end up used with any texture.
+        let (u, v, w) = info.wrap_mode;
+        if !self.share.private_caps.npot_texture_wrap
+            && [u, v, w].iter().any(|&wrap| wrap != i::WrapMode::Clamp)
+        {
+            unsupported |= i::SamplerFields::WRAP_MODE;
+        }
+        if !unsupported.is_empty() {
+            return Err(i::SamplerCreationError::Unsupported(unsupported));
+        }
 
         if !self
             .share
@@ -1253,7 +2216,7 @@ impl d::Device<B> for Device {
         );
 
         if let Err(_) = self.share.check() {
-            Err(d::AllocationError::OutOfMemory(d::OutOfMemory::Host))
+            Err(d::OutOfMemory::Host.into())
         } else {
             Ok(n::FatSampler::Sampler(name))
         }
@@ -1269,6 +2232,7 @@ impl d::Device<B> for Device {
             .share
             .legacy_features
             .contains(LegacyFeatures::CONSTANT_BUFFER)
+            && !self.share.private_caps.emulate_uniform_buffers
             && usage.contains(buffer::Usage::UNIFORM)
         {
             return Err(buffer::CreationError::UnsupportedUsage(usage));
@@ -1309,6 +2273,7 @@ impl d::Device<B> for Device {
                     buffer: raw,
                     range: offset..offset + size,
                     target: target,
+                    shadow: memory.emulate_map_allocation.map(n::HostShadow),
                 };
             }
             None => {
@@ -1341,10 +2306,12 @@ impl d::Device<B> for Device {
                 ptr
             };
 
-            ptr.offset(offset as isize)
+            ptr.offset(buffer::cast(offset).map_err(|_| d::MapError::OutOfBounds)?)
         } else {
+            let gl_offset = buffer::cast(offset).map_err(|_| d::MapError::OutOfBounds)?;
+            let gl_size = buffer::cast(size).map_err(|_| d::MapError::OutOfBounds)?;
             gl.bind_buffer(target, Some(buffer));
-            let raw = gl.map_buffer_range(target, offset as i32, size as i32, memory.map_flags);
+            let raw = gl.map_buffer_range(target, gl_offset, gl_size, memory.map_flags);
             gl.bind_buffer(target, None);
             raw
         };
@@ -1363,8 +2330,12 @@ impl d::Device<B> for Device {
         gl.bind_buffer(target, Some(buffer));
 
         if self.share.private_caps.emulate_map {
-            let ptr = memory.emulate_map_allocation.take().unwrap();
-            let _ = Box::from_raw(slice::from_raw_parts_mut(ptr, memory.size as usize));
+            // The host allocation backing the emulated uniform buffers is freed
+            // with the memory instead.
+            if !self.share.private_caps.emulate_uniform_buffers {
+                let ptr = memory.emulate_map_allocation.take().unwrap();
+                let _ = Box::from_raw(slice::from_raw_parts_mut(ptr, memory.size as usize));
+            }
         } else {
             gl.unmap_buffer(target);
         }
@@ -1386,15 +2357,22 @@ impl d::Device<B> for Device {
             let (buffer, target) = mem.buffer.expect("cannot flush image memory");
             gl.bind_buffer(target, Some(buffer));
 
-            let offset = segment.offset;
-            let size = segment.size.unwrap_or(mem.size - segment.offset);
+            let offset: i32 = buffer::cast(segment.offset).map_err(|err| {
+                log::error!("Invalid mapped memory range: {}", err);
+                d::OutOfMemory::Host
+            })?;
+            let size: i32 = buffer::cast(segment.size.unwrap_or(mem.size - segment.offset))
+                .map_err(|err| {
+                    log::error!("Invalid mapped memory range: {}", err);
+                    d::OutOfMemory::Host
+                })?;
 
             if self.share.private_caps.emulate_map {
                 let ptr = mem.emulate_map_allocation.unwrap();
                 let slice = slice::from_raw_parts_mut(ptr.offset(offset as isize), size as usize);
-                gl.buffer_sub_data_u8_slice(target, offset as i32, slice);
+                gl.buffer_sub_data_u8_slice(target, offset, slice);
             } else {
-                gl.flush_mapped_buffer_range(target, offset as i32, size as i32);
+                gl.flush_mapped_buffer_range(target, offset, size);
             }
             gl.bind_buffer(target, None);
             if let Err(err) = self.share.check() {
@@ -1418,15 +2396,22 @@ impl d::Device<B> for Device {
             let (buffer, target) = mem.buffer.expect("cannot invalidate image memory");
             gl.bind_buffer(target, Some(buffer));
 
-            let offset = segment.offset;
-            let size = segment.size.unwrap_or(mem.size - segment.offset);
+            let offset: i32 = buffer::cast(segment.offset).map_err(|err| {
+                log::error!("Invalid mapped memory range: {}", err);
+                d::OutOfMemory::Host
+            })?;
+            let size: i32 = buffer::cast(segment.size.unwrap_or(mem.size - segment.offset))
+                .map_err(|err| {
+                    log::error!("Invalid mapped memory range: {}", err);
+                    d::OutOfMemory::Host
+                })?;
 
             if self.share.private_caps.emulate_map {
                 let ptr = mem.emulate_map_allocation.unwrap();
                 let slice = slice::from_raw_parts_mut(ptr.offset(offset as isize), size as usize);
-                gl.get_buffer_sub_data(target, offset as i32, slice);
+                gl.get_buffer_sub_data(target, offset, slice);
             } else {
-                gl.invalidate_buffer_sub_data(target, offset as i32, size as i32);
+                gl.invalidate_buffer_sub_data(target, offset, size);
                 gl.bind_buffer(target, None);
             }
 
@@ -1443,35 +2428,168 @@ impl d::Device<B> for Device {
 
     unsafe fn create_buffer_view(
         &self,
-        _: &n::Buffer,
-        _: Option<Format>,
-        _: buffer::SubRange,
+        buffer: &n::Buffer,
+        format: Option<Format>,
+        sub: buffer::SubRange,
     ) -> Result<n::BufferView, buffer::ViewCreationError> {
-        unimplemented!()
+        if self.share.public_caps.limits.max_texel_elements == 0 {
+            return Err(buffer::ViewCreationError::UnsupportedFormat(format));
+        }
+        let desc = format
+            .filter(|&format| conv::is_texel_buffer_format(format))
+            .and_then(conv::describe_format)
+            .ok_or(buffer::ViewCreationError::UnsupportedFormat(format))?;
+
+        let gl = &self.share.context;
+        let bounded_buffer = buffer.as_bound();
+        let range = crate::resolve_sub_range(&sub, bounded_buffer.range);
+        let offset = buffer::cast(range.start).map_err(|_| d::OutOfMemory::Device)?;
+        let size = buffer::cast(range.end - range.start).map_err(|_| d::OutOfMemory::Device)?;
+        let raw = gl.create_texture().unwrap();
+        gl.bind_texture(glow::TEXTURE_BUFFER, Some(raw));
+        gl.tex_buffer_range(
+            glow::TEXTURE_BUFFER,
+            desc.tex_internal,
+            Some(bounded_buffer.raw),
+            offset,
+            size,
+        );
+        gl.bind_texture(glow::TEXTURE_BUFFER, None);
+
+        Ok(n::BufferView {
+            raw,
+            format: desc.tex_internal,
+        })
+    }
+
+    fn format_properties(&self, format: Format) -> hal::format::Properties {
+        self.share.format_properties(Some(format))
+    }
+
+    unsafe fn create_image(
+        &self,
+        kind: i::Kind,
+        num_levels: i::Level,
+        format: Format,
+        tiling: i::Tiling,
+        usage: i::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: i::ViewCapabilities,
+    ) -> Result<n::Image, i::CreationError> {
+        self.create_partial_image(
+            kind,
+            0..num_levels,
+            format,
+            tiling,
+            usage,
+            sparse,
+            view_caps,
+        )
     }
 
-    unsafe fn create_image(
+    unsafe fn create_partial_image(
         &self,
         kind: i::Kind,
-        num_levels: i::Level,
+        levels: Range<i::Level>,
         format: Format,
-        _tiling: i::Tiling,
+        tiling: i::Tiling,
         usage: i::Usage,
-        _sparse: memory::SparseFlags,
-        _view_caps: i::ViewCapabilities,
+        sparse: memory::SparseFlags,
+        view_caps: i::ViewCapabilities,
     ) -> Result<n::Image, i::CreationError> {
+        if let Some((first, others)) = format.planes().split_first() {
+            // Multi-planar images are emulated with a texture per plane, so that
+            // each plane can be copied and viewed on its own.
+            let (width, height, layers) = match kind {
+                i::Kind::D2(w, h, l, 1) => (w, h, l),
+                _ => return Err(i::CreationError::Kind),
+            };
+            let plane_usage = usage | i::Usage::SAMPLED;
+            let mut image = self.create_partial_image(
+                kind,
+                levels.clone(),
+                first.format,
+                tiling,
+                plane_usage,
+                sparse,
+                view_caps,
+            )?;
+            for (index, plane) in others.iter().enumerate() {
+                let (w, h) = plane.extent(width, height);
+                match self.create_partial_image(
+                    i::Kind::D2(w, h, layers, 1),
+                    levels.clone(),
+                    plane.format,
+                    tiling,
+                    plane_usage,
+                    sparse,
+                    view_caps,
+                ) {
+                    Ok(plane_image) => {
+                        image.extra_planes[index] = Some(plane_image.object_type);
+                        image.requirements.size += plane_image.requirements.size;
+                    }
+                    Err(err) => {
+                        self.destroy_image(image);
+                        return Err(err);
+                    }
+                }
+            }
+            return Ok(image);
+        }
+
         let gl = &self.share.context;
 
-        let desc = conv::describe_format(format).ok_or(i::CreationError::Format(format))?;
         let channel = format.base_format().1;
+        // GL can't reinterpret the storage of a texture, so linear images that
+        // may be viewed as sRGB are stored sRGB-encoded. Their linear views then
+        // bypass the conversion in both directions.
+        let storage_format = match conv::srgb_format(format) {
+            Some(srgb_format)
+                if view_caps.contains(i::ViewCapabilities::MUTABLE_FORMAT)
+                    && self.share.private_caps.framebuffer_srgb
+                    && self.share.private_caps.srgb_decode =>
+            {
+                srgb_format
+            }
+            _ => format,
+        };
+        let desc = conv::describe_format(storage_format).ok_or(i::CreationError::Format(format))?;
+        // Compressed textures can only be allocated without data through texture storage.
+        let compressed = format.surface_desc().is_compressed();
+        if compressed
+            && (!self.share.supports_compression(format) || !self.share.private_caps.image_storage)
+        {
+            return Err(i::CreationError::Format(format));
+        }
+        // Texture storage always allocates from level 0, so partial chains
+        // are built level by level and clamped with `TEXTURE_BASE_LEVEL`.
+        let partial = levels.start != 0;
+        if partial && compressed {
+            return Err(i::CreationError::Levels(levels));
+        }
+        let use_storage = self.share.private_caps.image_storage && !partial;
+        let num_levels = levels.end;
+        let samples = kind.num_samples();
+        if samples > 1 {
+            let mask = self
+                .share
+                .sample_count_mask(format, desc.tex_internal, usage);
+            if mask & samples == 0 {
+                return Err(i::CreationError::Samples(samples));
+            }
+        }
 
         let mut pixel_count: u64 = 0;
-        let image = if num_levels > 1 || usage.intersects(i::Usage::STORAGE | i::Usage::SAMPLED) {
+        let image = if num_levels > 1
+            || compressed
+            || usage.intersects(i::Usage::STORAGE | i::Usage::SAMPLED)
+        {
             let name = gl.create_texture().unwrap();
             let target = match kind {
                 i::Kind::D2(w, h, 1, 1) => {
                     gl.bind_texture(glow::TEXTURE_2D, Some(name));
-                    if self.share.private_caps.image_storage {
+                    if use_storage {
                         gl.tex_storage_2d(
                             glow::TEXTURE_2D,
                             num_levels as _,
@@ -1481,14 +2599,21 @@ impl d::Device<B> for Device {
                         );
                         pixel_count += (w * h) as u64 * num_levels as u64;
                     } else {
+                        if partial {
+                            gl.tex_parameter_i32(
+                                glow::TEXTURE_2D,
+                                glow::TEXTURE_BASE_LEVEL,
+                                levels.start as _,
+                            );
+                        }
                         gl.tex_parameter_i32(
                             glow::TEXTURE_2D,
                             glow::TEXTURE_MAX_LEVEL,
                             (num_levels - 1) as _,
                         );
-                        let mut w = w;
-                        let mut h = h;
-                        for i in 0..num_levels {
+                        for i in levels.clone() {
+                            let w = std::cmp::max(w >> i, 1);
+                            let h = std::cmp::max(h >> i, 1);
                             gl.tex_image_2d(
                                 glow::TEXTURE_2D,
                                 i as _,
@@ -1501,8 +2626,6 @@ impl d::Device<B> for Device {
                                 None,
                             );
                             pixel_count += (w * h) as u64;
-                            w = std::cmp::max(w / 2, 1);
-                            h = std::cmp::max(h / 2, 1);
                         }
                     }
                     match channel {
@@ -1524,7 +2647,7 @@ impl d::Device<B> for Device {
                 }
                 i::Kind::D2(w, h, l, 1) => {
                     gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(name));
-                    if self.share.private_caps.image_storage {
+                    if use_storage {
                         gl.tex_storage_3d(
                             glow::TEXTURE_2D_ARRAY,
                             num_levels as _,
@@ -1535,14 +2658,21 @@ impl d::Device<B> for Device {
                         );
                         pixel_count += (w * h) as u64 * l as u64 * num_levels as u64;
                     } else {
+                        if partial {
+                            gl.tex_parameter_i32(
+                                glow::TEXTURE_2D_ARRAY,
+                                glow::TEXTURE_BASE_LEVEL,
+                                levels.start as _,
+                            );
+                        }
                         gl.tex_parameter_i32(
                             glow::TEXTURE_2D_ARRAY,
                             glow::TEXTURE_MAX_LEVEL,
                             (num_levels - 1) as _,
                         );
-                        let mut w = w;
-                        let mut h = h;
-                        for i in 0..num_levels {
+                        for i in levels.clone() {
+                            let w = std::cmp::max(w >> i, 1);
+                            let h = std::cmp::max(h >> i, 1);
                             gl.tex_image_3d(
                                 glow::TEXTURE_2D_ARRAY,
                                 i as _,
@@ -1556,8 +2686,6 @@ impl d::Device<B> for Device {
                                 None,
                             );
                             pixel_count += (w * h) as u64 * l as u64;
-                            w = std::cmp::max(w / 2, 1);
-                            h = std::cmp::max(h / 2, 1);
                         }
                     }
                     match channel {
@@ -1577,6 +2705,23 @@ impl d::Device<B> for Device {
                     };
                     glow::TEXTURE_2D_ARRAY
                 }
+                i::Kind::D2(w, h, 1, samples) => {
+                    if !self.share.private_caps.multisampled_textures || num_levels != 1 {
+                        gl.delete_texture(name);
+                        return Err(i::CreationError::Samples(samples));
+                    }
+                    gl.bind_texture(glow::TEXTURE_2D_MULTISAMPLE, Some(name));
+                    gl.tex_storage_2d_multisample(
+                        glow::TEXTURE_2D_MULTISAMPLE,
+                        samples as _,
+                        desc.tex_internal,
+                        w as _,
+                        h as _,
+                        true,
+                    );
+                    pixel_count += (w * h) as u64 * samples as u64;
+                    glow::TEXTURE_2D_MULTISAMPLE
+                }
                 _ => unimplemented!(),
             };
             n::ImageType::Texture {
@@ -1585,7 +2730,7 @@ impl d::Device<B> for Device {
                 format: desc.tex_external,
                 pixel_type: desc.data_type,
                 layer_count: kind.num_layers(),
-                level_count: num_levels,
+                level_count: num_levels - levels.start,
             }
         } else {
             let name = gl.create_renderbuffer().unwrap();
@@ -1613,9 +2758,27 @@ impl d::Device<B> for Device {
             }
         };
 
+        let immutable_storage = match image {
+            n::ImageType::Texture {
+                target: glow::TEXTURE_2D_MULTISAMPLE,
+                ..
+            } => true,
+            n::ImageType::Texture { .. } => use_storage,
+            n::ImageType::Renderbuffer { .. } => false,
+        };
         let surface_desc = format.base_format().0.desc();
-        let bytes_per_texel = surface_desc.bits / 8;
-        let size = pixel_count as u64 * bytes_per_texel as u64;
+        let bytes_per_block = surface_desc.bits as u64 / 8;
+        let size = if compressed {
+            // Partially covered blocks take as much space as whole ones.
+            let layers = kind.num_layers() as u64;
+            (0..num_levels).fold(0, |size, level| {
+                let extent = kind.level_extent(level);
+                let blocks = block_count(&surface_desc, extent.width, extent.height);
+                size + blocks * extent.depth as u64 * layers * bytes_per_block
+            })
+        } else {
+            pixel_count * bytes_per_block
+        };
         let type_mask = self.share.image_memory_type_mask();
 
         if let Err(err) = self.share.check() {
@@ -1627,14 +2790,18 @@ impl d::Device<B> for Device {
 
         Ok(n::Image {
             object_type: image,
+            extra_planes: [None; 2],
             kind,
             format_desc: surface_desc,
             channel,
+            srgb_storage: storage_format.base_format().1 == ChannelType::Srgb,
+            immutable_storage,
             requirements: memory::Requirements {
                 size,
                 alignment: 1,
                 type_mask,
             },
+            base_level: levels.start,
             num_levels,
             num_layers: kind.num_layers(),
         })
@@ -1679,17 +2846,30 @@ impl d::Device<B> for Device {
         kind: i::ViewKind,
         view_format: Format,
         swizzle: Swizzle,
-        _usage: i::Usage,
+        usage: i::Usage,
         range: i::SubresourceRange,
     ) -> Result<n::ImageView, i::ViewCreationError> {
-        assert_eq!(swizzle, Swizzle::NO);
-        match image.object_type {
+        // Only the sRGB conversion of the texels can be reinterpreted by a view.
+        let srgb_view = view_format.base_format().1 == ChannelType::Srgb;
+        let srgb = if image.srgb_storage {
+            Some(srgb_view)
+        } else {
+            if srgb_view {
+                log::warn!(
+                    "sRGB view format {:?} of a linear image without `MUTABLE_FORMAT`",
+                    view_format
+                );
+            }
+            None
+        };
+        match image.plane(range.aspects) {
             n::ImageType::Renderbuffer { raw, .. } => {
                 let level = range.level_start;
                 if range.level_start == 0 && range.layer_start == 0 {
                     Ok(n::ImageView::Renderbuffer {
                         raw,
                         aspects: image.format_desc.aspects,
+                        srgb,
                     })
                 } else if level != 0 {
                     Err(i::ViewCreationError::Level(level)) //TODO
@@ -1701,32 +2881,145 @@ impl d::Device<B> for Device {
                 target,
                 raw,
                 format,
+                pixel_type,
                 ..
             } => {
+                let levels = image.levels();
+                if range.level_start < levels.start || range.level_start >= levels.end {
+                    return Err(i::ViewCreationError::Level(range.level_start));
+                }
+                let level_end = range.level_start + range.resolve_level_count(levels.end);
+                if level_end > levels.end {
+                    return Err(i::ViewCreationError::Level(range.level_start));
+                }
+                if range.layer_start >= image.num_layers {
+                    return Err(i::LayerError::OutOfBounds.into());
+                }
+                let layer_end = range.layer_start + range.resolve_layer_count(image.num_layers);
+                if layer_end > image.num_layers {
+                    return Err(i::LayerError::OutOfBounds.into());
+                }
                 let is_3d = match kind {
                     i::ViewKind::D1 | i::ViewKind::D2 => false,
                     _ => true,
                 };
-                match conv::describe_format(view_format) {
-                    Some(description) => {
-                        let raw_view_format = description.tex_external;
-                        if format != raw_view_format {
-                            log::warn!(
-                                "View format {:?} is different from base {:?}",
-                                raw_view_format,
-                                format
+                let description = match conv::describe_format(view_format) {
+                    Some(description) => description,
+                    None => return Err(i::ViewCreationError::BadFormat(view_format)),
+                };
+                let view_target = match kind {
+                    i::ViewKind::D2 if target == glow::TEXTURE_2D_MULTISAMPLE => Some(target),
+                    i::ViewKind::D2 => Some(glow::TEXTURE_2D),
+                    i::ViewKind::D2Array if target == glow::TEXTURE_2D_MULTISAMPLE => {
+                        Some(glow::TEXTURE_2D_MULTISAMPLE_ARRAY)
+                    }
+                    i::ViewKind::D2Array => Some(glow::TEXTURE_2D_ARRAY),
+                    i::ViewKind::Cube => Some(glow::TEXTURE_CUBE_MAP),
+                    i::ViewKind::CubeArray => Some(glow::TEXTURE_CUBE_MAP_ARRAY),
+                    i::ViewKind::D1 | i::ViewKind::D1Array | i::ViewKind::D3 => None,
+                };
+                // Views of the same texels, besides the sRGB conversion, share the texture
+                // as attachments and storage images, which select their level and layer.
+                // Sampling sees all the levels and layers of a texture, with its swizzle,
+                // so it requires a texture view.
+                let reinterpreted =
+                    (description.tex_external, description.data_type) != (format, pixel_type);
+                let whole = range.level_start == levels.start
+                    && level_end == levels.end
+                    && range.layer_start == 0
+                    && layer_end == image.num_layers;
+                let needs_view = reinterpreted
+                    || (usage.contains(i::Usage::SAMPLED)
+                        && (swizzle != Swizzle::NO || !whole || view_target != Some(target)));
+
+                match (&self.share.texture_view, view_target) {
+                    (Some(fns), Some(view_target)) if needs_view && image.immutable_storage => {
+                        let gl = &self.share.context;
+                        let name = gl.create_texture().map_err(|_| d::OutOfMemory::Device)?;
+                        fns.texture_view(
+                            name,
+                            view_target,
+                            raw,
+                            description.tex_internal,
+                            range.level_start as u32..level_end as u32,
+                            range.layer_start as u32..layer_end as u32,
+                        );
+                        gl.bind_texture(view_target, Some(name));
+                        if swizzle != Swizzle::NO {
+                            let Swizzle(r, g, b, a) = swizzle;
+                            for &(parameter, component) in &[
+                                (glow::TEXTURE_SWIZZLE_R, r),
+                                (glow::TEXTURE_SWIZZLE_G, g),
+                                (glow::TEXTURE_SWIZZLE_B, b),
+                                (glow::TEXTURE_SWIZZLE_A, a),
+                            ] {
+                                gl.tex_parameter_i32(
+                                    view_target,
+                                    parameter,
+                                    conv::component_to_gl(component) as i32,
+                                );
+                            }
+                        }
+                        if let ChannelType::Uint | ChannelType::Sint = view_format.base_format().1 {
+                            gl.tex_parameter_i32(
+                                view_target,
+                                glow::TEXTURE_MIN_FILTER,
+                                glow::NEAREST as _,
+                            );
+                            gl.tex_parameter_i32(
+                                view_target,
+                                glow::TEXTURE_MAG_FILTER,
+                                glow::NEAREST as _,
                             );
                         }
+                        gl.bind_texture(view_target, None);
+
+                        let error = gl.get_error();
+                        if error != glow::NO_ERROR {
+                            // The formats of the view and the image aren't compatible.
+                            log::error!("Error creating a texture view: 0x{:X}", error);
+                            gl.delete_texture(name);
+                            return Err(i::ViewCreationError::BadFormat(view_format));
+                        }
+                        return Ok(n::ImageView::Texture {
+                            target: view_target,
+                            raw: name,
+                            is_3d,
+                            sub: i::SubresourceRange {
+                                aspects: range.aspects,
+                                level_start: 0,
+                                level_count: Some(level_end - range.level_start),
+                                layer_start: 0,
+                                layer_count: Some(layer_end - range.layer_start),
+                            },
+                            srgb,
+                            internal_format: Some(description.tex_internal),
+                            owned: true,
+                        });
                     }
-                    None => {
-                        log::warn!("View format {:?} is not supported", view_format);
+                    _ if reinterpreted => {
+                        log::warn!(
+                            "View format {:?} needs texture views of an immutable texture",
+                            view_format
+                        );
+                        return Err(i::ViewCreationError::BadFormat(view_format));
+                    }
+                    _ if needs_view && swizzle != Swizzle::NO => {
+                        log::warn!(
+                            "Swizzle {:?} is ignored without texture views of an immutable texture",
+                            swizzle
+                        );
                     }
+                    _ => {}
                 }
                 Ok(n::ImageView::Texture {
                     target,
                     raw,
                     is_3d,
                     sub: range,
+                    srgb,
+                    internal_format: Some(description.tex_internal),
+                    owned: false,
                 })
             }
         }
@@ -1769,9 +3062,14 @@ impl d::Device<B> for Device {
             .binary_search_by_key(&op.binding, |b| b.binding)
             .unwrap();
         let mut array_offset = op.array_offset;
+        let mut written = Vec::with_capacity(op.descriptors.size_hint().0);
 
         for descriptor in op.descriptors {
             let binding_layout = &op.set.layout[layout_index];
+            let slot = n::DescriptorSlot {
+                binding: binding_layout.binding,
+                array_index: array_offset,
+            };
             let binding = match descriptor {
                 pso::Descriptor::Buffer(buffer, ref sub) => {
                     let bounded_buffer = buffer.as_bound();
@@ -1794,16 +3092,18 @@ impl d::Device<B> for Device {
                     n::DescSetBindings::Buffer {
                         register,
                         buffer: bounded_buffer.raw,
-                        offset: range.start as i32,
-                        size: (range.end - range.start) as i32,
+                        offset: range.start,
+                        size: range.end - range.start,
+                        shadow: bounded_buffer
+                            .shadow
+                            .map(|shadow| n::HostShadow(shadow.0.add(range.start as usize))),
                     }
                 }
                 pso::Descriptor::CombinedImageSampler(view, _layout, sampler) => {
                     match *view {
-                        n::ImageView::Texture { target, raw, .. } => op
-                            .set
-                            .bindings
-                            .push(n::DescSetBindings::Texture(raw, target)),
+                        n::ImageView::Texture {
+                            target, raw, srgb, ..
+                        } => written.push((slot, n::DescSetBindings::Texture(raw, target, srgb))),
                         n::ImageView::Renderbuffer { .. } => {
                             panic!("Texture doesn't support shader binding")
                         }
@@ -1815,11 +3115,41 @@ impl d::Device<B> for Device {
                         }
                     }
                 }
-                pso::Descriptor::Image(view, _layout) => match *view {
-                    n::ImageView::Texture { target, raw, .. } => {
-                        n::DescSetBindings::Texture(raw, target)
-                    }
-                    n::ImageView::Renderbuffer { .. } => {
+                pso::Descriptor::Image(view, _layout) => match (binding_layout.ty, view) {
+                    (
+                        pso::DescriptorType::Image {
+                            ty: pso::ImageDescriptorType::Storage { read_only },
+                        },
+                        &n::ImageView::Texture {
+                            raw,
+                            is_3d,
+                            ref sub,
+                            internal_format,
+                            ..
+                        },
+                    ) => n::DescSetBindings::Image {
+                        texture: raw,
+                        level: sub.level_start as i32,
+                        layer: if is_3d {
+                            None
+                        } else {
+                            Some(sub.layer_start as i32)
+                        },
+                        access: if read_only {
+                            glow::READ_ONLY
+                        } else {
+                            glow::READ_WRITE
+                        },
+                        format: internal_format
+                            .expect("Storage image view of an unsupported format"),
+                    },
+                    (
+                        _,
+                        &n::ImageView::Texture {
+                            target, raw, srgb, ..
+                        },
+                    ) => n::DescSetBindings::Texture(raw, target, srgb),
+                    (_, &n::ImageView::Renderbuffer { .. }) => {
                         panic!("Texture doesn't support shader binding")
                     }
                 },
@@ -1827,11 +3157,29 @@ impl d::Device<B> for Device {
                     n::FatSampler::Sampler(sampler) => n::DescSetBindings::Sampler(sampler),
                     n::FatSampler::Info(ref info) => n::DescSetBindings::SamplerDesc(info.clone()),
                 },
-                pso::Descriptor::TexelBuffer(_view) => unimplemented!(),
+                pso::Descriptor::TexelBuffer(view) => match binding_layout.ty {
+                    pso::DescriptorType::Buffer {
+                        ty: pso::BufferDescriptorType::Storage { read_only },
+                        ..
+                    } => n::DescSetBindings::Image {
+                        texture: view.raw,
+                        level: 0,
+                        layer: None,
+                        access: if read_only {
+                            glow::READ_ONLY
+                        } else {
+                            glow::READ_WRITE
+                        },
+                        format: view.format,
+                    },
+                    _ => n::DescSetBindings::Texture(view.raw, glow::TEXTURE_BUFFER, None),
+                },
+                pso::Descriptor::AccelerationStructure(_) => {
+                    unreachable!("Acceleration structures are never exposed")
+                }
             };
 
-            //TODO: overwrite instead of pushing on top
-            op.set.bindings.push(binding);
+            written.push((slot, binding));
 
             array_offset += 1;
             if array_offset == binding_layout.count {
@@ -1839,6 +3187,8 @@ impl d::Device<B> for Device {
                 layout_index += 1;
             }
         }
+
+        op.set.update_slots(written);
     }
 
     unsafe fn copy_descriptor_set<'a>(&self, op: pso::DescriptorSetCopy<'a, B>) {
@@ -1846,18 +3196,23 @@ impl d::Device<B> for Device {
             panic!("copying within same descriptor set is not currently supported");
         }
 
-        // TODO: add support for array bindings when the OpenGL backend gets them
-        let src_start = op.src_binding as usize;
-        let src_end = src_start + op.count;
-        assert!(src_end <= op.src_set.bindings.len());
-
-        let src_slice = &op.src_set.bindings[src_start..src_end];
-
-        let dst_start = op.dst_binding as usize;
-        let dst_end = dst_start + op.count;
-        assert!(dst_end <= op.dst_set.bindings.len());
-
-        op.dst_set.bindings[dst_start..dst_end].clone_from_slice(src_slice);
+        // TODO: spill the copies over the following bindings
+        let src_indices = op.src_array_offset..op.src_array_offset + op.count;
+        let copies = op
+            .src_set
+            .bindings
+            .iter()
+            .filter(|&&(slot, _)| {
+                slot.binding == op.src_binding && src_indices.contains(&slot.array_index)
+            })
+            .map(|&(slot, ref binding)| {
+                let dst_slot = n::DescriptorSlot {
+                    binding: op.dst_binding,
+                    array_index: slot.array_index - op.src_array_offset + op.dst_array_offset,
+                };
+                (dst_slot, binding.clone())
+            });
+        op.dst_set.update_slots(copies.collect());
     }
 
     fn create_semaphore(&self) -> Result<n::Semaphore, d::OutOfMemory> {
@@ -1984,29 +3339,145 @@ impl d::Device<B> for Device {
         if let Some((buffer, _)) = memory.buffer {
             self.share.context.delete_buffer(buffer);
         }
+        if let Some(ptr) = memory.emulate_map_allocation {
+            let _ = Box::from_raw(slice::from_raw_parts_mut(ptr, memory.size as usize));
+        }
+        if let Some(memory_object) = memory.memory_object {
+            if let Some(ref fns) = self.share.memory_object {
+                fns.delete(memory_object);
+            }
+        }
+        self.share.memory_usage.free(memory.heap_index, memory.size);
+    }
+
+    fn memory_budget(&self) -> Vec<memory::HeapBudget> {
+        let gl = &self.share.context;
+        let mut budgets = self.share.memory_usage.budgets(&memory_heaps());
+
+        // The extensions only report the video memory, in kilobytes.
+        let local = &mut budgets[DEVICE_LOCAL_HEAP];
+        match self.share.private_caps.memory_info {
+            Some(MemoryInfoExtension::Nvx) => unsafe {
+                let total = gl.get_parameter_i32(GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX);
+                let available = gl.get_parameter_i32(GPU_MEMORY_INFO_CURRENT_AVAILABLE_VIDMEM_NVX);
+                local.budget = total as u64 * 1024;
+                local.usage = (total - available).max(0) as u64 * 1024;
+            },
+            Some(MemoryInfoExtension::Ati) => unsafe {
+                // The first of the four values is the total free memory of the pool.
+                let mut free = [0; 4];
+                gl.get_parameter_i32_slice(TEXTURE_FREE_MEMORY_ATI, &mut free);
+                local.budget = local.usage + free[0].max(0) as u64 * 1024;
+            },
+            None => {}
+        }
+        budgets
+    }
+
+    unsafe fn set_memory_priority(&self, _memory: &n::Memory, _priority: memory::Priority) {
+        // The driver manages the residency.
+    }
+
+    unsafe fn make_resident<'a, I>(&self, _memories: I) -> Result<(), d::OutOfMemory>
+    where
+        I: Iterator<Item = &'a n::Memory>,
+    {
+        Ok(())
+    }
+
+    unsafe fn evict<'a, I>(&self, _memories: I)
+    where
+        I: Iterator<Item = &'a n::Memory>,
+    {
     }
 
     unsafe fn create_query_pool(
         &self,
         ty: query::Type,
-        _count: query::Id,
-    ) -> Result<(), query::CreationError> {
-        Err(query::CreationError::Unsupported(ty))
+        count: query::Id,
+    ) -> Result<n::QueryPool, query::CreationError> {
+        if ty != query::Type::Timestamp || !self.share.private_caps.timestamp_query {
+            return Err(query::CreationError::Unsupported(ty));
+        }
+        let gl = &self.share.context;
+        let queries = (0..count).map(|_| gl.create_query().unwrap()).collect();
+        Ok(n::QueryPool { queries })
     }
 
-    unsafe fn destroy_query_pool(&self, _: ()) {
-        unimplemented!()
+    unsafe fn destroy_query_pool(&self, pool: n::QueryPool) {
+        let gl = &self.share.context;
+        for &query in pool.queries.iter() {
+            gl.delete_query(query);
+        }
+    }
+
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _geometry: &hal::acceleration_structure::GeometryInfo<B>,
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unreachable!("Acceleration structures are never exposed")
     }
 
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<B>,
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {}
+
     unsafe fn get_query_pool_results(
         &self,
-        _pool: &(),
-        _queries: Range<query::Id>,
-        _data: &mut [u8],
-        _stride: buffer::Stride,
-        _flags: query::ResultFlags,
+        pool: &n::QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
     ) -> Result<bool, d::WaitError> {
-        unimplemented!()
+        let gl = &self.share.context;
+        let mut all_available = true;
+        for (i, id) in queries.enumerate() {
+            let query = pool.queries[id as usize];
+            // Reading the result blocks until it's available.
+            let available = flags.contains(query::ResultFlags::WAIT)
+                || gl.get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE) != 0;
+            all_available &= available;
+
+            let mut value = 0u64;
+            if available {
+                gl.get_query_parameter_u64_with_offset(
+                    query,
+                    glow::QUERY_RESULT,
+                    &mut value as *mut u64 as usize,
+                );
+            } else if !flags.contains(query::ResultFlags::PARTIAL)
+                && !flags.contains(query::ResultFlags::WITH_AVAILABILITY)
+            {
+                continue;
+            }
+
+            let dst = data.as_mut_ptr().add(i * stride as usize);
+            if flags.contains(query::ResultFlags::BITS_64) {
+                *(dst as *mut u64) = value;
+                if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+                    *(dst as *mut u64).add(1) = available as u64;
+                }
+            } else {
+                *(dst as *mut u32) = value as u32;
+                if flags.contains(query::ResultFlags::WITH_AVAILABILITY) {
+                    *(dst as *mut u32).add(1) = available as u32;
+                }
+            }
+        }
+        Ok(all_available)
     }
 
     unsafe fn destroy_shader_module(&self, _: n::ShaderModule) {
@@ -2029,6 +3500,26 @@ impl d::Device<B> for Device {
         self.share.context.delete_program(pipeline.program);
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, B>,
+        _cache: Option<&()>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {}
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+        _data: &mut [u8],
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
     unsafe fn destroy_framebuffer(&self, framebuffer: n::Framebuffer) {
         self.share.context.delete_framebuffer(framebuffer.raw);
     }
@@ -2037,20 +3528,27 @@ impl d::Device<B> for Device {
         // Nothing to do
     }
 
-    unsafe fn destroy_buffer_view(&self, _: n::BufferView) {
-        // Nothing to do
+    unsafe fn destroy_buffer_view(&self, view: n::BufferView) {
+        self.share.context.delete_texture(view.raw);
     }
 
     unsafe fn destroy_image(&self, image: n::Image) {
         let gl = &self.share.context;
-        match image.object_type {
-            n::ImageType::Renderbuffer { raw, .. } => gl.delete_renderbuffer(raw),
-            n::ImageType::Texture { raw, .. } => gl.delete_texture(raw),
+        for object in image.objects() {
+            match object {
+                n::ImageType::Renderbuffer { raw, .. } => gl.delete_renderbuffer(raw),
+                n::ImageType::Texture { raw, .. } => gl.delete_texture(raw),
+            }
         }
     }
 
-    unsafe fn destroy_image_view(&self, _image_view: n::ImageView) {
-        // Nothing to do
+    unsafe fn destroy_image_view(&self, image_view: n::ImageView) {
+        if let n::ImageView::Texture {
+            raw, owned: true, ..
+        } = image_view
+        {
+            self.share.context.delete_texture(raw);
+        }
     }
 
     unsafe fn destroy_sampler(&self, sampler: n::FatSampler) {
@@ -2086,19 +3584,31 @@ impl d::Device<B> for Device {
         unimplemented!()
     }
 
-    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), d::OomOrDeviceLost> {
         unsafe {
             self.share.context.finish();
         }
-        Ok(())
+        self.share.device_status()
     }
 
-    unsafe fn set_image_name(&self, _image: &mut n::Image, _name: &str) {
-        // TODO
+    unsafe fn set_image_name(&self, image: &mut n::Image, name: &str) {
+        for object_type in image.objects() {
+            match object_type {
+                n::ImageType::Renderbuffer { raw, .. } => {
+                    self.label_object(glow::RENDERBUFFER, raw, name)
+                }
+                n::ImageType::Texture { raw, .. } => self.label_object(glow::TEXTURE, raw, name),
+            }
+        }
     }
 
-    unsafe fn set_buffer_name(&self, _buffer: &mut n::Buffer, _name: &str) {
-        // TODO
+    unsafe fn set_buffer_name(&self, buffer: &mut n::Buffer, name: &str) {
+        match *buffer {
+            n::Buffer::Bound { buffer, .. } => self.label_object(glow::BUFFER, buffer, name),
+            n::Buffer::Unbound { .. } => {
+                log::warn!("Buffer `{}` has to be bound to memory to be labeled", name)
+            }
+        }
     }
 
     unsafe fn set_command_buffer_name(
@@ -2117,8 +3627,8 @@ impl d::Device<B> for Device {
         // TODO
     }
 
-    unsafe fn set_framebuffer_name(&self, _framebuffer: &mut n::Framebuffer, _name: &str) {
-        // TODO
+    unsafe fn set_framebuffer_name(&self, framebuffer: &mut n::Framebuffer, name: &str) {
+        self.label_object(glow::FRAMEBUFFER, framebuffer.raw, name);
     }
 
     unsafe fn set_render_pass_name(&self, _render_pass: &mut n::RenderPass, _name: &str) {
@@ -2145,6 +3655,14 @@ impl d::Device<B> for Device {
         // TODO
     }
 
+    unsafe fn set_graphics_pipeline_name(&self, pipeline: &mut n::GraphicsPipeline, name: &str) {
+        self.label_object(glow::PROGRAM, pipeline.program, name);
+    }
+
+    unsafe fn set_compute_pipeline_name(&self, pipeline: &mut n::ComputePipeline, name: &str) {
+        self.label_object(glow::PROGRAM, pipeline.program, name);
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &hal::display::Display<B>,
@@ -2177,20 +3695,87 @@ impl d::Device<B> for Device {
         _sparse: hal::memory::SparseFlags,
         _type_mask: u32,
         _size: u64,
-    ) -> Result<(n::Buffer, n::Memory), hal::external_memory::ExternalResourceError>
-    {
-        unimplemented!()
+    ) -> Result<(n::Buffer, n::Memory), hal::external_memory::ExternalResourceError> {
+        // Memory objects can only be imported.
+        Err(hal::external_memory::ExternalResourceError::InvalidExternalHandle)
     }
 
     unsafe fn import_external_buffer(
         &self,
-        _external_memory: hal::external_memory::ExternalBufferMemory,
-        _usage: hal::buffer::Usage,
-        _sparse: hal::memory::SparseFlags,
-        _type_mask: u32,
-        _size: u64,
+        external_memory: hal::external_memory::ExternalBufferMemory,
+        usage: hal::buffer::Usage,
+        sparse: hal::memory::SparseFlags,
+        type_mask: u32,
+        size: u64,
     ) -> Result<(n::Buffer, n::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        use hal::external_memory::ExternalResourceError as Ere;
+
+        let fns = match self.share.memory_object {
+            Some(ref fns) if self.share.private_caps.buffer_storage => fns,
+            _ => return Err(Ere::InvalidExternalHandle),
+        };
+        let mut buffer = self
+            .create_buffer(size, usage, sparse)
+            .map_err(|err| match err {
+                buffer::CreationError::OutOfMemory(oom) => Ere::OutOfMemory(oom),
+                buffer::CreationError::UnsupportedUsage(_) => Ere::InvalidExternalHandle,
+            })?;
+        let type_mask = type_mask & self.get_buffer_requirements(&buffer).type_mask;
+        // The imported memory can't be mapped.
+        let mem_type = (0..self.share.memory_types.len()).find(|&id| {
+            let (memory_type, _) = self.share.memory_types[id];
+            type_mask & (1 << id) != 0
+                && !memory_type
+                    .properties
+                    .contains(memory::Properties::CPU_VISIBLE)
+        });
+        let (memory_type, memory_role) = match mem_type {
+            Some(id) => self.share.memory_types[id],
+            None => return Err(Ere::NoValidMemoryTypeId),
+        };
+        let target = match memory_role {
+            MemoryUsage::Buffer(buffer_usage)
+                if buffer_usage.contains(buffer::Usage::INDEX)
+                    && !self.share.private_caps.index_buffer_role_change =>
+            {
+                glow::ELEMENT_ARRAY_BUFFER
+            }
+            _ => glow::ARRAY_BUFFER,
+        };
+
+        let handle = ImportHandle::from_buffer_memory(&external_memory);
+        let memory_object = handle
+            .and_then(|handle| fns.import(external_memory.external_memory_type(), handle, size))
+            .ok_or(Ere::InvalidExternalHandle)?;
+
+        let gl = &self.share.context;
+        let raw = gl.create_buffer().unwrap();
+        gl.bind_buffer(target, Some(raw));
+        fns.buffer_storage(target, size, memory_object);
+        gl.bind_buffer(target, None);
+
+        let error = gl.get_error();
+        if error != glow::NO_ERROR {
+            log::error!("Error importing the memory of a buffer: 0x{:X}", error);
+            gl.delete_buffer(raw);
+            fns.delete(memory_object);
+            return Err(Ere::InvalidExternalHandle);
+        }
+
+        self.share
+            .memory_usage
+            .allocate(memory_type.heap_index, size);
+        let memory = n::Memory {
+            properties: memory_type.properties,
+            heap_index: memory_type.heap_index,
+            buffer: Some((raw, target)),
+            size,
+            map_flags: 0,
+            emulate_map_allocation: None,
+            memory_object: Some(memory_object),
+        };
+        self.bind_buffer_memory(&memory, 0, &mut buffer).unwrap();
+        Ok((buffer, memory))
     }
 
     unsafe fn create_allocate_external_image(
@@ -2205,22 +3790,149 @@ impl d::Device<B> for Device {
         _view_caps: i::ViewCapabilities,
         _type_mask: u32,
     ) -> Result<(n::Image, n::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        // Memory objects can only be imported.
+        Err(hal::external_memory::ExternalResourceError::InvalidExternalHandle)
     }
 
     unsafe fn import_external_image(
         &self,
-        _external_memory: hal::external_memory::ExternalImageMemory,
-        _kind: i::Kind,
-        _num_levels: i::Level,
-        _format: Format,
-        _tiling: i::Tiling,
+        external_memory: hal::external_memory::ExternalImageMemory,
+        kind: i::Kind,
+        num_levels: i::Level,
+        format: Format,
+        tiling: i::Tiling,
         _usage: i::Usage,
         _sparse: memory::SparseFlags,
         _view_caps: i::ViewCapabilities,
-        _type_mask: u32,
+        type_mask: u32,
+        size: u64,
     ) -> Result<(n::Image, n::Memory), hal::external_memory::ExternalResourceError> {
-        unimplemented!()
+        use hal::external_memory::ExternalResourceError as Ere;
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if let hal::external_memory::ExternalImageMemory::DmaBuf(ref fd, ref properties) =
+                external_memory
+            {
+                return self.import_dma_buf_image(
+                    **fd,
+                    properties.as_ref(),
+                    kind,
+                    num_levels,
+                    format,
+                    type_mask,
+                    size,
+                );
+            }
+        }
+
+        let fns = match self.share.memory_object {
+            Some(ref fns) => fns,
+            None => return Err(Ere::InvalidExternalHandle),
+        };
+        let desc = match conv::describe_format(format) {
+            Some(desc) if tiling == i::Tiling::Optimal && format.planes().len() <= 1 => desc,
+            _ => return Err(Ere::InvalidExternalHandle),
+        };
+        let (width, height, layers) = match kind {
+            i::Kind::D2(w, h, l, 1) => (w, h, l),
+            _ => return Err(Ere::InvalidExternalHandle),
+        };
+        let type_mask = type_mask & self.share.image_memory_type_mask();
+        if type_mask == 0 {
+            return Err(Ere::NoValidMemoryTypeId);
+        }
+        let (memory_type, _) = self.share.memory_types[type_mask.trailing_zeros() as usize];
+
+        // The size of the memory isn't part of the external memory, so the one
+        // allocated by the exporter is given by the caller.
+        let surface_desc = format.base_format().0.desc();
+
+        let handle = ImportHandle::from_image_memory(&external_memory);
+        let memory_object = handle
+            .and_then(|handle| fns.import(external_memory.external_memory_type(), handle, size))
+            .ok_or(Ere::InvalidExternalHandle)?;
+
+        let gl = &self.share.context;
+        let name = gl.create_texture().unwrap();
+        let target = if layers == 1 {
+            glow::TEXTURE_2D
+        } else {
+            glow::TEXTURE_2D_ARRAY
+        };
+        gl.bind_texture(target, Some(name));
+        if layers == 1 {
+            fns.tex_storage_2d(
+                target,
+                num_levels as _,
+                desc.tex_internal,
+                width as _,
+                height as _,
+                memory_object,
+            );
+        } else {
+            fns.tex_storage_3d(
+                target,
+                num_levels as _,
+                desc.tex_internal,
+                width as _,
+                height as _,
+                layers as _,
+                memory_object,
+            );
+        }
+        let channel = format.base_format().1;
+        if let ChannelType::Uint | ChannelType::Sint = channel {
+            gl.tex_parameter_i32(target, glow::TEXTURE_MIN_FILTER, glow::NEAREST as _);
+            gl.tex_parameter_i32(target, glow::TEXTURE_MAG_FILTER, glow::NEAREST as _);
+        }
+        gl.bind_texture(target, None);
+
+        let error = gl.get_error();
+        if error != glow::NO_ERROR {
+            log::error!("Error importing the memory of an image: 0x{:X}", error);
+            gl.delete_texture(name);
+            fns.delete(memory_object);
+            return Err(Ere::InvalidExternalHandle);
+        }
+
+        let image = n::Image {
+            object_type: n::ImageType::Texture {
+                target,
+                raw: name,
+                format: desc.tex_external,
+                pixel_type: desc.data_type,
+                layer_count: layers,
+                level_count: num_levels,
+            },
+            extra_planes: [None; 2],
+            kind,
+            format_desc: surface_desc,
+            channel,
+            srgb_storage: channel == ChannelType::Srgb,
+            immutable_storage: true,
+            requirements: memory::Requirements {
+                size,
+                alignment: 1,
+                type_mask: self.share.image_memory_type_mask(),
+            },
+            base_level: 0,
+            num_levels,
+            num_layers: layers,
+        };
+        self.share
+            .memory_usage
+            .allocate(memory_type.heap_index, size);
+        let memory = n::Memory {
+            properties: memory_type.properties,
+            heap_index: memory_type.heap_index,
+            buffer: None,
+            size,
+            map_flags: 0,
+            emulate_map_allocation: None,
+            memory_object: Some(memory_object),
+        };
+        Ok((image, memory))
     }
 
     unsafe fn export_memory(
@@ -2229,7 +3941,8 @@ impl d::Device<B> for Device {
         _memory: &n::Memory,
     ) -> Result<hal::external_memory::PlatformMemory, hal::external_memory::ExternalMemoryExportError>
     {
-        unimplemented!()
+        // GL has no way to share the storage of its objects.
+        Err(hal::external_memory::ExternalMemoryExportError::InvalidExternalHandle)
     }
 
     unsafe fn drm_format_modifier(&self, _image: &n::Image) -> Option<hal::format::DrmModifier> {
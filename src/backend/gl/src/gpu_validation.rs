@@ -0,0 +1,156 @@
+//! GPU-assisted validation (`InstanceFlags::GPU_VALIDATION`).
+//!
+//! The shaders are instrumented by `auxil::instrument` before their translation,
+//! checking their dynamic indices and writing the violations to a storage buffer.
+//! The queue writes the number of each draw and dispatch to the buffer, and reads
+//! it back after each submission to report the violations.
+
+use crate::{
+    info::{
+        Info,
+        Requirement::{Core, Es, Ext},
+    },
+    native, GlContainer,
+};
+
+use auxil::instrument;
+use glow::HasContext;
+use parking_lot::Mutex;
+
+use std::{mem, ptr};
+
+const RECORD_SIZE: usize = instrument::RECORD_WORDS * mem::size_of::<u32>();
+
+/// Checks of the shaders instrumented by a device.
+pub struct GpuValidation {
+    /// Storage buffer binding reserved for the violations.
+    pub binding: u8,
+    /// Checks of all the instrumented shaders, by their number minus one.
+    checks: Mutex<Vec<instrument::Check>>,
+}
+
+impl GpuValidation {
+    /// Reserve the last storage buffer binding for the violations, if the
+    /// storage buffers are supported by all the shader stages.
+    pub fn new(gl: &GlContainer, info: &Info, limits: &mut hal::Limits) -> Option<Self> {
+        let limits = &mut limits.descriptor_limits;
+        let storage_buffers = info.is_supported(&[
+            Core(4, 3),
+            Es(3, 1),
+            Ext("GL_ARB_shader_storage_buffer_object"),
+        ]);
+        // The fragment and compute stages are accounted for by the limits.
+        if !storage_buffers
+            || unsafe { gl.get_parameter_i32(glow::MAX_VERTEX_SHADER_STORAGE_BLOCKS) } <= 0
+            || limits.max_per_stage_descriptor_storage_buffers == 0
+            || limits.max_descriptor_set_storage_buffers == 0
+        {
+            log::warn!("GPU validation requires storage buffers in all the shader stages");
+            return None;
+        }
+        let binding = (limits.max_descriptor_set_storage_buffers - 1).min(u8::max_value() as u32);
+        limits.max_descriptor_set_storage_buffers = binding;
+        limits.max_per_stage_descriptor_storage_buffers -= 1;
+        Some(GpuValidation {
+            binding: binding as u8,
+            checks: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Instrument a SPIR-V module, registering its checks.
+    pub fn instrument(&self, spirv: &[u32]) -> Result<Vec<u32>, String> {
+        let mut checks = self.checks.lock();
+        let instrumented = instrument::instrument(spirv, checks.len() as u32)?;
+        checks.extend(instrumented.checks);
+        Ok(instrumented.words)
+    }
+}
+
+/// Storage buffer receiving the violations of the submissions of a queue.
+pub struct ViolationBuffer {
+    raw: native::RawBuffer,
+    binding: u32,
+    /// Command buffer and command of each draw and dispatch of the submission.
+    draws: Vec<(usize, usize)>,
+}
+
+impl ViolationBuffer {
+    pub fn new(gl: &GlContainer, validation: &GpuValidation) -> Self {
+        let raw = unsafe {
+            let buffer = gl.create_buffer().unwrap();
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(buffer));
+            gl.buffer_data_u8_slice(
+                glow::SHADER_STORAGE_BUFFER,
+                &[0; RECORD_SIZE],
+                glow::DYNAMIC_READ,
+            );
+            gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, None);
+            buffer
+        };
+        ViolationBuffer {
+            raw,
+            binding: validation.binding as u32,
+            draws: Vec::new(),
+        }
+    }
+
+    /// Bind the buffer to its reserved binding, after a reset of the state.
+    pub unsafe fn bind(&self, gl: &GlContainer) {
+        gl.bind_buffer_base(glow::SHADER_STORAGE_BUFFER, self.binding, Some(self.raw));
+    }
+
+    /// Identify the next draw or dispatch by the command recording it.
+    pub unsafe fn begin_draw(&mut self, gl: &GlContainer, command_buffer: usize, command: usize) {
+        let draw = self.draws.len() as u32;
+        self.draws.push((command_buffer, command));
+        gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.raw));
+        gl.buffer_sub_data_u8_slice(
+            glow::SHADER_STORAGE_BUFFER,
+            (instrument::CURRENT_DRAW * mem::size_of::<u32>()) as i32,
+            &draw.to_ne_bytes(),
+        );
+    }
+
+    /// Report the violation of the submission, if any, and clear it.
+    ///
+    /// Only the last violation is kept by the buffer.
+    pub unsafe fn report(&mut self, gl: &GlContainer, validation: &GpuValidation) {
+        if self.draws.is_empty() {
+            return;
+        }
+        let mut record = [0u32; instrument::RECORD_WORDS];
+        gl.memory_barrier(glow::BUFFER_UPDATE_BARRIER_BIT);
+        gl.bind_buffer(glow::SHADER_STORAGE_BUFFER, Some(self.raw));
+        let data = gl.map_buffer_range(
+            glow::SHADER_STORAGE_BUFFER,
+            0,
+            RECORD_SIZE as i32,
+            glow::MAP_READ_BIT,
+        );
+        if data.is_null() {
+            log::error!("Unable to read the violations of the shaders");
+        } else {
+            ptr::copy_nonoverlapping(data, record.as_mut_ptr() as *mut u8, RECORD_SIZE);
+            gl.unmap_buffer(glow::SHADER_STORAGE_BUFFER);
+        }
+
+        let number = record[instrument::CHECK] as usize;
+        if number != 0 {
+            let check = validation.checks.lock().get(number - 1).cloned();
+            let draw = self.draws.get(record[instrument::DRAW] as usize);
+            match (check, draw) {
+                (Some(check), Some(&(command_buffer, command))) => log::error!(
+                    "Out-of-range {}: index {} >= {}, in command {} of command buffer {}",
+                    check,
+                    record[instrument::INDEX],
+                    record[instrument::LENGTH],
+                    command,
+                    command_buffer,
+                ),
+                _ => log::error!("Invalid violation record {:?}", record),
+            }
+            gl.buffer_sub_data_u8_slice(glow::SHADER_STORAGE_BUFFER, 0, &[0; RECORD_SIZE]);
+        }
+        self.draws.clear();
+    }
+}
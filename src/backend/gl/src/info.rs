@@ -1,8 +1,10 @@
-use crate::{Error, GlContainer, MAX_COLOR_ATTACHMENTS};
+use crate::{Error, GlContainer, MAX_COLOR_ATTACHMENTS, MAX_SAMPLERS, MAX_TEXTURE_SLOTS};
 use glow::HasContext;
 use hal::{DynamicStates, Features, Limits, PerformanceCaveats, PhysicalDeviceProperties};
 use std::{collections::HashSet, fmt, str};
 
+use self::Requirement::*;
+
 /// A version number for a specific component of an OpenGL implementation
 #[derive(Clone, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Version {
@@ -164,6 +166,36 @@ fn get_usize(gl: &GlContainer, name: u32) -> Result<usize, Error> {
         Ok(value as usize)
     }
 }
+
+/// Query the sample counts supported by a renderable internal format, as a mask.
+pub(crate) fn get_sample_counts(gl: &GlContainer, internal_format: u32) -> hal::image::NumSamples {
+    let mut count = [0];
+    unsafe {
+        gl.get_internal_format_i32_slice(
+            glow::RENDERBUFFER,
+            internal_format,
+            glow::NUM_SAMPLE_COUNTS,
+            &mut count,
+        )
+    };
+    let mut samples = vec![0; count[0].max(0) as usize];
+    if !samples.is_empty() {
+        unsafe {
+            gl.get_internal_format_i32_slice(
+                glow::RENDERBUFFER,
+                internal_format,
+                glow::SAMPLES,
+                &mut samples,
+            )
+        };
+    }
+    // Single sampling isn't listed, and the other counts are powers of two.
+    samples
+        .iter()
+        .filter(|&&count| count > 1 && count <= 64 && (count as u32).is_power_of_two())
+        .fold(1, |mask, &count| mask | count as hal::image::NumSamples)
+}
+
 fn get_u64(gl: &GlContainer, name: u32) -> Result<u64, Error> {
     let value = unsafe { gl.get_parameter_i32(name) };
     let err = Error::from_error_code(unsafe { gl.get_error() });
@@ -208,7 +240,6 @@ pub struct PrivateCaps {
     pub index_buffer_role_change: bool,
     pub buffer_storage: bool,
     pub image_storage: bool,
-    pub clear_buffer: bool,
     pub program_interface: bool,
     pub frag_data_location: bool,
     pub sync: bool,
@@ -229,6 +260,42 @@ pub struct PrivateCaps {
     pub get_tex_image: bool,
     /// Inserting memory barriers.
     pub memory_barrier: bool,
+    /// Making framebuffer writes visible to the texture fetches of the following draws.
+    pub texture_barrier: bool,
+    /// Debug groups, markers and object labels of `KHR_debug`.
+    pub debug_markers: bool,
+    /// Multisampled textures, as opposed to multisampled renderbuffers only.
+    pub multisampled_textures: bool,
+    /// Querying format capabilities with `glGetInternalformativ`.
+    pub internal_format_query: bool,
+    /// Querying the sample counts of formats with `glGetInternalformativ`.
+    pub sample_count_query: bool,
+    /// Toggling the sRGB encoding of framebuffer writes with `FRAMEBUFFER_SRGB`.
+    pub framebuffer_srgb: bool,
+    /// Toggling the sRGB decoding of texture reads with `TEXTURE_SRGB_DECODE_EXT`.
+    pub srgb_decode: bool,
+    /// Emulating uniform buffers with plain uniforms, set from a host copy
+    /// of the buffer contents.
+    pub emulate_uniform_buffers: bool,
+    /// Discarding the framebuffer contents with `glInvalidateFramebuffer`.
+    pub framebuffer_invalidation: bool,
+    /// Writing timestamps with `glQueryCounter`.
+    pub timestamp_query: bool,
+    /// Wrapping non-power-of-two textures with modes other than `CLAMP_TO_EDGE`.
+    pub npot_texture_wrap: bool,
+    /// Extension reporting the free video memory, if any.
+    pub memory_info: Option<MemoryInfoExtension>,
+    /// Workarounds for the bugs of the driver.
+    pub workarounds: Workarounds,
+}
+
+/// Extensions reporting the free video memory of the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemoryInfoExtension {
+    /// `GL_NVX_gpu_memory_info`, reporting the total and the available memory.
+    Nvx,
+    /// `GL_ATI_meminfo`, reporting the free memory of the texture pool.
+    Ati,
 }
 
 /// OpenGL implementation information
@@ -276,6 +343,115 @@ bitflags::bitflags! {
         const EXPLICIT_LAYOUTS_IN_SHADER = 0x00002000;
         /// Support instanced input rate on attribute binding.
         const INSTANCED_ATTRIBUTE_BINDING = 0x00004000;
+        /// Support clearing the attachments of a framebuffer one by one.
+        const CLEAR_BUFFER = 0x00008000;
+        /// Support 32-bit indices.
+        const INDEX_U32 = 0x00010000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Features of the API that the backend emulates, because the driver lacks them.
+    pub struct Emulations: u32 {
+        /// Vertex attributes are specified on the default vertex array before
+        /// every draw, without vertex array objects.
+        const VERTEX_ARRAY_OBJECTS = 0x00000001;
+        /// Uniform buffers are emulated with plain uniforms, set from a host copy
+        /// of the buffer contents at submission. They have to be bound to
+        /// CPU-visible memory, and only be written by the host.
+        const UNIFORM_BUFFERS = 0x00000002;
+        /// Sampler states are applied to the textures they're used with, without
+        /// sampler objects.
+        const SAMPLER_OBJECTS = 0x00000004;
+        /// Mapped memory is a host allocation, uploaded when flushed.
+        const BUFFER_MAPPING = 0x00000008;
+        /// Draws with a base instance offset the instanced vertex attributes.
+        const BASE_INSTANCE = 0x00000010;
+        /// Resources are bound to their units after linking, without explicit
+        /// bindings in the shaders.
+        const EXPLICIT_BINDINGS = 0x00000020;
+    }
+}
+
+bitflags::bitflags! {
+    /// Workarounds for the bugs of specific drivers.
+    ///
+    /// They're detected from the platform and the version of the driver, and can be
+    /// forced on or off with [`WorkaroundOverrides`], or with the `GFX_GL_WORKAROUNDS`
+    /// environment variable listing their names, prefixed with `-` to disable them,
+    /// e.g. `GFX_GL_WORKAROUNDS=NO_DEBUG_MARKERS,-NO_BUFFER_STORAGE`.
+    pub struct Workarounds: u32 {
+        /// Buffers are allocated with `glBufferData`, as if `glBufferStorage` was
+        /// missing. Their mappings are then emulated.
+        /// Detected on Mali drivers, where the persistent mappings are unreliable.
+        const NO_BUFFER_STORAGE = 0x00000001;
+        /// Debug groups, markers and object labels are not sent to the driver.
+        /// Not detected on any driver, but forcing it helps ruling them out when
+        /// a driver crashes.
+        const NO_DEBUG_MARKERS = 0x00000002;
+    }
+}
+
+/// Names of the workarounds in `GFX_GL_WORKAROUNDS`.
+const WORKAROUND_NAMES: &[(&str, Workarounds)] = &[
+    ("NO_BUFFER_STORAGE", Workarounds::NO_BUFFER_STORAGE),
+    ("NO_DEBUG_MARKERS", Workarounds::NO_DEBUG_MARKERS),
+];
+
+impl Workarounds {
+    /// Detect the workarounds needed by the driver, then apply the overrides
+    /// of the application, and the ones of the environment.
+    fn detect(info: &Info, overrides: WorkaroundOverrides) -> Self {
+        let mut workarounds = Workarounds::empty();
+        if info.platform_name.vendor == "ARM" && info.platform_name.renderer.starts_with("Mali") {
+            workarounds |= Workarounds::NO_BUFFER_STORAGE;
+        }
+        for overrides in [overrides, WorkaroundOverrides::from_env()].iter() {
+            workarounds |= overrides.enable;
+            workarounds -= overrides.disable;
+        }
+        workarounds
+    }
+}
+
+/// Workarounds enabled or disabled regardless of the driver.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct WorkaroundOverrides {
+    /// Workarounds enabled even on the drivers they aren't detected on.
+    pub enable: Workarounds,
+    /// Workarounds disabled even on the drivers they are detected on.
+    pub disable: Workarounds,
+}
+
+impl WorkaroundOverrides {
+    /// Parse the overrides of the `GFX_GL_WORKAROUNDS` environment variable.
+    fn from_env() -> Self {
+        let mut overrides = WorkaroundOverrides::default();
+        for (workaround, disable) in hal::env_flags("GFX_GL_WORKAROUNDS", WORKAROUND_NAMES) {
+            if disable {
+                overrides.disable |= workaround;
+            } else {
+                overrides.enable |= workaround;
+            }
+        }
+        overrides
+    }
+}
+
+/// Set of features an adapter is restricted to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Profile {
+    /// All the features provided by the driver.
+    Native,
+    /// Only the features of OpenGL ES 2.0 and its extensions, even when the driver
+    /// provides a newer version. This allows testing the paths of older targets
+    /// on recent hardware.
+    Es2,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Profile::Native
     }
 }
 
@@ -287,7 +463,7 @@ pub enum Requirement<'a> {
 }
 
 impl Info {
-    fn get(gl: &GlContainer) -> Info {
+    fn get(gl: &GlContainer, profile: Profile) -> Info {
         let platform_name = PlatformName::get(gl);
         let raw_version = get_string(gl, glow::VERSION).unwrap_or_default();
         let version = Version::parse(&raw_version).unwrap();
@@ -317,6 +493,16 @@ impl Info {
                 .collect()
         };
 
+        let (version, shading_language) = match profile {
+            Profile::Native => (version, shading_language),
+            // Drivers may create a newer context than the requested one, and the
+            // extensions it advertises are also available on older ones.
+            Profile::Es2 => (
+                Version::new_embedded(2, 0, version.vendor_info),
+                Version::new_embedded(1, 0, shading_language.vendor_info),
+            ),
+        };
+
         Info {
             platform_name,
             version,
@@ -349,7 +535,6 @@ impl Info {
     }
 
     pub fn is_supported(&self, requirements: &[Requirement]) -> bool {
-        use self::Requirement::*;
         requirements.iter().any(|r| match *r {
             Core(major, minor) => self.is_version_supported(major, minor),
             Es(major, minor) => self.is_embedded_version_supported(major, minor),
@@ -579,10 +764,139 @@ impl TextureFormatFilter {
     }
 }
 
+/// Features which only depend on the version and the extensions of the driver.
+/// Each of them is supported if any of its requirements is met.
+const FEATURE_REQUIREMENTS: &[(Features, &[Requirement<'static>])] = &[
+    (
+        Features::INSTANCE_RATE,
+        &[Core(3, 3), Es(3, 0), Ext("GL_ARB_instanced_arrays")],
+    ),
+    // TODO: extension
+    (Features::SAMPLER_MIP_LOD_BIAS, &[Core(3, 3)]),
+    (
+        Features::SAMPLER_BORDER_COLOR,
+        &[
+            Core(2, 1),
+            Es(3, 2),
+            Ext("GL_EXT_texture_border_clamp"),
+            Ext("GL_OES_texture_border_clamp"),
+        ],
+    ),
+    (
+        Features::SAMPLER_REDUCTION,
+        &[
+            Ext("GL_ARB_texture_filter_minmax"),
+            Ext("GL_EXT_texture_filter_minmax"),
+        ],
+    ),
+    (
+        Features::SAMPLER_MIRROR_CLAMP_EDGE,
+        &[Core(4, 4), Ext("GL_ARB_texture_mirror_clamp_to_edge")],
+    ),
+    (
+        Features::DEPTH_CLAMP,
+        &[
+            Core(3, 2),
+            Ext("GL_ARB_depth_clamp"),
+            Ext("GL_EXT_depth_clamp"),
+        ],
+    ),
+    (
+        Features::CONSERVATIVE_RASTERIZATION,
+        &[Ext("GL_NV_conservative_raster")],
+    ),
+    // Emulated with a texture per plane, which needs red-green formats.
+    (Features::MULTI_PLANAR_FORMATS, &[Core(3, 0), Es(3, 0)]),
+    (
+        Features::FORMAT_ETC2,
+        &[Core(4, 3), Es(3, 0), Ext("GL_ARB_ES3_compatibility")],
+    ),
+    (
+        Features::FORMAT_ASTC_LDR,
+        &[Es(3, 2), Ext("GL_KHR_texture_compression_astc_ldr")],
+    ),
+];
+
+/// Legacy features which only depend on the version and the extensions of the
+/// driver. Each of them is supported if any of its requirements is met.
+const LEGACY_FEATURE_REQUIREMENTS: &[(LegacyFeatures, &[Requirement<'static>])] = &[
+    (
+        LegacyFeatures::DRAW_INSTANCED,
+        &[Core(3, 1), Es(3, 0), Ext("GL_ARB_draw_instanced")],
+    ),
+    (
+        LegacyFeatures::DRAW_INSTANCED_BASE,
+        &[Core(4, 2), Ext("GL_ARB_base_instance")],
+    ),
+    // TODO: extension
+    (LegacyFeatures::DRAW_INDEXED_BASE, &[Core(3, 2)]),
+    // TODO: extension
+    (
+        LegacyFeatures::DRAW_INDEXED_INSTANCED,
+        &[Core(3, 1), Es(3, 0)],
+    ),
+    // TODO: extension
+    (
+        LegacyFeatures::DRAW_INDEXED_INSTANCED_BASE_VERTEX,
+        &[Core(3, 2)],
+    ),
+    (
+        LegacyFeatures::VERTEX_BASE,
+        &[
+            Core(3, 2),
+            Es(3, 2),
+            Ext("GL_ARB_draw_elements_base_vertex"),
+        ],
+    ),
+    (
+        LegacyFeatures::SRGB_COLOR,
+        &[
+            Core(3, 1),
+            Es(3, 0),
+            Ext("GL_ARB_framebuffer_sRGB"),
+            Ext("GL_EXT_sRGB"),
+        ],
+    ),
+    (
+        LegacyFeatures::CONSTANT_BUFFER,
+        &[Core(3, 1), Es(3, 0), Ext("GL_ARB_uniform_buffer_object")],
+    ),
+    // TODO: extension
+    (LegacyFeatures::UNORDERED_ACCESS_VIEW, &[Core(4, 0)]),
+    (
+        LegacyFeatures::COPY_BUFFER,
+        &[
+            Core(3, 1),
+            Es(3, 0),
+            Ext("GL_ARB_copy_buffer"),
+            Ext("GL_NV_copy_buffer"),
+        ],
+    ),
+    (
+        LegacyFeatures::SAMPLER_OBJECTS,
+        &[Core(3, 3), Es(3, 0), Ext("GL_ARB_sampler_objects")],
+    ),
+    (
+        LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER,
+        &[Core(4, 2), Es(3, 1)],
+    ),
+    (
+        LegacyFeatures::INSTANCED_ATTRIBUTE_BINDING,
+        &[Core(3, 3), Es(3, 0)],
+    ),
+    (LegacyFeatures::CLEAR_BUFFER, &[Core(3, 0), Es(3, 0)]),
+    (
+        LegacyFeatures::INDEX_U32,
+        &[Core(2, 0), Es(3, 0), Ext("GL_OES_element_index_uint")],
+    ),
+];
+
 /// Load the information pertaining to the driver and the corresponding device
 /// capabilities.
 pub(crate) fn query_all(
     gl: &GlContainer,
+    profile: Profile,
+    workaround_overrides: WorkaroundOverrides,
 ) -> (
     Info,
     Features,
@@ -591,51 +905,161 @@ pub(crate) fn query_all(
     PrivateCaps,
     TextureFormatFilter,
 ) {
-    use self::Requirement::*;
-    let info = Info::get(gl);
+    let info = Info::get(gl, profile);
+    let workarounds = Workarounds::detect(&info, workaround_overrides);
     let max_texture_size = get_usize(gl, glow::MAX_TEXTURE_SIZE).unwrap_or(64) as u32;
     let max_samples = get_usize(gl, glow::MAX_SAMPLES).unwrap_or(8);
+    // Sample counts are powers of two, each of them being a bit of the mask.
     let max_samples_mask = (max_samples * 2 - 1) as u8;
+    let sample_count_query =
+        info.is_supported(&[Core(4, 2), Es(3, 0), Ext("GL_ARB_internalformat_query")]);
+    let sample_counts = |internal_format| {
+        if sample_count_query {
+            get_sample_counts(gl, internal_format)
+        } else {
+            max_samples_mask
+        }
+    };
     let max_texel_elements = if crate::is_webgl() {
         0
     } else {
         get_usize(gl, glow::MAX_TEXTURE_BUFFER_SIZE).unwrap_or(0)
     };
+    let min_texel_buffer_offset_alignment = if max_texel_elements == 0 {
+        1
+    } else {
+        get_u64(gl, glow::TEXTURE_BUFFER_OFFSET_ALIGNMENT).unwrap_or(256)
+    };
+    // Arrays of textures are bound to consecutive texture units.
+    let max_texture_units = get_usize(gl, glow::MAX_TEXTURE_IMAGE_UNITS)
+        .unwrap_or(MAX_TEXTURE_SLOTS)
+        .min(MAX_TEXTURE_SLOTS) as u32;
+    let max_combined_texture_units = get_usize(gl, glow::MAX_COMBINED_TEXTURE_IMAGE_UNITS)
+        .unwrap_or(MAX_TEXTURE_SLOTS)
+        .min(MAX_TEXTURE_SLOTS) as u32;
     let min_storage_buffer_offset_alignment = if crate::is_webgl() {
         256
     } else {
         get_u64(gl, glow::SHADER_STORAGE_BUFFER_OFFSET_ALIGNMENT).unwrap_or(256)
     };
 
+    let max_image_array_layers = get_usize(gl, glow::MAX_ARRAY_TEXTURE_LAYERS).unwrap_or(1) as u16;
+    let max_renderbuffer_size =
+        get_usize(gl, glow::MAX_RENDERBUFFER_SIZE).unwrap_or(max_texture_size as usize) as u32;
+    let mut max_viewport_dimensions = [max_renderbuffer_size as i32; 2];
+    unsafe { gl.get_parameter_i32_slice(glow::MAX_VIEWPORT_DIMS, &mut max_viewport_dimensions) };
+    let max_vertex_attributes = get_usize(gl, glow::MAX_VERTEX_ATTRIBS).unwrap_or(16);
+    // The varyings are only counted in vectors before GL 3.2 and GL ES 3.0.
+    let (max_vertex_output_components, max_fragment_input_components) =
+        if info.is_supported(&[Core(3, 2), Es(3, 0)]) {
+            (
+                get_usize(gl, glow::MAX_VERTEX_OUTPUT_COMPONENTS).unwrap_or(64),
+                get_usize(gl, glow::MAX_FRAGMENT_INPUT_COMPONENTS).unwrap_or(60),
+            )
+        } else {
+            let components = get_usize(gl, glow::MAX_VARYING_VECTORS).unwrap_or(8) * 4;
+            (components, components)
+        };
+
     let mut limits = Limits {
         max_image_1d_size: max_texture_size,
         max_image_2d_size: max_texture_size,
-        max_image_3d_size: max_texture_size,
-        max_image_cube_size: max_texture_size,
-        max_image_array_layers: get_usize(gl, glow::MAX_ARRAY_TEXTURE_LAYERS).unwrap_or(1) as u16,
+        max_image_3d_size: get_usize(gl, glow::MAX_3D_TEXTURE_SIZE)
+            .unwrap_or(max_texture_size as usize) as u32,
+        max_image_cube_size: get_usize(gl, glow::MAX_CUBE_MAP_TEXTURE_SIZE)
+            .unwrap_or(max_texture_size as usize) as u32,
+        max_image_array_layers,
         max_texel_elements,
+        max_framebuffer_layers: max_image_array_layers as usize,
+        max_framebuffer_extent: hal::image::Extent {
+            width: max_renderbuffer_size,
+            height: max_renderbuffer_size,
+            depth: max_image_array_layers as u32,
+        },
+        max_viewport_dimensions: [
+            max_viewport_dimensions[0] as u32,
+            max_viewport_dimensions[1] as u32,
+        ],
+        max_vertex_input_attributes: max_vertex_attributes,
+        // Without vertex attribute bindings, each attribute is sourced from a buffer of its own.
+        max_vertex_input_bindings: max_vertex_attributes,
+        max_vertex_input_attribute_offset: 2047,
+        max_vertex_input_binding_stride: 2048,
+        max_vertex_output_components,
+        max_fragment_input_components,
+        max_fragment_output_attachments: get_usize(gl, glow::MAX_DRAW_BUFFERS).unwrap_or(1),
         max_viewports: 1,
         optimal_buffer_copy_offset_alignment: 1,
         optimal_buffer_copy_pitch_alignment: 1,
-        min_texel_buffer_offset_alignment: 1,
+        min_texel_buffer_offset_alignment,
         min_uniform_buffer_offset_alignment: get_u64(gl, glow::UNIFORM_BUFFER_OFFSET_ALIGNMENT)
             .unwrap_or(1024),
         min_storage_buffer_offset_alignment,
-        framebuffer_color_sample_counts: max_samples_mask,
+        framebuffer_color_sample_counts: sample_counts(glow::RGBA8),
+        framebuffer_depth_sample_counts: sample_counts(glow::DEPTH24_STENCIL8),
+        framebuffer_stencil_sample_counts: sample_counts(glow::DEPTH24_STENCIL8),
         non_coherent_atom_size: 1,
         max_color_attachments: get_usize(gl, glow::MAX_COLOR_ATTACHMENTS)
             .unwrap_or(1)
             .min(MAX_COLOR_ATTACHMENTS),
+        descriptor_limits: hal::DescriptorLimits {
+            max_per_stage_descriptor_samplers: MAX_SAMPLERS as u32,
+            max_per_stage_descriptor_sampled_images: max_texture_units,
+            max_descriptor_set_samplers: MAX_SAMPLERS as u32,
+            max_descriptor_set_sampled_images: max_combined_texture_units,
+            ..hal::DescriptorLimits::default()
+        },
         ..Limits::default()
     };
 
-    if info.is_supported(&[Core(4, 0), Ext("GL_ARB_tessellation_shader")]) {
+    if info.is_supported(&[
+        Core(4, 0),
+        Es(3, 2),
+        Ext("GL_ARB_tessellation_shader"),
+        Ext("GL_EXT_tessellation_shader"),
+    ]) {
         limits.max_patch_size = get_usize(gl, glow::MAX_PATCH_VERTICES).unwrap_or(0) as _;
     }
     if info.is_supported(&[Core(4, 1)]) {
         // TODO: extension
         limits.max_viewports = get_usize(gl, glow::MAX_VIEWPORTS).unwrap_or(0);
     }
+    if info.is_supported(&[Core(4, 4), Es(3, 1)]) {
+        limits.max_vertex_input_attribute_offset =
+            get_usize(gl, glow::MAX_VERTEX_ATTRIB_RELATIVE_OFFSET).unwrap_or(2047);
+        limits.max_vertex_input_binding_stride =
+            get_usize(gl, glow::MAX_VERTEX_ATTRIB_STRIDE).unwrap_or(2048);
+    }
+    if !info.version.is_embedded {
+        limits.max_sampler_lod_bias = get_usize(gl, glow::MAX_TEXTURE_LOD_BIAS).unwrap_or(0) as f32;
+    }
+    if info.is_supported(&[
+        Core(4, 3),
+        Es(3, 1),
+        Ext("GL_ARB_shader_storage_buffer_object"),
+    ]) {
+        let max_blocks = get_usize(gl, glow::MAX_FRAGMENT_SHADER_STORAGE_BLOCKS)
+            .unwrap_or(0)
+            .min(get_usize(gl, glow::MAX_COMPUTE_SHADER_STORAGE_BLOCKS).unwrap_or(0));
+        limits.max_storage_buffer_range =
+            get_u64(gl, glow::MAX_SHADER_STORAGE_BLOCK_SIZE).unwrap_or(1 << 27);
+        limits
+            .descriptor_limits
+            .max_per_stage_descriptor_storage_buffers = max_blocks as u32;
+        limits.descriptor_limits.max_descriptor_set_storage_buffers =
+            get_usize(gl, glow::MAX_SHADER_STORAGE_BUFFER_BINDINGS).unwrap_or(0) as u32;
+    }
+    if info.is_supported(&[Ext("GL_OVR_multiview2")]) {
+        limits.max_multiview_view_count = get_usize(gl, glow::MAX_VIEWS_OVR).unwrap_or(0) as _;
+    }
+    // WebGL only exposes the elapsed time queries, not the timestamps.
+    let timestamp_query = !crate::is_webgl()
+        && info.is_supported(&[
+            Core(3, 3),
+            Ext("GL_ARB_timer_query"),
+            Ext("GL_EXT_disjoint_timer_query"),
+        ]);
+    limits.timestamp_compute_and_graphics = timestamp_query;
 
     //TODO: technically compute is exposed in Es(3, 1), but GLES requires 3.2
     // for any storage buffers. We need to investigate if this requirement
@@ -654,6 +1078,10 @@ pub(crate) fn query_all(
                     gl.get_parameter_indexed_i32(glow::MAX_COMPUTE_WORK_GROUP_SIZE, i as _) as u32;
             }
         }
+        limits.max_compute_work_group_invocations =
+            get_usize(gl, glow::MAX_COMPUTE_WORK_GROUP_INVOCATIONS).unwrap_or(128);
+        limits.max_compute_shared_memory_size =
+            get_usize(gl, glow::MAX_COMPUTE_SHARED_MEMORY_SIZE).unwrap_or(16384);
     }
 
     let mut features = Features::NDC_Y_UP | Features::MUTABLE_COMPARISON_SAMPLER;
@@ -662,93 +1090,122 @@ pub(crate) fn query_all(
     // TODO: Merge downlevel/legacy features?
     let mut legacy = LegacyFeatures::empty();
 
+    for &(feature, requirements) in FEATURE_REQUIREMENTS {
+        if info.is_supported(requirements) {
+            features |= feature;
+        }
+    }
+
     if info.is_supported(&[
         Core(4, 6),
         Ext("GL_ARB_texture_filter_anisotropic"),
         Ext("GL_EXT_texture_filter_anisotropic"),
     ]) {
         features |= Features::SAMPLER_ANISOTROPY;
+        limits.max_sampler_anisotropy =
+            get_usize(gl, glow::MAX_TEXTURE_MAX_ANISOTROPY).unwrap_or(1) as f32;
     }
-    if info.is_supported(&[Core(4, 2), Es(3, 1)]) {
-        legacy |= LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER;
+    // Naga can't generate tessellation shaders, so they need SPIRV-Cross.
+    if cfg!(feature = "cross") && limits.max_patch_size != 0 {
+        features |= Features::TESSELLATION_SHADER;
     }
-    if info.is_supported(&[Core(3, 3), Es(3, 0), Ext("GL_ARB_instanced_arrays")]) {
-        features |= Features::INSTANCE_RATE;
+    // Same for geometry shaders, which WebGL doesn't have at all.
+    if cfg!(feature = "cross")
+        && info.is_supported(&[
+            Core(3, 2),
+            Es(3, 2),
+            Ext("GL_EXT_geometry_shader"),
+            Ext("GL_OES_geometry_shader"),
+        ])
+        && !crate::is_webgl()
+    {
+        features |= Features::GEOMETRY_SHADER;
     }
-    if info.is_supported(&[Core(3, 3)]) {
-        // TODO: extension
-        features |= Features::SAMPLER_MIP_LOD_BIAS;
+    // SPIRV-Cross requires the extensions when the vertex or tessellation shaders
+    // write `gl_ViewportIndex` or `gl_Layer`.
+    if cfg!(feature = "cross") {
+        if info.is_supported(&[
+            Ext("GL_ARB_shader_viewport_layer_array"),
+            Ext("GL_NV_viewport_array2"),
+        ]) {
+            features |= Features::SHADER_OUTPUT_VIEWPORT_INDEX | Features::SHADER_OUTPUT_LAYER;
+        } else {
+            if info.is_supported(&[Ext("GL_AMD_vertex_shader_viewport_index")]) {
+                features |= Features::SHADER_OUTPUT_VIEWPORT_INDEX;
+            }
+            if info.is_supported(&[Ext("GL_AMD_vertex_shader_layer")]) {
+                features |= Features::SHADER_OUTPUT_LAYER;
+            }
+        }
+    }
+    // Drawing the captured vertices back is reported once its entry points are loaded.
+    if info.is_supported(&[Core(3, 0), Es(3, 0)]) {
+        features |= Features::TRANSFORM_FEEDBACK;
     }
-    if info.is_supported(&[Core(2, 1)]) {
-        features |= Features::SAMPLER_BORDER_COLOR;
+    // Arrays of textures are only reflected from the GLSL generated by SPIRV-Cross.
+    // Their length is bounded by `max_per_stage_descriptor_sampled_images`, which
+    // also applies to the variable count bindings, always allocated whole.
+    if cfg!(feature = "cross") {
+        features |=
+            Features::TEXTURE_DESCRIPTOR_ARRAY | Features::DESCRIPTOR_BINDING_VARIABLE_COUNT;
     }
-    if info.is_supported(&[Core(4, 4), Ext("ARB_texture_mirror_clamp_to_edge")]) {
-        features |= Features::SAMPLER_MIRROR_CLAMP_EDGE;
+    // Only the written descriptors are bound. The updates after binding aren't
+    // supported, since the descriptors are resolved into units when binding.
+    features |= Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND;
+    // The view count of vertex shaders is declared in the GLSL generated by SPIRV-Cross.
+    if cfg!(feature = "cross") && limits.max_multiview_view_count > 1 {
+        features |= Features::MULTIVIEW;
     }
     if info.is_supported(&[Core(4, 0), Es(3, 2), Ext("GL_EXT_draw_buffers2")]) && !crate::is_webgl()
     {
         features |= Features::INDEPENDENT_BLENDING;
     }
+    if info.is_supported(&[Ext("GL_EXT_texture_compression_s3tc")])
+        && info.is_supported(&[
+            Core(3, 0),
+            Ext("GL_ARB_texture_compression_rgtc"),
+            Ext("GL_EXT_texture_compression_rgtc"),
+        ])
+        && info.is_supported(&[
+            Core(4, 2),
+            Ext("GL_ARB_texture_compression_bptc"),
+            Ext("GL_EXT_texture_compression_bptc"),
+        ])
+    {
+        features |= Features::FORMAT_BC;
+    }
 
     // TODO
     if false && info.is_supported(&[Core(4, 3), Es(3, 1)]) {
         // TODO: extension
         legacy |= LegacyFeatures::INDIRECT_EXECUTION;
     }
-    if info.is_supported(&[Core(3, 1), Es(3, 0), Ext("GL_ARB_draw_instanced")]) {
-        legacy |= LegacyFeatures::DRAW_INSTANCED;
-    }
-    if info.is_supported(&[Core(4, 2), Ext("GL_ARB_base_instance")]) {
-        legacy |= LegacyFeatures::DRAW_INSTANCED_BASE;
-    }
-    if info.is_supported(&[Core(3, 2)]) {
-        // TODO: extension
-        legacy |= LegacyFeatures::DRAW_INDEXED_BASE;
-    }
-    if info.is_supported(&[Core(3, 1), Es(3, 0)]) {
-        // TODO: extension
-        legacy |= LegacyFeatures::DRAW_INDEXED_INSTANCED;
-    }
-    if info.is_supported(&[Core(3, 2)]) {
-        // TODO: extension
-        legacy |= LegacyFeatures::DRAW_INDEXED_INSTANCED_BASE_VERTEX;
-    }
-    if info.is_supported(&[
-        Core(3, 2),
-        Es(3, 2),
-        Ext("GL_ARB_draw_elements_base_vertex"),
-    ]) {
-        legacy |= LegacyFeatures::VERTEX_BASE;
-    }
-    if info.is_supported(&[
-        Core(3, 1),
-        Es(3, 0),
-        Ext("GL_ARB_framebuffer_sRGB"),
-        Ext("GL_EXT_sRGB"),
-    ]) {
-        legacy |= LegacyFeatures::SRGB_COLOR;
-    }
-    if info.is_supported(&[Core(3, 1), Es(3, 0), Ext("GL_ARB_uniform_buffer_object")]) {
-        legacy |= LegacyFeatures::CONSTANT_BUFFER;
-    }
-    if info.is_supported(&[Core(4, 0)]) {
-        // TODO: extension
-        legacy |= LegacyFeatures::UNORDERED_ACCESS_VIEW;
-    }
-    if info.is_supported(&[
-        Core(3, 1),
-        Es(3, 0),
-        Ext("GL_ARB_copy_buffer"),
-        Ext("GL_NV_copy_buffer"),
-    ]) {
-        legacy |= LegacyFeatures::COPY_BUFFER;
-    }
-    if info.is_supported(&[Core(3, 3), Es(3, 0), Ext("GL_ARB_sampler_objects")]) {
-        legacy |= LegacyFeatures::SAMPLER_OBJECTS;
+    for &(feature, requirements) in LEGACY_FEATURE_REQUIREMENTS {
+        if info.is_supported(requirements) {
+            legacy |= feature;
+        }
     }
-    if info.is_supported(&[Core(3, 3), Es(3, 0)]) {
-        legacy |= LegacyFeatures::INSTANCED_ATTRIBUTE_BINDING;
+
+    if legacy.contains(LegacyFeatures::CONSTANT_BUFFER) {
+        let max_blocks = get_usize(gl, glow::MAX_VERTEX_UNIFORM_BLOCKS)
+            .unwrap_or(12)
+            .min(get_usize(gl, glow::MAX_FRAGMENT_UNIFORM_BLOCKS).unwrap_or(12));
+        limits.max_uniform_buffer_range =
+            get_u64(gl, glow::MAX_UNIFORM_BLOCK_SIZE).unwrap_or(16384);
+        limits
+            .descriptor_limits
+            .max_per_stage_descriptor_uniform_buffers = max_blocks as u32;
+        limits.descriptor_limits.max_descriptor_set_uniform_buffers =
+            get_usize(gl, glow::MAX_COMBINED_UNIFORM_BLOCKS).unwrap_or(24) as u32;
     }
+    limits.max_draw_indexed_index_value = if !legacy.contains(LegacyFeatures::INDEX_U32) {
+        u16::MAX as u32
+    } else if info.is_supported(&[Core(4, 3), Es(3, 0)]) {
+        // The maximum is a 64-bit value, which `glGetIntegerv` clamps to `i32::MAX`.
+        get_usize(gl, glow::MAX_ELEMENT_INDEX).map_or(!0, |max| max as u32)
+    } else {
+        !0
+    };
 
     let mut performance_caveats = PerformanceCaveats::empty();
     //TODO: extension
@@ -758,7 +1215,12 @@ pub(crate) fn query_all(
     let properties = PhysicalDeviceProperties {
         limits,
         performance_caveats,
-        dynamic_pipeline_states: DynamicStates::all(),
+        dynamic_pipeline_states: DynamicStates::VIEWPORT
+            | DynamicStates::SCISSOR
+            | DynamicStates::LINE_WIDTH
+            | DynamicStates::BLEND_CONSTANTS
+            | DynamicStates::DEPTH_BIAS
+            | DynamicStates::STENCIL_REFERENCE,
         ..PhysicalDeviceProperties::default()
     };
 
@@ -766,7 +1228,7 @@ pub(crate) fn query_all(
         Core(4, 4),
         Ext("GL_ARB_buffer_storage"),
         Ext("GL_EXT_buffer_storage"),
-    ]);
+    ]) && !workarounds.contains(Workarounds::NO_BUFFER_STORAGE);
     // See https://github.com/gfx-rs/gfx/issues/3453
     let emulate_map = crate::is_webgl() || !buffer_storage;
 
@@ -780,7 +1242,6 @@ pub(crate) fn query_all(
         index_buffer_role_change: info.is_supported(&[Core(2, 0), Es(2, 0)]) && !crate::is_webgl(),
         image_storage: info.is_supported(&[Core(4, 2), Es(3, 0), Ext("GL_ARB_texture_storage")]),
         buffer_storage,
-        clear_buffer: info.is_supported(&[Core(3, 0), Es(3, 0)]),
         program_interface: info.is_supported(&[Core(4, 3), Ext("GL_ARB_program_interface_query")]),
         frag_data_location: !info.version.is_embedded,
         sync: info.is_supported(&[Core(3, 2), Es(3, 0), Ext("GL_ARB_sync")]), // TODO
@@ -790,6 +1251,48 @@ pub(crate) fn query_all(
         per_slot_color_mask: info.is_supported(&[Core(3, 0)]),
         get_tex_image: !info.version.is_embedded,
         memory_barrier: info.is_supported(&[Core(4, 2), Es(3, 1)]),
+        texture_barrier: info.is_supported(&[
+            Core(4, 5),
+            Ext("GL_ARB_texture_barrier"),
+            Ext("GL_NV_texture_barrier"),
+        ]),
+        debug_markers: !crate::is_webgl()
+            && info.is_supported(&[Core(4, 3), Es(3, 2), Ext("GL_KHR_debug")])
+            && !workarounds.contains(Workarounds::NO_DEBUG_MARKERS),
+        multisampled_textures: info.is_supported(&[
+            Core(4, 3),
+            Es(3, 1),
+            Ext("GL_ARB_texture_storage_multisample"),
+        ]),
+        internal_format_query: info
+            .is_supported(&[Core(4, 3), Ext("GL_ARB_internalformat_query2")]),
+        sample_count_query,
+        framebuffer_srgb: info.is_supported(&[
+            Core(3, 0),
+            Ext("GL_ARB_framebuffer_sRGB"),
+            Ext("GL_EXT_sRGB_write_control"),
+        ]),
+        srgb_decode: info.is_supported(&[Ext("GL_EXT_texture_sRGB_decode")]),
+        // The uniform blocks are flattened into plain uniforms by SPIRV-Cross,
+        // and read from the host allocation of the emulated mappings.
+        emulate_uniform_buffers: !legacy.contains(LegacyFeatures::CONSTANT_BUFFER)
+            && emulate_map
+            && cfg!(feature = "cross"),
+        framebuffer_invalidation: info.is_supported(&[
+            Core(4, 3),
+            Es(3, 0),
+            Ext("GL_ARB_invalidate_subdata"),
+        ]),
+        timestamp_query,
+        npot_texture_wrap: info.is_supported(&[Core(2, 0), Es(3, 0), Ext("GL_OES_texture_npot")]),
+        memory_info: if info.is_supported(&[Ext("GL_NVX_gpu_memory_info")]) {
+            Some(MemoryInfoExtension::Nvx)
+        } else if info.is_supported(&[Ext("GL_ATI_meminfo")]) {
+            Some(MemoryInfoExtension::Ati)
+        } else {
+            None
+        },
+        workarounds,
     };
 
     let filter = if info.is_supported(&[Es(3, 0)]) {
@@ -808,6 +1311,30 @@ pub(crate) fn query_all(
     (info, features, legacy, properties, private, filter)
 }
 
+/// Collect the features emulated by the backend for the given capabilities.
+pub(crate) fn emulations(legacy: LegacyFeatures, private: &PrivateCaps) -> Emulations {
+    let mut emulations = Emulations::empty();
+    if !private.vertex_array {
+        emulations |= Emulations::VERTEX_ARRAY_OBJECTS;
+    }
+    if private.emulate_uniform_buffers {
+        emulations |= Emulations::UNIFORM_BUFFERS;
+    }
+    if !legacy.contains(LegacyFeatures::SAMPLER_OBJECTS) {
+        emulations |= Emulations::SAMPLER_OBJECTS;
+    }
+    if private.emulate_map {
+        emulations |= Emulations::BUFFER_MAPPING;
+    }
+    if !legacy.contains(LegacyFeatures::DRAW_INSTANCED_BASE) {
+        emulations |= Emulations::BASE_INSTANCE;
+    }
+    if !legacy.contains(LegacyFeatures::EXPLICIT_LAYOUTS_IN_SHADER) {
+        emulations |= Emulations::EXPLICIT_BINDINGS;
+    }
+    emulations
+}
+
 #[cfg(test)]
 mod tests {
     use super::Version;
@@ -23,7 +23,7 @@ will be minimal, if any.
 #![allow(missing_docs, missing_copy_implementations)]
 
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     collections::HashMap,
     fmt,
     hash::BuildHasherDefault,
@@ -34,17 +34,28 @@ use std::{
 
 use hal::{adapter, buffer, display, image, memory, queue as q};
 
+pub use self::command::CommandStorageStats;
 pub use self::device::Device;
-pub use self::info::{Info, PlatformName, Version};
+pub use self::info::{
+    Emulations, Info, PlatformName, Profile, Version, WorkaroundOverrides, Workarounds,
+};
+pub use self::queue::{BaseInstanceStats, SubmissionError};
 
 mod command;
 mod conv;
 mod device;
+mod gpu_validation;
 mod info;
+mod memory_object;
 mod native;
+mod polygon_offset;
 mod pool;
 mod queue;
+mod robustness;
 mod state;
+mod texture_view;
+mod transform_feedback;
+mod translate;
 mod window;
 
 // Web implementation
@@ -52,7 +63,7 @@ mod window;
 pub use window::web::{Instance, Surface, Swapchain};
 
 #[cfg(not(target_arch = "wasm32"))]
-pub use window::egl::{Instance, Surface, Swapchain};
+pub use window::egl::{Instance, PresentTimings, Surface, Swapchain, VsyncCoordination};
 
 pub use glow::Context as GlContext;
 use glow::HasContext;
@@ -107,6 +118,7 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = native::ComputePipeline;
     type GraphicsPipeline = native::GraphicsPipeline;
+    type RayTracingPipeline = ();
     type PipelineLayout = native::PipelineLayout;
     type PipelineCache = ();
     type DescriptorSetLayout = native::DescriptorSetLayout;
@@ -116,7 +128,8 @@ impl hal::Backend for Backend {
     type Fence = native::Fence;
     type Semaphore = native::Semaphore;
     type Event = ();
-    type QueryPool = ();
+    type QueryPool = native::QueryPool;
+    type AccelerationStructure = ();
 
     type Display = ();
     type DisplayMode = ();
@@ -130,6 +143,7 @@ pub enum Error {
     InvalidOperation,
     InvalidFramebufferOperation,
     OutOfMemory,
+    ContextLost,
     UnknownError,
 }
 
@@ -142,11 +156,26 @@ impl Error {
             glow::INVALID_OPERATION => Error::InvalidOperation,
             glow::INVALID_FRAMEBUFFER_OPERATION => Error::InvalidFramebufferOperation,
             glow::OUT_OF_MEMORY => Error::OutOfMemory,
+            glow::CONTEXT_LOST | 0x9242 /*CONTEXT_LOST_WEBGL*/ => Error::ContextLost,
             _ => Error::UnknownError,
         }
     }
 }
 
+/// Options of the debug output of the driver, see [`PhysicalDevice::set_debug_output`].
+#[derive(Clone, Debug, Default)]
+pub struct DebugOutput {
+    /// IDs of the messages to ignore, such as the notifications a driver
+    /// repeats about the placement of the buffers.
+    pub ignored_ids: Vec<u32>,
+    /// Panic on the next check of the errors of the device after the driver
+    /// reported an error, instead of only logging it.
+    ///
+    /// The output is made synchronous, so that the logged message immediately
+    /// follows the call it's about.
+    pub panic_on_error: bool,
+}
+
 fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, message: &str) {
     let source_str = match source {
         glow::DEBUG_SOURCE_API => "API",
@@ -155,7 +184,7 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
         glow::DEBUG_SOURCE_THIRD_PARTY => "Third Party",
         glow::DEBUG_SOURCE_APPLICATION => "Application",
         glow::DEBUG_SOURCE_OTHER => "Other",
-        _ => unreachable!(),
+        _ => "Unknown",
     };
 
     let log_severity = match severity {
@@ -163,7 +192,7 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
         glow::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
         glow::DEBUG_SEVERITY_LOW => log::Level::Info,
         glow::DEBUG_SEVERITY_NOTIFICATION => log::Level::Trace,
-        _ => unreachable!(),
+        _ => log::Level::Debug,
     };
 
     let type_str = match gltype {
@@ -176,7 +205,7 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
         glow::DEBUG_TYPE_PORTABILITY => "Portability",
         glow::DEBUG_TYPE_PUSH_GROUP => "Push Group",
         glow::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "Undefined Behavior",
-        _ => unreachable!(),
+        _ => "Unknown",
     };
 
     log::log!(
@@ -192,6 +221,20 @@ fn debug_message_callback(source: u32, gltype: u32, id: u32, severity: u32, mess
 const DEVICE_LOCAL_HEAP: usize = 0;
 const CPU_VISIBLE_HEAP: usize = 1;
 
+/// Heaps of the memory types, indexed by `DEVICE_LOCAL_HEAP` and `CPU_VISIBLE_HEAP`.
+fn memory_heaps() -> Vec<adapter::MemoryHeap> {
+    vec![
+        adapter::MemoryHeap {
+            size: !0,
+            flags: memory::HeapFlags::DEVICE_LOCAL,
+        },
+        adapter::MemoryHeap {
+            size: !0,
+            flags: memory::HeapFlags::empty(),
+        },
+    ]
+}
+
 /// Memory types in the OpenGL backend are either usable for buffers and are backed by a real OpenGL
 /// buffer, or are used for images and are fake and not backed by any real raw buffer.
 #[derive(Copy, Clone, Debug)]
@@ -211,13 +254,45 @@ struct Share {
     // Indicates if there is an active logical device.
     open: Cell<bool>,
     memory_types: Vec<(adapter::MemoryType, MemoryUsage)>,
+    // Bytes allocated in each heap, reported when the driver can't be queried.
+    memory_usage: memory::HeapUsageTracker,
+    // Entry points importing external memory, if supported.
+    memory_object: Option<memory_object::MemoryObjectFns>,
+    egl_image: Option<memory_object::EglImageFns>,
+    // Entry points importing DMA-BUF images through EGL, if supported.
+    dma_buf: Option<memory_object::DmaBufFns>,
+    // Entry point querying the GPU resets, if supported.
+    reset_status: Option<robustness::ResetStatusFns>,
+    // Entry point clamping the depth bias, if supported.
+    polygon_offset_clamp: Option<polygon_offset::PolygonOffsetClampFns>,
+    // Entry points drawing the vertices captured by transform feedback, if supported.
+    draw_transform_feedback: Option<transform_feedback::DrawTransformFeedbackFns>,
+    // Entry point creating texture views, if supported.
+    texture_view: Option<texture_view::TextureViewFns>,
+    // Checks of the instrumented shaders, with GPU validation.
+    gpu_validation: Option<gpu_validation::GpuValidation>,
+    // Set once a reset is reported, as it's only reported once.
+    context_lost: Cell<bool>,
     texture_format_filter: info::TextureFormatFilter,
+    /// Options of the instance the context was created with.
+    instance_flags: hal::InstanceFlags,
+    /// Options of the debug output, installed when the device is opened.
+    debug_output: RefCell<DebugOutput>,
+    /// First error reported by the debug output since the last check,
+    /// with `DebugOutput::panic_on_error`.
+    debug_error: Arc<parking_lot::Mutex<Option<String>>>,
 }
 
 impl Share {
-    /// Fails during a debug build if the implementation's error flag was set.
+    /// Fails if the implementation's error flag was set, when the instance
+    /// validates the API usage.
+    ///
+    /// Panics if the debug output reported an error, with `DebugOutput::panic_on_error`.
     fn check(&self) -> Result<(), Error> {
-        if cfg!(debug_assertions) {
+        if let Some(message) = self.debug_error.lock().take() {
+            panic!("Error reported by the driver: {}", message);
+        }
+        if self.instance_flags.contains(hal::InstanceFlags::VALIDATION) {
             let gl = &self.context;
             let err = Error::from_error_code(unsafe { gl.get_error() });
             if err != Error::NoError {
@@ -227,9 +302,195 @@ impl Share {
         Ok(())
     }
 
+    /// Query the errors that the application can't prevent: running out of
+    /// memory, or losing the context after a GPU reset.
+    ///
+    /// Unlike `check`, this is also done in release builds. The resets are
+    /// queried with `glGetGraphicsResetStatus` when robustness is supported.
+    fn device_status(&self) -> Result<(), hal::device::OomOrDeviceLost> {
+        if self.context_lost.get() {
+            return Err(hal::device::DeviceLost.into());
+        }
+        if let Some(ref reset_status) = self.reset_status {
+            if unsafe { reset_status.is_reset() } {
+                self.context_lost.set(true);
+                return Err(hal::device::DeviceLost.into());
+            }
+        }
+        let gl = &self.context;
+        match Error::from_error_code(unsafe { gl.get_error() }) {
+            Error::NoError => Ok(()),
+            Error::OutOfMemory => Err(hal::device::OutOfMemory::Device.into()),
+            Error::ContextLost => {
+                self.context_lost.set(true);
+                Err(hal::device::DeviceLost.into())
+            }
+            other => {
+                log::error!("Unexpected error {:?}", other);
+                Ok(())
+            }
+        }
+    }
+
+    /// Check if the compression scheme of the format, if any, is supported.
+    fn supports_compression(&self, format: hal::format::Format) -> bool {
+        match conv::compression_feature(format.base_format().0) {
+            Some(feature) => self.supported_features.contains(feature),
+            None => true,
+        }
+    }
+
+    fn format_properties(&self, format: Option<hal::format::Format>) -> hal::format::Properties {
+        use hal::format::{BufferFeature as Bf, ImageFeature as If};
+
+        let mut buffer_features = Bf::VERTEX;
+        let mut optimal_tiling = If::TRANSFER_SRC | If::TRANSFER_DST | If::SAMPLED;
+        if let Some(format) = format {
+            if format.surface_desc().is_compressed() {
+                return hal::format::Properties {
+                    optimal_tiling: if self.supports_compression(format) {
+                        If::TRANSFER_DST | If::SAMPLED | If::SAMPLED_LINEAR
+                    } else {
+                        If::empty()
+                    },
+                    ..hal::format::Properties::default()
+                };
+            }
+            if format.is_planar() {
+                // Planes are separate textures, so they can be copied and viewed
+                // individually, but not sampled or blitted as a whole.
+                return hal::format::Properties {
+                    optimal_tiling: If::TRANSFER_SRC | If::TRANSFER_DST,
+                    ..hal::format::Properties::default()
+                };
+            }
+            if let Some(desc) = conv::describe_format(format) {
+                if self.private_caps.internal_format_query {
+                    return self.query_format_properties(format, desc.tex_internal);
+                }
+                if self.supports_texel_buffers() && conv::is_texel_buffer_format(format) {
+                    buffer_features |= Bf::UNIFORM_TEXEL;
+                }
+            }
+            // Blits are done with `glBlitFramebuffer`, which attaches both images.
+            if conv::is_renderable(format) {
+                optimal_tiling |= If::BLIT_SRC | If::BLIT_DST;
+            }
+        }
+
+        hal::format::Properties {
+            linear_tiling: If::TRANSFER_SRC | If::TRANSFER_DST,
+            optimal_tiling,
+            buffer_features,
+            drm_format_properties: Vec::new(),
+        }
+    }
+
+    /// Get the sample counts an image of this format and usage can be created with.
+    fn sample_count_mask(
+        &self,
+        format: hal::format::Format,
+        internal_format: u32,
+        usage: image::Usage,
+    ) -> image::NumSamples {
+        // Multisampled images are renderbuffers, unless they have to be
+        // sampled from, which requires multisampled textures.
+        if usage.intersects(image::Usage::SAMPLED | image::Usage::STORAGE)
+            && !self.private_caps.multisampled_textures
+        {
+            1
+        } else if self.private_caps.sample_count_query {
+            info::get_sample_counts(&self.context, internal_format)
+        } else if !conv::is_renderable(format) {
+            1
+        } else if format.is_depth() || format.is_stencil() {
+            self.public_caps.limits.framebuffer_depth_sample_counts
+        } else {
+            self.public_caps.limits.framebuffer_color_sample_counts
+        }
+    }
+
+    /// Check if buffer views can be created, to be bound as texel buffers.
+    fn supports_texel_buffers(&self) -> bool {
+        self.public_caps.limits.max_texel_elements != 0
+    }
+
+    /// Ask the driver about the capabilities of an internal format.
+    fn query_format_properties(
+        &self,
+        format: hal::format::Format,
+        internal_format: u32,
+    ) -> hal::format::Properties {
+        use hal::format::{Aspects, BufferFeature as Bf, ImageFeature as If};
+
+        let gl = &self.context;
+        // All the queried values are either `GL_TRUE`/`GL_FALSE`,
+        // or `GL_FULL_SUPPORT`/`GL_CAVEAT_SUPPORT`/`GL_NONE`.
+        let supports = |pname| {
+            let mut result = [0];
+            unsafe {
+                gl.get_internal_format_i32_slice(
+                    glow::TEXTURE_2D,
+                    internal_format,
+                    pname,
+                    &mut result,
+                )
+            };
+            result[0] != 0
+        };
+
+        if !supports(glow::INTERNALFORMAT_SUPPORTED) {
+            return hal::format::Properties::default();
+        }
+
+        let mut optimal_tiling = If::TRANSFER_SRC | If::TRANSFER_DST | If::SAMPLED;
+        if supports(glow::FILTER) {
+            optimal_tiling |= If::SAMPLED_LINEAR;
+        }
+        if supports(glow::FRAMEBUFFER_RENDERABLE) {
+            // Blits are done with `glBlitFramebuffer`, which attaches both images.
+            optimal_tiling |= If::BLIT_SRC | If::BLIT_DST;
+            if format.surface_desc().aspects.contains(Aspects::COLOR) {
+                optimal_tiling |= If::COLOR_ATTACHMENT;
+                if supports(glow::FRAMEBUFFER_BLEND) {
+                    optimal_tiling |= If::COLOR_ATTACHMENT_BLEND;
+                }
+            } else {
+                optimal_tiling |= If::DEPTH_STENCIL_ATTACHMENT;
+            }
+        }
+        let mut buffer_features = Bf::VERTEX;
+        let texel_buffers = self.supports_texel_buffers() && conv::is_texel_buffer_format(format);
+        if supports(glow::SHADER_IMAGE_STORE) {
+            optimal_tiling |= If::STORAGE;
+            if texel_buffers {
+                buffer_features |= Bf::STORAGE_TEXEL;
+            }
+        }
+        if texel_buffers {
+            buffer_features |= Bf::UNIFORM_TEXEL;
+        }
+
+        hal::format::Properties {
+            linear_tiling: If::TRANSFER_SRC | If::TRANSFER_DST,
+            optimal_tiling,
+            buffer_features,
+            drm_format_properties: Vec::new(),
+        }
+    }
+
     fn buffer_memory_type_mask(&self, usage: buffer::Usage) -> u32 {
         let mut type_mask = 0;
-        for (type_index, &(_, kind)) in self.memory_types.iter().enumerate() {
+        for (type_index, &(ref memory_type, kind)) in self.memory_types.iter().enumerate() {
+            // Emulated uniform buffers are read from the host copy of their memory.
+            if usage.contains(buffer::Usage::UNIFORM)
+                && self.private_caps.emulate_uniform_buffers
+                && !memory_type
+                    .properties
+                    .contains(memory::Properties::CPU_VISIBLE)
+            {
+                continue;
+            }
             match kind {
                 MemoryUsage::Buffer(buffer_usage) => {
                     if buffer_usage.contains(usage) {
@@ -354,23 +615,68 @@ unsafe impl<T: ?Sized> Sync for Wstarc<T> {}
 pub struct PhysicalDevice(Starc<Share>);
 
 impl PhysicalDevice {
-    fn new_adapter(context: GlContext) -> adapter::Adapter<Backend> {
+    fn new_adapter(
+        context: GlContext,
+        profile: info::Profile,
+        workaround_overrides: info::WorkaroundOverrides,
+        instance_flags: hal::InstanceFlags,
+        loader: Option<&dyn Fn(&str) -> *const std::os::raw::c_void>,
+        dma_buf: Option<memory_object::DmaBufFns>,
+    ) -> adapter::Adapter<Backend> {
         let gl = GlContainer { context };
         // query information
         let (
             info,
-            supported_features,
+            mut supported_features,
             legacy_features,
-            public_caps,
+            mut public_caps,
             private_caps,
             texture_format_filter,
-        ) = info::query_all(&gl);
+        ) = info::query_all(&gl, profile, workaround_overrides);
+        let memory_object =
+            loader.and_then(|loader| memory_object::MemoryObjectFns::load(&info, loader));
+        if memory_object.is_some() {
+            supported_features |= hal::Features::EXTERNAL_MEMORY;
+        }
+        let (luid, uuid) = match memory_object {
+            Some(ref fns) => unsafe { fns.device_ids(&gl) },
+            None => (None, None),
+        };
+        let egl_image = loader.and_then(|loader| memory_object::EglImageFns::load(&info, loader));
+        let dma_buf = dma_buf.filter(|_| egl_image.is_some());
+        if dma_buf.is_some() {
+            supported_features |= hal::Features::EXTERNAL_MEMORY;
+        }
+        let reset_status =
+            loader.and_then(|loader| robustness::ResetStatusFns::load(&info, loader));
+        let polygon_offset_clamp =
+            loader.and_then(|loader| polygon_offset::PolygonOffsetClampFns::load(&info, loader));
+        if polygon_offset_clamp.is_some() {
+            supported_features |= hal::Features::DEPTH_BIAS_CLAMP;
+        }
+        let draw_transform_feedback = loader
+            .and_then(|loader| transform_feedback::DrawTransformFeedbackFns::load(&info, loader));
+        if draw_transform_feedback.is_some() {
+            supported_features |= hal::Features::DRAW_TRANSFORM_FEEDBACK;
+        }
+        let texture_view =
+            loader.and_then(|loader| texture_view::TextureViewFns::load(&info, loader));
+        let gpu_validation = if instance_flags.contains(hal::InstanceFlags::GPU_VALIDATION) {
+            gpu_validation::GpuValidation::new(&gl, &info, &mut public_caps.limits)
+        } else {
+            None
+        };
         log::info!("Vendor: {:?}", info.platform_name.vendor);
         log::info!("Renderer: {:?}", info.platform_name.renderer);
         log::info!("Version: {:?}", info.version);
         log::info!("Shading Language: {:?}", info.shading_language);
         log::info!("Supported Features: {:?}", supported_features);
         log::info!("Legacy Features: {:?}", legacy_features);
+        log::info!("Workarounds: {:?}", private_caps.workarounds);
+        log::info!(
+            "Emulated Features: {:?}",
+            info::emulations(legacy_features, &private_caps)
+        );
         log::debug!("Public capabilities: {:#?}", public_caps);
         log::debug!("Private capabilities: {:#?}", private_caps);
         log::debug!("Texture format filter: {:#?}", texture_format_filter);
@@ -381,6 +687,8 @@ impl PhysicalDevice {
         let name = info.platform_name.renderer.clone();
         let vendor: std::string::String = info.platform_name.vendor.clone();
         let renderer: std::string::String = info.platform_name.renderer.clone();
+        // The version string continues with the driver name and version, e.g. "Mesa 21.0.3"
+        let driver = info.version.vendor_info.trim().to_string();
 
         let mut memory_types = Vec::new();
 
@@ -450,6 +758,19 @@ impl PhysicalDevice {
             private_caps,
             open: Cell::new(false),
             memory_types,
+            memory_usage: memory::HeapUsageTracker::new(memory_heaps().len()),
+            memory_object,
+            egl_image,
+            dma_buf,
+            reset_status,
+            polygon_offset_clamp,
+            draw_transform_feedback,
+            texture_view,
+            gpu_validation,
+            context_lost: Cell::new(false),
+            instance_flags,
+            debug_output: RefCell::new(DebugOutput::default()),
+            debug_error: Arc::new(parking_lot::Mutex::new(None)),
         };
         if let Err(err) = share.check() {
             panic!("Error querying info: {:?}", err);
@@ -481,7 +802,13 @@ impl PhysicalDevice {
             "mali",
             "intel",
         ];
-        let strings_that_imply_cpu = ["mesa offscreen", "swiftshader"];
+        let strings_that_imply_cpu = [
+            "mesa offscreen",
+            "swiftshader",
+            "llvmpipe",
+            "softpipe",
+            "software rasterizer",
+        ];
         // todo: Intel will release a discrete gpu soon, and we will need to update this logic when they do
         let inferred_device_type = if vendor_lower.contains("qualcomm")
             || vendor_lower.contains("intel")
@@ -513,25 +840,75 @@ impl PhysicalDevice {
         } else if vendor_lower.contains("intel") {
             0x8086
         } else {
-            0
+            // Mesa reports its own vendor, e.g. "X.Org", so look at the renderer
+            const RENDERER_VENDORS: [(&str, usize); 10] = [
+                ("radeon", 0x1002),
+                ("amd", 0x1002),
+                ("geforce", 0x10DE),
+                ("nvidia", 0x10DE),
+                ("intel", 0x8086),
+                ("mali", 0x13B5),
+                ("adreno", 0x5143),
+                ("powervr", 0x1010),
+                ("v3d", 0x14E4),
+                ("apple", 0x106B),
+            ];
+            RENDERER_VENDORS
+                .iter()
+                .find(|&&(s, _)| renderer_lower.contains(s))
+                .map_or(0, |&(_, id)| id)
+        };
+        // The devices without their own memory share it with the host
+        let unified_memory = match inferred_device_type {
+            hal::adapter::DeviceType::IntegratedGpu | hal::adapter::DeviceType::Cpu => true,
+            _ => false,
         };
 
         adapter::Adapter {
             info: adapter::AdapterInfo {
                 name,
                 vendor: vendor_id,
+                // GL doesn't expose the PCI ID of the device
                 device: 0,
                 device_type: inferred_device_type,
+                driver,
+                unified_memory,
+                // The identifiers are only exposed by `GL_EXT_memory_object`, along
+                // with the external memory.
+                luid,
+                uuid,
             },
             physical_device: PhysicalDevice(Starc::new(share)),
             queue_families: vec![QueueFamily],
         }
     }
 
+    /// Get the version, the extensions and the platform of the driver, for
+    /// working around the issues of specific implementations.
+    pub fn info(&self) -> &Info {
+        &self.0.info
+    }
+
+    /// Get the workarounds applied for the bugs of the driver.
+    pub fn workarounds(&self) -> Workarounds {
+        self.0.private_caps.workarounds
+    }
+
     /// Get GL-specific legacy feature flags.
     pub fn legacy_features(&self) -> &info::LegacyFeatures {
         &self.0.legacy_features
     }
+
+    /// Get the features of the API emulated by the backend on this adapter.
+    pub fn emulations(&self) -> Emulations {
+        info::emulations(self.0.legacy_features, &self.0.private_caps)
+    }
+
+    /// Set the options of the debug output of the driver, installed when the
+    /// device is opened with the `DEBUG` instance flag.
+    pub fn set_debug_output(&self, output: DebugOutput) {
+        *self.0.debug_output.borrow_mut() = output;
+    }
 }
 
 impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
@@ -555,21 +932,34 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         // initialize permanent states
         let gl = &self.0.context;
 
-        if cfg!(debug_assertions) && !cfg!(target_arch = "wasm32") && gl.supports_debug() {
+        if self.0.instance_flags.contains(hal::InstanceFlags::DEBUG)
+            && !cfg!(target_arch = "wasm32")
+            && self.0.info.is_supported(&[
+                info::Requirement::Core(4, 3),
+                info::Requirement::Es(3, 2),
+                info::Requirement::Ext("GL_KHR_debug"),
+            ])
+        {
             log::info!("Debug output is enabled");
+            let output = self.0.debug_output.borrow().clone();
+            let debug_error = Arc::clone(&self.0.debug_error);
             gl.enable(glow::DEBUG_OUTPUT);
-            gl.debug_message_callback(debug_message_callback);
-        }
-
-        if self
-            .0
-            .legacy_features
-            .contains(info::LegacyFeatures::SRGB_COLOR)
-            && !self.0.info.version.is_embedded
-        {
-            // `FRAMEBUFFER_SRGB` is enabled by default on embedded targets.
-            // TODO: Find way to emulate this on older Opengl versions.
-            gl.enable(glow::FRAMEBUFFER_SRGB);
+            if output.panic_on_error {
+                gl.enable(glow::DEBUG_OUTPUT_SYNCHRONOUS);
+            }
+            gl.debug_message_callback(move |source, gltype, id, severity, message| {
+                if output.ignored_ids.contains(&id) {
+                    return;
+                }
+                debug_message_callback(source, gltype, id, severity, message);
+                // Unwinding through the driver isn't allowed, so the panic is
+                // deferred to the next check.
+                if output.panic_on_error && gltype == glow::DEBUG_TYPE_ERROR {
+                    debug_error
+                        .lock()
+                        .get_or_insert_with(|| message.to_string());
+                }
+            });
         }
 
         gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
@@ -600,26 +990,31 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         })
     }
 
-    fn format_properties(&self, _: Option<hal::format::Format>) -> hal::format::Properties {
-        use hal::format::{BufferFeature as Bf, ImageFeature as If};
-
-        // TODO: These are for show
-        hal::format::Properties {
-            linear_tiling: If::TRANSFER_SRC | If::TRANSFER_DST | If::empty(),
-            optimal_tiling: If::TRANSFER_SRC | If::TRANSFER_DST | If::SAMPLED,
-            buffer_features: Bf::VERTEX,
-            drm_format_properties: Vec::new(),
-        }
+    fn format_properties(&self, format: Option<hal::format::Format>) -> hal::format::Properties {
+        self.0.format_properties(format)
     }
 
     fn image_format_properties(
         &self,
         format: hal::format::Format,
-        _dimensions: u8,
-        _tiling: image::Tiling,
-        _usage: image::Usage,
-        _view_caps: image::ViewCapabilities,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
     ) -> Option<image::FormatProperties> {
+        if let Some((first, others)) = format.planes().split_first() {
+            // Each plane is emulated with its own texture.
+            for plane in others {
+                self.image_format_properties(plane.format, dimensions, tiling, usage, view_caps)?;
+            }
+            return self.image_format_properties(
+                first.format,
+                dimensions,
+                tiling,
+                usage,
+                view_caps,
+            );
+        }
         let conv::FormatDescription {
             tex_external,
             tex_internal,
@@ -627,7 +1022,11 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             ..
         } = conv::describe_format(format)?;
 
-        if !self
+        if format.surface_desc().is_compressed() {
+            if !self.0.supports_compression(format) {
+                return None;
+            }
+        } else if !self
             .0
             .texture_format_filter
             .check(tex_internal, tex_external, data_type)
@@ -636,6 +1035,15 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             return None;
         }
 
+        let sample_count_mask = if dimensions == 2
+            && tiling == image::Tiling::Optimal
+            && !format.surface_desc().is_compressed()
+        {
+            self.0.sample_count_mask(format, tex_internal, usage)
+        } else {
+            1
+        };
+
         Some(image::FormatProperties {
             max_extent: image::Extent {
                 width: !0,
@@ -644,7 +1052,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             },
             max_levels: !0,
             max_layers: !0,
-            sample_count_mask: 127,
+            sample_count_mask,
             max_resource_size: !0,
         })
     }
@@ -657,16 +1065,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 .iter()
                 .map(|(mem_type, _)| *mem_type)
                 .collect(),
-            memory_heaps: vec![
-                adapter::MemoryHeap {
-                    size: !0,
-                    flags: memory::HeapFlags::DEVICE_LOCAL,
-                },
-                adapter::MemoryHeap {
-                    size: !0,
-                    flags: memory::HeapFlags::empty(),
-                },
-            ],
+            memory_heaps: memory_heaps(),
         }
     }
 
@@ -674,24 +1073,53 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
         &self,
         _usage: hal::buffer::Usage,
         _sparse: hal::memory::SparseFlags,
-        _memory_type: hal::external_memory::ExternalMemoryType,
+        memory_type: hal::external_memory::ExternalMemoryType,
     ) -> hal::external_memory::ExternalMemoryProperties {
-        unimplemented!()
+        // The storage of the buffer is allocated from a memory object with `glBufferStorageMemEXT`.
+        match self.0.memory_object {
+            Some(ref fns) if self.0.private_caps.buffer_storage => fns.properties(memory_type),
+            _ => hal::external_memory::ExternalMemoryProperties::empty(),
+        }
     }
 
     fn external_image_properties(
         &self,
-        _format: hal::format::Format,
-        _dimensions: u8,
-        _tiling: image::Tiling,
+        format: hal::format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
         _usage: image::Usage,
         _view_caps: image::ViewCapabilities,
-        _memory_type: hal::external_memory::ExternalMemoryType,
+        memory_type: hal::external_memory::ExternalMemoryType,
     ) -> Result<
         hal::external_memory::ExternalMemoryProperties,
         hal::external_memory::ExternalImagePropertiesError,
     > {
-        unimplemented!()
+        if conv::describe_format(format).is_none() || format.planes().len() > 1 {
+            return Err(hal::external_memory::ExternalImagePropertiesError::FormatNotSupported);
+        }
+        // The DMA-BUF are imported as EGL images, with the layout given by the exporter.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if memory_type == hal::external_memory::ExternalMemoryType::DmaBuf {
+                return Ok(
+                    if self.0.dma_buf.is_some()
+                        && dimensions == 2
+                        && memory_object::drm_fourcc(format).is_some()
+                    {
+                        hal::external_memory::ExternalMemoryProperties::IMPORTABLE
+                    } else {
+                        hal::external_memory::ExternalMemoryProperties::empty()
+                    },
+                );
+            }
+        }
+        // Only the optimal layout of the exporter is known to match the one of GL.
+        Ok(match self.0.memory_object {
+            Some(ref fns) if dimensions == 2 && tiling == image::Tiling::Optimal => {
+                fns.properties(memory_type)
+            }
+            _ => hal::external_memory::ExternalMemoryProperties::empty(),
+        })
     }
 
     fn features(&self) -> hal::Features {
@@ -0,0 +1,487 @@
+//! Import of the memory of other APIs (`GL_EXT_memory_object`, `GL_OES_EGL_image`,
+//! `EGL_EXT_image_dma_buf_import`).
+//!
+//! `glow` exposes neither memory objects nor EGL images, so their entry points
+//! are loaded here with the loader of the context. The memory can only be imported:
+//! GL has no way to export the storage of its own objects. The DMA-BUF file
+//! descriptors aren't memory objects, they are imported as EGL images instead.
+
+use crate::{
+    info::{Info, Requirement::Ext},
+    GlContainer,
+};
+use glow::HasContext;
+use hal::{
+    external_memory::{
+        ExternalBufferMemory, ExternalImageMemory, ExternalMemoryProperties, ExternalMemoryType,
+    },
+    format::Format,
+};
+
+use std::{mem, os::raw::c_void, ptr};
+
+#[cfg(windows)]
+const DEDICATED_MEMORY_OBJECT_EXT: u32 = 0x9581;
+const NUM_DEVICE_UUIDS_EXT: u32 = 0x9596;
+const DEVICE_UUID_EXT: u32 = 0x9597;
+#[cfg(windows)]
+const DEVICE_LUID_EXT: u32 = 0x9599;
+#[cfg(unix)]
+const HANDLE_TYPE_OPAQUE_FD_EXT: u32 = 0x9586;
+#[cfg(windows)]
+const HANDLE_TYPE_OPAQUE_WIN32_EXT: u32 = 0x9587;
+#[cfg(windows)]
+const HANDLE_TYPE_OPAQUE_WIN32_KMT_EXT: u32 = 0x9588;
+#[cfg(windows)]
+const HANDLE_TYPE_D3D12_TILEPOOL_EXT: u32 = 0x9589;
+#[cfg(windows)]
+const HANDLE_TYPE_D3D12_RESOURCE_EXT: u32 = 0x958A;
+#[cfg(windows)]
+const HANDLE_TYPE_D3D11_IMAGE_EXT: u32 = 0x958B;
+#[cfg(windows)]
+const HANDLE_TYPE_D3D11_IMAGE_KMT_EXT: u32 = 0x958C;
+
+type CreateMemoryObjects = unsafe extern "system" fn(n: i32, memory_objects: *mut u32);
+type DeleteMemoryObjects = unsafe extern "system" fn(n: i32, memory_objects: *const u32);
+#[cfg(windows)]
+type GetUnsignedBytev = unsafe extern "system" fn(pname: u32, data: *mut u8);
+type GetUnsignedByteIndexed = unsafe extern "system" fn(target: u32, index: u32, data: *mut u8);
+#[cfg(windows)]
+type MemoryObjectParameteriv =
+    unsafe extern "system" fn(memory_object: u32, pname: u32, params: *const i32);
+type TexStorageMem2D = unsafe extern "system" fn(
+    target: u32,
+    levels: i32,
+    internal_format: u32,
+    width: i32,
+    height: i32,
+    memory: u32,
+    offset: u64,
+);
+type TexStorageMem3D = unsafe extern "system" fn(
+    target: u32,
+    levels: i32,
+    internal_format: u32,
+    width: i32,
+    height: i32,
+    depth: i32,
+    memory: u32,
+    offset: u64,
+);
+type BufferStorageMem =
+    unsafe extern "system" fn(target: u32, size: isize, memory: u32, offset: u64);
+#[cfg(unix)]
+type ImportMemoryFd = unsafe extern "system" fn(memory: u32, size: u64, handle_type: u32, fd: i32);
+#[cfg(windows)]
+type ImportMemoryWin32Handle =
+    unsafe extern "system" fn(memory: u32, size: u64, handle_type: u32, handle: *mut c_void);
+type EglImageTargetTexture2D = unsafe extern "system" fn(target: u32, image: *const c_void);
+type EglCreateImage = unsafe extern "system" fn(
+    display: *mut c_void,
+    context: *mut c_void,
+    target: u32,
+    buffer: *mut c_void,
+    attrib_list: *const i32,
+) -> *mut c_void;
+type EglDestroyImage = unsafe extern "system" fn(display: *mut c_void, image: *mut c_void) -> u32;
+
+const EGL_HEIGHT: i32 = 0x3056;
+const EGL_WIDTH: i32 = 0x3057;
+const EGL_NONE: i32 = 0x3038;
+const EGL_LINUX_DMA_BUF_EXT: u32 = 0x3270;
+const EGL_LINUX_DRM_FOURCC_EXT: i32 = 0x3271;
+const EGL_DMA_BUF_PLANE0_FD_EXT: i32 = 0x3272;
+const EGL_DMA_BUF_PLANE0_OFFSET_EXT: i32 = 0x3273;
+const EGL_DMA_BUF_PLANE0_PITCH_EXT: i32 = 0x3274;
+const EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT: i32 = 0x3443;
+const EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT: i32 = 0x3444;
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+/// DRM format of the single plane formats that can be imported from a DMA-BUF.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+pub fn drm_fourcc(format: Format) -> Option<u32> {
+    Some(match format {
+        Format::R8Unorm => fourcc(b"R8  "),
+        Format::Rg8Unorm => fourcc(b"GR88"),
+        Format::R16Unorm => fourcc(b"R16 "),
+        Format::Rg16Unorm => fourcc(b"GR32"),
+        Format::R5g6b5Unorm => fourcc(b"RG16"),
+        Format::Rgba8Unorm | Format::Rgba8Srgb => fourcc(b"AB24"),
+        Format::Bgra8Unorm | Format::Bgra8Srgb => fourcc(b"AR24"),
+        Format::A2b10g10r10Unorm => fourcc(b"AB30"),
+        Format::Rgba16Sfloat => fourcc(b"AB4H"),
+        _ => return None,
+    })
+}
+
+/// Platform handle of the memory to import.
+pub enum ImportHandle {
+    /// File descriptor, owned by the memory object once imported.
+    #[cfg(unix)]
+    Fd(i32),
+    /// Windows handle, staying owned by the application.
+    #[cfg(windows)]
+    Win32(*mut c_void),
+}
+
+impl ImportHandle {
+    pub fn from_buffer_memory(memory: &ExternalBufferMemory) -> Option<Self> {
+        #[cfg(unix)]
+        let handle = memory.fd().map(|fd| ImportHandle::Fd(**fd));
+        #[cfg(windows)]
+        let handle = memory.handle().map(|handle| ImportHandle::Win32(**handle));
+        #[cfg(not(any(unix, windows)))]
+        let handle = None;
+        handle
+    }
+
+    pub fn from_image_memory(memory: &ExternalImageMemory) -> Option<Self> {
+        #[cfg(unix)]
+        let handle = memory.fd().map(|fd| ImportHandle::Fd(**fd));
+        #[cfg(windows)]
+        let handle = memory.handle().map(|handle| ImportHandle::Win32(**handle));
+        #[cfg(not(any(unix, windows)))]
+        let handle = None;
+        handle
+    }
+}
+
+/// Entry points of `GL_EXT_memory_object`, and of the `_fd` and `_win32`
+/// extensions importing the platform handles.
+pub struct MemoryObjectFns {
+    create_memory_objects: CreateMemoryObjects,
+    delete_memory_objects: DeleteMemoryObjects,
+    #[cfg(windows)]
+    get_unsigned_bytev: GetUnsignedBytev,
+    get_unsigned_byte_indexed: GetUnsignedByteIndexed,
+    #[cfg(windows)]
+    memory_object_parameteriv: MemoryObjectParameteriv,
+    tex_storage_mem_2d: TexStorageMem2D,
+    tex_storage_mem_3d: TexStorageMem3D,
+    buffer_storage_mem: BufferStorageMem,
+    #[cfg(unix)]
+    import_memory_fd: Option<ImportMemoryFd>,
+    #[cfg(windows)]
+    import_memory_win32_handle: Option<ImportMemoryWin32Handle>,
+}
+
+impl MemoryObjectFns {
+    /// Load the entry points, if the implementation supports memory objects.
+    pub fn load(info: &Info, loader: &dyn Fn(&str) -> *const c_void) -> Option<Self> {
+        if !info.is_supported(&[Ext("GL_EXT_memory_object")]) {
+            return None;
+        }
+        let load = |name: &str| {
+            let ptr = loader(name);
+            if ptr.is_null() {
+                log::warn!("Memory objects are advertised, but {} is missing", name);
+                None
+            } else {
+                Some(ptr)
+            }
+        };
+        unsafe {
+            Some(MemoryObjectFns {
+                create_memory_objects: mem::transmute(load("glCreateMemoryObjectsEXT")?),
+                delete_memory_objects: mem::transmute(load("glDeleteMemoryObjectsEXT")?),
+                #[cfg(windows)]
+                get_unsigned_bytev: mem::transmute(load("glGetUnsignedBytevEXT")?),
+                get_unsigned_byte_indexed: mem::transmute(load("glGetUnsignedBytei_vEXT")?),
+                #[cfg(windows)]
+                memory_object_parameteriv: mem::transmute(load("glMemoryObjectParameterivEXT")?),
+                tex_storage_mem_2d: mem::transmute(load("glTexStorageMem2DEXT")?),
+                tex_storage_mem_3d: mem::transmute(load("glTexStorageMem3DEXT")?),
+                buffer_storage_mem: mem::transmute(load("glBufferStorageMemEXT")?),
+                #[cfg(unix)]
+                import_memory_fd: if info.is_supported(&[Ext("GL_EXT_memory_object_fd")]) {
+                    Some(mem::transmute(load("glImportMemoryFdEXT")?))
+                } else {
+                    None
+                },
+                #[cfg(windows)]
+                import_memory_win32_handle: if info
+                    .is_supported(&[Ext("GL_EXT_memory_object_win32")])
+                {
+                    Some(mem::transmute(load("glImportMemoryWin32HandleEXT")?))
+                } else {
+                    None
+                },
+            })
+        }
+    }
+
+    /// Identifiers of the device, as the LUID and the UUID of `AdapterInfo`.
+    ///
+    /// The LUID is only exposed along with the import of the Windows handles.
+    /// The identifiers left zeroed by the implementation aren't reported.
+    pub unsafe fn device_ids(&self, gl: &GlContainer) -> (Option<[u8; 8]>, Option<[u8; 16]>) {
+        #[cfg(windows)]
+        let luid = if self.import_memory_win32_handle.is_some() {
+            let mut luid = [0; 8];
+            (self.get_unsigned_bytev)(DEVICE_LUID_EXT, luid.as_mut_ptr());
+            Some(luid)
+        } else {
+            None
+        };
+        #[cfg(not(windows))]
+        let luid = None;
+        // A device group has an identifier per device, the first one is reported.
+        let uuid = if gl.get_parameter_i32(NUM_DEVICE_UUIDS_EXT) > 0 {
+            let mut uuid = [0; 16];
+            (self.get_unsigned_byte_indexed)(DEVICE_UUID_EXT, 0, uuid.as_mut_ptr());
+            Some(uuid)
+        } else {
+            None
+        };
+        (
+            luid.filter(|luid| luid != &[0; 8]),
+            uuid.filter(|uuid| uuid != &[0; 16]),
+        )
+    }
+
+    /// Handle type of the external memory type, if it can be imported.
+    fn handle_type(&self, memory_type: ExternalMemoryType) -> Option<u32> {
+        match memory_type {
+            #[cfg(unix)]
+            ExternalMemoryType::OpaqueFd => {
+                self.import_memory_fd.map(|_| HANDLE_TYPE_OPAQUE_FD_EXT)
+            }
+            #[cfg(windows)]
+            _ if self.import_memory_win32_handle.is_none() => None,
+            #[cfg(windows)]
+            ExternalMemoryType::OpaqueWin32 => Some(HANDLE_TYPE_OPAQUE_WIN32_EXT),
+            #[cfg(windows)]
+            ExternalMemoryType::OpaqueWin32Kmt => Some(HANDLE_TYPE_OPAQUE_WIN32_KMT_EXT),
+            #[cfg(windows)]
+            ExternalMemoryType::D3D12Heap => Some(HANDLE_TYPE_D3D12_TILEPOOL_EXT),
+            #[cfg(windows)]
+            ExternalMemoryType::D3D12Resource => Some(HANDLE_TYPE_D3D12_RESOURCE_EXT),
+            #[cfg(windows)]
+            ExternalMemoryType::D3D11Texture => Some(HANDLE_TYPE_D3D11_IMAGE_EXT),
+            #[cfg(windows)]
+            ExternalMemoryType::D3D11TextureKmt => Some(HANDLE_TYPE_D3D11_IMAGE_KMT_EXT),
+            _ => None,
+        }
+    }
+
+    pub fn properties(&self, memory_type: ExternalMemoryType) -> ExternalMemoryProperties {
+        match self.handle_type(memory_type) {
+            Some(_) => ExternalMemoryProperties::IMPORTABLE,
+            None => ExternalMemoryProperties::empty(),
+        }
+    }
+
+    /// Import `size` bytes of external memory into a new memory object.
+    pub unsafe fn import(
+        &self,
+        memory_type: ExternalMemoryType,
+        handle: ImportHandle,
+        size: u64,
+    ) -> Option<u32> {
+        let handle_type = self.handle_type(memory_type)?;
+        let mut memory_object = 0;
+        (self.create_memory_objects)(1, &mut memory_object);
+        // The Direct3D resources are backed by allocations of their own.
+        #[cfg(windows)]
+        {
+            if let ExternalMemoryType::D3D12Resource
+            | ExternalMemoryType::D3D11Texture
+            | ExternalMemoryType::D3D11TextureKmt = memory_type
+            {
+                (self.memory_object_parameteriv)(memory_object, DEDICATED_MEMORY_OBJECT_EXT, &1);
+            }
+        }
+        match handle {
+            #[cfg(unix)]
+            ImportHandle::Fd(fd) => {
+                (self.import_memory_fd.unwrap())(memory_object, size, handle_type, fd)
+            }
+            #[cfg(windows)]
+            ImportHandle::Win32(handle) => {
+                (self.import_memory_win32_handle.unwrap())(memory_object, size, handle_type, handle)
+            }
+        }
+        Some(memory_object)
+    }
+
+    pub unsafe fn delete(&self, memory_object: u32) {
+        (self.delete_memory_objects)(1, &memory_object);
+    }
+
+    /// Allocate the storage of the texture bound to `target` from the memory object.
+    pub unsafe fn tex_storage_2d(
+        &self,
+        target: u32,
+        levels: i32,
+        internal_format: u32,
+        width: i32,
+        height: i32,
+        memory_object: u32,
+    ) {
+        (self.tex_storage_mem_2d)(
+            target,
+            levels,
+            internal_format,
+            width,
+            height,
+            memory_object,
+            0,
+        )
+    }
+
+    /// Allocate the storage of the array texture bound to `target` from the memory object.
+    pub unsafe fn tex_storage_3d(
+        &self,
+        target: u32,
+        levels: i32,
+        internal_format: u32,
+        width: i32,
+        height: i32,
+        depth: i32,
+        memory_object: u32,
+    ) {
+        (self.tex_storage_mem_3d)(
+            target,
+            levels,
+            internal_format,
+            width,
+            height,
+            depth,
+            memory_object,
+            0,
+        )
+    }
+
+    /// Allocate the storage of the buffer bound to `target` from the memory object.
+    pub unsafe fn buffer_storage(&self, target: u32, size: u64, memory_object: u32) {
+        (self.buffer_storage_mem)(target, size as isize, memory_object, 0)
+    }
+}
+
+/// Entry point of `GL_OES_EGL_image`, specifying the storage of a texture with an
+/// EGL image, such as a frame of a hardware video decoder.
+pub struct EglImageFns {
+    image_target_texture_2d: EglImageTargetTexture2D,
+}
+
+impl EglImageFns {
+    /// Load the entry point, if the implementation supports EGL images.
+    pub fn load(info: &Info, loader: &dyn Fn(&str) -> *const c_void) -> Option<Self> {
+        if !info.is_supported(&[Ext("GL_OES_EGL_image")]) {
+            return None;
+        }
+        let ptr = loader("glEGLImageTargetTexture2DOES");
+        if ptr.is_null() {
+            log::warn!("EGL images are advertised, but glEGLImageTargetTexture2DOES is missing");
+            return None;
+        }
+        Some(EglImageFns {
+            image_target_texture_2d: unsafe { mem::transmute(ptr) },
+        })
+    }
+
+    /// Use the EGL image as the storage of the texture bound to `target`.
+    pub unsafe fn image_target_texture_2d(&self, target: u32, image: *const c_void) {
+        (self.image_target_texture_2d)(target, image)
+    }
+}
+
+/// Entry points of `EGL_EXT_image_dma_buf_import`, creating EGL images from the
+/// DMA-BUF file descriptors, on the display of the context.
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+pub struct DmaBufFns {
+    display: *mut c_void,
+    create_image: EglCreateImage,
+    destroy_image: EglDestroyImage,
+    /// The layout can be given by a DRM format modifier
+    /// (`EGL_EXT_image_dma_buf_import_modifiers`).
+    modifiers: bool,
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "android")), allow(dead_code))]
+impl DmaBufFns {
+    /// Load the entry points, if the display supports importing DMA-BUF.
+    pub fn load(
+        display: *mut c_void,
+        display_extensions: &str,
+        loader: &dyn Fn(&str) -> *const c_void,
+    ) -> Option<Self> {
+        let supports = |name| display_extensions.split_whitespace().any(|ext| ext == name);
+        if !supports("EGL_EXT_image_dma_buf_import") {
+            return None;
+        }
+        let create_image = loader("eglCreateImageKHR");
+        let destroy_image = loader("eglDestroyImageKHR");
+        if create_image.is_null() || destroy_image.is_null() {
+            log::warn!("DMA-BUF import is advertised, but eglCreateImageKHR is missing");
+            return None;
+        }
+        Some(DmaBufFns {
+            display,
+            create_image: unsafe { mem::transmute(create_image) },
+            destroy_image: unsafe { mem::transmute(destroy_image) },
+            modifiers: supports("EGL_EXT_image_dma_buf_import_modifiers"),
+        })
+    }
+
+    /// Create an EGL image from the single plane DMA-BUF `fd`, of the DRM format
+    /// `fourcc`. The file descriptor stays owned by the caller.
+    pub unsafe fn create_image(
+        &self,
+        fd: i32,
+        width: u32,
+        height: u32,
+        fourcc: u32,
+        offset: u32,
+        pitch: u32,
+        modifier: Option<u64>,
+    ) -> Option<*mut c_void> {
+        let mut attributes = vec![
+            EGL_WIDTH,
+            width as i32,
+            EGL_HEIGHT,
+            height as i32,
+            EGL_LINUX_DRM_FOURCC_EXT,
+            fourcc as i32,
+            EGL_DMA_BUF_PLANE0_FD_EXT,
+            fd,
+            EGL_DMA_BUF_PLANE0_OFFSET_EXT,
+            offset as i32,
+            EGL_DMA_BUF_PLANE0_PITCH_EXT,
+            pitch as i32,
+        ];
+        if let Some(modifier) = modifier {
+            if !self.modifiers {
+                log::error!("DRM format modifiers are not supported by the display");
+                return None;
+            }
+            attributes.extend_from_slice(&[
+                EGL_DMA_BUF_PLANE0_MODIFIER_LO_EXT,
+                modifier as u32 as i32,
+                EGL_DMA_BUF_PLANE0_MODIFIER_HI_EXT,
+                (modifier >> 32) as u32 as i32,
+            ]);
+        }
+        attributes.push(EGL_NONE);
+        let image = (self.create_image)(
+            self.display,
+            ptr::null_mut(),
+            EGL_LINUX_DMA_BUF_EXT,
+            ptr::null_mut(),
+            attributes.as_ptr(),
+        );
+        if image.is_null() {
+            None
+        } else {
+            Some(image)
+        }
+    }
+
+    /// Destroy an EGL image, the textures specified with it keeping its storage.
+    pub unsafe fn destroy_image(&self, image: *mut c_void) {
+        (self.destroy_image)(self.display, image);
+    }
+}
@@ -6,7 +6,7 @@ use hal::{
     pass, pso, window as w,
 };
 
-use std::{borrow::Borrow, fmt, ops::Range, sync::Arc};
+use std::{borrow::Borrow, fmt, iter, ops::Range, sync::Arc};
 
 pub type TextureTarget = u32;
 pub type TextureFormat = u32;
@@ -20,12 +20,48 @@ pub type Program = <GlContext as glow::HasContext>::Program;
 pub type Renderbuffer = <GlContext as glow::HasContext>::Renderbuffer;
 pub type RawFramebuffer = <GlContext as glow::HasContext>::Framebuffer;
 pub type Texture = <GlContext as glow::HasContext>::Texture;
+pub type RawTransformFeedback = <GlContext as glow::HasContext>::TransformFeedback;
 pub type Sampler = <GlContext as glow::HasContext>::Sampler;
+pub type Query = <GlContext as glow::HasContext>::Query;
 // TODO: UniformLocation was copy in glow 0.3, but in 0.4 it isn't. Wrap it in a Starc for now
 // to make it `Sync + Send` instead.
 pub type UniformLocation = crate::Starc<<GlContext as glow::HasContext>::UniformLocation>;
 pub type DescriptorSetLayout = Arc<Vec<pso::DescriptorSetLayoutBinding>>;
 
+/// Stage of a shader attached to a program.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ShaderStage {
+    Vertex,
+    Hull,
+    Domain,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    /// Returns the matching Naga stage, if Naga can generate shaders for it.
+    pub fn to_naga(self) -> Option<naga::ShaderStage> {
+        match self {
+            ShaderStage::Vertex => Some(naga::ShaderStage::Vertex),
+            ShaderStage::Fragment => Some(naga::ShaderStage::Fragment),
+            ShaderStage::Compute => Some(naga::ShaderStage::Compute),
+            ShaderStage::Hull | ShaderStage::Domain | ShaderStage::Geometry => None,
+        }
+    }
+
+    pub fn to_flag(self) -> pso::ShaderStageFlags {
+        match self {
+            ShaderStage::Vertex => pso::ShaderStageFlags::VERTEX,
+            ShaderStage::Hull => pso::ShaderStageFlags::HULL,
+            ShaderStage::Domain => pso::ShaderStageFlags::DOMAIN,
+            ShaderStage::Geometry => pso::ShaderStageFlags::GEOMETRY,
+            ShaderStage::Fragment => pso::ShaderStageFlags::FRAGMENT,
+            ShaderStage::Compute => pso::ShaderStageFlags::COMPUTE,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Framebuffer {
     pub(crate) raw: RawFramebuffer,
@@ -41,6 +77,8 @@ pub enum Buffer {
         buffer: RawBuffer,
         range: Range<buffer::Offset>,
         target: u32,
+        /// Host copy of the memory, for the emulated uniform buffers.
+        shadow: Option<HostShadow>,
     },
 }
 
@@ -48,8 +86,23 @@ pub(crate) struct BoundedBuffer {
     pub raw: RawBuffer,
     pub range: Range<buffer::Offset>,
     pub target: u32,
+    pub shadow: Option<HostShadow>,
 }
 
+/// Pointer into the host allocation of an emulated memory mapping.
+///
+/// The allocation lives as long as the memory, which the user has to keep
+/// alive while its buffers are in use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct HostShadow(pub(crate) *mut u8);
+
+// The pointer is only dereferenced by the queue, while executing a submission.
+// The memory has to outlive its pending submissions, and the host must not
+// access the ranges they use meanwhile, so there is no concurrent access, as with
+// the allocation owned by `Memory`.
+unsafe impl Send for HostShadow {}
+unsafe impl Sync for HostShadow {}
+
 impl Buffer {
     // Asserts that the buffer is bound and returns the raw gl buffer along with its sub-range.
     pub(crate) fn as_bound(&self) -> BoundedBuffer {
@@ -59,17 +112,23 @@ impl Buffer {
                 buffer,
                 ref range,
                 target,
+                shadow,
             } => BoundedBuffer {
                 raw: buffer,
                 range: range.clone(),
                 target,
+                shadow,
             },
         }
     }
 }
 
+/// Texel buffer view, emulated with a buffer texture over the buffer range.
 #[derive(Debug)]
-pub struct BufferView;
+pub struct BufferView {
+    pub(crate) raw: Texture,
+    pub(crate) format: TextureFormat,
+}
 
 #[derive(Debug)]
 pub enum Fence {
@@ -83,6 +142,7 @@ unsafe impl Sync for Fence {}
 #[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum BindingRegister {
     Textures,
+    Images,
     UniformBuffers,
     StorageBuffers,
 }
@@ -100,31 +160,87 @@ pub struct GraphicsPipeline {
     pub(crate) attributes: Vec<AttributeDesc>,
     pub(crate) vertex_buffers: Vec<Option<pso::VertexBufferDesc>>,
     pub(crate) uniforms: Vec<UniformDesc>,
+    /// Members of the uniform blocks emulated with plain uniforms.
+    pub(crate) block_uniforms: Vec<BlockUniformDesc>,
     pub(crate) rasterizer: pso::Rasterizer,
     pub(crate) depth: Option<pso::DepthTest>,
     pub(crate) baked_states: pso::BakedStates,
+    pub(crate) dynamic_states: hal::DynamicStates,
     pub(crate) sampler_map: SamplerBindMap,
+    pub(crate) transform_feedback: Option<TransformFeedback>,
+    /// Base instance uniform of the patched vertex shader.
+    pub(crate) base_instance_uniform: Option<UniformDesc>,
+}
+
+/// Transform feedback setup of a graphics pipeline.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TransformFeedback {
+    /// Primitive mode to begin capturing with.
+    pub(crate) primitive: u32,
+    /// Primitives are discarded before rasterization while capturing.
+    pub(crate) rasterizer_discard: bool,
+}
+
+/// Pool of timestamp queries, written with `glQueryCounter`.
+#[derive(Debug)]
+pub struct QueryPool {
+    pub(crate) queries: Box<[Query]>,
 }
 
 #[derive(Clone, Debug)]
 pub struct ComputePipeline {
     pub(crate) program: Program,
+    pub(crate) uniforms: Vec<UniformDesc>,
+    /// Members of the uniform blocks emulated with plain uniforms.
+    pub(crate) block_uniforms: Vec<BlockUniformDesc>,
     pub(crate) sampler_map: SamplerBindMap,
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct Image {
     pub(crate) object_type: ImageType,
+    // Multi-planar formats are emulated with a texture per plane,
+    // `object_type` holding the first one and `extra_planes` the others.
+    pub(crate) extra_planes: [Option<ImageType>; 2],
     pub(crate) kind: i::Kind,
     pub(crate) format_desc: format::FormatDesc,
     // Required for clearing operations
     pub(crate) channel: format::ChannelType,
+    // Texels are stored sRGB-encoded, which differs from `channel` for
+    // linear images with a mutable format.
+    pub(crate) srgb_storage: bool,
+    // The storage of the texture is immutable, so it can be viewed by texture views.
+    pub(crate) immutable_storage: bool,
     pub(crate) requirements: Requirements,
+    // First allocated mip level, non-zero for partial mip chains
+    pub(crate) base_level: i::Level,
     pub(crate) num_levels: i::Level,
     pub(crate) num_layers: i::Layer,
 }
 
 impl Image {
+    /// Range of mip levels backed by the texture.
+    pub(crate) fn levels(&self) -> Range<i::Level> {
+        self.base_level..self.num_levels
+    }
+
+    /// Object backing the plane selected by `aspects`.
+    pub(crate) fn plane(&self, aspects: format::Aspects) -> ImageType {
+        let extra_plane = if aspects.contains(format::Aspects::PLANE_2) {
+            self.extra_planes[1]
+        } else if aspects.contains(format::Aspects::PLANE_1) {
+            self.extra_planes[0]
+        } else {
+            None
+        };
+        extra_plane.unwrap_or(self.object_type)
+    }
+
+    /// Objects backing all the planes of the image.
+    pub(crate) fn objects(&self) -> impl Iterator<Item = ImageType> + '_ {
+        iter::once(self.object_type).chain(self.extra_planes.iter().flatten().cloned())
+    }
+
     pub(crate) fn pitches(&self, level: i::Level) -> [buffer::Offset; 4] {
         let extent = self.kind.extent().at_level(level);
         let bytes_per_texel = self.format_desc.bits as i::Size >> 3;
@@ -169,12 +285,18 @@ pub enum ImageView {
     Renderbuffer {
         raw: Renderbuffer,
         aspects: format::Aspects,
+        srgb: Option<bool>,
     },
     Texture {
         target: TextureTarget,
         raw: Texture,
         is_3d: bool,
         sub: i::SubresourceRange,
+        srgb: Option<bool>,
+        /// Sized internal format of the view, used when binding it as a storage image.
+        internal_format: Option<TextureFormat>,
+        /// `raw` is a texture view created for this view, and deleted with it.
+        owned: bool,
     },
 }
 
@@ -185,6 +307,14 @@ impl ImageView {
             ImageView::Texture { ref sub, .. } => sub.aspects,
         }
     }
+
+    /// For views of sRGB-encoded storage, whether texel accesses go through
+    /// the sRGB conversion. `None` for views of linear storage.
+    pub(crate) fn srgb(&self) -> Option<bool> {
+        match *self {
+            ImageView::Renderbuffer { srgb, .. } | ImageView::Texture { srgb, .. } => srgb,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -219,7 +349,10 @@ impl SwapchainImage {
                     raw: renderbuffer,
                     format,
                 },
+                extra_planes: [None; 2],
                 channel,
+                srgb_storage: channel == format::ChannelType::Srgb,
+                immutable_storage: false,
                 kind: i::Kind::D2(extent.width as u32, extent.height as u32, 1, 1),
                 format_desc: format::FormatDesc {
                     bits: 0,
@@ -232,12 +365,18 @@ impl SwapchainImage {
                     alignment: 1,
                     type_mask: 0,
                 },
+                base_level: 0,
                 num_levels: 1,
                 num_layers: 1,
             },
             view: ImageView::Renderbuffer {
                 raw: renderbuffer,
                 aspects: format::Aspects::COLOR,
+                srgb: if channel == format::ChannelType::Srgb {
+                    Some(true)
+                } else {
+                    None
+                },
             },
         }
     }
@@ -248,19 +387,50 @@ pub(crate) enum DescSetBindings {
     Buffer {
         register: BindingRegister,
         buffer: RawBuffer,
-        offset: i32,
-        size: i32,
+        offset: buffer::Offset,
+        size: buffer::Offset,
+        /// Host copy of the bound range, for the emulated uniform buffers.
+        shadow: Option<HostShadow>,
+    },
+    /// Texture with the sRGB decoding of its view, see `ImageView::srgb`.
+    Texture(Texture, TextureTarget, Option<bool>),
+    /// Storage image, bound to an image unit with the given access.
+    Image {
+        texture: Texture,
+        level: i32,
+        layer: Option<i32>,
+        access: u32,
+        format: TextureFormat,
     },
-    Texture(Texture, TextureTarget),
     Sampler(Sampler),
     SamplerDesc(i::SamplerDesc),
 }
 
+/// Binding and array element a descriptor is written to.
+///
+/// The elements of an array are bound to consecutive units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct DescriptorSlot {
+    pub(crate) binding: pso::DescriptorBinding,
+    pub(crate) array_index: pso::DescriptorArrayIndex,
+}
+
 #[derive(Clone, Debug)]
 pub struct DescriptorSet {
     pub(crate) layout: DescriptorSetLayout,
     //TODO: use `UnsafeCell` instead
-    pub(crate) bindings: Vec<DescSetBindings>,
+    pub(crate) bindings: Vec<(DescriptorSlot, DescSetBindings)>,
+}
+
+impl DescriptorSet {
+    /// Replace the bindings of the slots written by `bindings`.
+    ///
+    /// A combined image sampler has two bindings in the same slot.
+    pub(crate) fn update_slots(&mut self, bindings: Vec<(DescriptorSlot, DescSetBindings)>) {
+        self.bindings
+            .retain(|&(slot, _)| !bindings.iter().any(|&(written, _)| written == slot));
+        self.bindings.extend(bindings);
+    }
 }
 
 #[derive(Debug)]
@@ -306,6 +476,8 @@ impl fmt::Debug for ShaderModule {
 #[derive(Debug)]
 pub struct Memory {
     pub(crate) properties: Properties,
+    /// Index of the heap the memory is allocated from.
+    pub(crate) heap_index: usize,
     /// Gl buffer and the target that should be used for map operations.  Image memory is faked and
     /// has no associated buffer, so this will be None for image memory.
     pub(crate) buffer: Option<(RawBuffer, u32)>,
@@ -313,6 +485,8 @@ pub struct Memory {
     pub(crate) size: u64,
     pub(crate) map_flags: u32,
     pub(crate) emulate_map_allocation: Option<*mut u8>,
+    /// Memory object holding the storage imported from another API.
+    pub(crate) memory_object: Option<u32>,
 }
 
 unsafe impl Send for Memory {}
@@ -328,6 +502,8 @@ pub struct RenderPass {
 pub struct SubpassDesc {
     pub(crate) color_attachments: Vec<usize>,
     pub(crate) depth_stencil: Option<usize>,
+    // Contiguous range of layers rendered with `OVR_multiview2`, zero without multiview.
+    pub(crate) view_mask: u32,
 }
 
 impl SubpassDesc {
@@ -359,6 +535,40 @@ pub(crate) struct PipelineLayoutSet {
 pub struct PipelineLayout {
     /// Resource mapping for descriptor sets.
     pub(crate) sets: Vec<PipelineLayoutSet>,
+    /// Push constant ranges, in bytes. Push constants are emulated with
+    /// plain uniforms, which are matched by their offset in the block.
+    pub(crate) push_constants: Vec<(pso::ShaderStageFlags, Range<u32>)>,
+    /// Texture units used by all the sets, checked against the limits when creating pipelines.
+    pub(crate) num_textures: usize,
+    /// Samplers used by all the sets, checked against the limits when creating pipelines.
+    pub(crate) num_samplers: usize,
+}
+
+impl PipelineLayout {
+    /// Warn about a shader stage accessing a binding that isn't visible to it.
+    ///
+    /// GL doesn't restrict the access, but the other backends do.
+    pub(crate) fn check_visibility(
+        &self,
+        set: u32,
+        binding: pso::DescriptorBinding,
+        stage: ShaderStage,
+    ) {
+        let visibility = self.sets[set as usize]
+            .layout
+            .iter()
+            .find(|b| b.binding == binding)
+            .map_or(pso::ShaderStageFlags::empty(), |b| b.stage_flags);
+        if !visibility.contains(stage.to_flag()) {
+            log::warn!(
+                "{:?} shader accesses binding {} of set {}, which is only visible to {:?}",
+                stage,
+                binding,
+                set,
+                visibility
+            );
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -378,10 +588,23 @@ pub struct AttributeDesc {
 #[derive(Clone, Debug)]
 pub struct UniformDesc {
     pub(crate) location: UniformLocation,
+    /// Offset of the uniform in the push constant block, in bytes.
     pub(crate) offset: u32,
+    /// Size of the uniform, in bytes.
+    pub(crate) size: u32,
     pub(crate) utype: u32,
 }
 
+/// Member of a uniform block emulated with plain uniforms, see
+/// `PrivateCaps::emulate_uniform_buffers`.
+#[derive(Clone, Debug)]
+pub struct BlockUniformDesc {
+    /// Uniform buffer slot of the block.
+    pub(crate) slot: u8,
+    /// Uniform of the member, with its offset in the block.
+    pub(crate) uniform: UniformDesc,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum VertexAttribFunction {
     Float,   // glVertexAttribPointer
@@ -0,0 +1,45 @@
+//! Clamped depth bias (`GL_ARB_polygon_offset_clamp`, `GL_EXT_polygon_offset_clamp`).
+//!
+//! `glow` doesn't expose `glPolygonOffsetClamp`, so its entry point is loaded
+//! here with the loader of the context. Without it, `Features::DEPTH_BIAS_CLAMP`
+//! isn't reported and the clamp of the bias is ignored.
+
+use crate::info::{
+    Info,
+    Requirement::{Core, Ext},
+};
+
+use std::{mem, os::raw::c_void};
+
+type PolygonOffsetClamp = unsafe extern "system" fn(factor: f32, units: f32, clamp: f32);
+
+/// Entry point setting a clamped depth bias.
+pub struct PolygonOffsetClampFns {
+    polygon_offset_clamp: PolygonOffsetClamp,
+}
+
+impl PolygonOffsetClampFns {
+    /// Load the entry point, if the implementation supports clamping the depth bias.
+    pub fn load(info: &Info, loader: &dyn Fn(&str) -> *const c_void) -> Option<Self> {
+        let name = if info.is_supported(&[Core(4, 6), Ext("GL_ARB_polygon_offset_clamp")]) {
+            "glPolygonOffsetClamp"
+        } else if info.is_supported(&[Ext("GL_EXT_polygon_offset_clamp")]) {
+            "glPolygonOffsetClampEXT"
+        } else {
+            return None;
+        };
+        let ptr = loader(name);
+        if ptr.is_null() {
+            log::warn!("Depth bias clamping is advertised, but {} is missing", name);
+            return None;
+        }
+        Some(PolygonOffsetClampFns {
+            polygon_offset_clamp: unsafe { mem::transmute(ptr) },
+        })
+    }
+
+    /// Set the depth bias of the polygons, clamped to `clamp` if it's not zero.
+    pub unsafe fn polygon_offset_clamp(&self, factor: f32, units: f32, clamp: f32) {
+        (self.polygon_offset_clamp)(factor, units, clamp);
+    }
+}
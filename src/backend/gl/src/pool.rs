@@ -1,6 +1,8 @@
 use crate::{
     command::{self, Command, CommandBuffer},
-    info, native as n, Backend, FastHashMap,
+    info, native as n,
+    translate::TranslationPool,
+    Backend, FastHashMap,
 };
 
 use parking_lot::Mutex;
@@ -59,6 +61,7 @@ pub struct CommandPool {
     pub(crate) limits: command::Limits,
     pub(crate) memory: Arc<Mutex<BufferMemory>>,
     pub(crate) legacy_features: info::LegacyFeatures,
+    pub(crate) translation_pool: Option<Arc<TranslationPool>>,
 }
 
 impl hal::pool::CommandPool<Backend> for CommandPool {
@@ -89,6 +92,7 @@ impl hal::pool::CommandPool<Backend> for CommandPool {
             self.limits,
             self.memory.clone(),
             self.legacy_features,
+            self.translation_pool.clone(),
         )
     }
 
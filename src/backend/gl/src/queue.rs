@@ -1,12 +1,72 @@
 use crate::{
-    command as com, device, info::LegacyFeatures, native, state, Backend, Device, GlContext, Share,
-    Starc, Surface, MAX_COLOR_ATTACHMENTS,
+    command as com, conv, device, gpu_validation::ViolationBuffer, info::LegacyFeatures, native,
+    state, Backend, Device, Error, GlContainer, GlContext, Share, Starc, Surface,
+    MAX_COLOR_ATTACHMENTS,
 };
 
 use arrayvec::ArrayVec;
 use glow::HasContext;
 
-use std::{mem, slice};
+use std::{
+    convert::TryFrom,
+    error, fmt, mem,
+    ops::{AddAssign, Range},
+    ptr, slice, str,
+    time::Instant,
+};
+
+/// Error raised by OpenGL while executing a submitted command.
+///
+/// GL errors are only checked in debug builds. A command reading out of the
+/// recorded data of a translated command buffer fails the submission of the
+/// whole command buffer with `Error::InvalidValue`, in all builds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubmissionError {
+    /// Index of the command buffer within the submission.
+    pub command_buffer: usize,
+    /// Index of the failing command within the command buffer.
+    pub command: usize,
+    /// Description of the failing command.
+    pub description: String,
+    /// The error reported by OpenGL.
+    pub error: Error,
+}
+
+impl fmt::Display for SubmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Error {:?} executing command {} of command buffer {}: {}",
+            self.error, self.command, self.command_buffer, self.description
+        )
+    }
+}
+
+impl error::Error for SubmissionError {}
+
+/// Statistics about draws with a non-zero first instance.
+///
+/// Without `GL_ARB_base_instance` (GL 4.2), the first instance is emulated by
+/// offsetting the instance-rate vertex attributes of every such draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BaseInstanceStats {
+    /// Number of draws with a non-zero first instance.
+    pub draws: u32,
+    /// Number of those draws that rebound the vertex attributes to emulate it.
+    pub emulated_draws: u32,
+    /// Number of those draws that updated the base instance uniform of a patched shader.
+    ///
+    /// See `Device::set_base_instance_patching`.
+    pub patched_draws: u32,
+}
+
+impl AddAssign for BaseInstanceStats {
+    fn add_assign(&mut self, other: Self) {
+        self.draws += other.draws;
+        self.emulated_draws += other.emulated_draws;
+        self.patched_draws += other.patched_draws;
+    }
+}
 
 // State caching system for command queue.
 //
@@ -27,6 +87,8 @@ struct State {
     num_viewports: usize,
     // Currently set scissor rects.
     num_scissors: usize,
+    // Active transform feedback pass.
+    transform_feedback: Option<native::TransformFeedback>,
 }
 
 impl State {
@@ -38,6 +100,7 @@ impl State {
             index_buffer: None,
             num_viewports: 0,
             num_scissors: 0,
+            transform_feedback: None,
         }
     }
 
@@ -60,10 +123,28 @@ pub struct Queue {
     state: State,
     fill_buffer: native::RawBuffer,
     fill_data: Box<[u32]>,
+    // Transform feedback object capturing the outputs, created on first use
+    // when the captured vertices can be drawn back.
+    transform_feedback_object: Option<native::RawTransformFeedback>,
+    // Storage buffer receiving the violations of the shaders, with GPU validation.
+    violations: Option<ViolationBuffer>,
+    // Base instance statistics of the submissions since the last present.
+    base_instance_stats: BaseInstanceStats,
+    // Base instance statistics of the last presented frame.
+    frame_base_instance_stats: BaseInstanceStats,
 }
 
 const FILL_DATA_WORDS: usize = 16 << 10;
 
+/// Convert a buffer offset or size into a GL integer, reporting values that
+/// would be truncated as `GL_INVALID_VALUE` instead of passing them on.
+fn gl_cast<T: TryFrom<hal::buffer::Offset>>(value: hal::buffer::Offset) -> Result<T, Error> {
+    hal::buffer::cast(value).map_err(|err| {
+        log::error!("{}", err);
+        Error::InvalidValue
+    })
+}
+
 impl Queue {
     /// Create a new command queue.
     pub(crate) fn new(
@@ -90,9 +171,22 @@ impl Queue {
             state: State::new(),
             fill_buffer,
             fill_data: vec![0; FILL_DATA_WORDS].into_boxed_slice(),
+            transform_feedback_object: None,
+            violations: share
+                .gpu_validation
+                .as_ref()
+                .map(|validation| ViolationBuffer::new(gl, validation)),
+            base_instance_stats: BaseInstanceStats::default(),
+            frame_base_instance_stats: BaseInstanceStats::default(),
         }
     }
 
+    /// Statistics about the draws with a non-zero first instance,
+    /// submitted during the last presented frame.
+    pub fn base_instance_stats(&self) -> BaseInstanceStats {
+        self.frame_base_instance_stats
+    }
+
     /// Access the OpenGL directly via a closure. OpenGL types and enumerations
     /// can be found in the `gl` crate.
     ///
@@ -175,13 +269,253 @@ impl Queue {
         Device::bind_target(&self.share.context, point, attachment, view)
     }
 
+    fn bind_target_multiview(
+        &mut self,
+        point: u32,
+        attachment: u32,
+        view: &native::ImageView,
+        view_mask: u32,
+    ) {
+        if view_mask == 0 {
+            self.bind_target(point, attachment, view)
+        } else {
+            Device::bind_target_multiview(&self.share.context, point, attachment, view, view_mask)
+        }
+    }
+
+    /// Upload block-compressed data from the bound unpack buffer into the bound texture.
+    ///
+    /// Compressed uploads can't skip the padding of rows, so padded rows of blocks
+    /// are uploaded one by one.
+    unsafe fn copy_buffer_to_compressed_texture(
+        gl: &GlContainer,
+        target: native::TextureTarget,
+        format: native::TextureFormat,
+        desc: hal::format::FormatDesc,
+        data: &com::BufferImageCopy,
+    ) -> Result<(), Error> {
+        let (block_width, block_height) = (desc.dim.0 as u32, desc.dim.1 as u32);
+        let block_size = desc.bits as u32 / 8;
+        let extent = data.image_extent;
+        let buffer_width = match data.buffer_width {
+            0 => extent.width,
+            width => width,
+        };
+        let buffer_height = match data.buffer_height {
+            0 => extent.height,
+            height => height,
+        };
+        let row_size = (extent.width + block_width - 1) / block_width * block_size;
+        let row_pitch = (buffer_width + block_width - 1) / block_width * block_size;
+        let rows = (extent.height + block_height - 1) / block_height;
+        let slice_pitch = row_pitch * ((buffer_height + block_height - 1) / block_height);
+
+        // Upload either whole layers at once, or rows of blocks.
+        let (chunks, chunk_rows) = if row_pitch == row_size {
+            (1, rows)
+        } else {
+            (rows, 1)
+        };
+        // Layers of arrays and slices of 3D textures are both addressed by depth,
+        // and each one is a separate slice of the buffer.
+        let depths = if target == glow::TEXTURE_3D {
+            let z = data.image_offset.z as u32;
+            z..z + extent.depth
+        } else {
+            data.image_layers.layers.start as u32..data.image_layers.layers.end as u32
+        };
+        for (i, layer) in depths.enumerate() {
+            for chunk in 0..chunks {
+                let y = chunk * block_height;
+                let height = (chunk_rows * block_height).min(extent.height - y);
+                let offset = data.buffer_offset
+                    + (i as u32 * slice_pitch + chunk * row_pitch) as hal::buffer::Offset;
+                let size = (chunk_rows - 1) * row_pitch + row_size;
+                let range = gl_cast(offset)?..gl_cast(offset + size as hal::buffer::Offset)?;
+                let pixels = glow::CompressedPixelUnpackData::BufferRange(range);
+                match target {
+                    glow::TEXTURE_2D => gl.compressed_tex_sub_image_2d(
+                        target,
+                        data.image_layers.level as _,
+                        data.image_offset.x,
+                        data.image_offset.y + y as i32,
+                        extent.width as _,
+                        height as _,
+                        format,
+                        pixels,
+                    ),
+                    glow::TEXTURE_CUBE_MAP => gl.compressed_tex_sub_image_2d(
+                        glow::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+                        data.image_layers.level as _,
+                        data.image_offset.x,
+                        data.image_offset.y + y as i32,
+                        extent.width as _,
+                        height as _,
+                        format,
+                        pixels,
+                    ),
+                    glow::TEXTURE_2D_ARRAY | glow::TEXTURE_CUBE_MAP_ARRAY | glow::TEXTURE_3D => gl
+                        .compressed_tex_sub_image_3d(
+                            target,
+                            data.image_layers.level as _,
+                            data.image_offset.x,
+                            data.image_offset.y + y as i32,
+                            layer as _,
+                            extent.width as _,
+                            height as _,
+                            1,
+                            format,
+                            pixels,
+                        ),
+                    _ => {
+                        log::error!(
+                            "Copying compressed data to texture target 0x{:X} is not implemented",
+                            target
+                        );
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Bind the transform feedback object of the queue, so that the vertex count
+    /// of the next capture can be drawn back with `glDrawTransformFeedback`.
+    ///
+    /// Without it, the outputs are captured with the default object.
+    fn bind_transform_feedback_object(&mut self) {
+        if self.share.draw_transform_feedback.is_none() {
+            return;
+        }
+        let gl = &self.share.context;
+        let object = match self.transform_feedback_object {
+            Some(object) => object,
+            None => {
+                let object = unsafe { gl.create_transform_feedback() }.unwrap();
+                self.transform_feedback_object = Some(object);
+                object
+            }
+        };
+        unsafe { gl.bind_transform_feedback(glow::TRANSFORM_FEEDBACK, Some(object)) };
+    }
+
+    /// Set the depth bias, with `glPolygonOffsetClamp` when it's clamped.
+    unsafe fn set_polygon_offset(&self, bias: &hal::pso::DepthBias) {
+        match self.share.polygon_offset_clamp {
+            Some(ref fns) if bias.clamp != 0.0 => {
+                fns.polygon_offset_clamp(bias.slope_factor, bias.const_factor, bias.clamp)
+            }
+            _ => {
+                if bias.clamp != 0.0 {
+                    log::warn!("Depth bias clamping is not supported");
+                }
+                self.share
+                    .context
+                    .polygon_offset(bias.slope_factor, bias.const_factor);
+            }
+        }
+    }
+
+    /// Return the layers of a blit region, which are depth slices for 3D textures.
+    ///
+    /// Flipped depth bounds mirror the region, returning the slices in reverse order.
+    fn blit_layers(
+        image: native::ImageType,
+        subresource: &hal::image::SubresourceLayers,
+        bounds: &Range<hal::image::Offset>,
+    ) -> Vec<hal::image::Layer> {
+        match image {
+            native::ImageType::Texture {
+                target: glow::TEXTURE_3D,
+                ..
+            } => {
+                let (start, end) = (bounds.start.z, bounds.end.z);
+                if start <= end {
+                    (start..end).map(|z| z as hal::image::Layer).collect()
+                } else {
+                    (end..start).rev().map(|z| z as hal::image::Layer).collect()
+                }
+            }
+            _ => subresource.layers.clone().collect(),
+        }
+    }
+
+    /// Return a view of a single layer of an image, to be attached to a framebuffer.
+    fn blit_view(
+        image: native::ImageType,
+        aspects: hal::format::Aspects,
+        level: hal::image::Level,
+        layer: hal::image::Layer,
+    ) -> native::ImageView {
+        match image {
+            native::ImageType::Renderbuffer { raw, .. } => native::ImageView::Renderbuffer {
+                raw,
+                aspects,
+                srgb: None,
+            },
+            native::ImageType::Texture { target, raw, .. } => native::ImageView::Texture {
+                target,
+                raw,
+                // The faces of cube maps are attached as 2D targets.
+                is_3d: match target {
+                    glow::TEXTURE_3D | glow::TEXTURE_2D_ARRAY | glow::TEXTURE_CUBE_MAP_ARRAY => {
+                        true
+                    }
+                    _ => false,
+                },
+                sub: hal::image::SubresourceRange {
+                    aspects,
+                    level_start: level,
+                    level_count: Some(1),
+                    layer_start: layer,
+                    layer_count: Some(1),
+                },
+                srgb: None,
+                internal_format: None,
+                owned: false,
+            },
+        }
+    }
+
     fn _unbind_target(&mut self, point: u32, attachment: u32) {
         let gl = &self.share.context;
         // TODO: Find workaround or use explicit `textarget` with the other `framebuffer_texture`
         unsafe { gl.framebuffer_texture(point, attachment, None, 0) };
     }
 
-    /// Return a reference to a stored data object.
+    /// Set a plain uniform from its raw data.
+    /// Upload the elements of a uniform, tightly packed in `raw`.
+    fn set_uniform(gl: &GlContainer, uniform: &native::UniformDesc, raw: &[u8]) {
+        let buffer = com::BufferSlice {
+            offset: 0,
+            size: raw.len() as u32,
+        };
+        let location = Some(&*uniform.location);
+        unsafe {
+            match uniform.utype {
+                glow::FLOAT => gl.uniform_1_f32_slice(location, Self::get(raw, buffer)),
+                glow::FLOAT_VEC2 => gl.uniform_2_f32_slice(location, Self::get(raw, buffer)),
+                glow::FLOAT_VEC3 => gl.uniform_3_f32_slice(location, Self::get(raw, buffer)),
+                glow::FLOAT_VEC4 => gl.uniform_4_f32_slice(location, Self::get(raw, buffer)),
+                glow::INT => gl.uniform_1_i32_slice(location, Self::get(raw, buffer)),
+                glow::INT_VEC2 => gl.uniform_2_i32_slice(location, Self::get(raw, buffer)),
+                glow::INT_VEC3 => gl.uniform_3_i32_slice(location, Self::get(raw, buffer)),
+                glow::INT_VEC4 => gl.uniform_4_i32_slice(location, Self::get(raw, buffer)),
+                glow::FLOAT_MAT2 => {
+                    gl.uniform_matrix_2_f32_slice(location, false, Self::get(raw, buffer))
+                }
+                glow::FLOAT_MAT3 => {
+                    gl.uniform_matrix_3_f32_slice(location, false, Self::get(raw, buffer))
+                }
+                glow::FLOAT_MAT4 => {
+                    gl.uniform_matrix_4_f32_slice(location, false, Self::get(raw, buffer))
+                }
+                _ => panic!("Unsupported uniform datatype!"),
+            }
+        }
+    }
+
     fn get<T>(data: &[u8], ptr: com::BufferSlice) -> &[T] {
         let u32_size = mem::size_of::<T>();
         assert_eq!(ptr.size % u32_size as u32, 0);
@@ -230,7 +564,7 @@ impl Queue {
         };
     }
 
-    fn process(&mut self, cmd: &com::Command, data_buf: &[u8]) {
+    fn process(&mut self, cmd: &com::Command, data_buf: &[u8]) -> Result<(), Error> {
         match *cmd {
             com::Command::BindIndexBuffer(buffer) => {
                 let gl = &self.share.context;
@@ -282,6 +616,19 @@ impl Queue {
                     log::error!("Instanced draw calls are not supported");
                 }
             }
+            com::Command::DrawTransformFeedback {
+                primitive,
+                ref instances,
+            } => match (
+                &self.share.draw_transform_feedback,
+                self.transform_feedback_object,
+            ) {
+                (Some(fns), Some(object)) => unsafe {
+                    fns.draw(primitive, object, instances.clone());
+                },
+                (Some(_), None) => log::warn!("No outputs were captured by transform feedback"),
+                (None, _) => log::error!("Drawing captured outputs is not supported"),
+            },
             com::Command::DrawIndexed {
                 primitive,
                 index_type,
@@ -293,6 +640,7 @@ impl Queue {
                 let gl = &self.share.context;
                 let legacy = &self.share.legacy_features;
                 let caveats = &self.share.public_caps.performance_caveats;
+                let index_buffer_offset: i32 = gl_cast(index_buffer_offset)?;
 
                 if instances == &(0u32..1) {
                     if base_vertex == 0 {
@@ -301,7 +649,7 @@ impl Queue {
                                 primitive,
                                 index_count as _,
                                 index_type,
-                                index_buffer_offset as i32,
+                                index_buffer_offset,
                             );
                         }
                     } else if legacy.contains(LegacyFeatures::DRAW_INDEXED_BASE) {
@@ -310,7 +658,7 @@ impl Queue {
                                 primitive,
                                 index_count as _,
                                 index_type,
-                                index_buffer_offset as i32,
+                                index_buffer_offset,
                                 base_vertex as _,
                             );
                         }
@@ -324,7 +672,7 @@ impl Queue {
                                 primitive,
                                 index_count as _,
                                 index_type,
-                                index_buffer_offset as i32,
+                                index_buffer_offset,
                                 instances.end as _,
                             );
                         }
@@ -336,7 +684,7 @@ impl Queue {
                                 primitive,
                                 index_count as _,
                                 index_type,
-                                index_buffer_offset as i32,
+                                index_buffer_offset,
                                 instances.end as _,
                                 base_vertex as _,
                             );
@@ -356,7 +704,7 @@ impl Queue {
                                 primitive,
                                 index_count as _,
                                 index_type,
-                                index_buffer_offset as i32,
+                                index_buffer_offset,
                                 (instances.end - instances.start) as _,
                                 base_vertex as _,
                                 instances.start as _,
@@ -379,10 +727,10 @@ impl Queue {
                 // If there is no compute support, this pattern should never be reached
                 // because no queue with compute capability can be created.
                 let gl = &self.share.context;
+                let offset = gl_cast(offset)?;
                 unsafe {
                     gl.bind_buffer(glow::DRAW_INDIRECT_BUFFER, Some(buffer));
-                    // TODO: possible integer conversion issue
-                    gl.dispatch_compute_indirect(offset as _);
+                    gl.dispatch_compute_indirect(offset);
                 }
             }
             com::Command::SetViewports {
@@ -452,9 +800,18 @@ impl Queue {
                 state::set_blend_color(&self.share.context, color);
             }
             com::Command::ClearBufferColorF(draw_buffer, mut cv) => unsafe {
-                self.share
-                    .context
-                    .clear_buffer_f32_slice(glow::COLOR, draw_buffer, &mut cv);
+                let gl = &self.share.context;
+                if self
+                    .share
+                    .legacy_features
+                    .contains(LegacyFeatures::CLEAR_BUFFER)
+                {
+                    gl.clear_buffer_f32_slice(glow::COLOR, draw_buffer, &mut cv);
+                } else {
+                    // Only the first draw buffer is recorded without `glClearBuffer`.
+                    gl.clear_color(cv[0], cv[1], cv[2], cv[3]);
+                    gl.clear(glow::COLOR_BUFFER_BIT);
+                }
             },
             com::Command::ClearBufferColorU(draw_buffer, mut cv) => unsafe {
                 self.share
@@ -466,6 +823,24 @@ impl Queue {
                     .context
                     .clear_buffer_i32_slice(glow::COLOR, draw_buffer, &mut cv);
             },
+            com::Command::ClearBufferDepthStencil(depth, stencil)
+                if !self
+                    .share
+                    .legacy_features
+                    .contains(LegacyFeatures::CLEAR_BUFFER) =>
+            unsafe {
+                let gl = &self.share.context;
+                let mut mask = 0;
+                if let Some(depth) = depth {
+                    gl.clear_depth_f32(depth);
+                    mask |= glow::DEPTH_BUFFER_BIT;
+                }
+                if let Some(stencil) = stencil {
+                    gl.clear_stencil(stencil as i32);
+                    mask |= glow::STENCIL_BUFFER_BIT;
+                }
+                gl.clear(mask);
+            },
             com::Command::ClearBufferDepthStencil(depth, stencil) => unsafe {
                 let gl = &self.share.context;
                 match (depth, stencil) {
@@ -489,11 +864,13 @@ impl Queue {
                 framebuffer,
                 ref colors,
                 ref depth_stencil,
+                view_mask,
             } => {
                 let gl = &self.share.context;
                 unsafe { gl.bind_framebuffer(target, Some(framebuffer)) };
                 for (i, view) in colors.iter().enumerate() {
-                    self.bind_target(target, glow::COLOR_ATTACHMENT0 + i as u32, view);
+                    let attachment = glow::COLOR_ATTACHMENT0 + i as u32;
+                    self.bind_target_multiview(target, attachment, view, view_mask);
                 }
                 if let Some(ref view) = *depth_stencil {
                     let aspects = view.aspects();
@@ -504,16 +881,17 @@ impl Queue {
                     } else {
                         glow::DEPTH_STENCIL_ATTACHMENT
                     };
-                    self.bind_target(target, attachment, view);
+                    self.bind_target_multiview(target, attachment, view, view_mask);
                 }
             }
-            com::Command::FillBuffer(buffer, ref range, value) => {
+            com::Command::FillBuffer(buffer, ref range, value, shadow) => {
                 //Note: buffers with `DYNAMIC_STORAGE_BIT` can't be uploaded to directly.
                 // And we expect the target buffers to be on GPU, where we assign this flag.
 
-                let total_size = (range.end - range.start) as i32;
+                let total_size: i32 = gl_cast(range.end - range.start)?;
+                let end: i32 = gl_cast(range.end)?;
                 let temp_size = (total_size as usize / 4).min(FILL_DATA_WORDS);
-                let mut dst_offset = range.start as i32;
+                let mut dst_offset = end - total_size;
                 for v in self.fill_data[..temp_size].iter_mut() {
                     *v = value;
                 }
@@ -528,8 +906,8 @@ impl Queue {
                     );
                     gl.bind_buffer(glow::COPY_WRITE_BUFFER, Some(buffer));
 
-                    while dst_offset < range.end as i32 {
-                        let copy_size = (temp_size as i32 * 4).min(range.end as i32 - dst_offset);
+                    while dst_offset < end {
+                        let copy_size = (temp_size as i32 * 4).min(end - dst_offset);
                         gl.copy_buffer_sub_data(
                             glow::COPY_READ_BUFFER,
                             glow::COPY_WRITE_BUFFER,
@@ -542,6 +920,40 @@ impl Queue {
 
                     gl.bind_buffer(glow::COPY_READ_BUFFER, None);
                     gl.bind_buffer(glow::COPY_WRITE_BUFFER, None);
+
+                    if let Some(shadow) = shadow {
+                        let words = shadow.0.add(gl_cast(range.start)?) as *mut u32;
+                        for i in 0..total_size as usize / 4 {
+                            words.add(i).write_unaligned(value);
+                        }
+                    }
+                }
+            }
+            com::Command::UpdateBuffer {
+                buffer,
+                target,
+                offset,
+                data,
+                shadow,
+            } => {
+                let data = Self::get_raw(data_buf, data);
+                let gl = &self.share.context;
+                unsafe {
+                    gl.bind_buffer(target, Some(buffer));
+                    gl.buffer_sub_data_u8_slice(target, gl_cast(offset)?, data);
+                    if target == glow::ELEMENT_ARRAY_BUFFER {
+                        gl.bind_buffer(target, self.state.index_buffer);
+                    } else {
+                        gl.bind_buffer(target, None);
+                    }
+
+                    if let Some(shadow) = shadow {
+                        ptr::copy_nonoverlapping(
+                            data.as_ptr(),
+                            shadow.0.add(gl_cast(offset)?),
+                            data.len(),
+                        );
+                    }
                 }
             }
             com::Command::SetDrawColorBuffers(ref indices) => {
@@ -551,6 +963,15 @@ impl Queue {
                     .collect::<ArrayVec<[_; MAX_COLOR_ATTACHMENTS]>>();
                 unsafe { self.share.context.draw_buffers(&gl_indices) };
             }
+            com::Command::InvalidateFramebuffer(ref attachments) => {
+                if self.share.private_caps.framebuffer_invalidation {
+                    unsafe {
+                        self.share
+                            .context
+                            .invalidate_framebuffer(glow::DRAW_FRAMEBUFFER, attachments)
+                    };
+                }
+            }
             com::Command::SetPatchSize(num) => unsafe {
                 self.share
                     .context
@@ -580,25 +1001,17 @@ impl Queue {
                     vertex_attrib_fn,
                     ..
                 } = attribute;
+                let offset: i32 = gl_cast(offset as hal::buffer::Offset)?;
                 let gl = &self.share.context;
 
                 gl.bind_buffer(glow::ARRAY_BUFFER, Some(handle));
 
                 match vertex_attrib_fn {
-                    Float => gl.vertex_attrib_pointer_f32(
-                        location,
-                        size,
-                        format,
-                        false,
-                        stride,
-                        offset as i32,
-                    ),
-                    Integer => {
-                        gl.vertex_attrib_pointer_i32(location, size, format, stride, offset as i32)
-                    }
-                    Double => {
-                        gl.vertex_attrib_pointer_f64(location, size, format, stride, offset as i32)
+                    Float => {
+                        gl.vertex_attrib_pointer_f32(location, size, format, false, stride, offset)
                     }
+                    Integer => gl.vertex_attrib_pointer_i32(location, size, format, stride, offset),
+                    Double => gl.vertex_attrib_pointer_f64(location, size, format, stride, offset),
                 }
 
                 if self
@@ -623,6 +1036,7 @@ impl Queue {
                 dst_buffer,
                 src_target: _,
                 dst_target,
+                dst_shadow,
                 data,
             } => unsafe {
                 let is_index_buffer_only_element_dst =
@@ -637,26 +1051,35 @@ impl Queue {
                     glow::COPY_WRITE_BUFFER
                 };
                 let gl = &self.share.context;
+                let src_offset = gl_cast(data.src)?;
+                let dst_offset = gl_cast(data.dst)?;
+                let size: i32 = gl_cast(data.size)?;
 
                 gl.bind_buffer(copy_src_target, Some(src_buffer));
                 gl.bind_buffer(copy_dst_target, Some(dst_buffer));
 
                 if is_index_buffer_only_element_dst {
-                    let mut buffer_data = vec![0; data.size as usize];
-                    gl.get_buffer_sub_data(copy_src_target, data.src as i32, &mut buffer_data);
-                    gl.buffer_sub_data_u8_slice(copy_dst_target, data.dst as i32, &buffer_data);
+                    let mut buffer_data = vec![0; size as usize];
+                    gl.get_buffer_sub_data(copy_src_target, src_offset, &mut buffer_data);
+                    gl.buffer_sub_data_u8_slice(copy_dst_target, dst_offset, &buffer_data);
                 } else {
                     gl.copy_buffer_sub_data(
                         copy_src_target,
                         copy_dst_target,
-                        data.src as _,
-                        data.dst as _,
-                        data.size as _,
+                        src_offset,
+                        dst_offset,
+                        size,
                     );
                 }
 
                 gl.bind_buffer(copy_src_target, None);
 
+                if let Some(shadow) = dst_shadow {
+                    let slice =
+                        slice::from_raw_parts_mut(shadow.0.add(gl_cast(data.dst)?), size as usize);
+                    gl.get_buffer_sub_data(copy_dst_target, dst_offset, slice);
+                }
+
                 if is_index_buffer_only_element_dst {
                     gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, self.state.index_buffer);
                 } else {
@@ -669,49 +1092,62 @@ impl Queue {
                 texture_target,
                 texture_format,
                 pixel_type,
+                compressed,
                 ref data,
             } => unsafe {
                 // TODO: Fix active texture
                 assert_eq!(data.image_offset.z, 0);
 
+                let buffer_offset = gl_cast(data.buffer_offset)?;
                 let gl = &self.share.context;
 
                 gl.active_texture(glow::TEXTURE0);
                 gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(src_buffer));
 
-                match texture_target {
-                    glow::TEXTURE_2D => {
-                        gl.bind_texture(glow::TEXTURE_2D, Some(dst_texture));
-                        gl.tex_sub_image_2d(
-                            glow::TEXTURE_2D,
-                            data.image_layers.level as _,
-                            data.image_offset.x,
-                            data.image_offset.y,
-                            data.image_extent.width as _,
-                            data.image_extent.height as _,
-                            texture_format,
-                            pixel_type,
-                            glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
-                        );
-                    }
-                    glow::TEXTURE_2D_ARRAY => {
-                        gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(dst_texture));
-                        gl.tex_sub_image_3d(
-                            glow::TEXTURE_2D_ARRAY,
-                            data.image_layers.level as _,
-                            data.image_offset.x,
-                            data.image_offset.y,
-                            data.image_layers.layers.start as i32,
-                            data.image_extent.width as _,
-                            data.image_extent.height as _,
-                            data.image_layers.layers.end as i32
-                                - data.image_layers.layers.start as i32,
-                            texture_format,
-                            pixel_type,
-                            glow::PixelUnpackData::BufferOffset(data.buffer_offset as u32),
-                        );
+                if let Some(desc) = compressed {
+                    gl.bind_texture(texture_target, Some(dst_texture));
+                    Self::copy_buffer_to_compressed_texture(
+                        gl,
+                        texture_target,
+                        texture_format,
+                        desc,
+                        data,
+                    )?;
+                } else {
+                    match texture_target {
+                        glow::TEXTURE_2D => {
+                            gl.bind_texture(glow::TEXTURE_2D, Some(dst_texture));
+                            gl.tex_sub_image_2d(
+                                glow::TEXTURE_2D,
+                                data.image_layers.level as _,
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                texture_format,
+                                pixel_type,
+                                glow::PixelUnpackData::BufferOffset(buffer_offset),
+                            );
+                        }
+                        glow::TEXTURE_2D_ARRAY => {
+                            gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(dst_texture));
+                            gl.tex_sub_image_3d(
+                                glow::TEXTURE_2D_ARRAY,
+                                data.image_layers.level as _,
+                                data.image_offset.x,
+                                data.image_offset.y,
+                                data.image_layers.layers.start as i32,
+                                data.image_extent.width as _,
+                                data.image_extent.height as _,
+                                data.image_layers.layers.end as i32
+                                    - data.image_layers.layers.start as i32,
+                                texture_format,
+                                pixel_type,
+                                glow::PixelUnpackData::BufferOffset(buffer_offset),
+                            );
+                        }
+                        _ => unimplemented!(),
                     }
-                    _ => unimplemented!(),
                 }
 
                 gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
@@ -732,6 +1168,7 @@ impl Queue {
                     // TODO: handle partial copies gracefully
                     assert_eq!(data.image_offset, hal::image::Offset { x: 0, y: 0, z: 0 });
                     assert_eq!(texture_target, glow::TEXTURE_2D);
+                    let buffer_offset = gl_cast(data.buffer_offset)?;
                     let gl = &self.share.context;
                     unsafe {
                         gl.active_texture(glow::TEXTURE0);
@@ -746,7 +1183,7 @@ impl Queue {
                             //data.image_extent.height as _,
                             texture_format,
                             pixel_type,
-                            glow::PixelPackData::BufferOffset(data.buffer_offset as u32),
+                            glow::PixelPackData::BufferOffset(buffer_offset),
                         );
                         gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
                     }
@@ -827,7 +1264,101 @@ impl Queue {
                     }
                 }
             }
+            com::Command::BlitImage {
+                src_image,
+                dst_image,
+                filter,
+                ref data,
+            } => {
+                let aspects = data.src_subresource.aspects;
+                let (attachment, mask) = if aspects.contains(hal::format::Aspects::COLOR) {
+                    (glow::COLOR_ATTACHMENT0, glow::COLOR_BUFFER_BIT)
+                } else {
+                    let depth = aspects.contains(hal::format::Aspects::DEPTH);
+                    let stencil = aspects.contains(hal::format::Aspects::STENCIL);
+                    match (depth, stencil) {
+                        (true, true) => (
+                            glow::DEPTH_STENCIL_ATTACHMENT,
+                            glow::DEPTH_BUFFER_BIT | glow::STENCIL_BUFFER_BIT,
+                        ),
+                        (true, false) => (glow::DEPTH_ATTACHMENT, glow::DEPTH_BUFFER_BIT),
+                        _ => (glow::STENCIL_ATTACHMENT, glow::STENCIL_BUFFER_BIT),
+                    }
+                };
+                // Depth and stencil can't be filtered.
+                let filter = if mask == glow::COLOR_BUFFER_BIT {
+                    filter
+                } else {
+                    glow::NEAREST
+                };
+
+                let src_layers =
+                    Self::blit_layers(src_image, &data.src_subresource, &data.src_bounds);
+                let dst_layers =
+                    Self::blit_layers(dst_image, &data.dst_subresource, &data.dst_bounds);
+                // The depth of 3D images is scaled by blitting each destination slice
+                // from the source slice nearest to its center.
+                let src_layers = (0..dst_layers.len())
+                    .filter_map(|i| {
+                        src_layers
+                            .get((2 * i + 1) * src_layers.len() / (2 * dst_layers.len()))
+                            .cloned()
+                    })
+                    .collect::<Vec<_>>();
+
+                let gl = &self.share.context;
+                unsafe {
+                    let src_fbo = gl.create_framebuffer().unwrap();
+                    let dst_fbo = gl.create_framebuffer().unwrap();
+                    gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(src_fbo));
+                    gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(dst_fbo));
+
+                    for (src_layer, dst_layer) in src_layers.into_iter().zip(dst_layers) {
+                        Device::bind_target(
+                            gl,
+                            glow::READ_FRAMEBUFFER,
+                            attachment,
+                            &Self::blit_view(
+                                src_image,
+                                aspects,
+                                data.src_subresource.level,
+                                src_layer,
+                            ),
+                        );
+                        Device::bind_target(
+                            gl,
+                            glow::DRAW_FRAMEBUFFER,
+                            attachment,
+                            &Self::blit_view(
+                                dst_image,
+                                aspects,
+                                data.dst_subresource.level,
+                                dst_layer,
+                            ),
+                        );
+                        gl.blit_framebuffer(
+                            data.src_bounds.start.x,
+                            data.src_bounds.start.y,
+                            data.src_bounds.end.x,
+                            data.src_bounds.end.y,
+                            data.dst_bounds.start.x,
+                            data.dst_bounds.start.y,
+                            data.dst_bounds.end.x,
+                            data.dst_bounds.end.y,
+                            mask,
+                            filter,
+                        );
+                    }
+
+                    gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+                    gl.delete_framebuffer(src_fbo);
+                    gl.delete_framebuffer(dst_fbo);
+                }
+            }
             com::Command::BindBufferRange(target, index, buffer, offset, size) => unsafe {
+                if target == glow::TRANSFORM_FEEDBACK_BUFFER {
+                    self.bind_transform_feedback_object();
+                }
                 let gl = &self.share.context;
                 gl.bind_buffer_range(target, index, Some(buffer), offset, size);
             },
@@ -836,6 +1367,40 @@ impl Queue {
                 gl.active_texture(glow::TEXTURE0 + index);
                 gl.bind_texture(textype, Some(texture));
             },
+            com::Command::BindImage {
+                unit,
+                texture,
+                level,
+                layer,
+                access,
+                format,
+            } => unsafe {
+                let gl = &self.share.context;
+                gl.bind_image_texture(
+                    unit,
+                    texture,
+                    level,
+                    layer.is_none(),
+                    layer.unwrap_or(0),
+                    access,
+                    format,
+                );
+            },
+            com::Command::SetTextureSrgbDecode(index, textype, decode) => unsafe {
+                if self.share.private_caps.srgb_decode {
+                    let gl = &self.share.context;
+                    gl.active_texture(glow::TEXTURE0 + index);
+                    gl.tex_parameter_i32(
+                        textype,
+                        glow::TEXTURE_SRGB_DECODE_EXT,
+                        if decode {
+                            glow::DECODE_EXT
+                        } else {
+                            glow::SKIP_DECODE_EXT
+                        } as i32,
+                    );
+                }
+            },
             com::Command::BindSampler(index, sampler) => unsafe {
                 let gl = &self.share.context;
                 gl.bind_sampler(index, Some(sampler));
@@ -901,75 +1466,34 @@ impl Queue {
                 ref uniform,
                 buffer,
             } => {
-                let gl = &self.share.context;
-
-                unsafe {
-                    match uniform.utype {
-                        glow::FLOAT => {
-                            let data = Self::get::<f32>(data_buf, buffer)[0];
-                            gl.uniform_1_f32(Some(&(*uniform.location).clone()), data);
-                        }
-                        glow::FLOAT_VEC2 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[f32; 2]>(data_buf, buffer)[0];
-                            gl.uniform_2_f32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::FLOAT_VEC3 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[f32; 3]>(data_buf, buffer)[0];
-                            gl.uniform_3_f32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::FLOAT_VEC4 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[f32; 4]>(data_buf, buffer)[0];
-                            gl.uniform_4_f32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::INT => {
-                            let data = Self::get::<i32>(data_buf, buffer)[0];
-                            gl.uniform_1_i32(Some(&(*uniform.location).clone()), data);
-                        }
-                        glow::INT_VEC2 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[i32; 2]>(data_buf, buffer)[0];
-                            gl.uniform_2_i32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::INT_VEC3 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[i32; 3]>(data_buf, buffer)[0];
-                            gl.uniform_3_i32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::INT_VEC4 => {
-                            // TODO: Remove`mut`
-                            let mut data = Self::get::<[i32; 4]>(data_buf, buffer)[0];
-                            gl.uniform_4_i32_slice(Some(&(*uniform.location).clone()), &mut data);
-                        }
-                        glow::FLOAT_MAT2 => {
-                            let data = Self::get::<[f32; 4]>(data_buf, buffer)[0];
-                            gl.uniform_matrix_2_f32_slice(
-                                Some(&(*uniform.location).clone()),
-                                false,
-                                &data,
-                            );
-                        }
-                        glow::FLOAT_MAT3 => {
-                            let data = Self::get::<[f32; 9]>(data_buf, buffer)[0];
-                            gl.uniform_matrix_3_f32_slice(
-                                Some(&(*uniform.location).clone()),
-                                false,
-                                &data,
-                            );
-                        }
-                        glow::FLOAT_MAT4 => {
-                            let data = Self::get::<[f32; 16]>(data_buf, buffer)[0];
-                            gl.uniform_matrix_4_f32_slice(
-                                Some(&(*uniform.location).clone()),
-                                false,
-                                &data,
-                            );
-                        }
-                        _ => panic!("Unsupported uniform datatype!"),
+                let data = conv::pack_push_constant(uniform.utype, Self::get_raw(data_buf, buffer));
+                let raw =
+                    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4) };
+                Self::set_uniform(&self.share.context, uniform, raw);
+            }
+            com::Command::BindBlockUniform {
+                ref uniform,
+                shadow,
+            } => {
+                // The columns of the matrices are 16 bytes apart in the std140 layout.
+                let (columns, column_size) = match uniform.utype {
+                    glow::FLOAT_MAT2 => (2, 8),
+                    glow::FLOAT_MAT3 => (3, 12),
+                    _ => (1, uniform.size as usize),
+                };
+                let mut data = vec![0u32; uniform.size as usize / 4];
+                for column in 0..columns {
+                    unsafe {
+                        ptr::copy_nonoverlapping(
+                            shadow.0.add(uniform.offset as usize + column * 16),
+                            (data.as_mut_ptr() as *mut u8).add(column * column_size),
+                            column_size,
+                        );
                     }
                 }
+                let raw =
+                    unsafe { slice::from_raw_parts(data.as_ptr() as *const u8, data.len() * 4) };
+                Self::set_uniform(&self.share.context, uniform, raw);
             }
             com::Command::BindRasterizer { rasterizer } => {
                 use hal::pso::FrontFace::*;
@@ -1005,11 +1529,10 @@ impl Queue {
                     Fill => (glow::FILL, glow::POLYGON_OFFSET_FILL),
                 };
 
+                // A dynamic width is set by `SetLineWidth`, but a static one
+                // has to override whatever the previous pipeline left behind.
                 if let hal::pso::State::Static(w) = rasterizer.line_width {
-                    if w != 1.0 {
-                        // Default value already 1.0
-                        unsafe { gl.line_width(w) };
-                    }
+                    unsafe { gl.line_width(w) };
                 }
 
                 //TODO: this is not available in GLES
@@ -1018,9 +1541,32 @@ impl Queue {
                 match rasterizer.depth_bias {
                     Some(hal::pso::State::Static(bias)) => unsafe {
                         gl.enable(gl_offset);
-                        gl.polygon_offset(bias.slope_factor as _, bias.const_factor as _);
+                        self.set_polygon_offset(&bias);
                     },
-                    _ => unsafe { gl.disable(gl_offset) },
+                    // The offset itself is set by `SetDepthBias`.
+                    Some(hal::pso::State::Dynamic) => unsafe { gl.enable(gl_offset) },
+                    None => unsafe { gl.disable(gl_offset) },
+                }
+
+                if self.features.contains(hal::Features::DEPTH_CLAMP) {
+                    match rasterizer.depth_clamping {
+                        true => unsafe { gl.enable(glow::DEPTH_CLAMP) },
+                        false => unsafe { gl.disable(glow::DEPTH_CLAMP) },
+                    }
+                } else if rasterizer.depth_clamping {
+                    log::warn!("Depth clamping is not supported");
+                }
+
+                if self
+                    .features
+                    .contains(hal::Features::CONSERVATIVE_RASTERIZATION)
+                {
+                    match rasterizer.conservative {
+                        true => unsafe { gl.enable(glow::CONSERVATIVE_RASTERIZATION_NV) },
+                        false => unsafe { gl.disable(glow::CONSERVATIVE_RASTERIZATION_NV) },
+                    }
+                } else if rasterizer.conservative {
+                    log::warn!("Conservative rasterization is not supported");
                 }
 
                 if !self.share.info.version.is_embedded {
@@ -1082,6 +1628,51 @@ impl Queue {
                     );
                 }
             },
+            com::Command::SetFramebufferSrgb(enable) => unsafe {
+                if self.share.private_caps.framebuffer_srgb {
+                    if enable {
+                        self.share.context.enable(glow::FRAMEBUFFER_SRGB);
+                    } else {
+                        self.share.context.disable(glow::FRAMEBUFFER_SRGB);
+                    }
+                }
+            },
+            com::Command::SetDepthBias(bias) => unsafe {
+                self.set_polygon_offset(&bias);
+            },
+            com::Command::SetLineWidth(width) => unsafe {
+                self.share.context.line_width(width);
+            },
+            com::Command::BeginTransformFeedback(tf) => {
+                self.bind_transform_feedback_object();
+                let gl = &self.share.context;
+                unsafe {
+                    if tf.rasterizer_discard {
+                        gl.enable(glow::RASTERIZER_DISCARD);
+                    }
+                    gl.begin_transform_feedback(tf.primitive);
+                }
+                self.state.transform_feedback = Some(tf);
+            }
+            com::Command::EndTransformFeedback => match self.state.transform_feedback.take() {
+                Some(tf) => {
+                    let gl = &self.share.context;
+                    unsafe {
+                        gl.end_transform_feedback();
+                        if tf.rasterizer_discard {
+                            gl.disable(glow::RASTERIZER_DISCARD);
+                        }
+                    }
+                }
+                None => log::warn!("Transform feedback is not active"),
+            },
+            com::Command::SetScissorTest(enable) => unsafe {
+                if enable {
+                    self.share.context.enable(glow::SCISSOR_TEST);
+                } else {
+                    self.share.context.disable(glow::SCISSOR_TEST);
+                }
+            },
             com::Command::SetDepthMask(write) => unsafe {
                 self.share.context.depth_mask(write);
             },
@@ -1103,28 +1694,75 @@ impl Queue {
                     }
                 }
             }
+            com::Command::TextureBarrier => {
+                if self.share.private_caps.texture_barrier {
+                    unsafe {
+                        self.share.context.texture_barrier();
+                    }
+                } else {
+                    log::warn!("Texture barriers are not supported, feedback loops are undefined");
+                }
+            }
+            com::Command::InsertDebugMarker(name) => {
+                if self.share.private_caps.debug_markers {
+                    let name = str::from_utf8(Self::get_raw(data_buf, name)).unwrap();
+                    unsafe {
+                        self.share.context.debug_message_insert(
+                            glow::DEBUG_SOURCE_APPLICATION,
+                            glow::DEBUG_TYPE_MARKER,
+                            0,
+                            glow::DEBUG_SEVERITY_NOTIFICATION,
+                            name,
+                        );
+                    }
+                }
+            }
+            com::Command::PushDebugGroup(name) => {
+                if self.share.private_caps.debug_markers {
+                    let name = str::from_utf8(Self::get_raw(data_buf, name)).unwrap();
+                    unsafe {
+                        self.share.context.push_debug_group(
+                            glow::DEBUG_SOURCE_APPLICATION,
+                            0,
+                            name,
+                        );
+                    }
+                }
+            }
+            com::Command::PopDebugGroup => {
+                if self.share.private_caps.debug_markers {
+                    unsafe {
+                        self.share.context.pop_debug_group();
+                    }
+                }
+            }
+            com::Command::WriteTimestamp(query) => unsafe {
+                self.share.context.query_counter(query, glow::TIMESTAMP);
+            },
         }
-        if let Err(err) = self.share.check() {
-            panic!("Error {:?} executing command: {:?}", err, cmd)
-        }
+        self.share.check()
     }
-}
 
-impl hal::queue::Queue<Backend> for Queue {
-    unsafe fn submit<'a, Ic, Iw, Is>(
+    /// Submit command buffers to the queue, stopping at the first command
+    /// that raises an OpenGL error.
+    ///
+    /// Unlike [`submit`](hal::queue::Queue::submit), which reports the errors
+    /// other than running out of memory as a lost device, this tells
+    /// applications which command failed and why. The fence is only
+    /// signaled if all the command buffers were executed.
+    pub unsafe fn submit_raw<'a, Ic>(
         &mut self,
         command_buffers: Ic,
-        _wait_semaphores: Iw,
-        _signal_semaphores: Is,
         fence: Option<&mut native::Fence>,
-    ) where
+    ) -> Result<(), SubmissionError>
+    where
         Ic: Iterator<Item = &'a com::CommandBuffer>,
-        Iw: Iterator<Item = (&'a native::Semaphore, hal::pso::PipelineStage)>,
-        Is: Iterator<Item = &'a native::Semaphore>,
     {
         use crate::pool::BufferMemory;
+        let mut num_command_buffers = 0;
         {
-            for cmd_buf in command_buffers {
+            for (cb_index, cmd_buf) in command_buffers.enumerate() {
+                num_command_buffers = cb_index + 1;
                 let cb = &cmd_buf.data;
                 let memory = cb
                     .memory
@@ -1136,30 +1774,123 @@ impl hal::queue::Queue<Backend> for Queue {
                     BufferMemory::Individual { ref storage, .. } => storage.get(&cb.id).unwrap(),
                 };
 
-                assert!(buffer.commands.len() >= (cb.buf.offset + cb.buf.size) as usize);
-                let commands = &buffer.commands
-                    [cb.buf.offset as usize..(cb.buf.offset + cb.buf.size) as usize];
+                let translated = match cmd_buf.translation {
+                    Some(ref translation) => match translation.wait() {
+                        Ok(commands) => Some(commands),
+                        Err(out_of_range) => {
+                            return Err(SubmissionError {
+                                command_buffer: cb_index,
+                                command: out_of_range.index,
+                                description: out_of_range.description,
+                                error: Error::InvalidValue,
+                            })
+                        }
+                    },
+                    None => None,
+                };
+                let commands = match translated {
+                    Some(ref commands) => &commands[..],
+                    None => {
+                        assert!(buffer.commands.len() >= (cb.buf.offset + cb.buf.size) as usize);
+                        &buffer.commands
+                            [cb.buf.offset as usize..(cb.buf.offset + cb.buf.size) as usize]
+                    }
+                };
                 self.reset_state();
-                for com in commands {
+                if let Some(ref violations) = self.violations {
+                    violations.bind(&self.share.context);
+                }
+                self.base_instance_stats += cmd_buf.base_instance_stats;
+                for (index, com) in commands.iter().enumerate() {
                     log::trace!("Execute command:{:?}", com);
-                    self.process(com, &buffer.data);
+                    if let Some(ref mut violations) = self.violations {
+                        match *com {
+                            com::Command::Draw { .. }
+                            | com::Command::DrawIndexed { .. }
+                            | com::Command::DrawTransformFeedback { .. }
+                            | com::Command::Dispatch(_)
+                            | com::Command::DispatchIndirect(..) => {
+                                violations.begin_draw(&self.share.context, cb_index, index)
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let Err(error) = self.process(com, &buffer.data) {
+                        return Err(SubmissionError {
+                            command_buffer: cb_index,
+                            command: index,
+                            description: format!("{:?}", com),
+                            error,
+                        });
+                    }
                 }
             }
         }
+        if let (Some(violations), Some(validation)) =
+            (self.violations.as_mut(), self.share.gpu_validation.as_ref())
+        {
+            violations.report(&self.share.context, validation);
+        }
 
         if let Some(fence) = fence {
             *fence = if self.share.private_caps.sync {
-                native::Fence::Pending(
-                    self.share
-                        .context
-                        .fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0)
-                        .unwrap(),
-                )
+                let gl = &self.share.context;
+                match gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0) {
+                    Ok(sync) => native::Fence::Pending(sync),
+                    // Creating a fence only fails if the context is lost or out of memory.
+                    Err(message) => {
+                        let error = match Error::from_error_code(gl.get_error()) {
+                            Error::NoError => Error::UnknownError,
+                            error => error,
+                        };
+                        return Err(SubmissionError {
+                            command_buffer: num_command_buffers,
+                            command: 0,
+                            description: format!("FenceSync: {}", message),
+                            error,
+                        });
+                    }
+                }
             } else {
                 self.share.context.flush();
                 native::Fence::Idle { signaled: true }
             }
         }
+        Ok(())
+    }
+}
+
+impl hal::queue::Queue<Backend> for Queue {
+    unsafe fn submit<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        _wait_semaphores: Iw,
+        _signal_semaphores: Is,
+        fence: Option<&mut native::Fence>,
+    ) -> Result<(), hal::device::OomOrDeviceLost>
+    where
+        Ic: Iterator<Item = &'a com::CommandBuffer>,
+        Iw: Iterator<Item = (&'a native::Semaphore, hal::pso::PipelineStage)>,
+        Is: Iterator<Item = &'a native::Semaphore>,
+    {
+        match self.submit_raw(command_buffers, fence) {
+            Ok(()) => self.share.device_status(),
+            // A reset also fails the commands, which only tells how it was noticed.
+            Err(err) if self.share.device_status().is_err() => {
+                log::error!("{}", err);
+                self.share.device_status()
+            }
+            Err(SubmissionError {
+                error: Error::OutOfMemory,
+                ..
+            }) => Err(hal::device::OutOfMemory::Device.into()),
+            // The remaining commands were skipped, leaving the context in a state
+            // the application doesn't know about, which it can't recover from.
+            Err(err) => {
+                log::error!("{}", err);
+                Err(hal::device::DeviceLost.into())
+            }
+        }
     }
 
     unsafe fn present(
@@ -1168,17 +1899,30 @@ impl hal::queue::Queue<Backend> for Queue {
         image: native::SwapchainImage,
         _wait_semaphore: Option<&mut native::Semaphore>,
     ) -> Result<Option<hal::window::Suboptimal>, hal::window::PresentError> {
+        self.frame_base_instance_stats = mem::take(&mut self.base_instance_stats);
         surface.present(image, &self.share.context)
     }
 
-    fn wait_idle(&mut self) -> Result<(), hal::device::OutOfMemory> {
+    fn wait_idle(&mut self) -> Result<(), hal::device::OomOrDeviceLost> {
         unsafe {
             self.share.context.finish();
         }
-        Ok(())
+        self.share.device_status()
     }
 
     fn timestamp_period(&self) -> f32 {
         1.0
     }
+
+    fn timestamp_calibration(&self) -> Option<hal::queue::TimestampCalibration> {
+        if !self.share.private_caps.timestamp_query {
+            return None;
+        }
+        // The current time of the GL server is in the same nanoseconds as the timestamp queries.
+        let gpu_timestamp = unsafe { self.share.context.get_parameter_i64(glow::TIMESTAMP) };
+        Some(hal::queue::TimestampCalibration {
+            gpu_timestamp: gpu_timestamp as u64,
+            cpu_instant: Instant::now(),
+        })
+    }
 }
@@ -0,0 +1,75 @@
+//! Detection of GPU resets (`GL_KHR_robustness`, `GL_ARB_robustness`, `GL_EXT_robustness`).
+//!
+//! `glow` doesn't expose `glGetGraphicsResetStatus`, so its entry point is loaded
+//! here with the loader of the context. Without it, a reset is only detected if
+//! the driver reports `CONTEXT_LOST` from `glGetError`.
+
+use crate::info::{
+    Info,
+    Requirement::{Core, Es, Ext},
+};
+
+use std::{mem, os::raw::c_void};
+
+const GUILTY_CONTEXT_RESET: u32 = 0x8253;
+const INNOCENT_CONTEXT_RESET: u32 = 0x8254;
+const UNKNOWN_CONTEXT_RESET: u32 = 0x8255;
+
+type GetGraphicsResetStatus = unsafe extern "system" fn() -> u32;
+
+/// Entry point querying the reset status of the context.
+pub struct ResetStatusFns {
+    get_graphics_reset_status: GetGraphicsResetStatus,
+}
+
+impl ResetStatusFns {
+    /// Load the entry point, if the implementation supports robustness.
+    pub fn load(info: &Info, loader: &dyn Fn(&str) -> *const c_void) -> Option<Self> {
+        let name = if info.is_supported(&[Core(4, 5), Es(3, 2)]) {
+            "glGetGraphicsResetStatus"
+        } else if info.is_supported(&[Ext("GL_KHR_robustness")]) {
+            if info.version.is_embedded {
+                "glGetGraphicsResetStatusKHR"
+            } else {
+                "glGetGraphicsResetStatus"
+            }
+        } else if info.is_supported(&[Ext("GL_ARB_robustness")]) {
+            "glGetGraphicsResetStatusARB"
+        } else if info.is_supported(&[Ext("GL_EXT_robustness")]) {
+            "glGetGraphicsResetStatusEXT"
+        } else {
+            return None;
+        };
+        let ptr = loader(name);
+        if ptr.is_null() {
+            log::warn!("Robustness is advertised, but {} is missing", name);
+            return None;
+        }
+        Some(ResetStatusFns {
+            get_graphics_reset_status: unsafe { mem::transmute(ptr) },
+        })
+    }
+
+    /// Check if the context has been reset by the GPU since the last call.
+    pub unsafe fn is_reset(&self) -> bool {
+        match (self.get_graphics_reset_status)() {
+            glow::NO_ERROR => false,
+            GUILTY_CONTEXT_RESET => {
+                log::error!("The context was reset by a GPU fault it caused");
+                true
+            }
+            INNOCENT_CONTEXT_RESET => {
+                log::error!("The context was reset by a GPU fault of another context");
+                true
+            }
+            UNKNOWN_CONTEXT_RESET => {
+                log::error!("The context was reset by a GPU fault of unknown origin");
+                true
+            }
+            other => {
+                log::error!("Unexpected reset status 0x{:X}", other);
+                false
+            }
+        }
+    }
+}
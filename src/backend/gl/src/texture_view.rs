@@ -0,0 +1,76 @@
+//! Views of the storage of textures (`GL_ARB_texture_view`, `GL_OES_texture_view`).
+//!
+//! `glow` doesn't expose `glTextureView`, so its entry point is loaded here with
+//! the loader of the context. A texture view is a separate texture object sharing
+//! the storage of another one, with its own format, level and layer ranges, and
+//! sampling parameters. Without it, the views of an image can't reinterpret its
+//! format, nor restrict the levels and layers that are sampled.
+
+use crate::info::{
+    Info,
+    Requirement::{Core, Ext},
+};
+
+use std::{mem, ops::Range, os::raw::c_void};
+
+type TextureView = unsafe extern "system" fn(
+    texture: u32,
+    target: u32,
+    original_texture: u32,
+    internal_format: u32,
+    min_level: u32,
+    num_levels: u32,
+    min_layer: u32,
+    num_layers: u32,
+);
+
+/// Entry point creating texture views.
+pub struct TextureViewFns {
+    texture_view: TextureView,
+}
+
+impl TextureViewFns {
+    /// Load the entry point, if the implementation supports texture views.
+    pub fn load(info: &Info, loader: &dyn Fn(&str) -> *const c_void) -> Option<Self> {
+        let name = if info.is_supported(&[Core(4, 3), Ext("GL_ARB_texture_view")]) {
+            "glTextureView"
+        } else if info.is_supported(&[Ext("GL_OES_texture_view")]) {
+            "glTextureViewOES"
+        } else if info.is_supported(&[Ext("GL_EXT_texture_view")]) {
+            "glTextureViewEXT"
+        } else {
+            return None;
+        };
+        let ptr = loader(name);
+        if ptr.is_null() {
+            log::warn!("Texture views are advertised, but {} is missing", name);
+            return None;
+        }
+        Some(TextureViewFns {
+            texture_view: unsafe { mem::transmute(ptr) },
+        })
+    }
+
+    /// Initialize the newly created `texture` as a view of the levels and layers
+    /// of `original`, which must have an immutable storage.
+    pub unsafe fn texture_view(
+        &self,
+        texture: u32,
+        target: u32,
+        original: u32,
+        internal_format: u32,
+        levels: Range<u32>,
+        layers: Range<u32>,
+    ) {
+        (self.texture_view)(
+            texture,
+            target,
+            original,
+            internal_format,
+            levels.start,
+            levels.end - levels.start,
+            layers.start,
+            layers.end - layers.start,
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! Drawing the vertices captured by transform feedback (`GL_ARB_transform_feedback2`,
+//! `GL_ARB_transform_feedback_instanced`).
+//!
+//! `glow` doesn't expose `glDrawTransformFeedback`, so its entry points are loaded
+//! here with the loader of the context. The vertex count stays on the GPU, instead
+//! of being read back from a query and stalling the queue.
+
+use crate::info::{
+    Info,
+    Requirement::{Core, Ext},
+};
+
+use std::{mem, ops::Range, os::raw::c_void};
+
+type DrawTransformFeedback = unsafe extern "system" fn(mode: u32, id: u32);
+type DrawTransformFeedbackInstanced =
+    unsafe extern "system" fn(mode: u32, id: u32, instance_count: i32);
+
+/// Entry points drawing from a transform feedback object.
+pub struct DrawTransformFeedbackFns {
+    draw: DrawTransformFeedback,
+    draw_instanced: Option<DrawTransformFeedbackInstanced>,
+}
+
+impl DrawTransformFeedbackFns {
+    /// Load the entry points, if the implementation supports transform feedback objects.
+    pub fn load(info: &Info, loader: &dyn Fn(&str) -> *const c_void) -> Option<Self> {
+        if !info.is_supported(&[Core(4, 0), Ext("GL_ARB_transform_feedback2")]) {
+            return None;
+        }
+        let draw = loader("glDrawTransformFeedback");
+        if draw.is_null() {
+            log::warn!(
+                "Transform feedback objects are advertised, but glDrawTransformFeedback is missing"
+            );
+            return None;
+        }
+        let draw_instanced =
+            if info.is_supported(&[Core(4, 2), Ext("GL_ARB_transform_feedback_instanced")]) {
+                let ptr = loader("glDrawTransformFeedbackInstanced");
+                if ptr.is_null() {
+                    None
+                } else {
+                    Some(unsafe { mem::transmute(ptr) })
+                }
+            } else {
+                None
+            };
+        Some(DrawTransformFeedbackFns {
+            draw: unsafe { mem::transmute(draw) },
+            draw_instanced,
+        })
+    }
+
+    /// Draw `instances` of the vertices last captured by the transform feedback object `id`.
+    pub unsafe fn draw(&self, mode: u32, id: u32, instances: Range<hal::InstanceCount>) {
+        if instances.start != 0 {
+            log::error!("Transform feedback draws with non-zero base instance are not supported");
+        } else if instances.end == 1 {
+            (self.draw)(mode, id);
+        } else if let Some(draw_instanced) = self.draw_instanced {
+            draw_instanced(mode, id, instances.end as _);
+        } else {
+            log::error!("Instanced transform feedback draws are not supported");
+        }
+    }
+}
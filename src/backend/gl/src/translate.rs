@@ -0,0 +1,313 @@
+//! Translation of command buffers on worker threads.
+//!
+//! Submitting a command buffer executes its recorded commands one by one on the
+//! submitting thread. With `Device::set_command_translation_threads`, the command
+//! buffers of the pools created with `RESET_INDIVIDUAL` are instead handed to a
+//! pool of worker threads when they are finished. These check that the commands
+//! only read recorded data, failing the submission of the command buffer
+//! otherwise, and flatten them into a stream without the commands setting a
+//! state that is already set, so that the submission only executes the GL calls
+//! that are left.
+//!
+//! The state is tracked from the start of each command buffer, since the queue
+//! resets it between command buffers.
+
+use crate::{
+    command::{BufferSlice, Command},
+    native as n, FastHashMap,
+};
+
+use hal::pso;
+use parking_lot::{Condvar, MappedMutexGuard, Mutex, MutexGuard};
+
+use std::{
+    fmt,
+    hash::Hash,
+    sync::{mpsc, Arc},
+    thread,
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads translating the finished command buffers.
+pub(crate) struct TranslationPool {
+    // Dropped before joining the workers, to let them stop.
+    sender: Mutex<Option<mpsc::Sender<Job>>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl fmt::Debug for TranslationPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TranslationPool")
+            .field("threads", &self.workers.len())
+            .finish()
+    }
+}
+
+impl TranslationPool {
+    pub(crate) fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|index| {
+                let receiver = Arc::clone(&receiver);
+                thread::Builder::new()
+                    .name(format!("gfx-gl-translation-{}", index))
+                    .spawn(move || loop {
+                        let job = receiver.lock().recv();
+                        match job {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    })
+                    .expect("Unable to spawn a command translation thread")
+            })
+            .collect();
+        TranslationPool {
+            sender: Mutex::new(Some(sender)),
+            workers,
+        }
+    }
+
+    /// Start translating `commands`, reading data within the first `data_len`
+    /// bytes of the data buffer of the command buffer.
+    pub(crate) fn translate(&self, commands: Vec<Command>, data_len: usize) -> Translation {
+        let translation = Translation::default();
+        let result = Arc::clone(&translation.result);
+        let job: Job = Box::new(move || {
+            let stream = translate(commands, data_len);
+            let (ref commands, ref done) = *result;
+            *commands.lock() = Some(stream);
+            done.notify_all();
+        });
+        if let Some(ref sender) = *self.sender.lock() {
+            // The workers only stop once the sender is dropped.
+            if let Err(mpsc::SendError(job)) = sender.send(job) {
+                job();
+            }
+        }
+        translation
+    }
+}
+
+impl Drop for TranslationPool {
+    fn drop(&mut self) {
+        self.sender.lock().take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// A recorded command reading out of the data of its command buffer.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OutOfRange {
+    /// Index of the command within the command buffer.
+    pub index: usize,
+    /// Description of the command.
+    pub description: String,
+}
+
+type TranslationResult = Result<Vec<Command>, OutOfRange>;
+
+/// Commands of a command buffer, being translated by a `TranslationPool`.
+#[derive(Debug, Default)]
+pub(crate) struct Translation {
+    result: Arc<(Mutex<Option<TranslationResult>>, Condvar)>,
+}
+
+impl Translation {
+    /// Wait for the translation to complete, returning the commands to execute,
+    /// or the first command reading out of the recorded data.
+    pub(crate) fn wait(&self) -> Result<MappedMutexGuard<'_, [Command]>, OutOfRange> {
+        let (ref commands, ref done) = *self.result;
+        let mut guard = commands.lock();
+        while guard.is_none() {
+            done.wait(&mut guard);
+        }
+        if let Some(Err(ref error)) = *guard {
+            return Err(error.clone());
+        }
+        Ok(MutexGuard::map(guard, |result| match *result {
+            Some(Ok(ref mut commands)) => commands.as_mut_slice(),
+            _ => unreachable!(),
+        }))
+    }
+}
+
+/// State set by the translated commands, unknown if `None`.
+#[derive(Default)]
+struct State {
+    program: Option<n::Program>,
+    index_buffer: Option<n::RawBuffer>,
+    textures: FastHashMap<u32, (n::Texture, n::TextureTarget)>,
+    samplers: FastHashMap<u32, n::Sampler>,
+    buffer_ranges: FastHashMap<(u32, u32), (n::RawBuffer, i32, i32)>,
+    rasterizer: Option<pso::Rasterizer>,
+    depth: Option<Option<pso::Comparison>>,
+    depth_bias: Option<pso::DepthBias>,
+    depth_mask: Option<bool>,
+    stencil_mask: Option<pso::StencilValue>,
+    line_width: Option<f32>,
+    scissor_test: Option<bool>,
+    blend_color: Option<pso::ColorValue>,
+}
+
+/// Set `state` to `value`, returning whether it changed.
+fn set<T: PartialEq>(state: &mut Option<T>, value: T) -> bool {
+    if state.as_ref() == Some(&value) {
+        false
+    } else {
+        *state = Some(value);
+        true
+    }
+}
+
+/// Set the state of `key` to `value`, returning whether it changed.
+fn set_at<K: Eq + Hash, T: Copy + PartialEq>(
+    states: &mut FastHashMap<K, T>,
+    key: K,
+    value: T,
+) -> bool {
+    states.insert(key, value) != Some(value)
+}
+
+fn data_slices(command: &Command) -> impl Iterator<Item = BufferSlice> {
+    let (first, second) = match *command {
+        Command::BindUniform { buffer, .. } => (Some(buffer), None),
+        Command::SetViewports {
+            viewport_ptr,
+            depth_range_ptr,
+            ..
+        } => (Some(viewport_ptr), Some(depth_range_ptr)),
+        Command::SetScissors(_, slice)
+        | Command::InsertDebugMarker(slice)
+        | Command::PushDebugGroup(slice) => (Some(slice), None),
+        _ => (None, None),
+    };
+    first.into_iter().chain(second)
+}
+
+fn translate(commands: Vec<Command>, data_len: usize) -> TranslationResult {
+    let mut state = State::default();
+    let mut stream = Vec::with_capacity(commands.len());
+
+    for (index, command) in commands.into_iter().enumerate() {
+        if data_slices(&command).any(|slice| (slice.offset + slice.size) as usize > data_len) {
+            return Err(OutOfRange {
+                index,
+                description: format!("{:?}", command),
+            });
+        }
+
+        let keep = match command {
+            Command::BindProgram(program) => set(&mut state.program, program),
+            Command::BindIndexBuffer(buffer) => set(&mut state.index_buffer, buffer),
+            Command::BindTexture(unit, texture, target) => {
+                set_at(&mut state.textures, unit, (texture, target))
+            }
+            Command::BindSampler(unit, sampler) => set_at(&mut state.samplers, unit, sampler),
+            Command::BindBufferRange(target, index, buffer, offset, size) => set_at(
+                &mut state.buffer_ranges,
+                (target, index),
+                (buffer, offset, size),
+            ),
+            Command::BindRasterizer { rasterizer } => {
+                let changed = set(&mut state.rasterizer, rasterizer);
+                if changed {
+                    // The rasterizer also sets the line width and the depth bias.
+                    state.line_width = None;
+                    state.depth_bias = None;
+                }
+                changed
+            }
+            Command::BindDepth(depth) => set(&mut state.depth, depth),
+            Command::SetDepthBias(bias) => {
+                state.rasterizer = None;
+                set(&mut state.depth_bias, bias)
+            }
+            Command::SetLineWidth(width) => {
+                state.rasterizer = None;
+                set(&mut state.line_width, width)
+            }
+            Command::SetDepthMask(write) => set(&mut state.depth_mask, write),
+            Command::SetStencilMask(value) => set(&mut state.stencil_mask, value),
+            Command::SetStencilMaskSeparate(_) => {
+                state.stencil_mask = None;
+                true
+            }
+            Command::SetScissorTest(enable) => set(&mut state.scissor_test, enable),
+            Command::SetBlendColor(color) => set(&mut state.blend_color, color),
+            // Commands leaving the tracked state as is.
+            Command::Draw { .. }
+            | Command::DrawIndexed { .. }
+            | Command::DrawTransformFeedback { .. }
+            | Command::Dispatch(_)
+            | Command::DispatchIndirect(..)
+            | Command::BindUniform { .. }
+            | Command::BindBlockUniform { .. }
+            | Command::BindAttribute(..)
+            | Command::BindImage { .. }
+            | Command::SetViewports { .. }
+            | Command::SetScissors(..)
+            | Command::SetPatchSize(_)
+            | Command::SetBlend(_)
+            | Command::SetBlendSlot(..)
+            | Command::SetColorMask(..)
+            | Command::SetDrawColorBuffers(_)
+            | Command::SetFramebufferSrgb(_)
+            | Command::SetTextureSrgbDecode(..)
+            | Command::SetTextureSamplerSettings(..)
+            | Command::ClearBufferColorF(..)
+            | Command::ClearBufferColorU(..)
+            | Command::ClearBufferColorI(..)
+            | Command::ClearBufferDepthStencil(..)
+            | Command::MemoryBarrier(_)
+            | Command::TextureBarrier
+            | Command::InsertDebugMarker(_)
+            | Command::PushDebugGroup(_)
+            | Command::PopDebugGroup => true,
+            // The others may bind resources behind the back of the tracked state,
+            // e.g. to copy them.
+            _ => {
+                state = State::default();
+                true
+            }
+        };
+        if keep {
+            stream.push(command);
+        }
+    }
+
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker(offset: u32, size: u32) -> Command {
+        Command::InsertDebugMarker(BufferSlice { offset, size })
+    }
+
+    #[test]
+    fn redundant_states_are_skipped() {
+        let commands = vec![
+            Command::SetDepthMask(true),
+            Command::SetDepthMask(true),
+            marker(0, 4),
+            Command::SetDepthMask(false),
+        ];
+        let stream = translate(commands, 4).unwrap();
+        assert_eq!(stream.len(), 3);
+        assert!(matches!(stream[2], Command::SetDepthMask(false)));
+    }
+
+    #[test]
+    fn reading_out_of_the_recorded_data_fails() {
+        let commands = vec![Command::SetDepthMask(true), marker(0, 4), marker(4, 4)];
+        let error = translate(commands, 6).unwrap_err();
+        assert_eq!(error.index, 2);
+        assert_eq!(error.description, format!("{:?}", marker(4, 4)));
+    }
+}
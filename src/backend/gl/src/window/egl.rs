@@ -1,10 +1,17 @@
 //! EGL-based surface and swapchain.
 
-use crate::{conv, native, GlContainer, PhysicalDevice, Starc};
+use crate::{
+    conv, native, GlContainer, PhysicalDevice, Profile, Share, Starc, WorkaroundOverrides,
+};
 use glow::HasContext;
 use hal::{image, window as w};
 use parking_lot::Mutex;
-use std::{os::raw, ptr};
+use std::{
+    collections::VecDeque,
+    os::raw,
+    ptr,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug)]
 pub struct Swapchain {
@@ -14,12 +21,90 @@ pub struct Swapchain {
     extent: w::Extent2D,
     format: native::TextureFormat,
     channel: hal::format::ChannelType,
+    color_space: w::ColorSpace,
+    share: Starc<Share>,
+    /// Maximum number of frames queued for presentation.
+    frame_latency: usize,
+    /// Fences inserted after each swap, emulating the frame latency waitable object.
+    frame_fences: VecDeque<glow::Fence>,
+    /// Number of vertical blanks to wait for on present.
+    swap_interval: u32,
+    timings: PresentTimings,
+    last_present: Option<Instant>,
+    /// Set when the surface was resumed with a window of another extent.
+    out_of_date: bool,
+}
+
+/// How presents of multiple surfaces are synchronized with the vertical blank.
+///
+/// `eglSwapBuffers` blocks until the vertical blank for every surface with a non-zero
+/// swap interval, so presenting several windows in a row takes as many refresh periods.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VsyncCoordination {
+    /// Every swapchain waits for the vertical blank on its own.
+    Independent,
+    /// Only the first present of every round over the surfaces waits for the vertical blank,
+    /// the other ones are presented right after it.
+    Grouped,
+}
+
+impl Default for VsyncCoordination {
+    fn default() -> Self {
+        VsyncCoordination::Independent
+    }
+}
+
+#[derive(Debug, Default)]
+struct VsyncGroup {
+    mode: VsyncCoordination,
+    next_surface_id: usize,
+    /// Surfaces presented since the last present that waited for the vertical blank.
+    presented: Vec<usize>,
+}
+
+impl VsyncGroup {
+    /// Returns the swap interval to use for this present of the surface.
+    fn swap_interval(&mut self, surface_id: usize, interval: u32) -> u32 {
+        match self.mode {
+            VsyncCoordination::Independent => interval,
+            // Surfaces presenting immediately don't take part in the round.
+            VsyncCoordination::Grouped if interval == 0 => 0,
+            VsyncCoordination::Grouped => {
+                if self.presented.is_empty() || self.presented.contains(&surface_id) {
+                    self.presented.clear();
+                    self.presented.push(surface_id);
+                    interval
+                } else {
+                    self.presented.push(surface_id);
+                    0
+                }
+            }
+        }
+    }
+}
+
+/// Frame time statistics of a swapchain, measured between consecutive presents.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PresentTimings {
+    /// Number of presented frames.
+    pub frames: u64,
+    /// Number of presents that waited for the vertical blank.
+    pub vsync_waits: u64,
+    /// Time between the last two presents.
+    pub last: Duration,
+    /// Moving average of the time between presents.
+    pub average: Duration,
+    /// Longest time between two presents.
+    pub max: Duration,
 }
 
 #[derive(Debug)]
 pub struct Instance {
     wsi_library: Option<libloading::Library>,
+    /// Created without a window system, see `Instance::create_headless`.
+    headless: bool,
     inner: Mutex<Inner>,
+    vsync_group: Starc<Mutex<VsyncGroup>>,
 }
 
 #[derive(Debug)]
@@ -28,19 +113,32 @@ pub struct Inner {
     version: (i32, i32),
     supports_native_window: bool,
     display: egl::Display,
+    /// Extensions of the display.
+    display_extensions: String,
     config: egl::Config,
     context: egl::Context,
     /// Dummy pbuffer (1x1).
     /// Required for `eglMakeCurrent` on platforms that doesn't supports `EGL_KHR_surfaceless_context`.
     pbuffer: Option<egl::Surface>,
     wl_display: Option<*mut raw::c_void>,
+    /// Profile the enumerated adapters are restricted to.
+    profile: Profile,
+    /// Workarounds forced on or off on the enumerated adapters.
+    workaround_overrides: WorkaroundOverrides,
+    /// Options the context was created with.
+    flags: hal::InstanceFlags,
 }
 
 unsafe impl Send for Instance {}
 unsafe impl Sync for Instance {}
 
+const EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT: i32 = 0x30BF;
+const EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT: i32 = 0x3138;
+const EGL_LOSE_CONTEXT_ON_RESET_EXT: i32 = 0x31BF;
+const EGL_CONTEXT_OPENGL_NO_ERROR_KHR: i32 = 0x31B3;
 const EGL_PLATFORM_WAYLAND_KHR: u32 = 0x31D8;
 const EGL_PLATFORM_X11_KHR: u32 = 0x31D5;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
 
 type XOpenDisplayFun =
     unsafe extern "system" fn(display_name: *const raw::c_char) -> *mut raw::c_void;
@@ -148,11 +246,34 @@ fn choose_config(
     Err(hal::UnsupportedBackend)
 }
 
+/// Load the EGL library, returning it with its client extensions.
+fn load_egl() -> Result<(Starc<egl::DynamicInstance<egl::EGL1_4>>, String), hal::UnsupportedBackend>
+{
+    let egl = match unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() } {
+        Ok(egl) => Starc::new(egl),
+        Err(e) => {
+            log::warn!("Unable to open libEGL.so: {:?}", e);
+            return Err(hal::UnsupportedBackend);
+        }
+    };
+
+    let client_extensions = egl.query_string(None, egl::EXTENSIONS);
+
+    let client_ext_str = match client_extensions {
+        Ok(ext) => ext.to_string_lossy().into_owned(),
+        Err(_) => String::new(),
+    };
+    log::info!("Client extensions: {:?}", client_ext_str);
+
+    Ok((egl, client_ext_str))
+}
+
 impl Inner {
     fn create(
         egl: Starc<egl::DynamicInstance<egl::EGL1_4>>,
         display: egl::Display,
         wsi_library: Option<&libloading::Library>,
+        flags: hal::InstanceFlags,
     ) -> Result<Self, hal::UnsupportedBackend> {
         let version = egl
             .initialize(display)
@@ -161,7 +282,8 @@ impl Inner {
         let display_extensions = egl
             .query_string(Some(display), egl::EXTENSIONS)
             .unwrap()
-            .to_string_lossy();
+            .to_string_lossy()
+            .into_owned();
         log::info!(
             "Display vendor {:?}, version {:?}, extensions: {:?}",
             vendor,
@@ -187,24 +309,66 @@ impl Inner {
         let (config, supports_native_window) = choose_config(&egl, display)?;
         egl.bind_api(egl::OPENGL_ES_API).unwrap();
 
+        let mut flags = flags;
+        if flags.contains(hal::InstanceFlags::ROBUSTNESS)
+            && !display_extensions.contains("EGL_EXT_create_context_robustness")
+        {
+            log::warn!("Robust contexts are not supported");
+            flags -= hal::InstanceFlags::ROBUSTNESS;
+        }
+        if flags.contains(hal::InstanceFlags::NO_ERROR) {
+            if !display_extensions.contains("EGL_KHR_create_context_no_error") {
+                log::warn!("Contexts without errors are not supported");
+                flags -= hal::InstanceFlags::NO_ERROR;
+            } else if flags.intersects(
+                hal::InstanceFlags::DEBUG
+                    | hal::InstanceFlags::VALIDATION
+                    | hal::InstanceFlags::ROBUSTNESS,
+            ) {
+                log::warn!("Contexts without errors can't be debugged, validated, or robust");
+                flags -= hal::InstanceFlags::NO_ERROR;
+            }
+        }
+
         //TODO: make it so `Device` == EGL Context
-        let mut context_attributes = vec![
-            egl::CONTEXT_CLIENT_VERSION,
-            3, // Request GLES 3.0 or higher
-        ];
-        if cfg!(debug_assertions) && wsi_library.is_none() && !cfg!(target_os = "android") {
-            //TODO: figure out why this is needed
-            context_attributes.push(egl::CONTEXT_OPENGL_DEBUG);
-            context_attributes.push(egl::TRUE as _);
-        }
-        context_attributes.push(egl::NONE as _);
-        let context = match egl.create_context(display, config, None, &context_attributes) {
-            Ok(context) => context,
-            Err(e) => {
-                log::warn!("unable to create GLES 3.x context: {:?}", e);
-                return Err(hal::UnsupportedBackend);
+        let mut context = None;
+        // Request GLES 3.0 or higher, falling back to GLES 2.0
+        for &client_version in [3, 2].iter() {
+            let mut context_attributes = vec![egl::CONTEXT_CLIENT_VERSION, client_version];
+            if flags.contains(hal::InstanceFlags::DEBUG)
+                && wsi_library.is_none()
+                && !cfg!(target_os = "android")
+            {
+                //TODO: figure out why this is needed
+                context_attributes.push(egl::CONTEXT_OPENGL_DEBUG);
+                context_attributes.push(egl::TRUE as _);
             }
-        };
+            if flags.contains(hal::InstanceFlags::ROBUSTNESS) {
+                context_attributes.push(EGL_CONTEXT_OPENGL_ROBUST_ACCESS_EXT);
+                context_attributes.push(egl::TRUE as _);
+                context_attributes.push(EGL_CONTEXT_OPENGL_RESET_NOTIFICATION_STRATEGY_EXT);
+                context_attributes.push(EGL_LOSE_CONTEXT_ON_RESET_EXT);
+            }
+            if flags.contains(hal::InstanceFlags::NO_ERROR) {
+                context_attributes.push(EGL_CONTEXT_OPENGL_NO_ERROR_KHR);
+                context_attributes.push(egl::TRUE as _);
+            }
+            context_attributes.push(egl::NONE as _);
+            match egl.create_context(display, config, None, &context_attributes) {
+                Ok(raw) => {
+                    context = Some(raw);
+                    break;
+                }
+                Err(e) => {
+                    log::warn!(
+                        "unable to create GLES {}.x context: {:?}",
+                        client_version,
+                        e
+                    );
+                }
+            }
+        }
+        let context = context.ok_or(hal::UnsupportedBackend)?;
 
         // Testing if context can be binded without surface
         // and creating dummy pbuffer surface if not.
@@ -226,12 +390,16 @@ impl Inner {
         Ok(Self {
             egl,
             display,
+            display_extensions,
             version,
             supports_native_window,
             config,
             context,
             pbuffer,
             wl_display: None,
+            profile: Profile::default(),
+            workaround_overrides: WorkaroundOverrides::default(),
+            flags,
         })
     }
 }
@@ -248,22 +416,17 @@ impl Drop for Inner {
 }
 
 impl hal::Instance<crate::Backend> for Instance {
-    fn create(_: &str, _: u32) -> Result<Self, hal::UnsupportedBackend> {
-        let egl = match unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() } {
-            Ok(egl) => Starc::new(egl),
-            Err(e) => {
-                log::warn!("Unable to open libEGL.so: {:?}", e);
-                return Err(hal::UnsupportedBackend);
-            }
-        };
-
-        let client_extensions = egl.query_string(None, egl::EXTENSIONS);
+    fn create(name: &str, version: u32) -> Result<Self, hal::UnsupportedBackend> {
+        Self::create_with_flags(name, version, hal::InstanceFlags::default())
+    }
 
-        let client_ext_str = match client_extensions {
-            Ok(ext) => ext.to_string_lossy().into_owned(),
-            Err(_) => String::new(),
-        };
-        log::info!("Client extensions: {:?}", client_ext_str);
+    fn create_with_flags(
+        _: &str,
+        _: u32,
+        flags: hal::InstanceFlags,
+    ) -> Result<Self, hal::UnsupportedBackend> {
+        let flags = flags.with_env();
+        let (egl, client_ext_str) = load_egl()?;
 
         let mut wsi_library = None;
 
@@ -304,11 +467,13 @@ impl hal::Instance<crate::Backend> for Instance {
             egl.get_display(egl::DEFAULT_DISPLAY).unwrap()
         };
 
-        let inner = Inner::create(egl.clone(), display, wsi_library.as_ref())?;
+        let inner = Inner::create(egl.clone(), display, wsi_library.as_ref(), flags)?;
 
         Ok(Instance {
             inner: Mutex::new(inner),
             wsi_library,
+            headless: false,
+            vsync_group: Starc::new(Mutex::new(VsyncGroup::default())),
         })
     }
 
@@ -324,16 +489,27 @@ impl hal::Instance<crate::Backend> for Instance {
             )
             .unwrap();
 
-        let context = unsafe {
-            glow::Context::from_loader_function(|name| {
-                inner
-                    .egl
-                    .get_proc_address(name)
-                    .map_or(ptr::null(), |p| p as *const _)
-            })
+        let loader = |name: &str| -> *const std::os::raw::c_void {
+            inner
+                .egl
+                .get_proc_address(name)
+                .map_or(ptr::null(), |p| p as *const _)
         };
+        let context = unsafe { glow::Context::from_loader_function(loader) };
+        let dma_buf = crate::memory_object::DmaBufFns::load(
+            inner.display.as_ptr(),
+            &inner.display_extensions,
+            &loader,
+        );
         // Create physical device
-        vec![PhysicalDevice::new_adapter(context)]
+        vec![PhysicalDevice::new_adapter(
+            context,
+            inner.profile,
+            inner.workaround_overrides,
+            inner.flags,
+            Some(&loader),
+            dma_buf,
+        )]
     }
 
     #[cfg_attr(target_os = "macos", allow(unused, unused_mut, unreachable_code))]
@@ -341,160 +517,46 @@ impl hal::Instance<crate::Backend> for Instance {
         &self,
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
     ) -> Result<Surface, w::InitError> {
-        use raw_window_handle::RawWindowHandle as Rwh;
+        if self.headless {
+            log::error!("Surfaces can't be created from a headless instance");
+            return Err(w::InitError::WindowSystemUnavailable);
+        }
 
         let mut inner = self.inner.lock();
-        let mut wl_window = None;
-        #[cfg(not(any(target_os = "android", target_os = "macos")))]
-        let (mut temp_xlib_handle, mut temp_xcb_handle);
-        let native_window_ptr = match has_handle.raw_window_handle() {
-            #[cfg(not(any(target_os = "android", target_os = "macos")))]
-            Rwh::Xlib(handle) => {
-                temp_xlib_handle = handle.window;
-                &mut temp_xlib_handle as *mut _ as *mut std::ffi::c_void
-            }
-            #[cfg(not(any(target_os = "android", target_os = "macos")))]
-            Rwh::Xcb(handle) => {
-                temp_xcb_handle = handle.window;
-                &mut temp_xcb_handle as *mut _ as *mut std::ffi::c_void
-            }
-            #[cfg(target_os = "android")]
-            Rwh::Android(handle) => handle.a_native_window as *mut _ as *mut std::ffi::c_void,
-            #[cfg(not(any(target_os = "android", target_os = "macos")))]
-            Rwh::Wayland(handle) => {
-                /* Wayland displays are not sharable between surfaces so if the
-                 * surface we receive from this handle is from a different
-                 * display, we must re-initialize the context.
-                 *
-                 * See gfx-rs/gfx#3545
-                 */
-                if inner
-                    .wl_display
-                    .map(|ptr| ptr != handle.display)
-                    .unwrap_or(true)
-                {
-                    use std::ops::DerefMut;
-                    let display_attributes = [egl::ATTRIB_NONE];
-                    let display = inner
-                        .egl
-                        .upcast::<egl::EGL1_5>()
-                        .unwrap()
-                        .get_platform_display(
-                            EGL_PLATFORM_WAYLAND_KHR,
-                            handle.display,
-                            &display_attributes,
-                        )
-                        .unwrap();
-
-                    let new_inner =
-                        Inner::create(inner.egl.clone(), display, self.wsi_library.as_ref())
-                            .map_err(|_| w::InitError::UnsupportedWindowHandle)?;
-
-                    let old_inner = std::mem::replace(inner.deref_mut(), new_inner);
-                    inner.wl_display = Some(handle.display);
-                    drop(old_inner);
-                }
+        let (raw, wl_window) = self.create_window_surface(&mut inner, has_handle, true)?;
 
-                let wl_egl_window_create: libloading::Symbol<WlEglWindowCreateFun> = self
-                    .wsi_library
-                    .as_ref()
-                    .expect("unsupported window")
-                    .get(b"wl_egl_window_create")
-                    .unwrap();
-                let result = wl_egl_window_create(handle.surface, 640, 480) as *mut _
-                    as *mut std::ffi::c_void;
-                wl_window = Some(result);
-                result
-            }
-            other => {
-                log::error!("Unsupported window: {:?}", other);
-                return Err(w::InitError::UnsupportedWindowHandle);
-            }
+        let id = {
+            let mut group = self.vsync_group.lock();
+            group.next_surface_id += 1;
+            group.next_surface_id
         };
-
-        let mut attributes = vec![
-            egl::RENDER_BUFFER as usize,
-            if cfg!(target_os = "android") {
-                egl::BACK_BUFFER as usize
-            } else {
-                egl::SINGLE_BUFFER as usize
-            },
-        ];
-        if inner.version >= (1, 5) {
-            // Always enable sRGB in EGL 1.5
-            attributes.push(egl::GL_COLORSPACE as usize);
-            attributes.push(egl::GL_COLORSPACE_SRGB as usize);
-        }
-        attributes.push(egl::ATTRIB_NONE);
-
-        let raw = if let Some(egl) = inner.egl.upcast::<egl::EGL1_5>() {
-            egl.create_platform_window_surface(
-                inner.display,
-                inner.config,
-                native_window_ptr,
-                &attributes,
-            )
-            .map_err(|e| {
-                log::warn!("Error in create_platform_window_surface: {:?}", e);
-                w::InitError::UnsupportedWindowHandle
-            })
-        } else {
-            let attributes_i32: Vec<i32> = attributes.iter().map(|a| (*a as i32).into()).collect();
-            inner
-                .egl
-                .create_window_surface(
-                    inner.display,
-                    inner.config,
-                    native_window_ptr,
-                    Some(&attributes_i32),
-                )
-                .map_err(|e| {
-                    log::warn!("Error in create_platform_window_surface: {:?}", e);
-                    w::InitError::UnsupportedWindowHandle
-                })
-        }?;
-
-        #[cfg(target_os = "android")]
-        {
-            let format = inner
-                .egl
-                .get_config_attrib(inner.display, inner.config, egl::NATIVE_VISUAL_ID)
-                .unwrap();
-
-            let ret = ANativeWindow_setBuffersGeometry(native_window_ptr, 0, 0, format);
-
-            if ret != 0 {
-                log::error!("Error returned from ANativeWindow_setBuffersGeometry");
-                return Err(w::InitError::UnsupportedWindowHandle);
-            }
-        }
+        // The swap intervals of EGL take the place of `WGL_EXT_swap_control` and
+        // `GLX_EXT_swap_control`, the contexts being created with EGL on every platform.
+        let immediate = inner
+            .egl
+            .get_config_attrib(inner.display, inner.config, egl::MIN_SWAP_INTERVAL)
+            .map_or(false, |min| min == 0);
 
         Ok(Surface {
             egl: inner.egl.clone(),
-            raw,
+            raw: Some(raw),
             display: inner.display,
             context: inner.context,
             presentable: inner.supports_native_window,
             pbuffer: inner.pbuffer,
             wl_window,
+            id,
+            vsync_group: self.vsync_group.clone(),
+            swap_interval: None,
+            immediate,
+            frame_latency: None,
             swapchain: None,
         })
     }
 
     unsafe fn destroy_surface(&self, surface: Surface) {
-        let inner = self.inner.lock();
-        inner
-            .egl
-            .destroy_surface(inner.display, surface.raw)
-            .unwrap();
-        if let Some(wl_window) = surface.wl_window {
-            let wl_egl_window_destroy: libloading::Symbol<WlEglWindowDestroyFun> = self
-                .wsi_library
-                .as_ref()
-                .expect("unsupported window")
-                .get(b"wl_egl_window_destroy")
-                .unwrap();
-            wl_egl_window_destroy(wl_window)
+        if let Some(raw) = surface.raw {
+            self.destroy_window_surface(raw, surface.wl_window);
         }
     }
 
@@ -513,12 +575,22 @@ impl hal::Instance<crate::Backend> for Instance {
 #[derive(Debug)]
 pub struct Surface {
     egl: Starc<egl::DynamicInstance<egl::EGL1_4>>,
-    raw: egl::Surface,
+    /// Window surface, `None` while suspended, see `Instance::suspend_surface`.
+    raw: Option<egl::Surface>,
     display: egl::Display,
     context: egl::Context,
     pbuffer: Option<egl::Surface>,
     presentable: bool,
     wl_window: Option<*mut raw::c_void>,
+    id: usize,
+    vsync_group: Starc<Mutex<VsyncGroup>>,
+    /// Swap interval currently set on the EGL surface.
+    swap_interval: Option<u32>,
+    /// The config supports a swap interval of 0, presenting without waiting
+    /// for the vertical blank.
+    immediate: bool,
+    /// Latency set without a swapchain, replacing the one of the next configuration.
+    frame_latency: Option<u32>,
     pub(crate) swapchain: Option<Swapchain>,
 }
 
@@ -533,6 +605,9 @@ impl w::PresentationSurface<crate::Backend> for Surface {
         device: &crate::Device,
         config: w::SwapchainConfig,
     ) -> Result<(), w::SwapchainError> {
+        if let w::ColorSpace::ExtendedSrgbLinear | w::ColorSpace::Hdr10St2084 = config.color_space {
+            return Err(w::SwapchainError::UnsupportedColorSpace);
+        }
         self.unconfigure_swapchain(device);
 
         if let Some(window) = self.wl_window {
@@ -576,6 +651,22 @@ impl w::PresentationSurface<crate::Backend> for Surface {
             extent: config.extent,
             format: desc.tex_internal,
             channel: config.format.base_format().1,
+            color_space: config.color_space,
+            share: device.share.clone(),
+            frame_latency: self
+                .frame_latency
+                .take()
+                .or(config.frame_latency)
+                .unwrap_or(config.image_count)
+                .max(1) as usize,
+            frame_fences: VecDeque::new(),
+            swap_interval: match config.present_mode {
+                w::PresentMode::IMMEDIATE => 0,
+                _ => 1,
+            },
+            timings: PresentTimings::default(),
+            last_present: None,
+            out_of_date: false,
         });
 
         Ok(())
@@ -586,6 +677,9 @@ impl w::PresentationSurface<crate::Backend> for Surface {
         if let Some(sc) = self.swapchain.take() {
             gl.delete_renderbuffer(sc.renderbuffer);
             gl.delete_framebuffer(sc.framebuffer);
+            for fence in sc.frame_fences {
+                gl.delete_sync(fence);
+            }
         }
     }
 
@@ -594,10 +688,88 @@ impl w::PresentationSurface<crate::Backend> for Surface {
         _timeout_ns: u64,
     ) -> Result<(Self::SwapchainImage, Option<w::Suboptimal>), w::AcquireError> {
         let sc = self.swapchain.as_ref().unwrap();
+        if self.raw.is_none() || sc.out_of_date {
+            return Err(w::OutOfDate.into());
+        }
         let sc_image =
             native::SwapchainImage::new(sc.renderbuffer, sc.format, sc.extent, sc.channel);
         Ok((sc_image, None))
     }
+
+    unsafe fn wait_for_available_frame(&mut self, timeout_ns: u64) -> Result<(), w::AcquireError> {
+        let sc = match self.swapchain {
+            Some(ref mut sc) => sc,
+            None => return Err(w::OutOfDate.into()),
+        };
+        if sc.frame_fences.len() < sc.frame_latency {
+            return Ok(());
+        }
+        // The fences can only be waited for with the context current.
+        self.egl
+            .make_current(self.display, self.pbuffer, self.pbuffer, Some(self.context))
+            .map_err(map_acquire_error)?;
+        let gl = &sc.share.context;
+        let fence = sc.frame_fences[0];
+        // The timeout of a single wait is limited to `i32::MAX` nanoseconds
+        // (about 2.1 seconds) by glow, longer ones are split into several waits.
+        let mut remaining_ns = timeout_ns;
+        loop {
+            let wait_ns = remaining_ns.min(i32::MAX as u64);
+            match gl.client_wait_sync(fence, glow::SYNC_FLUSH_COMMANDS_BIT, wait_ns as i32) {
+                glow::TIMEOUT_EXPIRED if remaining_ns > wait_ns => remaining_ns -= wait_ns,
+                glow::TIMEOUT_EXPIRED => return Err(w::AcquireError::NotReady { timeout: true }),
+                glow::CONDITION_SATISFIED | glow::ALREADY_SIGNALED => {
+                    gl.delete_sync(fence);
+                    sc.frame_fences.pop_front();
+                    return Ok(());
+                }
+                // The wait fails on invalid fences, rather than on device loss,
+                // which is only reported by the reset status.
+                _ => {
+                    sc.share.device_status().map_err(|err| match err {
+                        hal::device::OomOrDeviceLost::OutOfMemory(oom) => {
+                            w::AcquireError::OutOfMemory(oom)
+                        }
+                        hal::device::OomOrDeviceLost::DeviceLost(lost) => {
+                            w::AcquireError::DeviceLost(lost)
+                        }
+                    })?;
+                    log::error!("Unable to wait for the frame fence, dropping it");
+                    gl.delete_sync(fence);
+                    sc.frame_fences.pop_front();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn present_statistics(&mut self) -> Option<w::PresentStatistics> {
+        let sc = self.swapchain.as_ref()?;
+        Some(w::PresentStatistics {
+            present_count: sc.timings.frames,
+            // EGL doesn't report when the frames are displayed, returning from
+            // `eglSwapBuffers` is the closest point.
+            present_time: sc.last_present,
+            vblank_count: None,
+            dropped_frames: None,
+        })
+    }
+
+    unsafe fn set_frame_latency(&mut self, latency: u32) {
+        let sc = match self.swapchain {
+            Some(ref mut sc) => sc,
+            None => {
+                self.frame_latency = Some(latency);
+                return;
+            }
+        };
+        sc.frame_latency = latency.max(1) as usize;
+        // The newest fences imply the older ones, which no longer need to be waited for.
+        while sc.frame_fences.len() > sc.frame_latency {
+            let fence = sc.frame_fences.pop_front().unwrap();
+            sc.share.context.delete_sync(fence);
+        }
+    }
 }
 
 impl w::Surface<crate::Backend> for Surface {
@@ -606,8 +778,12 @@ impl w::Surface<crate::Backend> for Surface {
     }
 
     fn capabilities(&self, _physical_device: &PhysicalDevice) -> w::SurfaceCapabilities {
+        let mut present_modes = w::PresentMode::FIFO;
+        if self.immediate {
+            present_modes |= w::PresentMode::IMMEDIATE;
+        }
         w::SurfaceCapabilities {
-            present_modes: w::PresentMode::FIFO,                  //TODO
+            present_modes,
             composite_alpha_modes: w::CompositeAlphaMode::OPAQUE, //TODO
             image_count: 2..=2,
             current_extent: None,
@@ -632,24 +808,381 @@ impl w::Surface<crate::Backend> for Surface {
     }
 }
 
+impl Instance {
+    /// Create an instance without connecting to a window system, for compute
+    /// and offscreen rendering, e.g. on CI machines.
+    ///
+    /// The surfaceless platform of Mesa is used when available
+    /// (`EGL_MESA_platform_surfaceless`), and the default display otherwise.
+    /// The contexts are made current without a surface when supported, and with
+    /// a dummy pbuffer otherwise.
+    ///
+    /// Surfaces can't be created from the returned instance, which uses the
+    /// [default][hal::InstanceFlags::default] options, overridden by the environment.
+    pub fn create_headless() -> Result<Self, hal::UnsupportedBackend> {
+        let flags = hal::InstanceFlags::default().with_env();
+        let (egl, client_ext_str) = load_egl()?;
+
+        let display = match egl.upcast::<egl::EGL1_5>() {
+            Some(egl) if client_ext_str.contains("EGL_MESA_platform_surfaceless") => {
+                log::info!("Using surfaceless platform");
+                let display_attributes = [egl::ATTRIB_NONE];
+                egl.get_platform_display(
+                    EGL_PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY,
+                    &display_attributes,
+                )
+                .map_err(|e| {
+                    log::warn!("Error in get_platform_display: {:?}", e);
+                    hal::UnsupportedBackend
+                })?
+            }
+            _ => {
+                log::info!("Using default platform");
+                egl.get_display(egl::DEFAULT_DISPLAY)
+                    .ok_or(hal::UnsupportedBackend)?
+            }
+        };
+
+        let inner = Inner::create(egl, display, None, flags)?;
+
+        Ok(Instance {
+            inner: Mutex::new(inner),
+            wsi_library: None,
+            headless: true,
+            vsync_group: Starc::new(Mutex::new(VsyncGroup::default())),
+        })
+    }
+
+    /// Restrict the adapters enumerated from now on to a profile.
+    ///
+    /// With [`Profile::Es2`], the backend only relies on the features of OpenGL ES 2.0
+    /// and emulates the other ones where it can, see
+    /// [`PhysicalDevice::emulations`][crate::PhysicalDevice::emulations].
+    pub fn set_profile(&self, profile: Profile) {
+        self.inner.lock().profile = profile;
+    }
+
+    /// Force workarounds on or off on the adapters enumerated from now on,
+    /// regardless of the driver they're detected on.
+    ///
+    /// The overrides of the `GFX_GL_WORKAROUNDS` environment variable
+    /// take precedence, see [`Workarounds`][crate::Workarounds].
+    pub fn set_workaround_overrides(&self, overrides: WorkaroundOverrides) {
+        self.inner.lock().workaround_overrides = overrides;
+    }
+
+    /// Set how presents of the surfaces created from this instance are synchronized
+    /// with the vertical blank.
+    pub fn set_vsync_coordination(&self, mode: VsyncCoordination) {
+        let mut group = self.vsync_group.lock();
+        group.mode = mode;
+        group.presented.clear();
+    }
+
+    /// Destroy the window surface of `surface`, before its native window is
+    /// destroyed, such as when an Android activity is paused.
+    ///
+    /// The swapchain and the context are kept, as the swapchain images are
+    /// blitted to the window surface on present. Until the surface is resumed,
+    /// acquiring and presenting images fail with `OutOfDate`.
+    pub unsafe fn suspend_surface(&self, surface: &mut Surface) {
+        if let Some(raw) = surface.raw.take() {
+            self.destroy_window_surface(raw, surface.wl_window.take());
+        }
+    }
+
+    /// Create a new window surface for `surface` from a native window,
+    /// such as when an Android activity is resumed.
+    ///
+    /// The swapchain is kept if the new window has the same extent, and has
+    /// to be configured again otherwise, as reported by `acquire_image`.
+    pub unsafe fn resume_surface(
+        &self,
+        surface: &mut Surface,
+        has_handle: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<(), w::InitError> {
+        self.suspend_surface(surface);
+        let mut inner = self.inner.lock();
+        if inner.context != surface.context {
+            log::error!("The context of the surface was replaced");
+            return Err(w::InitError::WindowSystemUnavailable);
+        }
+        let (raw, wl_window) = self.create_window_surface(&mut inner, has_handle, false)?;
+        drop(inner);
+        surface.raw = Some(raw);
+        surface.wl_window = wl_window;
+        // The swap interval is a state of the window surface.
+        surface.swap_interval = None;
+
+        let query = |attribute| {
+            surface
+                .egl
+                .query_surface(surface.display, raw, attribute)
+                .unwrap_or(0) as u32
+        };
+        let extent = w::Extent2D {
+            width: query(egl::WIDTH),
+            height: query(egl::HEIGHT),
+        };
+        if let Some(ref mut sc) = surface.swapchain {
+            sc.out_of_date = extent != sc.extent;
+        }
+        Ok(())
+    }
+
+    /// Create a window surface for the context from a native window, returning
+    /// it with its Wayland window, if any.
+    ///
+    /// The context is created again on the display of a Wayland window if it's not
+    /// the one of the context, with `recreate_display`. Otherwise, such a window
+    /// is rejected, since the context is still used by the surfaces and devices.
+    #[cfg_attr(target_os = "macos", allow(unused, unused_mut, unreachable_code))]
+    unsafe fn create_window_surface(
+        &self,
+        inner: &mut Inner,
+        has_handle: &impl raw_window_handle::HasRawWindowHandle,
+        recreate_display: bool,
+    ) -> Result<(egl::Surface, Option<*mut raw::c_void>), w::InitError> {
+        use raw_window_handle::RawWindowHandle as Rwh;
+
+        let mut wl_window = None;
+        #[cfg(not(any(target_os = "android", target_os = "macos")))]
+        let (mut temp_xlib_handle, mut temp_xcb_handle);
+        let native_window_ptr = match has_handle.raw_window_handle() {
+            #[cfg(not(any(target_os = "android", target_os = "macos")))]
+            Rwh::Xlib(handle) if handle.window == 0 => {
+                return Err(w::InitError::InvalidWindowHandle)
+            }
+            #[cfg(not(any(target_os = "android", target_os = "macos")))]
+            Rwh::Xlib(handle) => {
+                temp_xlib_handle = handle.window;
+                &mut temp_xlib_handle as *mut _ as *mut std::ffi::c_void
+            }
+            #[cfg(not(any(target_os = "android", target_os = "macos")))]
+            Rwh::Xcb(handle) if handle.window == 0 => {
+                return Err(w::InitError::InvalidWindowHandle)
+            }
+            #[cfg(not(any(target_os = "android", target_os = "macos")))]
+            Rwh::Xcb(handle) => {
+                temp_xcb_handle = handle.window;
+                &mut temp_xcb_handle as *mut _ as *mut std::ffi::c_void
+            }
+            #[cfg(target_os = "android")]
+            Rwh::Android(handle) if handle.a_native_window.is_null() => {
+                return Err(w::InitError::InvalidWindowHandle)
+            }
+            #[cfg(target_os = "android")]
+            Rwh::Android(handle) => handle.a_native_window as *mut _ as *mut std::ffi::c_void,
+            #[cfg(not(any(target_os = "android", target_os = "macos")))]
+            Rwh::Wayland(handle) if handle.display.is_null() || handle.surface.is_null() => {
+                return Err(w::InitError::InvalidWindowHandle)
+            }
+            #[cfg(not(any(target_os = "android", target_os = "macos")))]
+            Rwh::Wayland(handle) => {
+                let wsi_library = self
+                    .wsi_library
+                    .as_ref()
+                    .ok_or(w::InitError::WindowSystemUnavailable)?;
+
+                /* Wayland displays are not sharable between surfaces so if the
+                 * surface we receive from this handle is from a different
+                 * display, we must re-initialize the context.
+                 *
+                 * See gfx-rs/gfx#3545
+                 */
+                if inner
+                    .wl_display
+                    .map(|ptr| ptr != handle.display)
+                    .unwrap_or(true)
+                {
+                    if !recreate_display {
+                        log::error!("The window isn't on the Wayland display of the context");
+                        return Err(w::InitError::WindowSystemUnavailable);
+                    }
+                    let display_attributes = [egl::ATTRIB_NONE];
+                    let display = inner
+                        .egl
+                        .upcast::<egl::EGL1_5>()
+                        .unwrap()
+                        .get_platform_display(
+                            EGL_PLATFORM_WAYLAND_KHR,
+                            handle.display,
+                            &display_attributes,
+                        )
+                        .unwrap();
+
+                    let new_inner =
+                        Inner::create(inner.egl.clone(), display, Some(wsi_library), inner.flags)
+                            .map_err(|_| w::InitError::WindowSystemUnavailable)?;
+
+                    let old_inner = std::mem::replace(&mut *inner, new_inner);
+                    inner.wl_display = Some(handle.display);
+                    inner.profile = old_inner.profile;
+                    inner.workaround_overrides = old_inner.workaround_overrides;
+                    drop(old_inner);
+                }
+
+                let wl_egl_window_create: libloading::Symbol<WlEglWindowCreateFun> =
+                    wsi_library.get(b"wl_egl_window_create").unwrap();
+                let result = wl_egl_window_create(handle.surface, 640, 480) as *mut _
+                    as *mut std::ffi::c_void;
+                wl_window = Some(result);
+                result
+            }
+            other => {
+                log::error!("Unsupported window: {:?}", other);
+                return Err(w::InitError::UnsupportedWindowHandle);
+            }
+        };
+
+        let mut attributes = vec![
+            egl::RENDER_BUFFER as usize,
+            if cfg!(target_os = "android") {
+                egl::BACK_BUFFER as usize
+            } else {
+                egl::SINGLE_BUFFER as usize
+            },
+        ];
+        if inner.version >= (1, 5) {
+            // Always enable sRGB in EGL 1.5
+            attributes.push(egl::GL_COLORSPACE as usize);
+            attributes.push(egl::GL_COLORSPACE_SRGB as usize);
+        }
+        attributes.push(egl::ATTRIB_NONE);
+
+        let raw = if let Some(egl) = inner.egl.upcast::<egl::EGL1_5>() {
+            egl.create_platform_window_surface(
+                inner.display,
+                inner.config,
+                native_window_ptr,
+                &attributes,
+            )
+            .map_err(|e| {
+                log::warn!("Error in create_platform_window_surface: {:?}", e);
+                w::InitError::Unknown
+            })
+        } else {
+            let attributes_i32: Vec<i32> = attributes.iter().map(|a| (*a as i32).into()).collect();
+            inner
+                .egl
+                .create_window_surface(
+                    inner.display,
+                    inner.config,
+                    native_window_ptr,
+                    Some(&attributes_i32),
+                )
+                .map_err(|e| {
+                    log::warn!("Error in create_platform_window_surface: {:?}", e);
+                    w::InitError::Unknown
+                })
+        }?;
+
+        #[cfg(target_os = "android")]
+        {
+            let format = inner
+                .egl
+                .get_config_attrib(inner.display, inner.config, egl::NATIVE_VISUAL_ID)
+                .unwrap();
+
+            let ret = ANativeWindow_setBuffersGeometry(native_window_ptr, 0, 0, format);
+
+            if ret != 0 {
+                log::error!("Error returned from ANativeWindow_setBuffersGeometry");
+                return Err(w::InitError::Unknown);
+            }
+        }
+
+        Ok((raw, wl_window))
+    }
+
+    unsafe fn destroy_window_surface(
+        &self,
+        raw: egl::Surface,
+        wl_window: Option<*mut raw::c_void>,
+    ) {
+        let inner = self.inner.lock();
+        inner.egl.destroy_surface(inner.display, raw).unwrap();
+        if let Some(wl_window) = wl_window {
+            let wl_egl_window_destroy: libloading::Symbol<WlEglWindowDestroyFun> = self
+                .wsi_library
+                .as_ref()
+                .expect("unsupported window")
+                .get(b"wl_egl_window_destroy")
+                .unwrap();
+            wl_egl_window_destroy(wl_window)
+        }
+    }
+}
+
+/// Map the errors of presenting to a window surface.
+///
+/// Android destroys the contexts on power events, and the native windows
+/// when the activities are paused.
+fn map_present_error(error: egl::Error) -> w::PresentError {
+    match error {
+        egl::Error::ContextLost => w::PresentError::DeviceLost(hal::device::DeviceLost),
+        egl::Error::BadAlloc => w::PresentError::OutOfMemory(hal::device::OutOfMemory::Host),
+        egl::Error::BadSurface | egl::Error::BadNativeWindow => w::SurfaceLost.into(),
+        other => {
+            log::error!("Unable to present: {:?}", other);
+            w::OutOfDate.into()
+        }
+    }
+}
+
+/// Map an error making the context current before waiting for a frame.
+fn map_acquire_error(error: egl::Error) -> w::AcquireError {
+    match error {
+        egl::Error::ContextLost => w::AcquireError::DeviceLost(hal::device::DeviceLost),
+        egl::Error::BadAlloc => w::AcquireError::OutOfMemory(hal::device::OutOfMemory::Host),
+        egl::Error::BadSurface | egl::Error::BadNativeWindow => w::SurfaceLost.into(),
+        other => {
+            log::error!("Unable to make the context current: {:?}", other);
+            w::OutOfDate.into()
+        }
+    }
+}
+
 impl Surface {
+    /// Set the number of vertical blanks to wait for on present,
+    /// overriding the one implied by the present mode until the swapchain is reconfigured.
+    pub fn set_swap_interval(&mut self, interval: u32) {
+        match self.swapchain {
+            Some(ref mut sc) => sc.swap_interval = interval,
+            None => log::warn!("Swap interval set without a swapchain"),
+        }
+    }
+
+    /// Frame time statistics of the current swapchain.
+    pub fn present_timings(&self) -> Option<PresentTimings> {
+        self.swapchain.as_ref().map(|sc| sc.timings)
+    }
+
     pub(crate) unsafe fn present(
         &mut self,
         _image: native::SwapchainImage,
         gl: &GlContainer,
     ) -> Result<Option<w::Suboptimal>, w::PresentError> {
-        let sc = self.swapchain.as_ref().unwrap();
+        let sc = self.swapchain.as_mut().unwrap();
+        let raw = match self.raw {
+            Some(raw) => raw,
+            None => return Err(w::OutOfDate.into()),
+        };
 
         self.egl
-            .make_current(
-                self.display,
-                Some(self.raw),
-                Some(self.raw),
-                Some(self.context),
-            )
-            .unwrap();
+            .make_current(self.display, Some(raw), Some(raw), Some(self.context))
+            .map_err(map_present_error)?;
         gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
         gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(sc.framebuffer));
+        if sc.share.private_caps.framebuffer_srgb {
+            // The blit only converts between the encodings of the swapchain
+            // image and the window surface with `FRAMEBUFFER_SRGB` enabled.
+            match sc.color_space {
+                w::ColorSpace::Linear => gl.enable(glow::FRAMEBUFFER_SRGB),
+                _ => gl.disable(glow::FRAMEBUFFER_SRGB),
+            }
+        }
         gl.blit_framebuffer(
             0,
             0,
@@ -664,7 +1197,51 @@ impl Surface {
         );
         gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
 
-        self.egl.swap_buffers(self.display, self.raw).unwrap();
+        let interval = self
+            .vsync_group
+            .lock()
+            .swap_interval(self.id, sc.swap_interval);
+        if self.swap_interval != Some(interval) {
+            // The interval is stored on the surface bound for drawing.
+            match self.egl.swap_interval(self.display, interval as egl::Int) {
+                Ok(()) => self.swap_interval = Some(interval),
+                // Drivers may reject intervals they don't support, presenting
+                // with the current one is better than not presenting at all.
+                Err(err) => log::warn!(
+                    "Unable to set the swap interval to {}, keeping the current one: {:?}",
+                    interval,
+                    err
+                ),
+            }
+        }
+
+        self.egl
+            .swap_buffers(self.display, raw)
+            .map_err(map_present_error)?;
+
+        let now = Instant::now();
+        let timings = &mut sc.timings;
+        timings.frames += 1;
+        if interval != 0 {
+            timings.vsync_waits += 1;
+        }
+        if let Some(last_present) = sc.last_present.replace(now) {
+            timings.last = now - last_present;
+            timings.max = timings.max.max(timings.last);
+            timings.average = if timings.frames == 2 {
+                timings.last
+            } else {
+                timings.average.mul_f64(0.9) + timings.last.mul_f64(0.1)
+            };
+        }
+
+        // Older fences are implied by the newer ones, in case nobody waits for them.
+        if sc.frame_fences.len() >= sc.frame_latency {
+            let fence = sc.frame_fences.pop_front().unwrap();
+            gl.delete_sync(fence);
+        }
+        sc.frame_fences
+            .push_back(gl.fence_sync(glow::SYNC_GPU_COMMANDS_COMPLETE, 0).unwrap());
 
         self.egl
             .make_current(self.display, self.pbuffer, self.pbuffer, Some(self.context))
@@ -59,7 +59,14 @@ impl hal::Instance<B> for Instance {
             None => return Vec::new(),
         };
 
-        let adapter = PhysicalDevice::new_adapter(context);
+        let adapter = PhysicalDevice::new_adapter(
+            context,
+            crate::Profile::Native,
+            crate::WorkaroundOverrides::default(),
+            hal::InstanceFlags::default().with_env(),
+            None,
+            None,
+        );
         vec![adapter]
     }
 
@@ -67,28 +74,29 @@ impl hal::Instance<B> for Instance {
         &self,
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
     ) -> Result<Surface, window::InitError> {
-        if let raw_window_handle::RawWindowHandle::Web(handle) = has_handle.raw_window_handle() {
-            let canvas: Starc<web_sys::HtmlCanvasElement> = Starc::new(
-                web_sys::window()
-                    .and_then(|win| win.document())
-                    .expect("Cannot get document")
-                    .query_selector(&format!("canvas[data-raw-handle=\"{}\"]", handle.id))
-                    .expect("Cannot query for canvas")
-                    .expect("Canvas is not found")
-                    .dyn_into()
-                    .expect("Failed to downcast to canvas type"),
-            );
-
-            *self.canvas.lock() = Some(canvas.clone());
-
-            Ok(Surface {
-                canvas,
-                swapchain: None,
-                renderbuffer: None,
-            })
-        } else {
-            unreachable!()
-        }
+        let handle = match has_handle.raw_window_handle() {
+            raw_window_handle::RawWindowHandle::Web(handle) => handle,
+            _ => return Err(window::InitError::UnsupportedWindowHandle),
+        };
+        // The canvas is looked up by the identifier of the handle.
+        let canvas: Starc<web_sys::HtmlCanvasElement> = Starc::new(
+            web_sys::window()
+                .and_then(|win| win.document())
+                .expect("Cannot get document")
+                .query_selector(&format!("canvas[data-raw-handle=\"{}\"]", handle.id))
+                .ok()
+                .flatten()
+                .and_then(|element| element.dyn_into().ok())
+                .ok_or(window::InitError::InvalidWindowHandle)?,
+        );
+
+        *self.canvas.lock() = Some(canvas.clone());
+
+        Ok(Surface {
+            canvas,
+            swapchain: None,
+            renderbuffer: None,
+        })
     }
 
     unsafe fn destroy_surface(&self, surface: Surface) {
@@ -187,6 +195,10 @@ impl window::PresentationSurface<B> for Surface {
         device: &Device,
         config: window::SwapchainConfig,
     ) -> Result<(), window::SwapchainError> {
+        // The canvas is always composited as sRGB.
+        if config.color_space != window::ColorSpace::SrgbNonlinear {
+            return Err(window::SwapchainError::UnsupportedColorSpace);
+        }
         let gl = &device.share.context;
 
         if let Some(swapchain) = self.swapchain.take() {
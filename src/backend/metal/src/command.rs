@@ -8,7 +8,7 @@ use crate::{
 
 use hal::{
     buffer, command as com,
-    device::OutOfMemory,
+    device::OomOrDeviceLost,
     format::{Aspects, FormatDesc},
     image as i, memory,
     pass::AttachmentLoadOp,
@@ -2275,7 +2275,8 @@ impl hal::queue::Queue<Backend> for Queue {
         wait_semaphores: Iw,
         signal_semaphores: Is,
         fence: Option<&mut native::Fence>,
-    ) where
+    ) -> Result<(), OomOrDeviceLost>
+    where
         Ic: Iterator<Item = &'a CommandBuffer>,
         Iw: Iterator<Item = (&'a native::Semaphore, pso::PipelineStage)>,
         Is: Iterator<Item = &'a native::Semaphore>,
@@ -2476,6 +2477,7 @@ impl hal::queue::Queue<Backend> for Queue {
                 counters.signal_command_buffers += 1;
             }
         }
+        Ok(())
     }
 
     unsafe fn present(
@@ -2515,7 +2517,7 @@ impl hal::queue::Queue<Backend> for Queue {
         Ok(None)
     }
 
-    fn wait_idle(&mut self) -> Result<(), OutOfMemory> {
+    fn wait_idle(&mut self) -> Result<(), OomOrDeviceLost> {
         QueueInner::wait_idle(&self.shared.queue);
         Ok(())
     }
@@ -3578,6 +3580,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         self.inner.borrow_mut().sink().pre_render().issue(com);
     }
 
+    unsafe fn set_shading_rate(
+        &mut self,
+        _rate: pso::ShadingRate,
+        _combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
         assign_sides(&mut self.state.stencil.reference_values, faces, value);
         let com =
@@ -4272,6 +4282,47 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         });
     }
 
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        _descs: &[hal::acceleration_structure::BuildDesc<Backend>],
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        _src: &(),
+        _dst: &(),
+        _mode: hal::acceleration_structure::CopyMode,
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &native::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a native::DescriptorSet>,
+        J: Iterator<Item = com::DescriptorSetOffset>,
+    {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _tables: &pso::ShaderBindingTables<Backend>,
+        _count: WorkGroupCount,
+    ) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &native::Buffer, dst: &native::Buffer, regions: T)
     where
         T: Iterator<Item = com::BufferCopy>,
@@ -4663,6 +4714,25 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         unimplemented!()
     }
 
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, _: u32, _: I)
+    where
+        I: Iterator<Item = (&'a native::Buffer, buffer::SubRange)>,
+    {
+        error!("Metal doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        error!("Metal doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        error!("Metal doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, _: Range<InstanceCount>) {
+        error!("Metal doesn't support DRAW_TRANSFORM_FEEDBACK");
+    }
+
     unsafe fn draw_mesh_tasks(&mut self, _: TaskCount, _: TaskCount) {
         unimplemented!()
     }
@@ -183,6 +183,19 @@ impl MemoryTypes {
     }
 }
 
+fn memory_heaps(shared: &Shared) -> Vec<adapter::MemoryHeap> {
+    vec![
+        adapter::MemoryHeap {
+            size: !0, //TODO: private memory limits
+            flags: memory::HeapFlags::DEVICE_LOCAL,
+        },
+        adapter::MemoryHeap {
+            size: shared.private_caps.max_buffer_size,
+            flags: memory::HeapFlags::empty(),
+        },
+    ]
+}
+
 #[derive(Debug)]
 pub struct PhysicalDevice {
     pub(crate) shared: Arc<Shared>,
@@ -378,16 +391,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
 
     fn memory_properties(&self) -> adapter::MemoryProperties {
         adapter::MemoryProperties {
-            memory_heaps: vec![
-                adapter::MemoryHeap {
-                    size: !0, //TODO: private memory limits
-                    flags: memory::HeapFlags::DEVICE_LOCAL,
-                },
-                adapter::MemoryHeap {
-                    size: self.shared.private_caps.max_buffer_size,
-                    flags: memory::HeapFlags::empty(),
-                },
-            ],
+            memory_heaps: memory_heaps(&self.shared),
             memory_types: self.memory_types.to_vec(),
         }
     }
@@ -1682,6 +1686,10 @@ impl hal::device::Device<Backend> for Device {
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
         profiling::scope!("create_graphics_pipeline");
         trace!("create_graphics_pipeline {:#?}", pipeline_desc);
+        if pipeline_desc.transform_feedback.is_some() {
+            // `Features::TRANSFORM_FEEDBACK` isn't exposed.
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
 
         let pipeline = metal::RenderPipelineDescriptor::new();
         let pipeline_layout = &pipeline_desc.layout;
@@ -2184,7 +2192,12 @@ impl hal::device::Device<Backend> for Device {
     unsafe fn create_sampler(
         &self,
         info: &image::SamplerDesc,
-    ) -> Result<n::Sampler, d::AllocationError> {
+    ) -> Result<n::Sampler, image::SamplerCreationError> {
+        let unsupported = info.unsupported_fields(self.features);
+        if !unsupported.is_empty() {
+            return Err(image::SamplerCreationError::Unsupported(unsupported));
+        }
+
         Ok(n::Sampler {
             raw: match self.make_sampler_descriptor(info) {
                 Some(ref descriptor) => Some(self.shared.device.lock().new_sampler(descriptor)),
@@ -2582,6 +2595,9 @@ impl hal::device::Device<Backend> for Device {
                                 image::Layout::General,
                             );
                         }
+                        pso::Descriptor::AccelerationStructure(_) => {
+                            unreachable!("Acceleration structures are never exposed")
+                        }
                         pso::Descriptor::Buffer(buf, ref sub) => {
                             let (raw, range) = buf.as_bound();
                             debug_assert!(
@@ -2672,6 +2688,9 @@ impl hal::device::Device<Backend> for Device {
                             data.ptr = (&**view.raw).as_ptr();
                             arg_index += 1;
                         }
+                        pso::Descriptor::AccelerationStructure(_) => {
+                            unreachable!("Acceleration structures are never exposed")
+                        }
                         pso::Descriptor::Buffer(buffer, ref sub) => {
                             let (buf_raw, buf_range) = buffer.as_bound();
                             encoder.set_buffer(arg_index, buf_raw, buf_range.start + sub.offset);
@@ -2702,6 +2721,26 @@ impl hal::device::Device<Backend> for Device {
 
     unsafe fn destroy_compute_pipeline(&self, _pipeline: n::ComputePipeline) {}
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {}
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+        _data: &mut [u8],
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
     unsafe fn destroy_framebuffer(&self, _framebuffer: n::Framebuffer) {}
 
     unsafe fn destroy_semaphore(&self, _semaphore: n::Semaphore) {}
@@ -2748,6 +2787,35 @@ impl hal::device::Device<Backend> for Device {
         }
     }
 
+    fn memory_budget(&self) -> Vec<memory::HeapBudget> {
+        // The usage of the heaps isn't tracked.
+        memory_heaps(&self.shared)
+            .into_iter()
+            .map(|heap| memory::HeapBudget {
+                usage: 0,
+                budget: heap.size,
+            })
+            .collect()
+    }
+
+    unsafe fn set_memory_priority(&self, _memory: &n::Memory, _priority: memory::Priority) {
+        // The residency of the memory isn't managed by the backend.
+    }
+
+    unsafe fn make_resident<'a, I>(&self, _memories: I) -> Result<(), d::OutOfMemory>
+    where
+        I: Iterator<Item = &'a n::Memory>,
+    {
+        Ok(())
+    }
+
+    unsafe fn evict<'a, I>(&self, _memories: I)
+    where
+        I: Iterator<Item = &'a n::Memory>,
+    {
+        // The residency of the memory isn't managed by the backend.
+    }
+
     unsafe fn create_buffer(
         &self,
         size: u64,
@@ -2944,6 +3012,10 @@ impl hal::device::Device<Backend> for Device {
         //nothing to do
     }
 
+    fn format_properties(&self, format: format::Format) -> format::Properties {
+        self.shared.private_caps.map_format_properties(format)
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -3393,6 +3465,29 @@ impl hal::device::Device<Backend> for Device {
         }
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _geometry: &hal::acceleration_structure::GeometryInfo<Backend>,
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {}
+
     unsafe fn get_query_pool_results(
         &self,
         pool: &n::QueryPool,
@@ -3464,7 +3559,7 @@ impl hal::device::Device<Backend> for Device {
         Ok(is_ready)
     }
 
-    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), d::OomOrDeviceLost> {
         command::QueueInner::wait_idle(&self.shared.queue);
         Ok(())
     }
@@ -3554,6 +3649,14 @@ impl hal::device::Device<Backend> for Device {
         // TODO
     }
 
+    unsafe fn set_graphics_pipeline_name(&self, _pipeline: &mut n::GraphicsPipeline, _name: &str) {
+        // Pipeline states are immutable, their label can only be set on creation.
+    }
+
+    unsafe fn set_compute_pipeline_name(&self, _pipeline: &mut n::ComputePipeline, _name: &str) {
+        // Pipeline states are immutable, their label can only be set on creation.
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &display::Display<Backend>,
@@ -3628,6 +3731,7 @@ impl hal::device::Device<Backend> for Device {
         _sparse: memory::SparseFlags,
         _view_caps: image::ViewCapabilities,
         _type_mask: u32,
+        __size: u64,
     ) -> Result<(n::Image, n::Memory), hal::external_memory::ExternalResourceError> {
         unimplemented!()
     }
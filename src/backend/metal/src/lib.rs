@@ -271,19 +271,38 @@ impl hal::Instance<Backend> for Instance {
         let mut adapters: Vec<Adapter<Backend>> = devices
             .into_iter()
             .map(|dev| {
-                let name = dev.name().into();
+                let name: String = dev.name().into();
+                // Metal doesn't expose the PCI IDs, but the name starts with the vendor
+                let name_lower = name.to_lowercase();
+                let vendor = if name_lower.starts_with("amd") {
+                    0x1002
+                } else if name_lower.starts_with("intel") {
+                    0x8086
+                } else if name_lower.starts_with("nvidia") {
+                    0x10DE
+                } else if name_lower.starts_with("apple") {
+                    0x106B
+                } else {
+                    0
+                };
                 let shared = Shared::new(dev, &self.experiments);
                 let physical_device = device::PhysicalDevice::new(Arc::new(shared));
+                let low_power = physical_device.shared.private_caps.low_power;
                 Adapter {
                     info: AdapterInfo {
                         name,
-                        vendor: 0,
+                        vendor,
                         device: 0,
-                        device_type: if physical_device.shared.private_caps.low_power {
+                        device_type: if low_power {
                             DeviceType::IntegratedGpu
                         } else {
                             DeviceType::DiscreteGpu
                         },
+                        driver: String::new(),
+                        // The low power devices are the integrated ones, including Apple GPUs
+                        unified_memory: low_power,
+                        luid: None,
+                        uuid: None,
                     },
                     physical_device,
                     queue_families: vec![QueueFamily {}],
@@ -304,6 +323,14 @@ impl hal::Instance<Backend> for Instance {
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
     ) -> Result<Surface, hal::window::InitError> {
         match has_handle.raw_window_handle() {
+            #[cfg(target_os = "ios")]
+            raw_window_handle::RawWindowHandle::IOS(handle) if handle.ui_view.is_null() => {
+                Err(hal::window::InitError::InvalidWindowHandle)
+            }
+            #[cfg(target_os = "macos")]
+            raw_window_handle::RawWindowHandle::MacOS(handle) if handle.ns_view.is_null() => {
+                Err(hal::window::InitError::InvalidWindowHandle)
+            }
             #[cfg(target_os = "ios")]
             raw_window_handle::RawWindowHandle::IOS(handle) => {
                 Ok(self.create_surface_from_uiview(handle.ui_view))
@@ -496,6 +523,7 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = native::ComputePipeline;
     type GraphicsPipeline = native::GraphicsPipeline;
+    type RayTracingPipeline = ();
     type PipelineCache = native::PipelineCache;
     type PipelineLayout = native::PipelineLayout;
     type DescriptorSetLayout = native::DescriptorSetLayout;
@@ -506,6 +534,7 @@ impl hal::Backend for Backend {
     type Semaphore = native::Semaphore;
     type Event = native::Event;
     type QueryPool = native::QueryPool;
+    type AccelerationStructure = ();
 
     type Display = ();
     type DisplayMode = ();
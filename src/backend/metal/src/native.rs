@@ -914,6 +914,7 @@ impl From<pso::DescriptorType> for DescriptorContent {
                 base | storage
             }
             pso::DescriptorType::InputAttachment => DescriptorContent::TEXTURE,
+            pso::DescriptorType::AccelerationStructure => unimplemented!(),
         }
     }
 }
@@ -1041,6 +1042,7 @@ impl ArgumentArray {
                 },
             },
             Dt::InputAttachment => MTLResourceUsage::Sample,
+            Dt::AccelerationStructure => unimplemented!(),
         }
     }
 
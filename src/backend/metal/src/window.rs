@@ -244,6 +244,9 @@ impl w::PresentationSurface<Backend> for Surface {
         if !image::Usage::COLOR_ATTACHMENT.contains(config.image_usage) {
             warn!("Swapchain usage {:?} is not expected", config.image_usage);
         }
+        if config.color_space != w::ColorSpace::SrgbNonlinear {
+            return Err(w::SwapchainError::UnsupportedColorSpace);
+        }
         #[cfg(target_os = "macos")]
         {
             if self.view.is_some() && self.main_thread_id != thread::current().id() {
@@ -137,6 +137,18 @@ where
                         .build(),
                 );
             }
+            memory::Barrier::Aliasing { .. } => {
+                // Vulkan has no aliasing barriers, so make the writes to the
+                // shared memory visible to any access of the next resource.
+                global.push(
+                    vk::MemoryBarrier::builder()
+                        .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                        .dst_access_mask(
+                            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+                        )
+                        .build(),
+                );
+            }
         }
     }
 
@@ -611,6 +623,14 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
         );
     }
 
+    unsafe fn set_shading_rate(
+        &mut self,
+        _rate: pso::ShadingRate,
+        _combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        unimplemented!()
+    }
+
     unsafe fn bind_graphics_pipeline(&mut self, pipeline: &n::GraphicsPipeline) {
         self.device
             .raw
@@ -673,6 +693,47 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             .cmd_dispatch_indirect(self.raw, buffer.raw, offset)
     }
 
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        _descs: &[hal::acceleration_structure::BuildDesc<Backend>],
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        _src: &(),
+        _dst: &(),
+        _mode: hal::acceleration_structure::CopyMode,
+    ) {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &n::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a n::DescriptorSet>,
+        J: Iterator<Item = com::DescriptorSetOffset>,
+    {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _tables: &pso::ShaderBindingTables<Backend>,
+        _count: WorkGroupCount,
+    ) {
+        unreachable!("Ray tracing pipelines are never exposed")
+    }
+
     unsafe fn copy_buffer<T>(&mut self, src: &n::Buffer, dst: &n::Buffer, regions: T)
     where
         T: Iterator<Item = com::BufferCopy>,
@@ -814,6 +875,25 @@ impl com::CommandBuffer<Backend> for CommandBuffer {
             .cmd_draw_indexed_indirect(self.raw, buffer.raw, offset, draw_count, stride)
     }
 
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, _: u32, _: I)
+    where
+        I: Iterator<Item = (&'a n::Buffer, buffer::SubRange)>,
+    {
+        error!("Vulkan doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        error!("Vulkan doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        error!("Vulkan doesn't support TRANSFORM_FEEDBACK");
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, _: Range<InstanceCount>) {
+        error!("Vulkan doesn't support DRAW_TRANSFORM_FEEDBACK");
+    }
+
     unsafe fn draw_mesh_tasks(&mut self, task_count: TaskCount, first_task: TaskCount) {
         self.device
             .extension_fns
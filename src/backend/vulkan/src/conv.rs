@@ -13,14 +13,26 @@ use hal::{
 use std::mem;
 
 pub fn map_format(format: format::Format) -> vk::Format {
-    vk::Format::from_raw(format as i32)
+    match format {
+        format::Format::Nv12Unorm => vk::Format::G8_B8R8_2PLANE_420_UNORM,
+        format::Format::P010Unorm => vk::Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16,
+        format::Format::Yuv420Unorm => vk::Format::G8_B8_R8_3PLANE_420_UNORM,
+        _ => vk::Format::from_raw(format as i32),
+    }
 }
 
 pub fn map_vk_format(vk_format: vk::Format) -> Option<format::Format> {
-    if (vk_format.as_raw() as usize) < format::NUM_FORMATS && vk_format != vk::Format::UNDEFINED {
-        Some(unsafe { mem::transmute(vk_format) })
-    } else {
-        None
+    match vk_format {
+        vk::Format::G8_B8R8_2PLANE_420_UNORM => Some(format::Format::Nv12Unorm),
+        vk::Format::G10X6_B10X6R10X6_2PLANE_420_UNORM_3PACK16 => Some(format::Format::P010Unorm),
+        vk::Format::G8_B8_R8_3PLANE_420_UNORM => Some(format::Format::Yuv420Unorm),
+        // Multi-planar formats are the only ones not matching the Vulkan values.
+        _ if (vk_format.as_raw() as usize) < format::Format::Nv12Unorm as usize
+            && vk_format != vk::Format::UNDEFINED =>
+        {
+            Some(unsafe { mem::transmute(vk_format) })
+        }
+        _ => None,
     }
 }
 
@@ -69,6 +81,9 @@ pub fn map_image_layout(layout: image::Layout) -> vk::ImageLayout {
         Il::Undefined => vk::ImageLayout::UNDEFINED,
         Il::Preinitialized => vk::ImageLayout::PREINITIALIZED,
         Il::Present => vk::ImageLayout::PRESENT_SRC_KHR,
+        Il::ShadingRateAttachmentOptimal => {
+            vk::ImageLayout::FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR
+        }
     }
 }
 
@@ -199,6 +214,9 @@ pub fn map_descriptor_type(ty: pso::DescriptorType) -> vk::DescriptorType {
             },
         },
         pso::DescriptorType::InputAttachment => vk::DescriptorType::INPUT_ATTACHMENT,
+        pso::DescriptorType::AccelerationStructure => {
+            vk::DescriptorType::ACCELERATION_STRUCTURE_KHR
+        }
     }
 }
 
@@ -586,13 +604,30 @@ pub fn map_descriptor_pool_create_flags(
     vk::DescriptorPoolCreateFlags::from_raw(flags.bits())
 }
 
+pub fn map_descriptor_binding_flags(
+    flags: pso::DescriptorBindingFlags,
+) -> vk::DescriptorBindingFlags {
+    let mut raw = vk::DescriptorBindingFlags::empty();
+    if flags.contains(pso::DescriptorBindingFlags::PARTIALLY_BOUND) {
+        raw |= vk::DescriptorBindingFlags::PARTIALLY_BOUND;
+    }
+    if flags.contains(pso::DescriptorBindingFlags::UPDATE_AFTER_BIND) {
+        raw |= vk::DescriptorBindingFlags::UPDATE_AFTER_BIND;
+    }
+    if flags.contains(pso::DescriptorBindingFlags::VARIABLE_COUNT) {
+        raw |= vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT;
+    }
+    raw
+}
+
 pub fn map_sample_count_flags(samples: image::NumSamples) -> vk::SampleCountFlags {
     vk::SampleCountFlags::from_raw((samples as u32) & vk::SampleCountFlags::all().as_raw())
 }
 
 pub fn map_vk_memory_properties(flags: vk::MemoryPropertyFlags) -> hal::memory::Properties {
     use crate::memory::Properties;
-    let mut properties = Properties::empty();
+    // Vulkan resources always share the memory bound to them.
+    let mut properties = Properties::ALIASABLE;
 
     if flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL) {
         properties |= Properties::DEVICE_LOCAL;
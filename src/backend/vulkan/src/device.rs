@@ -14,7 +14,9 @@ use hal::{
 
 use std::{ffi::CString, marker::PhantomData, mem, ops::Range, ptr, sync::Arc};
 
-use crate::{command as cmd, conv, native as n, pool::RawCommandPool, window as w, Backend as B};
+use crate::{
+    command as cmd, conv, native as n, pool::RawCommandPool, window as w, Backend as B, ExtensionFn,
+};
 use ash::vk::Handle;
 
 #[derive(Debug, Default)]
@@ -454,6 +456,176 @@ impl<'a> ComputePipelineInfoBuf<'a> {
     }
 }
 
+/// Storage of the descriptor writes of a `vkUpdateDescriptorSets` call.
+///
+/// The info pointers of the raw writes hold indices into the info vectors
+/// until the writes are finished, since the vectors may still grow.
+#[derive(Debug, Default)]
+struct DescriptorWrites {
+    raw_writes: Vec<vk::WriteDescriptorSet>,
+    image_infos: Vec<vk::DescriptorImageInfo>,
+    buffer_infos: Vec<vk::DescriptorBufferInfo>,
+    texel_buffer_views: Vec<vk::BufferView>,
+}
+
+impl DescriptorWrites {
+    fn push<'a, I>(&mut self, op: pso::DescriptorSetWrite<'a, B, I>)
+    where
+        I: Iterator<Item = pso::Descriptor<'a, B>>,
+    {
+        let descriptors = op.descriptors;
+        let DescriptorWrites {
+            ref mut raw_writes,
+            ref mut image_infos,
+            ref mut buffer_infos,
+            ref mut texel_buffer_views,
+        } = *self;
+        raw_writes.reserve(descriptors.size_hint().0);
+
+        // gfx-hal allows the type and stages to be different between the descriptor
+        // in a single write, while Vulkan requires them to be the same.
+        // Writes of other sets may precede, so the first descriptor always starts a new write.
+        let mut last = None;
+
+        let mut binding_pos = op
+            .set
+            .bindings
+            .binary_search_by_key(&op.binding, |b| b.binding)
+            .expect("Descriptor set writes don't match the set layout!");
+        let mut array_offset = op.array_offset;
+
+        for descriptor in descriptors {
+            let layout_binding = &op.set.bindings[binding_pos];
+            array_offset += 1;
+            if array_offset == layout_binding.count {
+                array_offset = 0;
+                binding_pos += 1;
+            }
+
+            let descriptor_type = conv::map_descriptor_type(layout_binding.ty);
+            if last == Some((descriptor_type, layout_binding.stage_flags)) {
+                raw_writes.last_mut().unwrap().descriptor_count += 1;
+            } else {
+                last = Some((descriptor_type, layout_binding.stage_flags));
+                raw_writes.push(vk::WriteDescriptorSet {
+                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
+                    p_next: ptr::null(),
+                    dst_set: op.set.raw,
+                    dst_binding: layout_binding.binding,
+                    dst_array_element: if layout_binding.binding == op.binding {
+                        op.array_offset as _
+                    } else {
+                        0
+                    },
+                    descriptor_count: 1,
+                    descriptor_type,
+                    p_image_info: image_infos.len() as _,
+                    p_buffer_info: buffer_infos.len() as _,
+                    p_texel_buffer_view: texel_buffer_views.len() as _,
+                });
+            }
+
+            match descriptor {
+                pso::Descriptor::Sampler(sampler) => {
+                    image_infos.push(
+                        vk::DescriptorImageInfo::builder()
+                            .sampler(sampler.0)
+                            .image_view(vk::ImageView::null())
+                            .image_layout(vk::ImageLayout::GENERAL)
+                            .build(),
+                    );
+                }
+                pso::Descriptor::Image(view, layout) => {
+                    image_infos.push(
+                        vk::DescriptorImageInfo::builder()
+                            .sampler(vk::Sampler::null())
+                            .image_view(view.raw)
+                            .image_layout(conv::map_image_layout(layout))
+                            .build(),
+                    );
+                }
+                pso::Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                    image_infos.push(
+                        vk::DescriptorImageInfo::builder()
+                            .sampler(sampler.0)
+                            .image_view(view.raw)
+                            .image_layout(conv::map_image_layout(layout))
+                            .build(),
+                    );
+                }
+                pso::Descriptor::Buffer(buffer, ref sub) => {
+                    buffer_infos.push(
+                        vk::DescriptorBufferInfo::builder()
+                            .buffer(buffer.raw)
+                            .offset(sub.offset)
+                            .range(sub.size.unwrap_or(vk::WHOLE_SIZE))
+                            .build(),
+                    );
+                }
+                pso::Descriptor::TexelBuffer(view) => {
+                    texel_buffer_views.push(view.raw);
+                }
+                pso::Descriptor::AccelerationStructure(_) => {
+                    unreachable!("Acceleration structures are never exposed")
+                }
+            }
+        }
+    }
+
+    /// Patch the info pointers, returning the raw writes.
+    fn finish(&mut self) -> &[vk::WriteDescriptorSet] {
+        let DescriptorWrites {
+            ref mut raw_writes,
+            ref image_infos,
+            ref buffer_infos,
+            ref texel_buffer_views,
+        } = *self;
+        for raw in raw_writes.iter_mut() {
+            use crate::vk::DescriptorType as Dt;
+            match raw.descriptor_type {
+                Dt::SAMPLER
+                | Dt::SAMPLED_IMAGE
+                | Dt::STORAGE_IMAGE
+                | Dt::COMBINED_IMAGE_SAMPLER
+                | Dt::INPUT_ATTACHMENT => {
+                    raw.p_buffer_info = ptr::null();
+                    raw.p_texel_buffer_view = ptr::null();
+                    raw.p_image_info = image_infos[raw.p_image_info as usize..].as_ptr();
+                }
+                Dt::UNIFORM_TEXEL_BUFFER | Dt::STORAGE_TEXEL_BUFFER => {
+                    raw.p_buffer_info = ptr::null();
+                    raw.p_image_info = ptr::null();
+                    raw.p_texel_buffer_view =
+                        texel_buffer_views[raw.p_texel_buffer_view as usize..].as_ptr();
+                }
+                Dt::UNIFORM_BUFFER
+                | Dt::STORAGE_BUFFER
+                | Dt::STORAGE_BUFFER_DYNAMIC
+                | Dt::UNIFORM_BUFFER_DYNAMIC => {
+                    raw.p_image_info = ptr::null();
+                    raw.p_texel_buffer_view = ptr::null();
+                    raw.p_buffer_info = buffer_infos[raw.p_buffer_info as usize..].as_ptr();
+                }
+                _ => panic!("unknown descriptor type"),
+            }
+        }
+
+        raw_writes
+    }
+}
+
+fn map_descriptor_set_copy(op: pso::DescriptorSetCopy<B>) -> vk::CopyDescriptorSet {
+    vk::CopyDescriptorSet::builder()
+        .src_set(op.src_set.raw)
+        .src_binding(op.src_binding as u32)
+        .src_array_element(op.src_array_offset as u32)
+        .dst_set(op.dst_set.raw)
+        .dst_binding(op.dst_binding as u32)
+        .dst_array_element(op.dst_array_offset as u32)
+        .descriptor_count(op.count as u32)
+        .build()
+}
+
 impl d::Device<B> for super::Device {
     unsafe fn allocate_memory(
         &self,
@@ -736,6 +908,10 @@ impl d::Device<B> for super::Device {
         cache: Option<&n::PipelineCache>,
     ) -> Result<n::GraphicsPipeline, pso::CreationError> {
         debug!("create_graphics_pipeline {:?}", desc);
+        if desc.transform_feedback.is_some() {
+            // `Features::TRANSFORM_FEEDBACK` isn't exposed.
+            return Err(pso::CreationError::UnsupportedPipeline);
+        }
         let buf = GraphicsPipelineInfoBuf::new(desc, &self.shared);
 
         let info = {
@@ -973,21 +1149,17 @@ impl d::Device<B> for super::Device {
     unsafe fn create_sampler(
         &self,
         desc: &image::SamplerDesc,
-    ) -> Result<n::Sampler, d::AllocationError> {
+    ) -> Result<n::Sampler, image::SamplerCreationError> {
         use hal::pso::Comparison;
 
-        let (anisotropy_enable, max_anisotropy) =
-            desc.anisotropy_clamp.map_or((false, 1.0), |aniso| {
-                if self.shared.features.contains(Features::SAMPLER_ANISOTROPY) {
-                    (true, aniso as f32)
-                } else {
-                    warn!(
-                        "Anisotropy({}) was requested on a device with disabled feature",
-                        aniso
-                    );
-                    (false, 1.0)
-                }
-            });
+        let unsupported = desc.unsupported_fields(self.shared.features);
+        if !unsupported.is_empty() {
+            return Err(image::SamplerCreationError::Unsupported(unsupported));
+        }
+
+        let (anisotropy_enable, max_anisotropy) = desc
+            .anisotropy_clamp
+            .map_or((false, 1.0), |aniso| (true, aniso as f32));
 
         let mut reduction_info;
         let mut info = vk::SamplerCreateInfo::builder()
@@ -1021,7 +1193,9 @@ impl d::Device<B> for super::Device {
 
         match result {
             Ok(sampler) => Ok(n::Sampler(sampler)),
-            Err(vk::Result::ERROR_TOO_MANY_OBJECTS) => Err(d::AllocationError::TooManyObjects),
+            Err(vk::Result::ERROR_TOO_MANY_OBJECTS) => {
+                Err(image::SamplerCreationError::TooManyObjects)
+            }
             Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
             Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
             _ => unreachable!(),
@@ -1103,6 +1277,38 @@ impl d::Device<B> for super::Device {
         }
     }
 
+    fn format_properties(&self, format: format::Format) -> format::Properties {
+        use ash::version::InstanceV1_0;
+
+        let properties = unsafe {
+            self.shared
+                .instance
+                .inner
+                .get_physical_device_format_properties(
+                    self.shared.physical_device,
+                    conv::map_format(format),
+                )
+        };
+        let supports_sampler_filter_minmax =
+            self.shared.features.contains(Features::SAMPLER_REDUCTION);
+
+        format::Properties {
+            linear_tiling: conv::map_image_features(
+                properties.linear_tiling_features,
+                self.shared.format_transfer_bits,
+                supports_sampler_filter_minmax,
+            ),
+            optimal_tiling: conv::map_image_features(
+                properties.optimal_tiling_features,
+                self.shared.format_transfer_bits,
+                supports_sampler_filter_minmax,
+            ),
+            buffer_features: conv::map_buffer_features(properties.buffer_features),
+            // DRM format modifiers are only queried on the physical device.
+            drm_format_properties: Vec::new(),
+        }
+    }
+
     unsafe fn create_image(
         &self,
         kind: image::Kind,
@@ -1290,9 +1496,29 @@ impl d::Device<B> for super::Device {
                 // TODO raw_bindings doesnt implement fmt::Debug
                 // debug!("create_descriptor_set_layout {:?}", raw_bindings);
 
-                let info = vk::DescriptorSetLayoutCreateInfo::builder()
-                    .flags(vk::DescriptorSetLayoutCreateFlags::empty())
+                let mut flags = vk::DescriptorSetLayoutCreateFlags::empty();
+                if bindings.iter().any(|b| {
+                    b.flags
+                        .contains(pso::DescriptorBindingFlags::UPDATE_AFTER_BIND)
+                }) {
+                    flags |= vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL;
+                }
+                let raw_binding_flags = bindings
+                    .iter()
+                    .map(|b| conv::map_descriptor_binding_flags(b.flags))
+                    .collect::<Vec<_>>();
+                let mut binding_flags_info =
+                    vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder()
+                        .binding_flags(&raw_binding_flags);
+
+                let mut info = vk::DescriptorSetLayoutCreateInfo::builder()
+                    .flags(flags)
                     .bindings(&raw_bindings);
+                // The binding flags are only chained when used, as they require
+                // the descriptor indexing extension.
+                if bindings.iter().any(|b| !b.flags.is_empty()) {
+                    info = info.push_next(&mut binding_flags_info);
+                }
 
                 self.shared.raw.create_descriptor_set_layout(&info, None)
             })
@@ -1313,146 +1539,31 @@ impl d::Device<B> for super::Device {
     where
         I: Iterator<Item = pso::Descriptor<'a, B>>,
     {
-        let descriptors = op.descriptors;
-        let mut raw_writes =
-            Vec::<vk::WriteDescriptorSet>::with_capacity(descriptors.size_hint().0);
-        let mut image_infos = Vec::new();
-        let mut buffer_infos = Vec::new();
-        let mut texel_buffer_views = Vec::new();
-
-        // gfx-hal allows the type and stages to be different between the descriptor
-        // in a single write, while Vulkan requires them to be the same.
-        let mut last_type = vk::DescriptorType::SAMPLER;
-        let mut last_stages = pso::ShaderStageFlags::empty();
-
-        let mut binding_pos = op
-            .set
-            .bindings
-            .binary_search_by_key(&op.binding, |b| b.binding)
-            .expect("Descriptor set writes don't match the set layout!");
-        let mut array_offset = op.array_offset;
-
-        for descriptor in descriptors {
-            let layout_binding = &op.set.bindings[binding_pos];
-            array_offset += 1;
-            if array_offset == layout_binding.count {
-                array_offset = 0;
-                binding_pos += 1;
-            }
-
-            let descriptor_type = conv::map_descriptor_type(layout_binding.ty);
-            if descriptor_type == last_type && layout_binding.stage_flags == last_stages {
-                raw_writes.last_mut().unwrap().descriptor_count += 1;
-            } else {
-                last_type = descriptor_type;
-                last_stages = layout_binding.stage_flags;
-                raw_writes.push(vk::WriteDescriptorSet {
-                    s_type: vk::StructureType::WRITE_DESCRIPTOR_SET,
-                    p_next: ptr::null(),
-                    dst_set: op.set.raw,
-                    dst_binding: layout_binding.binding,
-                    dst_array_element: if layout_binding.binding == op.binding {
-                        op.array_offset as _
-                    } else {
-                        0
-                    },
-                    descriptor_count: 1,
-                    descriptor_type,
-                    p_image_info: image_infos.len() as _,
-                    p_buffer_info: buffer_infos.len() as _,
-                    p_texel_buffer_view: texel_buffer_views.len() as _,
-                });
-            }
-
-            match descriptor {
-                pso::Descriptor::Sampler(sampler) => {
-                    image_infos.push(
-                        vk::DescriptorImageInfo::builder()
-                            .sampler(sampler.0)
-                            .image_view(vk::ImageView::null())
-                            .image_layout(vk::ImageLayout::GENERAL)
-                            .build(),
-                    );
-                }
-                pso::Descriptor::Image(view, layout) => {
-                    image_infos.push(
-                        vk::DescriptorImageInfo::builder()
-                            .sampler(vk::Sampler::null())
-                            .image_view(view.raw)
-                            .image_layout(conv::map_image_layout(layout))
-                            .build(),
-                    );
-                }
-                pso::Descriptor::CombinedImageSampler(view, layout, sampler) => {
-                    image_infos.push(
-                        vk::DescriptorImageInfo::builder()
-                            .sampler(sampler.0)
-                            .image_view(view.raw)
-                            .image_layout(conv::map_image_layout(layout))
-                            .build(),
-                    );
-                }
-                pso::Descriptor::Buffer(buffer, ref sub) => {
-                    buffer_infos.push(
-                        vk::DescriptorBufferInfo::builder()
-                            .buffer(buffer.raw)
-                            .offset(sub.offset)
-                            .range(sub.size.unwrap_or(vk::WHOLE_SIZE))
-                            .build(),
-                    );
-                }
-                pso::Descriptor::TexelBuffer(view) => {
-                    texel_buffer_views.push(view.raw);
-                }
-            }
-        }
-
-        // Patch the pointers now that we have all the storage allocated.
-        for raw in raw_writes.iter_mut() {
-            use crate::vk::DescriptorType as Dt;
-            match raw.descriptor_type {
-                Dt::SAMPLER
-                | Dt::SAMPLED_IMAGE
-                | Dt::STORAGE_IMAGE
-                | Dt::COMBINED_IMAGE_SAMPLER
-                | Dt::INPUT_ATTACHMENT => {
-                    raw.p_buffer_info = ptr::null();
-                    raw.p_texel_buffer_view = ptr::null();
-                    raw.p_image_info = image_infos[raw.p_image_info as usize..].as_ptr();
-                }
-                Dt::UNIFORM_TEXEL_BUFFER | Dt::STORAGE_TEXEL_BUFFER => {
-                    raw.p_buffer_info = ptr::null();
-                    raw.p_image_info = ptr::null();
-                    raw.p_texel_buffer_view =
-                        texel_buffer_views[raw.p_texel_buffer_view as usize..].as_ptr();
-                }
-                Dt::UNIFORM_BUFFER
-                | Dt::STORAGE_BUFFER
-                | Dt::STORAGE_BUFFER_DYNAMIC
-                | Dt::UNIFORM_BUFFER_DYNAMIC => {
-                    raw.p_image_info = ptr::null();
-                    raw.p_texel_buffer_view = ptr::null();
-                    raw.p_buffer_info = buffer_infos[raw.p_buffer_info as usize..].as_ptr();
-                }
-                _ => panic!("unknown descriptor type"),
-            }
-        }
-
-        self.shared.raw.update_descriptor_sets(&raw_writes, &[]);
+        let mut writes = DescriptorWrites::default();
+        writes.push(op);
+        self.shared.raw.update_descriptor_sets(writes.finish(), &[]);
     }
 
     unsafe fn copy_descriptor_set<'a>(&self, op: pso::DescriptorSetCopy<'a, B>) {
-        let copy = vk::CopyDescriptorSet::builder()
-            .src_set(op.src_set.raw)
-            .src_binding(op.src_binding as u32)
-            .src_array_element(op.src_array_offset as u32)
-            .dst_set(op.dst_set.raw)
-            .dst_binding(op.dst_binding as u32)
-            .dst_array_element(op.dst_array_offset as u32)
-            .descriptor_count(op.count as u32)
-            .build();
+        self.shared
+            .raw
+            .update_descriptor_sets(&[], &[map_descriptor_set_copy(op)]);
+    }
 
-        self.shared.raw.update_descriptor_sets(&[], &[copy]);
+    unsafe fn update_descriptor_sets<'a, I, J, K>(&self, writes: I, copies: K)
+    where
+        I: Iterator<Item = pso::DescriptorSetWrite<'a, B, J>>,
+        J: Iterator<Item = pso::Descriptor<'a, B>>,
+        K: Iterator<Item = pso::DescriptorSetCopy<'a, B>>,
+    {
+        let mut raw_writes = DescriptorWrites::default();
+        for op in writes {
+            raw_writes.push(op);
+        }
+        let raw_copies = copies.map(map_descriptor_set_copy).collect::<Vec<_>>();
+        self.shared
+            .raw
+            .update_descriptor_sets(raw_writes.finish(), &raw_copies);
     }
 
     unsafe fn map_memory(
@@ -1640,6 +1751,76 @@ impl d::Device<B> for super::Device {
         self.shared.raw.free_memory(memory.raw, None);
     }
 
+    fn memory_budget(&self) -> Vec<memory::HeapBudget> {
+        let instance = &self.shared.instance;
+        let physical_device = self.shared.physical_device;
+        match instance.get_physical_device_properties {
+            Some(ref get_physical_device_properties) if self.shared.memory_budget => {
+                let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::builder();
+                let mut properties2 = vk::PhysicalDeviceMemoryProperties2::builder()
+                    .push_next(&mut budget_properties)
+                    .build();
+                match get_physical_device_properties {
+                    ExtensionFn::Promoted => {
+                        use ash::version::InstanceV1_1;
+                        unsafe {
+                            instance.inner.get_physical_device_memory_properties2(
+                                physical_device,
+                                &mut properties2,
+                            );
+                        }
+                    }
+                    ExtensionFn::Extension(get_physical_device_properties) => unsafe {
+                        get_physical_device_properties.get_physical_device_memory_properties2_khr(
+                            physical_device,
+                            &mut properties2,
+                        );
+                    },
+                }
+                let heap_count = properties2.memory_properties.memory_heap_count as usize;
+                (0..heap_count)
+                    .map(|i| memory::HeapBudget {
+                        usage: budget_properties.heap_usage[i],
+                        budget: budget_properties.heap_budget[i],
+                    })
+                    .collect()
+            }
+            _ => {
+                // Without `VK_EXT_memory_budget`, the usage of the heaps isn't known.
+                let properties = unsafe {
+                    instance
+                        .inner
+                        .get_physical_device_memory_properties(physical_device)
+                };
+                properties.memory_heaps[..properties.memory_heap_count as usize]
+                    .iter()
+                    .map(|heap| memory::HeapBudget {
+                        usage: 0,
+                        budget: heap.size,
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    unsafe fn set_memory_priority(&self, _memory: &n::Memory, _priority: memory::Priority) {
+        // The residency of the memory isn't managed by the backend.
+    }
+
+    unsafe fn make_resident<'a, I>(&self, _memories: I) -> Result<(), d::OutOfMemory>
+    where
+        I: Iterator<Item = &'a n::Memory>,
+    {
+        Ok(())
+    }
+
+    unsafe fn evict<'a, I>(&self, _memories: I)
+    where
+        I: Iterator<Item = &'a n::Memory>,
+    {
+        // The residency of the memory isn't managed by the backend.
+    }
+
     unsafe fn create_query_pool(
         &self,
         ty: query::Type,
@@ -1709,6 +1890,29 @@ impl d::Device<B> for super::Device {
         self.shared.raw.destroy_query_pool(pool.0, None);
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _geometry: &hal::acceleration_structure::GeometryInfo<B>,
+    ) -> hal::acceleration_structure::SizeRequirements {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<B>,
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        unreachable!("Acceleration structures are never exposed")
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {}
+
     unsafe fn destroy_shader_module(&self, module: n::ShaderModule) {
         self.shared.raw.destroy_shader_module(module.raw, None);
     }
@@ -1729,6 +1933,26 @@ impl d::Device<B> for super::Device {
         self.shared.raw.destroy_pipeline(pipeline.0, None);
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, B>,
+        _cache: Option<&n::PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {}
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+        _data: &mut [u8],
+    ) -> Result<(), hal::device::OutOfMemory> {
+        Err(hal::device::OutOfMemory::Host)
+    }
+
     unsafe fn destroy_framebuffer(&self, fb: n::Framebuffer) {
         match fb {
             n::Framebuffer::ImageLess(raw) => {
@@ -1784,11 +2008,12 @@ impl d::Device<B> for super::Device {
         self.shared.raw.destroy_event(event.0, None);
     }
 
-    fn wait_idle(&self) -> Result<(), d::OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), d::OomOrDeviceLost> {
         match unsafe { self.shared.raw.device_wait_idle() } {
             Ok(()) => Ok(()),
-            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host),
-            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(d::OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(d::OutOfMemory::Device.into()),
+            Err(vk::Result::ERROR_DEVICE_LOST) => Err(d::DeviceLost.into()),
             _ => unreachable!(),
         }
     }
@@ -1866,6 +2091,16 @@ impl d::Device<B> for super::Device {
             .set_object_name(vk::ObjectType::PIPELINE_LAYOUT, pipeline_layout.raw, name)
     }
 
+    unsafe fn set_graphics_pipeline_name(&self, pipeline: &mut n::GraphicsPipeline, name: &str) {
+        self.shared
+            .set_object_name(vk::ObjectType::PIPELINE, pipeline.0, name)
+    }
+
+    unsafe fn set_compute_pipeline_name(&self, pipeline: &mut n::ComputePipeline, name: &str) {
+        self.shared
+            .set_object_name(vk::ObjectType::PIPELINE, pipeline.0, name)
+    }
+
     unsafe fn set_display_power_state(
         &self,
         display: &hal::display::Display<B>,
@@ -2552,6 +2787,7 @@ impl d::Device<B> for super::Device {
         sparse: memory::SparseFlags,
         view_caps: image::ViewCapabilities,
         type_mask: u32,
+        _size: u64,
     ) -> Result<(n::Image, n::Memory), hal::external_memory::ExternalResourceError> {
         if self.shared.extension_fns.external_memory.is_none() {
             panic!(
@@ -3079,6 +3315,10 @@ impl super::Device {
         config: SwapchainConfig,
         provided_old_swapchain: Option<w::Swapchain>,
     ) -> Result<(w::Swapchain, Vec<n::Image>), hal::window::SwapchainError> {
+        // The other color spaces require `VK_EXT_swapchain_colorspace`.
+        if config.color_space != hal::window::ColorSpace::SrgbNonlinear {
+            return Err(hal::window::SwapchainError::UnsupportedColorSpace);
+        }
         let functor = khr::Swapchain::new(&surface.raw.instance.inner, &self.shared.raw);
 
         let old_swapchain = match provided_old_swapchain {
@@ -3206,6 +3446,11 @@ impl super::Device {
             _ => unreachable!(),
         }
     }
+
+    /// RenderDoc integration, to configure the captures.
+    pub fn render_doc(&self) -> &gfx_renderdoc::RenderDoc {
+        &self.render_doc
+    }
 }
 
 #[test]
@@ -38,7 +38,7 @@ use ash::{
 
 use hal::{
     adapter,
-    device::{DeviceLost, OutOfMemory},
+    device::{DeviceLost, OomOrDeviceLost, OutOfMemory},
     display, image, memory,
     pso::PipelineStage,
     queue,
@@ -50,7 +50,7 @@ use std::{
     borrow::Cow,
     cmp,
     ffi::{CStr, CString},
-    fmt, slice,
+    fmt, ptr, slice,
     sync::Arc,
     thread, unreachable,
 };
@@ -342,6 +342,36 @@ unsafe extern "system" fn debug_report_callback(
     vk::FALSE
 }
 
+fn validation_layer_name() -> &'static CStr {
+    CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap()
+}
+
+/// Check if a layer provides the given instance extension.
+fn layer_has_extension(entry: &Entry, layer: &CStr, extension: &CStr) -> bool {
+    let fp = entry.fp_v1_0();
+    let mut count = 0;
+    let result = unsafe {
+        fp.enumerate_instance_extension_properties(layer.as_ptr(), &mut count, ptr::null_mut())
+    };
+    if result != vk::Result::SUCCESS {
+        return false;
+    }
+    let mut properties = vec![vk::ExtensionProperties::default(); count as usize];
+    let result = unsafe {
+        fp.enumerate_instance_extension_properties(
+            layer.as_ptr(),
+            &mut count,
+            properties.as_mut_ptr(),
+        )
+    };
+    if result != vk::Result::SUCCESS && result != vk::Result::INCOMPLETE {
+        return false;
+    }
+    properties[..count as usize].iter().any(
+        |properties| unsafe { CStr::from_ptr(properties.extension_name.as_ptr()) } == extension,
+    )
+}
+
 impl Instance {
     pub fn required_extensions(
         entry: &Entry,
@@ -428,7 +458,7 @@ impl Instance {
         // Check requested layers against the available layers
         let mut layers: Vec<&'static CStr> = Vec::new();
         if cfg!(debug_assertions) {
-            layers.push(CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap());
+            layers.push(validation_layer_name());
         }
 
         // Only keep available layers.
@@ -580,6 +610,15 @@ impl Instance {
 
 impl hal::Instance<Backend> for Instance {
     fn create(name: &str, version: u32) -> Result<Self, hal::UnsupportedBackend> {
+        Self::create_with_flags(name, version, hal::InstanceFlags::default())
+    }
+
+    fn create_with_flags(
+        name: &str,
+        version: u32,
+        flags: hal::InstanceFlags,
+    ) -> Result<Self, hal::UnsupportedBackend> {
+        let flags = flags.with_env();
         #[cfg(not(feature = "use-rtld-next"))]
         let entry = match unsafe { Entry::new() } {
             Ok(entry) => entry,
@@ -634,10 +673,38 @@ impl hal::Instance<Backend> for Instance {
                 .into()
             });
 
-        let extensions = Instance::required_extensions(&entry, driver_api_version)?;
+        let mut extensions = Instance::required_extensions(&entry, driver_api_version)?;
 
         let layers = Instance::required_layers(&entry)?;
 
+        // GPU-assisted validation instruments the shaders with bounds and descriptor
+        // index checks, reporting the violations with the offending draw or dispatch
+        // through the debug messenger. It's provided by the validation layer, along
+        // with the extension enabling it.
+        let gpu_validation = flags.contains(hal::InstanceFlags::GPU_VALIDATION)
+            && if !layers.contains(&validation_layer_name()) {
+                warn!("GPU-assisted validation requires the validation layer");
+                false
+            } else if !layer_has_extension(
+                &entry,
+                validation_layer_name(),
+                vk::ExtValidationFeaturesFn::name(),
+            ) {
+                warn!("GPU-assisted validation is not supported by the validation layer");
+                false
+            } else {
+                true
+            };
+        if gpu_validation {
+            extensions.push(vk::ExtValidationFeaturesFn::name());
+        }
+        let gpu_validation_features = [
+            vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+            vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+        ];
+        let mut validation_features = vk::ValidationFeaturesEXT::builder()
+            .enabled_validation_features(&gpu_validation_features);
+
         let instance = {
             let str_pointers = layers
                 .iter()
@@ -648,11 +715,14 @@ impl hal::Instance<Backend> for Instance {
                 })
                 .collect::<Vec<_>>();
 
-            let create_info = vk::InstanceCreateInfo::builder()
+            let mut create_info = vk::InstanceCreateInfo::builder()
                 .flags(vk::InstanceCreateFlags::empty())
                 .application_info(&app_info)
                 .enabled_layer_names(&str_pointers[..layers.len()])
                 .enabled_extension_names(&str_pointers[layers.len()..]);
+            if gpu_validation {
+                create_info = create_info.push_next(&mut validation_features);
+            }
 
             unsafe { entry.create_instance(&create_info, None) }.map_err(|e| {
                 warn!("Unable to create Vulkan instance: {:?}", e);
@@ -681,8 +751,18 @@ impl hal::Instance<Backend> for Instance {
         &self,
         has_handle: &impl raw_window_handle::HasRawWindowHandle,
     ) -> Result<window::Surface, hal::window::InitError> {
+        use hal::window::InitError;
         use raw_window_handle::RawWindowHandle;
 
+        // Reject the window systems whose surface extension isn't enabled on the instance.
+        let require = |extension: &'static CStr| {
+            if self.extensions.contains(&extension) {
+                Ok(())
+            } else {
+                Err(InitError::WindowSystemUnavailable)
+            }
+        };
+
         match has_handle.raw_window_handle() {
             #[cfg(all(
                 unix,
@@ -690,9 +770,11 @@ impl hal::Instance<Backend> for Instance {
                 not(target_os = "macos"),
                 not(target_os = "solaris")
             ))]
-            RawWindowHandle::Wayland(handle)
-                if self.extensions.contains(&khr::WaylandSurface::name()) =>
-            {
+            RawWindowHandle::Wayland(handle) => {
+                if handle.display.is_null() || handle.surface.is_null() {
+                    return Err(InitError::InvalidWindowHandle);
+                }
+                require(khr::WaylandSurface::name())?;
                 Ok(self.create_surface_from_wayland(handle.display, handle.surface))
             }
             #[cfg(all(
@@ -701,9 +783,11 @@ impl hal::Instance<Backend> for Instance {
                 not(target_os = "macos"),
                 not(target_os = "solaris")
             ))]
-            RawWindowHandle::Xlib(handle)
-                if self.extensions.contains(&khr::XlibSurface::name()) =>
-            {
+            RawWindowHandle::Xlib(handle) => {
+                if handle.display.is_null() || handle.window == 0 {
+                    return Err(InitError::InvalidWindowHandle);
+                }
+                require(khr::XlibSurface::name())?;
                 Ok(self.create_surface_from_xlib(handle.display as *mut _, handle.window))
             }
             #[cfg(all(
@@ -712,23 +796,41 @@ impl hal::Instance<Backend> for Instance {
                 not(target_os = "macos"),
                 not(target_os = "ios")
             ))]
-            RawWindowHandle::Xcb(handle) if self.extensions.contains(&khr::XcbSurface::name()) => {
+            RawWindowHandle::Xcb(handle) => {
+                if handle.connection.is_null() || handle.window == 0 {
+                    return Err(InitError::InvalidWindowHandle);
+                }
+                require(khr::XcbSurface::name())?;
                 Ok(self.create_surface_from_xcb(handle.connection as *mut _, handle.window))
             }
             #[cfg(target_os = "android")]
             RawWindowHandle::Android(handle) => {
+                if handle.a_native_window.is_null() {
+                    return Err(InitError::InvalidWindowHandle);
+                }
+                require(khr::AndroidSurface::name())?;
                 Ok(self.create_surface_android(handle.a_native_window))
             }
             #[cfg(windows)]
             RawWindowHandle::Windows(handle) => {
                 use winapi::um::libloaderapi::GetModuleHandleW;
 
+                if handle.hwnd.is_null() {
+                    return Err(InitError::InvalidWindowHandle);
+                }
+                require(khr::Win32Surface::name())?;
                 let hinstance = GetModuleHandleW(std::ptr::null());
                 Ok(self.create_surface_from_hwnd(hinstance as *mut _, handle.hwnd))
             }
             #[cfg(target_os = "macos")]
-            RawWindowHandle::MacOS(handle) => Ok(self.create_surface_from_ns_view(handle.ns_view)),
-            _ => Err(hal::window::InitError::UnsupportedWindowHandle),
+            RawWindowHandle::MacOS(handle) => {
+                if handle.ns_view.is_null() {
+                    return Err(InitError::InvalidWindowHandle);
+                }
+                require(ash::extensions::mvk::MacOSSurface::name())?;
+                Ok(self.create_surface_from_ns_view(handle.ns_view))
+            }
+            _ => Err(InitError::UnsupportedWindowHandle),
         }
     }
 
@@ -872,6 +974,9 @@ pub struct RawDevice {
     handle_is_external: bool,
     features: Features,
     instance: Arc<RawInstance>,
+    physical_device: vk::PhysicalDevice,
+    /// Format features report `TRANSFER_SRC`/`TRANSFER_DST` (`VK_KHR_maintenance1`).
+    format_transfer_bits: bool,
     extension_fns: DeviceExtensionFunctions,
     /// The `hal::Features::NDC_Y_UP` flag is implemented with either `VK_AMD_negative_viewport_height` or `VK_KHR_maintenance1`/1.1+. The AMD extension for negative viewport height does not require a Y shift.
     ///
@@ -880,6 +985,8 @@ pub struct RawDevice {
     imageless_framebuffers: bool,
     image_view_usage: bool,
     timestamp_period: f32,
+    /// The heap budgets are queried with `VK_EXT_memory_budget`.
+    memory_budget: bool,
 }
 
 impl fmt::Debug for RawDevice {
@@ -957,6 +1064,15 @@ pub struct Queue {
     raw: RawCommandQueue,
     device: Arc<RawDevice>,
     swapchain_fn: khr::Swapchain,
+    render_doc: Arc<gfx_renderdoc::RenderDoc>,
+    captures: gfx_renderdoc::FrameCaptures,
+}
+
+impl Queue {
+    fn capture_handle(&self) -> gfx_renderdoc::Handle {
+        use ash::vk::Handle;
+        self.device.raw.handle().as_raw() as *mut _
+    }
 }
 
 impl fmt::Debug for Queue {
@@ -972,7 +1088,8 @@ impl queue::Queue<Backend> for Queue {
         wait_semaphores: Iw,
         signal_semaphores: Is,
         fence: Option<&mut native::Fence>,
-    ) where
+    ) -> Result<(), OomOrDeviceLost>
+    where
         Ic: Iterator<Item = &'a command::CommandBuffer>,
         Iw: Iterator<Item = (&'a native::Semaphore, PipelineStage)>,
         Is: Iterator<Item = &'a native::Semaphore>,
@@ -1001,9 +1118,15 @@ impl queue::Queue<Backend> for Queue {
 
         let fence_raw = fence.map(|fence| fence.0).unwrap_or(vk::Fence::null());
 
-        let result = self.device.raw.queue_submit(*self.raw, &[*info], fence_raw);
-        if let Err(e) = result {
-            error!("Submit resulted in {:?}", e);
+        match self.device.raw.queue_submit(*self.raw, &[*info], fence_raw) {
+            Ok(()) => Ok(()),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(OutOfMemory::Device.into()),
+            Err(vk::Result::ERROR_DEVICE_LOST) => Err(DeviceLost.into()),
+            Err(e) => {
+                error!("Submit resulted in {:?}", e);
+                Ok(())
+            }
         }
     }
 
@@ -1171,7 +1294,7 @@ impl queue::Queue<Backend> for Queue {
             .swapchains(swapchains)
             .image_indices(image_indices);
 
-        match self.swapchain_fn.queue_present(*self.raw, &present_info) {
+        let result = match self.swapchain_fn.queue_present(*self.raw, &present_info) {
             Ok(false) => Ok(None),
             Ok(true) => Ok(Some(Suboptimal)),
             Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(OutOfMemory::Host.into()),
@@ -1180,14 +1303,19 @@ impl queue::Queue<Backend> for Queue {
             Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Err(OutOfDate.into()),
             Err(vk::Result::ERROR_SURFACE_LOST_KHR) => Err(SurfaceLost.into()),
             _ => panic!("Failed to present frame"),
-        }
+        };
+        let handle = self.capture_handle();
+        self.captures
+            .frame_presented(&self.render_doc, handle, ptr::null_mut());
+        result
     }
 
-    fn wait_idle(&mut self) -> Result<(), OutOfMemory> {
+    fn wait_idle(&mut self) -> Result<(), OomOrDeviceLost> {
         match unsafe { self.device.raw.queue_wait_idle(*self.raw) } {
             Ok(()) => Ok(()),
-            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(OutOfMemory::Host),
-            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(OutOfMemory::Device),
+            Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => Err(OutOfMemory::Host.into()),
+            Err(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY) => Err(OutOfMemory::Device.into()),
+            Err(vk::Result::ERROR_DEVICE_LOST) => Err(DeviceLost.into()),
             Err(_) => unreachable!(),
         }
     }
@@ -1195,6 +1323,14 @@ impl queue::Queue<Backend> for Queue {
     fn timestamp_period(&self) -> f32 {
         self.device.timestamp_period
     }
+
+    fn capture_next_frames(&mut self, frames: u32) {
+        let handle = self.capture_handle();
+        unsafe {
+            self.captures
+                .capture_next_frames(&self.render_doc, handle, ptr::null_mut(), frames)
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -1202,7 +1338,7 @@ pub struct Device {
     shared: Arc<RawDevice>,
     vendor_id: u32,
     valid_ash_memory_types: u32,
-    render_doc: gfx_renderdoc::RenderDoc,
+    render_doc: Arc<gfx_renderdoc::RenderDoc>,
     #[cfg(feature = "naga")]
     naga_options: naga::back::spv::Options,
 }
@@ -1234,6 +1370,7 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = native::ComputePipeline;
     type GraphicsPipeline = native::GraphicsPipeline;
+    type RayTracingPipeline = ();
     type PipelineLayout = native::PipelineLayout;
     type PipelineCache = native::PipelineCache;
     type DescriptorSetLayout = native::DescriptorSetLayout;
@@ -1244,6 +1381,7 @@ impl hal::Backend for Backend {
     type Semaphore = native::Semaphore;
     type Event = native::Event;
     type QueryPool = native::QueryPool;
+    type AccelerationStructure = ();
 
     type Display = native::Display;
     type DisplayMode = native::DisplayMode;
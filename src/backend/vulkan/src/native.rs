@@ -136,6 +136,19 @@ impl DescriptorPool {
     }
 }
 
+fn map_allocation_error(err: vk::Result) -> pso::AllocationError {
+    match err {
+        vk::Result::ERROR_OUT_OF_HOST_MEMORY => {
+            pso::AllocationError::OutOfMemory(OutOfMemory::Host)
+        }
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+            pso::AllocationError::OutOfMemory(OutOfMemory::Device)
+        }
+        vk::Result::ERROR_OUT_OF_POOL_MEMORY => pso::AllocationError::OutOfPoolMemory,
+        _ => pso::AllocationError::FragmentedPool,
+    }
+}
+
 impl pso::DescriptorPool<Backend> for DescriptorPool {
     unsafe fn allocate_one(
         &mut self,
@@ -154,16 +167,31 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
                 raw: sets.pop().unwrap(),
                 bindings: Arc::clone(&layout.bindings),
             })
-            .map_err(|err| match err {
-                vk::Result::ERROR_OUT_OF_HOST_MEMORY => {
-                    pso::AllocationError::OutOfMemory(OutOfMemory::Host)
-                }
-                vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
-                    pso::AllocationError::OutOfMemory(OutOfMemory::Device)
-                }
-                vk::Result::ERROR_OUT_OF_POOL_MEMORY => pso::AllocationError::OutOfPoolMemory,
-                _ => pso::AllocationError::FragmentedPool,
+            .map_err(map_allocation_error)
+    }
+
+    unsafe fn allocate_variable(
+        &mut self,
+        layout: &DescriptorSetLayout,
+        count: pso::DescriptorArrayIndex,
+    ) -> Result<DescriptorSet, pso::AllocationError> {
+        let raw_layouts = [layout.raw];
+        let counts = [count as u32];
+        let mut count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+            .descriptor_counts(&counts);
+        let info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.raw)
+            .set_layouts(&raw_layouts)
+            .push_next(&mut count_info);
+
+        self.device
+            .raw
+            .allocate_descriptor_sets(&info)
+            .map(|mut sets| DescriptorSet {
+                raw: sets.pop().unwrap(),
+                bindings: Arc::clone(&layout.bindings),
             })
+            .map_err(map_allocation_error)
     }
 
     unsafe fn allocate<'a, I, E>(
@@ -196,16 +224,7 @@ impl pso::DescriptorPool<Backend> for DescriptorPool {
                         .map(|(raw, bindings)| DescriptorSet { raw, bindings }),
                 )
             })
-            .map_err(|err| match err {
-                vk::Result::ERROR_OUT_OF_HOST_MEMORY => {
-                    pso::AllocationError::OutOfMemory(OutOfMemory::Host)
-                }
-                vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
-                    pso::AllocationError::OutOfMemory(OutOfMemory::Device)
-                }
-                vk::Result::ERROR_OUT_OF_POOL_MEMORY => pso::AllocationError::OutOfPoolMemory,
-                _ => pso::AllocationError::FragmentedPool,
-            })
+            .map_err(map_allocation_error)
     }
 
     unsafe fn free<I>(&mut self, descriptor_sets: I)
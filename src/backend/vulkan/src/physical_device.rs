@@ -72,6 +72,7 @@ impl PhysicalDeviceFeatures {
     ) -> PhysicalDeviceFeatures {
         // This must follow the "Valid Usage" requirements of [`VkDeviceCreateInfo`](https://www.khronos.org/registry/vulkan/specs/1.2-extensions/man/html/VkDeviceCreateInfo.html).
         let features = requested_features;
+        let update_after_bind = features.contains(Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND);
         PhysicalDeviceFeatures {
             // vk::PhysicalDeviceFeatures is a struct composed of Bool32's while
             // Features is a bitfield so we need to map everything manually
@@ -185,7 +186,26 @@ impl PhysicalDeviceFeatures {
                         .runtime_descriptor_array(
                             features.contains(Features::UNSIZED_DESCRIPTOR_ARRAY),
                         )
+                        .descriptor_binding_partially_bound(
+                            features.contains(Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND),
+                        )
+                        .descriptor_binding_variable_descriptor_count(
+                            features.contains(Features::DESCRIPTOR_BINDING_VARIABLE_COUNT),
+                        )
+                        .descriptor_binding_sampled_image_update_after_bind(update_after_bind)
+                        .descriptor_binding_storage_image_update_after_bind(update_after_bind)
+                        .descriptor_binding_storage_buffer_update_after_bind(update_after_bind)
+                        .descriptor_binding_uniform_texel_buffer_update_after_bind(
+                            update_after_bind,
+                        )
+                        .descriptor_binding_storage_texel_buffer_update_after_bind(
+                            update_after_bind,
+                        )
                         .sampler_filter_minmax(features.contains(Features::SAMPLER_REDUCTION))
+                        .shader_output_viewport_index(
+                            features.contains(Features::SHADER_OUTPUT_VIEWPORT_INDEX),
+                        )
+                        .shader_output_layer(features.contains(Features::SHADER_OUTPUT_LAYER))
                         .imageless_framebuffer(supports_vulkan12_imageless_framebuffer)
                         .build(),
                 )
@@ -212,6 +232,21 @@ impl PhysicalDeviceFeatures {
                         .runtime_descriptor_array(
                             features.contains(Features::UNSIZED_DESCRIPTOR_ARRAY),
                         )
+                        .descriptor_binding_partially_bound(
+                            features.contains(Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND),
+                        )
+                        .descriptor_binding_variable_descriptor_count(
+                            features.contains(Features::DESCRIPTOR_BINDING_VARIABLE_COUNT),
+                        )
+                        .descriptor_binding_sampled_image_update_after_bind(update_after_bind)
+                        .descriptor_binding_storage_image_update_after_bind(update_after_bind)
+                        .descriptor_binding_storage_buffer_update_after_bind(update_after_bind)
+                        .descriptor_binding_uniform_texel_buffer_update_after_bind(
+                            update_after_bind,
+                        )
+                        .descriptor_binding_storage_texel_buffer_update_after_bind(
+                            update_after_bind,
+                        )
                         .build(),
                 )
             } else {
@@ -442,6 +477,13 @@ impl PhysicalDeviceFeatures {
             bits |= Features::CONSERVATIVE_RASTERIZATION
         }
 
+        // Promoted to 1.2 as two optional features, reported above.
+        if info.api_version() < Version::V1_2
+            && info.supports_extension(vk::ExtShaderViewportIndexLayerFn::name())
+        {
+            bits |= Features::SHADER_OUTPUT_VIEWPORT_INDEX | Features::SHADER_OUTPUT_LAYER;
+        }
+
         if info.api_version() >= Version::V1_1
             || (info.supports_extension(vk::KhrGetPhysicalDeviceProperties2Fn::name())
                 && info.supports_extension(vk::KhrExternalMemoryFn::name()))
@@ -474,6 +516,12 @@ impl PhysicalDeviceFeatures {
             if vulkan_1_2.draw_indirect_count != 0 {
                 bits |= Features::DRAW_INDIRECT_COUNT
             }
+            if vulkan_1_2.shader_output_viewport_index != 0 {
+                bits |= Features::SHADER_OUTPUT_VIEWPORT_INDEX;
+            }
+            if vulkan_1_2.shader_output_layer != 0 {
+                bits |= Features::SHADER_OUTPUT_LAYER;
+            }
         }
 
         if let Some(ref descriptor_indexing) = self.descriptor_indexing {
@@ -492,6 +540,23 @@ impl PhysicalDeviceFeatures {
             if descriptor_indexing.runtime_descriptor_array != 0 {
                 bits |= Features::UNSIZED_DESCRIPTOR_ARRAY;
             }
+            if descriptor_indexing.descriptor_binding_partially_bound != 0 {
+                bits |= Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND;
+            }
+            if descriptor_indexing.descriptor_binding_variable_descriptor_count != 0 {
+                bits |= Features::DESCRIPTOR_BINDING_VARIABLE_COUNT;
+            }
+            // Uniform buffers and samplers can't be updated after binding.
+            if descriptor_indexing.descriptor_binding_sampled_image_update_after_bind != 0
+                && descriptor_indexing.descriptor_binding_storage_image_update_after_bind != 0
+                && descriptor_indexing.descriptor_binding_storage_buffer_update_after_bind != 0
+                && descriptor_indexing.descriptor_binding_uniform_texel_buffer_update_after_bind
+                    != 0
+                && descriptor_indexing.descriptor_binding_storage_texel_buffer_update_after_bind
+                    != 0
+            {
+                bits |= Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND;
+            }
         }
 
         if let Some(ref mesh_shader) = self.mesh_shader {
@@ -555,6 +620,11 @@ impl PhysicalDeviceInfo {
             requested_extensions.push(vk::ExtSamplerFilterMinmaxFn::name());
         }
 
+        // Used by `Device::memory_budget`, falling back to the heap sizes without it.
+        if self.supports_extension(vk::ExtMemoryBudgetFn::name()) {
+            requested_extensions.push(vk::ExtMemoryBudgetFn::name());
+        }
+
         if self.api_version() < Version::V1_2
             && requested_features.intersects(Features::DESCRIPTOR_INDEXING_MASK)
         {
@@ -581,6 +651,13 @@ impl PhysicalDeviceInfo {
             requested_extensions.push(MeshShader::name());
         }
 
+        if self.api_version() < Version::V1_2
+            && requested_features
+                .intersects(Features::SHADER_OUTPUT_VIEWPORT_INDEX | Features::SHADER_OUTPUT_LAYER)
+        {
+            requested_extensions.push(vk::ExtShaderViewportIndexLayerFn::name());
+        }
+
         if self.api_version() < Version::V1_2
             && requested_features.contains(Features::DRAW_INDIRECT_COUNT)
         {
@@ -978,6 +1055,10 @@ impl PhysicalDevice {
                 handle_is_external,
                 features: requested_features,
                 instance: Arc::clone(&self.instance),
+                physical_device: self.handle,
+                format_transfer_bits: self
+                    .device_info
+                    .supports_extension(vk::KhrMaintenance1Fn::name()),
                 extension_fns: DeviceExtensionFunctions {
                     mesh_shaders: mesh_fn,
                     draw_indirect_count: indirect_count_fn,
@@ -1008,6 +1089,8 @@ impl PhysicalDevice {
                         .device_info
                         .supports_extension(vk::KhrMaintenance2Fn::name()),
                 timestamp_period: self.device_info.properties.limits.timestamp_period,
+                memory_budget: enabled_extensions.contains(&vk::ExtMemoryBudgetFn::name())
+                    && self.instance.get_physical_device_properties.is_some(),
             }),
             vendor_id: self.device_info.properties.vendor_id,
             valid_ash_memory_types,
@@ -1028,6 +1111,8 @@ impl PhysicalDevice {
                         raw: Arc::new(queue_raw),
                         device: device_arc.clone(),
                         swapchain_fn: swapchain_fn.clone(),
+                        render_doc: Arc::clone(&device.render_doc),
+                        captures: Default::default(),
                     });
                 }
                 family_raw
@@ -1068,13 +1153,35 @@ impl PhysicalDevice {
     }
 }
 
+/// Decode the driver version, which is packed in a vendor specific way.
+fn driver_version_string(vendor_id: u32, version: u32) -> String {
+    const NVIDIA: u32 = 0x10DE;
+    const INTEL: u32 = 0x8086;
+    match vendor_id {
+        NVIDIA => format!(
+            "{}.{}.{}.{}",
+            version >> 22,
+            (version >> 14) & 0xFF,
+            (version >> 6) & 0xFF,
+            version & 0x3F
+        ),
+        INTEL if cfg!(windows) => format!("{}.{}", version >> 14, version & 0x3FFF),
+        _ => format!(
+            "{}.{}.{}",
+            vk::version_major(version),
+            vk::version_minor(version),
+            vk::version_patch(version)
+        ),
+    }
+}
+
 pub(crate) fn load_adapter(
     instance: &Arc<RawInstance>,
     device: vk::PhysicalDevice,
 ) -> adapter::Adapter<Backend> {
     let (device_info, device_features) = PhysicalDeviceInfo::load(instance, device);
 
-    let info = adapter::AdapterInfo {
+    let mut info = adapter::AdapterInfo {
         name: unsafe {
             CStr::from_ptr(device_info.properties.device_name.as_ptr())
                 .to_str()
@@ -1091,8 +1198,46 @@ pub(crate) fn load_adapter(
             ash::vk::PhysicalDeviceType::CPU => adapter::DeviceType::Cpu,
             _ => adapter::DeviceType::Other,
         },
+        driver: driver_version_string(
+            device_info.properties.vendor_id,
+            device_info.properties.driver_version,
+        ),
+        unified_memory: match device_info.properties.device_type {
+            ash::vk::PhysicalDeviceType::INTEGRATED_GPU | ash::vk::PhysicalDeviceType::CPU => true,
+            _ => false,
+        },
+        luid: None,
+        uuid: None,
     };
 
+    if let Some(ref get_physical_device_properties) = instance.get_physical_device_properties {
+        let mut id_properties = vk::PhysicalDeviceIDProperties::builder();
+        let mut properties2 = vk::PhysicalDeviceProperties2::builder()
+            .push_next(&mut id_properties)
+            .build();
+        match get_physical_device_properties {
+            ExtensionFn::Promoted => {
+                use ash::version::InstanceV1_1;
+                unsafe {
+                    instance
+                        .inner
+                        .get_physical_device_properties2(device, &mut properties2);
+                }
+            }
+            ExtensionFn::Extension(get_physical_device_properties) => unsafe {
+                get_physical_device_properties
+                    .get_physical_device_properties2_khr(device, &mut properties2);
+            },
+        }
+        // Drivers not knowing the structure leave it zeroed.
+        if id_properties.device_uuid != [0; vk::UUID_SIZE] {
+            info.uuid = Some(id_properties.device_uuid);
+        }
+        if id_properties.device_luid_valid == vk::TRUE {
+            info.luid = Some(id_properties.device_luid);
+        }
+    }
+
     let available_features = {
         let mut bits = device_features.to_hal_features(&device_info);
 
@@ -1319,7 +1464,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             }
         };
 
-        format::Properties {
+        let mut properties = format::Properties {
             linear_tiling: conv::map_image_features(
                 properties.linear_tiling_features,
                 supports_transfer_bits,
@@ -1332,7 +1477,17 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             ),
             buffer_features: conv::map_buffer_features(properties.buffer_features),
             drm_format_properties,
-        }
+        };
+        if format.map_or(false, |format| format.is_planar()) {
+            // Sampling multi-planar images requires sampler YCbCr conversions,
+            // which aren't exposed.
+            let sampled = format::ImageFeature::SAMPLED
+                | format::ImageFeature::SAMPLED_LINEAR
+                | format::ImageFeature::SAMPLED_MINMAX;
+            properties.linear_tiling -= sampled;
+            properties.optimal_tiling -= sampled;
+        }
+        properties
     }
 
     fn image_format_properties(
@@ -1689,6 +1844,8 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                 max_fragment_input_components: limits.max_fragment_input_components as _,
                 max_fragment_output_attachments: limits.max_fragment_output_attachments as _,
                 max_framebuffer_layers: limits.max_framebuffer_layers as _,
+                // Multiview render passes aren't exposed yet.
+                max_multiview_view_count: 0,
                 max_geometry_input_components: limits.max_geometry_input_components as _,
                 max_geometry_output_components: limits.max_geometry_output_components as _,
                 max_geometry_output_vertices: limits.max_geometry_output_vertices as _,
@@ -1791,6 +1948,7 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
                     .mesh_output_per_vertex_granularity,
                 mesh_output_per_primitive_granularity: mesh_shader_properties
                     .mesh_output_per_primitive_granularity,
+                first_task: true,
             };
 
             sampler_reduction_capabilities = hal::SamplerReductionProperties {
@@ -1813,6 +1971,8 @@ impl adapter::PhysicalDevice<Backend> for PhysicalDevice {
             descriptor_indexing: descriptor_indexing_capabilities,
             mesh_shader: mesh_shader_capabilities,
             sampler_reduction: sampler_reduction_capabilities,
+            ray_tracing: Default::default(),
+            shading_rate: Default::default(),
             performance_caveats: Default::default(),
             dynamic_pipeline_states: DynamicStates::all(),
             downlevel: DownlevelProperties::all_enabled(),
@@ -7,7 +7,7 @@ use hal::{
         CommandBufferInheritanceInfo, DescriptorSetOffset, ImageBlit, ImageCopy, ImageResolve,
         Level, SubpassContents,
     },
-    device::OutOfMemory,
+    device::OomOrDeviceLost,
     image::{Filter, Layout, SubresourceRange},
     memory::{Barrier, Dependencies},
     pso, query,
@@ -29,7 +29,8 @@ impl hal::queue::Queue<Backend> for Queue {
         _: Iw,
         _: Is,
         _fence: Option<&mut <Backend as hal::Backend>::Fence>,
-    ) where
+    ) -> Result<(), OomOrDeviceLost>
+    where
         Ic: Iterator<Item = &'a <Backend as hal::Backend>::CommandBuffer>,
     {
         todo!()
@@ -44,7 +45,7 @@ impl hal::queue::Queue<Backend> for Queue {
         todo!()
     }
 
-    fn wait_idle(&mut self) -> Result<(), OutOfMemory> {
+    fn wait_idle(&mut self) -> Result<(), OomOrDeviceLost> {
         todo!()
     }
 }
@@ -233,6 +234,14 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn set_shading_rate(
+        &mut self,
+        _rate: pso::ShadingRate,
+        _combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        todo!()
+    }
+
     unsafe fn begin_render_pass<T>(
         &mut self,
         _render_pass: &<Backend as hal::Backend>::RenderPass,
@@ -311,6 +320,47 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        _descs: &[hal::acceleration_structure::BuildDesc<Backend>],
+    ) {
+        todo!()
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        _src: &(),
+        _dst: &(),
+        _mode: hal::acceleration_structure::CopyMode,
+    ) {
+        todo!()
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, _pipeline: &()) {
+        todo!()
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        _layout: &<Backend as hal::Backend>::PipelineLayout,
+        _first_set: usize,
+        _sets: I,
+        _offsets: J,
+    ) where
+        I: Iterator<Item = &'a <Backend as hal::Backend>::DescriptorSet>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        todo!()
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        _tables: &pso::ShaderBindingTables<Backend>,
+        _count: WorkGroupCount,
+    ) {
+        todo!()
+    }
+
     unsafe fn copy_buffer<T>(
         &mut self,
         _src: &<Backend as hal::Backend>::Buffer,
@@ -420,6 +470,25 @@ impl hal::command::CommandBuffer<Backend> for CommandBuffer {
         todo!()
     }
 
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, _first_binding: u32, _buffers: I)
+    where
+        I: Iterator<Item = (&'a <Backend as hal::Backend>::Buffer, buffer::SubRange)>,
+    {
+        todo!()
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        todo!()
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        todo!()
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, _instances: Range<InstanceCount>) {
+        todo!()
+    }
+
     unsafe fn draw_mesh_tasks(&mut self, _task_count: TaskCount, _first_task: TaskCount) {
         todo!()
     }
@@ -6,7 +6,8 @@ use std::{
 use hal::{
     buffer,
     device::{
-        AllocationError, BindError, DeviceLost, MapError, OutOfMemory, ShaderError, WaitError,
+        AllocationError, BindError, DeviceLost, MapError, OomOrDeviceLost, OutOfMemory,
+        ShaderError, WaitError,
     },
     display, format, image, memory,
     memory::{Requirements, Segment},
@@ -34,6 +35,33 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    fn memory_budget(&self) -> Vec<memory::HeapBudget> {
+        // WebGPU doesn't expose its memory, so there are no heaps to report.
+        Vec::new()
+    }
+
+    unsafe fn set_memory_priority(
+        &self,
+        _memory: &<Backend as hal::Backend>::Memory,
+        _priority: memory::Priority,
+    ) {
+        // The residency of the memory isn't managed by the backend.
+    }
+
+    unsafe fn make_resident<'a, I>(&self, _memories: I) -> Result<(), OutOfMemory>
+    where
+        I: Iterator<Item = &'a <Backend as hal::Backend>::Memory>,
+    {
+        Ok(())
+    }
+
+    unsafe fn evict<'a, I>(&self, _memories: I)
+    where
+        I: Iterator<Item = &'a <Backend as hal::Backend>::Memory>,
+    {
+        // The residency of the memory isn't managed by the backend.
+    }
+
     unsafe fn create_command_pool(
         &self,
         _family: QueueFamilyId,
@@ -136,6 +164,28 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        _desc: &pso::RayTracingPipelineDesc<'a, Backend>,
+        _cache: Option<&<Backend as hal::Backend>::PipelineCache>,
+    ) -> Result<(), pso::CreationError> {
+        Err(pso::CreationError::UnsupportedPipeline)
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, _pipeline: ()) {
+        todo!()
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        _pipeline: &(),
+        _first_group: u32,
+        _group_count: u32,
+        _data: &mut [u8],
+    ) -> Result<(), hal::device::OutOfMemory> {
+        todo!()
+    }
+
     unsafe fn create_framebuffer<I>(
         &self,
         _pass: &<Backend as hal::Backend>::RenderPass,
@@ -205,6 +255,10 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    fn format_properties(&self, _format: format::Format) -> format::Properties {
+        todo!()
+    }
+
     unsafe fn create_image(
         &self,
         _kind: image::Kind,
@@ -264,7 +318,7 @@ impl hal::device::Device<Backend> for Device {
     unsafe fn create_sampler(
         &self,
         _desc: &image::SamplerDesc,
-    ) -> Result<<Backend as hal::Backend>::Sampler, AllocationError> {
+    ) -> Result<<Backend as hal::Backend>::Sampler, image::SamplerCreationError> {
         todo!()
     }
 
@@ -434,6 +488,31 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        _geometry: &hal::acceleration_structure::GeometryInfo<Backend>,
+    ) -> hal::acceleration_structure::SizeRequirements {
+        todo!()
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        _desc: &hal::acceleration_structure::CreateDesc<Backend>,
+    ) -> Result<(), hal::device::OutOfMemory> {
+        todo!()
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        _accel_struct: &(),
+    ) -> hal::acceleration_structure::DeviceAddress {
+        todo!()
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, _accel_struct: ()) {
+        todo!()
+    }
+
     unsafe fn get_query_pool_results(
         &self,
         _pool: &<Backend as hal::Backend>::QueryPool,
@@ -445,7 +524,7 @@ impl hal::device::Device<Backend> for Device {
         todo!()
     }
 
-    fn wait_idle(&self) -> Result<(), OutOfMemory> {
+    fn wait_idle(&self) -> Result<(), OomOrDeviceLost> {
         todo!()
     }
 
@@ -517,6 +596,22 @@ impl hal::device::Device<Backend> for Device {
         // TODO
     }
 
+    unsafe fn set_graphics_pipeline_name(
+        &self,
+        _pipeline: &mut <Backend as hal::Backend>::GraphicsPipeline,
+        _name: &str,
+    ) {
+        // TODO
+    }
+
+    unsafe fn set_compute_pipeline_name(
+        &self,
+        _pipeline: &mut <Backend as hal::Backend>::ComputePipeline,
+        _name: &str,
+    ) {
+        // TODO
+    }
+
     unsafe fn set_display_power_state(
         &self,
         _display: &display::Display<Backend>,
@@ -46,6 +46,7 @@ impl hal::Backend for Backend {
 
     type ComputePipeline = ();
     type GraphicsPipeline = ();
+    type RayTracingPipeline = ();
     type PipelineCache = ();
     type PipelineLayout = ();
     type DescriptorSetLayout = ();
@@ -56,6 +57,7 @@ impl hal::Backend for Backend {
     type Semaphore = ();
     type Event = ();
     type QueryPool = ();
+    type AccelerationStructure = ();
 
     type Display = ();
     type DisplayMode = ();
@@ -150,6 +152,10 @@ fn map_wgpu_adapter_to_hal_adapter(adapter: web_sys::GpuAdapter) -> Adapter<Back
         vendor: 0,
         device: 0,
         device_type: DeviceType::Other,
+        driver: String::new(),
+        unified_memory: false,
+        luid: None,
+        uuid: None,
     };
     let physical_device = PhysicalDevice(adapter);
     let queue_family = QueueFamily {};
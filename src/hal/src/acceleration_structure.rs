@@ -0,0 +1,243 @@
+//! Acceleration structures, the spatial data structures traversed by the rays
+//! of a [ray tracing pipeline][crate::pso::RayTracingPipelineDesc].
+//!
+//! The geometry of a scene is built into bottom level acceleration structures,
+//! out of triangles or axis-aligned bounding boxes. These are then referenced by
+//! the [instances][Instance] of a top level acceleration structure, which is the
+//! one bound to the shaders.
+//!
+//! Acceleration structures are stored in buffers created with the
+//! [`ACCELERATION_STRUCTURE_STORAGE`][buffer::Usage::ACCELERATION_STRUCTURE_STORAGE]
+//! usage. The size of the storage, and of the scratch buffer needed to build them,
+//! is given by [`Device::get_acceleration_structure_build_requirements`].
+//!
+//! This functionality requires `Features::ACCELERATION_STRUCTURE`.
+//!
+//! [`Device::get_acceleration_structure_build_requirements`]:
+//! crate::device::Device::get_acceleration_structure_build_requirements
+
+use crate::{buffer, format::Format, Backend, IndexType};
+
+/// Address of an acceleration structure in device memory, used to
+/// reference it from an [`Instance`].
+pub type DeviceAddress = u64;
+
+/// Type of an acceleration structure.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Type {
+    /// Holds instances of bottom level acceleration structures.
+    TopLevel,
+    /// Holds triangles or bounding boxes.
+    BottomLevel,
+}
+
+bitflags!(
+    /// Options for building an acceleration structure.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct BuildFlags: u32 {
+        /// The acceleration structure can be updated after it's built,
+        /// by building it again with a source.
+        const ALLOW_UPDATE = 0x1;
+        /// The acceleration structure can be the source of a compacting copy.
+        const ALLOW_COMPACTION = 0x2;
+        /// Favor the speed of the traversal over the speed of the build.
+        const PREFER_FAST_TRACE = 0x4;
+        /// Favor the speed of the build over the speed of the traversal.
+        const PREFER_FAST_BUILD = 0x8;
+        /// Minimize the memory used by the acceleration structure and its build,
+        /// possibly at the expense of the speed of both.
+        const LOW_MEMORY = 0x10;
+    }
+);
+
+bitflags!(
+    /// Options for a geometry of a bottom level acceleration structure.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct GeometryFlags: u32 {
+        /// The any-hit shaders are never invoked for this geometry.
+        const OPAQUE = 0x1;
+        /// The any-hit shaders are invoked at most once per primitive of this geometry.
+        const NO_DUPLICATE_ANY_HIT_INVOCATION = 0x2;
+    }
+);
+
+bitflags!(
+    /// Options for an [`Instance`] of a top level acceleration structure.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct InstanceFlags: u8 {
+        /// Face culling is disabled for this instance.
+        const TRIANGLE_CULL_DISABLE = 0x1;
+        /// Counter-clockwise triangles are front facing, instead of clockwise ones.
+        const TRIANGLE_FRONT_COUNTERCLOCKWISE = 0x2;
+        /// All the geometries of the instance are treated as opaque.
+        const FORCE_OPAQUE = 0x4;
+        /// None of the geometries of the instance are treated as opaque.
+        const FORCE_NO_OPAQUE = 0x8;
+    }
+);
+
+/// Triangles of a bottom level acceleration structure.
+#[derive(Debug)]
+pub struct Triangles<'a, B: Backend> {
+    /// Format of the vertex positions. Only the first three components are used.
+    pub vertex_format: Format,
+    /// Buffer holding the vertex positions, and the offset of the first one.
+    pub vertex_buffer: (&'a B::Buffer, buffer::Offset),
+    /// Distance between two vertex positions, in bytes.
+    pub vertex_stride: buffer::Stride,
+    /// Number of vertices in the vertex buffer.
+    pub vertex_count: u32,
+    /// Buffer holding the indices, and the offset of the first one.
+    /// The triangles are not indexed if this is `None`.
+    pub index_buffer: Option<(&'a B::Buffer, buffer::Offset, IndexType)>,
+    /// Buffer holding a row-major 3x4 matrix of `f32`, used to transform
+    /// the vertex positions, and its offset.
+    pub transform: Option<(&'a B::Buffer, buffer::Offset)>,
+}
+
+/// Geometry of an acceleration structure.
+#[derive(Debug)]
+pub enum Geometry<'a, B: Backend> {
+    /// Triangles, for a bottom level acceleration structure.
+    Triangles(Triangles<'a, B>),
+    /// Axis-aligned bounding boxes, for a bottom level acceleration structure.
+    ///
+    /// Each box is stored as its minimum and its maximum corners, with three `f32`
+    /// components each.
+    Aabbs {
+        /// Buffer holding the boxes, and the offset of the first one.
+        buffer: (&'a B::Buffer, buffer::Offset),
+        /// Distance between two boxes, in bytes.
+        stride: buffer::Stride,
+    },
+    /// Instances, for a top level acceleration structure.
+    ///
+    /// The instances are tightly packed, and laid out as [`Instance`].
+    Instances {
+        /// Buffer holding the instances, and the offset of the first one.
+        buffer: (&'a B::Buffer, buffer::Offset),
+    },
+}
+
+/// Geometry of an acceleration structure, along with the number of primitives
+/// built out of it.
+#[derive(Debug)]
+pub struct GeometryDesc<'a, B: Backend> {
+    /// Geometry data.
+    pub geometry: Geometry<'a, B>,
+    /// Number of triangles, boxes or instances of the geometry.
+    pub primitive_count: u32,
+    /// Options of the geometry.
+    pub flags: GeometryFlags,
+}
+
+/// Contents of an acceleration structure.
+///
+/// Top level acceleration structures have a single geometry of instances,
+/// bottom level ones have any number of geometries which are either all
+/// triangles or all bounding boxes.
+#[derive(Debug)]
+pub struct GeometryInfo<'a, B: Backend> {
+    /// Type of the acceleration structure.
+    pub ty: Type,
+    /// Options of the build.
+    pub flags: BuildFlags,
+    /// Geometries of the acceleration structure.
+    pub geometries: &'a [GeometryDesc<'a, B>],
+}
+
+/// Sizes of the buffers needed to build an acceleration structure.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SizeRequirements {
+    /// Size of the acceleration structure storage.
+    pub acceleration_structure_size: u64,
+    /// Size of the scratch buffer needed to build the acceleration structure.
+    pub build_scratch_size: u64,
+    /// Size of the scratch buffer needed to update the acceleration structure.
+    pub update_scratch_size: u64,
+}
+
+/// Description of an acceleration structure to create.
+#[derive(Debug)]
+pub struct CreateDesc<'a, B: Backend> {
+    /// Type of the acceleration structure.
+    pub ty: Type,
+    /// Buffer in which the acceleration structure is stored. It must be created with
+    /// the [`ACCELERATION_STRUCTURE_STORAGE`][buffer::Usage::ACCELERATION_STRUCTURE_STORAGE]
+    /// usage, and stay alive as long as the acceleration structure.
+    pub buffer: &'a B::Buffer,
+    /// Offset of the acceleration structure in the buffer, aligned to 256 bytes.
+    pub offset: buffer::Offset,
+    /// Size of the acceleration structure, as given by
+    /// [`SizeRequirements::acceleration_structure_size`].
+    pub size: u64,
+}
+
+/// Description of an acceleration structure build.
+#[derive(Debug)]
+pub struct BuildDesc<'a, B: Backend> {
+    /// Contents of the acceleration structure.
+    pub geometry: GeometryInfo<'a, B>,
+    /// Acceleration structure to update, which must have been built with
+    /// [`ALLOW_UPDATE`][BuildFlags::ALLOW_UPDATE] and the same geometries.
+    /// The acceleration structure is built from scratch if this is `None`.
+    pub src: Option<&'a B::AccelerationStructure>,
+    /// Acceleration structure to build. It can be the same as `src`.
+    pub dst: &'a B::AccelerationStructure,
+    /// Buffer used as scratch memory during the build, and the offset at which the
+    /// scratch memory starts. It must be at least as large as the `build_scratch_size`,
+    /// or `update_scratch_size` if `src` is given, of the [`SizeRequirements`].
+    pub scratch: (&'a B::Buffer, buffer::Offset),
+}
+
+/// Mode of an acceleration structure copy.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CopyMode {
+    /// Copy the acceleration structure as is.
+    Clone,
+    /// Copy the acceleration structure into a smaller storage. The source must
+    /// have been built with [`ALLOW_COMPACTION`][BuildFlags::ALLOW_COMPACTION].
+    Compact,
+}
+
+/// Instance of a bottom level acceleration structure in a top level one,
+/// laid out as expected by the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct Instance {
+    /// Row-major 3x4 matrix transforming the acceleration structure.
+    pub transform: [[f32; 4]; 3],
+    /// Bits 0..24 hold the custom index of the instance, available to the shaders,
+    /// and bits 24..32 hold the visibility mask, against which the mask of the
+    /// traced rays is tested.
+    pub custom_index_and_mask: u32,
+    /// Bits 0..24 hold the offset of the hit groups of the instance in the shader
+    /// binding table, and bits 24..32 hold the [`InstanceFlags`].
+    pub shader_binding_table_offset_and_flags: u32,
+    /// Address of the bottom level acceleration structure, as given by
+    /// [`Device::get_acceleration_structure_address`][crate::device::Device::get_acceleration_structure_address].
+    pub acceleration_structure: DeviceAddress,
+}
+
+impl Instance {
+    /// Create an instance of an acceleration structure.
+    pub fn new(
+        transform: [[f32; 4]; 3],
+        custom_index: u32,
+        mask: u8,
+        shader_binding_table_offset: u32,
+        flags: InstanceFlags,
+        acceleration_structure: DeviceAddress,
+    ) -> Self {
+        debug_assert!(custom_index < 1 << 24 && shader_binding_table_offset < 1 << 24);
+        Instance {
+            transform,
+            custom_index_and_mask: custom_index | (mask as u32) << 24,
+            shader_binding_table_offset_and_flags: shader_binding_table_offset
+                | (flags.bits() as u32) << 24,
+            acceleration_structure,
+        }
+    }
+}
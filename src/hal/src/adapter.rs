@@ -99,6 +99,28 @@ pub trait PhysicalDevice<B: Backend>: fmt::Debug + Any + Send + Sync {
         requested_features: Features,
     ) -> Result<Gpu<B>, device::CreationError>;
 
+    /// Number of nodes of the device, which is more than one for several GPUs
+    /// linked together.
+    fn node_count(&self) -> u32 {
+        1
+    }
+
+    /// Create a new [logical device][crate::device::Device] spanning the `nodes` of
+    /// this physical device.
+    ///
+    /// The queues are created on each of the nodes, with one [queue group][QueueGroup]
+    /// per node and family. The devices with a single node only accept `1` for `nodes`,
+    /// which makes this equivalent to [`open`][PhysicalDevice::open].
+    unsafe fn open_group(
+        &self,
+        families: &[(&B::QueueFamily, &[QueuePriority])],
+        requested_features: Features,
+        nodes: device::NodeMask,
+    ) -> Result<Gpu<B>, device::CreationError> {
+        assert_eq!(nodes, 1, "The device has a single node");
+        self.open(families, requested_features)
+    }
+
     /// Fetch details for a particular format.
     fn format_properties(&self, format: Option<format::Format>) -> format::Properties;
 
@@ -240,6 +262,36 @@ pub struct AdapterInfo {
     pub device: usize,
     /// Type of device
     pub device_type: DeviceType,
+    /// Driver version, in the format of the vendor, or empty if unknown
+    pub driver: String,
+    /// Whether the device shares its memory with the host, like integrated GPUs.
+    ///
+    /// The device local memory is then as fast to access from the host,
+    /// making staging copies unnecessary.
+    pub unified_memory: bool,
+    /// Locally unique identifier of the adapter, if known.
+    ///
+    /// It identifies the adapter across the APIs of the system until the next
+    /// reboot, like DXGI on Windows.
+    pub luid: Option<[u8; 8]>,
+    /// Universally unique identifier of the device, if known.
+    ///
+    /// It identifies the device across the APIs, the processes and the reboots.
+    pub uuid: Option<[u8; 16]>,
+}
+
+impl AdapterInfo {
+    /// Check if `self` and `other` describe the same adapter, possibly enumerated
+    /// by different backends.
+    ///
+    /// Returns `None` if the adapters share no identifier to compare.
+    pub fn same_adapter(&self, other: &AdapterInfo) -> Option<bool> {
+        match (self.luid, other.luid, self.uuid, other.uuid) {
+            (Some(a), Some(b), _, _) => Some(a == b),
+            (_, _, Some(a), Some(b)) => Some(a == b),
+            _ => None,
+        }
+    }
 }
 
 /// Information about a graphics device, supported by the backend.
@@ -258,3 +310,205 @@ pub struct Adapter<B: Backend> {
     /// [Queue families][crate::queue::family::QueueFamily] supported by this adapter.
     pub queue_families: Vec<B::QueueFamily>,
 }
+
+impl<B: Backend> Adapter<B> {
+    /// Check that the adapter supports all of the given `features`.
+    pub fn require_features(&self, features: Features) -> Result<(), Rejection> {
+        let missing = features - self.physical_device.features();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Rejection::MissingFeatures(missing))
+        }
+    }
+
+    /// Check that the largest device-local memory heap of the adapter holds
+    /// at least `size` bytes.
+    pub fn require_device_local_memory(&self, size: u64) -> Result<(), Rejection> {
+        let available = self
+            .physical_device
+            .memory_properties()
+            .memory_heaps
+            .iter()
+            .filter(|heap| heap.flags.contains(memory::HeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .max()
+            .unwrap_or(0);
+        if available >= size {
+            Ok(())
+        } else {
+            Err(Rejection::InsufficientMemory {
+                required: size,
+                available,
+            })
+        }
+    }
+}
+
+/// Reason for an [adapter][Adapter] not being chosen by [`select_adapter`].
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum Rejection {
+    /// Some of the required features are not supported.
+    #[error("Missing features: {0:?}")]
+    MissingFeatures(Features),
+    /// The adapter is not of a wanted type.
+    #[error("Unwanted device type: {0:?}")]
+    DeviceType(DeviceType),
+    /// The adapter doesn't have enough device-local memory.
+    #[error("Not enough device-local memory: {available} bytes available, {required} required")]
+    InsufficientMemory {
+        /// Amount of memory asked for, in bytes.
+        required: u64,
+        /// Size of the largest device-local heap, in bytes.
+        available: u64,
+    },
+    /// The adapter is usable, but a better one was found.
+    #[error("Scored {score}, while the chosen adapter scored {best}")]
+    Outscored {
+        /// Score of this adapter.
+        score: u32,
+        /// Score of the chosen adapter.
+        best: u32,
+    },
+    /// Application-specific reason.
+    #[error("{0}")]
+    Other(String),
+}
+
+/// An adapter that wasn't chosen, along with the reason why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RejectedAdapter {
+    /// Information about the rejected adapter.
+    pub info: AdapterInfo,
+    /// Why the adapter was rejected.
+    pub reason: Rejection,
+}
+
+/// Kind of adapter to pick with [`Instance::pick_adapter`][crate::Instance::pick_adapter].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AdapterPreference {
+    /// The fastest adapter, preferring the discrete GPUs over the integrated ones.
+    HighPerformance,
+    /// The adapter using the least power, preferring the integrated GPUs over
+    /// the discrete ones.
+    LowPower,
+    /// A software implementation running on the CPU, rejecting the hardware adapters.
+    Software,
+}
+
+impl AdapterPreference {
+    /// Score an adapter of this preference, for [`select_adapter`].
+    ///
+    /// The hardware preferences accept any adapter, falling back to the
+    /// software ones if nothing else is available.
+    pub fn score(&self, info: &AdapterInfo) -> Result<u32, Rejection> {
+        let software = match info.device_type {
+            DeviceType::Cpu | DeviceType::VirtualGpu => true,
+            _ => false,
+        };
+        match *self {
+            AdapterPreference::HighPerformance => Ok(match info.device_type {
+                DeviceType::DiscreteGpu => 3,
+                DeviceType::IntegratedGpu => 2,
+                DeviceType::Other => 1,
+                DeviceType::VirtualGpu | DeviceType::Cpu => 0,
+            }),
+            AdapterPreference::LowPower => Ok(match info.device_type {
+                DeviceType::IntegratedGpu => 3,
+                DeviceType::DiscreteGpu => 2,
+                DeviceType::Other => 1,
+                DeviceType::VirtualGpu | DeviceType::Cpu => 0,
+            }),
+            AdapterPreference::Software if software => Ok(0),
+            AdapterPreference::Software => Err(Rejection::DeviceType(info.device_type.clone())),
+        }
+    }
+}
+
+/// Outcome of an [adapter selection][select_adapter].
+#[derive(Debug)]
+pub struct AdapterSelection<B: Backend> {
+    /// The adapter with the highest score, if any was accepted.
+    pub adapter: Option<Adapter<B>>,
+    /// All the other adapters, in enumeration order.
+    pub rejected: Vec<RejectedAdapter>,
+}
+
+/// Choose one of the `adapters` according to an application-defined policy.
+///
+/// The `score` callback returns either a score for an acceptable adapter, where
+/// higher is better, or the reason why the adapter can't be used.
+/// When several adapters share the best score, the first one enumerated wins.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate gfx_backend_empty;
+/// # extern crate gfx_hal;
+/// use gfx_backend_empty as backend;
+/// use gfx_hal::{
+///     adapter::{DeviceType, Rejection},
+///     Features, Instance,
+/// };
+///
+/// let instance = backend::Instance::create("My App", 1).unwrap();
+/// let selection = instance.select_adapter(|adapter| {
+///     adapter.require_features(Features::SAMPLER_ANISOTROPY)?;
+///     adapter.require_device_local_memory(256 << 20)?;
+///     match adapter.info.device_type {
+///         DeviceType::DiscreteGpu => Ok(2),
+///         DeviceType::IntegratedGpu => Ok(1),
+///         ref other => Err(Rejection::DeviceType(other.clone())),
+///     }
+/// });
+/// for rejected in &selection.rejected {
+///     println!("Skipped {}: {}", rejected.info.name, rejected.reason);
+/// }
+/// ```
+pub fn select_adapter<B, F>(adapters: Vec<Adapter<B>>, mut score: F) -> AdapterSelection<B>
+where
+    B: Backend,
+    F: FnMut(&Adapter<B>) -> Result<u32, Rejection>,
+{
+    let mut best: Option<(usize, u32, Adapter<B>)> = None;
+    let mut scored = Vec::new();
+    let mut rejected = Vec::new();
+
+    for (index, adapter) in adapters.into_iter().enumerate() {
+        match score(&adapter) {
+            Ok(value) => {
+                scored.push((index, value, adapter.info.clone()));
+                match best {
+                    Some((_, best_value, _)) if best_value >= value => {}
+                    _ => best = Some((index, value, adapter)),
+                }
+            }
+            Err(reason) => rejected.push((
+                index,
+                RejectedAdapter {
+                    info: adapter.info,
+                    reason,
+                },
+            )),
+        }
+    }
+
+    if let Some((best_index, best_value, _)) = best {
+        for (index, value, info) in scored {
+            if index != best_index {
+                let reason = Rejection::Outscored {
+                    score: value,
+                    best: best_value,
+                };
+                rejected.push((index, RejectedAdapter { info, reason }));
+            }
+        }
+    }
+    rejected.sort_by_key(|&(index, _)| index);
+
+    AdapterSelection {
+        adapter: best.map(|(_, _, adapter)| adapter),
+        rejected: rejected.into_iter().map(|(_, rejected)| rejected).collect(),
+    }
+}
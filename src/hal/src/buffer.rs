@@ -7,6 +7,8 @@
 
 use crate::{device::OutOfMemory, format::Format};
 
+use std::convert::TryFrom;
+
 /// An offset inside a buffer, in bytes.
 pub type Offset = u64;
 
@@ -39,6 +41,27 @@ impl SubRange {
 /// Buffer state.
 pub type State = Access;
 
+/// Error converting an offset or size into a narrower integer type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Value {value} does not fit into {target}")]
+pub struct CastError {
+    /// The value that was being converted.
+    pub value: Offset,
+    /// Name of the target type.
+    pub target: &'static str,
+}
+
+/// Convert an offset or size into the integer type expected by a backend API,
+/// such as `GLintptr`, `GLsizei` or `UINT`.
+///
+/// Unlike an `as` cast, this fails instead of silently truncating the value.
+pub fn cast<T: TryFrom<Offset>>(value: Offset) -> Result<T, CastError> {
+    T::try_from(value).map_err(|_| CastError {
+        value,
+        target: std::any::type_name::<T>(),
+    })
+}
+
 /// Error creating a buffer.
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum CreationError {
@@ -85,6 +108,12 @@ bitflags!(
         const VERTEX = 0x80;
         ///
         const INDIRECT = 0x100;
+        /// Holds the records of a shader binding table.
+        const SHADER_BINDING_TABLE = 0x400;
+        /// Holds the geometry, instances or transforms read by acceleration structure builds.
+        const ACCELERATION_STRUCTURE_BUILD_INPUT = 0x8_0000;
+        /// Stores acceleration structures.
+        const ACCELERATION_STRUCTURE_STORAGE = 0x10_0000;
     }
 );
 
@@ -144,5 +173,31 @@ bitflags!(
         const MEMORY_READ = 0x8000;
         ///
         const MEMORY_WRITE = 0x10000;
+        /// Write vertex outputs captured by transform feedback.
+        const TRANSFORM_FEEDBACK_WRITE = 0x200_0000;
+        /// Read acceleration structures, in the ray tracing shaders or by acceleration
+        /// structure builds and copies.
+        const ACCELERATION_STRUCTURE_READ = 0x20_0000;
+        /// Write acceleration structures, by acceleration structure builds and copies.
+        const ACCELERATION_STRUCTURE_WRITE = 0x40_0000;
     }
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cast_boundaries() {
+        assert_eq!(cast::<i32>(0), Ok(0));
+        assert_eq!(cast::<i32>(i32::MAX as Offset), Ok(i32::MAX));
+        let err = cast::<i32>(i32::MAX as Offset + 1).unwrap_err();
+        assert_eq!(err.value, 1 << 31);
+        assert!(err.to_string().contains("2147483648"), "{}", err);
+        assert_eq!(cast::<u32>(u32::MAX as Offset), Ok(u32::MAX));
+        assert!(cast::<u32>(u32::MAX as Offset + 1).is_err());
+        assert_eq!(cast::<u64>(Offset::MAX), Ok(u64::MAX));
+        assert!(cast::<i64>(Offset::MAX).is_err());
+        assert!(cast::<isize>(Offset::MAX).is_err());
+    }
+}
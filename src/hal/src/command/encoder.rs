@@ -0,0 +1,541 @@
+//! Typed command encoders.
+//!
+//! The [`CommandBuffer`] trait exposes all the commands at once, leaving it to the
+//! user to only record the ones supported by the queue family of the command buffer,
+//! and to keep the draws within render passes. An [`Encoder`] wraps a command buffer
+//! in the recording state and checks both at compile time instead:
+//!
+//! - the capability `C` of the encoder, one of the [`General`], [`Graphics`],
+//!   [`Compute`] and [`Transfer`] markers, restricts the commands it exposes;
+//! - [`Encoder::begin_render_pass`] returns a [`RenderPassEncoder`], which alone
+//!   exposes the draws, and ends the render pass when dropped.
+//!
+//! With the `layout-tracking` feature, [`Encoder::transition`] records the barriers
+//! moving an image from the state tracked by a [`LayoutTracker`][crate::track::LayoutTracker].
+
+use crate::{
+    buffer,
+    command::{
+        AttachmentClear, BufferCopy, BufferImageCopy, ClearValue, CommandBuffer,
+        CommandBufferFlags, DescriptorSetOffset, ImageBlit, ImageCopy, ImageResolve,
+        RenderAttachmentInfo, SubpassContents,
+    },
+    image::{Filter, Layout, SubresourceRange},
+    memory::{Barrier, Dependencies},
+    pso,
+    queue::QueueType,
+    Backend, DrawCount, IndexCount, IndexType, InstanceCount, VertexCount, VertexOffset,
+    WorkGroupCount,
+};
+
+use std::{fmt, marker::PhantomData, ops::Range};
+
+/// Capability of the queue family a command buffer is allocated from.
+pub trait Capability: fmt::Debug {
+    /// Check if the queues of the given type support this capability.
+    fn supported_by(queue_type: QueueType) -> bool;
+}
+
+/// Marker of the queue families supporting all the operations.
+#[derive(Debug)]
+pub enum General {}
+/// Marker of the queue families supporting graphics, compute and transfer operations.
+#[derive(Debug)]
+pub enum Graphics {}
+/// Marker of the queue families supporting compute and transfer operations.
+#[derive(Debug)]
+pub enum Compute {}
+/// Marker of the queue families only supporting transfer operations.
+#[derive(Debug)]
+pub enum Transfer {}
+
+impl Capability for General {
+    fn supported_by(queue_type: QueueType) -> bool {
+        queue_type == QueueType::General
+    }
+}
+impl Capability for Graphics {
+    fn supported_by(queue_type: QueueType) -> bool {
+        queue_type.supports_graphics()
+    }
+}
+impl Capability for Compute {
+    fn supported_by(queue_type: QueueType) -> bool {
+        queue_type.supports_compute()
+    }
+}
+impl Capability for Transfer {
+    fn supported_by(queue_type: QueueType) -> bool {
+        queue_type.supports_transfer()
+    }
+}
+
+/// The capability includes the operations of the capability `T`.
+pub trait Supports<T>: Capability {}
+
+impl<C: Capability> Supports<Transfer> for C {}
+impl Supports<Compute> for General {}
+impl Supports<Compute> for Graphics {}
+impl Supports<Compute> for Compute {}
+impl Supports<Graphics> for General {}
+impl Supports<Graphics> for Graphics {}
+
+/// Command buffer in the recording state, only exposing the commands supported
+/// by the capability `C`.
+///
+/// The commands are as unsafe as their [`CommandBuffer`] counterparts, except
+/// for the queue capability and render pass requirements, which are enforced
+/// by the types.
+#[derive(Debug)]
+pub struct Encoder<'a, B: Backend, C> {
+    raw: &'a mut B::CommandBuffer,
+    _capability: PhantomData<C>,
+}
+
+impl<'a, B: Backend, C: Capability> Encoder<'a, B, C> {
+    /// Begin recording a primary command buffer.
+    ///
+    /// # Safety
+    ///
+    /// - The command buffer must be in the initial state.
+    /// - It must be allocated from a pool of a queue family supporting `C`,
+    ///   see [`Capability::supported_by`].
+    pub unsafe fn begin(raw: &'a mut B::CommandBuffer, flags: CommandBufferFlags) -> Self {
+        raw.begin_primary(flags);
+        Self::from_raw(raw)
+    }
+
+    /// Wrap a command buffer that is already recording.
+    ///
+    /// # Safety
+    ///
+    /// - The command buffer must be in the recording state, outside of a render pass.
+    /// - It must be allocated from a pool of a queue family supporting `C`,
+    ///   see [`Capability::supported_by`].
+    pub unsafe fn from_raw(raw: &'a mut B::CommandBuffer) -> Self {
+        Encoder {
+            raw,
+            _capability: PhantomData,
+        }
+    }
+
+    /// Access the raw command buffer, e.g. to record commands the encoder doesn't expose.
+    pub fn raw(&mut self) -> &mut B::CommandBuffer {
+        self.raw
+    }
+
+    /// Finish recording, returning the raw command buffer in the executable state.
+    pub unsafe fn finish(self) -> &'a mut B::CommandBuffer {
+        self.raw.finish();
+        self.raw
+    }
+
+    /// Insert a synchronization dependency between pipeline stages.
+    pub unsafe fn pipeline_barrier<'b, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: IntoIterator<Item = Barrier<'b, B>>,
+    {
+        self.raw
+            .pipeline_barrier(stages, dependencies, barriers.into_iter());
+    }
+
+    /// Start a debug marker scope.
+    pub unsafe fn begin_debug_marker(&mut self, name: &str, color: u32) {
+        self.raw.begin_debug_marker(name, color);
+    }
+
+    /// End the last started debug marker scope.
+    pub unsafe fn end_debug_marker(&mut self) {
+        self.raw.end_debug_marker();
+    }
+}
+
+impl<'a, B: Backend, C: Supports<Transfer>> Encoder<'a, B, C> {
+    /// Fill a buffer with the given `u32` value.
+    pub unsafe fn fill_buffer(&mut self, buffer: &B::Buffer, range: buffer::SubRange, data: u32) {
+        self.raw.fill_buffer(buffer, range, data);
+    }
+
+    /// Copy data from the given slice into a buffer.
+    pub unsafe fn update_buffer(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        data: &[u8],
+    ) {
+        self.raw.update_buffer(buffer, offset, data);
+    }
+
+    /// Copy regions from the source to the destination buffer.
+    pub unsafe fn copy_buffer<T>(&mut self, src: &B::Buffer, dst: &B::Buffer, regions: T)
+    where
+        T: IntoIterator<Item = BufferCopy>,
+    {
+        self.raw.copy_buffer(src, dst, regions.into_iter());
+    }
+
+    /// Copy regions from the source to the destination image.
+    pub unsafe fn copy_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Image,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator<Item = ImageCopy>,
+    {
+        self.raw
+            .copy_image(src, src_layout, dst, dst_layout, regions.into_iter());
+    }
+
+    /// Copy regions from the source buffer to the destination image.
+    pub unsafe fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &B::Buffer,
+        dst: &B::Image,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator<Item = BufferImageCopy>,
+    {
+        self.raw
+            .copy_buffer_to_image(src, dst, dst_layout, regions.into_iter());
+    }
+
+    /// Copy regions from the source image to the destination buffer.
+    pub unsafe fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Buffer,
+        regions: T,
+    ) where
+        T: IntoIterator<Item = BufferImageCopy>,
+    {
+        self.raw
+            .copy_image_to_buffer(src, src_layout, dst, regions.into_iter());
+    }
+}
+
+impl<'a, B: Backend, C: Supports<Compute>> Encoder<'a, B, C> {
+    /// Clear an image to the given color/depth/stencil.
+    pub unsafe fn clear_image<T>(
+        &mut self,
+        image: &B::Image,
+        layout: Layout,
+        value: ClearValue,
+        subresource_ranges: T,
+    ) where
+        T: IntoIterator<Item = SubresourceRange>,
+    {
+        self.raw
+            .clear_image(image, layout, value, subresource_ranges.into_iter());
+    }
+
+    /// Bind a compute pipeline.
+    pub unsafe fn bind_compute_pipeline(&mut self, pipeline: &B::ComputePipeline) {
+        self.raw.bind_compute_pipeline(pipeline);
+    }
+
+    /// Bind compute descriptor sets, starting at `first_set`.
+    pub unsafe fn bind_compute_descriptor_sets<'b, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator<Item = &'b B::DescriptorSet>,
+        J: IntoIterator<Item = DescriptorSetOffset>,
+    {
+        self.raw.bind_compute_descriptor_sets(
+            layout,
+            first_set,
+            sets.into_iter(),
+            offsets.into_iter(),
+        );
+    }
+
+    /// Modify the push constants of the compute pipeline.
+    pub unsafe fn push_compute_constants(
+        &mut self,
+        layout: &B::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.raw.push_compute_constants(layout, offset, constants);
+    }
+
+    /// Dispatch `count` work groups of the bound compute pipeline.
+    pub unsafe fn dispatch(&mut self, count: WorkGroupCount) {
+        self.raw.dispatch(count);
+    }
+
+    /// Dispatch work groups, reading the count from the buffer.
+    pub unsafe fn dispatch_indirect(&mut self, buffer: &B::Buffer, offset: buffer::Offset) {
+        self.raw.dispatch_indirect(buffer, offset);
+    }
+}
+
+impl<'a, B: Backend, C: Supports<Graphics>> Encoder<'a, B, C> {
+    /// Begin a render pass, recorded inline.
+    ///
+    /// The render pass ends when the returned encoder is dropped.
+    pub unsafe fn begin_render_pass<'b, T>(
+        &mut self,
+        render_pass: &B::RenderPass,
+        framebuffer: &B::Framebuffer,
+        render_area: pso::Rect,
+        attachments: T,
+    ) -> RenderPassEncoder<'_, B>
+    where
+        T: IntoIterator<Item = RenderAttachmentInfo<'b, B>>,
+    {
+        self.raw.begin_render_pass(
+            render_pass,
+            framebuffer,
+            render_area,
+            attachments.into_iter(),
+            SubpassContents::Inline,
+        );
+        RenderPassEncoder { raw: self.raw }
+    }
+
+    /// Copy regions from the source to the destination image, with scaling and filtering.
+    pub unsafe fn blit_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Image,
+        dst_layout: Layout,
+        filter: Filter,
+        regions: T,
+    ) where
+        T: IntoIterator<Item = ImageBlit>,
+    {
+        self.raw.blit_image(
+            src,
+            src_layout,
+            dst,
+            dst_layout,
+            filter,
+            regions.into_iter(),
+        );
+    }
+
+    /// Resolve regions of a multisampled image into a non-multisampled one.
+    pub unsafe fn resolve_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Image,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: IntoIterator<Item = ImageResolve>,
+    {
+        self.raw
+            .resolve_image(src, src_layout, dst, dst_layout, regions.into_iter());
+    }
+}
+
+#[cfg(feature = "layout-tracking")]
+impl<'a, B: Backend, C: Capability> Encoder<'a, B, C> {
+    /// Transition a subresource range of the image registered as `id` from its
+    /// tracked state to `state`, recording the barriers and applying them to the tracker.
+    ///
+    /// Nothing is recorded if the range is already in `state`.
+    pub unsafe fn transition(
+        &mut self,
+        tracker: &mut crate::track::LayoutTracker<B>,
+        stages: Range<pso::PipelineStage>,
+        id: crate::track::ImageId,
+        image: &B::Image,
+        range: &SubresourceRange,
+        state: crate::image::State,
+    ) -> Result<(), crate::track::LayoutError> {
+        let barriers = tracker.transition(id, image, range, state)?;
+        if !barriers.is_empty() {
+            self.raw
+                .pipeline_barrier(stages, Dependencies::empty(), barriers.into_iter());
+        }
+        Ok(())
+    }
+}
+
+/// Render pass being recorded by an [`Encoder`], ended when dropped.
+#[derive(Debug)]
+pub struct RenderPassEncoder<'a, B: Backend> {
+    raw: &'a mut B::CommandBuffer,
+}
+
+impl<'a, B: Backend> Drop for RenderPassEncoder<'a, B> {
+    fn drop(&mut self) {
+        unsafe {
+            self.raw.end_render_pass();
+        }
+    }
+}
+
+impl<'a, B: Backend> RenderPassEncoder<'a, B> {
+    /// Step to the next subpass, recorded inline.
+    pub unsafe fn next_subpass(&mut self) {
+        self.raw.next_subpass(SubpassContents::Inline);
+    }
+
+    /// Bind a graphics pipeline.
+    pub unsafe fn bind_graphics_pipeline(&mut self, pipeline: &B::GraphicsPipeline) {
+        self.raw.bind_graphics_pipeline(pipeline);
+    }
+
+    /// Bind graphics descriptor sets, starting at `first_set`.
+    pub unsafe fn bind_graphics_descriptor_sets<'b, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: IntoIterator<Item = &'b B::DescriptorSet>,
+        J: IntoIterator<Item = DescriptorSetOffset>,
+    {
+        self.raw.bind_graphics_descriptor_sets(
+            layout,
+            first_set,
+            sets.into_iter(),
+            offsets.into_iter(),
+        );
+    }
+
+    /// Modify the push constants of the graphics pipeline.
+    pub unsafe fn push_graphics_constants(
+        &mut self,
+        layout: &B::PipelineLayout,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.raw
+            .push_graphics_constants(layout, stages, offset, constants);
+    }
+
+    /// Bind vertex buffers, starting at `first_binding`.
+    pub unsafe fn bind_vertex_buffers<'b, T>(&mut self, first_binding: pso::BufferIndex, buffers: T)
+    where
+        T: IntoIterator<Item = (&'b B::Buffer, buffer::SubRange)>,
+    {
+        self.raw
+            .bind_vertex_buffers(first_binding, buffers.into_iter());
+    }
+
+    /// Bind the index buffer.
+    pub unsafe fn bind_index_buffer(
+        &mut self,
+        buffer: &B::Buffer,
+        sub: buffer::SubRange,
+        ty: IndexType,
+    ) {
+        self.raw.bind_index_buffer(buffer, sub, ty);
+    }
+
+    /// Set the viewports, starting at `first_viewport`.
+    pub unsafe fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: IntoIterator<Item = pso::Viewport>,
+    {
+        self.raw
+            .set_viewports(first_viewport, viewports.into_iter());
+    }
+
+    /// Set the scissor rectangles, starting at `first_scissor`.
+    pub unsafe fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: IntoIterator<Item = pso::Rect>,
+    {
+        self.raw.set_scissors(first_scissor, rects.into_iter());
+    }
+
+    /// Set the stencil reference value.
+    pub unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.raw.set_stencil_reference(faces, value);
+    }
+
+    /// Set the blend constant color.
+    pub unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.raw.set_blend_constants(color);
+    }
+
+    /// Clear regions of the attachments of the current subpass.
+    pub unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: IntoIterator<Item = AttachmentClear>,
+        U: IntoIterator<Item = pso::ClearRect>,
+    {
+        self.raw
+            .clear_attachments(clears.into_iter(), rects.into_iter());
+    }
+
+    /// Clear the depth of the depth-stencil attachment within the rects.
+    pub unsafe fn clear_depth<U>(&mut self, depth: pso::DepthValue, rects: U)
+    where
+        U: IntoIterator<Item = pso::ClearRect>,
+    {
+        self.raw.clear_depth(depth, rects.into_iter());
+    }
+
+    /// Clear the stencil of the depth-stencil attachment within the rects.
+    pub unsafe fn clear_stencil<U>(&mut self, stencil: pso::StencilValue, rects: U)
+    where
+        U: IntoIterator<Item = pso::ClearRect>,
+    {
+        self.raw.clear_stencil(stencil, rects.into_iter());
+    }
+
+    /// Draw non-indexed primitives.
+    pub unsafe fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.raw.draw(vertices, instances);
+    }
+
+    /// Draw indexed primitives.
+    pub unsafe fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.raw.draw_indexed(indices, base_vertex, instances);
+    }
+
+    /// Draw non-indexed primitives, reading the parameters from the buffer.
+    pub unsafe fn draw_indirect(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        self.raw.draw_indirect(buffer, offset, draw_count, stride);
+    }
+
+    /// Draw indexed primitives, reading the parameters from the buffer.
+    pub unsafe fn draw_indexed_indirect(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        self.raw
+            .draw_indexed_indirect(buffer, offset, draw_count, stride);
+    }
+
+    /// Access the raw command buffer, e.g. to record commands the encoder doesn't expose.
+    pub fn raw(&mut self) -> &mut B::CommandBuffer {
+        self.raw
+    }
+}
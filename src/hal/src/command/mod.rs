@@ -10,14 +10,17 @@
 //! that contain snippets of commands that do specific things, similar to function calls.
 //!
 //! All the possible commands are exposed by the [`CommandBuffer`][CommandBuffer] trait.
+//! The [`Encoder`][Encoder] wraps it to only expose the commands allowed by the queue
+//! capability and the current render pass.
 
 // TODO: Document pipelines and subpasses better.
 
 mod clear;
+mod encoder;
 mod structs;
 
 use crate::{
-    buffer,
+    acceleration_structure, buffer,
     image::{Filter, Layout, SubresourceRange},
     memory::{Barrier, Dependencies},
     pass, pso, query, Backend, DrawCount, IndexCount, IndexType, InstanceCount, TaskCount,
@@ -27,6 +30,7 @@ use crate::{
 use std::{any::Any, fmt, ops::Range};
 
 pub use self::clear::*;
+pub use self::encoder::*;
 pub use self::structs::*;
 
 /// Offset for dynamic descriptors.
@@ -61,7 +65,8 @@ pub enum Level {
 }
 
 /// Specifies how commands for the following render passes will be recorded.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum SubpassContents {
     /// Contents of the subpass will be inline in the command buffer,
     /// NOT in secondary command buffers.
@@ -161,6 +166,46 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
         T: Iterator<Item = AttachmentClear>,
         U: Iterator<Item = pso::ClearRect>;
 
+    /// Clear the depth of the depth-stencil attachment of the current subpass,
+    /// within the given rect's, leaving its stencil untouched.
+    ///
+    /// Like [`clear_attachments`][CommandBuffer::clear_attachments], the clear
+    /// ignores the write masks of the bound pipeline, which remain in effect for
+    /// the following draws.
+    ///
+    /// The depth-stencil attachment of the current subpass must have a depth aspect.
+    unsafe fn clear_depth<U>(&mut self, depth: pso::DepthValue, rects: U)
+    where
+        U: Iterator<Item = pso::ClearRect>,
+    {
+        let clear = AttachmentClear::DepthStencil {
+            depth: Some(depth),
+            stencil: None,
+        };
+        self.clear_attachments(std::iter::once(clear), rects)
+    }
+
+    /// Clear the stencil of the depth-stencil attachment of the current subpass,
+    /// within the given rect's, leaving its depth untouched.
+    ///
+    /// This is typically used to reset a stencil mask in the middle of a pass, e.g.
+    /// between the portals or the clipped widgets drawn with it.
+    /// Like [`clear_attachments`][CommandBuffer::clear_attachments], the clear
+    /// ignores the write masks of the bound pipeline, which remain in effect for
+    /// the following draws.
+    ///
+    /// The depth-stencil attachment of the current subpass must have a stencil aspect.
+    unsafe fn clear_stencil<U>(&mut self, stencil: pso::StencilValue, rects: U)
+    where
+        U: Iterator<Item = pso::ClearRect>,
+    {
+        let clear = AttachmentClear::DepthStencil {
+            depth: None,
+            stencil: Some(stencil),
+        };
+        self.clear_attachments(std::iter::once(clear), rects)
+    }
+
     /// "Resolves" a multisampled image, converting it into a non-multisampled
     /// image. Takes an iterator of regions to apply the resolution to.
     unsafe fn resolve_image<T>(
@@ -175,6 +220,9 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
 
     /// Copies regions from the source to destination image,
     /// applying scaling, filtering and potentially format conversion.
+    ///
+    /// Blitting the `COLOR` aspect of a multi-planar source image converts it
+    /// from YCbCr (BT.709, narrow range) to RGB, if the source format supports `BLIT_SRC`.
     unsafe fn blit_image<T>(
         &mut self,
         src: &B::Image,
@@ -281,6 +329,19 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Set the depth bias dynamically.
     unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias);
 
+    /// Set the shading rate of the following draws.
+    ///
+    /// The first combiner merges `rate` with the rate written by the shaders for each
+    /// primitive, and the second merges the result with the rate of the shading rate
+    /// attachment of the subpass, if any.
+    ///
+    /// Only valid to call if `Features::SHADING_RATE` is enabled.
+    unsafe fn set_shading_rate(
+        &mut self,
+        rate: pso::ShadingRate,
+        combiners: [pso::ShadingRateCombiner; 2],
+    );
+
     /// Begins recording commands for a render pass on the given framebuffer.
     ///
     /// # Arguments
@@ -375,6 +436,53 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// buffer during execution.
     unsafe fn dispatch_indirect(&mut self, buffer: &B::Buffer, offset: buffer::Offset);
 
+    /// Build or update acceleration structures.
+    ///
+    /// The geometry, instance and transform buffers are read in the
+    /// `ACCELERATION_STRUCTURE_BUILD` stage, and the acceleration structures written
+    /// with the `ACCELERATION_STRUCTURE_WRITE` access. Building a top level acceleration
+    /// structure requires a barrier after the builds of the bottom level ones it references.
+    ///
+    /// Requires `Features::ACCELERATION_STRUCTURE`.
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        descs: &[acceleration_structure::BuildDesc<B>],
+    );
+
+    /// Copy an acceleration structure into another one.
+    ///
+    /// Requires `Features::ACCELERATION_STRUCTURE`.
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        src: &B::AccelerationStructure,
+        dst: &B::AccelerationStructure,
+        mode: acceleration_structure::CopyMode,
+    );
+
+    /// Bind a ray tracing pipeline.
+    ///
+    /// Requires `Features::RAY_TRACING_PIPELINE`.
+    unsafe fn bind_ray_tracing_pipeline(&mut self, pipeline: &B::RayTracingPipeline);
+
+    /// Takes an iterator of ray tracing `DescriptorSet`'s, and binds them to the command buffer,
+    /// `first_set` is the index that the first descriptor is mapped to in the command buffer.
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a B::DescriptorSet>,
+        J: Iterator<Item = DescriptorSetOffset>;
+
+    /// Trace `width`x`height`x`depth` rays with the bound ray tracing pipeline,
+    /// invoking the shader groups of the given shader binding tables.
+    ///
+    /// - This function must be called outside of a render pass.
+    /// - Requires `Features::RAY_TRACING_PIPELINE`.
+    unsafe fn trace_rays(&mut self, tables: &pso::ShaderBindingTables<B>, count: WorkGroupCount);
+
     /// Adds a command to copy regions from the source to destination buffer.
     unsafe fn copy_buffer<T>(&mut self, src: &B::Buffer, dst: &B::Buffer, regions: T)
     where
@@ -502,6 +610,30 @@ pub trait CommandBuffer<B: Backend>: fmt::Debug + Any + Send + Sync {
         _stride: buffer::Stride,
     );
 
+    /// Bind buffer ranges that receive the outputs captured by transform feedback,
+    /// starting at the `first_binding` transform feedback buffer binding.
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: Iterator<Item = (&'a B::Buffer, buffer::SubRange)>;
+
+    /// Starts capturing the outputs declared by the `transform_feedback` of the
+    /// bound graphics pipeline into the bound transform feedback buffers.
+    ///
+    /// Outputs are written from the start of each buffer range. The pipeline
+    /// must not be changed until `end_transform_feedback` is recorded.
+    ///
+    /// Requires `Features::TRANSFORM_FEEDBACK`.
+    unsafe fn begin_transform_feedback(&mut self);
+
+    /// Stops capturing outputs started by `begin_transform_feedback`.
+    unsafe fn end_transform_feedback(&mut self);
+
+    /// Draws as many vertices as were captured by the transform feedback
+    /// pass that most recently ended on the queue.
+    ///
+    /// Requires `Features::DRAW_TRANSFORM_FEEDBACK`.
+    unsafe fn draw_transform_feedback(&mut self, instances: Range<InstanceCount>);
+
     /// Dispatches `task_count` of threads. Similar to compute dispatch.
     unsafe fn draw_mesh_tasks(&mut self, task_count: TaskCount, first_task: TaskCount);
 
@@ -83,4 +83,8 @@ pub struct ImageBlit {
     pub dst_subresource: image::SubresourceLayers,
     /// Destination image bounds.
     pub dst_bounds: Range<image::Offset>,
+    /// Conversion to RGB of the values of a multi-planar source image.
+    ///
+    /// Must be `Some(_)` if the source image has a multi-planar format, and `None` otherwise.
+    pub ycbcr_conversion: Option<image::YcbcrConversion>,
 }
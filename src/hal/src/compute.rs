@@ -0,0 +1,393 @@
+//! Compute-only usage of a device.
+//!
+//! Applications only running compute work, e.g. using the device as a portable
+//! GPGPU layer, don't need the render passes, swapchains and frames of the
+//! rendering applications, but still have to manage a command pool, the fences
+//! of their submissions, and staging buffers to read the results back.
+//! A [`ComputeEncoder`] does this on their behalf:
+//!
+//! - [`ComputeEncoder::submit`] records commands through an [`Encoder`] only exposing
+//!   the compute and transfer commands, and submits them without blocking. The
+//!   returned [`Ticket`] can be polled with [`ComputeEncoder::is_complete`], or
+//!   waited on with [`ComputeEncoder::wait`].
+//! - [`ComputeEncoder::run`] does the same, but blocks until the commands complete.
+//! - [`ComputeEncoder::readback`] copies a range of a buffer into a staging buffer,
+//!   whose contents are returned by [`ComputeEncoder::read`] once the copy is
+//!   complete. [`ComputeEncoder::read_buffer`] does both at once.
+//!
+//! All the calls have to use the same device and queue, the queue being of the
+//! family the encoder is created for.
+
+use crate::{
+    adapter::MemoryType,
+    buffer,
+    command::{BufferCopy, CommandBufferFlags, Compute, Encoder, Level},
+    device::{Device, DeviceLost, OutOfMemory, WaitError},
+    memory::{Barrier, Dependencies, Properties, Segment},
+    pool::{CommandPool, CommandPoolCreateFlags},
+    pso::PipelineStage,
+    queue::{Queue, QueueFamily, QueueFamilyId},
+    upload::{self, UploadError},
+    Backend,
+};
+
+use std::{collections::VecDeque, iter, ptr};
+
+/// Identifier of a submission of a [`ComputeEncoder`], increasing with every submission.
+pub type Ticket = u64;
+
+/// Error occurring while submitting compute work or reading its results back.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ComputeError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+    /// The device was lost.
+    #[error(transparent)]
+    DeviceLost(#[from] DeviceLost),
+    /// Creating or mapping a staging buffer failed.
+    #[error(transparent)]
+    Staging(#[from] UploadError),
+}
+
+impl From<WaitError> for ComputeError {
+    fn from(error: WaitError) -> Self {
+        match error {
+            WaitError::OutOfMemory(e) => ComputeError::OutOfMemory(e),
+            WaitError::DeviceLost(e) => ComputeError::DeviceLost(e),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Submission<B: Backend> {
+    ticket: Ticket,
+    cmd_buffer: B::CommandBuffer,
+    fence: B::Fence,
+}
+
+/// Buffer contents being copied back to the host, see [`ComputeEncoder::readback`].
+#[derive(Debug)]
+pub struct Readback<B: Backend> {
+    ticket: Ticket,
+    buffer: B::Buffer,
+    memory: B::Memory,
+    size: u64,
+}
+
+impl<B: Backend> Readback<B> {
+    /// Ticket of the submission copying the contents.
+    pub fn ticket(&self) -> Ticket {
+        self.ticket
+    }
+
+    /// Size of the contents, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// Helper submitting compute work to a queue, and reading its results back.
+///
+/// See the [module documentation][self].
+#[derive(Debug)]
+pub struct ComputeEncoder<B: Backend> {
+    family: QueueFamilyId,
+    pool: B::CommandPool,
+    memory_types: Vec<MemoryType>,
+    in_flight: VecDeque<Submission<B>>,
+    free_fences: Vec<B::Fence>,
+    next_ticket: Ticket,
+    completed: Ticket,
+}
+
+impl<B: Backend> ComputeEncoder<B> {
+    /// Create an encoder submitting to the queues of `family`.
+    ///
+    /// `memory_types` are the memory types of the physical device, as returned by
+    /// [`memory_properties`][crate::adapter::PhysicalDevice::memory_properties],
+    /// used to allocate the staging buffers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the queues of `family` don't support compute operations.
+    pub unsafe fn new<D: Device<B> + ?Sized>(
+        device: &D,
+        family: &B::QueueFamily,
+        memory_types: &[MemoryType],
+    ) -> Result<Self, OutOfMemory> {
+        assert!(
+            family.queue_type().supports_compute(),
+            "Queue family {:?} doesn't support compute operations",
+            family.id()
+        );
+        let pool = device.create_command_pool(family.id(), CommandPoolCreateFlags::TRANSIENT)?;
+        Ok(ComputeEncoder {
+            family: family.id(),
+            pool,
+            memory_types: memory_types.to_vec(),
+            in_flight: VecDeque::new(),
+            free_fences: Vec::new(),
+            next_ticket: 1,
+            completed: 0,
+        })
+    }
+
+    /// Family of the queues the encoder submits to.
+    pub fn family(&self) -> QueueFamilyId {
+        self.family
+    }
+
+    /// Ticket of the last completed submission, or `0` if none has completed yet.
+    pub fn completed_ticket(&self) -> Ticket {
+        self.completed
+    }
+
+    /// Record the commands produced by `record`, and submit them to `queue`
+    /// without waiting for them to complete.
+    pub unsafe fn submit<D, F>(
+        &mut self,
+        device: &D,
+        queue: &mut B::Queue,
+        record: F,
+    ) -> Result<Ticket, ComputeError>
+    where
+        D: Device<B> + ?Sized,
+        F: FnOnce(&mut Encoder<B, Compute>),
+    {
+        let mut fence = match self.free_fences.pop() {
+            Some(mut fence) => {
+                if let Err(e) = device.reset_fence(&mut fence) {
+                    device.destroy_fence(fence);
+                    return Err(e.into());
+                }
+                fence
+            }
+            None => device.create_fence(false)?,
+        };
+
+        let mut cmd_buffer = self.pool.allocate_one(Level::Primary);
+        let mut encoder = Encoder::begin(&mut cmd_buffer, CommandBufferFlags::ONE_TIME_SUBMIT);
+        record(&mut encoder);
+        encoder.finish();
+
+        if let Err(e) = queue.submit(
+            iter::once(&cmd_buffer),
+            iter::empty(),
+            iter::empty(),
+            Some(&mut fence),
+        ) {
+            self.pool.free(iter::once(cmd_buffer));
+            self.free_fences.push(fence);
+            return Err(WaitError::from(e).into());
+        }
+
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        self.in_flight.push_back(Submission {
+            ticket,
+            cmd_buffer,
+            fence,
+        });
+        Ok(ticket)
+    }
+
+    /// Record and submit the commands produced by `record`, and wait for them to complete.
+    pub unsafe fn run<D, F>(
+        &mut self,
+        device: &D,
+        queue: &mut B::Queue,
+        record: F,
+    ) -> Result<(), ComputeError>
+    where
+        D: Device<B> + ?Sized,
+        F: FnOnce(&mut Encoder<B, Compute>),
+    {
+        let ticket = self.submit(device, queue, record)?;
+        Ok(self.wait(device, ticket)?)
+    }
+
+    /// Check if the submission `ticket` has completed, without blocking.
+    ///
+    /// The command buffers of the completed submissions are freed.
+    pub unsafe fn is_complete<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        ticket: Ticket,
+    ) -> Result<bool, DeviceLost> {
+        while let Some(submission) = self.in_flight.front() {
+            if submission.ticket > ticket || !device.get_fence_status(&submission.fence)? {
+                break;
+            }
+            self.reclaim_oldest();
+        }
+        Ok(self.completed >= ticket)
+    }
+
+    /// Wait for the submission `ticket`, and the ones preceding it, to complete.
+    pub unsafe fn wait<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        ticket: Ticket,
+    ) -> Result<(), WaitError> {
+        while let Some(submission) = self.in_flight.front() {
+            if submission.ticket > ticket {
+                break;
+            }
+            device.wait_for_fence(&submission.fence, !0)?;
+            self.reclaim_oldest();
+        }
+        Ok(())
+    }
+
+    /// Wait for all the submissions to complete.
+    pub unsafe fn wait_idle<D: Device<B> + ?Sized>(&mut self, device: &D) -> Result<(), WaitError> {
+        self.wait(device, self.next_ticket - 1)
+    }
+
+    fn reclaim_oldest(&mut self) {
+        let submission = self.in_flight.pop_front().unwrap();
+        unsafe {
+            self.pool.free(iter::once(submission.cmd_buffer));
+        }
+        self.free_fences.push(submission.fence);
+        self.completed = submission.ticket;
+    }
+
+    /// Copy `size` bytes of `buffer`, starting at `offset`, into a staging buffer.
+    ///
+    /// The copy waits for the compute shader writes of the previous submissions,
+    /// and doesn't block. The contents are retrieved with [`ComputeEncoder::read`].
+    pub unsafe fn readback<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        queue: &mut B::Queue,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        size: u64,
+    ) -> Result<Readback<B>, ComputeError> {
+        let (staging, memory) = upload::allocate_bound_buffer::<B, D>(
+            device,
+            &self.memory_types,
+            size,
+            buffer::Usage::TRANSFER_DST,
+            Properties::CPU_VISIBLE,
+        )?;
+        let submitted = self.submit(device, queue, |encoder| {
+            encoder.pipeline_barrier(
+                PipelineStage::COMPUTE_SHADER | PipelineStage::TRANSFER..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                iter::once(Barrier::AllBuffers(
+                    buffer::Access::SHADER_WRITE | buffer::Access::TRANSFER_WRITE
+                        ..buffer::Access::TRANSFER_READ,
+                )),
+            );
+            encoder.copy_buffer(
+                buffer,
+                &staging,
+                iter::once(BufferCopy {
+                    src: offset,
+                    dst: 0,
+                    size,
+                }),
+            );
+            encoder.pipeline_barrier(
+                PipelineStage::TRANSFER..PipelineStage::HOST,
+                Dependencies::empty(),
+                iter::once(Barrier::Buffer {
+                    states: buffer::Access::TRANSFER_WRITE..buffer::Access::HOST_READ,
+                    target: &staging,
+                    range: buffer::SubRange::WHOLE,
+                    families: None,
+                }),
+            );
+        });
+        match submitted {
+            Ok(ticket) => Ok(Readback {
+                ticket,
+                buffer: staging,
+                memory,
+                size,
+            }),
+            Err(e) => {
+                device.destroy_buffer(staging);
+                device.free_memory(memory);
+                Err(e)
+            }
+        }
+    }
+
+    /// Wait for a [readback][ComputeEncoder::readback] to complete, and return
+    /// the contents of the buffer. The staging buffer is destroyed.
+    pub unsafe fn read<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        readback: Readback<B>,
+    ) -> Result<Vec<u8>, ComputeError> {
+        let Readback {
+            ticket,
+            buffer,
+            mut memory,
+            size,
+        } = readback;
+        let result = self
+            .wait(device, ticket)
+            .map_err(ComputeError::from)
+            .and_then(|()| {
+                let mapping = device
+                    .map_memory(&mut memory, Segment::ALL)
+                    .map_err(UploadError::from)?;
+                let result = device
+                    .invalidate_mapped_memory_ranges(iter::once((&memory, Segment::ALL)))
+                    .map(|()| {
+                        let mut data = vec![0; size as usize];
+                        ptr::copy_nonoverlapping(mapping, data.as_mut_ptr(), data.len());
+                        data
+                    });
+                device.unmap_memory(&mut memory);
+                Ok(result?)
+            });
+        device.destroy_buffer(buffer);
+        device.free_memory(memory);
+        result
+    }
+
+    /// Copy `size` bytes of `buffer`, starting at `offset`, back to the host,
+    /// blocking until the copy is complete.
+    pub unsafe fn read_buffer<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        queue: &mut B::Queue,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        size: u64,
+    ) -> Result<Vec<u8>, ComputeError> {
+        let readback = self.readback(device, queue, buffer, offset, size)?;
+        self.read(device, readback)
+    }
+
+    /// Destroy a readback without reading its contents, waiting for its copy to complete.
+    pub unsafe fn discard<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        readback: Readback<B>,
+    ) -> Result<(), WaitError> {
+        let result = self.wait(device, readback.ticket);
+        device.destroy_buffer(readback.buffer);
+        device.free_memory(readback.memory);
+        result
+    }
+
+    /// Wait for all the submissions, and destroy the encoder resources.
+    pub unsafe fn destroy<D: Device<B> + ?Sized>(mut self, device: &D) -> Result<(), WaitError> {
+        let result = self.wait_idle(device);
+        for submission in self.in_flight.drain(..) {
+            device.destroy_fence(submission.fence);
+        }
+        for fence in self.free_fences.drain(..) {
+            device.destroy_fence(fence);
+        }
+        device.destroy_command_pool(self.pool);
+        result
+    }
+}
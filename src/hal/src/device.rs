@@ -12,7 +12,7 @@
 //! and is used to actually do things.
 
 use crate::{
-    buffer, display, external_memory, format, image, memory,
+    acceleration_structure, buffer, display, external_memory, format, frame, image, memory,
     memory::{Requirements, Segment},
     pass,
     pool::CommandPoolCreateFlags,
@@ -20,11 +20,15 @@ use crate::{
     pso::DescriptorPoolCreateFlags,
     query,
     queue::QueueFamilyId,
-    Backend, MemoryTypeId,
+    upload, Backend, MemoryTypeId,
 };
 
 use std::{any::Any, fmt, iter, ops::Range};
 
+/// Set of the [nodes][crate::adapter::PhysicalDevice::node_count] of a device,
+/// with one bit per node.
+pub type NodeMask = u32;
+
 /// Error occurred caused device to be lost.
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 #[error("Device lost")]
@@ -41,6 +45,20 @@ pub enum OutOfMemory {
     Device,
 }
 
+/// Error occurring when submitting work to the device, or waiting for it to idle.
+///
+/// Once the device is lost, all the following operations on it fail as well,
+/// and the application has to recreate the device to recover.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum OomOrDeviceLost {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+    /// Device is lost
+    #[error(transparent)]
+    DeviceLost(#[from] DeviceLost),
+}
+
 /// Error occurring when waiting for fences or events.
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum WaitError {
@@ -52,6 +70,15 @@ pub enum WaitError {
     DeviceLost(#[from] DeviceLost),
 }
 
+impl From<OomOrDeviceLost> for WaitError {
+    fn from(error: OomOrDeviceLost) -> Self {
+        match error {
+            OomOrDeviceLost::OutOfMemory(oom) => WaitError::OutOfMemory(oom),
+            OomOrDeviceLost::DeviceLost(lost) => WaitError::DeviceLost(lost),
+        }
+    }
+}
+
 /// Possible cause of allocation failure.
 #[derive(Clone, Debug, PartialEq, thiserror::Error)]
 pub enum AllocationError {
@@ -201,9 +228,70 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         size: u64,
     ) -> Result<B::Memory, AllocationError>;
 
+    /// Allocate memory on the node `node` of the device, accessible to the command
+    /// buffers of the `visible_nodes`.
+    ///
+    /// Transfer commands recorded for one of the `visible_nodes` can copy between
+    /// this memory and the memory of its own node. The devices with a single node
+    /// only accept the node `0`, which makes this equivalent to
+    /// [`allocate_memory`][Device::allocate_memory].
+    unsafe fn allocate_node_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+        node: u32,
+        visible_nodes: NodeMask,
+    ) -> Result<B::Memory, AllocationError> {
+        assert_eq!(
+            (node, visible_nodes),
+            (0, 1),
+            "The device has a single node"
+        );
+        self.allocate_memory(memory_type, size)
+    }
+
     /// Free device memory
     unsafe fn free_memory(&self, memory: B::Memory);
 
+    /// Get the memory usage of the process and its budget in each heap, in the order
+    /// of the [memory heaps][crate::adapter::MemoryProperties::memory_heaps] of the adapter.
+    ///
+    /// The values are queried from the driver when possible. Otherwise, the usage
+    /// only accounts for the memory allocated through this device, and the budget
+    /// is the size of the heap.
+    fn memory_budget(&self) -> Vec<memory::HeapBudget>;
+
+    /// Set the priority of a memory object to stay resident in its heap.
+    ///
+    /// This is a hint, ignored by the backends that don't manage the residency of
+    /// the memory objects.
+    unsafe fn set_memory_priority(&self, memory: &B::Memory, priority: memory::Priority);
+
+    /// Make memory objects previously [evicted][Device::evict] resident again, so the
+    /// resources bound to them can be used by the device.
+    ///
+    /// The call blocks until the memory objects are resident. It has no effect on
+    /// the backends that don't manage the residency of the memory objects.
+    unsafe fn make_resident<'a, I>(&self, memories: I) -> Result<(), OutOfMemory>
+    where
+        I: Iterator<Item = &'a B::Memory>;
+
+    /// Evict memory objects from their heaps, leaving room for the other allocations.
+    ///
+    /// The contents of the memory objects are preserved, but the resources bound
+    /// to them can't be used by the device until they're made resident again.
+    /// It has no effect on the backends that don't manage the residency of the
+    /// memory objects.
+    ///
+    /// # Safety
+    ///
+    /// The resources bound to the memory objects must not be in use by the device,
+    /// and the command buffers using them must not be submitted until the memory
+    /// objects are [resident][Device::make_resident] again.
+    unsafe fn evict<'a, I>(&self, memories: I)
+    where
+        I: Iterator<Item = &'a B::Memory>;
+
     /// Create a new [command pool][crate::pool::CommandPool] for a given queue family.
     ///
     /// *Note*: the family has to be associated with one of [the queue groups
@@ -214,6 +302,21 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         create_flags: CommandPoolCreateFlags,
     ) -> Result<B::CommandPool, OutOfMemory>;
 
+    /// Create a new command pool, whose command buffers are executed by the queues
+    /// of the node `node` of the device.
+    ///
+    /// The devices with a single node only accept the node `0`, which makes this
+    /// equivalent to [`create_command_pool`][Device::create_command_pool].
+    unsafe fn create_node_command_pool(
+        &self,
+        family: QueueFamilyId,
+        create_flags: CommandPoolCreateFlags,
+        node: u32,
+    ) -> Result<B::CommandPool, OutOfMemory> {
+        assert_eq!(node, 0, "The device has a single node");
+        self.create_command_pool(family, create_flags)
+    }
+
     /// Destroy a command pool.
     unsafe fn destroy_command_pool(&self, pool: B::CommandPool);
 
@@ -329,6 +432,34 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// which references the compute pipeline, has finished execution.
     unsafe fn destroy_compute_pipeline(&self, pipeline: B::ComputePipeline);
 
+    /// Create a ray tracing pipeline.
+    ///
+    /// Requires `Features::RAY_TRACING_PIPELINE`.
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        desc: &pso::RayTracingPipelineDesc<'a, B>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<B::RayTracingPipeline, pso::CreationError>;
+
+    /// Destroy a ray tracing pipeline.
+    ///
+    /// The ray tracing pipeline shouldn't be destroyed before any submitted command buffer,
+    /// which references the ray tracing pipeline, has finished execution.
+    unsafe fn destroy_ray_tracing_pipeline(&self, pipeline: B::RayTracingPipeline);
+
+    /// Get the handles of the shader groups `first_group..first_group + group_count`
+    /// of a ray tracing pipeline, to be copied into the records of a shader binding table.
+    ///
+    /// `data` must hold `group_count` handles of `RayTracingProperties::shader_group_handle_size`
+    /// bytes, which are written tightly packed.
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        pipeline: &B::RayTracingPipeline,
+        first_group: u32,
+        group_count: u32,
+        data: &mut [u8],
+    ) -> Result<(), OutOfMemory>;
+
     /// Create a new framebuffer object.
     ///
     /// # Safety
@@ -379,6 +510,20 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         sparse: memory::SparseFlags,
     ) -> Result<B::Buffer, buffer::CreationError>;
 
+    /// Create a new device-local buffer filled with `data`.
+    ///
+    /// The contents are written into a temporary staging buffer and copied over on the queue
+    /// of the given `context`. This call blocks until the copy has completed, at which point
+    /// the buffer is ready to be accessed according to its `usage`.
+    unsafe fn create_buffer_with_data(
+        &self,
+        data: &[u8],
+        usage: buffer::Usage,
+        context: &mut upload::UploadContext<B>,
+    ) -> Result<(B::Buffer, B::Memory), upload::UploadError> {
+        upload::create_buffer_with_data(self, data, usage, context)
+    }
+
     /// Get memory requirements for the buffer
     unsafe fn get_buffer_requirements(&self, buf: &B::Buffer) -> Requirements;
 
@@ -386,6 +531,9 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     ///
     /// Be sure to check that there is enough memory available for the buffer.
     /// Use `get_buffer_requirements` to acquire the memory requirements.
+    ///
+    /// The memory range may overlap the ones of other resources,
+    /// see [`bind_image_memory`][Device::bind_image_memory].
     unsafe fn bind_buffer_memory(
         &self,
         memory: &B::Memory,
@@ -410,6 +558,14 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a buffer view object
     unsafe fn destroy_buffer_view(&self, view: B::BufferView);
 
+    /// Query the features supported by a format on this device.
+    ///
+    /// Reports the linear, optimal and buffer features of the format, which tell
+    /// whether it can be rendered to, sampled with filtering, used as a storage image,
+    /// or blitted. This matches [`PhysicalDevice::format_properties`][crate::adapter::PhysicalDevice::format_properties],
+    /// except that DRM format modifiers may not be reported.
+    fn format_properties(&self, format: format::Format) -> format::Properties;
+
     //TODO: add a list of supported formats for casting the views
 
     /// Create a new image object
@@ -424,6 +580,49 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         view_caps: image::ViewCapabilities,
     ) -> Result<B::Image, image::CreationError>;
 
+    /// Create a new image object with a partial mip chain.
+    ///
+    /// Only the mip levels in `levels` are allocated, while level 0 keeps the full
+    /// extent of `kind`. Views and copies of the image may only access these levels.
+    /// This allows streaming systems to allocate the small mip tail of an image first.
+    ///
+    /// The default implementation only supports chains starting at level 0.
+    unsafe fn create_partial_image(
+        &self,
+        kind: image::Kind,
+        levels: Range<image::Level>,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: memory::SparseFlags,
+        view_caps: image::ViewCapabilities,
+    ) -> Result<B::Image, image::CreationError> {
+        if levels.start != 0 {
+            return Err(image::CreationError::Levels(levels));
+        }
+        self.create_image(kind, levels.end, format, tiling, usage, sparse, view_caps)
+    }
+
+    /// Create a new single-level, device-local image filled with `data`.
+    ///
+    /// `data` has to contain the tightly packed texels of all the layers (or depth slices)
+    /// of the image. The contents are written into a temporary staging buffer and copied
+    /// over on the queue of the given `context`. This call blocks until the copy has completed,
+    /// after which the image is in the `ShaderReadOnlyOptimal` layout.
+    ///
+    /// For formats with both a depth and a stencil aspect, the depth texels, padded to
+    /// 2 or 4 bytes, come first and are followed by the stencil texels of a byte each.
+    unsafe fn create_image_with_data(
+        &self,
+        kind: image::Kind,
+        format: format::Format,
+        usage: image::Usage,
+        data: &[u8],
+        context: &mut upload::UploadContext<B>,
+    ) -> Result<(B::Image, B::Memory), upload::UploadError> {
+        upload::create_image_with_data(self, kind, format, usage, data, context)
+    }
+
     /// Get memory requirements for the Image
     unsafe fn get_image_requirements(&self, image: &B::Image) -> Requirements;
 
@@ -435,6 +634,12 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     ) -> image::SubresourceFootprint;
 
     /// Bind device memory to an image object
+    ///
+    /// The memory range may overlap the ones of other resources, e.g. to reuse the memory
+    /// of the transient attachments of a frame. On the memory types with the
+    /// [`ALIASABLE`][crate::memory::Properties::ALIASABLE] property, the resources then share
+    /// their storage: only one of them can be used at a time, switching between them with
+    /// a [`Barrier::Aliasing`][crate::memory::Barrier::Aliasing].
     unsafe fn bind_image_memory(
         &self,
         memory: &B::Memory,
@@ -463,10 +668,14 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     unsafe fn destroy_image_view(&self, view: B::ImageView);
 
     /// Create a new sampler object
+    ///
+    /// Returns [`SamplerCreationError::Unsupported`][image::SamplerCreationError::Unsupported]
+    /// listing the fields of `desc` the device can't honor, instead of sampling with
+    /// undefined results.
     unsafe fn create_sampler(
         &self,
         desc: &image::SamplerDesc,
-    ) -> Result<B::Sampler, AllocationError>;
+    ) -> Result<B::Sampler, image::SamplerCreationError>;
 
     /// Destroy a sampler object
     unsafe fn destroy_sampler(&self, sampler: B::Sampler);
@@ -517,6 +726,29 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Structure specifying a copy descriptor set operation.
     unsafe fn copy_descriptor_set<'a>(&self, op: pso::DescriptorSetCopy<'a, B>);
 
+    /// Update descriptor sets in a batch, applying the `writes` in order, then the `copies`.
+    ///
+    /// This is equivalent to calling [`write_descriptor_set`][Device::write_descriptor_set]
+    /// and [`copy_descriptor_set`][Device::copy_descriptor_set] for each operation, but
+    /// backends can apply the whole batch at once, which is considerably cheaper when
+    /// updating many sets.
+    ///
+    /// *Note*: a set is mutably borrowed by its write, so consecutive bindings of a set
+    /// are written by a single write spilling over them, see [`pso::DescriptorSetWrite`].
+    unsafe fn update_descriptor_sets<'a, I, J, K>(&self, writes: I, copies: K)
+    where
+        I: Iterator<Item = pso::DescriptorSetWrite<'a, B, J>>,
+        J: Iterator<Item = pso::Descriptor<'a, B>>,
+        K: Iterator<Item = pso::DescriptorSetCopy<'a, B>>,
+    {
+        for op in writes {
+            self.write_descriptor_set(op);
+        }
+        for op in copies {
+            self.copy_descriptor_set(op);
+        }
+    }
+
     /// Map a memory object into application address space
     ///
     /// Call `map_memory()` to retrieve a host virtual address pointer to a region of a mappable memory object
@@ -632,6 +864,28 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a fence object
     unsafe fn destroy_fence(&self, fence: B::Fence);
 
+    /// Begin a frame tracked by `frames`, returning its epoch.
+    ///
+    /// Waits for the oldest frame if too many of them are in flight, reclaims
+    /// the completed frames with their retired resources and staging buffers,
+    /// and samples the memory budget. See [`Frames::begin_frame`][frame::Frames::begin_frame].
+    unsafe fn begin_frame(
+        &self,
+        frames: &mut frame::Frames<B>,
+    ) -> Result<frame::Epoch, frame::FrameError> {
+        frames.begin_frame(self)
+    }
+
+    /// End the frame being recorded by `frames`, `fence` being signaled by the
+    /// last submission of the frame. See [`Frames::end_frame`][frame::Frames::end_frame].
+    fn end_frame(
+        &self,
+        frames: &mut frame::Frames<B>,
+        fence: B::Fence,
+    ) -> Result<frame::Epoch, frame::FrameError> {
+        frames.end_frame(fence)
+    }
+
     /// Create an event object.
     fn create_event(&self) -> Result<B::Event, OutOfMemory>;
 
@@ -662,6 +916,39 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Destroy a query pool object
     unsafe fn destroy_query_pool(&self, pool: B::QueryPool);
 
+    /// Get the sizes of the storage and scratch buffers needed to build an
+    /// acceleration structure with the given contents.
+    ///
+    /// Only the number of primitives and the formats of the geometries are
+    /// taken into account, the data in their buffers isn't read.
+    ///
+    /// Requires `Features::ACCELERATION_STRUCTURE`.
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        geometry: &acceleration_structure::GeometryInfo<B>,
+    ) -> acceleration_structure::SizeRequirements;
+
+    /// Create an acceleration structure in a region of a buffer.
+    ///
+    /// Requires `Features::ACCELERATION_STRUCTURE`.
+    unsafe fn create_acceleration_structure(
+        &self,
+        desc: &acceleration_structure::CreateDesc<B>,
+    ) -> Result<B::AccelerationStructure, OutOfMemory>;
+
+    /// Get the address of a bottom level acceleration structure, to reference
+    /// it from the [instances][acceleration_structure::Instance] of a top level one.
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        accel_struct: &B::AccelerationStructure,
+    ) -> acceleration_structure::DeviceAddress;
+
+    /// Destroy an acceleration structure. The storage buffer isn't affected.
+    ///
+    /// The acceleration structure shouldn't be destroyed before any submitted command buffer,
+    /// which references the acceleration structure, has finished execution.
+    unsafe fn destroy_acceleration_structure(&self, accel_struct: B::AccelerationStructure);
+
     /// Get query pool results into the specified CPU memory.
     /// Returns `Ok(false)` if the results are not ready yet and neither of `WAIT` or `PARTIAL` flags are set.
     unsafe fn get_query_pool_results(
@@ -676,7 +963,7 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Wait for all queues associated with this device to idle.
     ///
     /// Host access to all queues needs to be **externally** sycnhronized!
-    fn wait_idle(&self) -> Result<(), OutOfMemory>;
+    fn wait_idle(&self) -> Result<(), OomOrDeviceLost>;
 
     /// Associate a name with an image, for easier debugging in external tools or with validation
     /// layers that can print a friendly name when referring to objects in error messages
@@ -713,6 +1000,12 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// Associate a name with a pipeline layout, for easier debugging in external tools or with
     /// validation layers that can print a friendly name when referring to objects in error messages
     unsafe fn set_pipeline_layout_name(&self, pipeline_layout: &mut B::PipelineLayout, name: &str);
+    /// Associate a name with a graphics pipeline, for easier debugging in external tools or with
+    /// validation layers that can print a friendly name when referring to objects in error messages
+    unsafe fn set_graphics_pipeline_name(&self, pipeline: &mut B::GraphicsPipeline, name: &str);
+    /// Associate a name with a compute pipeline, for easier debugging in external tools or with
+    /// validation layers that can print a friendly name when referring to objects in error messages
+    unsafe fn set_compute_pipeline_name(&self, pipeline: &mut B::ComputePipeline, name: &str);
 
     /// Control the power state of the provided display
     unsafe fn set_display_power_state(
@@ -830,6 +1123,8 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// * `sparse` - the sparse flags of the image.
     /// * `view_caps` - the view capabilities of the image.
     /// * `type_mask` - a memory type mask containing all the desired memory type ids.
+    /// * `size` - the size of the external memory, as allocated by the exporter. The backends
+    ///   sizing the memory from the requirements of the image ignore it.
     /// # Errors
     ///
     /// - Returns `OutOfMemory` if the implementation goes out of memory during the operation.
@@ -848,6 +1143,7 @@ pub trait Device<B: Backend>: fmt::Debug + Any + Send + Sync {
         sparse: memory::SparseFlags,
         view_caps: image::ViewCapabilities,
         type_mask: u32,
+        size: u64,
     ) -> Result<(B::Image, B::Memory), external_memory::ExternalResourceError>;
 
     /// Export memory as os type (Fd, Handle or Ptr) based on the requested external memory type.
@@ -26,6 +26,12 @@ bitflags!(
         const DEPTH = 0x2;
         /// Stencil aspect.
         const STENCIL = 0x4;
+        /// First plane of a multi-planar format.
+        const PLANE_0 = 0x10;
+        /// Second plane of a multi-planar format.
+        const PLANE_1 = 0x20;
+        /// Third plane of a multi-planar format.
+        const PLANE_2 = 0x40;
     }
 );
 
@@ -40,6 +46,8 @@ pub struct FormatDesc {
     /// * The total number can be larger than the sum of individual format bits
     ///   (`color`, `alpha`, `depth` and `stencil`) for packed formats.
     /// * For compressed formats, this denotes the number of bits per block.
+    /// * For multi-planar formats, this denotes the average number of bits per texel.
+    ///   Copies operate on individual planes, using the bits of the plane format.
     pub bits: u16,
     /// Dimensions (width, height) of the texel blocks.
     pub dim: (u8, u8),
@@ -59,6 +67,69 @@ impl FormatDesc {
     }
 }
 
+/// Description of a plane of a multi-planar format.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PlaneDesc {
+    /// Aspect selecting the plane in views, copies and barriers.
+    pub aspect: Aspects,
+    /// Format of the plane, used by views and copies of this plane alone.
+    pub format: Format,
+    /// Factors by which the width and height of the image are divided for this plane.
+    pub subsampling: (u8, u8),
+}
+
+impl PlaneDesc {
+    /// Get the extent of the plane, given the extent of the image.
+    pub fn extent(&self, width: u32, height: u32) -> (u32, u32) {
+        let (sx, sy) = (self.subsampling.0 as u32, self.subsampling.1 as u32);
+        ((width + sx - 1) / sx, (height + sy - 1) / sy)
+    }
+}
+
+const PLANES_G8_B8R8_420: [PlaneDesc; 2] = [
+    PlaneDesc {
+        aspect: Aspects::PLANE_0,
+        format: Format::R8Unorm,
+        subsampling: (1, 1),
+    },
+    PlaneDesc {
+        aspect: Aspects::PLANE_1,
+        format: Format::Rg8Unorm,
+        subsampling: (2, 2),
+    },
+];
+
+const PLANES_G16_B16R16_420: [PlaneDesc; 2] = [
+    PlaneDesc {
+        aspect: Aspects::PLANE_0,
+        format: Format::R16Unorm,
+        subsampling: (1, 1),
+    },
+    PlaneDesc {
+        aspect: Aspects::PLANE_1,
+        format: Format::Rg16Unorm,
+        subsampling: (2, 2),
+    },
+];
+
+const PLANES_G8_B8_R8_420: [PlaneDesc; 3] = [
+    PlaneDesc {
+        aspect: Aspects::PLANE_0,
+        format: Format::R8Unorm,
+        subsampling: (1, 1),
+    },
+    PlaneDesc {
+        aspect: Aspects::PLANE_1,
+        format: Format::R8Unorm,
+        subsampling: (2, 2),
+    },
+    PlaneDesc {
+        aspect: Aspects::PLANE_2,
+        format: Format::R8Unorm,
+        subsampling: (2, 2),
+    },
+];
+
 /// Description of the bits distribution of a format.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct FormatBits {
@@ -282,10 +353,113 @@ macro_rules! surface_types {
                     }, )*
                 }
             }
+
+            /// Return the planes of a multi-planar surface type,
+            /// or an empty slice for single-plane ones.
+            pub fn planes(&self) -> &'static [PlaneDesc] {
+                match *self {
+                    SurfaceType::G8_B8R8_420 => &PLANES_G8_B8R8_420,
+                    SurfaceType::G16_B16R16_420 => &PLANES_G16_B16R16_420,
+                    SurfaceType::G8_B8_R8_420 => &PLANES_G8_B8_R8_420,
+                    _ => &[],
+                }
+            }
+        }
+
+        /// Surface types as types, allowing formats to be checked at compile time.
+        pub mod surface {
+            $(
+                #[allow(missing_docs, non_camel_case_types)]
+                #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+                pub struct $name;
+
+                impl super::SurfaceTyped for $name {
+                    const SELF: super::SurfaceType = super::SurfaceType::$name;
+                }
+
+                $( aspect_surface!($aspect, $name); )*
+            )*
         }
     }
 }
 
+macro_rules! aspect_surface {
+    (COLOR, $name:ident) => {
+        impl super::ColorSurface for $name {}
+    };
+    (DEPTH, $name:ident) => {
+        impl super::DepthSurface for $name {}
+    };
+    (STENCIL, $name:ident) => {
+        impl super::StencilSurface for $name {}
+    };
+    (PLANE_0, $name:ident) => {};
+    (PLANE_1, $name:ident) => {};
+    (PLANE_2, $name:ident) => {};
+}
+
+/// Compile-time surface type.
+pub trait SurfaceTyped {
+    /// Associated run-time surface type.
+    const SELF: SurfaceType;
+}
+
+/// Surface types with a color aspect.
+pub trait ColorSurface: SurfaceTyped {}
+
+/// Surface types with a depth aspect.
+pub trait DepthSurface: SurfaceTyped {}
+
+/// Surface types with a stencil aspect.
+pub trait StencilSurface: SurfaceTyped {}
+
+/// Surface types that can be used for vertex elements and texel buffers,
+/// meaning the uncompressed color surfaces the vertex input can read.
+pub trait BufferSurface: ColorSurface {}
+
+/// Compile-time channel type.
+pub trait ChannelTyped {
+    /// Associated run-time channel type.
+    const SELF: ChannelType;
+}
+
+/// Channel types read as floating-point values by the shaders.
+pub trait FloatChannel: ChannelTyped {}
+
+/// Channel types read as integer values by the shaders.
+pub trait IntegerChannel: ChannelTyped {}
+
+/// Channel types as types, allowing formats to be checked at compile time.
+pub mod channel {
+    macro_rules! channel_types {
+        { $($name:ident: $kind:ident,)* } => {
+            $(
+                #[allow(missing_docs)]
+                #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+                pub struct $name;
+
+                impl super::ChannelTyped for $name {
+                    const SELF: super::ChannelType = super::ChannelType::$name;
+                }
+
+                impl super::$kind for $name {}
+            )*
+        }
+    }
+
+    channel_types! {
+        Unorm: FloatChannel,
+        Snorm: FloatChannel,
+        Uint: IntegerChannel,
+        Sint: IntegerChannel,
+        Ufloat: FloatChannel,
+        Sfloat: FloatChannel,
+        Uscaled: FloatChannel,
+        Sscaled: FloatChannel,
+        Srgb: FloatChannel,
+    }
+}
+
 // ident { num_bits, aspects, dim, (color, alpha, ..) }
 // if the number of bits is given with exclamation (e.g. `!16`), the format is considered packed
 surface_types! {
@@ -354,6 +528,23 @@ surface_types! {
     ASTC_10x10          { 128, COLOR, (10, 10) },
     ASTC_12x10          { 128, COLOR, (12, 10) },
     ASTC_12x12          { 128, COLOR, (12, 12) },
+    G8_B8R8_420         {  12, COLOR | PLANE_0 | PLANE_1, (1, 1), color: 24 },
+    G16_B16R16_420      {  24, COLOR | PLANE_0 | PLANE_1, (1, 1), color: 48 },
+    G8_B8_R8_420        {  12, COLOR | PLANE_0 | PLANE_1 | PLANE_2, (1, 1), color: 24 },
+}
+
+macro_rules! buffer_surfaces {
+    { $($name:ident,)* } => {
+        $( impl BufferSurface for surface::$name {} )*
+    }
+}
+
+// The packed formats of less than 8 bits per channel, and the shared exponent
+// and packed float ones, can't be read by the vertex input.
+buffer_surfaces! {
+    R8, R8_G8, R8_G8_B8, R8_G8_B8_A8, B8_G8_R8_A8, A8_B8_G8_R8,
+    A2_R10_G10_B10, A2_B10_G10_R10, R16, R16_G16, R16_G16_B16, R16_G16_B16_A16,
+    R32, R32_G32, R32_G32_B32, R32_G32_B32_A32, R64, R64_G64, R64_G64_B64, R64_G64_B64_A64,
 }
 
 /// Generic run-time base format.
@@ -365,8 +556,52 @@ pub struct BaseFormat(pub SurfaceType, pub ChannelType);
 pub trait AsFormat {
     /// Associated format.
     const SELF: Format;
+    /// Compile-time surface type of the format.
+    type Surface: SurfaceTyped;
+    /// Compile-time channel type of the format.
+    type Channel: ChannelTyped;
 }
 
+/// Formats that can be used for vertex elements and texel buffers.
+pub trait BufferFormat: AsFormat {}
+impl<F: AsFormat> BufferFormat for F where F::Surface: BufferSurface {}
+
+/// Formats that can be used for color attachments and sampled as colors.
+pub trait ColorFormat: AsFormat {}
+impl<F: AsFormat> ColorFormat for F where F::Surface: ColorSurface {}
+
+/// Formats with a depth aspect.
+pub trait DepthFormat: AsFormat {}
+impl<F: AsFormat> DepthFormat for F where F::Surface: DepthSurface {}
+
+/// Formats with a stencil aspect.
+pub trait StencilFormat: AsFormat {}
+impl<F: AsFormat> StencilFormat for F where F::Surface: StencilSurface {}
+
+/// Formats of the views that can be created on an image of format `F`.
+///
+/// This is checked at compile time, and is more conservative than the run-time rules:
+/// the view has to share the surface type of the image, only reinterpreting the channels,
+/// for example `Rgba8Srgb` views of a `Rgba8Unorm` image.
+///
+/// ```
+/// # use gfx_hal::format::{AsFormat, Format, Rgba8Srgb, Rgba8Unorm, ViewFormat};
+/// fn view_format<I: AsFormat, V: ViewFormat<I>>() -> Format {
+///     V::SELF
+/// }
+/// assert_eq!(view_format::<Rgba8Unorm, Rgba8Srgb>(), Format::Rgba8Srgb);
+/// ```
+///
+/// ```compile_fail
+/// # use gfx_hal::format::{AsFormat, Format, R32Sfloat, Rgba8Unorm, ViewFormat};
+/// fn view_format<I: AsFormat, V: ViewFormat<I>>() -> Format {
+///     V::SELF
+/// }
+/// view_format::<Rgba8Unorm, R32Sfloat>();
+/// ```
+pub trait ViewFormat<F: AsFormat>: AsFormat {}
+impl<F: AsFormat, V: AsFormat<Surface = F::Surface>> ViewFormat<F> for V {}
+
 macro_rules! formats {
     {
         $name:ident = ($surface:ident, $channel:ident),
@@ -409,6 +644,8 @@ macro_rules! formats {
 
             impl AsFormat for $name {
                 const SELF: Format = Format::$name;
+                type Surface = surface::$surface;
+                type Channel = channel::$channel;
             }
 
         $(
@@ -421,6 +658,8 @@ macro_rules! formats {
 
             impl AsFormat for $name_tail {
                 const SELF: Format = Format::$name_tail;
+                type Surface = surface::$surface_tail;
+                type Channel = channel::$channel_tail;
             }
 
         )*
@@ -613,6 +852,13 @@ formats! {
     Astc12x10Srgb = (ASTC_12x10, Srgb),
     Astc12x12Unorm = (ASTC_12x12, Unorm),
     Astc12x12Srgb = (ASTC_12x12, Srgb),
+    // Multi-planar formats, not contiguous with the above in Vulkan.
+    // NV12: 8-bit luma plane, then interleaved 8-bit chroma at half resolution.
+    Nv12Unorm = (G8_B8R8_420, Unorm),
+    // P010: same layout with 10-bit values, stored in the high bits of 16-bit ones.
+    P010Unorm = (G16_B16R16_420, Unorm),
+    // YUV420: 8-bit luma plane, then separate 8-bit Cb and Cr planes at half resolution.
+    Yuv420Unorm = (G8_B8_R8_420, Unorm),
 }
 
 impl Format {
@@ -643,18 +889,36 @@ impl Format {
     pub fn is_stencil(self) -> bool {
         self.surface_desc().aspects.contains(Aspects::STENCIL)
     }
+
+    /// Returns if the format is multi-planar.
+    pub fn is_planar(self) -> bool {
+        !self.planes().is_empty()
+    }
+
+    /// Returns the planes of a multi-planar format, or an empty slice otherwise.
+    pub fn planes(self) -> &'static [PlaneDesc] {
+        self.base_format().0.planes()
+    }
 }
 
 // Common vertex attribute formats
 impl AsFormat for f32 {
     const SELF: Format = Format::R32Sfloat;
+    type Surface = surface::R32;
+    type Channel = channel::Sfloat;
 }
 impl AsFormat for [f32; 2] {
     const SELF: Format = Format::Rg32Sfloat;
+    type Surface = surface::R32_G32;
+    type Channel = channel::Sfloat;
 }
 impl AsFormat for [f32; 3] {
     const SELF: Format = Format::Rgb32Sfloat;
+    type Surface = surface::R32_G32_B32;
+    type Channel = channel::Sfloat;
 }
 impl AsFormat for [f32; 4] {
     const SELF: Format = Format::Rgba32Sfloat;
+    type Surface = surface::R32_G32_B32_A32;
+    type Channel = channel::Sfloat;
 }
@@ -0,0 +1,447 @@
+//! Frame epochs driving the reclamation of resources.
+//!
+//! A resource can't be destroyed while the device may still access it, and waiting
+//! for the device to go idle before every destruction stalls the pipeline. Instead,
+//! the submissions can be grouped into frames with [`Frames::begin_frame`] and
+//! [`Frames::end_frame`], the latter taking the fence signaled by the last submission
+//! of the frame. Each frame is identified by an increasing [`Epoch`].
+//!
+//! Resources handed to [`Frames::retire`] are destroyed once the frame they were
+//! retired in has completed. The completed frames are reclaimed at the start of
+//! every frame, so the reclamation happens at a steady cadence without the user
+//! having to track the fences of the individual submissions. Starting a frame also
+//! waits for the oldest one when too many of them are in flight, throttling the
+//! host to the device.
+//!
+//! Tearing down a large scene retires many resources at once, and destroying them
+//! all in the following frame may cause a hitch. A [`DestructionBudget`] set with
+//! [`Frames::set_destruction_budget`] limits the number of resources destroyed
+//! per frame, or the time spent destroying them, the remaining resources being
+//! destroyed in the following frames. [`Frames::flush_all`] destroys all of them
+//! regardless of the budget, e.g. on shutdown.
+//!
+//! The fences of the completed frames are kept in a pool, and can be reused for
+//! the following frames with [`Frames::acquire_fence`]. Likewise, the staging
+//! buffers handed to [`Frames::retire_staging`] are recycled by
+//! [`Frames::acquire_staging`] once their frame has completed.
+//!
+//! The frames are usually driven through [`Device::begin_frame`] and
+//! [`Device::end_frame`]. Beginning a frame also samples the
+//! [memory budget][Device::memory_budget] of the device, available from
+//! [`Frames::memory_budget`], and the [epoch][Frames::epoch] of the frame
+//! buckets the timings of the [`FrameProfiler`][crate::profiler::FrameProfiler].
+
+use crate::{
+    buffer,
+    device::{Device, DeviceLost, OutOfMemory, WaitError, WaitFor},
+    memory::HeapBudget,
+    Backend,
+};
+
+use std::{
+    collections::VecDeque,
+    mem,
+    time::{Duration, Instant},
+};
+
+/// Identifier of a frame. The first frame has the epoch `1`.
+pub type Epoch = u64;
+
+/// Error occurring when beginning or ending a frame.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum FrameError {
+    /// A frame was begun while the previous one is still being recorded.
+    #[error("Frame {0} hasn't ended")]
+    NotEnded(Epoch),
+    /// A frame was ended while none is being recorded.
+    #[error("No frame has begun")]
+    NotBegun,
+    /// Waiting for the oldest frame in flight failed.
+    #[error(transparent)]
+    Wait(#[from] WaitError),
+}
+
+/// A CPU-visible buffer staging transfers, recycled once its frame has completed.
+#[derive(Debug)]
+pub struct StagingBuffer<B: Backend> {
+    /// The buffer, bound to the start of `memory`.
+    pub buffer: B::Buffer,
+    /// Memory backing the buffer.
+    pub memory: B::Memory,
+    /// Size of the buffer, in bytes.
+    pub size: buffer::Offset,
+}
+
+/// A resource retired by the user, destroyed once its frame has completed.
+#[derive(Debug)]
+#[allow(missing_docs)]
+pub enum Retired<B: Backend> {
+    Memory(B::Memory),
+    Buffer(B::Buffer),
+    BufferView(B::BufferView),
+    Image(B::Image),
+    ImageView(B::ImageView),
+    Sampler(B::Sampler),
+    Framebuffer(B::Framebuffer),
+    DescriptorPool(B::DescriptorPool),
+    GraphicsPipeline(B::GraphicsPipeline),
+    ComputePipeline(B::ComputePipeline),
+    QueryPool(B::QueryPool),
+}
+
+impl<B: Backend> Retired<B> {
+    unsafe fn destroy<D: Device<B> + ?Sized>(self, device: &D) {
+        match self {
+            Retired::Memory(memory) => device.free_memory(memory),
+            Retired::Buffer(buffer) => device.destroy_buffer(buffer),
+            Retired::BufferView(view) => device.destroy_buffer_view(view),
+            Retired::Image(image) => device.destroy_image(image),
+            Retired::ImageView(view) => device.destroy_image_view(view),
+            Retired::Sampler(sampler) => device.destroy_sampler(sampler),
+            Retired::Framebuffer(framebuffer) => device.destroy_framebuffer(framebuffer),
+            Retired::DescriptorPool(pool) => device.destroy_descriptor_pool(pool),
+            Retired::GraphicsPipeline(pipeline) => device.destroy_graphics_pipeline(pipeline),
+            Retired::ComputePipeline(pipeline) => device.destroy_compute_pipeline(pipeline),
+            Retired::QueryPool(pool) => device.destroy_query_pool(pool),
+        }
+    }
+}
+
+/// Limit on the destruction of the retired resources, applied every time the
+/// completed frames are reclaimed.
+///
+/// At least one resource is destroyed per reclamation if any is pending, so the
+/// destruction always makes progress.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DestructionBudget {
+    /// Maximum number of resources to destroy, unlimited if `None`.
+    pub max_resources: Option<usize>,
+    /// Maximum time to spend destroying resources, unlimited if `None`.
+    pub max_time: Option<Duration>,
+}
+
+impl DestructionBudget {
+    /// Budget without any limit, destroying the resources as soon as their
+    /// frame has completed.
+    pub const UNLIMITED: Self = DestructionBudget {
+        max_resources: None,
+        max_time: None,
+    };
+
+    /// Number of resources to destroy out of the `pending` ones, before
+    /// checking the time limit.
+    fn max_count(&self, pending: usize) -> usize {
+        match self.max_resources {
+            Some(max) => max.max(1).min(pending),
+            None => pending,
+        }
+    }
+}
+
+/// Index of the smallest of the `sizes` holding at least `size` bytes.
+fn best_fit(sizes: impl Iterator<Item = buffer::Offset>, size: buffer::Offset) -> Option<usize> {
+    sizes
+        .enumerate()
+        .filter(|&(_, fit)| fit >= size)
+        .min_by_key(|&(_, fit)| fit)
+        .map(|(index, _)| index)
+}
+
+#[derive(Debug)]
+struct Frame<B: Backend> {
+    epoch: Epoch,
+    fence: B::Fence,
+    retired: Vec<Retired<B>>,
+    staging: Vec<StagingBuffer<B>>,
+}
+
+/// Tracker of the frames in flight, and of the resources retired during them.
+///
+/// All the calls have to use the same device.
+#[derive(Debug)]
+pub struct Frames<B: Backend> {
+    max_in_flight: usize,
+    /// Epoch of the frame being recorded, or of the next one.
+    epoch: Epoch,
+    recording: bool,
+    completed: Epoch,
+    retired: Vec<Retired<B>>,
+    in_flight: VecDeque<Frame<B>>,
+    free_fences: Vec<B::Fence>,
+    budget: DestructionBudget,
+    /// Resources of the completed frames left over by the budget.
+    pending: VecDeque<Retired<B>>,
+    staging: Vec<StagingBuffer<B>>,
+    free_staging: Vec<StagingBuffer<B>>,
+    memory_budget: Vec<HeapBudget>,
+}
+
+impl<B: Backend> Frames<B> {
+    /// Create a new tracker, allowing up to `max_in_flight` frames to execute
+    /// on the device while the next one is recorded.
+    pub fn new(max_in_flight: usize) -> Self {
+        assert_ne!(max_in_flight, 0);
+        Frames {
+            max_in_flight,
+            epoch: 1,
+            recording: false,
+            completed: 0,
+            retired: Vec::new(),
+            in_flight: VecDeque::new(),
+            free_fences: Vec::new(),
+            budget: DestructionBudget::UNLIMITED,
+            pending: VecDeque::new(),
+            staging: Vec::new(),
+            free_staging: Vec::new(),
+            memory_budget: Vec::new(),
+        }
+    }
+
+    /// Set the limit on the resources destroyed every time the completed frames
+    /// are reclaimed. The default is [`DestructionBudget::UNLIMITED`].
+    pub fn set_destruction_budget(&mut self, budget: DestructionBudget) {
+        self.budget = budget;
+    }
+
+    /// Number of the resources of completed frames waiting to be destroyed,
+    /// because of the destruction budget.
+    pub fn pending_destructions(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Epoch of the frame being recorded, or of the next one if none is.
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    /// Epoch of the last completed frame, or `0` if none has completed yet.
+    pub fn completed_epoch(&self) -> Epoch {
+        self.completed
+    }
+
+    /// Number of the ended frames that haven't been reclaimed yet.
+    pub fn frames_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Check if a frame is being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Memory usage and budget of each heap, sampled when the last frame began.
+    pub fn memory_budget(&self) -> &[HeapBudget] {
+        &self.memory_budget
+    }
+
+    /// Begin a new frame, returning its epoch.
+    ///
+    /// Waits for the oldest frame if `max_in_flight` of them are executing,
+    /// then reclaims the completed frames and samples the memory budget.
+    pub unsafe fn begin_frame<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+    ) -> Result<Epoch, FrameError> {
+        if self.recording {
+            return Err(FrameError::NotEnded(self.epoch));
+        }
+        if self.in_flight.len() >= self.max_in_flight {
+            let oldest = self.in_flight.front().unwrap();
+            device.wait_for_fence(&oldest.fence, !0)?;
+        }
+        self.cleanup(device).map_err(WaitError::from)?;
+        self.memory_budget = device.memory_budget();
+        self.recording = true;
+        Ok(self.epoch)
+    }
+
+    /// End the current frame. The `fence` has to be signaled by the last
+    /// submission of the frame, and is owned by the tracker from now on.
+    ///
+    /// Returns the epoch of the ended frame. If no frame has begun, the fence
+    /// is only kept to be [acquired][Frames::acquire_fence] again.
+    pub fn end_frame(&mut self, fence: B::Fence) -> Result<Epoch, FrameError> {
+        if !self.recording {
+            self.free_fences.push(fence);
+            return Err(FrameError::NotBegun);
+        }
+        let epoch = self.epoch;
+        self.in_flight.push_back(Frame {
+            epoch,
+            fence,
+            retired: mem::take(&mut self.retired),
+            staging: mem::take(&mut self.staging),
+        });
+        self.epoch += 1;
+        self.recording = false;
+        Ok(epoch)
+    }
+
+    /// Get an unsignaled fence to pass to `end_frame`, reusing the fence of
+    /// a completed frame if there is one.
+    pub unsafe fn acquire_fence<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+    ) -> Result<B::Fence, OutOfMemory> {
+        match self.free_fences.pop() {
+            Some(mut fence) => match device.reset_fence(&mut fence) {
+                Ok(()) => Ok(fence),
+                Err(e) => {
+                    device.destroy_fence(fence);
+                    Err(e)
+                }
+            },
+            None => device.create_fence(false),
+        }
+    }
+
+    /// Retire a resource. It's destroyed once the current frame, or the next
+    /// one if none is being recorded, has completed.
+    ///
+    /// The resource must not be used by the frames following this one.
+    pub fn retire(&mut self, resource: Retired<B>) {
+        self.retired.push(resource);
+    }
+
+    /// Hand over a staging buffer used by the current frame, or the next one
+    /// if none is being recorded. It can be acquired again once the frame has completed.
+    pub fn retire_staging(&mut self, staging: StagingBuffer<B>) {
+        self.staging.push(staging);
+    }
+
+    /// Take the smallest staging buffer of the completed frames holding at least
+    /// `size` bytes, if there is one.
+    pub fn acquire_staging(&mut self, size: buffer::Offset) -> Option<StagingBuffer<B>> {
+        let index = best_fit(self.free_staging.iter().map(|staging| staging.size), size)?;
+        Some(self.free_staging.swap_remove(index))
+    }
+
+    /// Reclaim the frames that have completed, without blocking, and destroy
+    /// their resources within the destruction budget.
+    ///
+    /// Returns the epoch of the last completed frame.
+    pub unsafe fn cleanup<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+    ) -> Result<Epoch, DeviceLost> {
+        while let Some(frame) = self.in_flight.front() {
+            if !device.get_fence_status(&frame.fence)? {
+                break;
+            }
+            self.reclaim_oldest();
+        }
+        self.destroy_pending(device);
+        Ok(self.completed)
+    }
+
+    /// Wait for all the frames in flight, and destroy all the retired resources
+    /// regardless of the destruction budget.
+    ///
+    /// The resources retired during the frame being recorded, if any, are kept
+    /// until it has completed.
+    pub unsafe fn flush_all<D: Device<B> + ?Sized>(&mut self, device: &D) -> Result<(), WaitError> {
+        if !self.in_flight.is_empty() {
+            device.wait_for_fences(
+                self.in_flight.iter().map(|frame| &frame.fence),
+                WaitFor::All,
+                !0,
+            )?;
+        }
+        while !self.in_flight.is_empty() {
+            self.reclaim_oldest();
+        }
+        if !self.recording {
+            self.pending.extend(self.retired.drain(..));
+        }
+        for resource in self.pending.drain(..) {
+            resource.destroy(device);
+        }
+        Ok(())
+    }
+
+    fn reclaim_oldest(&mut self) {
+        let frame = self.in_flight.pop_front().unwrap();
+        self.pending.extend(frame.retired);
+        self.free_staging.extend(frame.staging);
+        self.free_fences.push(frame.fence);
+        self.completed = frame.epoch;
+    }
+
+    unsafe fn destroy_pending<D: Device<B> + ?Sized>(&mut self, device: &D) {
+        let count = self.budget.max_count(self.pending.len());
+        let deadline = self
+            .budget
+            .max_time
+            .map(|max_time| (Instant::now(), max_time));
+        for i in 0..count {
+            if let Some((start, max_time)) = deadline {
+                if i != 0 && start.elapsed() >= max_time {
+                    break;
+                }
+            }
+            self.pending.pop_front().unwrap().destroy(device);
+        }
+    }
+
+    /// Wait for all the frames in flight, and destroy the retired resources
+    /// together with the fences and the staging buffers.
+    pub unsafe fn destroy<D: Device<B> + ?Sized>(self, device: &D) -> Result<(), WaitError> {
+        let waited = device.wait_for_fences(
+            self.in_flight.iter().map(|frame| &frame.fence),
+            WaitFor::All,
+            !0,
+        );
+        let mut staging = self.staging;
+        staging.extend(self.free_staging);
+        let retired = self
+            .in_flight
+            .into_iter()
+            .flat_map(|frame| {
+                device.destroy_fence(frame.fence);
+                staging.extend(frame.staging);
+                frame.retired
+            })
+            .chain(self.retired)
+            .chain(self.pending)
+            .collect::<Vec<_>>();
+        for resource in retired {
+            resource.destroy(device);
+        }
+        for staging in staging {
+            device.destroy_buffer(staging.buffer);
+            device.free_memory(staging.memory);
+        }
+        for fence in self.free_fences {
+            device.destroy_fence(fence);
+        }
+        waited.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destruction_budget_always_makes_progress() {
+        let budget = |max_resources| DestructionBudget {
+            max_resources,
+            max_time: Some(Duration::from_millis(1)),
+        };
+        assert_eq!(DestructionBudget::UNLIMITED.max_count(5), 5);
+        assert_eq!(budget(Some(2)).max_count(5), 2);
+        assert_eq!(budget(Some(2)).max_count(1), 1);
+        assert_eq!(budget(Some(0)).max_count(5), 1);
+        assert_eq!(budget(Some(0)).max_count(0), 0);
+    }
+
+    #[test]
+    fn staging_buffers_are_the_best_fit() {
+        let sizes = [64, 16, 256, 32];
+        assert_eq!(best_fit(sizes.iter().cloned(), 1), Some(1));
+        assert_eq!(best_fit(sizes.iter().cloned(), 32), Some(3));
+        assert_eq!(best_fit(sizes.iter().cloned(), 33), Some(0));
+        assert_eq!(best_fit(sizes.iter().cloned(), 512), None);
+        assert_eq!(best_fit(std::iter::empty(), 0), None);
+    }
+}
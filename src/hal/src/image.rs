@@ -6,6 +6,7 @@ use crate::{
     buffer::Offset as RawOffset,
     device, format,
     pso::{Comparison, Rect},
+    Features,
 };
 use std::{f32, hash, ops::Range};
 
@@ -126,6 +127,9 @@ pub enum CreationError {
     /// The mentioned usage mode is not supported
     #[error("Unsupported usage: {0:?}")]
     Usage(Usage),
+    /// The requested range of mip levels is not supported.
+    #[error("Unsupported mip levels: {0:?}")]
+    Levels(Range<Level>),
 }
 
 /// Error creating an `ImageView`.
@@ -340,8 +344,6 @@ bitflags!(
 );
 
 bitflags!(
-    /// TODO: Find out if TRANSIENT_ATTACHMENT + INPUT_ATTACHMENT
-    /// are applicable on backends other than Vulkan. --AP
     /// Image usage flags
     #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct Usage: u32 {
@@ -357,10 +359,19 @@ bitflags!(
         const COLOR_ATTACHMENT = 0x10;
         /// The image is used as a depth attachment.
         const DEPTH_STENCIL_ATTACHMENT = 0x20;
+        /// The image is an attachment whose contents are never stored to memory, e.g. a
+        /// depth buffer only used within a render pass. It can only be combined with
+        /// the attachment usages.
         ///
+        /// Tile-based GPUs can keep the contents of such images in the tile memory.
+        /// They are backed by `LAZILY_ALLOCATED` memory when available, and are good
+        /// candidates for `ALIASABLE` memory otherwise, as they don't outlive the passes
+        /// using them.
         const TRANSIENT_ATTACHMENT = 0x40;
-        ///
+        /// The image is used as an input attachment, read by the fragment shaders of a subpass.
         const INPUT_ATTACHMENT = 0x80;
+        /// The image is used as the shading rate attachment of a subpass.
+        const SHADING_RATE_ATTACHMENT = 0x100;
 
     }
 );
@@ -477,6 +488,110 @@ impl Into<[f32; 4]> for BorderColor {
     }
 }
 
+/// Color model of the YCbCr values of a multi-planar image.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum YcbcrModel {
+    /// The values are already RGB, the luma being green, Cb blue and Cr red.
+    /// No range expansion is applied.
+    RgbIdentity,
+    /// Only expand the range of the values, without converting them to RGB.
+    YcbcrIdentity,
+    /// ITU-R BT.601, used by standard definition video.
+    Bt601,
+    /// ITU-R BT.709, used by high definition video.
+    Bt709,
+    /// ITU-R BT.2020, used by ultra high definition and HDR video.
+    Bt2020,
+}
+
+/// Range of the encoded YCbCr values.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum YcbcrRange {
+    /// The values use the whole range of the bits.
+    Full,
+    /// The values leave head and foot room, luma being within `16..=235`
+    /// and chroma within `16..=240` when stored with 8 bits.
+    Narrow,
+}
+
+/// Conversion of the multi-planar images from YCbCr to RGB, when blitting them
+/// with `ImageBlit::ycbcr_conversion`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct YcbcrConversion {
+    /// Color model of the values.
+    pub model: YcbcrModel,
+    /// Range of the values.
+    pub range: YcbcrRange,
+}
+
+impl Default for YcbcrConversion {
+    /// BT.709 in narrow range, produced by most hardware video decoders.
+    fn default() -> Self {
+        YcbcrConversion {
+            model: YcbcrModel::Bt709,
+            range: YcbcrRange::Narrow,
+        }
+    }
+}
+
+impl YcbcrConversion {
+    /// Returns the matrix converting the normalized `(Y, Cb, Cr, 1)` values, stored
+    /// with `bits` bits, to the `(R, G, B)` ones.
+    ///
+    /// This allows shaders to convert the values sampled from the views of the
+    /// individual planes.
+    pub fn to_rgb_matrix(&self, bits: u8) -> [[f32; 4]; 3] {
+        let max = ((1u32 << bits) - 1) as f32;
+        let scale = (1u32 << bits.saturating_sub(8)) as f32;
+        // Expansion of the luma and chroma values, as `y * factor + offset`.
+        let ((y_factor, y_offset), (c_factor, c_offset)) = match (self.model, self.range) {
+            (YcbcrModel::RgbIdentity, _) => {
+                return [
+                    [0.0, 0.0, 1.0, 0.0],
+                    [1.0, 0.0, 0.0, 0.0],
+                    [0.0, 1.0, 0.0, 0.0],
+                ];
+            }
+            (_, YcbcrRange::Full) => ((1.0, 0.0), (1.0, -((1u32 << (bits - 1)) as f32) / max)),
+            (_, YcbcrRange::Narrow) => (
+                (max / (219.0 * scale), -16.0 / 219.0),
+                (max / (224.0 * scale), -128.0 / 224.0),
+            ),
+        };
+        let (kr, kb) = match self.model {
+            YcbcrModel::RgbIdentity => unreachable!(),
+            YcbcrModel::YcbcrIdentity => {
+                return [
+                    [0.0, 0.0, c_factor, c_offset],
+                    [y_factor, 0.0, 0.0, y_offset],
+                    [0.0, c_factor, 0.0, c_offset],
+                ];
+            }
+            YcbcrModel::Bt601 => (0.299, 0.114),
+            YcbcrModel::Bt709 => (0.2126, 0.0722),
+            YcbcrModel::Bt2020 => (0.2627, 0.0593),
+        };
+        let kg = 1.0 - kr - kb;
+        let r_cr = 2.0 * (1.0 - kr);
+        let g_cb = -2.0 * kb * (1.0 - kb) / kg;
+        let g_cr = -2.0 * kr * (1.0 - kr) / kg;
+        let b_cb = 2.0 * (1.0 - kb);
+        [
+            [y_factor, 0.0, r_cr * c_factor, y_offset + r_cr * c_offset],
+            [
+                y_factor,
+                g_cb * c_factor,
+                g_cr * c_factor,
+                y_offset + (g_cb + g_cr) * c_offset,
+            ],
+            [y_factor, b_cb * c_factor, 0.0, y_offset + b_cb * c_offset],
+        ]
+    }
+}
+
 /// Specifies how to sample from an image.  These are all the parameters
 /// available that alter how the GPU goes from a coordinate in an image
 /// to producing an actual value from the texture, including filtering/
@@ -531,6 +646,111 @@ impl SamplerDesc {
             anisotropy_clamp: None,
         }
     }
+
+    /// Returns the fields that can't be used with the enabled `features`,
+    /// or that are not allowed together with unnormalized coordinates or
+    /// with a comparison.
+    ///
+    /// Backends perform this check in `create_sampler`, adding their own restrictions.
+    pub fn unsupported_fields(&self, features: Features) -> SamplerFields {
+        let mut fields = SamplerFields::empty();
+        let (u, v, w) = self.wrap_mode;
+        let wraps = [u, v, w];
+
+        if self.reduction_mode != ReductionMode::WeightedAverage
+            && !features.contains(Features::SAMPLER_REDUCTION)
+        {
+            fields |= SamplerFields::REDUCTION_MODE;
+        }
+        // Comparison samplers always reduce with a weighted average.
+        if self.reduction_mode != ReductionMode::WeightedAverage && self.comparison.is_some() {
+            fields |= SamplerFields::REDUCTION_MODE;
+        }
+        if wraps.contains(&WrapMode::MirrorClamp)
+            && !features.contains(Features::SAMPLER_MIRROR_CLAMP_EDGE)
+        {
+            fields |= SamplerFields::WRAP_MODE;
+        }
+        if wraps.contains(&WrapMode::Border) && !features.contains(Features::SAMPLER_BORDER_COLOR) {
+            fields |= SamplerFields::WRAP_MODE | SamplerFields::BORDER;
+        }
+        if self.lod_bias.0 != 0.0 && !features.contains(Features::SAMPLER_MIP_LOD_BIAS) {
+            fields |= SamplerFields::LOD_BIAS;
+        }
+        if self.anisotropy_clamp.is_some() && !features.contains(Features::SAMPLER_ANISOTROPY) {
+            fields |= SamplerFields::ANISOTROPY;
+        }
+
+        if !self.normalized {
+            if self.min_filter != self.mag_filter || self.mip_filter != Filter::Nearest {
+                fields |= SamplerFields::FILTER;
+            }
+            if self.lod_range.start.0 != 0.0 || self.lod_range.end.0 != 0.0 {
+                fields |= SamplerFields::LOD_RANGE;
+            }
+            let clamped = |wrap| wrap == WrapMode::Clamp || wrap == WrapMode::Border;
+            if !clamped(u) || !clamped(v) {
+                fields |= SamplerFields::WRAP_MODE;
+            }
+            if self.comparison.is_some() {
+                fields |= SamplerFields::COMPARISON;
+            }
+            if self.anisotropy_clamp.is_some() {
+                fields |= SamplerFields::ANISOTROPY;
+            }
+        }
+
+        fields
+    }
+}
+
+bitflags!(
+    /// Fields of a [`SamplerDesc`].
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct SamplerFields: u32 {
+        /// Minification, magnification or mip filter.
+        const FILTER = 0x1;
+        /// Reduction mode.
+        const REDUCTION_MODE = 0x2;
+        /// Wrap mode of any axis.
+        const WRAP_MODE = 0x4;
+        /// LOD bias.
+        const LOD_BIAS = 0x8;
+        /// LOD range.
+        const LOD_RANGE = 0x10;
+        /// Comparison mode.
+        const COMPARISON = 0x20;
+        /// Border color.
+        const BORDER = 0x40;
+        /// Unnormalized coordinates.
+        const NORMALIZED = 0x80;
+        /// Anisotropic filtering.
+        const ANISOTROPY = 0x100;
+    }
+);
+
+/// Error creating a sampler.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum SamplerCreationError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] device::OutOfMemory),
+    /// Cannot create any more objects.
+    #[error("Too many objects")]
+    TooManyObjects,
+    /// The device doesn't support some of the fields of the sampler description.
+    #[error("Unsupported sampler fields: {0:?}")]
+    Unsupported(SamplerFields),
+}
+
+impl From<device::AllocationError> for SamplerCreationError {
+    fn from(error: device::AllocationError) -> Self {
+        match error {
+            device::AllocationError::OutOfMemory(oom) => SamplerCreationError::OutOfMemory(oom),
+            device::AllocationError::TooManyObjects => SamplerCreationError::TooManyObjects,
+        }
+    }
 }
 
 /// Specifies options for how memory for an image is arranged.
@@ -569,6 +789,8 @@ pub enum Layout {
     Preinitialized,
     /// The layout that an image must be in to be presented to the display.
     Present,
+    /// Must only be used as a shading rate attachment in a framebuffer.
+    ShadingRateAttachmentOptimal,
 }
 
 impl Default for Layout {
@@ -610,6 +832,8 @@ bitflags!(
         const MEMORY_READ = 0x8000;
         /// Write access for memory to be accessed by a non-specific entity.
         const MEMORY_WRITE = 0x10000;
+        /// Read access to a shading rate attachment while rasterizing.
+        const SHADING_RATE_ATTACHMENT_READ = 0x80_0000;
     }
 );
 
@@ -48,20 +48,31 @@ extern crate serde;
 
 use std::{any::Any, fmt, hash::Hash};
 
+pub mod acceleration_structure;
 pub mod adapter;
 pub mod buffer;
 pub mod command;
+pub mod compute;
 pub mod device;
 pub mod display;
 pub mod external_memory;
 pub mod format;
+pub mod frame;
 pub mod image;
 pub mod memory;
 pub mod pass;
 pub mod pool;
+pub mod profiler;
 pub mod pso;
 pub mod query;
 pub mod queue;
+#[cfg(feature = "trace")]
+pub mod trace;
+#[cfg(feature = "layout-tracking")]
+pub mod track;
+pub mod upload;
+#[cfg(feature = "validation")]
+pub mod validation;
 pub mod window;
 
 /// Prelude module re-exports all the traits necessary to use `gfx-hal`.
@@ -243,7 +254,7 @@ bitflags! {
         /// Allow descriptor arrays to be unsized in shaders
         const UNSIZED_DESCRIPTOR_ARRAY = 0x0800_0000_0000_0000;
         /// Mask for all the features associated with descriptor indexing.
-        const DESCRIPTOR_INDEXING_MASK = Features::SAMPLED_TEXTURE_DESCRIPTOR_INDEXING.bits | Features::STORAGE_TEXTURE_DESCRIPTOR_INDEXING.bits | Features::UNSIZED_DESCRIPTOR_ARRAY.bits | Features::UNIFORM_BUFFER_DESCRIPTOR_INDEXING.bits | Features::STORAGE_BUFFER_DESCRIPTOR_INDEXING.bits;
+        const DESCRIPTOR_INDEXING_MASK = Features::SAMPLED_TEXTURE_DESCRIPTOR_INDEXING.bits | Features::STORAGE_TEXTURE_DESCRIPTOR_INDEXING.bits | Features::UNSIZED_DESCRIPTOR_ARRAY.bits | Features::UNIFORM_BUFFER_DESCRIPTOR_INDEXING.bits | Features::STORAGE_BUFFER_DESCRIPTOR_INDEXING.bits | Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND.bits | Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND.bits | Features::DESCRIPTOR_BINDING_VARIABLE_COUNT.bits;
 
         /// Enable draw_indirect_count and draw_indexed_indirect_count
         const DRAW_INDIRECT_COUNT = 0x1000_0000_0000_0000;
@@ -293,6 +304,48 @@ bitflags! {
         const SAMPLER_REDUCTION = 0x0004 << 96;
         /// Supports external memory import and export.
         const EXTERNAL_MEMORY = 0x0008 << 96;
+        /// Pipeline barriers are recorded natively with layout-based barriers and access
+        /// scopes, instead of being emulated with coarser resource state transitions.
+        const ENHANCED_BARRIERS = 0x0010 << 96;
+        /// Supports capturing vertex outputs into buffers with transform feedback.
+        const TRANSFORM_FEEDBACK = 0x0020 << 96;
+        /// Supports drawing the vertices captured by transform feedback
+        /// with `draw_transform_feedback`.
+        const DRAW_TRANSFORM_FEEDBACK = 0x0040 << 96;
+        /// Supports images of multi-planar formats, such as `Nv12Unorm`,
+        /// with views and copies of the individual planes.
+        ///
+        /// Blitting the whole image into an RGB one, with the `YcbcrConversion` of
+        /// the regions, is reported by the `BLIT_SRC` feature of the format. Otherwise,
+        /// the shaders can sample the views of the planes and convert the values with
+        /// `YcbcrConversion::to_rgb_matrix`.
+        const MULTI_PLANAR_FORMATS = 0x0080 << 96;
+        /// Supports rendering a subpass into several layers of its attachments at once,
+        /// selected by a non-zero `SubpassDesc::view_mask`.
+        const MULTIVIEW = 0x0100 << 96;
+        /// Supports the `PARTIALLY_BOUND` flag on descriptor set layout bindings.
+        const DESCRIPTOR_BINDING_PARTIALLY_BOUND = 0x0200 << 96;
+        /// Supports the `UPDATE_AFTER_BIND` flag on descriptor set layout bindings.
+        const DESCRIPTOR_BINDING_UPDATE_AFTER_BIND = 0x0400 << 96;
+        /// Supports the `VARIABLE_COUNT` flag on descriptor set layout bindings.
+        const DESCRIPTOR_BINDING_VARIABLE_COUNT = 0x0800 << 96;
+        /// Supports writing the viewport index from the vertex and tessellation shaders,
+        /// instead of only from geometry shaders.
+        const SHADER_OUTPUT_VIEWPORT_INDEX = 0x1000 << 96;
+        /// Supports writing the layer of the framebuffer attachments from the vertex and
+        /// tessellation shaders, instead of only from geometry shaders. This allows
+        /// rendering all the faces of a cube map, or all the cascades of a shadow map,
+        /// with instancing in a single pass.
+        const SHADER_OUTPUT_LAYER = 0x2000 << 96;
+        /// Supports building [acceleration structures][acceleration_structure]
+        /// and binding them to shaders.
+        const ACCELERATION_STRUCTURE = 0x4000 << 96;
+        /// Supports ray tracing pipelines and `trace_rays`.
+        const RAY_TRACING_PIPELINE = 0x8000 << 96;
+        /// Supports changing the shading rate of the draws with `set_shading_rate`.
+        const SHADING_RATE = 0x0001 << 112;
+        /// Supports shading rate attachments in subpasses, see `SubpassDesc::shading_rate`.
+        const SHADING_RATE_ATTACHMENT = 0x0002 << 112;
     }
 }
 
@@ -346,6 +399,10 @@ pub struct PhysicalDeviceProperties {
     pub descriptor_indexing: DescriptorIndexingProperties,
     /// Mesh Shader properties.
     pub mesh_shader: MeshShaderProperties,
+    /// Ray tracing properties.
+    pub ray_tracing: RayTracingProperties,
+    /// Variable rate shading properties.
+    pub shading_rate: ShadingRateProperties,
     /// Sampler reduction modes.
     pub sampler_reduction: SamplerReductionProperties,
     /// Downlevel properties.
@@ -424,6 +481,8 @@ pub struct Limits {
     pub max_bound_descriptor_sets: pso::DescriptorSetIndex,
     ///
     pub max_framebuffer_layers: usize,
+    /// Maximum number of views rendered by a subpass with multiview.
+    pub max_multiview_view_count: u32,
     ///
     pub descriptor_limits: DescriptorLimits,
 
@@ -579,6 +638,39 @@ pub struct MeshShaderProperties {
     /// The granularity with which mesh outputs qualified as per-primitive are allocated. The value can be used to
     /// compute the memory size used by the mesh shader, which must be less than or equal to
     pub mesh_output_per_primitive_granularity: u32,
+    /// Whether the draw mesh tasks commands can start from a non-zero task.
+    pub first_task: bool,
+}
+
+/// Resource limits related to ray tracing pipelines.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RayTracingProperties {
+    /// Size of the handle of a shader group, at the start of each shader record.
+    pub shader_group_handle_size: u32,
+    /// Required alignment of the stride of the shader records.
+    pub shader_group_handle_alignment: u32,
+    /// Required alignment of the start of a shader binding table.
+    pub shader_group_base_alignment: u32,
+    /// The maximum stride of the shader records, in bytes.
+    pub max_shader_group_stride: u32,
+    /// The maximum depth of recursive `traceRay` calls.
+    pub max_ray_recursion_depth: u32,
+    /// The maximum number of primitives in a bottom level acceleration structure.
+    pub max_primitive_count: u64,
+    /// The maximum number of instances in a top level acceleration structure.
+    pub max_instance_count: u64,
+}
+
+/// Properties of variable rate shading.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ShadingRateProperties {
+    /// Width and height, in pixels, of the area covered by each texel of a
+    /// shading rate attachment.
+    pub attachment_tile_size: u32,
+    /// Support for the shading rates coarser than 2x2.
+    pub large_rates: bool,
 }
 
 /// Resource limits related to the reduction samplers.
@@ -657,6 +749,110 @@ pub enum IndexType {
 #[error("Backend is not supported on this platform")]
 pub struct UnsupportedBackend;
 
+bitflags! {
+    /// Options of the creation of an instance.
+    ///
+    /// The backends ignore the options they have no equivalent for, and the ones
+    /// the driver doesn't support.
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct InstanceFlags: u32 {
+        /// Report the messages of the driver and of its debug layers through `log`.
+        const DEBUG = 0x0001;
+        /// Enable the validation of the API usage by the driver or its debug layers.
+        const VALIDATION = 0x0002;
+        /// Instrument the shaders to validate their resource accesses on the GPU.
+        /// This is much slower than the validation of the API usage, which it requires.
+        ///
+        /// On Vulkan and DX12, the instrumentation is done by the debug layers of the
+        /// driver. The GL backend instruments the SPIR-V shaders itself, checking their
+        /// dynamic indices into arrays and descriptor arrays, and reserves the last
+        /// storage buffer binding for it. The violations are logged along with the
+        /// offending draw or dispatch. Shaders are not instrumented on the other backends.
+        const GPU_VALIDATION = 0x0004;
+        /// Bound the out-of-range accesses of the shaders, and report the device
+        /// resets as lost devices instead of undefined behavior.
+        const ROBUSTNESS = 0x0008;
+        /// Skip the error checks of the driver, for the performance of release
+        /// builds. Any invalid usage is undefined behavior. This is incompatible
+        /// with the other flags, which take precedence.
+        const NO_ERROR = 0x0010;
+    }
+}
+
+/// Names of the flags in `GFX_INSTANCE_FLAGS`.
+const INSTANCE_FLAG_NAMES: &[(&str, InstanceFlags)] = &[
+    ("DEBUG", InstanceFlags::DEBUG),
+    ("VALIDATION", InstanceFlags::VALIDATION),
+    ("GPU_VALIDATION", InstanceFlags::GPU_VALIDATION),
+    ("ROBUSTNESS", InstanceFlags::ROBUSTNESS),
+    ("NO_ERROR", InstanceFlags::NO_ERROR),
+];
+
+impl InstanceFlags {
+    /// Apply the overrides of the environment to the flags.
+    ///
+    /// `GFX_INSTANCE_FLAGS` lists the names of the flags to add, separated by commas,
+    /// and prefixed with `-` for the ones to remove, e.g. `DEBUG,-VALIDATION`,
+    /// see [`env_flags`].
+    /// `GFX_GPU_VALIDATION` adds [`GPU_VALIDATION`][InstanceFlags::GPU_VALIDATION]
+    /// and [`VALIDATION`][InstanceFlags::VALIDATION] when set.
+    pub fn with_env(mut self) -> Self {
+        if std::env::var("GFX_GPU_VALIDATION").is_ok() {
+            self |= InstanceFlags::VALIDATION | InstanceFlags::GPU_VALIDATION;
+        }
+        for (flag, remove) in env_flags("GFX_INSTANCE_FLAGS", INSTANCE_FLAG_NAMES) {
+            if remove {
+                self -= flag;
+            } else {
+                self |= flag;
+            }
+        }
+        self
+    }
+}
+
+/// Parse the flags listed by the environment variable `var`, returning each of
+/// them with whether it's removed.
+///
+/// The names of the flags are separated by commas, and prefixed with `-` for the
+/// ones to remove. Their values are looked up in `names`, the unknown names being
+/// ignored with a warning.
+pub fn env_flags<T: Copy>(var: &str, names: &[(&str, T)]) -> Vec<(T, bool)> {
+    let value = match std::env::var(var) {
+        Ok(value) => value,
+        Err(_) => return Vec::new(),
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let (name, remove) = match name.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            match names.iter().find(|&&(known, _)| known == name) {
+                Some(&(_, flag)) => Some((flag, remove)),
+                None => {
+                    log::warn!("Unknown flag {:?} in {}", name, var);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+impl Default for InstanceFlags {
+    /// Debug builds report the messages of the driver and validate the API usage.
+    fn default() -> Self {
+        if cfg!(debug_assertions) {
+            InstanceFlags::DEBUG | InstanceFlags::VALIDATION
+        } else {
+            InstanceFlags::empty()
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 /// Physical device limits for external memory management
@@ -711,13 +907,56 @@ pub trait Instance<B: Backend>: Any + Send + Sync + Sized {
     /// on the current platform][UnsupportedBackend].
     fn create(name: &str, version: u32) -> Result<Self, UnsupportedBackend>;
 
+    /// Create a new instance with the given options, overridden by the environment
+    /// as described in [`InstanceFlags::with_env`].
+    ///
+    /// [`create`][Instance::create] uses the [default][InstanceFlags::default] options.
+    /// The backends without options ignore them.
+    fn create_with_flags(
+        name: &str,
+        version: u32,
+        flags: InstanceFlags,
+    ) -> Result<Self, UnsupportedBackend> {
+        let _ = flags;
+        Self::create(name, version)
+    }
+
     /// Return all available [graphics adapters][adapter::Adapter].
     fn enumerate_adapters(&self) -> Vec<adapter::Adapter<B>>;
 
+    /// Choose one of the available adapters with an application-defined `score`.
+    ///
+    /// See [`select_adapter`][adapter::select_adapter] for details.
+    fn select_adapter<F>(&self, score: F) -> adapter::AdapterSelection<B>
+    where
+        F: FnMut(&adapter::Adapter<B>) -> Result<u32, adapter::Rejection>,
+    {
+        adapter::select_adapter(self.enumerate_adapters(), score)
+    }
+
+    /// Pick the available adapter best matching a `preference`, if any.
+    ///
+    /// See [`AdapterPreference::score`][adapter::AdapterPreference::score] for the
+    /// ranking of the adapters.
+    fn pick_adapter(&self, preference: adapter::AdapterPreference) -> Option<adapter::Adapter<B>> {
+        self.select_adapter(|adapter| preference.score(&adapter.info))
+            .adapter
+    }
+
     /// Create a new [surface][window::Surface].
     ///
     /// Surfaces can be used to render to windows.
     ///
+    /// The backends accept the handles of the window systems they present to on
+    /// the current platform: Win32, Xlib, XCB, Wayland, AppKit, UIKit, Android
+    /// or web canvases.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`InitError`][window::InitError] if the kind of handle isn't
+    /// supported by the backend, if the handle is null, or if the instance can't
+    /// present to its window system.
+    ///
     /// # Safety
     ///
     /// This method can cause undefined behavior if `raw_window_handle` isn't
@@ -821,6 +1060,8 @@ pub trait Backend: 'static + Sized + Eq + Clone + Hash + fmt::Debug + Any + Send
     type ComputePipeline: fmt::Debug + Any + Send + Sync;
     /// The corresponding graphics pipeline type for this backend.
     type GraphicsPipeline: fmt::Debug + Any + Send + Sync;
+    /// The corresponding ray tracing pipeline type for this backend.
+    type RayTracingPipeline: fmt::Debug + Any + Send + Sync;
     /// The corresponding pipeline cache type for this backend.
     type PipelineCache: fmt::Debug + Any + Send + Sync;
     /// The corresponding pipeline layout type for this backend.
@@ -840,6 +1081,8 @@ pub trait Backend: 'static + Sized + Eq + Clone + Hash + fmt::Debug + Any + Send
     type Event: fmt::Debug + Any + Send + Sync;
     /// The corresponding query pool type for this backend.
     type QueryPool: fmt::Debug + Any + Send + Sync;
+    /// The corresponding [acceleration structure][acceleration_structure] type for this backend.
+    type AccelerationStructure: fmt::Debug + Any + Send + Sync;
     /// The corresponding display type for this backend.
     type Display: fmt::Debug + Any + Send + Sync;
     /// The corresponding display mode type for this backend
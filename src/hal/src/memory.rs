@@ -1,7 +1,10 @@
 //! Types to describe the properties of memory allocated for graphics resources.
 
-use crate::{buffer, image, queue, Backend};
-use std::ops::Range;
+use crate::{adapter::MemoryHeap, buffer, image, queue, Backend};
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 bitflags!(
     /// Memory property flags.
@@ -26,6 +29,14 @@ bitflags!(
         /// Memory that may be lazily allocated as needed on the GPU
         /// and *must not* be visible to the CPU.
         const LAZILY_ALLOCATED = 0x10;
+
+        /// Resources bound to overlapping ranges of this memory share their storage,
+        /// e.g. for the transient attachments of a frame graph.
+        ///
+        /// Switching between the aliased resources requires a [`Barrier::Aliasing`].
+        /// Overlapping bindings are also valid on the other memory types, but the
+        /// resources may not share any storage.
+        const ALIASABLE = 0x20;
     }
 );
 
@@ -86,6 +97,33 @@ pub enum Barrier<'a, B: Backend> {
         /// Can be `None` to indicate no ownership transfer.
         families: Option<Range<queue::QueueFamilyId>>,
     },
+    /// A barrier switching the use of aliased memory from one resource to another.
+    ///
+    /// The contents of the resource used after the barrier are undefined, images
+    /// have to be transitioned from the `Undefined` layout before their use.
+    Aliasing {
+        /// The resource used before the barrier, or `None` for any of them.
+        before: Option<Aliased<'a, B>>,
+        /// The resource used after the barrier, or `None` for any of them.
+        after: Option<Aliased<'a, B>>,
+    },
+}
+
+/// A resource bound to memory shared with other resources.
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum Aliased<'a, B: Backend> {
+    Buffer(&'a B::Buffer),
+    Image(&'a B::Image),
+}
+
+impl<'a, B: Backend> Clone for Aliased<'a, B> {
+    fn clone(&self) -> Self {
+        match *self {
+            Aliased::Buffer(buffer) => Aliased::Buffer(buffer),
+            Aliased::Image(image) => Aliased::Image(image),
+        }
+    }
 }
 
 impl<'a, B: Backend> Barrier<'a, B> {
@@ -111,6 +149,90 @@ pub struct Requirements {
     pub type_mask: u32,
 }
 
+/// Memory usage of the process in a heap, and the amount it can use.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HeapBudget {
+    /// Bytes currently used in the heap.
+    pub usage: u64,
+    /// Bytes that can be used in the heap before the allocations start failing,
+    /// or degrade the performance. It changes with the memory used by the other
+    /// processes and by the system.
+    pub budget: u64,
+}
+
+/// Priority of a memory object to stay resident in its heap, when the memory used
+/// in the heap exceeds its [budget][HeapBudget].
+///
+/// The memory objects with the lowest priority are the first to be moved out of
+/// the heap, making the device access them more slowly, or not at all.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Priority {
+    /// Memory that can be moved out of the heap first.
+    Minimum,
+    /// Memory less important than the default.
+    Low,
+    /// Default priority of the memory objects.
+    Normal,
+    /// Memory more important than the default.
+    High,
+    /// Memory that the device can't do without, like the render targets.
+    Maximum,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+/// Bytes allocated through a device in each memory heap.
+///
+/// This is the fallback of the backends reporting the memory usage when the driver
+/// can't be queried for it. Only the allocations made through the device are
+/// accounted, not the internal ones of the driver.
+#[derive(Debug, Default)]
+pub struct HeapUsageTracker {
+    heaps: Vec<AtomicU64>,
+}
+
+impl HeapUsageTracker {
+    /// Create a tracker of `heap_count` heaps, with nothing allocated.
+    pub fn new(heap_count: usize) -> Self {
+        HeapUsageTracker {
+            heaps: (0..heap_count).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Account for an allocation of `size` bytes in the heap `heap_index`.
+    pub fn allocate(&self, heap_index: usize, size: u64) {
+        self.heaps[heap_index].fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Account for the release of an allocation of `size` bytes in the heap `heap_index`.
+    pub fn free(&self, heap_index: usize, size: u64) {
+        self.heaps[heap_index].fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Bytes allocated in the heap `heap_index`.
+    pub fn usage(&self, heap_index: usize) -> u64 {
+        self.heaps[heap_index].load(Ordering::Relaxed)
+    }
+
+    /// Report the tracked usage of the `heaps`, with their whole size as budget.
+    pub fn budgets(&self, heaps: &[MemoryHeap]) -> Vec<HeapBudget> {
+        heaps
+            .iter()
+            .enumerate()
+            .map(|(index, heap)| HeapBudget {
+                usage: self.usage(index),
+                budget: heap.size,
+            })
+            .collect()
+    }
+}
+
 /// A linear segment within a memory block.
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -167,6 +167,17 @@ pub struct SubpassDesc<'a> {
     /// Attachments that are not used by the subpass but must be preserved to be
     /// passed on to subsequent passes.
     pub preserves: &'a [AttachmentId],
+    /// Which array layers of the attachments are rendered by each draw, as a bitmask
+    /// of view indices, which shaders can read with `ViewIndex`.
+    ///
+    /// Zero disables multiview, and any other value requires `Features::MULTIVIEW`.
+    pub view_mask: u32,
+    /// Attachment holding one shading rate per tile of the framebuffer, combined with
+    /// the rate set by `set_shading_rate`. Its texels cover
+    /// `ShadingRateProperties::attachment_tile_size` pixels in each dimension.
+    ///
+    /// Requires `Features::SHADING_RATE_ATTACHMENT`.
+    pub shading_rate: Option<&'a AttachmentRef>,
 }
 
 /// A sub-pass borrow of a pass.
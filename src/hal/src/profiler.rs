@@ -0,0 +1,442 @@
+//! Timing of the frames on the device.
+//!
+//! Timestamp queries give the time at which the device reached a point of a command
+//! buffer, in ticks of a counter unrelated to the clocks of the host. A
+//! [`FrameProfiler`] writes a pair of timestamps around the labeled scopes of the
+//! command buffers of a frame, converts the ticks to nanoseconds with the
+//! [timestamp period][Queue::timestamp_period] of the queue, and gathers the
+//! durations into a [`FrameReport`] following the nesting of the scopes.
+//!
+//! Each frame in flight writes into its own query pool, and the results of a frame
+//! are read when its pool comes back around, by which point the frame is expected
+//! to have completed. Used along with [`Frames`][crate::frame::Frames] created with
+//! the same number of frames in flight, reading the results never waits on the device.
+//!
+//! If the queue supports [calibration][Queue::timestamp_calibration], the reports
+//! also place the frames on the timeline of the host, so they can be correlated with
+//! the work of the host that recorded them.
+//!
+//! The command buffers have to be submitted to queues supporting timestamp queries,
+//! see `Limits::timestamp_compute_and_graphics`.
+
+use crate::{
+    buffer,
+    command::CommandBuffer,
+    device::{Device, WaitError},
+    frame::{Epoch, Frames},
+    pso::PipelineStage,
+    query,
+    queue::{Queue, TimestampCalibration},
+    Backend,
+};
+
+use std::{
+    fmt, mem, slice,
+    time::{Duration, Instant},
+};
+
+/// Time spent by the device in a scope of a frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScopeTiming {
+    /// Label of the scope.
+    pub label: String,
+    /// Start of the scope, relative to the start of the first scope of the frame.
+    pub start: Duration,
+    /// Time between the start and the end of the scope.
+    pub duration: Duration,
+    /// Scopes nested in this one, in the order they were recorded.
+    pub children: Vec<ScopeTiming>,
+}
+
+impl ScopeTiming {
+    fn write_tree(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{}: {:?}",
+            "",
+            self.label,
+            self.duration,
+            indent = 2 * depth
+        )?;
+        for child in &self.children {
+            child.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Timings of the scopes of a frame.
+///
+/// The `Display` implementation prints the scopes as an indented tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameReport {
+    /// Epoch of the frame.
+    pub epoch: Epoch,
+    /// Time of the host at which the first scope of the frame started on the device,
+    /// if the queue supports timestamp calibration.
+    pub cpu_start: Option<Instant>,
+    /// Outermost scopes of the frame, in the order they were recorded.
+    pub scopes: Vec<ScopeTiming>,
+    /// Number of scopes left out because the query pool of the frame was full.
+    pub dropped_scopes: u32,
+}
+
+impl FrameReport {
+    /// Time between the start of the first scope and the end of the last one.
+    pub fn duration(&self) -> Duration {
+        self.scopes
+            .iter()
+            .map(|scope| scope.start + scope.duration)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for FrameReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Frame {}: {:?}", self.epoch, self.duration())?;
+        for scope in &self.scopes {
+            scope.write_tree(f, 1)?;
+        }
+        if self.dropped_scopes != 0 {
+            writeln!(f, "  ({} scopes dropped)", self.dropped_scopes)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Scope {
+    label: String,
+    /// Index of the innermost timed scope enclosing this one.
+    parent: Option<usize>,
+}
+
+#[derive(Debug)]
+struct Slot<B: Backend> {
+    /// Holds the start and end timestamps of each scope, one after the other.
+    pool: B::QueryPool,
+    /// Frame recorded into the pool, whose results haven't been read yet.
+    epoch: Option<Epoch>,
+    scopes: Vec<Scope>,
+    dropped_scopes: u32,
+}
+
+/// Gather the timings of the `scopes` into the tree of the outermost ones, from
+/// their start and end `ticks` relative to `origin`.
+fn scope_tree(
+    scopes: &[Scope],
+    ticks: &[u64],
+    origin: u64,
+    timestamp_period: f32,
+) -> Vec<ScopeTiming> {
+    let to_duration =
+        |ticks: u64| Duration::from_nanos((ticks as f64 * timestamp_period as f64) as u64);
+    let mut timings = scopes
+        .iter()
+        .enumerate()
+        .map(|(index, scope)| {
+            let (start, end) = (ticks[2 * index], ticks[2 * index + 1]);
+            Some(ScopeTiming {
+                label: scope.label.clone(),
+                start: to_duration(start.saturating_sub(origin)),
+                duration: to_duration(end.saturating_sub(start)),
+                children: Vec::new(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Nested scopes always come after their parent, so walking backwards
+    // completes each scope before it's attached to its parent.
+    let mut roots = Vec::new();
+    for (index, scope) in scopes.iter().enumerate().rev() {
+        let mut timing = timings[index].take().unwrap();
+        timing.children.reverse();
+        match scope.parent {
+            Some(parent) => timings[parent].as_mut().unwrap().children.push(timing),
+            None => roots.push(timing),
+        }
+    }
+    roots.reverse();
+    roots
+}
+
+/// Profiler measuring the time spent by the device in the labeled scopes of the frames.
+///
+/// All the calls have to use the same device, and the command buffers have to be
+/// submitted to queues with the timestamp period of the queue given on creation.
+#[derive(Debug)]
+pub struct FrameProfiler<B: Backend> {
+    slots: Vec<Slot<B>>,
+    /// Index of the slot of the frame being recorded, or of the last one.
+    current: usize,
+    recording: bool,
+    max_scopes: u32,
+    /// Stack of the scopes being recorded, `None` for the dropped ones.
+    open_scopes: Vec<Option<usize>>,
+    timestamp_period: f32,
+    calibration: Option<TimestampCalibration>,
+}
+
+impl<B: Backend> FrameProfiler<B> {
+    /// Create a profiler for up to `max_in_flight` frames executing on the device
+    /// while the next one is recorded, timing up to `max_scopes` scopes per frame.
+    ///
+    /// The timestamp period and the calibration are taken from `queue`.
+    pub unsafe fn new<D: Device<B> + ?Sized>(
+        device: &D,
+        queue: &B::Queue,
+        max_in_flight: usize,
+        max_scopes: u32,
+    ) -> Result<Self, query::CreationError> {
+        assert_ne!(max_in_flight, 0);
+        assert_ne!(max_scopes, 0);
+        let mut slots = Vec::with_capacity(max_in_flight + 1);
+        for _ in 0..=max_in_flight {
+            match device.create_query_pool(query::Type::Timestamp, 2 * max_scopes) {
+                Ok(pool) => slots.push(Slot {
+                    pool,
+                    epoch: None,
+                    scopes: Vec::new(),
+                    dropped_scopes: 0,
+                }),
+                Err(err) => {
+                    for slot in slots {
+                        device.destroy_query_pool(slot.pool);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(FrameProfiler {
+            slots,
+            current: 0,
+            recording: false,
+            max_scopes,
+            open_scopes: Vec::new(),
+            timestamp_period: queue.timestamp_period(),
+            calibration: queue.timestamp_calibration(),
+        })
+    }
+
+    /// Sample the clocks of the `queue` and of the host again, compensating for
+    /// their drift since the last calibration.
+    pub fn calibrate(&mut self, queue: &B::Queue) {
+        self.calibration = queue.timestamp_calibration();
+    }
+
+    /// Begin profiling the frame being recorded by `frames`, returning the report
+    /// of the frame that previously used its query pool, if any.
+    ///
+    /// The report of a frame carries its epoch, so the timings can be bucketed
+    /// along with the other work of the frame.
+    ///
+    /// The query pool is reset in `command_buffer`, outside of a render pass.
+    /// It has to be submitted before the other command buffers of the frame.
+    ///
+    /// # Safety
+    ///
+    /// The frame that previously used the query pool, `max_in_flight + 1` frames
+    /// ago, has to be submitted. Its results are waited for if it hasn't completed.
+    pub unsafe fn begin_frame<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        command_buffer: &mut B::CommandBuffer,
+        frames: &Frames<B>,
+    ) -> Result<Option<FrameReport>, WaitError> {
+        assert!(!self.recording, "The previous frame hasn't ended");
+        self.current = (self.current + 1) % self.slots.len();
+        let report = self.read_results(device)?;
+
+        let slot = &mut self.slots[self.current];
+        command_buffer.reset_query_pool(&slot.pool, 0..2 * self.max_scopes);
+        slot.epoch = Some(frames.epoch());
+        self.recording = true;
+        Ok(report)
+    }
+
+    /// End the frame being recorded, all of its scopes having ended.
+    pub fn end_frame(&mut self) {
+        assert!(self.recording, "No frame has begun");
+        assert!(
+            self.open_scopes.is_empty(),
+            "{} scopes haven't ended",
+            self.open_scopes.len()
+        );
+        self.recording = false;
+    }
+
+    /// Begin a scope labeled `label` in `command_buffer`, nested in the scope
+    /// being recorded, if any.
+    ///
+    /// The scope is dropped from the report if the frame already has `max_scopes`
+    /// of them.
+    pub unsafe fn begin_scope(&mut self, command_buffer: &mut B::CommandBuffer, label: &str) {
+        assert!(self.recording, "No frame has begun");
+        let slot = &mut self.slots[self.current];
+        let index = slot.scopes.len();
+        if index as u32 == self.max_scopes {
+            slot.dropped_scopes += 1;
+            self.open_scopes.push(None);
+            return;
+        }
+
+        slot.scopes.push(Scope {
+            label: label.to_string(),
+            parent: self.open_scopes.iter().rev().find_map(|&scope| scope),
+        });
+        command_buffer.write_timestamp(
+            PipelineStage::TOP_OF_PIPE,
+            query::Query {
+                pool: &slot.pool,
+                id: 2 * index as query::Id,
+            },
+        );
+        self.open_scopes.push(Some(index));
+    }
+
+    /// End the innermost scope being recorded, in `command_buffer`.
+    pub unsafe fn end_scope(&mut self, command_buffer: &mut B::CommandBuffer) {
+        let scope = self.open_scopes.pop().expect("No scope has begun");
+        if let Some(index) = scope {
+            command_buffer.write_timestamp(
+                PipelineStage::BOTTOM_OF_PIPE,
+                query::Query {
+                    pool: &self.slots[self.current].pool,
+                    id: 2 * index as query::Id + 1,
+                },
+            );
+        }
+    }
+
+    /// Read the results of the frame recorded into the current slot.
+    unsafe fn read_results<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+    ) -> Result<Option<FrameReport>, WaitError> {
+        let timestamp_period = self.timestamp_period;
+        let slot = &mut self.slots[self.current];
+        let epoch = match slot.epoch.take() {
+            Some(epoch) => epoch,
+            None => return Ok(None),
+        };
+        let scopes = mem::take(&mut slot.scopes);
+        let dropped_scopes = mem::replace(&mut slot.dropped_scopes, 0);
+        if scopes.is_empty() {
+            return Ok(Some(FrameReport {
+                epoch,
+                cpu_start: None,
+                scopes: Vec::new(),
+                dropped_scopes,
+            }));
+        }
+
+        let mut ticks = vec![0u64; 2 * scopes.len()];
+        device.get_query_pool_results(
+            &slot.pool,
+            0..ticks.len() as query::Id,
+            slice::from_raw_parts_mut(
+                ticks.as_mut_ptr() as *mut u8,
+                ticks.len() * mem::size_of::<u64>(),
+            ),
+            mem::size_of::<u64>() as buffer::Stride,
+            query::ResultFlags::BITS_64 | query::ResultFlags::WAIT,
+        )?;
+
+        // The scopes recorded in different command buffers may execute in any order.
+        let origin = ticks.iter().step_by(2).cloned().min().unwrap();
+        Ok(Some(FrameReport {
+            epoch,
+            cpu_start: self
+                .calibration
+                .and_then(|calibration| calibration.instant(origin, timestamp_period)),
+            scopes: scope_tree(&scopes, &ticks, origin, timestamp_period),
+            dropped_scopes,
+        }))
+    }
+
+    /// Destroy the query pools of the profiler.
+    ///
+    /// # Safety
+    ///
+    /// None of the frames recorded with the profiler may be executing.
+    pub unsafe fn destroy<D: Device<B> + ?Sized>(self, device: &D) {
+        for slot in self.slots {
+            device.destroy_query_pool(slot.pool);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(label: &str, parent: Option<usize>) -> Scope {
+        Scope {
+            label: label.to_string(),
+            parent,
+        }
+    }
+
+    fn timing(label: &str, start: u64, duration: u64, children: Vec<ScopeTiming>) -> ScopeTiming {
+        ScopeTiming {
+            label: label.to_string(),
+            start: Duration::from_micros(start),
+            duration: Duration::from_micros(duration),
+            children,
+        }
+    }
+
+    #[test]
+    fn scope_tree_follows_the_nesting() {
+        let scopes = [
+            scope("scene", None),
+            scope("shadows", Some(0)),
+            scope("cascade", Some(1)),
+            scope("lights", Some(0)),
+            scope("ui", None),
+        ];
+        // Ticks of 2µs, starting at 5.
+        let ticks = [5, 455, 55, 205, 105, 155, 255, 405, 505, 605];
+
+        assert_eq!(
+            scope_tree(&scopes, &ticks, 5, 2000.0),
+            [
+                timing(
+                    "scene",
+                    0,
+                    900,
+                    vec![
+                        timing(
+                            "shadows",
+                            100,
+                            300,
+                            vec![timing("cascade", 200, 100, vec![])]
+                        ),
+                        timing("lights", 500, 300, vec![]),
+                    ]
+                ),
+                timing("ui", 1000, 200, vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_reports_print_the_scope_tree() {
+        let report = FrameReport {
+            epoch: 3,
+            cpu_start: None,
+            scopes: vec![
+                timing("scene", 0, 900, vec![timing("shadows", 100, 300, vec![])]),
+                timing("ui", 1000, 200, vec![]),
+            ],
+            dropped_scopes: 1,
+        };
+
+        assert_eq!(report.duration(), Duration::from_micros(1200));
+        assert_eq!(
+            report.to_string(),
+            "Frame 3: 1.2ms\n  scene: 900µs\n    shadows: 300µs\n  ui: 200µs\n  (1 scopes dropped)\n"
+        );
+    }
+}
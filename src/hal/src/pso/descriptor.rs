@@ -69,7 +69,8 @@ pub enum ImageDescriptorType {
     },
     /// A storage image allows load, store and atomic operations.
     Storage {
-        /// If true, store operations are not permitted on this image.
+        /// If true, store operations are not permitted on this image,
+        /// which lets backends bind it with read-only access.
         read_only: bool,
     },
 }
@@ -94,6 +95,10 @@ pub enum DescriptorType {
     },
     /// A descriptor associated with an input attachment.
     InputAttachment,
+    /// A descriptor associated with a top level acceleration structure.
+    ///
+    /// Requires `Features::ACCELERATION_STRUCTURE`.
+    AccelerationStructure,
 }
 
 /// Information about the contents of and in which stages descriptors may be bound to a descriptor
@@ -108,7 +113,7 @@ pub enum DescriptorType {
 /// The binding _must_ match with the corresponding shader interface.
 ///
 /// [`DescriptorPool`]: trait.DescriptorPool.html
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DescriptorSetLayoutBinding {
     /// Descriptor bindings range.
@@ -124,6 +129,37 @@ pub struct DescriptorSetLayoutBinding {
     pub stage_flags: ShaderStageFlags,
     /// Use the associated list of immutable samplers.
     pub immutable_samplers: bool,
+    /// Descriptor indexing flags of the binding.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flags: DescriptorBindingFlags,
+}
+
+bitflags! {
+    /// Descriptor indexing flags of a descriptor set layout binding.
+    #[derive(Default)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct DescriptorBindingFlags: u32 {
+        /// The descriptors don't have to be written before the set is used, as long as
+        /// the shaders don't access the unwritten ones.
+        ///
+        /// Requires `Features::DESCRIPTOR_BINDING_PARTIALLY_BOUND`.
+        const PARTIALLY_BOUND = 0x1;
+        /// The descriptors can be written after the set is bound, until the command
+        /// buffers using it are submitted. The set has to be allocated from a pool
+        /// created with `DescriptorPoolCreateFlags::UPDATE_AFTER_BIND`.
+        ///
+        /// Sampler descriptors, including the samplers of combined image samplers,
+        /// can't be updated after binding.
+        ///
+        /// Requires `Features::DESCRIPTOR_BINDING_UPDATE_AFTER_BIND`.
+        const UPDATE_AFTER_BIND = 0x2;
+        /// The number of descriptors is chosen when allocating the set with
+        /// [`DescriptorPool::allocate_variable`], `count` being an upper bound.
+        /// Only the binding with the highest number can have a variable count.
+        ///
+        /// Requires `Features::DESCRIPTOR_BINDING_VARIABLE_COUNT`.
+        const VARIABLE_COUNT = 0x4;
+    }
 }
 
 /// Set of descriptors of a specific type.
@@ -202,6 +238,20 @@ pub trait DescriptorPool<B: Backend>: Send + Sync + fmt::Debug {
         Ok(())
     }
 
+    /// Allocate a descriptor set from the pool, with `count` descriptors in its
+    /// [variable count][DescriptorBindingFlags::VARIABLE_COUNT] binding.
+    ///
+    /// `count` must not exceed the `count` of the binding in the layout. Backends
+    /// without native support allocate the whole binding instead.
+    unsafe fn allocate_variable(
+        &mut self,
+        layout: &B::DescriptorSetLayout,
+        count: DescriptorArrayIndex,
+    ) -> Result<B::DescriptorSet, AllocationError> {
+        let _ = count;
+        self.allocate_one(layout)
+    }
+
     /// Free the given descriptor sets provided as an iterator.
     unsafe fn free<I>(&mut self, descriptor_sets: I)
     where
@@ -249,6 +299,7 @@ pub enum Descriptor<'a, B: Backend> {
     CombinedImageSampler(&'a B::ImageView, Layout, &'a B::Sampler),
     Buffer(&'a B::Buffer, SubRange),
     TexelBuffer(&'a B::BufferView),
+    AccelerationStructure(&'a B::AccelerationStructure),
 }
 
 /// Copies a range of descriptors to be bound from one descriptor set to another.
@@ -288,5 +339,8 @@ bitflags! {
         /// Specifies that descriptor sets are allowed to be freed from the pool
         /// individually.
         const FREE_DESCRIPTOR_SET = 0x1;
+        /// Specifies that the descriptor sets allocated from the pool can have bindings
+        /// with the [`DescriptorBindingFlags::UPDATE_AFTER_BIND`] flag.
+        const UPDATE_AFTER_BIND = 0x2;
     }
 }
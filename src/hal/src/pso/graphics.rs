@@ -3,11 +3,11 @@
 use crate::{
     image, pass,
     pso::{
-        input_assembler::{AttributeDesc, InputAssemblerDesc, VertexBufferDesc},
+        input_assembler::{AttributeDesc, InputAssemblerDesc, Location, VertexBufferDesc},
         output_merger::{ColorBlendDesc, DepthStencilDesc, Face},
         BasePipeline, EntryPoint, PipelineCreationFlags, State,
     },
-    Backend,
+    Backend, DynamicStates,
 };
 
 use std::ops::Range;
@@ -141,6 +141,11 @@ pub struct GraphicsPipelineDesc<'a, B: Backend> {
     /// The parent pipeline, which may be
     /// `BasePipeline::None`.
     pub parent: BasePipeline<'a, B::GraphicsPipeline>,
+    /// Outputs of the vertex processing stages to capture into the
+    /// transform feedback buffers, if any.
+    ///
+    /// Requires `Features::TRANSFORM_FEEDBACK`.
+    pub transform_feedback: Option<TransformFeedbackDesc>,
 }
 
 impl<'a, B: Backend> GraphicsPipelineDesc<'a, B> {
@@ -165,7 +170,87 @@ impl<'a, B: Backend> GraphicsPipelineDesc<'a, B> {
             subpass,
             flags: PipelineCreationFlags::empty(),
             parent: BasePipeline::None,
+            transform_feedback: None,
+        }
+    }
+
+    /// Returns the set of states that are left dynamic by this descriptor.
+    ///
+    /// Everything else is baked into the pipeline, and the matching `set_*`
+    /// commands must not be recorded while it is bound. States that don't
+    /// affect the pipeline, like depth bounds with the test disabled,
+    /// are never reported as dynamic.
+    pub fn dynamic_states(&self) -> DynamicStates {
+        let mut states = DynamicStates::empty();
+        if self.baked_states.viewport.is_none() {
+            states |= DynamicStates::VIEWPORT;
+        }
+        if self.baked_states.scissor.is_none() {
+            states |= DynamicStates::SCISSOR;
+        }
+        if self.baked_states.blend_constants.is_none() {
+            states |= DynamicStates::BLEND_CONSTANTS;
+        }
+        if self.depth_stencil.depth_bounds && self.baked_states.depth_bounds.is_none() {
+            states |= DynamicStates::DEPTH_BOUNDS;
+        }
+        if self.rasterizer.line_width.is_dynamic() {
+            states |= DynamicStates::LINE_WIDTH;
+        }
+        if let Some(State::Dynamic) = self.rasterizer.depth_bias {
+            states |= DynamicStates::DEPTH_BIAS;
+        }
+        if let Some(ref stencil) = self.depth_stencil.stencil {
+            if stencil.read_masks.is_dynamic() {
+                states |= DynamicStates::STENCIL_READ_MASK;
+            }
+            if stencil.write_masks.is_dynamic() {
+                states |= DynamicStates::STENCIL_WRITE_MASK;
+            }
+            if stencil.reference_values.is_dynamic() {
+                states |= DynamicStates::STENCIL_REFERENCE;
+            }
+        }
+        states
+    }
+}
+
+/// An output of the last vertex processing stage captured by transform feedback.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransformFeedbackVarying {
+    /// Location of the shader output.
+    pub location: Location,
+    /// Number of 32-bit components of the output, from 1 to 4.
+    pub components: u8,
+    /// Transform feedback buffer binding the output is written to.
+    pub buffer: u32,
+}
+
+/// Declares which vertex outputs a graphics pipeline captures into buffers.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TransformFeedbackDesc {
+    /// Captured outputs. Outputs written to the same buffer are tightly
+    /// packed, in the order they are listed here.
+    pub varyings: Vec<TransformFeedbackVarying>,
+    /// Whether the primitives are rasterized after being captured.
+    /// If false, they are discarded once written to the buffers.
+    pub rasterize: bool,
+}
+
+impl TransformFeedbackDesc {
+    /// Returns the number of bytes written to each buffer binding per vertex.
+    pub fn buffer_strides(&self) -> Vec<u32> {
+        let mut strides = Vec::new();
+        for varying in &self.varyings {
+            let buffer = varying.buffer as usize;
+            if strides.len() <= buffer {
+                strides.resize(buffer + 1, 0);
+            }
+            strides[buffer] += varying.components as u32 * 4;
         }
+        strides
     }
 }
 
@@ -285,6 +370,7 @@ pub type SampleMask = u64;
 
 ///
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Multisampling {
     ///
     pub rasterization_samples: image::NumSamples,
@@ -299,3 +385,49 @@ pub struct Multisampling {
     ///
     pub alpha_to_one: bool,
 }
+
+/// Size of the blocks of pixels covered by a single fragment shader invocation,
+/// with variable rate shading.
+///
+/// The rates larger than 2x2 require `ShadingRateProperties::large_rates`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ShadingRate {
+    /// One invocation per pixel.
+    Rate1x1,
+    /// One invocation per pixel wide, two pixels tall, block.
+    Rate1x2,
+    /// One invocation per two pixels wide, one pixel tall, block.
+    Rate2x1,
+    /// One invocation per 2x2 block of pixels.
+    Rate2x2,
+    /// One invocation per two pixels wide, four pixels tall, block.
+    Rate2x4,
+    /// One invocation per four pixels wide, two pixels tall, block.
+    Rate4x2,
+    /// One invocation per 4x4 block of pixels.
+    Rate4x4,
+}
+
+impl Default for ShadingRate {
+    fn default() -> Self {
+        ShadingRate::Rate1x1
+    }
+}
+
+/// Operation combining two shading rates into one.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ShadingRateCombiner {
+    /// Keep the current rate, ignoring the other one.
+    Keep,
+    /// Replace the current rate by the other one.
+    Replace,
+    /// Use the finer of the two rates.
+    Min,
+    /// Use the coarser of the two rates.
+    Max,
+    /// Multiply the sizes of the blocks of both rates, clamped to the
+    /// largest rate supported.
+    Sum,
+}
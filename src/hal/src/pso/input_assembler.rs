@@ -46,6 +46,16 @@ pub struct Element<F> {
     pub offset: ElemOffset,
 }
 
+/// Typed elements are statically checked to have a format usable for vertex elements.
+impl<F: format::BufferFormat> From<Element<F>> for Element<format::Format> {
+    fn from(element: Element<F>) -> Self {
+        Element {
+            format: F::SELF,
+            offset: element.offset,
+        }
+    }
+}
+
 /// Vertex buffer description. Notably, completely separate from resource `Descriptor`s
 /// used in `DescriptorSet`s.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
@@ -0,0 +1,560 @@
+//! Manifests of the pipelines created by an application.
+//!
+//! Creating a pipeline the first time it's used causes a hitch, as the driver
+//! compiles its shaders on the spot. A [`PipelineManifest`] records the pipelines
+//! created during a session, so they can be created ahead of time at the startup
+//! of the next ones with [`PipelineManifest::warm_up`], ideally hitting a pipeline
+//! cache saved from a previous session.
+//!
+//! The manifest doesn't hold any backend object: the shader modules, pipeline
+//! layouts and render passes are referenced by keys chosen by the application,
+//! through the [`ManifestObjects`] trait. With the `serde` feature, the manifest
+//! can be stored in any format, and the manifests collected on multiple machines
+//! can be combined with [`PipelineManifest::merge_with`].
+
+use crate::{
+    pass::SubpassId,
+    pso::{
+        AttributeDesc, BakedStates, BasePipeline, BlendDesc, ComputePipelineDesc, CreationError,
+        DepthStencilDesc, EntryPoint, GraphicsPipelineDesc, InputAssemblerDesc, Multisampling,
+        PipelineCreationFlags, PrimitiveAssemblerDesc, Rasterizer, Specialization,
+        TransformFeedbackDesc, VertexBufferDesc,
+    },
+    Backend,
+};
+
+use std::{borrow::Cow, collections::BTreeMap};
+
+/// Error occurring when recording or warming up the pipelines of a manifest.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ManifestError {
+    /// A pipeline references an object without a key.
+    #[error("Pipeline references an object without a key")]
+    UnknownObject,
+    /// A pipeline references a key without an object.
+    #[error("Pipeline references the key `{0}` without an object")]
+    MissingObject(String),
+    /// The pipeline creation failed.
+    #[error(transparent)]
+    Creation(#[from] CreationError),
+}
+
+/// Mapping between the objects referenced by the pipelines and their keys in
+/// a [`PipelineManifest`].
+///
+/// The keys are chosen by the application, and must identify the same objects
+/// from a session to another, for example by using the paths of the shaders.
+pub trait ManifestObjects<B: Backend> {
+    /// Get the key of a shader module.
+    fn shader_module_key(&self, module: &B::ShaderModule) -> Option<String>;
+    /// Get the key of a pipeline layout.
+    fn pipeline_layout_key(&self, layout: &B::PipelineLayout) -> Option<String>;
+    /// Get the key of a render pass.
+    fn render_pass_key(&self, render_pass: &B::RenderPass) -> Option<String>;
+    /// Get the shader module with a key.
+    fn shader_module(&self, key: &str) -> Option<&B::ShaderModule>;
+    /// Get the pipeline layout with a key.
+    fn pipeline_layout(&self, key: &str) -> Option<&B::PipelineLayout>;
+    /// Get the render pass with a key.
+    fn render_pass(&self, key: &str) -> Option<&B::RenderPass>;
+}
+
+/// Recorded shader entry point.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EntryPointRecord {
+    /// Entry point name.
+    pub entry: String,
+    /// Key of the shader module.
+    pub module: String,
+    /// Specialization constants to be used in the shader.
+    pub specialization: Specialization<'static>,
+}
+
+impl EntryPointRecord {
+    fn new<B: Backend>(
+        entry: &EntryPoint<B>,
+        objects: &impl ManifestObjects<B>,
+    ) -> Result<Self, ManifestError> {
+        Ok(EntryPointRecord {
+            entry: entry.entry.to_string(),
+            module: objects
+                .shader_module_key(entry.module)
+                .ok_or(ManifestError::UnknownObject)?,
+            specialization: Specialization {
+                constants: Cow::Owned(entry.specialization.constants.to_vec()),
+                data: Cow::Owned(entry.specialization.data.to_vec()),
+            },
+        })
+    }
+
+    fn to_entry_point<'a, B: Backend>(
+        &'a self,
+        objects: &'a impl ManifestObjects<B>,
+    ) -> Result<EntryPoint<'a, B>, ManifestError> {
+        Ok(EntryPoint {
+            entry: &self.entry,
+            module: objects
+                .shader_module(&self.module)
+                .ok_or_else(|| ManifestError::MissingObject(self.module.clone()))?,
+            specialization: self.specialization.clone(),
+        })
+    }
+}
+
+/// Recorded primitive assembler of a graphics pipeline.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PrimitiveAssemblerRecord {
+    /// Vertex based pipeline.
+    Vertex {
+        /// Vertex buffers (IA).
+        buffers: Vec<VertexBufferDesc>,
+        /// Vertex attributes (IA).
+        attributes: Vec<AttributeDesc>,
+        /// Input assembler attributes, e.g. topology.
+        input_assembler: InputAssemblerDesc,
+        /// A shader that outputs a vertex in a model.
+        vertex: EntryPointRecord,
+        /// Tesselation shaders consisting of:
+        ///
+        /// 1. Hull shader: takes in an input patch (values representing
+        ///    a small portion of a shape, which may be actual geometry or may
+        ///    be parameters for creating geometry) and produces one or more
+        ///    output patches.
+        ///
+        /// 2. Domain shader: takes in domains produced from a hull shader's output
+        ///    patches and computes actual vertex positions.
+        tessellation: Option<(EntryPointRecord, EntryPointRecord)>,
+        /// A shader that takes given input vertexes and outputs zero
+        /// or more output vertexes.
+        geometry: Option<EntryPointRecord>,
+    },
+    /// Mesh shading pipeline.
+    Mesh {
+        /// A shader that creates a variable amount of mesh shader
+        /// invocations.
+        task: Option<EntryPointRecord>,
+        /// A shader of which each workgroup emits zero or
+        /// more output primitives and the group of vertices and their
+        /// associated data required for each output primitive.
+        mesh: EntryPointRecord,
+    },
+}
+
+/// Recorded graphics pipeline.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GraphicsPipelineRecord {
+    /// Pipeline label.
+    pub label: Option<String>,
+    /// Primitive assembler.
+    pub primitive_assembler: PrimitiveAssemblerRecord,
+    /// Rasterizer setup.
+    pub rasterizer: Rasterizer,
+    /// A shader that outputs a value for a fragment.
+    pub fragment: Option<EntryPointRecord>,
+    /// Description of how blend operations should be performed.
+    pub blender: BlendDesc,
+    /// Depth stencil.
+    pub depth_stencil: DepthStencilDesc,
+    /// Multisampling.
+    pub multisampling: Option<Multisampling>,
+    /// Static pipeline states.
+    pub baked_states: BakedStates,
+    /// Key of the pipeline layout.
+    pub layout: String,
+    /// Key of the render pass.
+    pub render_pass: String,
+    /// Index of the subpass in the render pass.
+    pub subpass: SubpassId,
+    /// Options that may be set to alter pipeline properties.
+    pub flags: PipelineCreationFlags,
+    /// Transform feedback.
+    pub transform_feedback: Option<TransformFeedbackDesc>,
+}
+
+impl GraphicsPipelineRecord {
+    /// Record a graphics pipeline description, referencing the objects by their keys.
+    ///
+    /// The parent pipeline isn't recorded.
+    pub fn new<B: Backend>(
+        desc: &GraphicsPipelineDesc<B>,
+        objects: &impl ManifestObjects<B>,
+    ) -> Result<Self, ManifestError> {
+        let primitive_assembler = match desc.primitive_assembler {
+            PrimitiveAssemblerDesc::Vertex {
+                buffers,
+                attributes,
+                ref input_assembler,
+                ref vertex,
+                ref tessellation,
+                ref geometry,
+            } => PrimitiveAssemblerRecord::Vertex {
+                buffers: buffers.to_vec(),
+                attributes: attributes.to_vec(),
+                input_assembler: input_assembler.clone(),
+                vertex: EntryPointRecord::new(vertex, objects)?,
+                tessellation: match *tessellation {
+                    Some((ref hull, ref domain)) => Some((
+                        EntryPointRecord::new(hull, objects)?,
+                        EntryPointRecord::new(domain, objects)?,
+                    )),
+                    None => None,
+                },
+                geometry: geometry
+                    .as_ref()
+                    .map(|geometry| EntryPointRecord::new(geometry, objects))
+                    .transpose()?,
+            },
+            PrimitiveAssemblerDesc::Mesh { ref task, ref mesh } => PrimitiveAssemblerRecord::Mesh {
+                task: task
+                    .as_ref()
+                    .map(|task| EntryPointRecord::new(task, objects))
+                    .transpose()?,
+                mesh: EntryPointRecord::new(mesh, objects)?,
+            },
+        };
+        Ok(GraphicsPipelineRecord {
+            label: desc.label.map(str::to_string),
+            primitive_assembler,
+            rasterizer: desc.rasterizer,
+            fragment: desc
+                .fragment
+                .as_ref()
+                .map(|fragment| EntryPointRecord::new(fragment, objects))
+                .transpose()?,
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            multisampling: desc.multisampling.clone(),
+            baked_states: desc.baked_states.clone(),
+            layout: objects
+                .pipeline_layout_key(desc.layout)
+                .ok_or(ManifestError::UnknownObject)?,
+            render_pass: objects
+                .render_pass_key(desc.subpass.main_pass)
+                .ok_or(ManifestError::UnknownObject)?,
+            subpass: desc.subpass.index,
+            flags: desc.flags,
+            transform_feedback: desc.transform_feedback.clone(),
+        })
+    }
+
+    /// Get the pipeline description, with the objects looked up by their keys.
+    ///
+    /// The description has no parent pipeline.
+    pub fn to_desc<'a, B: Backend>(
+        &'a self,
+        objects: &'a impl ManifestObjects<B>,
+    ) -> Result<GraphicsPipelineDesc<'a, B>, ManifestError> {
+        let primitive_assembler = match self.primitive_assembler {
+            PrimitiveAssemblerRecord::Vertex {
+                ref buffers,
+                ref attributes,
+                ref input_assembler,
+                ref vertex,
+                ref tessellation,
+                ref geometry,
+            } => PrimitiveAssemblerDesc::Vertex {
+                buffers,
+                attributes,
+                input_assembler: input_assembler.clone(),
+                vertex: vertex.to_entry_point(objects)?,
+                tessellation: match *tessellation {
+                    Some((ref hull, ref domain)) => Some((
+                        hull.to_entry_point(objects)?,
+                        domain.to_entry_point(objects)?,
+                    )),
+                    None => None,
+                },
+                geometry: geometry
+                    .as_ref()
+                    .map(|geometry| geometry.to_entry_point(objects))
+                    .transpose()?,
+            },
+            PrimitiveAssemblerRecord::Mesh { ref task, ref mesh } => PrimitiveAssemblerDesc::Mesh {
+                task: task
+                    .as_ref()
+                    .map(|task| task.to_entry_point(objects))
+                    .transpose()?,
+                mesh: mesh.to_entry_point(objects)?,
+            },
+        };
+        Ok(GraphicsPipelineDesc {
+            label: self.label.as_deref(),
+            primitive_assembler,
+            rasterizer: self.rasterizer,
+            fragment: self
+                .fragment
+                .as_ref()
+                .map(|fragment| fragment.to_entry_point(objects))
+                .transpose()?,
+            blender: self.blender.clone(),
+            depth_stencil: self.depth_stencil,
+            multisampling: self.multisampling.clone(),
+            baked_states: self.baked_states.clone(),
+            layout: objects
+                .pipeline_layout(&self.layout)
+                .ok_or_else(|| ManifestError::MissingObject(self.layout.clone()))?,
+            subpass: crate::pass::Subpass {
+                index: self.subpass,
+                main_pass: objects
+                    .render_pass(&self.render_pass)
+                    .ok_or_else(|| ManifestError::MissingObject(self.render_pass.clone()))?,
+            },
+            flags: self.flags,
+            parent: BasePipeline::None,
+            transform_feedback: self.transform_feedback.clone(),
+        })
+    }
+}
+
+/// Recorded compute pipeline.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ComputePipelineRecord {
+    /// Pipeline label.
+    pub label: Option<String>,
+    /// The shader entry point that performs the computation.
+    pub shader: EntryPointRecord,
+    /// Key of the pipeline layout.
+    pub layout: String,
+    /// Any flags necessary for the pipeline creation.
+    pub flags: PipelineCreationFlags,
+}
+
+impl ComputePipelineRecord {
+    /// Record a compute pipeline description, referencing the objects by their keys.
+    ///
+    /// The parent pipeline isn't recorded.
+    pub fn new<B: Backend>(
+        desc: &ComputePipelineDesc<B>,
+        objects: &impl ManifestObjects<B>,
+    ) -> Result<Self, ManifestError> {
+        Ok(ComputePipelineRecord {
+            label: desc.label.map(str::to_string),
+            shader: EntryPointRecord::new(&desc.shader, objects)?,
+            layout: objects
+                .pipeline_layout_key(desc.layout)
+                .ok_or(ManifestError::UnknownObject)?,
+            flags: desc.flags,
+        })
+    }
+
+    /// Get the pipeline description, with the objects looked up by their keys.
+    ///
+    /// The description has no parent pipeline.
+    pub fn to_desc<'a, B: Backend>(
+        &'a self,
+        objects: &'a impl ManifestObjects<B>,
+    ) -> Result<ComputePipelineDesc<'a, B>, ManifestError> {
+        Ok(ComputePipelineDesc {
+            label: self.label.as_deref(),
+            shader: self.shader.to_entry_point(objects)?,
+            layout: objects
+                .pipeline_layout(&self.layout)
+                .ok_or_else(|| ManifestError::MissingObject(self.layout.clone()))?,
+            flags: self.flags,
+            parent: BasePipeline::None,
+        })
+    }
+}
+
+/// Recorded pipeline.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PipelineRecord {
+    /// Graphics pipeline.
+    Graphics(GraphicsPipelineRecord),
+    /// Compute pipeline.
+    Compute(ComputePipelineRecord),
+}
+
+/// Pipeline created from a [`PipelineRecord`].
+#[derive(Debug)]
+pub enum WarmPipeline<B: Backend> {
+    /// Graphics pipeline.
+    Graphics(B::GraphicsPipeline),
+    /// Compute pipeline.
+    Compute(B::ComputePipeline),
+}
+
+/// Set of the pipelines created by an application, keyed by the application.
+///
+/// See the [module documentation][self].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PipelineManifest {
+    /// Recorded pipelines.
+    pub pipelines: BTreeMap<String, PipelineRecord>,
+}
+
+impl PipelineManifest {
+    /// Create an empty manifest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a graphics pipeline under `key`, replacing any pipeline recorded
+    /// under the same key.
+    ///
+    /// The parent pipeline isn't recorded.
+    pub fn record_graphics<B: Backend>(
+        &mut self,
+        key: &str,
+        desc: &GraphicsPipelineDesc<B>,
+        objects: &impl ManifestObjects<B>,
+    ) -> Result<(), ManifestError> {
+        let record = GraphicsPipelineRecord::new(desc, objects)?;
+        self.pipelines
+            .insert(key.to_string(), PipelineRecord::Graphics(record));
+        Ok(())
+    }
+
+    /// Record a compute pipeline under `key`, replacing any pipeline recorded
+    /// under the same key.
+    ///
+    /// The parent pipeline isn't recorded.
+    pub fn record_compute<B: Backend>(
+        &mut self,
+        key: &str,
+        desc: &ComputePipelineDesc<B>,
+        objects: &impl ManifestObjects<B>,
+    ) -> Result<(), ManifestError> {
+        let record = ComputePipelineRecord::new(desc, objects)?;
+        self.pipelines
+            .insert(key.to_string(), PipelineRecord::Compute(record));
+        Ok(())
+    }
+
+    /// Add the pipelines of another manifest, keeping the recorded pipeline when
+    /// both manifests have one under the same key.
+    pub fn merge(&mut self, other: PipelineManifest) {
+        self.merge_with(other, |_, _, _| {})
+    }
+
+    /// Add the pipelines of another manifest.
+    ///
+    /// When both manifests have different pipelines under the same key, `resolve`
+    /// is called with the key, the recorded pipeline and the other one, and can
+    /// update the recorded pipeline.
+    pub fn merge_with<F>(&mut self, other: PipelineManifest, mut resolve: F)
+    where
+        F: FnMut(&str, &mut PipelineRecord, PipelineRecord),
+    {
+        for (key, record) in other.pipelines {
+            match self.pipelines.get_mut(&key) {
+                Some(recorded) => {
+                    if *recorded != record {
+                        resolve(&key, recorded, record);
+                    }
+                }
+                None => {
+                    self.pipelines.insert(key, record);
+                }
+            }
+        }
+    }
+
+    /// Create all the recorded pipelines, in the order of their keys.
+    ///
+    /// The pipelines can be kept for their first use, or destroyed right away
+    /// when the only purpose is to fill the `cache`.
+    ///
+    /// # Safety
+    ///
+    /// The objects must be valid for the creation of the recorded pipelines, see
+    /// [`create_graphics_pipeline`][crate::device::Device::create_graphics_pipeline]
+    /// and [`create_compute_pipeline`][crate::device::Device::create_compute_pipeline].
+    pub unsafe fn warm_up<B: Backend>(
+        &self,
+        device: &B::Device,
+        cache: Option<&B::PipelineCache>,
+        objects: &impl ManifestObjects<B>,
+    ) -> Vec<(String, Result<WarmPipeline<B>, ManifestError>)> {
+        use crate::device::Device;
+
+        self.pipelines
+            .iter()
+            .map(|(key, record)| {
+                let pipeline = match *record {
+                    PipelineRecord::Graphics(ref record) => {
+                        record.to_desc(objects).and_then(|desc| {
+                            device
+                                .create_graphics_pipeline(&desc, cache)
+                                .map(WarmPipeline::Graphics)
+                                .map_err(ManifestError::from)
+                        })
+                    }
+                    PipelineRecord::Compute(ref record) => {
+                        record.to_desc(objects).and_then(|desc| {
+                            device
+                                .create_compute_pipeline(&desc, cache)
+                                .map(WarmPipeline::Compute)
+                                .map_err(ManifestError::from)
+                        })
+                    }
+                };
+                (key.clone(), pipeline)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compute(entry: &str) -> PipelineRecord {
+        PipelineRecord::Compute(ComputePipelineRecord {
+            label: None,
+            shader: EntryPointRecord {
+                entry: entry.to_string(),
+                module: "shaders/blur.spv".to_string(),
+                specialization: Specialization::EMPTY,
+            },
+            layout: "shaders/blur".to_string(),
+            flags: PipelineCreationFlags::empty(),
+        })
+    }
+
+    fn manifest(pipelines: &[(&str, PipelineRecord)]) -> PipelineManifest {
+        PipelineManifest {
+            pipelines: pipelines
+                .iter()
+                .map(|(key, record)| (key.to_string(), record.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn merge_adds_the_missing_pipelines() {
+        let mut recorded = manifest(&[("blur", compute("main"))]);
+        let other = manifest(&[("blur", compute("main")), ("sharpen", compute("main"))]);
+
+        // Identical pipelines aren't conflicts.
+        recorded.merge_with(other.clone(), |key, _, _| {
+            panic!("Unexpected conflict on {}", key)
+        });
+        assert_eq!(recorded, other);
+    }
+
+    #[test]
+    fn merge_resolves_the_conflicts() {
+        let mut recorded = manifest(&[("blur", compute("main")), ("sharpen", compute("main"))]);
+        let other = manifest(&[("blur", compute("main_fast"))]);
+
+        let mut merged = recorded.clone();
+        merged.merge(other.clone());
+        assert_eq!(merged, recorded);
+
+        let mut conflicts = Vec::new();
+        recorded.merge_with(other, |key, recorded, record| {
+            conflicts.push(key.to_string());
+            *recorded = record;
+        });
+        assert_eq!(conflicts, ["blur"]);
+        assert_eq!(recorded.pipelines["blur"], compute("main_fast"));
+        assert_eq!(recorded.pipelines["sharpen"], compute("main"));
+    }
+}
@@ -2,17 +2,21 @@
 //!
 //! This module contains items used to create and manage Pipelines.
 
-use crate::{device, pass, Backend};
+use crate::{device, pass, Backend, DynamicStates};
 
 mod compute;
 mod descriptor;
 mod graphics;
 mod input_assembler;
+mod manifest;
 mod output_merger;
+mod permutation;
+mod ray_tracing;
 mod specialization;
 
 pub use self::{
-    compute::*, descriptor::*, graphics::*, input_assembler::*, output_merger::*, specialization::*,
+    compute::*, descriptor::*, graphics::*, input_assembler::*, manifest::*, output_merger::*,
+    permutation::*, ray_tracing::*, specialization::*,
 };
 
 /// Error types happening upon PSO creation on the device side.
@@ -36,6 +40,29 @@ pub enum CreationError {
     /// The specialization values are incorrect.
     #[error("Specialization failed: {0:}")]
     InvalidSpecialization(String),
+    /// Some of the dynamic states are not supported by the device.
+    #[error("Unsupported dynamic states: {0:?}")]
+    UnsupportedDynamicStates(DynamicStates),
+    /// The subpass or the blend targets have more color attachments than
+    /// [`max_color_attachments`][crate::Limits::max_color_attachments].
+    #[error("{count} color attachments exceed the limit of {limit}")]
+    TooManyColorAttachments {
+        /// Number of color attachments of the pipeline.
+        count: usize,
+        /// Maximum number of color attachments of the device.
+        limit: usize,
+    },
+    /// The pipeline layout has more descriptors of a type than
+    /// [`descriptor_limits`][crate::Limits::descriptor_limits] allow.
+    #[error("{count} {ty:?} descriptors exceed the limit of {limit}")]
+    TooManyDescriptors {
+        /// Type of the descriptors.
+        ty: DescriptorType,
+        /// Number of descriptors of the pipeline layout.
+        count: usize,
+        /// Maximum number of descriptors of the device.
+        limit: usize,
+    },
     /// Out of either host or device memory.
     #[error(transparent)]
     OutOfMemory(#[from] device::OutOfMemory),
@@ -83,6 +110,12 @@ bitflags!(
         const TASK_SHADER = 0x80000;
         /// Mesh shader stage.
         const MESH_SHADER = 0x100000;
+        /// Ray tracing shader execution.
+        const RAY_TRACING_SHADER = 0x200000;
+        /// Acceleration structure build and copy command execution.
+        const ACCELERATION_STRUCTURE_BUILD = 0x2000000;
+        /// Shading rate attachment consumption.
+        const SHADING_RATE_ATTACHMENT = 0x400000;
     }
 );
 
@@ -107,6 +140,21 @@ bitflags!(
         const TASK     = 0x40;
         /// Mesh shader stage.
         const MESH     = 0x80;
+        /// Ray generation shader stage.
+        const RAYGEN   = 0x100;
+        /// Any-hit shader stage.
+        const ANY_HIT  = 0x200;
+        /// Closest hit shader stage.
+        const CLOSEST_HIT = 0x400;
+        /// Miss shader stage.
+        const MISS     = 0x800;
+        /// Intersection shader stage.
+        const INTERSECTION = 0x1000;
+        /// Callable shader stage.
+        const CALLABLE = 0x2000;
+        /// All ray tracing pipeline shader stages.
+        const RAY_TRACING = Self::RAYGEN.bits | Self::ANY_HIT.bits | Self::CLOSEST_HIT.bits |
+            Self::MISS.bits | Self::INTERSECTION.bits | Self::CALLABLE.bits;
         /// All graphics pipeline shader stages.
         const GRAPHICS = Self::VERTEX.bits | Self::HULL.bits |
             Self::DOMAIN.bits | Self::GEOMETRY.bits | Self::FRAGMENT.bits;
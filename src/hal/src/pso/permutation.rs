@@ -0,0 +1,475 @@
+//! Permutations of the pipelines of a material.
+//!
+//! A material usually comes with a set of switches, e.g. whether it uses a normal
+//! map or how many lights it supports, each combination of the values requiring its
+//! own pipeline. Creating all of them upfront is rarely an option, as their number
+//! grows exponentially with the switches.
+//!
+//! A [`PermutationManager`] declares the [switches][Switch] of a material, and only
+//! creates the pipeline of a [permutation][PermutationKey] when it's first requested.
+//! The switches are handed to the creation as a [`Variant`], exposing them both as
+//! preprocessor defines, for shaders compiled per permutation, and as
+//! [specialization constants][Specialization], for shaders compiled once.
+//!
+//! The number of pipelines kept alive can be bounded, in which case the least
+//! recently used one is evicted to make room for a new permutation. The evicted
+//! pipelines are handed to [`Frames::retire`], as the command buffers in flight
+//! may still use them.
+
+use crate::{
+    frame::{Frames, Retired},
+    pso::{CreationError, Specialization, SpecializationConstant},
+    Backend,
+};
+
+use std::{borrow::Cow, collections::HashMap, fmt};
+
+/// Value of a permutation switch.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SwitchValue {
+    /// Boolean switch, e.g. enabling a feature of the material.
+    Bool(bool),
+    /// Integer switch, e.g. a number of lights.
+    Int(i32),
+}
+
+impl SwitchValue {
+    /// Value as a preprocessor define, `1` or `0` for the boolean switches.
+    pub fn define(&self) -> String {
+        match *self {
+            SwitchValue::Bool(value) => (value as u32).to_string(),
+            SwitchValue::Int(value) => value.to_string(),
+        }
+    }
+
+    /// Value as specialization constant data.
+    ///
+    /// The boolean switches are 32 bits wide, matching the booleans in SPIR-V.
+    pub fn constant_data(&self) -> [u8; 4] {
+        match *self {
+            SwitchValue::Bool(value) => (value as u32).to_ne_bytes(),
+            SwitchValue::Int(value) => value.to_ne_bytes(),
+        }
+    }
+
+    fn same_kind(&self, other: &Self) -> bool {
+        matches!(
+            (*self, *other),
+            (SwitchValue::Bool(_), SwitchValue::Bool(_))
+                | (SwitchValue::Int(_), SwitchValue::Int(_))
+        )
+    }
+}
+
+/// Declaration of a permutation switch.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Switch {
+    /// Name of the switch, also used as the name of its define.
+    pub name: String,
+    /// Identifier of the specialization constant fed with the switch, if any.
+    pub constant_id: Option<u32>,
+    /// Value of the switch when a permutation doesn't set it.
+    pub default: SwitchValue,
+}
+
+impl Switch {
+    /// Declare a boolean switch.
+    pub fn bool(name: impl Into<String>, default: bool) -> Self {
+        Switch {
+            name: name.into(),
+            constant_id: None,
+            default: SwitchValue::Bool(default),
+        }
+    }
+
+    /// Declare an integer switch.
+    pub fn int(name: impl Into<String>, default: i32) -> Self {
+        Switch {
+            name: name.into(),
+            constant_id: None,
+            default: SwitchValue::Int(default),
+        }
+    }
+
+    /// Feed the switch to the specialization constant `id`.
+    pub fn with_constant_id(mut self, id: u32) -> Self {
+        self.constant_id = Some(id);
+        self
+    }
+}
+
+/// Error occurring when building a permutation key.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum PermutationError {
+    /// The switch isn't declared by the manager.
+    #[error("Unknown switch `{0}`")]
+    UnknownSwitch(String),
+    /// The value doesn't match the kind of the switch.
+    #[error("Value {1:?} doesn't match the kind of the switch `{0}`")]
+    KindMismatch(String, SwitchValue),
+}
+
+/// Values of all the switches of a [`PermutationManager`], in declaration order.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PermutationKey(Vec<SwitchValue>);
+
+impl PermutationKey {
+    fn new<'a, I>(switches: &[Switch], values: I) -> Result<Self, PermutationError>
+    where
+        I: IntoIterator<Item = (&'a str, SwitchValue)>,
+    {
+        let mut key = switches
+            .iter()
+            .map(|switch| switch.default)
+            .collect::<Vec<_>>();
+        for (name, value) in values {
+            let index = switches
+                .iter()
+                .position(|switch| switch.name == name)
+                .ok_or_else(|| PermutationError::UnknownSwitch(name.to_string()))?;
+            if !switches[index].default.same_kind(&value) {
+                return Err(PermutationError::KindMismatch(name.to_string(), value));
+            }
+            key[index] = value;
+        }
+        Ok(PermutationKey(key))
+    }
+
+    /// Values of the switches, in declaration order.
+    pub fn values(&self) -> &[SwitchValue] {
+        &self.0
+    }
+}
+
+/// Permutation being created, handed to the creation function of the pipeline.
+#[derive(Clone, Copy, Debug)]
+pub struct Variant<'a> {
+    switches: &'a [Switch],
+    key: &'a PermutationKey,
+}
+
+impl<'a> Variant<'a> {
+    /// Key of the permutation.
+    pub fn key(&self) -> &'a PermutationKey {
+        self.key
+    }
+
+    /// Value of a switch.
+    pub fn value(&self, name: &str) -> Option<SwitchValue> {
+        self.switches
+            .iter()
+            .position(|switch| switch.name == name)
+            .map(|index| self.key.0[index])
+    }
+
+    /// Switches as preprocessor defines, as pairs of names and values.
+    pub fn defines(&self) -> impl Iterator<Item = (&'a str, String)> {
+        self.switches
+            .iter()
+            .zip(&self.key.0)
+            .map(|(switch, value)| (switch.name.as_str(), value.define()))
+    }
+
+    /// Specialization feeding the switches with a constant identifier.
+    pub fn specialization(&self) -> Specialization<'static> {
+        let mut constants = Vec::new();
+        let mut data = Vec::new();
+        for (switch, value) in self.switches.iter().zip(&self.key.0) {
+            if let Some(id) = switch.constant_id {
+                let offset = data.len() as u16;
+                data.extend_from_slice(&value.constant_data());
+                constants.push(SpecializationConstant {
+                    id,
+                    range: offset..data.len() as u16,
+                });
+            }
+        }
+        Specialization {
+            constants: Cow::Owned(constants),
+            data: Cow::Owned(data),
+        }
+    }
+}
+
+/// Statistics of a [`PermutationManager`].
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PermutationStats {
+    /// Number of requests served by an existing pipeline.
+    pub hits: u64,
+    /// Number of pipelines created.
+    pub creations: u64,
+    /// Number of pipeline creations that failed.
+    pub failures: u64,
+    /// Number of pipelines evicted to make room for new permutations.
+    pub evictions: u64,
+}
+
+#[derive(Debug)]
+struct Entry<P> {
+    pipeline: P,
+    last_used: u64,
+}
+
+/// Lazily created pipelines of the permutations of a material.
+///
+/// `P` is either `B::GraphicsPipeline` or `B::ComputePipeline`, see
+/// [`PermutationManager::graphics`] and [`PermutationManager::compute`].
+///
+/// See the [module documentation][self].
+pub struct PermutationManager<B: Backend, P> {
+    switches: Vec<Switch>,
+    capacity: Option<usize>,
+    pipelines: HashMap<PermutationKey, Entry<P>>,
+    retire: fn(P) -> Retired<B>,
+    tick: u64,
+    stats: PermutationStats,
+}
+
+impl<B: Backend, P: fmt::Debug> fmt::Debug for PermutationManager<B, P> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PermutationManager")
+            .field("switches", &self.switches)
+            .field("capacity", &self.capacity)
+            .field("pipelines", &self.pipelines)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<B: Backend> PermutationManager<B, B::GraphicsPipeline> {
+    /// Create a manager of graphics pipelines with the given switches.
+    pub fn graphics(switches: Vec<Switch>) -> Self {
+        Self::new(switches, Retired::GraphicsPipeline)
+    }
+}
+
+impl<B: Backend> PermutationManager<B, B::ComputePipeline> {
+    /// Create a manager of compute pipelines with the given switches.
+    pub fn compute(switches: Vec<Switch>) -> Self {
+        Self::new(switches, Retired::ComputePipeline)
+    }
+}
+
+impl<B: Backend, P> PermutationManager<B, P> {
+    fn new(switches: Vec<Switch>, retire: fn(P) -> Retired<B>) -> Self {
+        PermutationManager {
+            switches,
+            capacity: None,
+            pipelines: HashMap::new(),
+            retire,
+            tick: 0,
+            stats: PermutationStats::default(),
+        }
+    }
+
+    /// Bound the number of pipelines kept alive, at least one.
+    ///
+    /// The least recently used pipelines are only evicted when creating new ones.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Declared switches.
+    pub fn switches(&self) -> &[Switch] {
+        &self.switches
+    }
+
+    /// Statistics since the creation of the manager.
+    pub fn stats(&self) -> PermutationStats {
+        self.stats
+    }
+
+    /// Number of pipelines alive.
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Check if no pipeline is alive.
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Build the key of a permutation from the values of some switches, the
+    /// other switches taking their default value.
+    pub fn key<'a, I>(&self, values: I) -> Result<PermutationKey, PermutationError>
+    where
+        I: IntoIterator<Item = (&'a str, SwitchValue)>,
+    {
+        PermutationKey::new(&self.switches, values)
+    }
+
+    /// Get the pipeline of a permutation, creating it with `create` if it's not alive.
+    ///
+    /// If the manager is full, the least recently used pipeline is retired into `frames`
+    /// once the new one has been created.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key wasn't built by this manager.
+    pub fn get_or_create<F>(
+        &mut self,
+        key: &PermutationKey,
+        frames: &mut Frames<B>,
+        create: F,
+    ) -> Result<&P, CreationError>
+    where
+        F: FnOnce(Variant) -> Result<P, CreationError>,
+    {
+        assert_eq!(key.0.len(), self.switches.len());
+        self.tick += 1;
+
+        if self.pipelines.contains_key(key) {
+            self.stats.hits += 1;
+        } else {
+            let variant = Variant {
+                switches: &self.switches,
+                key,
+            };
+            let pipeline = match create(variant) {
+                Ok(pipeline) => pipeline,
+                Err(err) => {
+                    self.stats.failures += 1;
+                    return Err(err);
+                }
+            };
+            self.stats.creations += 1;
+
+            if let Some(capacity) = self.capacity {
+                while self.pipelines.len() >= capacity {
+                    self.evict_lru(frames);
+                }
+            }
+            self.pipelines.insert(
+                key.clone(),
+                Entry {
+                    pipeline,
+                    last_used: 0,
+                },
+            );
+        }
+
+        let entry = self.pipelines.get_mut(key).unwrap();
+        entry.last_used = self.tick;
+        Ok(&entry.pipeline)
+    }
+
+    /// Get the pipeline of a permutation if it's alive, without creating it.
+    pub fn get(&mut self, key: &PermutationKey) -> Option<&P> {
+        let tick = self.tick + 1;
+        let entry = self.pipelines.get_mut(key)?;
+        self.tick = tick;
+        self.stats.hits += 1;
+        entry.last_used = tick;
+        Some(&entry.pipeline)
+    }
+
+    /// Retire the pipeline of a permutation into `frames`, if it's alive.
+    pub fn evict(&mut self, key: &PermutationKey, frames: &mut Frames<B>) -> bool {
+        match self.pipelines.remove(key) {
+            Some(entry) => {
+                frames.retire((self.retire)(entry.pipeline));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Retire all the pipelines into `frames`, e.g. after reloading the shaders.
+    pub fn clear(&mut self, frames: &mut Frames<B>) {
+        for (_, entry) in self.pipelines.drain() {
+            frames.retire((self.retire)(entry.pipeline));
+        }
+    }
+
+    fn evict_lru(&mut self, frames: &mut Frames<B>) {
+        let key = self
+            .pipelines
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = key {
+            self.evict(&key, frames);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switches() -> Vec<Switch> {
+        vec![
+            Switch::bool("NORMAL_MAP", false).with_constant_id(0),
+            Switch::int("LIGHTS", 4),
+            Switch::int("SHADOWS", 1).with_constant_id(7),
+        ]
+    }
+
+    #[test]
+    fn keys_default_the_missing_switches() {
+        let switches = switches();
+        let key = PermutationKey::new(&switches, vec![("LIGHTS", SwitchValue::Int(8))]).unwrap();
+        assert_eq!(
+            key.values(),
+            [
+                SwitchValue::Bool(false),
+                SwitchValue::Int(8),
+                SwitchValue::Int(1)
+            ]
+        );
+
+        assert_eq!(
+            PermutationKey::new(&switches, vec![("FOG", SwitchValue::Bool(true))]),
+            Err(PermutationError::UnknownSwitch("FOG".to_string()))
+        );
+        assert_eq!(
+            PermutationKey::new(&switches, vec![("LIGHTS", SwitchValue::Bool(true))]),
+            Err(PermutationError::KindMismatch(
+                "LIGHTS".to_string(),
+                SwitchValue::Bool(true)
+            ))
+        );
+    }
+
+    #[test]
+    fn variants_expose_the_switches() {
+        let switches = switches();
+        let key =
+            PermutationKey::new(&switches, vec![("NORMAL_MAP", SwitchValue::Bool(true))]).unwrap();
+        let variant = Variant {
+            switches: &switches,
+            key: &key,
+        };
+
+        assert_eq!(variant.value("LIGHTS"), Some(SwitchValue::Int(4)));
+        assert_eq!(variant.value("FOG"), None);
+        assert_eq!(
+            variant.defines().collect::<Vec<_>>(),
+            [
+                ("NORMAL_MAP", "1".to_string()),
+                ("LIGHTS", "4".to_string()),
+                ("SHADOWS", "1".to_string())
+            ]
+        );
+
+        // Only the switches with a constant identifier are specialized.
+        let specialization = variant.specialization();
+        assert_eq!(
+            specialization.constants[..],
+            [
+                SpecializationConstant { id: 0, range: 0..4 },
+                SpecializationConstant { id: 7, range: 4..8 },
+            ]
+        );
+        assert_eq!(
+            specialization.data[..],
+            [1u32.to_ne_bytes(), 1i32.to_ne_bytes()].concat()[..]
+        );
+    }
+}
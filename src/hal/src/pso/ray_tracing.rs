@@ -0,0 +1,80 @@
+//! Ray tracing pipeline descriptor.
+
+use crate::{buffer, pso::EntryPoint, Backend};
+
+/// Group of shaders of a ray tracing pipeline, selected as a whole by the records
+/// of a [shader binding table][ShaderBindingTable].
+#[derive(Debug)]
+pub enum ShaderGroup<'a, B: Backend> {
+    /// Shader generating the rays.
+    RayGeneration(EntryPoint<'a, B>),
+    /// Shader invoked when a ray doesn't hit anything.
+    Miss(EntryPoint<'a, B>),
+    /// Shader invoked from another shader, with `executeCallable`.
+    Callable(EntryPoint<'a, B>),
+    /// Shaders invoked when a ray hits a triangle.
+    TrianglesHitGroup {
+        /// Shader invoked for the closest hit of the ray.
+        closest_hit: Option<EntryPoint<'a, B>>,
+        /// Shader invoked for every candidate hit of the ray.
+        any_hit: Option<EntryPoint<'a, B>>,
+    },
+    /// Shaders invoked when a ray hits a bounding box.
+    ProceduralHitGroup {
+        /// Shader testing the intersection of the ray with the primitive in the box.
+        intersection: EntryPoint<'a, B>,
+        /// Shader invoked for the closest hit of the ray.
+        closest_hit: Option<EntryPoint<'a, B>>,
+        /// Shader invoked for every candidate hit of the ray.
+        any_hit: Option<EntryPoint<'a, B>>,
+    },
+}
+
+/// A description of the data needed to construct a ray tracing pipeline.
+///
+/// Requires `Features::RAY_TRACING_PIPELINE`.
+#[derive(Debug)]
+pub struct RayTracingPipelineDesc<'a, B: Backend> {
+    /// Pipeline label
+    pub label: Option<&'a str>,
+    /// Shader groups of the pipeline. Their handles, given by
+    /// [`Device::get_ray_tracing_shader_group_handles`][crate::device::Device::get_ray_tracing_shader_group_handles],
+    /// are in the same order.
+    pub groups: &'a [ShaderGroup<'a, B>],
+    /// Maximum depth of recursive `traceRay` calls, at most
+    /// `RayTracingProperties::max_ray_recursion_depth`.
+    pub max_recursion_depth: u32,
+    /// Maximum size of the ray payloads of the shaders, in bytes.
+    pub max_payload_size: u32,
+    /// Maximum size of the hit attributes of the shaders, in bytes.
+    pub max_attribute_size: u32,
+    /// Pipeline layout.
+    pub layout: &'a B::PipelineLayout,
+}
+
+/// Region of a buffer holding shader records, each starting with the handle of
+/// a shader group.
+#[derive(Debug)]
+pub struct ShaderBindingTable<'a, B: Backend> {
+    /// Buffer holding the records.
+    pub buffer: &'a B::Buffer,
+    /// Offset of the first record, aligned to `RayTracingProperties::shader_group_base_alignment`.
+    pub offset: buffer::Offset,
+    /// Distance between two records, aligned to `RayTracingProperties::shader_group_handle_alignment`.
+    pub stride: buffer::Stride,
+    /// Size of the region, in bytes.
+    pub size: buffer::Offset,
+}
+
+/// Shader binding tables used by `trace_rays`.
+#[derive(Debug)]
+pub struct ShaderBindingTables<'a, B: Backend> {
+    /// Record of the ray generation shader group. Only the first record is used.
+    pub ray_generation: ShaderBindingTable<'a, B>,
+    /// Records of the miss shader groups.
+    pub miss: Option<ShaderBindingTable<'a, B>>,
+    /// Records of the hit groups.
+    pub hit: Option<ShaderBindingTable<'a, B>>,
+    /// Records of the callable shader groups.
+    pub callable: Option<ShaderBindingTable<'a, B>>,
+}
@@ -4,6 +4,7 @@ use std::{borrow::Cow, ops::Range, slice};
 
 /// Description of a specialization constant for the pipeline.
 #[derive(Debug, Clone, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SpecializationConstant {
     /// Constant identifier in shader source.
     pub id: u32,
@@ -22,7 +23,8 @@ pub struct SpecializationConstant {
 /// but they share most of the internal stuff and are fast to produce.
 /// More importantly, they are fast to execute, since the driver
 /// can optimize out the branch on that other PSO creation.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Specialization<'a> {
     /// Array of descriptors of specialization constants to override.
     pub constants: Cow<'a, [SpecializationConstant]>,
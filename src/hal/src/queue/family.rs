@@ -33,6 +33,9 @@ pub struct QueueFamilyId(pub usize);
 pub struct QueueGroup<B: Backend> {
     /// Family index for the queues in this group.
     pub family: QueueFamilyId,
+    /// Index of the [node][crate::adapter::PhysicalDevice::node_count] of the device
+    /// executing the queues.
+    pub node: u32,
     /// List of queues.
     pub queues: Vec<B::Queue>,
 }
@@ -42,6 +45,7 @@ impl<B: Backend> QueueGroup<B> {
     pub fn new(family: QueueFamilyId) -> Self {
         QueueGroup {
             family,
+            node: 0,
             queues: Vec::new(),
         }
     }
@@ -7,18 +7,52 @@
 //! `Queue<B, C>` has the capability defined by `C`: graphics, compute and transfer.
 
 pub mod family;
+pub mod throttle;
 
 use crate::{
-    device::OutOfMemory,
+    device::OomOrDeviceLost,
     pso,
     window::{PresentError, PresentationSurface, Suboptimal},
     Backend,
 };
-use std::{any::Any, fmt};
+use std::{
+    any::Any,
+    fmt,
+    time::{Duration, Instant},
+};
 
 pub use self::family::{QueueFamily, QueueFamilyId, QueueGroup};
+pub use self::throttle::TransferThrottle;
 use crate::memory::{SparseBind, SparseImageBind};
 
+/// Value of the timestamp counter of a queue, and the time of the host at which
+/// the counter had this value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimestampCalibration {
+    /// Timestamp, in the same units as the results of timestamp queries.
+    pub gpu_timestamp: u64,
+    /// Time of the host matching the timestamp.
+    pub cpu_instant: Instant,
+}
+
+impl TimestampCalibration {
+    /// Convert a timestamp of the queue into a time of the host, given the
+    /// [timestamp period][Queue::timestamp_period] of the queue.
+    ///
+    /// Returns `None` if the time can't be represented by an `Instant`.
+    pub fn instant(&self, timestamp: u64, timestamp_period: f32) -> Option<Instant> {
+        let to_duration =
+            |ticks: u64| Duration::from_nanos((ticks as f64 * timestamp_period as f64) as u64);
+        if timestamp >= self.gpu_timestamp {
+            self.cpu_instant
+                .checked_add(to_duration(timestamp - self.gpu_timestamp))
+        } else {
+            self.cpu_instant
+                .checked_sub(to_duration(self.gpu_timestamp - timestamp))
+        }
+    }
+}
+
 /// The type of the queue, an enum encompassing `queue::Capability`
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -112,6 +146,9 @@ pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// * `fence` - must be in unsignaled state, and will be signaled after
     ///   all command buffers in the submission have finished execution.
     ///
+    /// Returns an error if the device ran out of memory or was lost, in which
+    /// case the fence and the semaphores are not signaled.
+    ///
     /// # Safety
     ///
     /// It's not checked that the queue can process the submitted command buffers.
@@ -124,7 +161,8 @@ pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
         wait_semaphores: Iw,
         signal_semaphores: Is,
         fence: Option<&mut B::Fence>,
-    ) where
+    ) -> Result<(), OomOrDeviceLost>
+    where
         Ic: Iterator<Item = &'a B::CommandBuffer>,
         Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage)>,
         Is: Iterator<Item = &'a B::Semaphore>;
@@ -143,8 +181,29 @@ pub trait Queue<B: Backend>: fmt::Debug + Any + Send + Sync {
     ) -> Result<Option<Suboptimal>, PresentError>;
 
     /// Wait for the queue to be idle.
-    fn wait_idle(&mut self) -> Result<(), OutOfMemory>;
+    fn wait_idle(&mut self) -> Result<(), OomOrDeviceLost>;
 
     /// The amount of nanoseconds that causes a timestamp query value to increment by one.
     fn timestamp_period(&self) -> f32;
+
+    /// Sample the timestamp counter of the queue together with the clock of the host,
+    /// to place the results of timestamp queries on the timeline of the host.
+    ///
+    /// The two clocks drift apart over time, so the calibration should be renewed
+    /// periodically. Returns `None` if the backend can't sample them together.
+    fn timestamp_calibration(&self) -> Option<TimestampCalibration> {
+        None
+    }
+
+    /// Capture the next `frames` frames with a frame debugger, such as RenderDoc,
+    /// if one is attached.
+    ///
+    /// The capture starts immediately, and each captured frame is ended by a
+    /// [`present`][Queue::present] on this queue. Passing `0` ends the capture
+    /// in progress.
+    ///
+    /// This is ignored by the backends without programmatic captures.
+    fn capture_next_frames(&mut self, frames: u32) {
+        let _ = frames;
+    }
 }
@@ -0,0 +1,192 @@
+//! Artificial throttling of the transfer submissions.
+//!
+//! Streaming code has to cope with transfers that take longer than a frame, e.g.
+//! on a slow PCIe link, but development machines rarely exhibit this. Submitting
+//! the transfers through a [`TransferThrottle`] holds the submitting thread for the
+//! time their bytes would take to cross a bus of the configured bandwidth, so the
+//! fallbacks of the streaming code can be exercised in tests and during development.
+//!
+//! The throttle blocks the calling thread after each submission, so the GPU
+//! doesn't wait for it, and is meant for the queues used by background
+//! transfers, not for the submissions of the render loop.
+
+use crate::{device::OomOrDeviceLost, pso, queue::Queue, Backend};
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Throttle limiting the bandwidth of the transfer submissions.
+#[derive(Debug)]
+pub struct TransferThrottle {
+    bytes_per_second: Option<u64>,
+    /// Time at which the previously submitted transfers are considered complete.
+    deadline: Option<Instant>,
+}
+
+impl Default for TransferThrottle {
+    fn default() -> Self {
+        TransferThrottle::unlimited()
+    }
+}
+
+impl TransferThrottle {
+    /// Create a throttle limiting the transfers to `bytes_per_second`.
+    pub fn new(bytes_per_second: u64) -> Self {
+        assert_ne!(bytes_per_second, 0);
+        TransferThrottle {
+            bytes_per_second: Some(bytes_per_second),
+            deadline: None,
+        }
+    }
+
+    /// Create a throttle that doesn't delay the submissions.
+    pub fn unlimited() -> Self {
+        TransferThrottle {
+            bytes_per_second: None,
+            deadline: None,
+        }
+    }
+
+    /// Bandwidth of the simulated bus, or `None` if unlimited.
+    pub fn bytes_per_second(&self) -> Option<u64> {
+        self.bytes_per_second
+    }
+
+    /// Change the bandwidth of the simulated bus, `None` disabling the throttle.
+    pub fn set_bytes_per_second(&mut self, bytes_per_second: Option<u64>) {
+        assert_ne!(bytes_per_second, Some(0));
+        self.bytes_per_second = bytes_per_second;
+        self.deadline = None;
+    }
+
+    /// Block until `bytes` more could have been transferred at the configured
+    /// bandwidth, accounting for the transfers throttled previously.
+    pub fn wait(&mut self, bytes: u64) {
+        if let Some(deadline) = self.schedule(bytes) {
+            sleep_until(deadline);
+        }
+    }
+
+    /// Queue the transfer of `bytes` behind the previous ones, returning the
+    /// time at which it's over, or `None` if the throttle is disabled.
+    ///
+    /// Transfers that would end beyond the range of the clock end about a
+    /// century later instead.
+    fn schedule(&mut self, bytes: u64) -> Option<Instant> {
+        let bytes_per_second = self.bytes_per_second?;
+        let nanos = (bytes % bytes_per_second) as u128 * 1_000_000_000 / bytes_per_second as u128;
+        let duration = Duration::new(bytes / bytes_per_second, nanos as u32);
+        let now = Instant::now();
+        // The bus is idle if the previous transfers are over.
+        let start = match self.deadline {
+            Some(deadline) if deadline > now => deadline,
+            _ => now,
+        };
+        let deadline = start
+            .checked_add(duration)
+            .or_else(|| start.checked_add(Duration::from_secs(u32::max_value() as u64)))
+            .unwrap_or(start);
+        self.deadline = Some(deadline);
+        Some(deadline)
+    }
+
+    /// Submit command buffers transferring `bytes` to the queue, once the
+    /// throttle allows it.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Queue::submit`].
+    pub unsafe fn submit<'a, B, Ic, Iw, Is>(
+        &mut self,
+        queue: &mut B::Queue,
+        bytes: u64,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut B::Fence>,
+    ) -> Result<(), OomOrDeviceLost>
+    where
+        B: Backend,
+        Ic: Iterator<Item = &'a B::CommandBuffer>,
+        Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage)>,
+        Is: Iterator<Item = &'a B::Semaphore>,
+    {
+        let deadline = self.schedule(bytes);
+        queue.submit(command_buffers, wait_semaphores, signal_semaphores, fence)?;
+        if let Some(deadline) = deadline {
+            sleep_until(deadline);
+        }
+        Ok(())
+    }
+}
+
+/// Sleep until `deadline`, which may already be over.
+fn sleep_until(deadline: Instant) {
+    if let Some(delay) = deadline.checked_duration_since(Instant::now()) {
+        thread::sleep(delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timed(f: impl FnOnce()) -> Duration {
+        let start = Instant::now();
+        f();
+        start.elapsed()
+    }
+
+    #[test]
+    fn unlimited_throttle_doesnt_wait() {
+        let mut throttle = TransferThrottle::unlimited();
+        assert!(timed(|| throttle.wait(u64::MAX)) < Duration::from_millis(100));
+        assert_eq!(throttle.bytes_per_second(), None);
+    }
+
+    #[test]
+    fn throttle_waits_for_the_bytes_to_transfer() {
+        let mut throttle = TransferThrottle::new(1000);
+        assert!(timed(|| throttle.wait(50)) >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn throttle_queues_transfers_behind_pending_ones() {
+        let mut throttle = TransferThrottle::new(1000);
+        let elapsed = timed(|| {
+            throttle.wait(30);
+            throttle.wait(30);
+        });
+        assert!(elapsed >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn throttle_doesnt_count_the_idle_time() {
+        let mut throttle = TransferThrottle::new(1000);
+        throttle.wait(20);
+        thread::sleep(Duration::from_millis(40));
+        // The bus has been idle, so the time slept doesn't make up for new transfers.
+        assert!(timed(|| throttle.wait(20)) >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn throttle_schedules_huge_transfers() {
+        let mut throttle = TransferThrottle::new(1);
+        let now = Instant::now();
+        let first = throttle.schedule(u64::MAX).unwrap();
+        assert!(first > now + Duration::from_secs(3600));
+        assert!(throttle.schedule(u64::MAX).unwrap() >= first);
+    }
+
+    #[test]
+    fn throttle_is_reset_when_reconfigured() {
+        let mut throttle = TransferThrottle::new(1);
+        throttle.deadline = Some(Instant::now() + Duration::from_secs(3600));
+        throttle.set_bytes_per_second(Some(1000));
+        assert!(timed(|| throttle.wait(10)) < Duration::from_secs(1));
+        throttle.set_bytes_per_second(None);
+        assert!(timed(|| throttle.wait(u64::MAX)) < Duration::from_secs(1));
+    }
+}
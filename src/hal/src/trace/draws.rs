@@ -0,0 +1,130 @@
+use super::{Command, Handle, Object};
+use crate::{
+    pso::{AttributeDesc, Primitive, PrimitiveAssemblerRecord, VertexBufferDesc},
+    IndexType,
+};
+
+/// Kind of a traced draw.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawKind {
+    /// Non-indexed draw, direct or indirect.
+    Vertex,
+    /// Indexed draw, direct or indirect.
+    Indexed,
+    /// Draw of the vertices captured by transform feedback.
+    TransformFeedback,
+    /// Mesh shading draw, direct or indirect.
+    MeshTasks,
+}
+
+/// Details of a traced draw, gathered from the state bound when it's recorded.
+///
+/// This is meant for tools displaying per-draw information, e.g. overlays,
+/// without looking into the state of the backends.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawInfo<'a> {
+    /// Index of the draw in the traced commands.
+    pub command: usize,
+    /// Kind of the draw.
+    pub kind: DrawKind,
+    /// Handle of the bound graphics pipeline, if any.
+    pub pipeline: Option<Handle>,
+    /// Label of the bound graphics pipeline.
+    pub label: Option<&'a str>,
+    /// Topology of the primitives, unless the pipeline uses mesh shading.
+    pub primitive: Option<Primitive>,
+    /// Vertex buffers layouts of the pipeline.
+    pub vertex_buffers: &'a [VertexBufferDesc],
+    /// Vertex attributes of the pipeline, with their formats.
+    pub attributes: &'a [AttributeDesc],
+    /// Type of the bound index buffer, for indexed draws.
+    pub index_type: Option<IndexType>,
+}
+
+/// Iterator over the draws of traced commands.
+///
+/// Returned by [`Trace::draws`][super::Trace::draws] and
+/// [`Tracer::draws`][super::Tracer::draws].
+#[derive(Debug)]
+pub struct Draws<'a> {
+    objects: &'a [Object],
+    commands: std::iter::Enumerate<std::slice::Iter<'a, Command>>,
+    pipeline: Option<Handle>,
+    index_type: Option<IndexType>,
+}
+
+impl<'a> Draws<'a> {
+    pub(super) fn new(objects: &'a [Object], commands: &'a [Command]) -> Self {
+        Draws {
+            objects,
+            commands: commands.iter().enumerate(),
+            pipeline: None,
+            index_type: None,
+        }
+    }
+
+    fn info(&self, command: usize, kind: DrawKind) -> DrawInfo<'a> {
+        let mut info = DrawInfo {
+            command,
+            kind,
+            pipeline: self.pipeline,
+            label: None,
+            primitive: None,
+            vertex_buffers: &[],
+            attributes: &[],
+            index_type: match kind {
+                DrawKind::Indexed => self.index_type,
+                _ => None,
+            },
+        };
+        let record = match self.pipeline.and_then(|h| self.objects.get(h as usize)) {
+            Some(Object::GraphicsPipeline(record)) => record,
+            _ => return info,
+        };
+        info.label = record.label.as_deref();
+        if let PrimitiveAssemblerRecord::Vertex {
+            ref buffers,
+            ref attributes,
+            ref input_assembler,
+            ..
+        } = record.primitive_assembler
+        {
+            info.primitive = Some(input_assembler.primitive);
+            info.vertex_buffers = buffers;
+            info.attributes = attributes;
+        }
+        info
+    }
+}
+
+impl<'a> Iterator for Draws<'a> {
+    type Item = DrawInfo<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((index, command)) = self.commands.next() {
+            let kind = match *command {
+                Command::BindGraphicsPipeline(pipeline) => {
+                    self.pipeline = Some(pipeline);
+                    continue;
+                }
+                Command::BindIndexBuffer { ty, .. } => {
+                    self.index_type = Some(ty);
+                    continue;
+                }
+                Command::Draw { .. }
+                | Command::DrawIndirect { .. }
+                | Command::DrawIndirectCount { .. } => DrawKind::Vertex,
+                Command::DrawIndexed { .. }
+                | Command::DrawIndexedIndirect { .. }
+                | Command::DrawIndexedIndirectCount { .. } => DrawKind::Indexed,
+                Command::DrawTransformFeedback(_) => DrawKind::TransformFeedback,
+                Command::DrawMeshTasks { .. }
+                | Command::DrawMeshTasksIndirect { .. }
+                | Command::DrawMeshTasksIndirectCount { .. } => DrawKind::MeshTasks,
+                _ => continue,
+            };
+            return Some(self.info(index, kind));
+        }
+        None
+    }
+}
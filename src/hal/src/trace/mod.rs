@@ -0,0 +1,647 @@
+//! Traces of recorded command streams.
+//!
+//! A [`Trace`] holds the commands recorded into command buffers, the description
+//! of the objects they reference and the data blobs they upload, in a form that
+//! doesn't depend on the backend. It can be serialized in any format supported
+//! by `serde`, attached to a bug report, and replayed later on any backend.
+//!
+//! Traces are recorded with a [`Tracer`]:
+//! - the objects used by the traced commands are registered with the tracer,
+//!   which assigns them increasing [`Handle`]s. While registered, objects are
+//!   found by their type and address, so they must not be moved, and must be
+//!   unregistered before they're destroyed. Registering an object at the
+//!   address of a stale one replaces it;
+//! - the commands are recorded into a [`TracedCommandBuffer`], which forwards
+//!   them to a backend command buffer and keeps a copy on the side;
+//! - the recorded commands are added to the trace with [`Tracer::capture`],
+//!   in the order the command buffers are submitted.
+//!
+//! A [`Replayer`] recreates the objects of a trace on a device, and re-executes
+//! its commands.
+//!
+//! The draws of traced commands can be inspected with [`Trace::draws`] and
+//! [`Tracer::draws`], which report the state bound for each of them, e.g. the
+//! vertex formats, the index type and the primitive topology.
+//!
+//! The contents of the buffers and images are only known to the trace when they
+//! are given at registration, and the descriptor sets are replayed with all the
+//! writes traced for them. Secondary command buffers can't be traced.
+//!
+//! This module is only available with the `trace` feature.
+
+mod draws;
+mod record;
+mod replay;
+
+pub use self::{
+    draws::{DrawInfo, DrawKind, Draws},
+    record::{TracedCommandBuffer, Tracer},
+    replay::{ReplayError, Replayer},
+};
+
+use crate::{
+    buffer,
+    command::{
+        AttachmentClear, BufferCopy, BufferImageCopy, ClearColor, ClearValue, ImageBlit, ImageCopy,
+        ImageResolve, SubpassContents,
+    },
+    format::{Format, Swizzle},
+    image::{
+        self, Extent, FramebufferAttachment, Layout, SamplerDesc, SubresourceRange,
+        ViewCapabilities,
+    },
+    memory::Dependencies,
+    pass::{Attachment, AttachmentId, AttachmentRef, SubpassDependency, SubpassDesc},
+    pso::{
+        self, ComputePipelineRecord, DescriptorArrayIndex, DescriptorBinding,
+        DescriptorSetLayoutBinding, GraphicsPipelineRecord, PipelineStage, ShaderStageFlags,
+    },
+    query,
+    queue::QueueFamilyId,
+    DrawCount, IndexCount, IndexType, InstanceCount, TaskCount, VertexCount, VertexOffset,
+    WorkGroupCount,
+};
+
+use std::ops::Range;
+
+/// Index of an object in [`Trace::objects`].
+pub type Handle = u32;
+/// Index of a data blob in [`Trace::blobs`].
+pub type BlobId = u32;
+
+/// Error occurring while tracing.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum TraceError {
+    /// An object hasn't been registered with the tracer.
+    #[error("Object is not registered")]
+    UnknownObject,
+    /// The command can't be traced.
+    #[error("Command `{0}` can't be traced")]
+    Unsupported(&'static str),
+}
+
+/// Raw bits of a [`ClearValue`], which is interpreted by the command using it.
+pub type ClearBits = [u32; 4];
+
+fn clear_bits(value: ClearValue) -> ClearBits {
+    unsafe { value.color.uint32 }
+}
+
+fn clear_value(bits: ClearBits) -> ClearValue {
+    ClearValue {
+        color: ClearColor { uint32: bits },
+    }
+}
+
+/// Properties a traced image is created with, see
+/// [`create_image`][crate::device::Device::create_image].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub kind: image::Kind,
+    pub mip_levels: image::Level,
+    pub format: Format,
+    pub tiling: image::Tiling,
+    pub usage: image::Usage,
+    pub view_caps: ViewCapabilities,
+}
+
+/// Initial contents of a traced image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImageContents {
+    /// Tightly packed texels of the first mip level, for all the array layers.
+    pub data: BlobId,
+    /// State the image is in after the upload.
+    pub state: image::State,
+}
+
+/// Traced subpass of a render pass, see [`SubpassDesc`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubpassRecord {
+    /// Which attachments will be used as color buffers.
+    pub colors: Vec<AttachmentRef>,
+    /// Which attachments will be used as depth/stencil buffers.
+    pub depth_stencil: Option<AttachmentRef>,
+    /// Which attachments will be used as input attachments.
+    pub inputs: Vec<AttachmentRef>,
+    /// Which attachments will be used as resolve destinations.
+    pub resolves: Vec<AttachmentRef>,
+    /// Attachments that must be preserved to be passed on to subsequent passes.
+    pub preserves: Vec<AttachmentId>,
+    /// Which array layers of the attachments are rendered by each draw.
+    pub view_mask: u32,
+    /// Which attachment will be used as shading rate attachment.
+    pub shading_rate: Option<AttachmentRef>,
+}
+
+impl SubpassRecord {
+    fn new(desc: &SubpassDesc) -> Self {
+        SubpassRecord {
+            colors: desc.colors.to_vec(),
+            depth_stencil: desc.depth_stencil.cloned(),
+            inputs: desc.inputs.to_vec(),
+            resolves: desc.resolves.to_vec(),
+            preserves: desc.preserves.to_vec(),
+            view_mask: desc.view_mask,
+            shading_rate: desc.shading_rate.cloned(),
+        }
+    }
+
+    fn to_desc(&self) -> SubpassDesc<'_> {
+        SubpassDesc {
+            colors: &self.colors,
+            depth_stencil: self.depth_stencil.as_ref(),
+            inputs: &self.inputs,
+            resolves: &self.resolves,
+            preserves: &self.preserves,
+            view_mask: self.view_mask,
+            shading_rate: self.shading_rate.as_ref(),
+        }
+    }
+}
+
+/// Traced descriptor, see [`Descriptor`][pso::Descriptor].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DescriptorRecord {
+    Sampler(Handle),
+    Image(Handle, Layout),
+    CombinedImageSampler(Handle, Layout, Handle),
+    Buffer(Handle, buffer::SubRange),
+    TexelBuffer(Handle),
+}
+
+/// Traced write of a descriptor set, see [`DescriptorSetWrite`][pso::DescriptorSetWrite].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DescriptorWriteRecord {
+    /// Binding index to start writing at.
+    pub binding: DescriptorBinding,
+    /// Offset into the array to copy to.
+    pub array_offset: DescriptorArrayIndex,
+    /// Descriptors to write to the set.
+    pub descriptors: Vec<DescriptorRecord>,
+}
+
+/// Object referenced by the traced commands.
+///
+/// The objects only reference the objects registered before them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Object {
+    /// Buffer, bound to device-local memory on replay.
+    Buffer {
+        /// Size of the buffer.
+        size: u64,
+        /// Usage of the buffer.
+        usage: buffer::Usage,
+        /// Initial contents of the buffer.
+        contents: Option<BlobId>,
+    },
+    /// Buffer view.
+    BufferView {
+        /// Viewed buffer.
+        buffer: Handle,
+        /// Format of the view.
+        format: Option<Format>,
+        /// Viewed range of the buffer.
+        range: buffer::SubRange,
+    },
+    /// Image, bound to device-local memory on replay.
+    Image {
+        /// Properties of the image.
+        info: ImageInfo,
+        /// Initial contents of the image.
+        contents: Option<ImageContents>,
+    },
+    /// Image view.
+    ImageView {
+        /// Viewed image.
+        image: Handle,
+        /// Kind of the view.
+        view_kind: image::ViewKind,
+        /// Format of the view.
+        format: Format,
+        /// Component mapping of the view.
+        swizzle: Swizzle,
+        /// Usage of the view.
+        usage: image::Usage,
+        /// Viewed subresources of the image.
+        range: SubresourceRange,
+    },
+    /// Sampler.
+    Sampler(SamplerDesc),
+    /// Shader module.
+    ShaderModule {
+        /// SPIR-V binary of the module.
+        spirv: Vec<u32>,
+    },
+    /// Descriptor set layout.
+    DescriptorSetLayout {
+        /// Bindings of the layout.
+        bindings: Vec<DescriptorSetLayoutBinding>,
+        /// Immutable samplers of the bindings.
+        immutable_samplers: Vec<Handle>,
+    },
+    /// Pipeline layout.
+    PipelineLayout {
+        /// Descriptor set layouts.
+        set_layouts: Vec<Handle>,
+        /// Ranges of push constants.
+        push_constants: Vec<(ShaderStageFlags, Range<u32>)>,
+    },
+    /// Render pass.
+    RenderPass {
+        /// Attachments of the render pass.
+        attachments: Vec<Attachment>,
+        /// Subpasses of the render pass.
+        subpasses: Vec<SubpassRecord>,
+        /// Dependencies between the subpasses.
+        dependencies: Vec<SubpassDependency>,
+    },
+    /// Framebuffer.
+    Framebuffer {
+        /// Render pass the framebuffer is compatible with.
+        render_pass: Handle,
+        /// Attachments of the framebuffer.
+        attachments: Vec<FramebufferAttachment>,
+        /// Extent of the framebuffer.
+        extent: Extent,
+    },
+    /// Graphics pipeline, referencing the objects by their handles formatted as keys.
+    GraphicsPipeline(Box<GraphicsPipelineRecord>),
+    /// Compute pipeline, referencing the objects by their handles formatted as keys.
+    ComputePipeline(ComputePipelineRecord),
+    /// Descriptor set, allocated from a dedicated pool on replay.
+    DescriptorSet {
+        /// Layout of the set.
+        layout: Handle,
+        /// Writes of the set, in order.
+        writes: Vec<DescriptorWriteRecord>,
+    },
+    /// Query pool.
+    QueryPool {
+        /// Type of the queries.
+        ty: query::Type,
+        /// Number of queries.
+        count: query::Id,
+    },
+    /// Event.
+    Event,
+}
+
+/// Traced barrier, see [`Barrier`][crate::memory::Barrier].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BarrierRecord {
+    AllBuffers(Range<buffer::Access>),
+    AllImages(Range<image::Access>),
+    Buffer {
+        states: Range<buffer::State>,
+        target: Handle,
+        range: buffer::SubRange,
+        families: Option<Range<QueueFamilyId>>,
+    },
+    Image {
+        states: Range<image::State>,
+        target: Handle,
+        range: SubresourceRange,
+        families: Option<Range<QueueFamilyId>>,
+    },
+    Aliasing {
+        before: Option<AliasedRecord>,
+        after: Option<AliasedRecord>,
+    },
+}
+
+/// Traced aliased resource, see [`Aliased`][crate::memory::Aliased].
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AliasedRecord {
+    Buffer(Handle),
+    Image(Handle),
+}
+
+/// Traced attachment clear, see [`AttachmentClear`].
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AttachmentClearRecord {
+    Color {
+        index: usize,
+        value: ClearBits,
+    },
+    DepthStencil {
+        depth: Option<pso::DepthValue>,
+        stencil: Option<pso::StencilValue>,
+    },
+}
+
+impl From<AttachmentClear> for AttachmentClearRecord {
+    fn from(clear: AttachmentClear) -> Self {
+        match clear {
+            AttachmentClear::Color { index, value } => AttachmentClearRecord::Color {
+                index,
+                value: unsafe { value.uint32 },
+            },
+            AttachmentClear::DepthStencil { depth, stencil } => {
+                AttachmentClearRecord::DepthStencil { depth, stencil }
+            }
+        }
+    }
+}
+
+impl From<&AttachmentClearRecord> for AttachmentClear {
+    fn from(clear: &AttachmentClearRecord) -> Self {
+        match *clear {
+            AttachmentClearRecord::Color { index, value } => AttachmentClear::Color {
+                index,
+                value: ClearColor { uint32: value },
+            },
+            AttachmentClearRecord::DepthStencil { depth, stencil } => {
+                AttachmentClear::DepthStencil { depth, stencil }
+            }
+        }
+    }
+}
+
+/// Traced command, mirroring the methods of [`CommandBuffer`][crate::command::CommandBuffer].
+///
+/// The objects are referenced by their handles, and the queries by the handle
+/// of their pool and their index.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Command {
+    PipelineBarrier {
+        stages: Range<PipelineStage>,
+        dependencies: Dependencies,
+        barriers: Vec<BarrierRecord>,
+    },
+    FillBuffer {
+        buffer: Handle,
+        range: buffer::SubRange,
+        data: u32,
+    },
+    UpdateBuffer {
+        buffer: Handle,
+        offset: buffer::Offset,
+        data: BlobId,
+    },
+    ClearImage {
+        image: Handle,
+        layout: Layout,
+        value: ClearBits,
+        subresource_ranges: Vec<SubresourceRange>,
+    },
+    ClearAttachments {
+        clears: Vec<AttachmentClearRecord>,
+        rects: Vec<pso::ClearRect>,
+    },
+    ResolveImage {
+        src: Handle,
+        src_layout: Layout,
+        dst: Handle,
+        dst_layout: Layout,
+        regions: Vec<ImageResolve>,
+    },
+    BlitImage {
+        src: Handle,
+        src_layout: Layout,
+        dst: Handle,
+        dst_layout: Layout,
+        filter: image::Filter,
+        regions: Vec<ImageBlit>,
+    },
+    BindIndexBuffer {
+        buffer: Handle,
+        sub: buffer::SubRange,
+        ty: IndexType,
+    },
+    BindVertexBuffers {
+        first_binding: pso::BufferIndex,
+        buffers: Vec<(Handle, buffer::SubRange)>,
+    },
+    SetViewports {
+        first_viewport: u32,
+        viewports: Vec<pso::Viewport>,
+    },
+    SetScissors {
+        first_scissor: u32,
+        rects: Vec<pso::Rect>,
+    },
+    SetStencilReference {
+        faces: pso::Face,
+        value: pso::StencilValue,
+    },
+    SetStencilReadMask {
+        faces: pso::Face,
+        value: pso::StencilValue,
+    },
+    SetStencilWriteMask {
+        faces: pso::Face,
+        value: pso::StencilValue,
+    },
+    SetBlendConstants(pso::ColorValue),
+    SetDepthBounds(Range<f32>),
+    SetLineWidth(f32),
+    SetDepthBias(pso::DepthBias),
+    SetShadingRate {
+        rate: pso::ShadingRate,
+        combiners: [pso::ShadingRateCombiner; 2],
+    },
+    BeginRenderPass {
+        render_pass: Handle,
+        framebuffer: Handle,
+        render_area: pso::Rect,
+        attachments: Vec<(Handle, ClearBits)>,
+        first_subpass: SubpassContents,
+    },
+    NextSubpass(SubpassContents),
+    EndRenderPass,
+    BindGraphicsPipeline(Handle),
+    BindGraphicsDescriptorSets {
+        layout: Handle,
+        first_set: usize,
+        sets: Vec<Handle>,
+        offsets: Vec<u32>,
+    },
+    BindComputePipeline(Handle),
+    BindComputeDescriptorSets {
+        layout: Handle,
+        first_set: usize,
+        sets: Vec<Handle>,
+        offsets: Vec<u32>,
+    },
+    Dispatch(WorkGroupCount),
+    DispatchIndirect {
+        buffer: Handle,
+        offset: buffer::Offset,
+    },
+    CopyBuffer {
+        src: Handle,
+        dst: Handle,
+        regions: Vec<BufferCopy>,
+    },
+    CopyImage {
+        src: Handle,
+        src_layout: Layout,
+        dst: Handle,
+        dst_layout: Layout,
+        regions: Vec<ImageCopy>,
+    },
+    CopyBufferToImage {
+        src: Handle,
+        dst: Handle,
+        dst_layout: Layout,
+        regions: Vec<BufferImageCopy>,
+    },
+    CopyImageToBuffer {
+        src: Handle,
+        src_layout: Layout,
+        dst: Handle,
+        regions: Vec<BufferImageCopy>,
+    },
+    Draw {
+        vertices: Range<VertexCount>,
+        instances: Range<InstanceCount>,
+    },
+    DrawIndexed {
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    },
+    DrawIndirect {
+        buffer: Handle,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    },
+    DrawIndexedIndirect {
+        buffer: Handle,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    },
+    DrawIndirectCount {
+        buffer: Handle,
+        offset: buffer::Offset,
+        count_buffer: Handle,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
+    },
+    DrawIndexedIndirectCount {
+        buffer: Handle,
+        offset: buffer::Offset,
+        count_buffer: Handle,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
+    },
+    BindTransformFeedbackBuffers {
+        first_binding: u32,
+        buffers: Vec<(Handle, buffer::SubRange)>,
+    },
+    BeginTransformFeedback,
+    EndTransformFeedback,
+    DrawTransformFeedback(Range<InstanceCount>),
+    DrawMeshTasks {
+        task_count: TaskCount,
+        first_task: TaskCount,
+    },
+    DrawMeshTasksIndirect {
+        buffer: Handle,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    },
+    DrawMeshTasksIndirectCount {
+        buffer: Handle,
+        offset: buffer::Offset,
+        count_buffer: Handle,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: DrawCount,
+        stride: buffer::Stride,
+    },
+    SetEvent {
+        event: Handle,
+        stages: PipelineStage,
+    },
+    ResetEvent {
+        event: Handle,
+        stages: PipelineStage,
+    },
+    WaitEvents {
+        events: Vec<Handle>,
+        stages: Range<PipelineStage>,
+        barriers: Vec<BarrierRecord>,
+    },
+    BeginQuery {
+        pool: Handle,
+        id: query::Id,
+        flags: query::ControlFlags,
+    },
+    EndQuery {
+        pool: Handle,
+        id: query::Id,
+    },
+    ResetQueryPool {
+        pool: Handle,
+        queries: Range<query::Id>,
+    },
+    CopyQueryPoolResults {
+        pool: Handle,
+        queries: Range<query::Id>,
+        buffer: Handle,
+        offset: buffer::Offset,
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
+    },
+    WriteTimestamp {
+        stage: PipelineStage,
+        pool: Handle,
+        id: query::Id,
+    },
+    PushGraphicsConstants {
+        layout: Handle,
+        stages: ShaderStageFlags,
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    PushComputeConstants {
+        layout: Handle,
+        offset: u32,
+        constants: Vec<u32>,
+    },
+    InsertDebugMarker {
+        name: String,
+        color: u32,
+    },
+    BeginDebugMarker {
+        name: String,
+        color: u32,
+    },
+    EndDebugMarker,
+}
+
+/// Recorded command stream, with the objects and data it references.
+///
+/// See the [module documentation][self].
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Trace {
+    /// Objects referenced by the commands, indexed by their handles.
+    pub objects: Vec<Object>,
+    /// Data blobs uploaded by the objects and the commands.
+    pub blobs: Vec<Vec<u8>>,
+    /// Commands of the captured command buffers, in submission order.
+    pub commands: Vec<Command>,
+}
+
+impl Trace {
+    /// Create an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Iterate over the draws of the traced commands.
+    pub fn draws(&self) -> Draws<'_> {
+        Draws::new(&self.objects, &self.commands)
+    }
+}
@@ -0,0 +1,1472 @@
+use super::{
+    clear_bits, AliasedRecord, AttachmentClearRecord, BarrierRecord, BlobId, Command,
+    DescriptorRecord, DescriptorWriteRecord, Draws, Handle, ImageContents, ImageInfo, Object,
+    SubpassRecord, Trace, TraceError,
+};
+use crate::{
+    acceleration_structure, buffer,
+    command::{
+        AttachmentClear, BufferCopy, BufferImageCopy, ClearValue, CommandBuffer,
+        CommandBufferFlags, CommandBufferInheritanceInfo, DescriptorSetOffset, ImageBlit,
+        ImageCopy, ImageResolve, RenderAttachmentInfo, SubpassContents,
+    },
+    device::Device,
+    format::{Format, Swizzle},
+    image::{self, Extent, Filter, FramebufferAttachment, Layout, SamplerDesc, SubresourceRange},
+    memory::{Aliased, Barrier, Dependencies},
+    pass,
+    pso::{
+        self, ComputePipelineDesc, ComputePipelineRecord, Descriptor, DescriptorSetWrite,
+        GraphicsPipelineDesc, GraphicsPipelineRecord, ManifestObjects,
+    },
+    query, Backend, DrawCount, IndexCount, IndexType, InstanceCount, TaskCount, VertexCount,
+    VertexOffset, WorkGroupCount,
+};
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    ops::Range,
+    sync::{Arc, RwLock},
+};
+
+/// Type and address of a registered object.
+type Key = (TypeId, usize);
+type Handles = Arc<RwLock<HashMap<Key, Handle>>>;
+
+fn key<T: Any>(object: &T) -> Key {
+    let ptr: *const T = object;
+    (TypeId::of::<T>(), ptr as usize)
+}
+
+fn lookup<T: Any>(handles: &HashMap<Key, Handle>, object: &T) -> Result<Handle, TraceError> {
+    handles
+        .get(&key(object))
+        .cloned()
+        .ok_or(TraceError::UnknownObject)
+}
+
+/// Keys of the objects referenced by the traced pipelines.
+struct Keys<'a>(&'a HashMap<Key, Handle>);
+
+impl<B: Backend> ManifestObjects<B> for Keys<'_> {
+    fn shader_module_key(&self, module: &B::ShaderModule) -> Option<String> {
+        self.0.get(&key(module)).map(Handle::to_string)
+    }
+    fn pipeline_layout_key(&self, layout: &B::PipelineLayout) -> Option<String> {
+        self.0.get(&key(layout)).map(Handle::to_string)
+    }
+    fn render_pass_key(&self, render_pass: &B::RenderPass) -> Option<String> {
+        self.0.get(&key(render_pass)).map(Handle::to_string)
+    }
+    fn shader_module(&self, _key: &str) -> Option<&B::ShaderModule> {
+        None
+    }
+    fn pipeline_layout(&self, _key: &str) -> Option<&B::PipelineLayout> {
+        None
+    }
+    fn render_pass(&self, _key: &str) -> Option<&B::RenderPass> {
+        None
+    }
+}
+
+/// Recorder of a [`Trace`].
+///
+/// See the [module documentation][super].
+#[derive(Debug)]
+pub struct Tracer<B: Backend> {
+    trace: Trace,
+    /// Handles of the live objects, assigned in the order of registration.
+    handles: Handles,
+    next_handle: Handle,
+    phantom: PhantomData<B>,
+}
+
+impl<B: Backend> Default for Tracer<B> {
+    fn default() -> Self {
+        Tracer {
+            trace: Trace::new(),
+            handles: Handles::default(),
+            next_handle: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<B: Backend> Tracer<B> {
+    /// Create a new tracer, with an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the trace recorded so far.
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Consume the tracer, returning the recorded trace.
+    pub fn into_trace(self) -> Trace {
+        self.trace
+    }
+
+    /// Get the handle of a registered object.
+    pub fn handle<T: Any>(&self, object: &T) -> Option<Handle> {
+        lookup(&self.handles.read().unwrap(), object).ok()
+    }
+
+    /// Stop identifying an object by its address, which has to be done before
+    /// it's destroyed or moved.
+    ///
+    /// The object stays in the trace, as the captured commands may reference it.
+    pub fn unregister<T: Any>(&mut self, object: &T) {
+        self.handles.write().unwrap().remove(&key(object));
+    }
+
+    fn add_object<T: Any>(&mut self, object: &T, record: Object) -> Handle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        debug_assert_eq!(handle as usize, self.trace.objects.len());
+        self.trace.objects.push(record);
+        if let Some(stale) = self.handles.write().unwrap().insert(key(object), handle) {
+            // The previous object at this address was destroyed without being
+            // unregistered: the new registration replaces it.
+            log::warn!(
+                "Object {} was not unregistered before its address got reused by object {}",
+                stale,
+                handle
+            );
+        }
+        handle
+    }
+
+    fn add_blob(&mut self, data: &[u8]) -> BlobId {
+        self.trace.blobs.push(data.to_vec());
+        (self.trace.blobs.len() - 1) as BlobId
+    }
+
+    fn lookup<T: Any>(&self, object: &T) -> Result<Handle, TraceError> {
+        lookup(&self.handles.read().unwrap(), object)
+    }
+
+    /// Register a buffer created with the given size and usage.
+    ///
+    /// The `contents` are uploaded to the buffer before the replay.
+    pub fn register_buffer(
+        &mut self,
+        buffer: &B::Buffer,
+        size: u64,
+        usage: buffer::Usage,
+        contents: Option<&[u8]>,
+    ) -> Handle {
+        let contents = contents.map(|data| self.add_blob(data));
+        self.add_object(
+            buffer,
+            Object::Buffer {
+                size,
+                usage,
+                contents,
+            },
+        )
+    }
+
+    /// Register a view of a registered buffer.
+    pub fn register_buffer_view(
+        &mut self,
+        view: &B::BufferView,
+        buffer: &B::Buffer,
+        format: Option<Format>,
+        range: buffer::SubRange,
+    ) -> Result<Handle, TraceError> {
+        let buffer = self.lookup(buffer)?;
+        Ok(self.add_object(
+            view,
+            Object::BufferView {
+                buffer,
+                format,
+                range,
+            },
+        ))
+    }
+
+    /// Register an image created with the given properties.
+    ///
+    /// The `contents` are tightly packed texels of the first mip level, for
+    /// all the array layers, that are uploaded to the image before the replay.
+    /// The image is then left in the given state.
+    pub fn register_image(
+        &mut self,
+        image: &B::Image,
+        info: ImageInfo,
+        contents: Option<(&[u8], image::State)>,
+    ) -> Handle {
+        let contents = contents.map(|(data, state)| ImageContents {
+            data: self.add_blob(data),
+            state,
+        });
+        self.add_object(image, Object::Image { info, contents })
+    }
+
+    /// Register a view of a registered image.
+    pub fn register_image_view(
+        &mut self,
+        view: &B::ImageView,
+        image: &B::Image,
+        view_kind: image::ViewKind,
+        format: Format,
+        swizzle: Swizzle,
+        usage: image::Usage,
+        range: SubresourceRange,
+    ) -> Result<Handle, TraceError> {
+        let image = self.lookup(image)?;
+        Ok(self.add_object(
+            view,
+            Object::ImageView {
+                image,
+                view_kind,
+                format,
+                swizzle,
+                usage,
+                range,
+            },
+        ))
+    }
+
+    /// Register a sampler.
+    pub fn register_sampler(&mut self, sampler: &B::Sampler, desc: &SamplerDesc) -> Handle {
+        self.add_object(sampler, Object::Sampler(desc.clone()))
+    }
+
+    /// Register a shader module created from the SPIR-V binary.
+    pub fn register_shader_module(&mut self, module: &B::ShaderModule, spirv: &[u32]) -> Handle {
+        self.add_object(
+            module,
+            Object::ShaderModule {
+                spirv: spirv.to_vec(),
+            },
+        )
+    }
+
+    /// Register a descriptor set layout, whose immutable samplers are registered.
+    pub fn register_descriptor_set_layout<'a, I, J>(
+        &mut self,
+        layout: &B::DescriptorSetLayout,
+        bindings: I,
+        immutable_samplers: J,
+    ) -> Result<Handle, TraceError>
+    where
+        I: Iterator<Item = pso::DescriptorSetLayoutBinding>,
+        J: Iterator<Item = &'a B::Sampler>,
+    {
+        let immutable_samplers = immutable_samplers
+            .map(|sampler| self.lookup(sampler))
+            .collect::<Result<_, _>>()?;
+        Ok(self.add_object(
+            layout,
+            Object::DescriptorSetLayout {
+                bindings: bindings.collect(),
+                immutable_samplers,
+            },
+        ))
+    }
+
+    /// Register a pipeline layout, whose descriptor set layouts are registered.
+    pub fn register_pipeline_layout<'a, Is, Ic>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        set_layouts: Is,
+        push_constants: Ic,
+    ) -> Result<Handle, TraceError>
+    where
+        Is: Iterator<Item = &'a B::DescriptorSetLayout>,
+        Ic: Iterator<Item = (pso::ShaderStageFlags, Range<u32>)>,
+    {
+        let set_layouts = set_layouts
+            .map(|set_layout| self.lookup(set_layout))
+            .collect::<Result<_, _>>()?;
+        Ok(self.add_object(
+            layout,
+            Object::PipelineLayout {
+                set_layouts,
+                push_constants: push_constants.collect(),
+            },
+        ))
+    }
+
+    /// Register a render pass.
+    pub fn register_render_pass<'a, Ia, Is, Id>(
+        &mut self,
+        render_pass: &B::RenderPass,
+        attachments: Ia,
+        subpasses: Is,
+        dependencies: Id,
+    ) -> Handle
+    where
+        Ia: Iterator<Item = pass::Attachment>,
+        Is: Iterator<Item = pass::SubpassDesc<'a>>,
+        Id: Iterator<Item = pass::SubpassDependency>,
+    {
+        self.add_object(
+            render_pass,
+            Object::RenderPass {
+                attachments: attachments.collect(),
+                subpasses: subpasses.map(|desc| SubpassRecord::new(&desc)).collect(),
+                dependencies: dependencies.collect(),
+            },
+        )
+    }
+
+    /// Register a framebuffer of a registered render pass.
+    pub fn register_framebuffer<I>(
+        &mut self,
+        framebuffer: &B::Framebuffer,
+        render_pass: &B::RenderPass,
+        attachments: I,
+        extent: Extent,
+    ) -> Result<Handle, TraceError>
+    where
+        I: Iterator<Item = FramebufferAttachment>,
+    {
+        let render_pass = self.lookup(render_pass)?;
+        Ok(self.add_object(
+            framebuffer,
+            Object::Framebuffer {
+                render_pass,
+                attachments: attachments.collect(),
+                extent,
+            },
+        ))
+    }
+
+    /// Register a graphics pipeline, whose shader modules, layout and render
+    /// pass are registered.
+    ///
+    /// The parent pipeline isn't traced.
+    pub fn register_graphics_pipeline(
+        &mut self,
+        pipeline: &B::GraphicsPipeline,
+        desc: &GraphicsPipelineDesc<B>,
+    ) -> Result<Handle, TraceError> {
+        let record = GraphicsPipelineRecord::new(desc, &Keys(&self.handles.read().unwrap()))
+            .map_err(|_| TraceError::UnknownObject)?;
+        Ok(self.add_object(pipeline, Object::GraphicsPipeline(Box::new(record))))
+    }
+
+    /// Register a compute pipeline, whose shader module and layout are registered.
+    ///
+    /// The parent pipeline isn't traced.
+    pub fn register_compute_pipeline(
+        &mut self,
+        pipeline: &B::ComputePipeline,
+        desc: &ComputePipelineDesc<B>,
+    ) -> Result<Handle, TraceError> {
+        let record = ComputePipelineRecord::new(desc, &Keys(&self.handles.read().unwrap()))
+            .map_err(|_| TraceError::UnknownObject)?;
+        Ok(self.add_object(pipeline, Object::ComputePipeline(record)))
+    }
+
+    /// Register a descriptor set allocated with a registered layout.
+    ///
+    /// The descriptors have to be written with [`write_descriptor_set`][Tracer::write_descriptor_set].
+    pub fn register_descriptor_set(
+        &mut self,
+        set: &B::DescriptorSet,
+        layout: &B::DescriptorSetLayout,
+    ) -> Result<Handle, TraceError> {
+        let layout = self.lookup(layout)?;
+        Ok(self.add_object(
+            set,
+            Object::DescriptorSet {
+                layout,
+                writes: Vec::new(),
+            },
+        ))
+    }
+
+    /// Write the descriptors of a registered set with the `device`, tracing the write.
+    ///
+    /// The write is performed even if it can't be traced.
+    ///
+    /// # Safety
+    ///
+    /// See [`write_descriptor_set`][Device::write_descriptor_set].
+    pub unsafe fn write_descriptor_set<'a, I>(
+        &mut self,
+        device: &B::Device,
+        op: DescriptorSetWrite<'a, B, I>,
+    ) -> Result<(), TraceError>
+    where
+        I: Iterator<Item = Descriptor<'a, B>>,
+    {
+        let DescriptorSetWrite {
+            set,
+            binding,
+            array_offset,
+            descriptors,
+        } = op;
+        let descriptors = descriptors.collect::<Vec<_>>();
+        let record = {
+            let handles = self.handles.read().unwrap();
+            lookup(&handles, &*set).and_then(|set| {
+                let descriptors = descriptors
+                    .iter()
+                    .map(|descriptor| {
+                        Ok(match *descriptor {
+                            Descriptor::Sampler(sampler) => {
+                                DescriptorRecord::Sampler(lookup(&handles, sampler)?)
+                            }
+                            Descriptor::Image(view, layout) => {
+                                DescriptorRecord::Image(lookup(&handles, view)?, layout)
+                            }
+                            Descriptor::CombinedImageSampler(view, layout, sampler) => {
+                                DescriptorRecord::CombinedImageSampler(
+                                    lookup(&handles, view)?,
+                                    layout,
+                                    lookup(&handles, sampler)?,
+                                )
+                            }
+                            Descriptor::Buffer(buffer, ref range) => {
+                                DescriptorRecord::Buffer(lookup(&handles, buffer)?, range.clone())
+                            }
+                            Descriptor::TexelBuffer(view) => {
+                                DescriptorRecord::TexelBuffer(lookup(&handles, view)?)
+                            }
+                            Descriptor::AccelerationStructure(_) => {
+                                return Err(TraceError::Unsupported("write_descriptor_set"))
+                            }
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok((set, descriptors))
+            })
+        };
+        let result = record.map(|(handle, records)| {
+            if let Object::DescriptorSet { ref mut writes, .. } =
+                self.trace.objects[handle as usize]
+            {
+                writes.push(DescriptorWriteRecord {
+                    binding,
+                    array_offset,
+                    descriptors: records,
+                });
+            }
+        });
+        device.write_descriptor_set(DescriptorSetWrite {
+            set,
+            binding,
+            array_offset,
+            descriptors: descriptors.into_iter(),
+        });
+        result
+    }
+
+    /// Register a query pool.
+    pub fn register_query_pool(
+        &mut self,
+        pool: &B::QueryPool,
+        ty: query::Type,
+        count: query::Id,
+    ) -> Handle {
+        self.add_object(pool, Object::QueryPool { ty, count })
+    }
+
+    /// Register an event.
+    pub fn register_event(&mut self, event: &B::Event) -> Handle {
+        self.add_object(event, Object::Event)
+    }
+
+    /// Add the commands recorded into a command buffer to the trace.
+    ///
+    /// Returns an error if the commands reference unregistered objects, or
+    /// can't be traced.
+    pub fn capture(&mut self, cmd_buffer: &TracedCommandBuffer<B>) -> Result<(), TraceError> {
+        if let Some(ref error) = cmd_buffer.error {
+            return Err(error.clone());
+        }
+        let base = self.trace.blobs.len() as BlobId;
+        self.trace.blobs.extend(cmd_buffer.blobs.iter().cloned());
+        self.trace
+            .commands
+            .extend(cmd_buffer.commands.iter().map(|command| match *command {
+                Command::UpdateBuffer {
+                    buffer,
+                    offset,
+                    data,
+                } => Command::UpdateBuffer {
+                    buffer,
+                    offset,
+                    data: base + data,
+                },
+                ref other => other.clone(),
+            }));
+        Ok(())
+    }
+
+    /// Iterate over the draws recorded into a command buffer so far.
+    pub fn draws<'a>(&'a self, cmd_buffer: &'a TracedCommandBuffer<B>) -> Draws<'a> {
+        Draws::new(&self.trace.objects, &cmd_buffer.commands)
+    }
+}
+
+/// Command buffer recording the commands for a [`Tracer`], while forwarding
+/// them to a backend command buffer.
+///
+/// The backend command buffer is submitted as usual, and the commands are added
+/// to the trace with [`Tracer::capture`]. Beginning or resetting the command
+/// buffer discards the recorded commands.
+#[derive(Debug)]
+pub struct TracedCommandBuffer<B: Backend> {
+    raw: B::CommandBuffer,
+    handles: Handles,
+    commands: Vec<Command>,
+    blobs: Vec<Vec<u8>>,
+    error: Option<TraceError>,
+}
+
+impl<B: Backend> TracedCommandBuffer<B> {
+    /// Wrap a backend command buffer, looking up the objects registered with `tracer`.
+    pub fn new(raw: B::CommandBuffer, tracer: &Tracer<B>) -> Self {
+        TracedCommandBuffer {
+            raw,
+            handles: Arc::clone(&tracer.handles),
+            commands: Vec::new(),
+            blobs: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Get the backend command buffer.
+    pub fn raw(&self) -> &B::CommandBuffer {
+        &self.raw
+    }
+
+    /// Unwrap the backend command buffer.
+    pub fn into_raw(self) -> B::CommandBuffer {
+        self.raw
+    }
+
+    fn clear(&mut self) {
+        self.commands.clear();
+        self.blobs.clear();
+        self.error = None;
+    }
+
+    fn fail(&mut self, error: TraceError) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+
+    fn handle<T: Any>(&mut self, object: &T) -> Handle {
+        let handle = lookup(&self.handles.read().unwrap(), object);
+        handle.unwrap_or_else(|error| {
+            self.fail(error);
+            !0
+        })
+    }
+
+    fn barrier(&mut self, barrier: &Barrier<B>) -> BarrierRecord {
+        match *barrier {
+            Barrier::AllBuffers(ref states) => BarrierRecord::AllBuffers(states.clone()),
+            Barrier::AllImages(ref states) => BarrierRecord::AllImages(states.clone()),
+            Barrier::Buffer {
+                ref states,
+                target,
+                ref range,
+                ref families,
+            } => BarrierRecord::Buffer {
+                states: states.clone(),
+                target: self.handle(target),
+                range: range.clone(),
+                families: families.clone(),
+            },
+            Barrier::Image {
+                ref states,
+                target,
+                ref range,
+                ref families,
+            } => BarrierRecord::Image {
+                states: states.clone(),
+                target: self.handle(target),
+                range: range.clone(),
+                families: families.clone(),
+            },
+            Barrier::Aliasing {
+                ref before,
+                ref after,
+            } => BarrierRecord::Aliasing {
+                before: before.as_ref().map(|resource| self.aliased(resource)),
+                after: after.as_ref().map(|resource| self.aliased(resource)),
+            },
+        }
+    }
+
+    fn aliased(&mut self, resource: &Aliased<B>) -> AliasedRecord {
+        match *resource {
+            Aliased::Buffer(buffer) => AliasedRecord::Buffer(self.handle(buffer)),
+            Aliased::Image(image) => AliasedRecord::Image(self.handle(image)),
+        }
+    }
+}
+
+impl<B: Backend> CommandBuffer<B> for TracedCommandBuffer<B> {
+    unsafe fn begin(
+        &mut self,
+        flags: CommandBufferFlags,
+        inheritance_info: CommandBufferInheritanceInfo<B>,
+    ) {
+        self.clear();
+        if inheritance_info.subpass.is_some() || inheritance_info.framebuffer.is_some() {
+            self.fail(TraceError::Unsupported("begin"));
+        }
+        self.raw.begin(flags, inheritance_info);
+    }
+
+    unsafe fn finish(&mut self) {
+        self.raw.finish();
+    }
+
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.clear();
+        self.raw.reset(release_resources);
+    }
+
+    unsafe fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: Iterator<Item = Barrier<'a, B>>,
+    {
+        let barriers = barriers.collect::<Vec<_>>();
+        let records = barriers.iter().map(|b| self.barrier(b)).collect();
+        self.commands.push(Command::PipelineBarrier {
+            stages: stages.clone(),
+            dependencies,
+            barriers: records,
+        });
+        self.raw
+            .pipeline_barrier(stages, dependencies, barriers.into_iter());
+    }
+
+    unsafe fn fill_buffer(&mut self, buffer: &B::Buffer, range: buffer::SubRange, data: u32) {
+        let handle = self.handle(buffer);
+        self.commands.push(Command::FillBuffer {
+            buffer: handle,
+            range: range.clone(),
+            data,
+        });
+        self.raw.fill_buffer(buffer, range, data);
+    }
+
+    unsafe fn update_buffer(&mut self, buffer: &B::Buffer, offset: buffer::Offset, data: &[u8]) {
+        let handle = self.handle(buffer);
+        self.blobs.push(data.to_vec());
+        self.commands.push(Command::UpdateBuffer {
+            buffer: handle,
+            offset,
+            data: (self.blobs.len() - 1) as BlobId,
+        });
+        self.raw.update_buffer(buffer, offset, data);
+    }
+
+    unsafe fn clear_image<T>(
+        &mut self,
+        image: &B::Image,
+        layout: Layout,
+        value: ClearValue,
+        subresource_ranges: T,
+    ) where
+        T: Iterator<Item = SubresourceRange>,
+    {
+        let handle = self.handle(image);
+        let subresource_ranges = subresource_ranges.collect::<Vec<_>>();
+        self.commands.push(Command::ClearImage {
+            image: handle,
+            layout,
+            value: clear_bits(value),
+            subresource_ranges: subresource_ranges.clone(),
+        });
+        self.raw
+            .clear_image(image, layout, value, subresource_ranges.into_iter());
+    }
+
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: Iterator<Item = AttachmentClear>,
+        U: Iterator<Item = pso::ClearRect>,
+    {
+        let clears = clears.collect::<Vec<_>>();
+        let rects = rects.collect::<Vec<_>>();
+        self.commands.push(Command::ClearAttachments {
+            clears: clears
+                .iter()
+                .map(|&clear| AttachmentClearRecord::from(clear))
+                .collect(),
+            rects: rects.clone(),
+        });
+        self.raw
+            .clear_attachments(clears.into_iter(), rects.into_iter());
+    }
+
+    unsafe fn resolve_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Image,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: Iterator<Item = ImageResolve>,
+    {
+        let regions = regions.collect::<Vec<_>>();
+        let command = Command::ResolveImage {
+            src: self.handle(src),
+            src_layout,
+            dst: self.handle(dst),
+            dst_layout,
+            regions: regions.clone(),
+        };
+        self.commands.push(command);
+        self.raw
+            .resolve_image(src, src_layout, dst, dst_layout, regions.into_iter());
+    }
+
+    unsafe fn blit_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Image,
+        dst_layout: Layout,
+        filter: Filter,
+        regions: T,
+    ) where
+        T: Iterator<Item = ImageBlit>,
+    {
+        let regions = regions.collect::<Vec<_>>();
+        let command = Command::BlitImage {
+            src: self.handle(src),
+            src_layout,
+            dst: self.handle(dst),
+            dst_layout,
+            filter,
+            regions: regions.clone(),
+        };
+        self.commands.push(command);
+        self.raw.blit_image(
+            src,
+            src_layout,
+            dst,
+            dst_layout,
+            filter,
+            regions.into_iter(),
+        );
+    }
+
+    unsafe fn bind_index_buffer(
+        &mut self,
+        buffer: &B::Buffer,
+        sub: buffer::SubRange,
+        ty: IndexType,
+    ) {
+        let handle = self.handle(buffer);
+        self.commands.push(Command::BindIndexBuffer {
+            buffer: handle,
+            sub: sub.clone(),
+            ty,
+        });
+        self.raw.bind_index_buffer(buffer, sub, ty);
+    }
+
+    unsafe fn bind_vertex_buffers<'a, T>(&mut self, first_binding: pso::BufferIndex, buffers: T)
+    where
+        T: Iterator<Item = (&'a B::Buffer, buffer::SubRange)>,
+    {
+        let buffers = buffers.collect::<Vec<_>>();
+        let records = buffers
+            .iter()
+            .map(|&(buffer, ref sub)| (self.handle(buffer), sub.clone()))
+            .collect();
+        self.commands.push(Command::BindVertexBuffers {
+            first_binding,
+            buffers: records,
+        });
+        self.raw
+            .bind_vertex_buffers(first_binding, buffers.into_iter());
+    }
+
+    unsafe fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: Iterator<Item = pso::Viewport>,
+    {
+        let viewports = viewports.collect::<Vec<_>>();
+        self.commands.push(Command::SetViewports {
+            first_viewport,
+            viewports: viewports.clone(),
+        });
+        self.raw
+            .set_viewports(first_viewport, viewports.into_iter());
+    }
+
+    unsafe fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: Iterator<Item = pso::Rect>,
+    {
+        let rects = rects.collect::<Vec<_>>();
+        self.commands.push(Command::SetScissors {
+            first_scissor,
+            rects: rects.clone(),
+        });
+        self.raw.set_scissors(first_scissor, rects.into_iter());
+    }
+
+    unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilReference { faces, value });
+        self.raw.set_stencil_reference(faces, value);
+    }
+
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilReadMask { faces, value });
+        self.raw.set_stencil_read_mask(faces, value);
+    }
+
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.commands
+            .push(Command::SetStencilWriteMask { faces, value });
+        self.raw.set_stencil_write_mask(faces, value);
+    }
+
+    unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.commands.push(Command::SetBlendConstants(color));
+        self.raw.set_blend_constants(color);
+    }
+
+    unsafe fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.commands.push(Command::SetDepthBounds(bounds.clone()));
+        self.raw.set_depth_bounds(bounds);
+    }
+
+    unsafe fn set_line_width(&mut self, width: f32) {
+        self.commands.push(Command::SetLineWidth(width));
+        self.raw.set_line_width(width);
+    }
+
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.commands.push(Command::SetDepthBias(depth_bias));
+        self.raw.set_depth_bias(depth_bias);
+    }
+
+    unsafe fn set_shading_rate(
+        &mut self,
+        rate: pso::ShadingRate,
+        combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        self.commands
+            .push(Command::SetShadingRate { rate, combiners });
+        self.raw.set_shading_rate(rate, combiners);
+    }
+
+    unsafe fn begin_render_pass<'a, T>(
+        &mut self,
+        render_pass: &B::RenderPass,
+        framebuffer: &B::Framebuffer,
+        render_area: pso::Rect,
+        attachments: T,
+        first_subpass: SubpassContents,
+    ) where
+        T: Iterator<Item = RenderAttachmentInfo<'a, B>>,
+    {
+        let attachments = attachments.collect::<Vec<_>>();
+        let command = Command::BeginRenderPass {
+            render_pass: self.handle(render_pass),
+            framebuffer: self.handle(framebuffer),
+            render_area,
+            attachments: attachments
+                .iter()
+                .map(|info| (self.handle(info.image_view), clear_bits(info.clear_value)))
+                .collect(),
+            first_subpass,
+        };
+        self.commands.push(command);
+        self.raw.begin_render_pass(
+            render_pass,
+            framebuffer,
+            render_area,
+            attachments.into_iter(),
+            first_subpass,
+        );
+    }
+
+    unsafe fn next_subpass(&mut self, contents: SubpassContents) {
+        self.commands.push(Command::NextSubpass(contents));
+        self.raw.next_subpass(contents);
+    }
+
+    unsafe fn end_render_pass(&mut self) {
+        self.commands.push(Command::EndRenderPass);
+        self.raw.end_render_pass();
+    }
+
+    unsafe fn bind_graphics_pipeline(&mut self, pipeline: &B::GraphicsPipeline) {
+        let handle = self.handle(pipeline);
+        self.commands.push(Command::BindGraphicsPipeline(handle));
+        self.raw.bind_graphics_pipeline(pipeline);
+    }
+
+    unsafe fn bind_graphics_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a B::DescriptorSet>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        let sets = sets.collect::<Vec<_>>();
+        let offsets = offsets.collect::<Vec<_>>();
+        let command = Command::BindGraphicsDescriptorSets {
+            layout: self.handle(layout),
+            first_set,
+            sets: sets.iter().map(|&set| self.handle(set)).collect(),
+            offsets: offsets.clone(),
+        };
+        self.commands.push(command);
+        self.raw.bind_graphics_descriptor_sets(
+            layout,
+            first_set,
+            sets.into_iter(),
+            offsets.into_iter(),
+        );
+    }
+
+    unsafe fn bind_compute_pipeline(&mut self, pipeline: &B::ComputePipeline) {
+        let handle = self.handle(pipeline);
+        self.commands.push(Command::BindComputePipeline(handle));
+        self.raw.bind_compute_pipeline(pipeline);
+    }
+
+    unsafe fn bind_compute_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a B::DescriptorSet>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        let sets = sets.collect::<Vec<_>>();
+        let offsets = offsets.collect::<Vec<_>>();
+        let command = Command::BindComputeDescriptorSets {
+            layout: self.handle(layout),
+            first_set,
+            sets: sets.iter().map(|&set| self.handle(set)).collect(),
+            offsets: offsets.clone(),
+        };
+        self.commands.push(command);
+        self.raw.bind_compute_descriptor_sets(
+            layout,
+            first_set,
+            sets.into_iter(),
+            offsets.into_iter(),
+        );
+    }
+
+    unsafe fn dispatch(&mut self, count: WorkGroupCount) {
+        self.commands.push(Command::Dispatch(count));
+        self.raw.dispatch(count);
+    }
+
+    unsafe fn dispatch_indirect(&mut self, buffer: &B::Buffer, offset: buffer::Offset) {
+        let handle = self.handle(buffer);
+        self.commands.push(Command::DispatchIndirect {
+            buffer: handle,
+            offset,
+        });
+        self.raw.dispatch_indirect(buffer, offset);
+    }
+
+    unsafe fn build_acceleration_structures(
+        &mut self,
+        descs: &[acceleration_structure::BuildDesc<B>],
+    ) {
+        self.fail(TraceError::Unsupported("build_acceleration_structures"));
+        self.raw.build_acceleration_structures(descs);
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        src: &B::AccelerationStructure,
+        dst: &B::AccelerationStructure,
+        mode: acceleration_structure::CopyMode,
+    ) {
+        self.fail(TraceError::Unsupported("copy_acceleration_structure"));
+        self.raw.copy_acceleration_structure(src, dst, mode);
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, pipeline: &B::RayTracingPipeline) {
+        self.fail(TraceError::Unsupported("bind_ray_tracing_pipeline"));
+        self.raw.bind_ray_tracing_pipeline(pipeline);
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &B::PipelineLayout,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a B::DescriptorSet>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        self.fail(TraceError::Unsupported("bind_ray_tracing_descriptor_sets"));
+        self.raw
+            .bind_ray_tracing_descriptor_sets(layout, first_set, sets, offsets);
+    }
+
+    unsafe fn trace_rays(&mut self, tables: &pso::ShaderBindingTables<B>, count: WorkGroupCount) {
+        self.fail(TraceError::Unsupported("trace_rays"));
+        self.raw.trace_rays(tables, count);
+    }
+
+    unsafe fn copy_buffer<T>(&mut self, src: &B::Buffer, dst: &B::Buffer, regions: T)
+    where
+        T: Iterator<Item = BufferCopy>,
+    {
+        let regions = regions.collect::<Vec<_>>();
+        let command = Command::CopyBuffer {
+            src: self.handle(src),
+            dst: self.handle(dst),
+            regions: regions.clone(),
+        };
+        self.commands.push(command);
+        self.raw.copy_buffer(src, dst, regions.into_iter());
+    }
+
+    unsafe fn copy_image<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Image,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: Iterator<Item = ImageCopy>,
+    {
+        let regions = regions.collect::<Vec<_>>();
+        let command = Command::CopyImage {
+            src: self.handle(src),
+            src_layout,
+            dst: self.handle(dst),
+            dst_layout,
+            regions: regions.clone(),
+        };
+        self.commands.push(command);
+        self.raw
+            .copy_image(src, src_layout, dst, dst_layout, regions.into_iter());
+    }
+
+    unsafe fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &B::Buffer,
+        dst: &B::Image,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: Iterator<Item = BufferImageCopy>,
+    {
+        let regions = regions.collect::<Vec<_>>();
+        let command = Command::CopyBufferToImage {
+            src: self.handle(src),
+            dst: self.handle(dst),
+            dst_layout,
+            regions: regions.clone(),
+        };
+        self.commands.push(command);
+        self.raw
+            .copy_buffer_to_image(src, dst, dst_layout, regions.into_iter());
+    }
+
+    unsafe fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &B::Image,
+        src_layout: Layout,
+        dst: &B::Buffer,
+        regions: T,
+    ) where
+        T: Iterator<Item = BufferImageCopy>,
+    {
+        let regions = regions.collect::<Vec<_>>();
+        let command = Command::CopyImageToBuffer {
+            src: self.handle(src),
+            src_layout,
+            dst: self.handle(dst),
+            regions: regions.clone(),
+        };
+        self.commands.push(command);
+        self.raw
+            .copy_image_to_buffer(src, src_layout, dst, regions.into_iter());
+    }
+
+    unsafe fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.commands.push(Command::Draw {
+            vertices: vertices.clone(),
+            instances: instances.clone(),
+        });
+        self.raw.draw(vertices, instances);
+    }
+
+    unsafe fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.commands.push(Command::DrawIndexed {
+            indices: indices.clone(),
+            base_vertex,
+            instances: instances.clone(),
+        });
+        self.raw.draw_indexed(indices, base_vertex, instances);
+    }
+
+    unsafe fn draw_indirect(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        let handle = self.handle(buffer);
+        self.commands.push(Command::DrawIndirect {
+            buffer: handle,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw.draw_indirect(buffer, offset, draw_count, stride);
+    }
+
+    unsafe fn draw_indexed_indirect(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        let handle = self.handle(buffer);
+        self.commands.push(Command::DrawIndexedIndirect {
+            buffer: handle,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw
+            .draw_indexed_indirect(buffer, offset, draw_count, stride);
+    }
+
+    unsafe fn draw_indirect_count(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        count_buffer: &B::Buffer,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
+    ) {
+        let command = Command::DrawIndirectCount {
+            buffer: self.handle(buffer),
+            offset,
+            count_buffer: self.handle(count_buffer),
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        };
+        self.commands.push(command);
+        self.raw.draw_indirect_count(
+            buffer,
+            offset,
+            count_buffer,
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        );
+    }
+
+    unsafe fn draw_indexed_indirect_count(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        count_buffer: &B::Buffer,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
+    ) {
+        let command = Command::DrawIndexedIndirectCount {
+            buffer: self.handle(buffer),
+            offset,
+            count_buffer: self.handle(count_buffer),
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        };
+        self.commands.push(command);
+        self.raw.draw_indexed_indirect_count(
+            buffer,
+            offset,
+            count_buffer,
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        );
+    }
+
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: Iterator<Item = (&'a B::Buffer, buffer::SubRange)>,
+    {
+        let buffers = buffers.collect::<Vec<_>>();
+        let records = buffers
+            .iter()
+            .map(|&(buffer, ref sub)| (self.handle(buffer), sub.clone()))
+            .collect();
+        self.commands.push(Command::BindTransformFeedbackBuffers {
+            first_binding,
+            buffers: records,
+        });
+        self.raw
+            .bind_transform_feedback_buffers(first_binding, buffers.into_iter());
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        self.commands.push(Command::BeginTransformFeedback);
+        self.raw.begin_transform_feedback();
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        self.commands.push(Command::EndTransformFeedback);
+        self.raw.end_transform_feedback();
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, instances: Range<InstanceCount>) {
+        self.commands
+            .push(Command::DrawTransformFeedback(instances.clone()));
+        self.raw.draw_transform_feedback(instances);
+    }
+
+    unsafe fn draw_mesh_tasks(&mut self, task_count: TaskCount, first_task: TaskCount) {
+        self.commands.push(Command::DrawMeshTasks {
+            task_count,
+            first_task,
+        });
+        self.raw.draw_mesh_tasks(task_count, first_task);
+    }
+
+    unsafe fn draw_mesh_tasks_indirect(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        let handle = self.handle(buffer);
+        self.commands.push(Command::DrawMeshTasksIndirect {
+            buffer: handle,
+            offset,
+            draw_count,
+            stride,
+        });
+        self.raw
+            .draw_mesh_tasks_indirect(buffer, offset, draw_count, stride);
+    }
+
+    unsafe fn draw_mesh_tasks_indirect_count(
+        &mut self,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        count_buffer: &B::Buffer,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        let command = Command::DrawMeshTasksIndirectCount {
+            buffer: self.handle(buffer),
+            offset,
+            count_buffer: self.handle(count_buffer),
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        };
+        self.commands.push(command);
+        self.raw.draw_mesh_tasks_indirect_count(
+            buffer,
+            offset,
+            count_buffer,
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        );
+    }
+
+    unsafe fn set_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        let handle = self.handle(event);
+        self.commands.push(Command::SetEvent {
+            event: handle,
+            stages,
+        });
+        self.raw.set_event(event, stages);
+    }
+
+    unsafe fn reset_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        let handle = self.handle(event);
+        self.commands.push(Command::ResetEvent {
+            event: handle,
+            stages,
+        });
+        self.raw.reset_event(event, stages);
+    }
+
+    unsafe fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: Iterator<Item = &'a B::Event>,
+        J: Iterator<Item = Barrier<'a, B>>,
+    {
+        let events = events.collect::<Vec<_>>();
+        let barriers = barriers.collect::<Vec<_>>();
+        let command = Command::WaitEvents {
+            events: events.iter().map(|&event| self.handle(event)).collect(),
+            stages: stages.clone(),
+            barriers: barriers.iter().map(|b| self.barrier(b)).collect(),
+        };
+        self.commands.push(command);
+        self.raw
+            .wait_events(events.into_iter(), stages, barriers.into_iter());
+    }
+
+    unsafe fn begin_query(&mut self, query: query::Query<B>, flags: query::ControlFlags) {
+        let pool = self.handle(query.pool);
+        self.commands.push(Command::BeginQuery {
+            pool,
+            id: query.id,
+            flags,
+        });
+        self.raw.begin_query(query, flags);
+    }
+
+    unsafe fn end_query(&mut self, query: query::Query<B>) {
+        let pool = self.handle(query.pool);
+        self.commands.push(Command::EndQuery { pool, id: query.id });
+        self.raw.end_query(query);
+    }
+
+    unsafe fn reset_query_pool(&mut self, pool: &B::QueryPool, queries: Range<query::Id>) {
+        let handle = self.handle(pool);
+        self.commands.push(Command::ResetQueryPool {
+            pool: handle,
+            queries: queries.clone(),
+        });
+        self.raw.reset_query_pool(pool, queries);
+    }
+
+    unsafe fn copy_query_pool_results(
+        &mut self,
+        pool: &B::QueryPool,
+        queries: Range<query::Id>,
+        buffer: &B::Buffer,
+        offset: buffer::Offset,
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
+    ) {
+        let command = Command::CopyQueryPoolResults {
+            pool: self.handle(pool),
+            queries: queries.clone(),
+            buffer: self.handle(buffer),
+            offset,
+            stride,
+            flags,
+        };
+        self.commands.push(command);
+        self.raw
+            .copy_query_pool_results(pool, queries, buffer, offset, stride, flags);
+    }
+
+    unsafe fn write_timestamp(&mut self, stage: pso::PipelineStage, query: query::Query<B>) {
+        let pool = self.handle(query.pool);
+        self.commands.push(Command::WriteTimestamp {
+            stage,
+            pool,
+            id: query.id,
+        });
+        self.raw.write_timestamp(stage, query);
+    }
+
+    unsafe fn push_graphics_constants(
+        &mut self,
+        layout: &B::PipelineLayout,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        let handle = self.handle(layout);
+        self.commands.push(Command::PushGraphicsConstants {
+            layout: handle,
+            stages,
+            offset,
+            constants: constants.to_vec(),
+        });
+        self.raw
+            .push_graphics_constants(layout, stages, offset, constants);
+    }
+
+    unsafe fn push_compute_constants(
+        &mut self,
+        layout: &B::PipelineLayout,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        let handle = self.handle(layout);
+        self.commands.push(Command::PushComputeConstants {
+            layout: handle,
+            offset,
+            constants: constants.to_vec(),
+        });
+        self.raw.push_compute_constants(layout, offset, constants);
+    }
+
+    unsafe fn execute_commands<'a, T>(&mut self, cmd_buffers: T)
+    where
+        T: Iterator<Item = &'a B::CommandBuffer>,
+    {
+        self.fail(TraceError::Unsupported("execute_commands"));
+        self.raw.execute_commands(cmd_buffers);
+    }
+
+    unsafe fn insert_debug_marker(&mut self, name: &str, color: u32) {
+        self.commands.push(Command::InsertDebugMarker {
+            name: name.to_string(),
+            color,
+        });
+        self.raw.insert_debug_marker(name, color);
+    }
+
+    unsafe fn begin_debug_marker(&mut self, name: &str, color: u32) {
+        self.commands.push(Command::BeginDebugMarker {
+            name: name.to_string(),
+            color,
+        });
+        self.raw.begin_debug_marker(name, color);
+    }
+
+    unsafe fn end_debug_marker(&mut self) {
+        self.commands.push(Command::EndDebugMarker);
+        self.raw.end_debug_marker();
+    }
+}
@@ -0,0 +1,1029 @@
+use super::{
+    clear_value, AliasedRecord, BarrierRecord, BlobId, Command, DescriptorRecord, Handle, Object,
+    Trace,
+};
+use crate::{
+    adapter::MemoryType,
+    buffer,
+    command::{CommandBuffer, CommandBufferFlags, Level, RenderAttachmentInfo},
+    device::{AllocationError, BindError, Device, OutOfMemory, ShaderError, WaitError},
+    image,
+    memory::{Aliased, Barrier, Properties, Requirements, SparseFlags},
+    pool::CommandPool,
+    pso::{
+        self, DescriptorPool, DescriptorPoolCreateFlags, DescriptorRangeDesc, DescriptorSetWrite,
+        ManifestError, ManifestObjects, PipelineStage,
+    },
+    query,
+    queue::Queue,
+    upload::{find_memory_type, ImageRegion, ImageUploader, UploadContext, UploadError},
+    Backend,
+};
+
+use std::iter;
+
+/// Error occurring while replaying a trace.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum ReplayError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+    /// Memory allocation failed.
+    #[error(transparent)]
+    Allocation(#[from] AllocationError),
+    /// Binding a resource to memory failed.
+    #[error(transparent)]
+    Bind(#[from] BindError),
+    /// Uploading the initial contents of a resource failed.
+    #[error(transparent)]
+    Upload(#[from] UploadError),
+    /// Buffer creation failed.
+    #[error(transparent)]
+    BufferCreation(#[from] buffer::CreationError),
+    /// Buffer view creation failed.
+    #[error(transparent)]
+    BufferViewCreation(#[from] buffer::ViewCreationError),
+    /// Image creation failed.
+    #[error(transparent)]
+    ImageCreation(#[from] image::CreationError),
+    /// Image view creation failed.
+    #[error(transparent)]
+    ImageViewCreation(#[from] image::ViewCreationError),
+    /// Sampler creation failed.
+    #[error(transparent)]
+    SamplerCreation(#[from] image::SamplerCreationError),
+    /// Shader module creation failed.
+    #[error(transparent)]
+    Shader(#[from] ShaderError),
+    /// Pipeline creation failed.
+    #[error(transparent)]
+    Pipeline(#[from] ManifestError),
+    /// Descriptor set allocation failed.
+    #[error(transparent)]
+    DescriptorAllocation(#[from] pso::AllocationError),
+    /// Query pool creation failed.
+    #[error(transparent)]
+    QueryPoolCreation(#[from] query::CreationError),
+    /// Waiting for the replayed commands failed.
+    #[error(transparent)]
+    Wait(#[from] WaitError),
+    /// None of the memory types satisfies the resource requirements.
+    #[error("No suitable memory type found")]
+    NoSuitableMemoryType,
+    /// The handle doesn't reference an object of the expected type.
+    #[error("Invalid object handle {0}")]
+    InvalidHandle(Handle),
+    /// The blob doesn't exist, or has an unexpected size.
+    #[error("Invalid data blob {0}")]
+    InvalidBlob(BlobId),
+}
+
+/// Object of a trace, created on the replay device.
+#[derive(Debug)]
+enum Replayed<B: Backend> {
+    Buffer(B::Buffer, B::Memory),
+    BufferView(B::BufferView),
+    Image(B::Image, B::Memory),
+    ImageView(B::ImageView),
+    Sampler(B::Sampler),
+    ShaderModule(B::ShaderModule),
+    DescriptorSetLayout(B::DescriptorSetLayout),
+    PipelineLayout(B::PipelineLayout),
+    RenderPass(B::RenderPass),
+    Framebuffer(B::Framebuffer),
+    GraphicsPipeline(B::GraphicsPipeline),
+    ComputePipeline(B::ComputePipeline),
+    DescriptorSet(B::DescriptorSet, B::DescriptorPool),
+    QueryPool(B::QueryPool),
+    Event(B::Event),
+}
+
+macro_rules! accessor {
+    ($name:ident, $variant:ident, $ty:ty) => {
+        fn $name(&self, handle: Handle) -> Result<&$ty, ReplayError> {
+            match self.objects.get(handle as usize) {
+                Some(Replayed::$variant(ref object, ..)) => Ok(object),
+                _ => Err(ReplayError::InvalidHandle(handle)),
+            }
+        }
+    };
+}
+
+fn blob(trace: &Trace, blob: BlobId) -> Result<&[u8], ReplayError> {
+    trace
+        .blobs
+        .get(blob as usize)
+        .map(Vec::as_slice)
+        .ok_or(ReplayError::InvalidBlob(blob))
+}
+
+/// Allocate device-local memory satisfying the requirements, if possible.
+unsafe fn allocate<B: Backend>(
+    device: &B::Device,
+    memory_types: &[MemoryType],
+    requirements: Requirements,
+) -> Result<B::Memory, ReplayError> {
+    let memory_type = find_memory_type(
+        memory_types,
+        requirements.type_mask,
+        Properties::DEVICE_LOCAL,
+    )
+    .or_else(|| find_memory_type(memory_types, requirements.type_mask, Properties::empty()))
+    .ok_or(ReplayError::NoSuitableMemoryType)?;
+    Ok(device.allocate_memory(memory_type, requirements.size)?)
+}
+
+/// Replayer of a [`Trace`], holding the objects of the trace created on a device.
+///
+/// See the [module documentation][super].
+#[derive(Debug)]
+pub struct Replayer<B: Backend> {
+    objects: Vec<Replayed<B>>,
+}
+
+impl<B: Backend> ManifestObjects<B> for Replayer<B> {
+    fn shader_module_key(&self, _module: &B::ShaderModule) -> Option<String> {
+        None
+    }
+    fn pipeline_layout_key(&self, _layout: &B::PipelineLayout) -> Option<String> {
+        None
+    }
+    fn render_pass_key(&self, _render_pass: &B::RenderPass) -> Option<String> {
+        None
+    }
+    fn shader_module(&self, key: &str) -> Option<&B::ShaderModule> {
+        self.shader_module(key.parse().ok()?).ok()
+    }
+    fn pipeline_layout(&self, key: &str) -> Option<&B::PipelineLayout> {
+        self.pipeline_layout(key.parse().ok()?).ok()
+    }
+    fn render_pass(&self, key: &str) -> Option<&B::RenderPass> {
+        self.render_pass(key.parse().ok()?).ok()
+    }
+}
+
+impl<B: Backend> Replayer<B> {
+    accessor!(buffer, Buffer, B::Buffer);
+    accessor!(buffer_view, BufferView, B::BufferView);
+    accessor!(image, Image, B::Image);
+    accessor!(image_view, ImageView, B::ImageView);
+    accessor!(sampler, Sampler, B::Sampler);
+    accessor!(shader_module, ShaderModule, B::ShaderModule);
+    accessor!(
+        descriptor_set_layout,
+        DescriptorSetLayout,
+        B::DescriptorSetLayout
+    );
+    accessor!(pipeline_layout, PipelineLayout, B::PipelineLayout);
+    accessor!(render_pass, RenderPass, B::RenderPass);
+    accessor!(framebuffer, Framebuffer, B::Framebuffer);
+    accessor!(graphics_pipeline, GraphicsPipeline, B::GraphicsPipeline);
+    accessor!(compute_pipeline, ComputePipeline, B::ComputePipeline);
+    accessor!(descriptor_set, DescriptorSet, B::DescriptorSet);
+    accessor!(query_pool, QueryPool, B::QueryPool);
+    accessor!(event, Event, B::Event);
+
+    /// Create the objects of a trace on the `device`, and upload their initial contents
+    /// with the `context`.
+    ///
+    /// The objects created before an error are destroyed.
+    ///
+    /// # Safety
+    ///
+    /// The device has to support the features and limits required by the objects.
+    pub unsafe fn new(
+        device: &B::Device,
+        trace: &Trace,
+        context: &mut UploadContext<B>,
+    ) -> Result<Self, ReplayError> {
+        let mut replayer = Replayer {
+            objects: Vec::with_capacity(trace.objects.len()),
+        };
+        let mut uploader = ImageUploader::new();
+        let mut result = Ok(());
+        for object in trace.objects.iter() {
+            match replayer.create(device, trace, object, context, &mut uploader) {
+                Ok(replayed) => replayer.objects.push(replayed),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        if let Err(e) = uploader.destroy(device, context) {
+            result = result.and(Err(e.into()));
+        }
+        match result {
+            Ok(()) => Ok(replayer),
+            Err(e) => {
+                replayer.destroy(device);
+                Err(e)
+            }
+        }
+    }
+
+    unsafe fn create(
+        &self,
+        device: &B::Device,
+        trace: &Trace,
+        object: &Object,
+        context: &mut UploadContext<B>,
+        uploader: &mut ImageUploader<B>,
+    ) -> Result<Replayed<B>, ReplayError> {
+        Ok(match *object {
+            Object::Buffer {
+                size,
+                usage,
+                contents,
+            } => {
+                if let Some(contents) = contents {
+                    let data = blob(trace, contents)?;
+                    if data.len() as u64 != size {
+                        return Err(ReplayError::InvalidBlob(contents));
+                    }
+                    let (buffer, memory) = device.create_buffer_with_data(data, usage, context)?;
+                    return Ok(Replayed::Buffer(buffer, memory));
+                }
+                let mut buffer = device.create_buffer(size, usage, SparseFlags::empty())?;
+                let requirements = device.get_buffer_requirements(&buffer);
+                let memory =
+                    allocate::<B>(device, context.memory_types, requirements).and_then(|memory| {
+                        match device.bind_buffer_memory(&memory, 0, &mut buffer) {
+                            Ok(()) => Ok(memory),
+                            Err(e) => {
+                                device.free_memory(memory);
+                                Err(e.into())
+                            }
+                        }
+                    });
+                match memory {
+                    Ok(memory) => Replayed::Buffer(buffer, memory),
+                    Err(e) => {
+                        device.destroy_buffer(buffer);
+                        return Err(e);
+                    }
+                }
+            }
+            Object::BufferView {
+                buffer,
+                format,
+                ref range,
+            } => Replayed::BufferView(device.create_buffer_view(
+                self.buffer(buffer)?,
+                format,
+                range.clone(),
+            )?),
+            Object::Image {
+                ref info,
+                ref contents,
+            } => {
+                let mut image = device.create_image(
+                    info.kind,
+                    info.mip_levels,
+                    info.format,
+                    info.tiling,
+                    info.usage,
+                    SparseFlags::empty(),
+                    info.view_caps,
+                )?;
+                let requirements = device.get_image_requirements(&image);
+                let memory = allocate::<B>(device, context.memory_types, requirements)
+                    .and_then(
+                        |memory| match device.bind_image_memory(&memory, 0, &mut image) {
+                            Ok(()) => Ok(memory),
+                            Err(e) => {
+                                device.free_memory(memory);
+                                Err(e.into())
+                            }
+                        },
+                    )
+                    .and_then(|memory| {
+                        let contents = match *contents {
+                            Some(ref contents) => contents,
+                            None => return Ok(memory),
+                        };
+                        let region = ImageRegion {
+                            layers: image::SubresourceLayers {
+                                aspects: info.format.surface_desc().aspects,
+                                level: 0,
+                                layers: 0..info.kind.num_layers(),
+                            },
+                            offset: image::Offset::ZERO,
+                            extent: info.kind.extent(),
+                        };
+                        let uploaded = blob(trace, contents.data).and_then(|data| {
+                            uploader
+                                .upload(
+                                    device,
+                                    context,
+                                    &image,
+                                    info.format,
+                                    &region,
+                                    data,
+                                    (image::Access::empty(), image::Layout::Undefined)
+                                        ..contents.state,
+                                    PipelineStage::TOP_OF_PIPE..PipelineStage::BOTTOM_OF_PIPE,
+                                )
+                                .map_err(ReplayError::from)
+                        });
+                        match uploaded {
+                            Ok(_) => Ok(memory),
+                            Err(e) => {
+                                device.free_memory(memory);
+                                Err(e)
+                            }
+                        }
+                    });
+                match memory {
+                    Ok(memory) => Replayed::Image(image, memory),
+                    Err(e) => {
+                        device.destroy_image(image);
+                        return Err(e);
+                    }
+                }
+            }
+            Object::ImageView {
+                image,
+                view_kind,
+                format,
+                swizzle,
+                usage,
+                ref range,
+            } => Replayed::ImageView(device.create_image_view(
+                self.image(image)?,
+                view_kind,
+                format,
+                swizzle,
+                usage,
+                range.clone(),
+            )?),
+            Object::Sampler(ref desc) => Replayed::Sampler(device.create_sampler(desc)?),
+            Object::ShaderModule { ref spirv } => {
+                Replayed::ShaderModule(device.create_shader_module(spirv)?)
+            }
+            Object::DescriptorSetLayout {
+                ref bindings,
+                ref immutable_samplers,
+            } => {
+                let immutable_samplers = immutable_samplers
+                    .iter()
+                    .map(|&sampler| self.sampler(sampler))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Replayed::DescriptorSetLayout(device.create_descriptor_set_layout(
+                    bindings.iter().cloned(),
+                    immutable_samplers.into_iter(),
+                )?)
+            }
+            Object::PipelineLayout {
+                ref set_layouts,
+                ref push_constants,
+            } => {
+                let set_layouts = set_layouts
+                    .iter()
+                    .map(|&layout| self.descriptor_set_layout(layout))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Replayed::PipelineLayout(device.create_pipeline_layout(
+                    set_layouts.into_iter(),
+                    push_constants.iter().cloned(),
+                )?)
+            }
+            Object::RenderPass {
+                ref attachments,
+                ref subpasses,
+                ref dependencies,
+            } => Replayed::RenderPass(device.create_render_pass(
+                attachments.iter().cloned(),
+                subpasses.iter().map(|subpass| subpass.to_desc()),
+                dependencies.iter().cloned(),
+            )?),
+            Object::Framebuffer {
+                render_pass,
+                ref attachments,
+                extent,
+            } => Replayed::Framebuffer(device.create_framebuffer(
+                self.render_pass(render_pass)?,
+                attachments.iter().cloned(),
+                extent,
+            )?),
+            Object::GraphicsPipeline(ref record) => {
+                let desc = record.to_desc(self)?;
+                Replayed::GraphicsPipeline(
+                    device
+                        .create_graphics_pipeline(&desc, None)
+                        .map_err(ManifestError::from)?,
+                )
+            }
+            Object::ComputePipeline(ref record) => {
+                let desc = record.to_desc(self)?;
+                Replayed::ComputePipeline(
+                    device
+                        .create_compute_pipeline(&desc, None)
+                        .map_err(ManifestError::from)?,
+                )
+            }
+            Object::DescriptorSet { layout, ref writes } => {
+                let bindings = match trace.objects.get(layout as usize) {
+                    Some(Object::DescriptorSetLayout { bindings, .. }) => bindings,
+                    _ => return Err(ReplayError::InvalidHandle(layout)),
+                };
+                let mut pool = device.create_descriptor_pool(
+                    1,
+                    bindings.iter().map(|binding| DescriptorRangeDesc {
+                        ty: binding.ty,
+                        count: binding.count,
+                    }),
+                    DescriptorPoolCreateFlags::empty(),
+                )?;
+                let set = self
+                    .descriptor_set_layout(layout)
+                    .and_then(|layout| Ok(pool.allocate_one(layout)?))
+                    .and_then(|mut set| {
+                        for write in writes.iter() {
+                            let descriptors = write
+                                .descriptors
+                                .iter()
+                                .map(|descriptor| self.descriptor(descriptor))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            device.write_descriptor_set(DescriptorSetWrite {
+                                set: &mut set,
+                                binding: write.binding,
+                                array_offset: write.array_offset,
+                                descriptors: descriptors.into_iter(),
+                            });
+                        }
+                        Ok(set)
+                    });
+                match set {
+                    Ok(set) => Replayed::DescriptorSet(set, pool),
+                    Err(e) => {
+                        device.destroy_descriptor_pool(pool);
+                        return Err(e);
+                    }
+                }
+            }
+            Object::QueryPool { ty, count } => {
+                Replayed::QueryPool(device.create_query_pool(ty, count)?)
+            }
+            Object::Event => Replayed::Event(device.create_event()?),
+        })
+    }
+
+    fn descriptor(
+        &self,
+        descriptor: &DescriptorRecord,
+    ) -> Result<pso::Descriptor<'_, B>, ReplayError> {
+        Ok(match *descriptor {
+            DescriptorRecord::Sampler(sampler) => pso::Descriptor::Sampler(self.sampler(sampler)?),
+            DescriptorRecord::Image(view, layout) => {
+                pso::Descriptor::Image(self.image_view(view)?, layout)
+            }
+            DescriptorRecord::CombinedImageSampler(view, layout, sampler) => {
+                pso::Descriptor::CombinedImageSampler(
+                    self.image_view(view)?,
+                    layout,
+                    self.sampler(sampler)?,
+                )
+            }
+            DescriptorRecord::Buffer(buffer, ref range) => {
+                pso::Descriptor::Buffer(self.buffer(buffer)?, range.clone())
+            }
+            DescriptorRecord::TexelBuffer(view) => {
+                pso::Descriptor::TexelBuffer(self.buffer_view(view)?)
+            }
+        })
+    }
+
+    fn barrier(&self, barrier: &BarrierRecord) -> Result<Barrier<'_, B>, ReplayError> {
+        Ok(match *barrier {
+            BarrierRecord::AllBuffers(ref states) => Barrier::AllBuffers(states.clone()),
+            BarrierRecord::AllImages(ref states) => Barrier::AllImages(states.clone()),
+            BarrierRecord::Buffer {
+                ref states,
+                target,
+                ref range,
+                ref families,
+            } => Barrier::Buffer {
+                states: states.clone(),
+                target: self.buffer(target)?,
+                range: range.clone(),
+                families: families.clone(),
+            },
+            BarrierRecord::Image {
+                ref states,
+                target,
+                ref range,
+                ref families,
+            } => Barrier::Image {
+                states: states.clone(),
+                target: self.image(target)?,
+                range: range.clone(),
+                families: families.clone(),
+            },
+            BarrierRecord::Aliasing { before, after } => Barrier::Aliasing {
+                before: before.map(|resource| self.aliased(resource)).transpose()?,
+                after: after.map(|resource| self.aliased(resource)).transpose()?,
+            },
+        })
+    }
+
+    fn aliased(&self, resource: AliasedRecord) -> Result<Aliased<'_, B>, ReplayError> {
+        Ok(match resource {
+            AliasedRecord::Buffer(buffer) => Aliased::Buffer(self.buffer(buffer)?),
+            AliasedRecord::Image(image) => Aliased::Image(self.image(image)?),
+        })
+    }
+
+    fn query(&self, pool: Handle, id: query::Id) -> Result<query::Query<'_, B>, ReplayError> {
+        Ok(query::Query {
+            pool: self.query_pool(pool)?,
+            id,
+        })
+    }
+
+    /// Record the commands of the trace into `cmd_buffer`, which has to be
+    /// in the recording state.
+    ///
+    /// Returns an error if a command references an invalid handle or blob, in
+    /// which case the commands preceding it are recorded.
+    ///
+    /// # Safety
+    ///
+    /// The trace has to be the one the replayer was created from.
+    pub unsafe fn record<C: CommandBuffer<B>>(
+        &self,
+        trace: &Trace,
+        cmd_buffer: &mut C,
+    ) -> Result<(), ReplayError> {
+        for command in trace.commands.iter() {
+            self.record_command(trace, command, cmd_buffer)?;
+        }
+        Ok(())
+    }
+
+    unsafe fn record_command<C: CommandBuffer<B>>(
+        &self,
+        trace: &Trace,
+        command: &Command,
+        cmd_buffer: &mut C,
+    ) -> Result<(), ReplayError> {
+        match *command {
+            Command::PipelineBarrier {
+                ref stages,
+                dependencies,
+                ref barriers,
+            } => {
+                let barriers = barriers
+                    .iter()
+                    .map(|barrier| self.barrier(barrier))
+                    .collect::<Result<Vec<_>, _>>()?;
+                cmd_buffer.pipeline_barrier(stages.clone(), dependencies, barriers.into_iter());
+            }
+            Command::FillBuffer {
+                buffer,
+                ref range,
+                data,
+            } => cmd_buffer.fill_buffer(self.buffer(buffer)?, range.clone(), data),
+            Command::UpdateBuffer {
+                buffer,
+                offset,
+                data,
+            } => cmd_buffer.update_buffer(self.buffer(buffer)?, offset, blob(trace, data)?),
+            Command::ClearImage {
+                image,
+                layout,
+                value,
+                ref subresource_ranges,
+            } => cmd_buffer.clear_image(
+                self.image(image)?,
+                layout,
+                clear_value(value),
+                subresource_ranges.iter().cloned(),
+            ),
+            Command::ClearAttachments {
+                ref clears,
+                ref rects,
+            } => cmd_buffer.clear_attachments(
+                clears.iter().map(|clear| clear.into()),
+                rects.iter().cloned(),
+            ),
+            Command::ResolveImage {
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                ref regions,
+            } => cmd_buffer.resolve_image(
+                self.image(src)?,
+                src_layout,
+                self.image(dst)?,
+                dst_layout,
+                regions.iter().cloned(),
+            ),
+            Command::BlitImage {
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                filter,
+                ref regions,
+            } => cmd_buffer.blit_image(
+                self.image(src)?,
+                src_layout,
+                self.image(dst)?,
+                dst_layout,
+                filter,
+                regions.iter().cloned(),
+            ),
+            Command::BindIndexBuffer {
+                buffer,
+                ref sub,
+                ty,
+            } => cmd_buffer.bind_index_buffer(self.buffer(buffer)?, sub.clone(), ty),
+            Command::BindVertexBuffers {
+                first_binding,
+                ref buffers,
+            } => {
+                let buffers = buffers
+                    .iter()
+                    .map(|&(buffer, ref sub)| Ok((self.buffer(buffer)?, sub.clone())))
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                cmd_buffer.bind_vertex_buffers(first_binding, buffers.into_iter());
+            }
+            Command::SetViewports {
+                first_viewport,
+                ref viewports,
+            } => cmd_buffer.set_viewports(first_viewport, viewports.iter().cloned()),
+            Command::SetScissors {
+                first_scissor,
+                ref rects,
+            } => cmd_buffer.set_scissors(first_scissor, rects.iter().cloned()),
+            Command::SetStencilReference { faces, value } => {
+                cmd_buffer.set_stencil_reference(faces, value)
+            }
+            Command::SetStencilReadMask { faces, value } => {
+                cmd_buffer.set_stencil_read_mask(faces, value)
+            }
+            Command::SetStencilWriteMask { faces, value } => {
+                cmd_buffer.set_stencil_write_mask(faces, value)
+            }
+            Command::SetBlendConstants(color) => cmd_buffer.set_blend_constants(color),
+            Command::SetDepthBounds(ref bounds) => cmd_buffer.set_depth_bounds(bounds.clone()),
+            Command::SetLineWidth(width) => cmd_buffer.set_line_width(width),
+            Command::SetDepthBias(depth_bias) => cmd_buffer.set_depth_bias(depth_bias),
+            Command::SetShadingRate { rate, combiners } => {
+                cmd_buffer.set_shading_rate(rate, combiners)
+            }
+            Command::BeginRenderPass {
+                render_pass,
+                framebuffer,
+                render_area,
+                ref attachments,
+                first_subpass,
+            } => {
+                let attachments = attachments
+                    .iter()
+                    .map(|&(view, value)| {
+                        Ok(RenderAttachmentInfo {
+                            image_view: self.image_view(view)?,
+                            clear_value: clear_value(value),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                cmd_buffer.begin_render_pass(
+                    self.render_pass(render_pass)?,
+                    self.framebuffer(framebuffer)?,
+                    render_area,
+                    attachments.into_iter(),
+                    first_subpass,
+                );
+            }
+            Command::NextSubpass(contents) => cmd_buffer.next_subpass(contents),
+            Command::EndRenderPass => cmd_buffer.end_render_pass(),
+            Command::BindGraphicsPipeline(pipeline) => {
+                cmd_buffer.bind_graphics_pipeline(self.graphics_pipeline(pipeline)?)
+            }
+            Command::BindGraphicsDescriptorSets {
+                layout,
+                first_set,
+                ref sets,
+                ref offsets,
+            } => {
+                let sets = sets
+                    .iter()
+                    .map(|&set| self.descriptor_set(set))
+                    .collect::<Result<Vec<_>, _>>()?;
+                cmd_buffer.bind_graphics_descriptor_sets(
+                    self.pipeline_layout(layout)?,
+                    first_set,
+                    sets.into_iter(),
+                    offsets.iter().cloned(),
+                );
+            }
+            Command::BindComputePipeline(pipeline) => {
+                cmd_buffer.bind_compute_pipeline(self.compute_pipeline(pipeline)?)
+            }
+            Command::BindComputeDescriptorSets {
+                layout,
+                first_set,
+                ref sets,
+                ref offsets,
+            } => {
+                let sets = sets
+                    .iter()
+                    .map(|&set| self.descriptor_set(set))
+                    .collect::<Result<Vec<_>, _>>()?;
+                cmd_buffer.bind_compute_descriptor_sets(
+                    self.pipeline_layout(layout)?,
+                    first_set,
+                    sets.into_iter(),
+                    offsets.iter().cloned(),
+                );
+            }
+            Command::Dispatch(count) => cmd_buffer.dispatch(count),
+            Command::DispatchIndirect { buffer, offset } => {
+                cmd_buffer.dispatch_indirect(self.buffer(buffer)?, offset)
+            }
+            Command::CopyBuffer {
+                src,
+                dst,
+                ref regions,
+            } => cmd_buffer.copy_buffer(
+                self.buffer(src)?,
+                self.buffer(dst)?,
+                regions.iter().cloned(),
+            ),
+            Command::CopyImage {
+                src,
+                src_layout,
+                dst,
+                dst_layout,
+                ref regions,
+            } => cmd_buffer.copy_image(
+                self.image(src)?,
+                src_layout,
+                self.image(dst)?,
+                dst_layout,
+                regions.iter().cloned(),
+            ),
+            Command::CopyBufferToImage {
+                src,
+                dst,
+                dst_layout,
+                ref regions,
+            } => cmd_buffer.copy_buffer_to_image(
+                self.buffer(src)?,
+                self.image(dst)?,
+                dst_layout,
+                regions.iter().cloned(),
+            ),
+            Command::CopyImageToBuffer {
+                src,
+                src_layout,
+                dst,
+                ref regions,
+            } => cmd_buffer.copy_image_to_buffer(
+                self.image(src)?,
+                src_layout,
+                self.buffer(dst)?,
+                regions.iter().cloned(),
+            ),
+            Command::Draw {
+                ref vertices,
+                ref instances,
+            } => cmd_buffer.draw(vertices.clone(), instances.clone()),
+            Command::DrawIndexed {
+                ref indices,
+                base_vertex,
+                ref instances,
+            } => cmd_buffer.draw_indexed(indices.clone(), base_vertex, instances.clone()),
+            Command::DrawIndirect {
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => cmd_buffer.draw_indirect(self.buffer(buffer)?, offset, draw_count, stride),
+            Command::DrawIndexedIndirect {
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => cmd_buffer.draw_indexed_indirect(self.buffer(buffer)?, offset, draw_count, stride),
+            Command::DrawIndirectCount {
+                buffer,
+                offset,
+                count_buffer,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            } => cmd_buffer.draw_indirect_count(
+                self.buffer(buffer)?,
+                offset,
+                self.buffer(count_buffer)?,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            ),
+            Command::DrawIndexedIndirectCount {
+                buffer,
+                offset,
+                count_buffer,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            } => cmd_buffer.draw_indexed_indirect_count(
+                self.buffer(buffer)?,
+                offset,
+                self.buffer(count_buffer)?,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            ),
+            Command::BindTransformFeedbackBuffers {
+                first_binding,
+                ref buffers,
+            } => {
+                let buffers = buffers
+                    .iter()
+                    .map(|&(buffer, ref sub)| Ok((self.buffer(buffer)?, sub.clone())))
+                    .collect::<Result<Vec<_>, ReplayError>>()?;
+                cmd_buffer.bind_transform_feedback_buffers(first_binding, buffers.into_iter());
+            }
+            Command::BeginTransformFeedback => cmd_buffer.begin_transform_feedback(),
+            Command::EndTransformFeedback => cmd_buffer.end_transform_feedback(),
+            Command::DrawTransformFeedback(ref instances) => {
+                cmd_buffer.draw_transform_feedback(instances.clone())
+            }
+            Command::DrawMeshTasks {
+                task_count,
+                first_task,
+            } => cmd_buffer.draw_mesh_tasks(task_count, first_task),
+            Command::DrawMeshTasksIndirect {
+                buffer,
+                offset,
+                draw_count,
+                stride,
+            } => cmd_buffer.draw_mesh_tasks_indirect(
+                self.buffer(buffer)?,
+                offset,
+                draw_count,
+                stride,
+            ),
+            Command::DrawMeshTasksIndirectCount {
+                buffer,
+                offset,
+                count_buffer,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            } => cmd_buffer.draw_mesh_tasks_indirect_count(
+                self.buffer(buffer)?,
+                offset,
+                self.buffer(count_buffer)?,
+                count_buffer_offset,
+                max_draw_count,
+                stride,
+            ),
+            Command::SetEvent { event, stages } => cmd_buffer.set_event(self.event(event)?, stages),
+            Command::ResetEvent { event, stages } => {
+                cmd_buffer.reset_event(self.event(event)?, stages)
+            }
+            Command::WaitEvents {
+                ref events,
+                ref stages,
+                ref barriers,
+            } => {
+                let events = events
+                    .iter()
+                    .map(|&event| self.event(event))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let barriers = barriers
+                    .iter()
+                    .map(|barrier| self.barrier(barrier))
+                    .collect::<Result<Vec<_>, _>>()?;
+                cmd_buffer.wait_events(events.into_iter(), stages.clone(), barriers.into_iter());
+            }
+            Command::BeginQuery { pool, id, flags } => {
+                cmd_buffer.begin_query(self.query(pool, id)?, flags)
+            }
+            Command::EndQuery { pool, id } => cmd_buffer.end_query(self.query(pool, id)?),
+            Command::ResetQueryPool { pool, ref queries } => {
+                cmd_buffer.reset_query_pool(self.query_pool(pool)?, queries.clone())
+            }
+            Command::CopyQueryPoolResults {
+                pool,
+                ref queries,
+                buffer,
+                offset,
+                stride,
+                flags,
+            } => cmd_buffer.copy_query_pool_results(
+                self.query_pool(pool)?,
+                queries.clone(),
+                self.buffer(buffer)?,
+                offset,
+                stride,
+                flags,
+            ),
+            Command::WriteTimestamp { stage, pool, id } => {
+                cmd_buffer.write_timestamp(stage, self.query(pool, id)?)
+            }
+            Command::PushGraphicsConstants {
+                layout,
+                stages,
+                offset,
+                ref constants,
+            } => cmd_buffer.push_graphics_constants(
+                self.pipeline_layout(layout)?,
+                stages,
+                offset,
+                constants,
+            ),
+            Command::PushComputeConstants {
+                layout,
+                offset,
+                ref constants,
+            } => {
+                cmd_buffer.push_compute_constants(self.pipeline_layout(layout)?, offset, constants)
+            }
+            Command::InsertDebugMarker { ref name, color } => {
+                cmd_buffer.insert_debug_marker(name, color)
+            }
+            Command::BeginDebugMarker { ref name, color } => {
+                cmd_buffer.begin_debug_marker(name, color)
+            }
+            Command::EndDebugMarker => cmd_buffer.end_debug_marker(),
+        }
+        Ok(())
+    }
+
+    /// Record the commands of the trace into a one-time command buffer, submit
+    /// it to the queue of the `context` and wait for it to complete.
+    ///
+    /// # Safety
+    ///
+    /// The trace has to be the one the replayer was created from, and the queue
+    /// has to support its commands.
+    pub unsafe fn execute(
+        &self,
+        device: &B::Device,
+        trace: &Trace,
+        context: &mut UploadContext<B>,
+    ) -> Result<(), ReplayError> {
+        let mut cmd_buffer = context.command_pool.allocate_one(Level::Primary);
+        cmd_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+        let recorded = self.record(trace, &mut cmd_buffer);
+        cmd_buffer.finish();
+        let result = recorded.and_then(|()| {
+            let mut fence = device.create_fence(false)?;
+            let submitted = context
+                .queue
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::empty(),
+                    Some(&mut fence),
+                )
+                .map_err(WaitError::from)
+                .and_then(|()| device.wait_for_fence(&fence, !0));
+            device.destroy_fence(fence);
+            submitted.map(|_| ()).map_err(ReplayError::from)
+        });
+        context.command_pool.free(iter::once(cmd_buffer));
+        result
+    }
+
+    /// Destroy the objects created on the `device`.
+    ///
+    /// # Safety
+    ///
+    /// The objects must not be used by any pending command buffer.
+    pub unsafe fn destroy(self, device: &B::Device) {
+        for object in self.objects.into_iter().rev() {
+            match object {
+                Replayed::Buffer(buffer, memory) => {
+                    device.destroy_buffer(buffer);
+                    device.free_memory(memory);
+                }
+                Replayed::BufferView(view) => device.destroy_buffer_view(view),
+                Replayed::Image(image, memory) => {
+                    device.destroy_image(image);
+                    device.free_memory(memory);
+                }
+                Replayed::ImageView(view) => device.destroy_image_view(view),
+                Replayed::Sampler(sampler) => device.destroy_sampler(sampler),
+                Replayed::ShaderModule(module) => device.destroy_shader_module(module),
+                Replayed::DescriptorSetLayout(layout) => {
+                    device.destroy_descriptor_set_layout(layout)
+                }
+                Replayed::PipelineLayout(layout) => device.destroy_pipeline_layout(layout),
+                Replayed::RenderPass(render_pass) => device.destroy_render_pass(render_pass),
+                Replayed::Framebuffer(framebuffer) => device.destroy_framebuffer(framebuffer),
+                Replayed::GraphicsPipeline(pipeline) => device.destroy_graphics_pipeline(pipeline),
+                Replayed::ComputePipeline(pipeline) => device.destroy_compute_pipeline(pipeline),
+                Replayed::DescriptorSet(_, pool) => device.destroy_descriptor_pool(pool),
+                Replayed::QueryPool(pool) => device.destroy_query_pool(pool),
+                Replayed::Event(event) => device.destroy_event(event),
+            }
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! Tracking of the image layouts on the host.
+//!
+//! Image layouts have no effect on some backends, such as GL, but using an image
+//! in a different layout than the one it's in results in undefined behavior on
+//! others, such as Vulkan and DX12. [`LayoutTracker`] mirrors the state of every
+//! subresource of the registered images, validates the recorded barriers and
+//! render passes against it, and reports the mismatches using the image names.
+//!
+//! The tracker can also compute the barriers transitioning an image from its
+//! tracked state, see [`LayoutTracker::transition`].
+//!
+//! The commands are tracked in the order they are passed to the tracker, which
+//! has to match the order they execute in on the device. Images are identified
+//! by the [`ImageId`] returned when registering them, which stays valid if the
+//! image is moved, and is never reused once the image is unregistered.
+//!
+//! This module is only available with the `layout-tracking` feature.
+
+use crate::{
+    format::Format,
+    image::{Access, Layer, Layout, Level, State, SubresourceRange},
+    memory::Barrier,
+    pass::Attachment,
+    Backend,
+};
+
+use std::{collections::HashMap, marker::PhantomData, ops::Range};
+
+/// Identifier of an image registered to a [`LayoutTracker`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct ImageId(u64);
+
+/// Error reported by the layout tracker.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum LayoutError {
+    /// The image hasn't been registered.
+    #[error("Image is not registered")]
+    UnknownImage,
+    /// The subresource range exceeds the image.
+    #[error("Subresource range {range:?} exceeds image `{name}`")]
+    OutOfRange {
+        /// Name of the image.
+        name: String,
+        /// Offending range.
+        range: SubresourceRange,
+    },
+    /// A subresource is used in a different layout than the one it's in.
+    #[error(
+        "Image `{name}` level {level} layer {layer} is in layout {actual:?}, but {expected:?} is expected"
+    )]
+    Mismatch {
+        /// Name of the image.
+        name: String,
+        /// Mip level of the subresource.
+        level: Level,
+        /// Array layer of the subresource.
+        layer: Layer,
+        /// Layout expected by the command.
+        expected: Layout,
+        /// Tracked layout of the subresource.
+        actual: Layout,
+    },
+}
+
+#[derive(Debug)]
+struct TrackedImage {
+    name: String,
+    levels: Level,
+    layers: Layer,
+    /// States of the subresources, indexed by `level * layers + layer`.
+    states: Vec<State>,
+}
+
+impl TrackedImage {
+    fn resolve(
+        &self,
+        range: &SubresourceRange,
+    ) -> Result<(Range<Level>, Range<Layer>), LayoutError> {
+        let level_end = match range.level_count {
+            Some(count) => range.level_start.checked_add(count),
+            None => Some(self.levels),
+        };
+        let layer_end = match range.layer_count {
+            Some(count) => range.layer_start.checked_add(count),
+            None => Some(self.layers),
+        };
+        match (level_end, layer_end) {
+            (Some(level_end), Some(layer_end))
+                if level_end <= self.levels && layer_end <= self.layers =>
+            {
+                Ok((range.level_start..level_end, range.layer_start..layer_end))
+            }
+            _ => Err(LayoutError::OutOfRange {
+                name: self.name.clone(),
+                range: range.clone(),
+            }),
+        }
+    }
+
+    fn index(&self, level: Level, layer: Layer) -> usize {
+        level as usize * self.layers as usize + layer as usize
+    }
+
+    /// Check that the range is in the `expected` layout, and move it to `state`.
+    ///
+    /// The state is updated even if the check fails, so that a single mistake
+    /// isn't reported by all the following commands.
+    fn update(
+        &mut self,
+        range: &SubresourceRange,
+        expected: Layout,
+        state: State,
+    ) -> Result<(), LayoutError> {
+        let (levels, layers) = self.resolve(range)?;
+        let mut result = Ok(());
+        for level in levels {
+            for layer in layers.clone() {
+                let index = self.index(level, layer);
+                let actual = self.states[index].1;
+                // Transitions from `Undefined` discard the contents, whatever the layout is.
+                if expected != Layout::Undefined && actual != expected && result.is_ok() {
+                    result = Err(LayoutError::Mismatch {
+                        name: self.name.clone(),
+                        level,
+                        layer,
+                        expected,
+                        actual,
+                    });
+                }
+                self.states[index] = state;
+            }
+        }
+        result
+    }
+
+    /// Move the range to `state`, returning the subresources that weren't in it
+    /// with their previous state.
+    ///
+    /// Subresources sharing the same state are merged per mip level.
+    fn transition(
+        &mut self,
+        range: &SubresourceRange,
+        state: State,
+    ) -> Result<Vec<(SubresourceRange, State)>, LayoutError> {
+        let (levels, layers) = self.resolve(range)?;
+        let mut transitions = Vec::new();
+        for level in levels {
+            let mut layer = layers.start;
+            while layer < layers.end {
+                let current = self.states[self.index(level, layer)];
+                let run = (layer..layers.end)
+                    .take_while(|&l| self.states[self.index(level, l)] == current)
+                    .count() as Layer;
+                if current != state {
+                    transitions.push((
+                        SubresourceRange {
+                            aspects: range.aspects,
+                            level_start: level,
+                            level_count: Some(1),
+                            layer_start: layer,
+                            layer_count: Some(run),
+                        },
+                        current,
+                    ));
+                    for l in layer..layer + run {
+                        let index = self.index(level, l);
+                        self.states[index] = state;
+                    }
+                }
+                layer += run;
+            }
+        }
+        Ok(transitions)
+    }
+}
+
+/// Host-side mirror of the layouts of the registered images.
+#[derive(Debug)]
+pub struct LayoutTracker<B: Backend> {
+    images: HashMap<ImageId, TrackedImage>,
+    next_id: u64,
+    phantom: PhantomData<B>,
+}
+
+impl<B: Backend> Default for LayoutTracker<B> {
+    fn default() -> Self {
+        LayoutTracker {
+            images: HashMap::new(),
+            next_id: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<B: Backend> LayoutTracker<B> {
+    /// Create a new tracker, with no image registered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn image_mut(&mut self, id: ImageId) -> Result<&mut TrackedImage, LayoutError> {
+        self.images.get_mut(&id).ok_or(LayoutError::UnknownImage)
+    }
+
+    /// Start tracking an image with the given number of mip levels and array layers,
+    /// all of them being in the `initial` layout.
+    ///
+    /// The `name` is used to report the errors involving the image, and the
+    /// returned id to refer to it in the other calls.
+    pub fn register_image(
+        &mut self,
+        name: &str,
+        levels: Level,
+        layers: Layer,
+        initial: Layout,
+    ) -> ImageId {
+        let id = ImageId(self.next_id);
+        self.next_id += 1;
+        let count = levels as usize * layers as usize;
+        self.images.insert(
+            id,
+            TrackedImage {
+                name: name.to_string(),
+                levels,
+                layers,
+                states: vec![(Access::empty(), initial); count],
+            },
+        );
+        id
+    }
+
+    /// Stop tracking an image, which has to be done before it's destroyed.
+    pub fn unregister_image(&mut self, id: ImageId) {
+        self.images.remove(&id);
+    }
+
+    /// Get the tracked state of a subresource.
+    pub fn state(&self, id: ImageId, level: Level, layer: Layer) -> Option<State> {
+        let tracked = self.images.get(&id)?;
+        if level < tracked.levels && layer < tracked.layers {
+            Some(tracked.states[tracked.index(level, layer)])
+        } else {
+            None
+        }
+    }
+
+    /// Check that a subresource range is in the given layout, for example before
+    /// copying from it or binding it to a descriptor set.
+    pub fn check(
+        &self,
+        id: ImageId,
+        range: &SubresourceRange,
+        layout: Layout,
+    ) -> Result<(), LayoutError> {
+        let tracked = self.images.get(&id).ok_or(LayoutError::UnknownImage)?;
+        let (levels, layers) = tracked.resolve(range)?;
+        for level in levels {
+            for layer in layers.clone() {
+                let actual = tracked.states[tracked.index(level, layer)].1;
+                if actual != layout {
+                    return Err(LayoutError::Mismatch {
+                        name: tracked.name.clone(),
+                        level,
+                        layer,
+                        expected: layout,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate and apply the layout transitions of the barriers passed to
+    /// [`pipeline_barrier`][crate::command::CommandBuffer::pipeline_barrier],
+    /// each paired with the id of its target image. Other barriers are ignored.
+    ///
+    /// All the barriers are applied, and the first mismatch is returned.
+    pub fn pipeline_barrier<'a, 'b: 'a, I>(&mut self, barriers: I) -> Result<(), LayoutError>
+    where
+        I: IntoIterator<Item = (ImageId, &'a Barrier<'b, B>)>,
+    {
+        let mut result = Ok(());
+        for (id, barrier) in barriers {
+            if let Barrier::Image {
+                ref states,
+                ref range,
+                ..
+            } = *barrier
+            {
+                let updated = self
+                    .image_mut(id)
+                    .and_then(|tracked| tracked.update(range, states.start.1, states.end));
+                if result.is_ok() {
+                    result = updated;
+                }
+            }
+        }
+        result
+    }
+
+    /// Validate and apply the layout transitions of a render pass, given its
+    /// attachments and the ids of the images and subresources bound to each of them.
+    ///
+    /// All the attachments are applied, and the first mismatch is returned.
+    pub fn render_pass<I>(
+        &mut self,
+        attachments: &[Attachment],
+        images: I,
+    ) -> Result<(), LayoutError>
+    where
+        I: IntoIterator<Item = (ImageId, SubresourceRange)>,
+    {
+        let mut result = Ok(());
+        for (attachment, (id, range)) in attachments.iter().zip(images) {
+            let access = match attachment.format {
+                Some(format) if !Format::is_color(format) => Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                _ => Access::COLOR_ATTACHMENT_WRITE,
+            };
+            let updated = self.image_mut(id).and_then(|tracked| {
+                tracked.update(
+                    &range,
+                    attachment.layouts.start,
+                    (access, attachment.layouts.end),
+                )
+            });
+            if result.is_ok() {
+                result = updated;
+            }
+        }
+        result
+    }
+
+    /// Compute the barriers transitioning a subresource range of the image
+    /// registered as `id` from its tracked state to `state`, and apply them to
+    /// the tracker. The barriers target `image`.
+    ///
+    /// Subresources sharing the same state are merged into a single barrier
+    /// per mip level. The subresources already in `state` are skipped, so write
+    /// accesses that need to be ordered with each other require an explicit barrier.
+    pub fn transition<'a>(
+        &mut self,
+        id: ImageId,
+        image: &'a B::Image,
+        range: &SubresourceRange,
+        state: State,
+    ) -> Result<Vec<Barrier<'a, B>>, LayoutError> {
+        let transitions = self.image_mut(id)?.transition(range, state)?;
+        Ok(transitions
+            .into_iter()
+            .map(|(range, current)| Barrier::Image {
+                states: current..state,
+                target: image,
+                range,
+                families: None,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Aspects;
+
+    fn image(levels: Level, layers: Layer, layout: Layout) -> TrackedImage {
+        TrackedImage {
+            name: "color".to_string(),
+            levels,
+            layers,
+            states: vec![(Access::empty(), layout); levels as usize * layers as usize],
+        }
+    }
+
+    fn range(level_start: Level, level_count: Option<Level>) -> SubresourceRange {
+        SubresourceRange {
+            aspects: Aspects::COLOR,
+            level_start,
+            level_count,
+            layer_start: 0,
+            layer_count: None,
+        }
+    }
+
+    #[test]
+    fn resolve_reports_overflowing_ranges() {
+        let tracked = image(2, 1, Layout::General);
+        assert_eq!(tracked.resolve(&range(0, None)), Ok((0..2, 0..1)));
+        assert_eq!(tracked.resolve(&range(1, Some(1))), Ok((1..2, 0..1)));
+        for range in &[range(1, Some(2)), range(1, Some(Level::MAX))] {
+            match tracked.resolve(range) {
+                Err(LayoutError::OutOfRange { ref name, .. }) => assert_eq!(name, "color"),
+                other => panic!("Unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn update_applies_the_state_despite_a_mismatch() {
+        let mut tracked = image(2, 1, Layout::General);
+        let state = (Access::SHADER_READ, Layout::ShaderReadOnlyOptimal);
+        match tracked.update(&range(0, None), Layout::TransferDstOptimal, state) {
+            Err(LayoutError::Mismatch {
+                level: 0,
+                layer: 0,
+                expected: Layout::TransferDstOptimal,
+                actual: Layout::General,
+                ..
+            }) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(tracked.states, vec![state; 2]);
+        // Transitions from `Undefined` don't check the layout.
+        let state = (Access::TRANSFER_WRITE, Layout::TransferDstOptimal);
+        assert_eq!(
+            tracked.update(&range(1, Some(1)), Layout::Undefined, state),
+            Ok(())
+        );
+        assert_eq!(tracked.states[1], state);
+    }
+
+    #[test]
+    fn transition_merges_the_layers_in_the_same_state() {
+        let mut tracked = image(2, 4, Layout::Undefined);
+        let read = (Access::SHADER_READ, Layout::ShaderReadOnlyOptimal);
+        let write = (Access::TRANSFER_WRITE, Layout::TransferDstOptimal);
+        tracked.states[2] = read;
+
+        let transitions = tracked.transition(&range(0, Some(1)), read).unwrap();
+        let runs = transitions
+            .iter()
+            .map(|(range, state)| (range.layer_start, range.layer_count, state.1))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            vec![
+                (0, Some(2), Layout::Undefined),
+                (3, Some(1), Layout::Undefined)
+            ]
+        );
+        assert_eq!(&tracked.states[..4], &[read; 4]);
+
+        // One barrier per level, and none for the subresources already in the state.
+        let transitions = tracked.transition(&range(0, None), write).unwrap();
+        assert_eq!(transitions.len(), 2);
+        assert!(tracked
+            .transition(&range(0, None), write)
+            .unwrap()
+            .is_empty());
+    }
+}
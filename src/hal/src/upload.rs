@@ -0,0 +1,1516 @@
+//! Resource creation with initial contents.
+//!
+//! Filling a device-local resource requires creating a CPU-visible staging buffer,
+//! recording a copy together with the appropriate barriers, submitting it and waiting
+//! for it to complete. The helpers in this module perform this sequence on behalf of
+//! the user, and back [`Device::create_buffer_with_data`] and
+//! [`Device::create_image_with_data`].
+//!
+//! If the device exposes a dedicated transfer queue, the copies can be routed to it
+//! by providing a [`TransferQueue`]. The ownership of the uploaded resources is then
+//! released by the transfer queue and acquired by the main queue of the context,
+//! with a semaphore ordering the two submissions.
+//!
+//! Transfers into and out of existing images are handled by [`ImageUploader`],
+//! which reuses its staging buffer and doesn't block on the submitted copies.
+//! Its uploads are routed to the transfer queue as well, if there is one.
+
+use crate::{
+    adapter::MemoryType,
+    buffer,
+    command::{BufferCopy, BufferImageCopy, CommandBuffer, CommandBufferFlags, Level},
+    device::{AllocationError, BindError, Device, MapError, OutOfMemory, WaitError},
+    format,
+    frame::StagingBuffer,
+    image,
+    memory::{Barrier, Dependencies, Properties, Segment},
+    pool::CommandPool,
+    pso::PipelineStage,
+    queue::{Queue, QueueFamilyId},
+    Backend, Limits, MemoryTypeId,
+};
+
+use std::{iter, ops::Range, ptr};
+
+/// Error occurring while creating a resource with initial data.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum UploadError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] OutOfMemory),
+    /// Memory allocation failed.
+    #[error(transparent)]
+    Allocation(#[from] AllocationError),
+    /// Buffer creation failed.
+    #[error(transparent)]
+    BufferCreation(#[from] buffer::CreationError),
+    /// Image creation failed.
+    #[error(transparent)]
+    ImageCreation(#[from] image::CreationError),
+    /// Binding a resource to memory failed.
+    #[error(transparent)]
+    Bind(#[from] BindError),
+    /// Mapping the staging memory failed.
+    #[error(transparent)]
+    Map(#[from] MapError),
+    /// Waiting for the upload to complete failed.
+    #[error(transparent)]
+    Wait(#[from] WaitError),
+    /// None of the memory types satisfies the resource requirements.
+    #[error("No suitable memory type found")]
+    NoSuitableMemoryType,
+    /// The given data has a different size than the target resource.
+    #[error("The given data has a different size ({0:}) than the target resource")]
+    Data(usize),
+    /// The given data is empty, while resources can't be empty.
+    #[error("The given data is empty")]
+    EmptyData,
+    /// No download is pending to read the data back from.
+    #[error("No download is pending")]
+    NoDownload,
+}
+
+/// A dedicated queue that the staging copies can be offloaded to.
+///
+/// The command pool has to be created for the queue family `family`.
+#[derive(Debug)]
+pub struct TransferQueue<'a, B: Backend> {
+    /// Queue that executes the copy commands.
+    pub queue: &'a mut B::Queue,
+    /// Pool used to allocate the transient command buffers.
+    pub command_pool: &'a mut B::CommandPool,
+    /// Family of the transfer queue.
+    pub family: QueueFamilyId,
+}
+
+/// Everything needed to schedule the staging copies of an upload.
+///
+/// The command pool has to be created for the queue family `family`.
+#[derive(Debug)]
+pub struct UploadContext<'a, B: Backend> {
+    /// Queue that uses the uploaded resources, and executes the copy commands
+    /// unless a `transfer` queue is given.
+    pub queue: &'a mut B::Queue,
+    /// Pool used to allocate the transient command buffers.
+    pub command_pool: &'a mut B::CommandPool,
+    /// Family of the queue.
+    pub family: QueueFamilyId,
+    /// Optional dedicated queue to execute the copy commands on.
+    ///
+    /// Backends with a single queue, such as GL, only support `None` here.
+    pub transfer: Option<TransferQueue<'a, B>>,
+    /// Memory types of the physical device, as returned by
+    /// [`memory_properties`][crate::adapter::PhysicalDevice::memory_properties].
+    pub memory_types: &'a [MemoryType],
+    /// Limits of the physical device.
+    pub limits: &'a Limits,
+}
+
+/// Find the first memory type allowed by `type_mask` that has all the requested properties.
+pub fn find_memory_type(
+    memory_types: &[MemoryType],
+    type_mask: u32,
+    properties: Properties,
+) -> Option<MemoryTypeId> {
+    memory_types
+        .iter()
+        .enumerate()
+        .position(|(id, ty)| type_mask & (1 << id) != 0 && ty.properties.contains(properties))
+        .map(MemoryTypeId)
+}
+
+/// Return the row pitch, in bytes, of tightly packed data of the given format and width.
+pub fn packed_row_pitch(format: format::Format, width: image::Size) -> u32 {
+    let desc = format.surface_desc();
+    let blocks = (width + desc.dim.0 as u32 - 1) / desc.dim.0 as u32;
+    blocks * (desc.bits as u32 / 8)
+}
+
+fn align(value: u64, alignment: u64) -> u64 {
+    if alignment <= 1 {
+        value
+    } else {
+        (value + alignment - 1) / alignment * alignment
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// Size of a block of `aspect` in the copies, in bytes.
+///
+/// The depth and stencil of a format with both aspects are copied separately,
+/// the depth taking 2 or 4 bytes and the stencil a single byte.
+fn aspect_block_size(format: format::Format, aspect: format::Aspects) -> u64 {
+    let desc = format.surface_desc();
+    match aspect {
+        _ if aspect == desc.aspects => desc.bits as u64 / 8,
+        format::Aspects::DEPTH if format.base_format().0.describe_bits().depth > 16 => 4,
+        format::Aspects::DEPTH => 2,
+        format::Aspects::STENCIL => 1,
+        _ => desc.bits as u64 / 8,
+    }
+}
+
+/// Placement of the texels of an aspect of an image region inside a staging buffer.
+///
+/// The rows are padded to the optimal copy pitch alignment of the device (256 bytes
+/// on DX12), while the user data is expected to be tightly packed.
+#[derive(Clone, Debug)]
+struct StagingLayout {
+    /// Aspect of the image being copied.
+    aspect: format::Aspects,
+    /// Offset of the first row in the staging buffer.
+    offset: u64,
+    /// Size of a block, in bytes.
+    block_size: u64,
+    /// Dimensions of a block, in texels.
+    block_dim: (u8, u8),
+    /// Pitch of a row of blocks in the user data.
+    packed_pitch: u64,
+    /// Pitch of a row of blocks in the staging buffer.
+    row_pitch: u64,
+    /// Number of block rows per depth slice.
+    rows: u64,
+    /// Number of depth slices, across all the layers.
+    slices: u64,
+}
+
+impl StagingLayout {
+    fn new(
+        format: format::Format,
+        aspect: format::Aspects,
+        extent: image::Extent,
+        layers: image::Layer,
+        limits: &Limits,
+        offset: u64,
+    ) -> Self {
+        let desc = format.surface_desc();
+        let block_size = aspect_block_size(format, aspect);
+        let blocks = (extent.width + desc.dim.0 as u32 - 1) / desc.dim.0 as u32;
+        let packed_pitch = blocks as u64 * block_size;
+        // The pitch is expressed in texels by the copy commands,
+        // so it has to be a multiple of the block size as well.
+        let alignment = limits.optimal_buffer_copy_pitch_alignment.max(1);
+        let alignment = alignment / gcd(alignment, block_size) * block_size;
+        StagingLayout {
+            aspect,
+            offset,
+            block_size,
+            block_dim: desc.dim,
+            packed_pitch,
+            row_pitch: align(packed_pitch, alignment),
+            rows: ((extent.height + desc.dim.1 as u32 - 1) / desc.dim.1 as u32) as u64,
+            slices: extent.depth as u64 * layers as u64,
+        }
+    }
+
+    /// Size of the tightly packed user data.
+    fn packed_size(&self) -> u64 {
+        self.packed_pitch * self.rows * self.slices
+    }
+
+    /// Size of the rows in the staging buffer.
+    fn size(&self) -> u64 {
+        self.row_pitch * self.rows * self.slices
+    }
+
+    /// Copy the packed `data` into the staging `mapping`.
+    unsafe fn write(&self, data: &[u8], mapping: *mut u8) {
+        for row in 0..self.rows * self.slices {
+            ptr::copy_nonoverlapping(
+                data[(row * self.packed_pitch) as usize..].as_ptr(),
+                mapping.offset((self.offset + row * self.row_pitch) as isize),
+                self.packed_pitch as usize,
+            );
+        }
+    }
+
+    /// Copy the staging `mapping` into the packed `data`.
+    unsafe fn read(&self, mapping: *const u8, data: &mut [u8]) {
+        for row in 0..self.rows * self.slices {
+            ptr::copy_nonoverlapping(
+                mapping.offset((self.offset + row * self.row_pitch) as isize),
+                data[(row * self.packed_pitch) as usize..].as_mut_ptr(),
+                self.packed_pitch as usize,
+            );
+        }
+    }
+
+    fn copy(
+        &self,
+        layers: image::SubresourceLayers,
+        offset: image::Offset,
+        extent: image::Extent,
+    ) -> BufferImageCopy {
+        BufferImageCopy {
+            buffer_offset: self.offset,
+            buffer_width: (self.row_pitch / self.block_size) as u32 * self.block_dim.0 as u32,
+            buffer_height: self.rows as u32 * self.block_dim.1 as u32,
+            image_layers: image::SubresourceLayers {
+                aspects: self.aspect,
+                ..layers
+            },
+            image_offset: offset,
+            image_extent: extent,
+        }
+    }
+}
+
+/// Placement of the aspects of an image region inside a staging buffer.
+///
+/// Copies can only cover a single aspect, so the aspects are laid out one after
+/// the other, both in the staging buffer and in the user data.
+#[derive(Clone, Debug)]
+struct RegionLayout {
+    aspects: Vec<StagingLayout>,
+}
+
+impl RegionLayout {
+    fn new(
+        format: format::Format,
+        aspects: format::Aspects,
+        extent: image::Extent,
+        layers: image::Layer,
+        limits: &Limits,
+    ) -> Self {
+        // The offsets of the depth/stencil copies have to be multiples of 4.
+        let alignment = limits.optimal_buffer_copy_offset_alignment.max(4);
+        let mut offset = 0;
+        let aspects = (0..8)
+            .filter_map(|bit| format::Aspects::from_bits(1 << bit))
+            .filter(|&aspect| aspects.contains(aspect))
+            .map(|aspect| {
+                let layout = StagingLayout::new(format, aspect, extent, layers, limits, offset);
+                offset = align(layout.offset + layout.size(), alignment);
+                layout
+            })
+            .collect();
+        RegionLayout { aspects }
+    }
+
+    /// Size of the tightly packed user data.
+    fn packed_size(&self) -> u64 {
+        self.aspects.iter().map(StagingLayout::packed_size).sum()
+    }
+
+    /// Size of the staging buffer.
+    fn size(&self) -> u64 {
+        self.aspects
+            .last()
+            .map_or(0, |layout| layout.offset + layout.size())
+    }
+
+    /// Copy the packed `data` into the staging `mapping`.
+    unsafe fn write(&self, data: &[u8], mapping: *mut u8) {
+        let mut start = 0;
+        for layout in &self.aspects {
+            layout.write(&data[start..], mapping);
+            start += layout.packed_size() as usize;
+        }
+    }
+
+    /// Copy the staging `mapping` into the packed `data`.
+    unsafe fn read(&self, mapping: *const u8, data: &mut [u8]) {
+        let mut start = 0;
+        for layout in &self.aspects {
+            layout.read(mapping, &mut data[start..]);
+            start += layout.packed_size() as usize;
+        }
+    }
+
+    /// Copy regions of the aspects, one per aspect.
+    fn copies(
+        &self,
+        layers: image::SubresourceLayers,
+        offset: image::Offset,
+        extent: image::Extent,
+    ) -> Vec<BufferImageCopy> {
+        self.aspects
+            .iter()
+            .map(|layout| layout.copy(layers.clone(), offset, extent))
+            .collect()
+    }
+}
+
+pub(crate) unsafe fn allocate_bound_buffer<B: Backend, D: Device<B> + ?Sized>(
+    device: &D,
+    memory_types: &[MemoryType],
+    size: u64,
+    usage: buffer::Usage,
+    properties: Properties,
+) -> Result<(B::Buffer, B::Memory), UploadError> {
+    let mut buffer = device.create_buffer(size, usage, crate::memory::SparseFlags::empty())?;
+    let requirements = device.get_buffer_requirements(&buffer);
+    let memory_type = match find_memory_type(memory_types, requirements.type_mask, properties) {
+        Some(ty) => ty,
+        None => {
+            device.destroy_buffer(buffer);
+            return Err(UploadError::NoSuitableMemoryType);
+        }
+    };
+    let memory = match device.allocate_memory(memory_type, requirements.size) {
+        Ok(memory) => memory,
+        Err(e) => {
+            device.destroy_buffer(buffer);
+            return Err(e.into());
+        }
+    };
+    if let Err(e) = device.bind_buffer_memory(&memory, 0, &mut buffer) {
+        device.destroy_buffer(buffer);
+        device.free_memory(memory);
+        return Err(e.into());
+    }
+    Ok((buffer, memory))
+}
+
+/// Create a CPU-visible staging buffer of `size` bytes, and let `fill` write into its mapping.
+unsafe fn create_staging_buffer<B: Backend, D: Device<B> + ?Sized, F: FnOnce(*mut u8)>(
+    device: &D,
+    memory_types: &[MemoryType],
+    size: u64,
+    fill: F,
+) -> Result<(B::Buffer, B::Memory), UploadError> {
+    let (buffer, mut memory) = allocate_bound_buffer::<B, D>(
+        device,
+        memory_types,
+        size,
+        buffer::Usage::TRANSFER_SRC,
+        Properties::CPU_VISIBLE,
+    )?;
+    let result = device.map_memory(&mut memory, Segment::ALL).map(|mapping| {
+        fill(mapping);
+        let flushed = device.flush_mapped_memory_ranges(iter::once((&memory, Segment::ALL)));
+        device.unmap_memory(&mut memory);
+        flushed
+    });
+    match result {
+        Ok(Ok(())) => Ok((buffer, memory)),
+        Ok(Err(e)) => {
+            device.destroy_buffer(buffer);
+            device.free_memory(memory);
+            Err(e.into())
+        }
+        Err(e) => {
+            device.destroy_buffer(buffer);
+            device.free_memory(memory);
+            Err(e.into())
+        }
+    }
+}
+
+/// Queue ownership transition performed by the barrier following a staging copy.
+#[derive(Clone, Debug)]
+enum Handoff {
+    /// The copy and the subsequent accesses happen on the same queue.
+    None,
+    /// Release the resource on the transfer queue.
+    Release(Option<Range<QueueFamilyId>>),
+    /// Acquire the resource on the main queue.
+    Acquire(Option<Range<QueueFamilyId>>),
+}
+
+impl Handoff {
+    fn stages(&self, dst: PipelineStage) -> Range<PipelineStage> {
+        match *self {
+            Handoff::None => PipelineStage::TRANSFER..dst,
+            Handoff::Release(_) => PipelineStage::TRANSFER..PipelineStage::BOTTOM_OF_PIPE,
+            Handoff::Acquire(_) => dst..dst,
+        }
+    }
+
+    fn families(&self) -> Option<Range<QueueFamilyId>> {
+        match *self {
+            Handoff::None => None,
+            Handoff::Release(ref families) | Handoff::Acquire(ref families) => families.clone(),
+        }
+    }
+
+    fn is_release(&self) -> bool {
+        matches!(*self, Handoff::Release(_))
+    }
+
+    fn is_acquire(&self) -> bool {
+        matches!(*self, Handoff::Acquire(_))
+    }
+}
+
+unsafe fn record_one<B: Backend, F: FnOnce(&mut B::CommandBuffer)>(
+    pool: &mut B::CommandPool,
+    record: F,
+) -> B::CommandBuffer {
+    let mut cmd_buffer = pool.allocate_one(Level::Primary);
+    cmd_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+    record(&mut cmd_buffer);
+    cmd_buffer.finish();
+    cmd_buffer
+}
+
+/// Record the copy commands with `record_copy`, followed by the barrier recorded by
+/// `record_barrier`, submit them and wait for completion.
+///
+/// `dst_stages` are the pipeline stages of the main queue that wait for the copy,
+/// in case it's executed on the transfer queue.
+unsafe fn submit_and_wait<B, D, C, R>(
+    device: &D,
+    context: &mut UploadContext<B>,
+    dst_stages: PipelineStage,
+    record_copy: C,
+    record_barrier: R,
+) -> Result<(), UploadError>
+where
+    B: Backend,
+    D: Device<B> + ?Sized,
+    C: FnOnce(&mut B::CommandBuffer),
+    R: Fn(&mut B::CommandBuffer, Handoff),
+{
+    let mut fence = device.create_fence(false)?;
+    let result = match context.transfer {
+        None => {
+            let cmd_buffer = record_one::<B, _>(context.command_pool, |cmd_buffer| {
+                record_copy(cmd_buffer);
+                record_barrier(cmd_buffer, Handoff::None);
+            });
+            let result = context
+                .queue
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::empty(),
+                    Some(&mut fence),
+                )
+                .map_err(WaitError::from)
+                .and_then(|()| device.wait_for_fence(&fence, !0));
+            context.command_pool.free(iter::once(cmd_buffer));
+            result
+        }
+        Some(ref mut transfer) => {
+            let semaphore = match device.create_semaphore() {
+                Ok(semaphore) => semaphore,
+                Err(e) => {
+                    device.destroy_fence(fence);
+                    return Err(e.into());
+                }
+            };
+            let families = if transfer.family == context.family {
+                None
+            } else {
+                Some(transfer.family..context.family)
+            };
+            let copy_cmd_buffer = record_one::<B, _>(transfer.command_pool, |cmd_buffer| {
+                record_copy(cmd_buffer);
+                record_barrier(cmd_buffer, Handoff::Release(families.clone()));
+            });
+            // Without an ownership transfer, the release barrier alone performs the
+            // layout transition, which mustn't be recorded a second time.
+            let acquire_cmd_buffer = record_one::<B, _>(context.command_pool, |cmd_buffer| {
+                if families.is_some() {
+                    record_barrier(cmd_buffer, Handoff::Acquire(families))
+                }
+            });
+
+            let main_queue = &mut context.queue;
+            let result = transfer
+                .queue
+                .submit(
+                    iter::once(&copy_cmd_buffer),
+                    iter::empty(),
+                    iter::once(&semaphore),
+                    None,
+                )
+                .and_then(|()| {
+                    main_queue.submit(
+                        iter::once(&acquire_cmd_buffer),
+                        iter::once((&semaphore, dst_stages)),
+                        iter::empty(),
+                        Some(&mut fence),
+                    )
+                })
+                .map_err(WaitError::from)
+                .and_then(|()| device.wait_for_fence(&fence, !0));
+
+            transfer.command_pool.free(iter::once(copy_cmd_buffer));
+            context.command_pool.free(iter::once(acquire_cmd_buffer));
+            device.destroy_semaphore(semaphore);
+            result
+        }
+    };
+
+    device.destroy_fence(fence);
+    result?;
+    Ok(())
+}
+
+fn buffer_access(usage: buffer::Usage) -> (buffer::Access, PipelineStage) {
+    let mut access = buffer::Access::empty();
+    let mut stages = PipelineStage::empty();
+    if usage.contains(buffer::Usage::VERTEX) {
+        access |= buffer::Access::VERTEX_BUFFER_READ;
+        stages |= PipelineStage::VERTEX_INPUT;
+    }
+    if usage.contains(buffer::Usage::INDEX) {
+        access |= buffer::Access::INDEX_BUFFER_READ;
+        stages |= PipelineStage::VERTEX_INPUT;
+    }
+    if usage.contains(buffer::Usage::INDIRECT) {
+        access |= buffer::Access::INDIRECT_COMMAND_READ;
+        stages |= PipelineStage::DRAW_INDIRECT;
+    }
+    if usage.contains(buffer::Usage::UNIFORM) {
+        access |= buffer::Access::UNIFORM_READ;
+        stages |= PipelineStage::VERTEX_SHADER
+            | PipelineStage::FRAGMENT_SHADER
+            | PipelineStage::COMPUTE_SHADER;
+    }
+    if usage.intersects(
+        buffer::Usage::STORAGE | buffer::Usage::UNIFORM_TEXEL | buffer::Usage::STORAGE_TEXEL,
+    ) {
+        access |= buffer::Access::SHADER_READ;
+        stages |= PipelineStage::VERTEX_SHADER
+            | PipelineStage::FRAGMENT_SHADER
+            | PipelineStage::COMPUTE_SHADER;
+    }
+    if usage.contains(buffer::Usage::TRANSFER_SRC) {
+        access |= buffer::Access::TRANSFER_READ;
+        stages |= PipelineStage::TRANSFER;
+    }
+    if stages.is_empty() {
+        stages = PipelineStage::BOTTOM_OF_PIPE;
+    }
+    (access, stages)
+}
+
+/// Implementation of [`Device::create_buffer_with_data`].
+pub unsafe fn create_buffer_with_data<B: Backend, D: Device<B> + ?Sized>(
+    device: &D,
+    data: &[u8],
+    usage: buffer::Usage,
+    context: &mut UploadContext<B>,
+) -> Result<(B::Buffer, B::Memory), UploadError> {
+    if data.is_empty() {
+        return Err(UploadError::EmptyData);
+    }
+    let size = data.len() as u64;
+    let (staging, staging_memory) =
+        create_staging_buffer::<B, D, _>(device, context.memory_types, size, |mapping| {
+            ptr::copy_nonoverlapping(data.as_ptr(), mapping, data.len())
+        })?;
+
+    let result = allocate_bound_buffer::<B, D>(
+        device,
+        context.memory_types,
+        size,
+        usage | buffer::Usage::TRANSFER_DST,
+        Properties::DEVICE_LOCAL,
+    );
+    let (buffer, memory) = match result {
+        Ok(pair) => pair,
+        Err(e) => {
+            device.destroy_buffer(staging);
+            device.free_memory(staging_memory);
+            return Err(e);
+        }
+    };
+
+    let (dst_access, dst_stages) = buffer_access(usage);
+    let result = submit_and_wait(
+        device,
+        context,
+        dst_stages,
+        |cmd_buffer| {
+            cmd_buffer.copy_buffer(
+                &staging,
+                &buffer,
+                iter::once(BufferCopy {
+                    src: 0,
+                    dst: 0,
+                    size,
+                }),
+            );
+        },
+        |cmd_buffer, handoff| {
+            let src = if handoff.is_acquire() {
+                buffer::Access::empty()
+            } else {
+                buffer::Access::TRANSFER_WRITE
+            };
+            let dst = if handoff.is_release() {
+                buffer::Access::empty()
+            } else {
+                dst_access
+            };
+            cmd_buffer.pipeline_barrier(
+                handoff.stages(dst_stages),
+                Dependencies::empty(),
+                iter::once(Barrier::Buffer {
+                    states: src..dst,
+                    target: &buffer,
+                    range: buffer::SubRange::WHOLE,
+                    families: handoff.families(),
+                }),
+            );
+        },
+    );
+
+    device.destroy_buffer(staging);
+    device.free_memory(staging_memory);
+    match result {
+        Ok(()) => Ok((buffer, memory)),
+        Err(e) => {
+            device.destroy_buffer(buffer);
+            device.free_memory(memory);
+            Err(e)
+        }
+    }
+}
+
+/// Implementation of [`Device::create_image_with_data`].
+pub unsafe fn create_image_with_data<B: Backend, D: Device<B> + ?Sized>(
+    device: &D,
+    kind: image::Kind,
+    format: format::Format,
+    usage: image::Usage,
+    data: &[u8],
+    context: &mut UploadContext<B>,
+) -> Result<(B::Image, B::Memory), UploadError> {
+    if data.is_empty() {
+        return Err(UploadError::EmptyData);
+    }
+    let extent = kind.extent();
+    let layers = kind.num_layers();
+    let desc = format.surface_desc();
+    let layout = RegionLayout::new(format, desc.aspects, extent, layers, context.limits);
+    if data.len() as u64 != layout.packed_size() {
+        return Err(UploadError::Data(data.len()));
+    }
+
+    let (staging, staging_memory) =
+        create_staging_buffer::<B, D, _>(device, context.memory_types, layout.size(), |mapping| {
+            layout.write(data, mapping)
+        })?;
+
+    let mut image = match device.create_image(
+        kind,
+        1,
+        format,
+        image::Tiling::Optimal,
+        usage | image::Usage::TRANSFER_DST,
+        crate::memory::SparseFlags::empty(),
+        image::ViewCapabilities::empty(),
+    ) {
+        Ok(image) => image,
+        Err(e) => {
+            device.destroy_buffer(staging);
+            device.free_memory(staging_memory);
+            return Err(e.into());
+        }
+    };
+    let requirements = device.get_image_requirements(&image);
+    let memory = find_memory_type(
+        context.memory_types,
+        requirements.type_mask,
+        Properties::DEVICE_LOCAL,
+    )
+    .ok_or(UploadError::NoSuitableMemoryType)
+    .and_then(|ty| Ok(device.allocate_memory(ty, requirements.size)?))
+    .and_then(
+        |memory| match device.bind_image_memory(&memory, 0, &mut image) {
+            Ok(()) => Ok(memory),
+            Err(e) => {
+                device.free_memory(memory);
+                Err(e.into())
+            }
+        },
+    );
+    let memory = match memory {
+        Ok(memory) => memory,
+        Err(e) => {
+            device.destroy_image(image);
+            device.destroy_buffer(staging);
+            device.free_memory(staging_memory);
+            return Err(e);
+        }
+    };
+
+    let range = image::SubresourceRange {
+        aspects: desc.aspects,
+        ..Default::default()
+    };
+    let shader_stages = PipelineStage::VERTEX_SHADER
+        | PipelineStage::FRAGMENT_SHADER
+        | PipelineStage::COMPUTE_SHADER;
+    let result = submit_and_wait(
+        device,
+        context,
+        shader_stages,
+        |cmd_buffer| {
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                iter::once(Barrier::Image {
+                    states: (image::Access::empty(), image::Layout::Undefined)
+                        ..(
+                            image::Access::TRANSFER_WRITE,
+                            image::Layout::TransferDstOptimal,
+                        ),
+                    target: &image,
+                    families: None,
+                    range: range.clone(),
+                }),
+            );
+            cmd_buffer.copy_buffer_to_image(
+                &staging,
+                &image,
+                image::Layout::TransferDstOptimal,
+                layout
+                    .copies(
+                        image::SubresourceLayers {
+                            aspects: desc.aspects,
+                            level: 0,
+                            layers: 0..layers,
+                        },
+                        image::Offset::ZERO,
+                        extent,
+                    )
+                    .into_iter(),
+            );
+        },
+        |cmd_buffer, handoff| {
+            let src = if handoff.is_acquire() {
+                image::Access::empty()
+            } else {
+                image::Access::TRANSFER_WRITE
+            };
+            let dst = if handoff.is_release() {
+                image::Access::empty()
+            } else {
+                image::Access::SHADER_READ
+            };
+            cmd_buffer.pipeline_barrier(
+                handoff.stages(shader_stages),
+                Dependencies::empty(),
+                iter::once(Barrier::Image {
+                    states: (src, image::Layout::TransferDstOptimal)
+                        ..(dst, image::Layout::ShaderReadOnlyOptimal),
+                    target: &image,
+                    families: handoff.families(),
+                    range: range.clone(),
+                }),
+            );
+        },
+    );
+
+    device.destroy_buffer(staging);
+    device.free_memory(staging_memory);
+    match result {
+        Ok(()) => Ok((image, memory)),
+        Err(e) => {
+            device.destroy_image(image);
+            device.free_memory(memory);
+            Err(e)
+        }
+    }
+}
+
+/// Region of an image to upload to or download from.
+#[derive(Clone, Debug)]
+pub struct ImageRegion {
+    /// Mip level and array layers of the region.
+    pub layers: image::SubresourceLayers,
+    /// Offset of the region, in texels.
+    pub offset: image::Offset,
+    /// Extent of the region, in texels.
+    pub extent: image::Extent,
+}
+
+#[derive(Debug)]
+struct PendingTransfer<B: Backend> {
+    /// Command buffer of the copy, allocated from the transfer command pool
+    /// if `handoff` is set.
+    cmd_buffer: B::CommandBuffer,
+    /// Command buffers of the main queue releasing the image to the transfer
+    /// queue and acquiring it back, if the copy is executed on the transfer queue.
+    handoff: Option<(B::CommandBuffer, B::CommandBuffer)>,
+    /// Layout of the staging buffer, if the transfer is a download.
+    download: Option<RegionLayout>,
+}
+
+/// Reusable helper transferring pixel data between the host and existing images.
+///
+/// The uploader keeps a single staging buffer around, growing it as needed, and
+/// records the copies together with the layout transitions of the target image.
+/// The transfers are submitted without waiting on them: the returned fence
+/// is signaled once the transfer is complete.
+///
+/// Only one transfer can be in flight at a time, since they share the staging buffer.
+/// Starting a new transfer waits for the previous one. All the calls have to use
+/// the same queues and command pools.
+///
+/// The uploads are executed on the `transfer` queue of the context if it's given,
+/// the image being handed over between the queues around the copy. The downloads
+/// are always executed on the main queue.
+#[derive(Debug)]
+pub struct ImageUploader<B: Backend> {
+    staging: Option<StagingBuffer<B>>,
+    fence: Option<B::Fence>,
+    /// Semaphores ordering the submissions of the uploads executed on the
+    /// transfer queue: the first one is signaled by the release of the image
+    /// on the main queue, the second one by the copy.
+    semaphores: Option<(B::Semaphore, B::Semaphore)>,
+    pending: Option<PendingTransfer<B>>,
+}
+
+impl<B: Backend> Default for ImageUploader<B> {
+    fn default() -> Self {
+        ImageUploader {
+            staging: None,
+            fence: None,
+            semaphores: None,
+            pending: None,
+        }
+    }
+}
+
+impl<B: Backend> ImageUploader<B> {
+    /// Create a new uploader. No resources are allocated until the first transfer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the transfer in flight, if any, to complete.
+    pub unsafe fn wait<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+    ) -> Result<(), UploadError> {
+        self.finish(device, context).map(|_| ())
+    }
+
+    /// Wait for the transfer in flight and free its command buffer,
+    /// returning the staging layout if it was a download.
+    unsafe fn finish<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+    ) -> Result<Option<RegionLayout>, UploadError> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+        let fence = self.fence.as_ref().unwrap();
+        if let Err(e) = device.wait_for_fence(fence, !0) {
+            self.pending = Some(pending);
+            return Err(e.into());
+        }
+        match pending.handoff {
+            Some((release, acquire)) => {
+                context
+                    .command_pool
+                    .free(iter::once(release).chain(iter::once(acquire)));
+                match context.transfer {
+                    Some(ref mut transfer) => {
+                        transfer.command_pool.free(iter::once(pending.cmd_buffer))
+                    }
+                    None => {
+                        log::warn!("Upload finished without its transfer queue");
+                        context.command_pool.free(iter::once(pending.cmd_buffer))
+                    }
+                }
+            }
+            None => context.command_pool.free(iter::once(pending.cmd_buffer)),
+        }
+        Ok(pending.download)
+    }
+
+    /// Prepare the uploader for a new transfer of `size` bytes.
+    unsafe fn prepare<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+        size: u64,
+    ) -> Result<(), UploadError> {
+        self.finish(device, context)?;
+        match self.fence {
+            Some(ref mut fence) => device.reset_fence(fence)?,
+            None => self.fence = Some(device.create_fence(false)?),
+        }
+        if self
+            .staging
+            .as_ref()
+            .map_or(false, |staging| staging.size >= size)
+        {
+            return Ok(());
+        }
+        if let Some(staging) = self.staging.take() {
+            device.destroy_buffer(staging.buffer);
+            device.free_memory(staging.memory);
+        }
+        let (buffer, memory) = allocate_bound_buffer::<B, D>(
+            device,
+            context.memory_types,
+            size,
+            buffer::Usage::TRANSFER_SRC | buffer::Usage::TRANSFER_DST,
+            Properties::CPU_VISIBLE,
+        )?;
+        self.staging = Some(StagingBuffer {
+            buffer,
+            memory,
+            size,
+        });
+        Ok(())
+    }
+
+    /// Record the commands produced by `record` and submit them to the context queue.
+    unsafe fn submit<F: FnOnce(&mut B::CommandBuffer, &B::Buffer)>(
+        &mut self,
+        context: &mut UploadContext<B>,
+        download: Option<RegionLayout>,
+        record: F,
+    ) -> Result<&B::Fence, UploadError> {
+        let staging = &self.staging.as_ref().unwrap().buffer;
+        let cmd_buffer = record_one::<B, _>(context.command_pool, |cmd_buffer| {
+            record(cmd_buffer, staging)
+        });
+        let fence = self.fence.as_mut().unwrap();
+        if let Err(e) = context.queue.submit(
+            iter::once(&cmd_buffer),
+            iter::empty(),
+            iter::empty(),
+            Some(fence),
+        ) {
+            context.command_pool.free(iter::once(cmd_buffer));
+            return Err(WaitError::from(e).into());
+        }
+        self.pending = Some(PendingTransfer {
+            cmd_buffer,
+            handoff: None,
+            download,
+        });
+        Ok(self.fence.as_ref().unwrap())
+    }
+
+    /// Record an upload split between the queues of the context, and submit it.
+    ///
+    /// `release` is recorded on the main queue, `copy` on the transfer queue, and
+    /// `acquire` on the main queue again, each submission waiting for the previous
+    /// one. The acquisition waits at the `dst_stages`.
+    unsafe fn submit_on_transfer<D, R, C, A>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+        dst_stages: PipelineStage,
+        release: R,
+        copy: C,
+        acquire: A,
+    ) -> Result<&B::Fence, UploadError>
+    where
+        D: Device<B> + ?Sized,
+        R: FnOnce(&mut B::CommandBuffer),
+        C: FnOnce(&mut B::CommandBuffer, &B::Buffer),
+        A: FnOnce(&mut B::CommandBuffer),
+    {
+        if self.semaphores.is_none() {
+            let released = device.create_semaphore()?;
+            let copied = match device.create_semaphore() {
+                Ok(semaphore) => semaphore,
+                Err(e) => {
+                    device.destroy_semaphore(released);
+                    return Err(e.into());
+                }
+            };
+            self.semaphores = Some((released, copied));
+        }
+        let (ref released, ref copied) = *self.semaphores.as_ref().unwrap();
+        let staging = &self.staging.as_ref().unwrap().buffer;
+        let transfer = context.transfer.as_mut().unwrap();
+
+        let release_cmd_buffer = record_one::<B, _>(context.command_pool, release);
+        let copy_cmd_buffer = record_one::<B, _>(transfer.command_pool, |cmd_buffer| {
+            copy(cmd_buffer, staging)
+        });
+        let acquire_cmd_buffer = record_one::<B, _>(context.command_pool, acquire);
+
+        let main_queue = &mut context.queue;
+        let fence = self.fence.as_mut().unwrap();
+        let result = main_queue
+            .submit(
+                iter::once(&release_cmd_buffer),
+                iter::empty(),
+                iter::once(released),
+                None,
+            )
+            .and_then(|()| {
+                transfer.queue.submit(
+                    iter::once(&copy_cmd_buffer),
+                    iter::once((released, PipelineStage::TRANSFER)),
+                    iter::once(copied),
+                    None,
+                )
+            })
+            .and_then(|()| {
+                main_queue.submit(
+                    iter::once(&acquire_cmd_buffer),
+                    iter::once((copied, dst_stages)),
+                    iter::empty(),
+                    Some(fence),
+                )
+            });
+        if let Err(e) = result {
+            transfer.command_pool.free(iter::once(copy_cmd_buffer));
+            context
+                .command_pool
+                .free(iter::once(release_cmd_buffer).chain(iter::once(acquire_cmd_buffer)));
+            return Err(WaitError::from(e).into());
+        }
+        self.pending = Some(PendingTransfer {
+            cmd_buffer: copy_cmd_buffer,
+            handoff: Some((release_cmd_buffer, acquire_cmd_buffer)),
+            download: None,
+        });
+        Ok(self.fence.as_ref().unwrap())
+    }
+
+    /// Upload tightly packed pixel `data` of the given `format` into a region of `image`.
+    ///
+    /// The aspects of the region are packed one after the other, as in
+    /// [`create_image_with_data`][Device::create_image_with_data].
+    ///
+    /// The image is transitioned from `states.start` to `TransferDstOptimal` for the
+    /// copy, and then to `states.end`. The transitions synchronize with the
+    /// `stages.start` accesses preceding the upload, and the `stages.end` accesses
+    /// following it.
+    ///
+    /// If the context has a `transfer` queue of another family, the ownership of
+    /// the image is transferred to it for the copy, and back to the main queue.
+    pub unsafe fn upload<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+        image: &B::Image,
+        format: format::Format,
+        region: &ImageRegion,
+        data: &[u8],
+        states: Range<image::State>,
+        stages: Range<PipelineStage>,
+    ) -> Result<&B::Fence, UploadError> {
+        let layout = RegionLayout::new(
+            format,
+            region.layers.aspects,
+            region.extent,
+            region.layers.layers.end - region.layers.layers.start,
+            context.limits,
+        );
+        if data.len() as u64 != layout.packed_size() {
+            return Err(UploadError::Data(data.len()));
+        }
+        self.prepare(device, context, layout.size())?;
+
+        let staging = self.staging.as_mut().unwrap();
+        let mapping = device.map_memory(&mut staging.memory, Segment::ALL)?;
+        layout.write(data, mapping);
+        let flushed =
+            device.flush_mapped_memory_ranges(iter::once((&staging.memory, Segment::ALL)));
+        device.unmap_memory(&mut staging.memory);
+        flushed?;
+
+        let range = image::SubresourceRange {
+            aspects: region.layers.aspects,
+            level_start: region.layers.level,
+            level_count: Some(1),
+            layer_start: region.layers.layers.start,
+            layer_count: Some(region.layers.layers.end - region.layers.layers.start),
+        };
+        let transfer_state = (
+            image::Access::TRANSFER_WRITE,
+            image::Layout::TransferDstOptimal,
+        );
+        let copies = layout.copies(region.layers.clone(), region.offset, region.extent);
+
+        if let Some(transfer_family) = context.transfer.as_ref().map(|transfer| transfer.family) {
+            let families = if transfer_family == context.family {
+                None
+            } else {
+                Some(context.family..transfer_family)
+            };
+            let barrier = |cmd_buffer: &mut B::CommandBuffer,
+                           states: Range<image::State>,
+                           stages: Range<PipelineStage>,
+                           families: Option<Range<QueueFamilyId>>| {
+                cmd_buffer.pipeline_barrier(
+                    stages,
+                    Dependencies::empty(),
+                    iter::once(Barrier::Image {
+                        states,
+                        target: image,
+                        families,
+                        range: range.clone(),
+                    }),
+                );
+            };
+            // Without an ownership transfer, the release barriers alone perform
+            // the layout transitions, the semaphores ordering the queues.
+            let dst_stages = stages.end;
+            return self.submit_on_transfer(
+                device,
+                context,
+                dst_stages,
+                |cmd_buffer| {
+                    barrier(
+                        cmd_buffer,
+                        states.start..(image::Access::empty(), transfer_state.1),
+                        stages.start..PipelineStage::BOTTOM_OF_PIPE,
+                        families.clone(),
+                    )
+                },
+                |cmd_buffer, staging| {
+                    if families.is_some() {
+                        barrier(
+                            cmd_buffer,
+                            (image::Access::empty(), states.start.1)..transfer_state,
+                            PipelineStage::TRANSFER..PipelineStage::TRANSFER,
+                            families.clone(),
+                        );
+                    }
+                    cmd_buffer.copy_buffer_to_image(
+                        staging,
+                        image,
+                        image::Layout::TransferDstOptimal,
+                        copies.into_iter(),
+                    );
+                    barrier(
+                        cmd_buffer,
+                        transfer_state..(image::Access::empty(), states.end.1),
+                        PipelineStage::TRANSFER..PipelineStage::BOTTOM_OF_PIPE,
+                        families
+                            .clone()
+                            .map(|families| families.end..families.start),
+                    );
+                },
+                |cmd_buffer| {
+                    if let Some(ref families) = families {
+                        barrier(
+                            cmd_buffer,
+                            (image::Access::empty(), transfer_state.1)..states.end,
+                            dst_stages..dst_stages,
+                            Some(families.end..families.start),
+                        );
+                    }
+                },
+            );
+        }
+
+        self.submit(context, None, |cmd_buffer, staging| {
+            cmd_buffer.pipeline_barrier(
+                stages.start..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                iter::once(Barrier::Image {
+                    states: states.start..transfer_state,
+                    target: image,
+                    families: None,
+                    range: range.clone(),
+                }),
+            );
+            cmd_buffer.copy_buffer_to_image(
+                staging,
+                image,
+                image::Layout::TransferDstOptimal,
+                copies.into_iter(),
+            );
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER..stages.end,
+                Dependencies::empty(),
+                iter::once(Barrier::Image {
+                    states: transfer_state..states.end,
+                    target: image,
+                    families: None,
+                    range,
+                }),
+            );
+        })
+    }
+
+    /// Download a region of `image`, of the given `format`, into the staging buffer.
+    ///
+    /// The image is transitioned from `state` to `TransferSrcOptimal` for the copy,
+    /// and back afterwards. The transitions synchronize with the `stages` accesses
+    /// surrounding the download. The pixel data can be retrieved with
+    /// [`read_download`][ImageUploader::read_download] once the transfer is complete.
+    pub unsafe fn download<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+        image: &B::Image,
+        format: format::Format,
+        region: &ImageRegion,
+        state: image::State,
+        stages: PipelineStage,
+    ) -> Result<&B::Fence, UploadError> {
+        let layout = RegionLayout::new(
+            format,
+            region.layers.aspects,
+            region.extent,
+            region.layers.layers.end - region.layers.layers.start,
+            context.limits,
+        );
+        self.prepare(device, context, layout.size())?;
+
+        let range = image::SubresourceRange {
+            aspects: region.layers.aspects,
+            level_start: region.layers.level,
+            level_count: Some(1),
+            layer_start: region.layers.layers.start,
+            layer_count: Some(region.layers.layers.end - region.layers.layers.start),
+        };
+        let transfer_state = (
+            image::Access::TRANSFER_READ,
+            image::Layout::TransferSrcOptimal,
+        );
+        let copies = layout.copies(region.layers.clone(), region.offset, region.extent);
+        self.submit(context, Some(layout), |cmd_buffer, staging| {
+            cmd_buffer.pipeline_barrier(
+                stages..PipelineStage::TRANSFER,
+                Dependencies::empty(),
+                iter::once(Barrier::Image {
+                    states: state..transfer_state,
+                    target: image,
+                    families: None,
+                    range: range.clone(),
+                }),
+            );
+            cmd_buffer.copy_image_to_buffer(
+                image,
+                image::Layout::TransferSrcOptimal,
+                staging,
+                copies.into_iter(),
+            );
+            cmd_buffer.pipeline_barrier(
+                PipelineStage::TRANSFER..stages | PipelineStage::HOST,
+                Dependencies::empty(),
+                vec![
+                    Barrier::Image {
+                        states: transfer_state..state,
+                        target: image,
+                        families: None,
+                        range,
+                    },
+                    Barrier::Buffer {
+                        states: buffer::Access::TRANSFER_WRITE..buffer::Access::HOST_READ,
+                        target: staging,
+                        range: buffer::SubRange::WHOLE,
+                        families: None,
+                    },
+                ]
+                .into_iter(),
+            );
+        })
+    }
+
+    /// Wait for the last [`download`][ImageUploader::download] to complete, and copy
+    /// its pixel data, tightly packed, into `data`.
+    pub unsafe fn read_download<D: Device<B> + ?Sized>(
+        &mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+        data: &mut [u8],
+    ) -> Result<(), UploadError> {
+        let layout = match self.pending {
+            Some(PendingTransfer {
+                download: Some(ref layout),
+                ..
+            }) => layout.clone(),
+            _ => return Err(UploadError::NoDownload),
+        };
+        if data.len() as u64 != layout.packed_size() {
+            return Err(UploadError::Data(data.len()));
+        }
+        self.finish(device, context)?;
+
+        let staging = self.staging.as_mut().unwrap();
+        let mapping = device.map_memory(&mut staging.memory, Segment::ALL)?;
+        let result =
+            device.invalidate_mapped_memory_ranges(iter::once((&staging.memory, Segment::ALL)));
+        if result.is_ok() {
+            layout.read(mapping, data);
+        }
+        device.unmap_memory(&mut staging.memory);
+        Ok(result?)
+    }
+
+    /// Wait for the transfer in flight and destroy the uploader resources.
+    pub unsafe fn destroy<D: Device<B> + ?Sized>(
+        mut self,
+        device: &D,
+        context: &mut UploadContext<B>,
+    ) -> Result<(), UploadError> {
+        let result = self.finish(device, context);
+        if let Some(staging) = self.staging.take() {
+            device.destroy_buffer(staging.buffer);
+            device.free_memory(staging.memory);
+        }
+        if let Some(fence) = self.fence.take() {
+            device.destroy_fence(fence);
+        }
+        if let Some((released, copied)) = self.semaphores.take() {
+            device.destroy_semaphore(released);
+            device.destroy_semaphore(copied);
+        }
+        result.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(pitch_alignment: buffer::Offset) -> Limits {
+        Limits {
+            optimal_buffer_copy_pitch_alignment: pitch_alignment,
+            ..Limits::default()
+        }
+    }
+
+    fn extent(width: image::Size, height: image::Size) -> image::Extent {
+        image::Extent {
+            width,
+            height,
+            depth: 1,
+        }
+    }
+
+    fn staging_layout(
+        format: format::Format,
+        extent: image::Extent,
+        layers: image::Layer,
+        limits: &Limits,
+    ) -> StagingLayout {
+        StagingLayout::new(
+            format,
+            format.surface_desc().aspects,
+            extent,
+            layers,
+            limits,
+            0,
+        )
+    }
+
+    #[test]
+    fn staging_layout_aligns_rows() {
+        let layout = staging_layout(format::Format::Rgba8Unorm, extent(3, 2), 1, &limits(256));
+        assert_eq!(layout.packed_pitch, 12);
+        assert_eq!(layout.row_pitch, 256);
+        assert_eq!(layout.packed_size(), 24);
+        assert_eq!(layout.size(), 512);
+        let copy = layout.copy(
+            image::SubresourceLayers {
+                aspects: format::Aspects::COLOR,
+                level: 0,
+                layers: 0..1,
+            },
+            image::Offset::ZERO,
+            extent(3, 2),
+        );
+        assert_eq!((copy.buffer_width, copy.buffer_height), (64, 2));
+    }
+
+    #[test]
+    fn staging_layout_pitch_is_whole_texels() {
+        // 12-byte texels do not divide the alignment.
+        let layout = staging_layout(format::Format::Rgb32Sfloat, extent(5, 1), 1, &limits(256));
+        assert_eq!(layout.row_pitch % 256, 0);
+        assert_eq!(layout.row_pitch % 12, 0);
+        assert_eq!(layout.row_pitch, 768);
+        // 3-byte texels.
+        let layout = staging_layout(format::Format::Rgb8Unorm, extent(100, 1), 1, &limits(4));
+        assert_eq!(layout.row_pitch, 300);
+        let layout = staging_layout(format::Format::Rgb8Unorm, extent(101, 1), 1, &limits(4));
+        assert_eq!(layout.row_pitch, 312);
+    }
+
+    #[test]
+    fn staging_layout_counts_blocks() {
+        // 4x4 blocks of 8 bytes, partial blocks included.
+        let layout = staging_layout(format::Format::Bc1RgbaUnorm, extent(6, 5), 3, &limits(1));
+        assert_eq!(layout.packed_pitch, 16);
+        assert_eq!(layout.rows, 2);
+        assert_eq!(layout.slices, 3);
+        assert_eq!(layout.packed_size(), 96);
+    }
+
+    #[test]
+    fn staging_layout_round_trip() {
+        let layout = staging_layout(format::Format::R8Unorm, extent(3, 2), 2, &limits(8));
+        let data = (0..layout.packed_size() as u8).collect::<Vec<_>>();
+        let mut staging = vec![0xFF; layout.size() as usize];
+        let mut result = vec![0; data.len()];
+        unsafe {
+            layout.write(&data, staging.as_mut_ptr());
+            layout.read(staging.as_ptr(), &mut result);
+        }
+        assert_eq!(&staging[..4], &[0, 1, 2, 0xFF]);
+        assert_eq!(&staging[8..11], &[3, 4, 5]);
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn region_layout_splits_depth_and_stencil() {
+        let layout = RegionLayout::new(
+            format::Format::D24UnormS8Uint,
+            format::Aspects::DEPTH | format::Aspects::STENCIL,
+            extent(3, 2),
+            1,
+            &limits(256),
+        );
+        assert_eq!(layout.packed_size(), 24 + 6);
+        assert_eq!(layout.size(), 1024);
+        let copies = layout.copies(
+            image::SubresourceLayers {
+                aspects: format::Aspects::DEPTH | format::Aspects::STENCIL,
+                level: 0,
+                layers: 0..1,
+            },
+            image::Offset::ZERO,
+            extent(3, 2),
+        );
+        let regions = copies
+            .iter()
+            .map(|copy| {
+                (
+                    copy.image_layers.aspects,
+                    copy.buffer_offset,
+                    copy.buffer_width,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regions,
+            [
+                (format::Aspects::DEPTH, 0, 64),
+                (format::Aspects::STENCIL, 512, 256)
+            ]
+        );
+
+        // The depth is padded to 2 bytes, the stencil follows at an aligned offset.
+        let layout = RegionLayout::new(
+            format::Format::D16UnormS8Uint,
+            format::Aspects::DEPTH | format::Aspects::STENCIL,
+            extent(3, 1),
+            1,
+            &limits(1),
+        );
+        let data = (0..layout.packed_size() as u8).collect::<Vec<_>>();
+        let mut staging = vec![0xFF; layout.size() as usize];
+        let mut result = vec![0; data.len()];
+        unsafe {
+            layout.write(&data, staging.as_mut_ptr());
+            layout.read(staging.as_ptr(), &mut result);
+        }
+        assert_eq!(staging, [0, 1, 2, 3, 4, 5, 0xFF, 0xFF, 6, 7, 8]);
+        assert_eq!(result, data);
+    }
+}
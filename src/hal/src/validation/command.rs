@@ -0,0 +1,1206 @@
+use super::{
+    barrier, geometry_descs, shader_binding_table, timeline, timeline::Timeline, Buffer,
+    ComputePipeline, DescriptorSet, GraphicsPipeline, Image, PipelineLayout, PipelineLayoutInfo,
+    RayTracingPipeline, SetLayoutInfo, Validation,
+};
+use crate::{
+    acceleration_structure as accel, buffer,
+    command::{
+        AttachmentClear, BufferCopy, BufferImageCopy, ClearValue, CommandBufferFlags,
+        CommandBufferInheritanceInfo, DescriptorSetOffset, ImageBlit, ImageCopy, ImageResolve,
+        Level, RenderAttachmentInfo, SubpassContents,
+    },
+    image::{Filter, Layout, SubresourceRange},
+    memory::{Barrier, Dependencies},
+    pass::SubpassId,
+    pool::CommandPoolCreateFlags,
+    pso, query as q, Backend, DrawCount, IndexCount, IndexType, InstanceCount, TaskCount,
+    VertexCount, VertexOffset, WorkGroupCount,
+};
+
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Initial,
+    Recording,
+    Executable,
+}
+
+/// Render pass the commands are recorded in.
+#[derive(Debug)]
+struct PassScope {
+    subpass: SubpassId,
+    /// The subpass contents are recorded in secondary command buffers.
+    secondary_contents: bool,
+    /// The render pass is continued from a primary command buffer.
+    inherited: bool,
+}
+
+/// Bindings of a pipeline bind point.
+#[derive(Debug, Default)]
+struct BindPoint {
+    /// Layout of the bound pipeline.
+    pipeline_layout: Option<Arc<PipelineLayoutInfo>>,
+    /// Pipeline layouts the descriptor sets have been bound with, indexed by set.
+    sets: Vec<Option<Arc<PipelineLayoutInfo>>>,
+}
+
+impl BindPoint {
+    fn bind_sets(
+        &mut self,
+        command: &str,
+        layout: &Arc<PipelineLayoutInfo>,
+        first_set: usize,
+        sets: &[&Arc<SetLayoutInfo>],
+        offsets: usize,
+    ) {
+        let end = first_set + sets.len();
+        if end > layout.sets.len() {
+            report!(
+                "`{}` binds the descriptor sets {}..{}, but the pipeline layout only has {}",
+                command,
+                first_set,
+                end,
+                layout.sets.len()
+            );
+        }
+        let expected = layout.sets.iter().skip(first_set);
+        for (index, (&set, expected)) in sets.iter().zip(expected).enumerate() {
+            if set != expected {
+                report!(
+                    "`{}` binds the descriptor set {} with a layout different from the one of the pipeline layout",
+                    command,
+                    first_set + index
+                );
+            }
+        }
+        let dynamic_offsets = sets.iter().map(|set| set.dynamic_offsets()).sum::<usize>();
+        if offsets != dynamic_offsets {
+            report!(
+                "`{}` is given {} dynamic offsets, but the descriptor sets have {} dynamic descriptors",
+                command,
+                offsets,
+                dynamic_offsets
+            );
+        }
+
+        if self.sets.len() < end {
+            self.sets.resize(end, None);
+        }
+        for slot in &mut self.sets[first_set..end] {
+            *slot = Some(Arc::clone(layout));
+        }
+    }
+
+    fn check(&self, command: &str) {
+        let layout = match self.pipeline_layout {
+            Some(ref layout) => layout,
+            None => {
+                report!("`{}` is recorded without a bound pipeline", command);
+                return;
+            }
+        };
+        for (index, set) in layout.sets.iter().enumerate() {
+            if set.bindings.is_empty() {
+                continue;
+            }
+            match self.sets.get(index) {
+                Some(Some(bound)) if bound.is_compatible_for_set(layout, index) => {}
+                Some(Some(_)) => report!(
+                    "`{}` uses the descriptor set {}, which is bound with a pipeline layout incompatible with the one of the pipeline",
+                    command,
+                    index
+                ),
+                _ => report!(
+                    "`{}` uses the descriptor set {}, which is not bound",
+                    command,
+                    index
+                ),
+            }
+        }
+    }
+}
+
+/// Command buffer of the validation adaptor.
+///
+/// Tracks the recording state, the render pass and the bindings, and checks
+/// the recorded commands against them.
+#[derive(Debug)]
+pub struct CommandBuffer<B: Backend> {
+    pub(super) raw: B::CommandBuffer,
+    level: Level,
+    pool_flags: CommandPoolCreateFlags,
+    /// Number of times the pool has been reset.
+    pool_resets: Arc<AtomicUsize>,
+    /// Value of `pool_resets` when the state last changed.
+    resets: usize,
+    state: State,
+    max_viewports: usize,
+    /// Whether the mesh tasks can start from a non-zero task.
+    first_mesh_task: bool,
+    pass: Option<PassScope>,
+    graphics: BindPoint,
+    compute: BindPoint,
+    ray_tracing: BindPoint,
+    /// Subpass of the bound graphics pipeline.
+    pipeline_subpass: SubpassId,
+    dynamic_viewport: bool,
+    dynamic_scissor: bool,
+    viewport_set: bool,
+    scissor_set: bool,
+    /// Name given with `set_command_buffer_name`, tagging the recorded touches.
+    pub(super) name: Option<String>,
+    timeline: Arc<Timeline>,
+    /// Resources used by the recorded commands, committed to the timeline on submission.
+    touches: Vec<(timeline::Id, &'static str)>,
+}
+
+impl<B: Backend> CommandBuffer<B> {
+    pub(super) fn new(
+        raw: B::CommandBuffer,
+        level: Level,
+        pool_flags: CommandPoolCreateFlags,
+        pool_resets: Arc<AtomicUsize>,
+        max_viewports: usize,
+        first_mesh_task: bool,
+        timeline: Arc<Timeline>,
+    ) -> Self {
+        let resets = pool_resets.load(Ordering::Relaxed);
+        CommandBuffer {
+            raw,
+            level,
+            pool_flags,
+            pool_resets,
+            resets,
+            state: State::Initial,
+            max_viewports,
+            first_mesh_task,
+            pass: None,
+            graphics: BindPoint::default(),
+            compute: BindPoint::default(),
+            ray_tracing: BindPoint::default(),
+            pipeline_subpass: 0,
+            dynamic_viewport: false,
+            dynamic_scissor: false,
+            viewport_set: false,
+            scissor_set: false,
+            name: None,
+            timeline,
+            touches: Vec::new(),
+        }
+    }
+
+    fn state(&self) -> State {
+        if self.pool_resets.load(Ordering::Relaxed) != self.resets {
+            State::Initial
+        } else {
+            self.state
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.state = state;
+        self.resets = self.pool_resets.load(Ordering::Relaxed);
+    }
+
+    fn reset_tracking(&mut self) {
+        self.pass = None;
+        self.graphics = BindPoint::default();
+        self.compute = BindPoint::default();
+        self.ray_tracing = BindPoint::default();
+        self.viewport_set = false;
+        self.scissor_set = false;
+        self.touches.clear();
+    }
+
+    /// Record the use of a resource by a command, if the usage timeline is enabled.
+    fn touch(&mut self, id: timeline::Id, operation: &'static str) {
+        if self.timeline.is_enabled() {
+            self.touches.push((id, operation));
+        }
+    }
+
+    pub(super) fn commit_touches(&self, timeline: &Timeline) {
+        timeline.commit(self.name.as_deref(), &self.touches);
+    }
+
+    pub(super) fn check_submit(&self) {
+        if self.level != Level::Primary {
+            report!("A secondary command buffer is submitted to a queue");
+        }
+        let state = self.state();
+        if state != State::Executable {
+            report!("A command buffer is submitted in the {:?} state", state);
+        }
+    }
+
+    fn check_recording(&self, command: &str) {
+        let state = self.state();
+        if state != State::Recording {
+            report!("`{}` is recorded in the {:?} state", command, state);
+        }
+    }
+
+    fn check_inside_pass(&self, command: &str) {
+        self.check_recording(command);
+        match self.pass {
+            Some(ref pass) if pass.secondary_contents => report!(
+                "`{}` is recorded inline in a subpass with secondary command buffer contents",
+                command
+            ),
+            Some(_) => {}
+            None => report!("`{}` is recorded outside of a render pass", command),
+        }
+    }
+
+    fn check_outside_pass(&self, command: &str) {
+        self.check_recording(command);
+        if self.pass.is_some() {
+            report!("`{}` is recorded inside of a render pass", command);
+        }
+    }
+
+    fn check_draw(&self, command: &str) {
+        self.check_inside_pass(command);
+        self.graphics.check(command);
+        if self.graphics.pipeline_layout.is_none() {
+            return;
+        }
+        if let Some(ref pass) = self.pass {
+            if pass.subpass != self.pipeline_subpass {
+                report!(
+                    "`{}` uses a pipeline created for the subpass {} in the subpass {}",
+                    command,
+                    self.pipeline_subpass,
+                    pass.subpass
+                );
+            }
+        }
+        if self.dynamic_viewport && !self.viewport_set {
+            report!(
+                "`{}` uses a pipeline without a baked viewport, but no viewport is set",
+                command
+            );
+        }
+        if self.dynamic_scissor && !self.scissor_set {
+            report!(
+                "`{}` uses a pipeline without a baked scissor, but no scissor is set",
+                command
+            );
+        }
+    }
+
+    fn check_dispatch(&self, command: &str) {
+        self.check_outside_pass(command);
+        self.compute.check(command);
+    }
+
+    fn check_trace(&self, command: &str) {
+        self.check_outside_pass(command);
+        self.ray_tracing.check(command);
+    }
+
+    fn check_viewports(&self, command: &str, first: u32, count: usize) -> bool {
+        self.check_recording(command);
+        let end = first as usize + count;
+        if end > self.max_viewports {
+            report!(
+                "`{}` sets the viewports {}..{}, but the device only supports {}",
+                command,
+                first,
+                end,
+                self.max_viewports
+            );
+        }
+        first == 0 && count != 0
+    }
+}
+
+impl<B: Backend> crate::command::CommandBuffer<Validation<B>> for CommandBuffer<B> {
+    unsafe fn begin(
+        &mut self,
+        flags: CommandBufferFlags,
+        inheritance_info: CommandBufferInheritanceInfo<Validation<B>>,
+    ) {
+        match self.state() {
+            State::Recording => report!("`begin` is called on a command buffer already recording"),
+            State::Executable
+                if !self
+                    .pool_flags
+                    .contains(CommandPoolCreateFlags::RESET_INDIVIDUAL) =>
+            {
+                report!("`begin` implicitly resets a command buffer allocated from a pool without `RESET_INDIVIDUAL`")
+            }
+            _ => {}
+        }
+        self.reset_tracking();
+        if self.level == Level::Secondary
+            && flags.contains(CommandBufferFlags::RENDER_PASS_CONTINUE)
+        {
+            match inheritance_info.subpass {
+                Some(ref subpass) => {
+                    self.pass = Some(PassScope {
+                        subpass: subpass.index,
+                        secondary_contents: false,
+                        inherited: true,
+                    })
+                }
+                None => report!("`begin` continues a render pass without an inherited subpass"),
+            }
+        }
+        self.set_state(State::Recording);
+
+        self.raw.begin(
+            flags,
+            CommandBufferInheritanceInfo {
+                subpass: inheritance_info.subpass.as_ref().map(super::subpass),
+                framebuffer: inheritance_info.framebuffer,
+                occlusion_query_enable: inheritance_info.occlusion_query_enable,
+                occlusion_query_flags: inheritance_info.occlusion_query_flags,
+                pipeline_statistics: inheritance_info.pipeline_statistics,
+            },
+        )
+    }
+
+    unsafe fn finish(&mut self) {
+        self.check_recording("finish");
+        match self.pass {
+            Some(ref pass) if !pass.inherited => {
+                report!("`finish` is called inside of a render pass")
+            }
+            _ => {}
+        }
+        self.set_state(State::Executable);
+        self.raw.finish()
+    }
+
+    unsafe fn reset(&mut self, release_resources: bool) {
+        if !self
+            .pool_flags
+            .contains(CommandPoolCreateFlags::RESET_INDIVIDUAL)
+        {
+            report!("`reset` is called on a command buffer allocated from a pool without `RESET_INDIVIDUAL`");
+        }
+        self.reset_tracking();
+        self.set_state(State::Initial);
+        self.raw.reset(release_resources)
+    }
+
+    unsafe fn pipeline_barrier<'a, T>(
+        &mut self,
+        stages: Range<pso::PipelineStage>,
+        dependencies: Dependencies,
+        barriers: T,
+    ) where
+        T: Iterator<Item = Barrier<'a, Validation<B>>>,
+    {
+        self.check_recording("pipeline_barrier");
+        let barriers = barriers.collect::<Vec<_>>();
+        for barrier in &barriers {
+            match *barrier {
+                Barrier::Buffer { target, .. } => self.touch(target.id, "pipeline_barrier"),
+                Barrier::Image { target, .. } => self.touch(target.id, "pipeline_barrier"),
+                _ => {}
+            }
+        }
+        self.raw
+            .pipeline_barrier(stages, dependencies, barriers.into_iter().map(barrier))
+    }
+
+    unsafe fn fill_buffer(&mut self, buffer: &Buffer<B>, range: buffer::SubRange, data: u32) {
+        self.check_outside_pass("fill_buffer");
+        self.touch(buffer.id, "fill_buffer");
+        self.raw.fill_buffer(&buffer.raw, range, data)
+    }
+
+    unsafe fn update_buffer(&mut self, buffer: &Buffer<B>, offset: buffer::Offset, data: &[u8]) {
+        self.check_outside_pass("update_buffer");
+        self.touch(buffer.id, "update_buffer");
+        self.raw.update_buffer(&buffer.raw, offset, data)
+    }
+
+    unsafe fn clear_image<T>(
+        &mut self,
+        image: &Image<B>,
+        layout: Layout,
+        value: ClearValue,
+        subresource_ranges: T,
+    ) where
+        T: Iterator<Item = SubresourceRange>,
+    {
+        self.check_outside_pass("clear_image");
+        self.touch(image.id, "clear_image");
+        self.raw
+            .clear_image(image.raw(), layout, value, subresource_ranges)
+    }
+
+    unsafe fn clear_attachments<T, U>(&mut self, clears: T, rects: U)
+    where
+        T: Iterator<Item = AttachmentClear>,
+        U: Iterator<Item = pso::ClearRect>,
+    {
+        self.check_inside_pass("clear_attachments");
+        self.raw.clear_attachments(clears, rects)
+    }
+
+    unsafe fn clear_depth<U>(&mut self, depth: pso::DepthValue, rects: U)
+    where
+        U: Iterator<Item = pso::ClearRect>,
+    {
+        self.check_inside_pass("clear_depth");
+        self.raw.clear_depth(depth, rects)
+    }
+
+    unsafe fn clear_stencil<U>(&mut self, stencil: pso::StencilValue, rects: U)
+    where
+        U: Iterator<Item = pso::ClearRect>,
+    {
+        self.check_inside_pass("clear_stencil");
+        self.raw.clear_stencil(stencil, rects)
+    }
+
+    unsafe fn resolve_image<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: Iterator<Item = ImageResolve>,
+    {
+        self.check_outside_pass("resolve_image");
+        self.touch(src.id, "resolve_image (source)");
+        self.touch(dst.id, "resolve_image (destination)");
+        self.raw
+            .resolve_image(src.raw(), src_layout, dst.raw(), dst_layout, regions)
+    }
+
+    unsafe fn blit_image<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        filter: Filter,
+        regions: T,
+    ) where
+        T: Iterator<Item = ImageBlit>,
+    {
+        self.check_outside_pass("blit_image");
+        self.touch(src.id, "blit_image (source)");
+        self.touch(dst.id, "blit_image (destination)");
+        self.raw.blit_image(
+            src.raw(),
+            src_layout,
+            dst.raw(),
+            dst_layout,
+            filter,
+            regions,
+        )
+    }
+
+    unsafe fn bind_index_buffer(
+        &mut self,
+        buffer: &Buffer<B>,
+        sub: buffer::SubRange,
+        ty: IndexType,
+    ) {
+        self.check_recording("bind_index_buffer");
+        self.touch(buffer.id, "bind_index_buffer");
+        self.raw.bind_index_buffer(&buffer.raw, sub, ty)
+    }
+
+    unsafe fn bind_vertex_buffers<'a, T>(&mut self, first_binding: pso::BufferIndex, buffers: T)
+    where
+        T: Iterator<Item = (&'a Buffer<B>, buffer::SubRange)>,
+    {
+        self.check_recording("bind_vertex_buffers");
+        let buffers = buffers.collect::<Vec<_>>();
+        for &(buffer, _) in &buffers {
+            self.touch(buffer.id, "bind_vertex_buffers");
+        }
+        self.raw.bind_vertex_buffers(
+            first_binding,
+            buffers.into_iter().map(|(buffer, sub)| (&buffer.raw, sub)),
+        )
+    }
+
+    unsafe fn set_viewports<T>(&mut self, first_viewport: u32, viewports: T)
+    where
+        T: Iterator<Item = pso::Viewport>,
+    {
+        let viewports = viewports.collect::<Vec<_>>();
+        if self.check_viewports("set_viewports", first_viewport, viewports.len()) {
+            self.viewport_set = true;
+        }
+        self.raw
+            .set_viewports(first_viewport, viewports.into_iter())
+    }
+
+    unsafe fn set_scissors<T>(&mut self, first_scissor: u32, rects: T)
+    where
+        T: Iterator<Item = pso::Rect>,
+    {
+        let rects = rects.collect::<Vec<_>>();
+        if self.check_viewports("set_scissors", first_scissor, rects.len()) {
+            self.scissor_set = true;
+        }
+        self.raw.set_scissors(first_scissor, rects.into_iter())
+    }
+
+    unsafe fn set_stencil_reference(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.check_recording("set_stencil_reference");
+        self.raw.set_stencil_reference(faces, value)
+    }
+
+    unsafe fn set_stencil_read_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.check_recording("set_stencil_read_mask");
+        self.raw.set_stencil_read_mask(faces, value)
+    }
+
+    unsafe fn set_stencil_write_mask(&mut self, faces: pso::Face, value: pso::StencilValue) {
+        self.check_recording("set_stencil_write_mask");
+        self.raw.set_stencil_write_mask(faces, value)
+    }
+
+    unsafe fn set_blend_constants(&mut self, color: pso::ColorValue) {
+        self.check_recording("set_blend_constants");
+        self.raw.set_blend_constants(color)
+    }
+
+    unsafe fn set_depth_bounds(&mut self, bounds: Range<f32>) {
+        self.check_recording("set_depth_bounds");
+        self.raw.set_depth_bounds(bounds)
+    }
+
+    unsafe fn set_line_width(&mut self, width: f32) {
+        self.check_recording("set_line_width");
+        self.raw.set_line_width(width)
+    }
+
+    unsafe fn set_depth_bias(&mut self, depth_bias: pso::DepthBias) {
+        self.check_recording("set_depth_bias");
+        self.raw.set_depth_bias(depth_bias)
+    }
+
+    unsafe fn set_shading_rate(
+        &mut self,
+        rate: pso::ShadingRate,
+        combiners: [pso::ShadingRateCombiner; 2],
+    ) {
+        self.check_recording("set_shading_rate");
+        self.raw.set_shading_rate(rate, combiners)
+    }
+
+    unsafe fn begin_render_pass<'a, T>(
+        &mut self,
+        render_pass: &B::RenderPass,
+        framebuffer: &B::Framebuffer,
+        render_area: pso::Rect,
+        attachments: T,
+        first_subpass: SubpassContents,
+    ) where
+        T: Iterator<Item = RenderAttachmentInfo<'a, Validation<B>>>,
+    {
+        self.check_outside_pass("begin_render_pass");
+        if self.level != Level::Primary {
+            report!("`begin_render_pass` is recorded in a secondary command buffer");
+        }
+        self.pass = Some(PassScope {
+            subpass: 0,
+            secondary_contents: matches!(first_subpass, SubpassContents::SecondaryBuffers),
+            inherited: false,
+        });
+        self.raw.begin_render_pass(
+            render_pass,
+            framebuffer,
+            render_area,
+            attachments.map(|attachment| RenderAttachmentInfo {
+                image_view: &attachment.image_view.raw,
+                clear_value: attachment.clear_value,
+            }),
+            first_subpass,
+        )
+    }
+
+    unsafe fn next_subpass(&mut self, contents: SubpassContents) {
+        self.check_recording("next_subpass");
+        match self.pass {
+            Some(ref mut pass) if !pass.inherited => {
+                pass.subpass += 1;
+                pass.secondary_contents = matches!(contents, SubpassContents::SecondaryBuffers);
+            }
+            _ => report!("`next_subpass` is recorded outside of a render pass"),
+        }
+        self.raw.next_subpass(contents)
+    }
+
+    unsafe fn end_render_pass(&mut self) {
+        self.check_recording("end_render_pass");
+        match self.pass {
+            Some(ref pass) if !pass.inherited => {}
+            _ => report!("`end_render_pass` is recorded outside of a render pass"),
+        }
+        self.pass = None;
+        self.raw.end_render_pass()
+    }
+
+    unsafe fn bind_graphics_pipeline(&mut self, pipeline: &GraphicsPipeline<B>) {
+        self.check_recording("bind_graphics_pipeline");
+        self.graphics.pipeline_layout = Some(Arc::clone(&pipeline.layout));
+        self.pipeline_subpass = pipeline.subpass;
+        self.dynamic_viewport = pipeline.dynamic_viewport;
+        self.dynamic_scissor = pipeline.dynamic_scissor;
+        self.raw.bind_graphics_pipeline(&pipeline.raw)
+    }
+
+    unsafe fn bind_graphics_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a DescriptorSet<B>>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        self.check_recording("bind_graphics_descriptor_sets");
+        let sets = sets.collect::<Vec<_>>();
+        let offsets = offsets.collect::<Vec<_>>();
+        self.graphics.bind_sets(
+            "bind_graphics_descriptor_sets",
+            &layout.info,
+            first_set,
+            &sets.iter().map(|set| &set.layout).collect::<Vec<_>>(),
+            offsets.len(),
+        );
+        self.raw.bind_graphics_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.into_iter().map(|set| &set.raw),
+            offsets.into_iter(),
+        )
+    }
+
+    unsafe fn bind_compute_pipeline(&mut self, pipeline: &ComputePipeline<B>) {
+        self.check_recording("bind_compute_pipeline");
+        self.compute.pipeline_layout = Some(Arc::clone(&pipeline.layout));
+        self.raw.bind_compute_pipeline(&pipeline.raw)
+    }
+
+    unsafe fn bind_compute_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a DescriptorSet<B>>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        self.check_recording("bind_compute_descriptor_sets");
+        let sets = sets.collect::<Vec<_>>();
+        let offsets = offsets.collect::<Vec<_>>();
+        self.compute.bind_sets(
+            "bind_compute_descriptor_sets",
+            &layout.info,
+            first_set,
+            &sets.iter().map(|set| &set.layout).collect::<Vec<_>>(),
+            offsets.len(),
+        );
+        self.raw.bind_compute_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.into_iter().map(|set| &set.raw),
+            offsets.into_iter(),
+        )
+    }
+
+    unsafe fn dispatch(&mut self, count: WorkGroupCount) {
+        self.check_dispatch("dispatch");
+        self.raw.dispatch(count)
+    }
+
+    unsafe fn dispatch_indirect(&mut self, buffer: &Buffer<B>, offset: buffer::Offset) {
+        self.check_dispatch("dispatch_indirect");
+        self.touch(buffer.id, "dispatch_indirect");
+        self.raw.dispatch_indirect(&buffer.raw, offset)
+    }
+
+    unsafe fn build_acceleration_structures(&mut self, descs: &[accel::BuildDesc<Validation<B>>]) {
+        self.check_outside_pass("build_acceleration_structures");
+        let geometries = descs
+            .iter()
+            .map(|desc| geometry_descs(desc.geometry.geometries))
+            .collect::<Vec<_>>();
+        let raw_descs = descs
+            .iter()
+            .zip(&geometries)
+            .map(|(desc, geometries)| {
+                for geometry in desc.geometry.geometries {
+                    match geometry.geometry {
+                        accel::Geometry::Triangles(ref triangles) => {
+                            self.touch(
+                                triangles.vertex_buffer.0.id,
+                                "build_acceleration_structures (vertices)",
+                            );
+                            if let Some((buffer, _, _)) = triangles.index_buffer {
+                                self.touch(buffer.id, "build_acceleration_structures (indices)");
+                            }
+                            if let Some((buffer, _)) = triangles.transform {
+                                self.touch(buffer.id, "build_acceleration_structures (transform)");
+                            }
+                        }
+                        accel::Geometry::Aabbs { buffer, .. } => {
+                            self.touch(buffer.0.id, "build_acceleration_structures (boxes)");
+                        }
+                        accel::Geometry::Instances { buffer } => {
+                            self.touch(buffer.0.id, "build_acceleration_structures (instances)");
+                        }
+                    }
+                }
+                self.touch(desc.scratch.0.id, "build_acceleration_structures (scratch)");
+                accel::BuildDesc {
+                    geometry: accel::GeometryInfo {
+                        ty: desc.geometry.ty,
+                        flags: desc.geometry.flags,
+                        geometries,
+                    },
+                    src: desc.src,
+                    dst: desc.dst,
+                    scratch: (&desc.scratch.0.raw, desc.scratch.1),
+                }
+            })
+            .collect::<Vec<_>>();
+        self.raw.build_acceleration_structures(&raw_descs)
+    }
+
+    unsafe fn copy_acceleration_structure(
+        &mut self,
+        src: &B::AccelerationStructure,
+        dst: &B::AccelerationStructure,
+        mode: accel::CopyMode,
+    ) {
+        self.check_outside_pass("copy_acceleration_structure");
+        self.raw.copy_acceleration_structure(src, dst, mode)
+    }
+
+    unsafe fn bind_ray_tracing_pipeline(&mut self, pipeline: &RayTracingPipeline<B>) {
+        self.check_recording("bind_ray_tracing_pipeline");
+        self.ray_tracing.pipeline_layout = Some(Arc::clone(&pipeline.layout));
+        self.raw.bind_ray_tracing_pipeline(&pipeline.raw)
+    }
+
+    unsafe fn bind_ray_tracing_descriptor_sets<'a, I, J>(
+        &mut self,
+        layout: &PipelineLayout<B>,
+        first_set: usize,
+        sets: I,
+        offsets: J,
+    ) where
+        I: Iterator<Item = &'a DescriptorSet<B>>,
+        J: Iterator<Item = DescriptorSetOffset>,
+    {
+        self.check_recording("bind_ray_tracing_descriptor_sets");
+        let sets = sets.collect::<Vec<_>>();
+        let offsets = offsets.collect::<Vec<_>>();
+        self.ray_tracing.bind_sets(
+            "bind_ray_tracing_descriptor_sets",
+            &layout.info,
+            first_set,
+            &sets.iter().map(|set| &set.layout).collect::<Vec<_>>(),
+            offsets.len(),
+        );
+        self.raw.bind_ray_tracing_descriptor_sets(
+            &layout.raw,
+            first_set,
+            sets.into_iter().map(|set| &set.raw),
+            offsets.into_iter(),
+        )
+    }
+
+    unsafe fn trace_rays(
+        &mut self,
+        tables: &pso::ShaderBindingTables<Validation<B>>,
+        count: WorkGroupCount,
+    ) {
+        self.check_trace("trace_rays");
+        self.touch(
+            tables.ray_generation.buffer.id,
+            "trace_rays (ray generation)",
+        );
+        for table in [&tables.miss, &tables.hit, &tables.callable].iter() {
+            if let Some(ref table) = **table {
+                self.touch(table.buffer.id, "trace_rays");
+            }
+        }
+        let raw_tables = pso::ShaderBindingTables {
+            ray_generation: shader_binding_table(&tables.ray_generation),
+            miss: tables.miss.as_ref().map(shader_binding_table),
+            hit: tables.hit.as_ref().map(shader_binding_table),
+            callable: tables.callable.as_ref().map(shader_binding_table),
+        };
+        self.raw.trace_rays(&raw_tables, count)
+    }
+
+    unsafe fn copy_buffer<T>(&mut self, src: &Buffer<B>, dst: &Buffer<B>, regions: T)
+    where
+        T: Iterator<Item = BufferCopy>,
+    {
+        self.check_outside_pass("copy_buffer");
+        self.touch(src.id, "copy_buffer (source)");
+        self.touch(dst.id, "copy_buffer (destination)");
+        self.raw.copy_buffer(&src.raw, &dst.raw, regions)
+    }
+
+    unsafe fn copy_image<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: Iterator<Item = ImageCopy>,
+    {
+        self.check_outside_pass("copy_image");
+        self.touch(src.id, "copy_image (source)");
+        self.touch(dst.id, "copy_image (destination)");
+        self.raw
+            .copy_image(src.raw(), src_layout, dst.raw(), dst_layout, regions)
+    }
+
+    unsafe fn copy_buffer_to_image<T>(
+        &mut self,
+        src: &Buffer<B>,
+        dst: &Image<B>,
+        dst_layout: Layout,
+        regions: T,
+    ) where
+        T: Iterator<Item = BufferImageCopy>,
+    {
+        self.check_outside_pass("copy_buffer_to_image");
+        self.touch(src.id, "copy_buffer_to_image (source)");
+        self.touch(dst.id, "copy_buffer_to_image (destination)");
+        self.raw
+            .copy_buffer_to_image(&src.raw, dst.raw(), dst_layout, regions)
+    }
+
+    unsafe fn copy_image_to_buffer<T>(
+        &mut self,
+        src: &Image<B>,
+        src_layout: Layout,
+        dst: &Buffer<B>,
+        regions: T,
+    ) where
+        T: Iterator<Item = BufferImageCopy>,
+    {
+        self.check_outside_pass("copy_image_to_buffer");
+        self.touch(src.id, "copy_image_to_buffer (source)");
+        self.touch(dst.id, "copy_image_to_buffer (destination)");
+        self.raw
+            .copy_image_to_buffer(src.raw(), src_layout, &dst.raw, regions)
+    }
+
+    unsafe fn draw(&mut self, vertices: Range<VertexCount>, instances: Range<InstanceCount>) {
+        self.check_draw("draw");
+        self.raw.draw(vertices, instances)
+    }
+
+    unsafe fn draw_indexed(
+        &mut self,
+        indices: Range<IndexCount>,
+        base_vertex: VertexOffset,
+        instances: Range<InstanceCount>,
+    ) {
+        self.check_draw("draw_indexed");
+        self.raw.draw_indexed(indices, base_vertex, instances)
+    }
+
+    unsafe fn draw_indirect(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        self.check_draw("draw_indirect");
+        self.touch(buffer.id, "draw_indirect");
+        self.raw
+            .draw_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn draw_indexed_indirect(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        self.check_draw("draw_indexed_indirect");
+        self.touch(buffer.id, "draw_indexed_indirect");
+        self.raw
+            .draw_indexed_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn draw_indirect_count(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        count_buffer: &Buffer<B>,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
+    ) {
+        self.check_draw("draw_indirect_count");
+        self.touch(buffer.id, "draw_indirect_count");
+        self.touch(count_buffer.id, "draw_indirect_count (count)");
+        self.raw.draw_indirect_count(
+            &buffer.raw,
+            offset,
+            &count_buffer.raw,
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        )
+    }
+
+    unsafe fn draw_indexed_indirect_count(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        count_buffer: &Buffer<B>,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: u32,
+        stride: buffer::Stride,
+    ) {
+        self.check_draw("draw_indexed_indirect_count");
+        self.touch(buffer.id, "draw_indexed_indirect_count");
+        self.touch(count_buffer.id, "draw_indexed_indirect_count (count)");
+        self.raw.draw_indexed_indirect_count(
+            &buffer.raw,
+            offset,
+            &count_buffer.raw,
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        )
+    }
+
+    unsafe fn bind_transform_feedback_buffers<'a, I>(&mut self, first_binding: u32, buffers: I)
+    where
+        I: Iterator<Item = (&'a Buffer<B>, buffer::SubRange)>,
+    {
+        self.check_recording("bind_transform_feedback_buffers");
+        let buffers = buffers.collect::<Vec<_>>();
+        for &(buffer, _) in &buffers {
+            self.touch(buffer.id, "bind_transform_feedback_buffers");
+        }
+        self.raw.bind_transform_feedback_buffers(
+            first_binding,
+            buffers.into_iter().map(|(buffer, sub)| (&buffer.raw, sub)),
+        )
+    }
+
+    unsafe fn begin_transform_feedback(&mut self) {
+        self.check_inside_pass("begin_transform_feedback");
+        self.raw.begin_transform_feedback()
+    }
+
+    unsafe fn end_transform_feedback(&mut self) {
+        self.check_inside_pass("end_transform_feedback");
+        self.raw.end_transform_feedback()
+    }
+
+    unsafe fn draw_transform_feedback(&mut self, instances: Range<InstanceCount>) {
+        self.check_draw("draw_transform_feedback");
+        self.raw.draw_transform_feedback(instances)
+    }
+
+    unsafe fn draw_mesh_tasks(&mut self, task_count: TaskCount, first_task: TaskCount) {
+        self.check_draw("draw_mesh_tasks");
+        if first_task != 0 && !self.first_mesh_task {
+            report!(
+                "`draw_mesh_tasks` starts from the task {}, but the device only supports starting from the first one",
+                first_task
+            );
+        }
+        self.raw.draw_mesh_tasks(task_count, first_task)
+    }
+
+    unsafe fn draw_mesh_tasks_indirect(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        self.check_draw("draw_mesh_tasks_indirect");
+        self.touch(buffer.id, "draw_mesh_tasks_indirect");
+        self.raw
+            .draw_mesh_tasks_indirect(&buffer.raw, offset, draw_count, stride)
+    }
+
+    unsafe fn draw_mesh_tasks_indirect_count(
+        &mut self,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        count_buffer: &Buffer<B>,
+        count_buffer_offset: buffer::Offset,
+        max_draw_count: DrawCount,
+        stride: buffer::Stride,
+    ) {
+        self.check_draw("draw_mesh_tasks_indirect_count");
+        self.touch(buffer.id, "draw_mesh_tasks_indirect_count");
+        self.touch(count_buffer.id, "draw_mesh_tasks_indirect_count (count)");
+        self.raw.draw_mesh_tasks_indirect_count(
+            &buffer.raw,
+            offset,
+            &count_buffer.raw,
+            count_buffer_offset,
+            max_draw_count,
+            stride,
+        )
+    }
+
+    unsafe fn set_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        self.check_outside_pass("set_event");
+        self.raw.set_event(event, stages)
+    }
+
+    unsafe fn reset_event(&mut self, event: &B::Event, stages: pso::PipelineStage) {
+        self.check_outside_pass("reset_event");
+        self.raw.reset_event(event, stages)
+    }
+
+    unsafe fn wait_events<'a, I, J>(
+        &mut self,
+        events: I,
+        stages: Range<pso::PipelineStage>,
+        barriers: J,
+    ) where
+        I: Iterator<Item = &'a B::Event>,
+        J: Iterator<Item = Barrier<'a, Validation<B>>>,
+    {
+        self.check_recording("wait_events");
+        self.raw.wait_events(events, stages, barriers.map(barrier))
+    }
+
+    unsafe fn begin_query(&mut self, query: q::Query<Validation<B>>, flags: q::ControlFlags) {
+        self.check_recording("begin_query");
+        self.raw.begin_query(super::query(query), flags)
+    }
+
+    unsafe fn end_query(&mut self, query: q::Query<Validation<B>>) {
+        self.check_recording("end_query");
+        self.raw.end_query(super::query(query))
+    }
+
+    unsafe fn reset_query_pool(&mut self, pool: &B::QueryPool, queries: Range<q::Id>) {
+        self.check_outside_pass("reset_query_pool");
+        self.raw.reset_query_pool(pool, queries)
+    }
+
+    unsafe fn copy_query_pool_results(
+        &mut self,
+        pool: &B::QueryPool,
+        queries: Range<q::Id>,
+        buffer: &Buffer<B>,
+        offset: buffer::Offset,
+        stride: buffer::Stride,
+        flags: q::ResultFlags,
+    ) {
+        self.check_outside_pass("copy_query_pool_results");
+        self.touch(buffer.id, "copy_query_pool_results");
+        self.raw
+            .copy_query_pool_results(pool, queries, &buffer.raw, offset, stride, flags)
+    }
+
+    unsafe fn write_timestamp(
+        &mut self,
+        stage: pso::PipelineStage,
+        query: q::Query<Validation<B>>,
+    ) {
+        self.check_recording("write_timestamp");
+        self.raw.write_timestamp(stage, super::query(query))
+    }
+
+    unsafe fn push_graphics_constants(
+        &mut self,
+        layout: &PipelineLayout<B>,
+        stages: pso::ShaderStageFlags,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.check_recording("push_graphics_constants");
+        self.raw
+            .push_graphics_constants(&layout.raw, stages, offset, constants)
+    }
+
+    unsafe fn push_compute_constants(
+        &mut self,
+        layout: &PipelineLayout<B>,
+        offset: u32,
+        constants: &[u32],
+    ) {
+        self.check_recording("push_compute_constants");
+        self.raw
+            .push_compute_constants(&layout.raw, offset, constants)
+    }
+
+    unsafe fn execute_commands<'a, T>(&mut self, cmd_buffers: T)
+    where
+        T: Iterator<Item = &'a CommandBuffer<B>>,
+    {
+        self.check_recording("execute_commands");
+        if self.level != Level::Primary {
+            report!("`execute_commands` is recorded in a secondary command buffer");
+        }
+        match self.pass {
+            Some(ref pass) if !pass.secondary_contents => {
+                report!("`execute_commands` is recorded in a subpass with inline contents")
+            }
+            _ => {}
+        }
+        let cmd_buffers = cmd_buffers.collect::<Vec<_>>();
+        for cmd_buffer in &cmd_buffers {
+            if cmd_buffer.level != Level::Secondary {
+                report!("A primary command buffer is executed by `execute_commands`");
+            }
+            let state = cmd_buffer.state();
+            if state != State::Executable {
+                report!(
+                    "A command buffer is executed by `execute_commands` in the {:?} state",
+                    state
+                );
+            }
+            // The uses of the secondary command buffers are tagged with the primary one.
+            self.touches.extend_from_slice(&cmd_buffer.touches);
+        }
+        self.raw
+            .execute_commands(cmd_buffers.into_iter().map(|cmd_buffer| &cmd_buffer.raw))
+    }
+
+    unsafe fn insert_debug_marker(&mut self, name: &str, color: u32) {
+        self.check_recording("insert_debug_marker");
+        self.raw.insert_debug_marker(name, color)
+    }
+
+    unsafe fn begin_debug_marker(&mut self, name: &str, color: u32) {
+        self.check_recording("begin_debug_marker");
+        self.raw.begin_debug_marker(name, color)
+    }
+
+    unsafe fn end_debug_marker(&mut self) {
+        self.check_recording("end_debug_marker");
+        self.raw.end_debug_marker()
+    }
+}
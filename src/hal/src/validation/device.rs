@@ -0,0 +1,1282 @@
+use super::{
+    base_pipeline, descriptor, entry_point, geometry_descs, has_node, shader_group, subpass,
+    timeline::{Timeline, Touch},
+    Buffer, CommandBuffer, ComputePipeline, DescriptorSet, DescriptorSetLayout, GraphicsPipeline,
+    Image, ImageView, PipelineLayout, PipelineLayoutInfo, RayTracingPipeline, SetLayoutInfo,
+    Validation,
+};
+use crate::{
+    acceleration_structure as accel, buffer,
+    command::Level,
+    device::{
+        AllocationError, BindError, DeviceLost, MapError, NodeMask, OomOrDeviceLost, OutOfMemory,
+        ShaderError, WaitError, WaitFor,
+    },
+    display, external_memory, format, image,
+    memory::{HeapBudget, Priority, Requirements, Segment, SparseFlags},
+    pass,
+    pool::CommandPoolCreateFlags,
+    pso::{self, DescriptorPoolCreateFlags},
+    query,
+    queue::QueueFamilyId,
+    Backend, Limits, MemoryTypeId,
+};
+
+use std::{
+    iter,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Logical device of the validation adaptor.
+#[derive(Debug)]
+pub struct Device<B: Backend> {
+    pub(super) raw: B::Device,
+    limits: Limits,
+    /// Whether the mesh tasks can start from a non-zero task.
+    first_mesh_task: bool,
+    /// Nodes the device is opened with.
+    nodes: NodeMask,
+    pub(super) timeline: Arc<Timeline>,
+}
+
+impl<B: Backend> Device<B> {
+    pub(super) fn new(
+        raw: B::Device,
+        limits: Limits,
+        first_mesh_task: bool,
+        nodes: NodeMask,
+    ) -> Self {
+        Device {
+            raw,
+            limits,
+            first_mesh_task,
+            nodes,
+            timeline: Arc::default(),
+        }
+    }
+
+    /// Record the uses of the buffers and images by the submitted commands,
+    /// keeping the ones of the last `frames` frames.
+    ///
+    /// A frame ends with every [`present`][crate::queue::Queue::present], or with
+    /// [`end_usage_frame`][Device::end_usage_frame] for applications not presenting.
+    /// The uses of a resource are retrieved with [`buffer_usage`][Device::buffer_usage]
+    /// and [`image_usage`][Device::image_usage], e.g. when a barrier or lifetime issue
+    /// is reported on it.
+    pub fn enable_usage_timeline(&self, frames: usize) {
+        self.timeline.enable(frames);
+    }
+
+    /// End the current frame of the usage timeline.
+    pub fn end_usage_frame(&self) {
+        self.timeline.end_frame();
+    }
+
+    /// Uses of a buffer within the last frames, oldest first.
+    pub fn buffer_usage(&self, buffer: &Buffer<B>) -> Vec<Touch> {
+        self.timeline.history(buffer.id)
+    }
+
+    /// Uses of an image within the last frames, oldest first.
+    pub fn image_usage(&self, image: &Image<B>) -> Vec<Touch> {
+        self.timeline.history(image.id)
+    }
+
+    fn wrap_command_pool(
+        &self,
+        raw: B::CommandPool,
+        flags: CommandPoolCreateFlags,
+    ) -> CommandPool<B> {
+        CommandPool {
+            raw,
+            flags,
+            resets: Arc::new(AtomicUsize::new(0)),
+            max_viewports: self.limits.max_viewports,
+            first_mesh_task: self.first_mesh_task,
+            timeline: Arc::clone(&self.timeline),
+        }
+    }
+}
+
+/// Command pool of the validation adaptor.
+#[derive(Debug)]
+pub struct CommandPool<B: Backend> {
+    raw: B::CommandPool,
+    flags: CommandPoolCreateFlags,
+    /// Number of times the pool has been reset, shared with the command buffers.
+    resets: Arc<AtomicUsize>,
+    max_viewports: usize,
+    first_mesh_task: bool,
+    timeline: Arc<Timeline>,
+}
+
+impl<B: Backend> crate::pool::CommandPool<Validation<B>> for CommandPool<B> {
+    unsafe fn reset(&mut self, release_resources: bool) {
+        self.resets.fetch_add(1, Ordering::Relaxed);
+        self.raw.reset(release_resources);
+    }
+
+    unsafe fn allocate_one(&mut self, level: Level) -> CommandBuffer<B> {
+        CommandBuffer::new(
+            self.raw.allocate_one(level),
+            level,
+            self.flags,
+            Arc::clone(&self.resets),
+            self.max_viewports,
+            self.first_mesh_task,
+            Arc::clone(&self.timeline),
+        )
+    }
+
+    unsafe fn allocate<E>(&mut self, num: usize, level: Level, list: &mut E)
+    where
+        E: Extend<CommandBuffer<B>>,
+    {
+        let mut raw = Vec::with_capacity(num);
+        self.raw.allocate(num, level, &mut raw);
+        list.extend(raw.into_iter().map(|raw| {
+            CommandBuffer::new(
+                raw,
+                level,
+                self.flags,
+                Arc::clone(&self.resets),
+                self.max_viewports,
+                self.first_mesh_task,
+                Arc::clone(&self.timeline),
+            )
+        }));
+    }
+
+    unsafe fn free<I>(&mut self, buffers: I)
+    where
+        I: Iterator<Item = CommandBuffer<B>>,
+    {
+        self.raw.free(buffers.map(|cmd_buffer| cmd_buffer.raw))
+    }
+}
+
+/// Descriptor pool of the validation adaptor.
+#[derive(Debug)]
+pub struct DescriptorPool<B: Backend> {
+    raw: B::DescriptorPool,
+    flags: DescriptorPoolCreateFlags,
+}
+
+impl<B: Backend> DescriptorPool<B> {
+    fn check_layout(&self, layout: &SetLayoutInfo) {
+        if layout.update_after_bind()
+            && !self
+                .flags
+                .contains(DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+        {
+            report!("Allocating a set with update after bind bindings from a pool without the `UPDATE_AFTER_BIND` flag");
+        }
+    }
+}
+
+impl<B: Backend> pso::DescriptorPool<Validation<B>> for DescriptorPool<B> {
+    unsafe fn allocate_one(
+        &mut self,
+        layout: &DescriptorSetLayout<B>,
+    ) -> Result<DescriptorSet<B>, pso::AllocationError> {
+        self.check_layout(&layout.info);
+        self.raw.allocate_one(&layout.raw).map(|raw| DescriptorSet {
+            raw,
+            layout: Arc::clone(&layout.info),
+        })
+    }
+
+    unsafe fn allocate_variable(
+        &mut self,
+        layout: &DescriptorSetLayout<B>,
+        count: pso::DescriptorArrayIndex,
+    ) -> Result<DescriptorSet<B>, pso::AllocationError> {
+        self.check_layout(&layout.info);
+        match layout.info.variable_count() {
+            Some(binding) if count > binding.count => report!(
+                "Variable count {} exceeds the count {} of binding {}",
+                count,
+                binding.count,
+                binding.binding
+            ),
+            Some(_) => {}
+            None => {
+                report!("Allocating a variable count for a layout without a variable count binding")
+            }
+        }
+        self.raw
+            .allocate_variable(&layout.raw, count)
+            .map(|raw| DescriptorSet {
+                raw,
+                layout: Arc::clone(&layout.info),
+            })
+    }
+
+    unsafe fn allocate<'a, I, E>(
+        &mut self,
+        layouts: I,
+        list: &mut E,
+    ) -> Result<(), pso::AllocationError>
+    where
+        I: Iterator<Item = &'a DescriptorSetLayout<B>>,
+        E: Extend<DescriptorSet<B>>,
+    {
+        let layouts = layouts.collect::<Vec<_>>();
+        for layout in &layouts {
+            self.check_layout(&layout.info);
+        }
+        let mut raw = Vec::with_capacity(layouts.len());
+        let result = self
+            .raw
+            .allocate(layouts.iter().map(|layout| &layout.raw), &mut raw);
+        list.extend(
+            raw.into_iter()
+                .zip(&layouts)
+                .map(|(raw, layout)| DescriptorSet {
+                    raw,
+                    layout: Arc::clone(&layout.info),
+                }),
+        );
+        result
+    }
+
+    unsafe fn free<I>(&mut self, descriptor_sets: I)
+    where
+        I: Iterator<Item = DescriptorSet<B>>,
+    {
+        self.raw.free(descriptor_sets.map(|set| set.raw))
+    }
+
+    unsafe fn reset(&mut self) {
+        self.raw.reset()
+    }
+}
+
+impl<B: Backend> crate::device::Device<Validation<B>> for Device<B> {
+    unsafe fn allocate_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+    ) -> Result<B::Memory, AllocationError> {
+        self.raw.allocate_memory(memory_type, size)
+    }
+
+    unsafe fn allocate_node_memory(
+        &self,
+        memory_type: MemoryTypeId,
+        size: u64,
+        node: u32,
+        visible_nodes: NodeMask,
+    ) -> Result<B::Memory, AllocationError> {
+        if !has_node(self.nodes, node) {
+            report!(
+                "Allocating memory on node {}, outside of the nodes {:#b} of the device",
+                node,
+                self.nodes
+            );
+        } else if !has_node(visible_nodes, node) {
+            report!("Memory of node {} isn't visible to it", node);
+        }
+        if visible_nodes & !self.nodes != 0 {
+            report!(
+                "Memory visible to the nodes {:#b}, outside of the nodes {:#b} of the device",
+                visible_nodes,
+                self.nodes
+            );
+        }
+        self.raw
+            .allocate_node_memory(memory_type, size, node, visible_nodes)
+    }
+
+    unsafe fn free_memory(&self, memory: B::Memory) {
+        self.raw.free_memory(memory)
+    }
+
+    fn memory_budget(&self) -> Vec<HeapBudget> {
+        self.raw.memory_budget()
+    }
+
+    unsafe fn set_memory_priority(&self, memory: &B::Memory, priority: Priority) {
+        self.raw.set_memory_priority(memory, priority)
+    }
+
+    unsafe fn make_resident<'a, I>(&self, memories: I) -> Result<(), OutOfMemory>
+    where
+        I: Iterator<Item = &'a B::Memory>,
+    {
+        self.raw.make_resident(memories)
+    }
+
+    unsafe fn evict<'a, I>(&self, memories: I)
+    where
+        I: Iterator<Item = &'a B::Memory>,
+    {
+        self.raw.evict(memories)
+    }
+
+    unsafe fn create_command_pool(
+        &self,
+        family: QueueFamilyId,
+        create_flags: CommandPoolCreateFlags,
+    ) -> Result<CommandPool<B>, OutOfMemory> {
+        self.raw
+            .create_command_pool(family, create_flags)
+            .map(|raw| self.wrap_command_pool(raw, create_flags))
+    }
+
+    unsafe fn create_node_command_pool(
+        &self,
+        family: QueueFamilyId,
+        create_flags: CommandPoolCreateFlags,
+        node: u32,
+    ) -> Result<CommandPool<B>, OutOfMemory> {
+        if !has_node(self.nodes, node) {
+            report!(
+                "Creating a command pool on node {}, outside of the nodes {:#b} of the device",
+                node,
+                self.nodes
+            );
+        }
+        self.raw
+            .create_node_command_pool(family, create_flags, node)
+            .map(|raw| self.wrap_command_pool(raw, create_flags))
+    }
+
+    unsafe fn destroy_command_pool(&self, pool: CommandPool<B>) {
+        self.raw.destroy_command_pool(pool.raw)
+    }
+
+    unsafe fn create_render_pass<'a, Ia, Is, Id>(
+        &self,
+        attachments: Ia,
+        subpasses: Is,
+        dependencies: Id,
+    ) -> Result<B::RenderPass, OutOfMemory>
+    where
+        Ia: Iterator<Item = pass::Attachment>,
+        Is: Iterator<Item = pass::SubpassDesc<'a>>,
+        Id: Iterator<Item = pass::SubpassDependency>,
+    {
+        let subpasses = subpasses.collect::<Vec<_>>();
+        for (index, subpass) in subpasses.iter().enumerate() {
+            if subpass.colors.len() > self.limits.max_color_attachments {
+                report!(
+                    "Subpass {} has {} color attachments, exceeding the limit of {}",
+                    index,
+                    subpass.colors.len(),
+                    self.limits.max_color_attachments
+                );
+            }
+        }
+        self.raw
+            .create_render_pass(attachments, subpasses.into_iter(), dependencies)
+    }
+
+    unsafe fn destroy_render_pass(&self, rp: B::RenderPass) {
+        self.raw.destroy_render_pass(rp)
+    }
+
+    unsafe fn create_pipeline_layout<'a, Is, Ic>(
+        &self,
+        set_layouts: Is,
+        push_constant: Ic,
+    ) -> Result<PipelineLayout<B>, OutOfMemory>
+    where
+        Is: Iterator<Item = &'a DescriptorSetLayout<B>>,
+        Ic: Iterator<Item = (pso::ShaderStageFlags, Range<u32>)>,
+    {
+        let set_layouts = set_layouts.collect::<Vec<_>>();
+        let push_constants = push_constant.collect::<Vec<_>>();
+        let raw = self.raw.create_pipeline_layout(
+            set_layouts.iter().map(|layout| &layout.raw),
+            push_constants.iter().cloned(),
+        )?;
+        Ok(PipelineLayout {
+            raw,
+            info: Arc::new(PipelineLayoutInfo {
+                sets: set_layouts
+                    .iter()
+                    .map(|layout| Arc::clone(&layout.info))
+                    .collect(),
+                push_constants,
+            }),
+        })
+    }
+
+    unsafe fn destroy_pipeline_layout(&self, layout: PipelineLayout<B>) {
+        self.raw.destroy_pipeline_layout(layout.raw)
+    }
+
+    unsafe fn create_pipeline_cache(
+        &self,
+        data: Option<&[u8]>,
+    ) -> Result<B::PipelineCache, OutOfMemory> {
+        self.raw.create_pipeline_cache(data)
+    }
+
+    unsafe fn get_pipeline_cache_data(
+        &self,
+        cache: &B::PipelineCache,
+    ) -> Result<Vec<u8>, OutOfMemory> {
+        self.raw.get_pipeline_cache_data(cache)
+    }
+
+    unsafe fn merge_pipeline_caches<'a, I>(
+        &self,
+        target: &mut B::PipelineCache,
+        sources: I,
+    ) -> Result<(), OutOfMemory>
+    where
+        I: Iterator<Item = &'a B::PipelineCache>,
+    {
+        self.raw.merge_pipeline_caches(target, sources)
+    }
+
+    unsafe fn destroy_pipeline_cache(&self, cache: B::PipelineCache) {
+        self.raw.destroy_pipeline_cache(cache)
+    }
+
+    unsafe fn create_graphics_pipeline<'a>(
+        &self,
+        desc: &pso::GraphicsPipelineDesc<'a, Validation<B>>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<GraphicsPipeline<B>, pso::CreationError> {
+        if desc.blender.targets.len() > self.limits.max_color_attachments {
+            report!(
+                "Pipeline has {} blend targets, exceeding the color attachment limit of {}",
+                desc.blender.targets.len(),
+                self.limits.max_color_attachments
+            );
+        }
+        let primitive_assembler = match desc.primitive_assembler {
+            pso::PrimitiveAssemblerDesc::Vertex {
+                buffers,
+                attributes,
+                ref input_assembler,
+                ref vertex,
+                ref tessellation,
+                ref geometry,
+            } => pso::PrimitiveAssemblerDesc::Vertex {
+                buffers,
+                attributes,
+                input_assembler: input_assembler.clone(),
+                vertex: entry_point(vertex),
+                tessellation: tessellation
+                    .as_ref()
+                    .map(|(hull, domain)| (entry_point(hull), entry_point(domain))),
+                geometry: geometry.as_ref().map(entry_point),
+            },
+            pso::PrimitiveAssemblerDesc::Mesh { ref task, ref mesh } => {
+                pso::PrimitiveAssemblerDesc::Mesh {
+                    task: task.as_ref().map(entry_point),
+                    mesh: entry_point(mesh),
+                }
+            }
+        };
+        let raw_desc = pso::GraphicsPipelineDesc {
+            label: desc.label,
+            primitive_assembler,
+            rasterizer: desc.rasterizer,
+            fragment: desc.fragment.as_ref().map(entry_point),
+            blender: desc.blender.clone(),
+            depth_stencil: desc.depth_stencil,
+            multisampling: desc.multisampling.clone(),
+            baked_states: desc.baked_states.clone(),
+            layout: &desc.layout.raw,
+            subpass: subpass(&desc.subpass),
+            flags: desc.flags,
+            parent: base_pipeline(&desc.parent, |pipeline: &'a GraphicsPipeline<B>| {
+                &pipeline.raw
+            }),
+            transform_feedback: desc.transform_feedback.clone(),
+        };
+        let raw = self.raw.create_graphics_pipeline(&raw_desc, cache)?;
+        Ok(GraphicsPipeline {
+            raw,
+            layout: Arc::clone(&desc.layout.info),
+            subpass: desc.subpass.index,
+            dynamic_viewport: desc.baked_states.viewport.is_none(),
+            dynamic_scissor: desc.baked_states.scissor.is_none(),
+        })
+    }
+
+    unsafe fn destroy_graphics_pipeline(&self, pipeline: GraphicsPipeline<B>) {
+        self.raw.destroy_graphics_pipeline(pipeline.raw)
+    }
+
+    unsafe fn create_compute_pipeline<'a>(
+        &self,
+        desc: &pso::ComputePipelineDesc<'a, Validation<B>>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<ComputePipeline<B>, pso::CreationError> {
+        let raw_desc = pso::ComputePipelineDesc {
+            label: desc.label,
+            shader: entry_point(&desc.shader),
+            layout: &desc.layout.raw,
+            flags: desc.flags,
+            parent: base_pipeline(&desc.parent, |pipeline: &'a ComputePipeline<B>| {
+                &pipeline.raw
+            }),
+        };
+        let raw = self.raw.create_compute_pipeline(&raw_desc, cache)?;
+        Ok(ComputePipeline {
+            raw,
+            layout: Arc::clone(&desc.layout.info),
+        })
+    }
+
+    unsafe fn destroy_compute_pipeline(&self, pipeline: ComputePipeline<B>) {
+        self.raw.destroy_compute_pipeline(pipeline.raw)
+    }
+
+    unsafe fn create_ray_tracing_pipeline<'a>(
+        &self,
+        desc: &pso::RayTracingPipelineDesc<'a, Validation<B>>,
+        cache: Option<&B::PipelineCache>,
+    ) -> Result<RayTracingPipeline<B>, pso::CreationError> {
+        let groups = desc.groups.iter().map(shader_group).collect::<Vec<_>>();
+        let raw_desc = pso::RayTracingPipelineDesc {
+            label: desc.label,
+            groups: &groups,
+            max_recursion_depth: desc.max_recursion_depth,
+            max_payload_size: desc.max_payload_size,
+            max_attribute_size: desc.max_attribute_size,
+            layout: &desc.layout.raw,
+        };
+        let raw = self.raw.create_ray_tracing_pipeline(&raw_desc, cache)?;
+        Ok(RayTracingPipeline {
+            raw,
+            layout: Arc::clone(&desc.layout.info),
+        })
+    }
+
+    unsafe fn destroy_ray_tracing_pipeline(&self, pipeline: RayTracingPipeline<B>) {
+        self.raw.destroy_ray_tracing_pipeline(pipeline.raw)
+    }
+
+    unsafe fn get_ray_tracing_shader_group_handles(
+        &self,
+        pipeline: &RayTracingPipeline<B>,
+        first_group: u32,
+        group_count: u32,
+        data: &mut [u8],
+    ) -> Result<(), OutOfMemory> {
+        self.raw
+            .get_ray_tracing_shader_group_handles(&pipeline.raw, first_group, group_count, data)
+    }
+
+    unsafe fn create_framebuffer<I>(
+        &self,
+        pass: &B::RenderPass,
+        attachments: I,
+        extent: image::Extent,
+    ) -> Result<B::Framebuffer, OutOfMemory>
+    where
+        I: Iterator<Item = image::FramebufferAttachment>,
+    {
+        let attachments = attachments.collect::<Vec<_>>();
+        let colors = attachments
+            .iter()
+            .filter(|attachment| attachment.format.is_color())
+            .count();
+        if colors > self.limits.max_color_attachments {
+            report!(
+                "Framebuffer has {} color attachments, exceeding the limit of {}",
+                colors,
+                self.limits.max_color_attachments
+            );
+        }
+        self.raw
+            .create_framebuffer(pass, attachments.into_iter(), extent)
+    }
+
+    unsafe fn destroy_framebuffer(&self, buf: B::Framebuffer) {
+        self.raw.destroy_framebuffer(buf)
+    }
+
+    unsafe fn create_shader_module(&self, spirv: &[u32]) -> Result<B::ShaderModule, ShaderError> {
+        self.raw.create_shader_module(spirv)
+    }
+
+    unsafe fn create_shader_module_from_naga(
+        &self,
+        shader: crate::device::NagaShader,
+    ) -> Result<B::ShaderModule, (ShaderError, crate::device::NagaShader)> {
+        self.raw.create_shader_module_from_naga(shader)
+    }
+
+    unsafe fn destroy_shader_module(&self, shader: B::ShaderModule) {
+        self.raw.destroy_shader_module(shader)
+    }
+
+    unsafe fn create_buffer(
+        &self,
+        size: u64,
+        usage: buffer::Usage,
+        sparse: SparseFlags,
+    ) -> Result<Buffer<B>, buffer::CreationError> {
+        self.raw.create_buffer(size, usage, sparse).map(Buffer::new)
+    }
+
+    unsafe fn get_buffer_requirements(&self, buf: &Buffer<B>) -> Requirements {
+        self.raw.get_buffer_requirements(&buf.raw)
+    }
+
+    unsafe fn bind_buffer_memory(
+        &self,
+        memory: &B::Memory,
+        offset: u64,
+        buf: &mut Buffer<B>,
+    ) -> Result<(), BindError> {
+        self.raw.bind_buffer_memory(memory, offset, &mut buf.raw)
+    }
+
+    unsafe fn destroy_buffer(&self, buffer: Buffer<B>) {
+        self.raw.destroy_buffer(buffer.raw)
+    }
+
+    unsafe fn create_buffer_view(
+        &self,
+        buf: &Buffer<B>,
+        fmt: Option<format::Format>,
+        range: buffer::SubRange,
+    ) -> Result<B::BufferView, buffer::ViewCreationError> {
+        check_buffer_view(&self.limits, fmt, &range);
+        self.raw.create_buffer_view(&buf.raw, fmt, range)
+    }
+
+    unsafe fn destroy_buffer_view(&self, view: B::BufferView) {
+        self.raw.destroy_buffer_view(view)
+    }
+
+    fn format_properties(&self, format: format::Format) -> format::Properties {
+        self.raw.format_properties(format)
+    }
+
+    unsafe fn create_image(
+        &self,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: SparseFlags,
+        view_caps: image::ViewCapabilities,
+    ) -> Result<Image<B>, image::CreationError> {
+        check_image_usage(usage);
+        self.raw
+            .create_image(kind, mip_levels, format, tiling, usage, sparse, view_caps)
+            .map(Image::new)
+    }
+
+    unsafe fn create_partial_image(
+        &self,
+        kind: image::Kind,
+        levels: Range<image::Level>,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: SparseFlags,
+        view_caps: image::ViewCapabilities,
+    ) -> Result<Image<B>, image::CreationError> {
+        check_image_usage(usage);
+        self.raw
+            .create_partial_image(kind, levels, format, tiling, usage, sparse, view_caps)
+            .map(Image::new)
+    }
+
+    unsafe fn get_image_requirements(&self, image: &Image<B>) -> Requirements {
+        self.raw.get_image_requirements(image.raw())
+    }
+
+    unsafe fn get_image_subresource_footprint(
+        &self,
+        image: &Image<B>,
+        subresource: image::Subresource,
+    ) -> image::SubresourceFootprint {
+        self.raw
+            .get_image_subresource_footprint(image.raw(), subresource)
+    }
+
+    unsafe fn bind_image_memory(
+        &self,
+        memory: &B::Memory,
+        offset: u64,
+        image: &mut Image<B>,
+    ) -> Result<(), BindError> {
+        self.raw.bind_image_memory(memory, offset, image.raw_mut())
+    }
+
+    unsafe fn destroy_image(&self, image: Image<B>) {
+        self.raw.destroy_image(image.into_raw())
+    }
+
+    unsafe fn create_image_view(
+        &self,
+        image: &Image<B>,
+        view_kind: image::ViewKind,
+        format: format::Format,
+        swizzle: format::Swizzle,
+        usage: image::Usage,
+        range: image::SubresourceRange,
+    ) -> Result<ImageView<B>, image::ViewCreationError> {
+        self.raw
+            .create_image_view(image.raw(), view_kind, format, swizzle, usage, range)
+            .map(|raw| ImageView { raw })
+    }
+
+    unsafe fn destroy_image_view(&self, view: ImageView<B>) {
+        self.raw.destroy_image_view(view.raw)
+    }
+
+    unsafe fn create_sampler(
+        &self,
+        desc: &image::SamplerDesc,
+    ) -> Result<B::Sampler, image::SamplerCreationError> {
+        self.raw.create_sampler(desc)
+    }
+
+    unsafe fn destroy_sampler(&self, sampler: B::Sampler) {
+        self.raw.destroy_sampler(sampler)
+    }
+
+    unsafe fn create_descriptor_pool<I>(
+        &self,
+        max_sets: usize,
+        descriptor_ranges: I,
+        flags: DescriptorPoolCreateFlags,
+    ) -> Result<DescriptorPool<B>, OutOfMemory>
+    where
+        I: Iterator<Item = pso::DescriptorRangeDesc>,
+    {
+        self.raw
+            .create_descriptor_pool(max_sets, descriptor_ranges, flags)
+            .map(|raw| DescriptorPool { raw, flags })
+    }
+
+    unsafe fn destroy_descriptor_pool(&self, pool: DescriptorPool<B>) {
+        self.raw.destroy_descriptor_pool(pool.raw)
+    }
+
+    unsafe fn create_descriptor_set_layout<'a, I, J>(
+        &self,
+        bindings: I,
+        immutable_samplers: J,
+    ) -> Result<DescriptorSetLayout<B>, OutOfMemory>
+    where
+        I: Iterator<Item = pso::DescriptorSetLayoutBinding>,
+        J: Iterator<Item = &'a B::Sampler>,
+    {
+        let bindings = bindings.collect::<Vec<_>>();
+        let highest = bindings.iter().map(|binding| binding.binding).max();
+        for binding in &bindings {
+            if binding
+                .flags
+                .contains(pso::DescriptorBindingFlags::VARIABLE_COUNT)
+                && Some(binding.binding) != highest
+            {
+                report!(
+                    "Binding {} has a variable count without being the highest binding",
+                    binding.binding
+                );
+            }
+            if binding
+                .flags
+                .contains(pso::DescriptorBindingFlags::UPDATE_AFTER_BIND)
+            {
+                match binding.ty {
+                    pso::DescriptorType::Sampler
+                    | pso::DescriptorType::Image {
+                        ty: pso::ImageDescriptorType::Sampled { with_sampler: true },
+                    }
+                    | pso::DescriptorType::Buffer {
+                        ty: pso::BufferDescriptorType::Uniform,
+                        format: pso::BufferDescriptorFormat::Structured { .. },
+                    } => report!(
+                        "Binding {} of type {:?} can't be updated after binding",
+                        binding.binding,
+                        binding.ty
+                    ),
+                    _ => {}
+                }
+            }
+        }
+        let raw = self
+            .raw
+            .create_descriptor_set_layout(bindings.iter().cloned(), immutable_samplers)?;
+        Ok(DescriptorSetLayout {
+            raw,
+            info: Arc::new(SetLayoutInfo::new(bindings)),
+        })
+    }
+
+    unsafe fn destroy_descriptor_set_layout(&self, layout: DescriptorSetLayout<B>) {
+        self.raw.destroy_descriptor_set_layout(layout.raw)
+    }
+
+    unsafe fn write_descriptor_set<'a, I>(&self, op: pso::DescriptorSetWrite<'a, Validation<B>, I>)
+    where
+        I: Iterator<Item = pso::Descriptor<'a, Validation<B>>>,
+    {
+        self.raw.write_descriptor_set(descriptor_set_write(op))
+    }
+
+    unsafe fn copy_descriptor_set<'a>(&self, op: pso::DescriptorSetCopy<'a, Validation<B>>) {
+        self.raw.copy_descriptor_set(descriptor_set_copy(op))
+    }
+
+    unsafe fn update_descriptor_sets<'a, I, J, K>(&self, writes: I, copies: K)
+    where
+        I: Iterator<Item = pso::DescriptorSetWrite<'a, Validation<B>, J>>,
+        J: Iterator<Item = pso::Descriptor<'a, Validation<B>>>,
+        K: Iterator<Item = pso::DescriptorSetCopy<'a, Validation<B>>>,
+    {
+        self.raw.update_descriptor_sets(
+            writes.map(descriptor_set_write),
+            copies.map(descriptor_set_copy),
+        )
+    }
+
+    unsafe fn map_memory(
+        &self,
+        memory: &mut B::Memory,
+        segment: Segment,
+    ) -> Result<*mut u8, MapError> {
+        self.raw.map_memory(memory, segment)
+    }
+
+    unsafe fn flush_mapped_memory_ranges<'a, I>(&self, ranges: I) -> Result<(), OutOfMemory>
+    where
+        I: Iterator<Item = (&'a B::Memory, Segment)>,
+    {
+        self.raw.flush_mapped_memory_ranges(ranges)
+    }
+
+    unsafe fn invalidate_mapped_memory_ranges<'a, I>(&self, ranges: I) -> Result<(), OutOfMemory>
+    where
+        I: Iterator<Item = (&'a B::Memory, Segment)>,
+    {
+        self.raw.invalidate_mapped_memory_ranges(ranges)
+    }
+
+    unsafe fn unmap_memory(&self, memory: &mut B::Memory) {
+        self.raw.unmap_memory(memory)
+    }
+
+    fn create_semaphore(&self) -> Result<B::Semaphore, OutOfMemory> {
+        self.raw.create_semaphore()
+    }
+
+    unsafe fn destroy_semaphore(&self, semaphore: B::Semaphore) {
+        self.raw.destroy_semaphore(semaphore)
+    }
+
+    fn create_fence(&self, signaled: bool) -> Result<B::Fence, OutOfMemory> {
+        self.raw.create_fence(signaled)
+    }
+
+    unsafe fn reset_fence(&self, fence: &mut B::Fence) -> Result<(), OutOfMemory> {
+        self.raw.reset_fence(fence)
+    }
+
+    unsafe fn wait_for_fence(&self, fence: &B::Fence, timeout_ns: u64) -> Result<bool, WaitError> {
+        self.raw.wait_for_fence(fence, timeout_ns)
+    }
+
+    unsafe fn wait_for_fences<'a, I>(
+        &self,
+        fences: I,
+        wait: WaitFor,
+        timeout_ns: u64,
+    ) -> Result<bool, WaitError>
+    where
+        I: Iterator<Item = &'a B::Fence>,
+    {
+        self.raw.wait_for_fences(fences, wait, timeout_ns)
+    }
+
+    unsafe fn get_fence_status(&self, fence: &B::Fence) -> Result<bool, DeviceLost> {
+        self.raw.get_fence_status(fence)
+    }
+
+    unsafe fn destroy_fence(&self, fence: B::Fence) {
+        self.raw.destroy_fence(fence)
+    }
+
+    fn create_event(&self) -> Result<B::Event, OutOfMemory> {
+        self.raw.create_event()
+    }
+
+    unsafe fn destroy_event(&self, event: B::Event) {
+        self.raw.destroy_event(event)
+    }
+
+    unsafe fn get_event_status(&self, event: &B::Event) -> Result<bool, WaitError> {
+        self.raw.get_event_status(event)
+    }
+
+    unsafe fn set_event(&self, event: &mut B::Event) -> Result<(), OutOfMemory> {
+        self.raw.set_event(event)
+    }
+
+    unsafe fn reset_event(&self, event: &mut B::Event) -> Result<(), OutOfMemory> {
+        self.raw.reset_event(event)
+    }
+
+    unsafe fn create_query_pool(
+        &self,
+        ty: query::Type,
+        count: query::Id,
+    ) -> Result<B::QueryPool, query::CreationError> {
+        self.raw.create_query_pool(ty, count)
+    }
+
+    unsafe fn destroy_query_pool(&self, pool: B::QueryPool) {
+        self.raw.destroy_query_pool(pool)
+    }
+
+    unsafe fn get_acceleration_structure_build_requirements(
+        &self,
+        geometry: &accel::GeometryInfo<Validation<B>>,
+    ) -> accel::SizeRequirements {
+        let geometries = geometry_descs(geometry.geometries);
+        self.raw
+            .get_acceleration_structure_build_requirements(&accel::GeometryInfo {
+                ty: geometry.ty,
+                flags: geometry.flags,
+                geometries: &geometries,
+            })
+    }
+
+    unsafe fn create_acceleration_structure(
+        &self,
+        desc: &accel::CreateDesc<Validation<B>>,
+    ) -> Result<B::AccelerationStructure, OutOfMemory> {
+        if desc.offset % 256 != 0 {
+            report!(
+                "Acceleration structure is created at the offset {}, which is not aligned to 256",
+                desc.offset
+            );
+        }
+        self.raw.create_acceleration_structure(&accel::CreateDesc {
+            ty: desc.ty,
+            buffer: &desc.buffer.raw,
+            offset: desc.offset,
+            size: desc.size,
+        })
+    }
+
+    unsafe fn get_acceleration_structure_address(
+        &self,
+        accel_struct: &B::AccelerationStructure,
+    ) -> accel::DeviceAddress {
+        self.raw.get_acceleration_structure_address(accel_struct)
+    }
+
+    unsafe fn destroy_acceleration_structure(&self, accel_struct: B::AccelerationStructure) {
+        self.raw.destroy_acceleration_structure(accel_struct)
+    }
+
+    unsafe fn get_query_pool_results(
+        &self,
+        pool: &B::QueryPool,
+        queries: Range<query::Id>,
+        data: &mut [u8],
+        stride: buffer::Stride,
+        flags: query::ResultFlags,
+    ) -> Result<bool, WaitError> {
+        self.raw
+            .get_query_pool_results(pool, queries, data, stride, flags)
+    }
+
+    fn wait_idle(&self) -> Result<(), OomOrDeviceLost> {
+        self.raw.wait_idle()
+    }
+
+    unsafe fn set_image_name(&self, image: &mut Image<B>, name: &str) {
+        self.raw.set_image_name(image.raw_mut(), name)
+    }
+
+    unsafe fn set_buffer_name(&self, buffer: &mut Buffer<B>, name: &str) {
+        self.raw.set_buffer_name(&mut buffer.raw, name)
+    }
+
+    unsafe fn set_command_buffer_name(&self, command_buffer: &mut CommandBuffer<B>, name: &str) {
+        command_buffer.name = Some(name.to_owned());
+        self.raw
+            .set_command_buffer_name(&mut command_buffer.raw, name)
+    }
+
+    unsafe fn set_semaphore_name(&self, semaphore: &mut B::Semaphore, name: &str) {
+        self.raw.set_semaphore_name(semaphore, name)
+    }
+
+    unsafe fn set_fence_name(&self, fence: &mut B::Fence, name: &str) {
+        self.raw.set_fence_name(fence, name)
+    }
+
+    unsafe fn set_framebuffer_name(&self, framebuffer: &mut B::Framebuffer, name: &str) {
+        self.raw.set_framebuffer_name(framebuffer, name)
+    }
+
+    unsafe fn set_render_pass_name(&self, render_pass: &mut B::RenderPass, name: &str) {
+        self.raw.set_render_pass_name(render_pass, name)
+    }
+
+    unsafe fn set_descriptor_set_name(&self, descriptor_set: &mut DescriptorSet<B>, name: &str) {
+        self.raw
+            .set_descriptor_set_name(&mut descriptor_set.raw, name)
+    }
+
+    unsafe fn set_descriptor_set_layout_name(
+        &self,
+        descriptor_set_layout: &mut DescriptorSetLayout<B>,
+        name: &str,
+    ) {
+        self.raw
+            .set_descriptor_set_layout_name(&mut descriptor_set_layout.raw, name)
+    }
+
+    unsafe fn set_pipeline_layout_name(&self, pipeline_layout: &mut PipelineLayout<B>, name: &str) {
+        self.raw
+            .set_pipeline_layout_name(&mut pipeline_layout.raw, name)
+    }
+
+    unsafe fn set_graphics_pipeline_name(&self, pipeline: &mut GraphicsPipeline<B>, name: &str) {
+        self.raw.set_graphics_pipeline_name(&mut pipeline.raw, name)
+    }
+
+    unsafe fn set_compute_pipeline_name(&self, pipeline: &mut ComputePipeline<B>, name: &str) {
+        self.raw.set_compute_pipeline_name(&mut pipeline.raw, name)
+    }
+
+    unsafe fn set_display_power_state(
+        &self,
+        _display: &display::Display<Validation<B>>,
+        _power_state: &display::control::PowerState,
+    ) -> Result<(), display::control::DisplayControlError> {
+        Err(display::control::DisplayControlError::UnsupportedFeature)
+    }
+
+    unsafe fn register_device_event(
+        &self,
+        device_event: &display::control::DeviceEvent,
+        fence: &mut B::Fence,
+    ) -> Result<(), display::control::DisplayControlError> {
+        self.raw.register_device_event(device_event, fence)
+    }
+
+    unsafe fn register_display_event(
+        &self,
+        _display: &display::Display<Validation<B>>,
+        _display_event: &display::control::DisplayEvent,
+        _fence: &mut B::Fence,
+    ) -> Result<(), display::control::DisplayControlError> {
+        Err(display::control::DisplayControlError::UnsupportedFeature)
+    }
+
+    unsafe fn create_allocate_external_buffer(
+        &self,
+        external_memory_type_flags: external_memory::ExternalBufferMemoryType,
+        usage: buffer::Usage,
+        sparse: SparseFlags,
+        type_mask: u32,
+        size: u64,
+    ) -> Result<(Buffer<B>, B::Memory), external_memory::ExternalResourceError> {
+        self.raw
+            .create_allocate_external_buffer(
+                external_memory_type_flags,
+                usage,
+                sparse,
+                type_mask,
+                size,
+            )
+            .map(|(buffer, memory)| (Buffer::new(buffer), memory))
+    }
+
+    unsafe fn import_external_buffer(
+        &self,
+        external_memory: external_memory::ExternalBufferMemory,
+        usage: buffer::Usage,
+        sparse: SparseFlags,
+        type_mask: u32,
+        size: u64,
+    ) -> Result<(Buffer<B>, B::Memory), external_memory::ExternalResourceError> {
+        self.raw
+            .import_external_buffer(external_memory, usage, sparse, type_mask, size)
+            .map(|(buffer, memory)| (Buffer::new(buffer), memory))
+    }
+
+    unsafe fn create_allocate_external_image(
+        &self,
+        external_memory_type: external_memory::ExternalImageMemoryType,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: SparseFlags,
+        view_caps: image::ViewCapabilities,
+        type_mask: u32,
+    ) -> Result<(Image<B>, B::Memory), external_memory::ExternalResourceError> {
+        self.raw
+            .create_allocate_external_image(
+                external_memory_type,
+                kind,
+                mip_levels,
+                format,
+                tiling,
+                usage,
+                sparse,
+                view_caps,
+                type_mask,
+            )
+            .map(|(image, memory)| (Image::new(image), memory))
+    }
+
+    unsafe fn import_external_image(
+        &self,
+        external_memory: external_memory::ExternalImageMemory,
+        kind: image::Kind,
+        mip_levels: image::Level,
+        format: format::Format,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        sparse: SparseFlags,
+        view_caps: image::ViewCapabilities,
+        type_mask: u32,
+        size: u64,
+    ) -> Result<(Image<B>, B::Memory), external_memory::ExternalResourceError> {
+        self.raw
+            .import_external_image(
+                external_memory,
+                kind,
+                mip_levels,
+                format,
+                tiling,
+                usage,
+                sparse,
+                view_caps,
+                type_mask,
+                size,
+            )
+            .map(|(image, memory)| (Image::new(image), memory))
+    }
+
+    unsafe fn export_memory(
+        &self,
+        external_memory_type: external_memory::ExternalMemoryType,
+        memory: &B::Memory,
+    ) -> Result<external_memory::PlatformMemory, external_memory::ExternalMemoryExportError> {
+        self.raw.export_memory(external_memory_type, memory)
+    }
+
+    unsafe fn drm_format_modifier(&self, image: &Image<B>) -> Option<format::DrmModifier> {
+        self.raw.drm_format_modifier(image.raw())
+    }
+
+    fn start_capture(&self) {
+        self.raw.start_capture()
+    }
+
+    fn stop_capture(&self) {
+        self.raw.stop_capture()
+    }
+}
+
+fn check_image_usage(usage: image::Usage) {
+    let attachments = image::Usage::COLOR_ATTACHMENT
+        | image::Usage::DEPTH_STENCIL_ATTACHMENT
+        | image::Usage::INPUT_ATTACHMENT
+        | image::Usage::TRANSIENT_ATTACHMENT;
+    if usage.contains(image::Usage::TRANSIENT_ATTACHMENT) && !attachments.contains(usage) {
+        report!(
+            "Transient attachment usage combined with {:?}",
+            usage - attachments
+        );
+    }
+}
+
+fn check_buffer_view(limits: &Limits, format: Option<format::Format>, range: &buffer::SubRange) {
+    let format = match format {
+        Some(format) => format,
+        None => {
+            report!("Buffer view without a format, which can't be bound as a texel buffer");
+            return;
+        }
+    };
+    // Backends without texel buffers may leave the alignment at zero.
+    let alignment = limits.min_texel_buffer_offset_alignment;
+    if alignment != 0 && range.offset % alignment != 0 {
+        report!(
+            "Buffer view offset {} is not aligned to `min_texel_buffer_offset_alignment` ({})",
+            range.offset,
+            alignment
+        );
+    }
+    let texel_size = format.surface_desc().bits as u64 / 8;
+    if let Some(size) = range.size {
+        if size / texel_size > limits.max_texel_elements as u64 {
+            report!(
+                "Buffer view of {} texels of {:?} exceeds `max_texel_elements` ({})",
+                size / texel_size,
+                format,
+                limits.max_texel_elements
+            );
+        }
+    }
+}
+
+fn descriptor_set_write<'a, B: Backend, I>(
+    op: pso::DescriptorSetWrite<'a, Validation<B>, I>,
+) -> pso::DescriptorSetWrite<'a, B, iter::Map<I, DescriptorMap<'a, B>>>
+where
+    I: Iterator<Item = pso::Descriptor<'a, Validation<B>>>,
+{
+    let pso::DescriptorSetWrite {
+        set,
+        binding,
+        array_offset,
+        descriptors,
+    } = op;
+    match set.layout.bindings.iter().find(|b| b.binding == binding) {
+        Some(b) if array_offset >= b.count => report!(
+            "`write_descriptor_set` starts at element {} of binding {}, which only has {}",
+            array_offset,
+            binding,
+            b.count
+        ),
+        Some(_) => {}
+        None => report!(
+            "`write_descriptor_set` writes to binding {}, which is not in the set layout",
+            binding
+        ),
+    }
+    let map: DescriptorMap<'a, B> = descriptor;
+    pso::DescriptorSetWrite {
+        set: &mut set.raw,
+        binding,
+        array_offset,
+        descriptors: descriptors.map(map),
+    }
+}
+
+type DescriptorMap<'a, B> = fn(pso::Descriptor<'a, Validation<B>>) -> pso::Descriptor<'a, B>;
+
+fn descriptor_set_copy<B: Backend>(
+    op: pso::DescriptorSetCopy<Validation<B>>,
+) -> pso::DescriptorSetCopy<B> {
+    pso::DescriptorSetCopy {
+        src_set: &op.src_set.raw,
+        src_binding: op.src_binding,
+        src_array_offset: op.src_array_offset,
+        dst_set: &mut op.dst_set.raw,
+        dst_binding: op.dst_binding,
+        dst_array_offset: op.dst_array_offset,
+        count: op.count,
+    }
+}
@@ -0,0 +1,972 @@
+//! Validation of the API usage on the host.
+//!
+//! Most backends don't check how the API is used, and an invalid usage that works
+//! on one of them, such as GL, may crash or corrupt the rendering on others, such
+//! as Vulkan and DX12. [`Validation`] is a backend adaptor wrapping any backend: it
+//! checks the recorded commands against the rules of the API before forwarding
+//! them to the wrapped backend. The following is checked:
+//! - the state of the command buffers: commands are only recorded between
+//!   [`begin`][crate::command::CommandBuffer::begin] and
+//!   [`finish`][crate::command::CommandBuffer::finish], and only finished
+//!   command buffers are submitted or executed.
+//! - the render pass scope of the commands: draws are only recorded inside of
+//!   a render pass, and transfers and dispatches only outside of one.
+//! - the compatibility of the bound descriptor sets with the layout of the
+//!   bound pipeline, and the number of the dynamic offsets.
+//! - the viewports and scissors of the pipelines without baked ones.
+//! - the descriptor indexing flags of the set layout bindings, and the pools and
+//!   variable counts their sets are allocated with.
+//! - the usage of the transient attachments, which can't be combined with
+//!   non-attachment usages.
+//! - the format, offset alignment and number of texels of the buffer views.
+//! - the offset alignment of the acceleration structures in their buffers.
+//! - the first task of the mesh task draws, on devices only starting from the first one.
+//! - the nodes of the device groups, and the nodes of their memory and command pools.
+//! - the number of color attachments of the subpasses, the framebuffers and the
+//!   blend targets of the pipelines, against
+//!   [`max_color_attachments`][crate::Limits::max_color_attachments].
+//!
+//! When debugging barrier or lifetime issues, the uses of the buffers and images
+//! by the submitted commands can be recorded over the last frames with
+//! [`Device::enable_usage_timeline`], and retrieved per resource.
+//!
+//! The violations are reported as errors through the `log` crate, together with
+//! a backtrace of the offending call. The calls are still forwarded afterwards.
+//!
+//! Wrapping a backend is only a matter of using `Validation<B>` in place of `B`:
+//!
+//! ```ignore
+//! let instance = <Validation<back::Backend> as Backend>::Instance::create("app", 1)?;
+//! ```
+//!
+//! Displays are not exposed through the adaptor.
+//!
+//! This module is only available with the `validation` feature.
+
+macro_rules! report {
+    ($($arg:tt)*) => {
+        $crate::validation::report(format_args!($($arg)*))
+    };
+}
+
+mod command;
+mod device;
+mod timeline;
+
+pub use self::{
+    command::CommandBuffer,
+    device::{CommandPool, DescriptorPool, Device},
+    timeline::Touch,
+};
+
+use crate::{
+    acceleration_structure as accel,
+    adapter::{self, MemoryProperties},
+    device as d, display, external_memory, format, image,
+    memory::{Aliased, Barrier, SparseBind, SparseImageBind},
+    pass::{Subpass, SubpassId},
+    pso::{self, Descriptor, DescriptorSetLayoutBinding, EntryPoint},
+    query,
+    queue::{self, QueueGroup, QueuePriority},
+    window::{self, PresentError, Suboptimal, SurfaceCapabilities, SurfaceFormat, SwapchainConfig},
+    Backend, Features, PhysicalDeviceProperties,
+};
+
+use std::{borrow::Borrow, fmt, marker::PhantomData, ops::Range, sync::Arc};
+
+fn report(message: fmt::Arguments) {
+    log::error!("{}\n{:?}", message, backtrace::Backtrace::new());
+}
+
+/// Whether the `node` is one of the `nodes`.
+fn has_node(nodes: d::NodeMask, node: u32) -> bool {
+    1u32.checked_shl(node).map_or(false, |bit| nodes & bit != 0)
+}
+
+/// Backend adaptor validating the usage of the wrapped backend `B`.
+///
+/// See the [module documentation][self] for the checks performed.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Validation<B: Backend>(PhantomData<B>);
+
+impl<B: Backend> Backend for Validation<B> {
+    type Instance = Instance<B>;
+    type PhysicalDevice = PhysicalDevice<B>;
+    type Device = Device<B>;
+    type Surface = Surface<B>;
+
+    type QueueFamily = B::QueueFamily;
+    type Queue = Queue<B>;
+    type CommandBuffer = CommandBuffer<B>;
+
+    type ShaderModule = B::ShaderModule;
+    type RenderPass = B::RenderPass;
+    type Framebuffer = B::Framebuffer;
+
+    type Memory = B::Memory;
+    type CommandPool = CommandPool<B>;
+
+    type Buffer = Buffer<B>;
+    type BufferView = B::BufferView;
+    type Image = Image<B>;
+    type ImageView = ImageView<B>;
+    type Sampler = B::Sampler;
+
+    type ComputePipeline = ComputePipeline<B>;
+    type GraphicsPipeline = GraphicsPipeline<B>;
+    type RayTracingPipeline = RayTracingPipeline<B>;
+    type PipelineCache = B::PipelineCache;
+    type PipelineLayout = PipelineLayout<B>;
+    type DescriptorPool = DescriptorPool<B>;
+    type DescriptorSet = DescriptorSet<B>;
+    type DescriptorSetLayout = DescriptorSetLayout<B>;
+
+    type Fence = B::Fence;
+    type Semaphore = B::Semaphore;
+    type Event = B::Event;
+    type QueryPool = B::QueryPool;
+    type AccelerationStructure = B::AccelerationStructure;
+    type Display = B::Display;
+    type DisplayMode = B::DisplayMode;
+}
+
+/// Layout of a descriptor set, as seen by the validation.
+#[derive(Debug, PartialEq)]
+struct SetLayoutInfo {
+    /// Bindings of the layout, sorted by binding number.
+    bindings: Vec<DescriptorSetLayoutBinding>,
+}
+
+impl SetLayoutInfo {
+    fn new(mut bindings: Vec<DescriptorSetLayoutBinding>) -> Self {
+        bindings.sort_by_key(|binding| binding.binding);
+        SetLayoutInfo { bindings }
+    }
+
+    fn dynamic_offsets(&self) -> usize {
+        self.bindings
+            .iter()
+            .filter(|binding| match binding.ty {
+                pso::DescriptorType::Buffer {
+                    format: pso::BufferDescriptorFormat::Structured { dynamic_offset },
+                    ..
+                } => dynamic_offset,
+                _ => false,
+            })
+            .map(|binding| binding.count)
+            .sum()
+    }
+
+    fn variable_count(&self) -> Option<&DescriptorSetLayoutBinding> {
+        self.bindings.last().filter(|binding| {
+            binding
+                .flags
+                .contains(pso::DescriptorBindingFlags::VARIABLE_COUNT)
+        })
+    }
+
+    fn update_after_bind(&self) -> bool {
+        self.bindings.iter().any(|binding| {
+            binding
+                .flags
+                .contains(pso::DescriptorBindingFlags::UPDATE_AFTER_BIND)
+        })
+    }
+}
+
+/// Layout of a pipeline, as seen by the validation.
+#[derive(Debug)]
+struct PipelineLayoutInfo {
+    sets: Vec<Arc<SetLayoutInfo>>,
+    push_constants: Vec<(pso::ShaderStageFlags, Range<u32>)>,
+}
+
+impl PipelineLayoutInfo {
+    /// Check if the descriptor set bound at `index` with this layout can be used
+    /// by a pipeline created with the `other` layout.
+    fn is_compatible_for_set(&self, other: &Self, index: usize) -> bool {
+        index < self.sets.len()
+            && index < other.sets.len()
+            && self.push_constants == other.push_constants
+            && self.sets[..=index] == other.sets[..=index]
+    }
+}
+
+/// Instance of the validation adaptor.
+#[derive(Debug)]
+pub struct Instance<B: Backend> {
+    raw: B::Instance,
+}
+
+impl<B: Backend> crate::Instance<Validation<B>> for Instance<B> {
+    fn create(name: &str, version: u32) -> Result<Self, crate::UnsupportedBackend> {
+        B::Instance::create(name, version).map(|raw| Instance { raw })
+    }
+
+    fn enumerate_adapters(&self) -> Vec<adapter::Adapter<Validation<B>>> {
+        self.raw
+            .enumerate_adapters()
+            .into_iter()
+            .map(|adapter| adapter::Adapter {
+                info: adapter.info,
+                physical_device: PhysicalDevice {
+                    raw: adapter.physical_device,
+                },
+                queue_families: adapter.queue_families,
+            })
+            .collect()
+    }
+
+    unsafe fn create_surface(
+        &self,
+        raw_window_handle: &impl raw_window_handle::HasRawWindowHandle,
+    ) -> Result<Surface<B>, window::InitError> {
+        self.raw
+            .create_surface(raw_window_handle)
+            .map(|raw| Surface { raw })
+    }
+
+    unsafe fn destroy_surface(&self, surface: Surface<B>) {
+        self.raw.destroy_surface(surface.raw);
+    }
+
+    unsafe fn create_display_plane_surface<'a>(
+        &self,
+        _display_plane: &display::DisplayPlane<'a, Validation<B>>,
+        _plane_stack_index: u32,
+        _transformation: display::SurfaceTransform,
+        _alpha: display::DisplayPlaneAlpha,
+        _image_extent: window::Extent2D,
+    ) -> Result<Surface<B>, display::DisplayPlaneSurfaceError> {
+        Err(display::DisplayPlaneSurfaceError::UnsupportedFeature)
+    }
+}
+
+/// Physical device of the validation adaptor.
+#[derive(Debug)]
+pub struct PhysicalDevice<B: Backend> {
+    raw: B::PhysicalDevice,
+}
+
+impl<B: Backend> PhysicalDevice<B> {
+    fn wrap_gpu(&self, gpu: adapter::Gpu<B>, nodes: d::NodeMask) -> adapter::Gpu<Validation<B>> {
+        let properties = adapter::PhysicalDevice::properties(&self.raw);
+        let device = Device::new(
+            gpu.device,
+            properties.limits,
+            properties.mesh_shader.first_task,
+            nodes,
+        );
+        let queue_groups = gpu
+            .queue_groups
+            .into_iter()
+            .map(|group| QueueGroup {
+                family: group.family,
+                node: group.node,
+                queues: group
+                    .queues
+                    .into_iter()
+                    .map(|raw| Queue {
+                        raw,
+                        timeline: Arc::clone(&device.timeline),
+                    })
+                    .collect(),
+            })
+            .collect();
+        adapter::Gpu {
+            device,
+            queue_groups,
+        }
+    }
+}
+
+impl<B: Backend> adapter::PhysicalDevice<Validation<B>> for PhysicalDevice<B> {
+    unsafe fn open(
+        &self,
+        families: &[(&B::QueueFamily, &[QueuePriority])],
+        requested_features: Features,
+    ) -> Result<adapter::Gpu<Validation<B>>, d::CreationError> {
+        let gpu = self.raw.open(families, requested_features)?;
+        Ok(self.wrap_gpu(gpu, 1))
+    }
+
+    fn node_count(&self) -> u32 {
+        self.raw.node_count()
+    }
+
+    unsafe fn open_group(
+        &self,
+        families: &[(&B::QueueFamily, &[QueuePriority])],
+        requested_features: Features,
+        nodes: d::NodeMask,
+    ) -> Result<adapter::Gpu<Validation<B>>, d::CreationError> {
+        let node_count = self.raw.node_count();
+        if nodes == 0 {
+            report!("No node is requested");
+        } else if nodes.checked_shr(node_count).unwrap_or(0) != 0 {
+            report!(
+                "Requested nodes {:#b} beyond the {} nodes of the device",
+                nodes,
+                node_count
+            );
+        }
+        let gpu = self.raw.open_group(families, requested_features, nodes)?;
+        Ok(self.wrap_gpu(gpu, nodes))
+    }
+
+    fn format_properties(&self, format: Option<format::Format>) -> format::Properties {
+        self.raw.format_properties(format)
+    }
+
+    fn image_format_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+    ) -> Option<image::FormatProperties> {
+        self.raw
+            .image_format_properties(format, dimensions, tiling, usage, view_caps)
+    }
+
+    fn memory_properties(&self) -> MemoryProperties {
+        self.raw.memory_properties()
+    }
+
+    fn external_buffer_properties(
+        &self,
+        usage: crate::buffer::Usage,
+        sparse: crate::memory::SparseFlags,
+        memory_type: external_memory::ExternalMemoryType,
+    ) -> external_memory::ExternalMemoryProperties {
+        self.raw
+            .external_buffer_properties(usage, sparse, memory_type)
+    }
+
+    fn external_image_properties(
+        &self,
+        format: format::Format,
+        dimensions: u8,
+        tiling: image::Tiling,
+        usage: image::Usage,
+        view_caps: image::ViewCapabilities,
+        external_memory_type: external_memory::ExternalMemoryType,
+    ) -> Result<
+        external_memory::ExternalMemoryProperties,
+        external_memory::ExternalImagePropertiesError,
+    > {
+        self.raw.external_image_properties(
+            format,
+            dimensions,
+            tiling,
+            usage,
+            view_caps,
+            external_memory_type,
+        )
+    }
+
+    fn features(&self) -> Features {
+        self.raw.features()
+    }
+
+    fn properties(&self) -> PhysicalDeviceProperties {
+        self.raw.properties()
+    }
+
+    fn is_valid_cache(&self, cache: &[u8]) -> bool {
+        self.raw.is_valid_cache(cache)
+    }
+
+    unsafe fn enumerate_displays(&self) -> Vec<display::Display<Validation<B>>> {
+        Vec::new()
+    }
+
+    unsafe fn enumerate_compatible_planes(
+        &self,
+        _display: &display::Display<Validation<B>>,
+    ) -> Vec<display::Plane> {
+        Vec::new()
+    }
+
+    unsafe fn create_display_mode(
+        &self,
+        _display: &display::Display<Validation<B>>,
+        _resolution: (u32, u32),
+        _refresh_rate: u32,
+    ) -> Result<display::DisplayMode<Validation<B>>, display::DisplayModeError> {
+        Err(display::DisplayModeError::UnsupportedDisplayMode)
+    }
+
+    unsafe fn create_display_plane<'a>(
+        &self,
+        _display: &'a display::DisplayMode<Validation<B>>,
+        _plane: &'a display::Plane,
+    ) -> Result<display::DisplayPlane<'a, Validation<B>>, d::OutOfMemory> {
+        Err(d::OutOfMemory::Host)
+    }
+}
+
+/// Surface of the validation adaptor.
+#[derive(Debug)]
+pub struct Surface<B: Backend> {
+    raw: B::Surface,
+}
+
+impl<B: Backend> window::Surface<Validation<B>> for Surface<B> {
+    fn supports_queue_family(&self, family: &B::QueueFamily) -> bool {
+        self.raw.supports_queue_family(family)
+    }
+
+    fn capabilities(&self, physical_device: &PhysicalDevice<B>) -> SurfaceCapabilities {
+        self.raw.capabilities(&physical_device.raw)
+    }
+
+    fn supported_formats(
+        &self,
+        physical_device: &PhysicalDevice<B>,
+    ) -> Option<Vec<format::Format>> {
+        self.raw.supported_formats(&physical_device.raw)
+    }
+
+    fn ranked_formats(&self, physical_device: &PhysicalDevice<B>) -> Vec<SurfaceFormat> {
+        self.raw.ranked_formats(&physical_device.raw)
+    }
+}
+
+impl<B: Backend> window::PresentationSurface<Validation<B>> for Surface<B> {
+    type SwapchainImage = SwapchainImage<B>;
+
+    unsafe fn configure_swapchain(
+        &mut self,
+        device: &Device<B>,
+        config: SwapchainConfig,
+    ) -> Result<(), window::SwapchainError> {
+        self.raw.configure_swapchain(&device.raw, config)
+    }
+
+    unsafe fn unconfigure_swapchain(&mut self, device: &Device<B>) {
+        self.raw.unconfigure_swapchain(&device.raw)
+    }
+
+    unsafe fn acquire_image(
+        &mut self,
+        timeout_ns: u64,
+    ) -> Result<(Self::SwapchainImage, Option<Suboptimal>), window::AcquireError> {
+        let (image, suboptimal) = self.raw.acquire_image(timeout_ns)?;
+        let image = Image {
+            raw: RawImage::Swapchain(image),
+            id: timeline::register(),
+        };
+        Ok((SwapchainImage(image), suboptimal))
+    }
+
+    unsafe fn wait_for_available_frame(
+        &mut self,
+        timeout_ns: u64,
+    ) -> Result<(), window::AcquireError> {
+        self.raw.wait_for_available_frame(timeout_ns)
+    }
+}
+
+/// Command queue of the validation adaptor.
+///
+/// Checks that the submitted command buffers are primary ones and have finished recording.
+#[derive(Debug)]
+pub struct Queue<B: Backend> {
+    raw: B::Queue,
+    timeline: Arc<timeline::Timeline>,
+}
+
+impl<B: Backend> queue::Queue<Validation<B>> for Queue<B> {
+    unsafe fn bind_sparse<'a, Iw, Is, Ibi, Ib, Iii, Io, Ii>(
+        &mut self,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        buffer_memory_binds: Ib,
+        image_opaque_memory_binds: Io,
+        image_memory_binds: Ii,
+        device: &Device<B>,
+        fence: Option<&B::Fence>,
+    ) where
+        Ibi: Iterator<Item = &'a SparseBind<&'a B::Memory>>,
+        Ib: Iterator<Item = (&'a mut Buffer<B>, Ibi)>,
+        Iii: Iterator<Item = &'a SparseImageBind<&'a B::Memory>>,
+        Io: Iterator<Item = (&'a mut Image<B>, Ibi)>,
+        Ii: Iterator<Item = (&'a mut Image<B>, Iii)>,
+        Iw: Iterator<Item = &'a B::Semaphore>,
+        Is: Iterator<Item = &'a B::Semaphore>,
+    {
+        self.raw.bind_sparse(
+            wait_semaphores,
+            signal_semaphores,
+            buffer_memory_binds.map(|(buffer, binds)| (&mut buffer.raw, binds)),
+            image_opaque_memory_binds.map(|(image, binds)| (image.raw_mut(), binds)),
+            image_memory_binds.map(|(image, binds)| (image.raw_mut(), binds)),
+            &device.raw,
+            fence,
+        )
+    }
+
+    unsafe fn submit<'a, Ic, Iw, Is>(
+        &mut self,
+        command_buffers: Ic,
+        wait_semaphores: Iw,
+        signal_semaphores: Is,
+        fence: Option<&mut B::Fence>,
+    ) -> Result<(), d::OomOrDeviceLost>
+    where
+        Ic: Iterator<Item = &'a CommandBuffer<B>>,
+        Iw: Iterator<Item = (&'a B::Semaphore, pso::PipelineStage)>,
+        Is: Iterator<Item = &'a B::Semaphore>,
+    {
+        let command_buffers = command_buffers.collect::<Vec<_>>();
+        for cmd_buffer in &command_buffers {
+            cmd_buffer.check_submit();
+            cmd_buffer.commit_touches(&self.timeline);
+        }
+        self.raw.submit(
+            command_buffers
+                .into_iter()
+                .map(|cmd_buffer| &cmd_buffer.raw),
+            wait_semaphores,
+            signal_semaphores,
+            fence,
+        )
+    }
+
+    unsafe fn present(
+        &mut self,
+        surface: &mut Surface<B>,
+        image: <Surface<B> as window::PresentationSurface<Validation<B>>>::SwapchainImage,
+        wait_semaphore: Option<&mut B::Semaphore>,
+    ) -> Result<Option<Suboptimal>, PresentError> {
+        self.timeline.end_frame();
+        self.raw
+            .present(&mut surface.raw, image.into_raw(), wait_semaphore)
+    }
+
+    fn wait_idle(&mut self) -> Result<(), d::OomOrDeviceLost> {
+        self.raw.wait_idle()
+    }
+
+    fn timestamp_period(&self) -> f32 {
+        self.raw.timestamp_period()
+    }
+
+    fn timestamp_calibration(&self) -> Option<queue::TimestampCalibration> {
+        self.raw.timestamp_calibration()
+    }
+
+    fn capture_next_frames(&mut self, frames: u32) {
+        self.raw.capture_next_frames(frames)
+    }
+}
+
+/// Descriptor set layout of the validation adaptor.
+#[derive(Debug)]
+pub struct DescriptorSetLayout<B: Backend> {
+    raw: B::DescriptorSetLayout,
+    info: Arc<SetLayoutInfo>,
+}
+
+/// Descriptor set of the validation adaptor.
+#[derive(Debug)]
+pub struct DescriptorSet<B: Backend> {
+    raw: B::DescriptorSet,
+    layout: Arc<SetLayoutInfo>,
+}
+
+/// Pipeline layout of the validation adaptor.
+#[derive(Debug)]
+pub struct PipelineLayout<B: Backend> {
+    raw: B::PipelineLayout,
+    info: Arc<PipelineLayoutInfo>,
+}
+
+/// Graphics pipeline of the validation adaptor.
+#[derive(Debug)]
+pub struct GraphicsPipeline<B: Backend> {
+    raw: B::GraphicsPipeline,
+    layout: Arc<PipelineLayoutInfo>,
+    subpass: SubpassId,
+    dynamic_viewport: bool,
+    dynamic_scissor: bool,
+}
+
+/// Compute pipeline of the validation adaptor.
+#[derive(Debug)]
+pub struct ComputePipeline<B: Backend> {
+    raw: B::ComputePipeline,
+    layout: Arc<PipelineLayoutInfo>,
+}
+
+/// Ray tracing pipeline of the validation adaptor.
+#[derive(Debug)]
+pub struct RayTracingPipeline<B: Backend> {
+    raw: B::RayTracingPipeline,
+    layout: Arc<PipelineLayoutInfo>,
+}
+
+/// Buffer of the validation adaptor.
+#[derive(Debug)]
+pub struct Buffer<B: Backend> {
+    raw: B::Buffer,
+    /// Identifier of the buffer in the usage timeline.
+    id: timeline::Id,
+}
+
+impl<B: Backend> Buffer<B> {
+    fn new(raw: B::Buffer) -> Self {
+        Buffer {
+            raw,
+            id: timeline::register(),
+        }
+    }
+}
+
+/// Image of the validation adaptor.
+#[derive(Debug)]
+pub struct Image<B: Backend> {
+    raw: RawImage<B>,
+    /// Identifier of the image in the usage timeline.
+    id: timeline::Id,
+}
+
+#[derive(Debug)]
+enum RawImage<B: Backend> {
+    Owned(B::Image),
+    /// Image borrowed from an acquired swapchain image.
+    Swapchain(<B::Surface as window::PresentationSurface<B>>::SwapchainImage),
+}
+
+impl<B: Backend> Image<B> {
+    fn new(raw: B::Image) -> Self {
+        Image {
+            raw: RawImage::Owned(raw),
+            id: timeline::register(),
+        }
+    }
+
+    fn raw(&self) -> &B::Image {
+        match self.raw {
+            RawImage::Owned(ref raw) => raw,
+            RawImage::Swapchain(ref image) => image.borrow(),
+        }
+    }
+
+    fn raw_mut(&mut self) -> &mut B::Image {
+        match self.raw {
+            RawImage::Owned(ref mut raw) => raw,
+            // Swapchain images are never handed out mutably.
+            RawImage::Swapchain(_) => unreachable!(),
+        }
+    }
+
+    fn into_raw(self) -> B::Image {
+        match self.raw {
+            RawImage::Owned(raw) => raw,
+            RawImage::Swapchain(_) => unreachable!(),
+        }
+    }
+}
+
+/// Image view of the validation adaptor.
+///
+/// It's transparent, for the views of the swapchain images to be borrowed from them.
+#[derive(Debug)]
+#[repr(transparent)]
+pub struct ImageView<B: Backend> {
+    raw: B::ImageView,
+}
+
+impl<B: Backend> ImageView<B> {
+    fn from_raw(raw: &B::ImageView) -> &Self {
+        let raw: *const B::ImageView = raw;
+        // Safe, as `ImageView` is a transparent wrapper of `B::ImageView`.
+        unsafe { &*(raw as *const Self) }
+    }
+}
+
+/// Swapchain image of the validation adaptor.
+///
+/// Each acquired image is registered anew in the usage timeline.
+#[derive(Debug)]
+pub struct SwapchainImage<B: Backend>(Image<B>);
+
+impl<B: Backend> SwapchainImage<B> {
+    fn into_raw(self) -> <B::Surface as window::PresentationSurface<B>>::SwapchainImage {
+        match self.0.raw {
+            RawImage::Swapchain(image) => image,
+            RawImage::Owned(_) => unreachable!(),
+        }
+    }
+}
+
+impl<B: Backend> Borrow<Image<B>> for SwapchainImage<B> {
+    fn borrow(&self) -> &Image<B> {
+        &self.0
+    }
+}
+
+impl<B: Backend> Borrow<ImageView<B>> for SwapchainImage<B> {
+    fn borrow(&self) -> &ImageView<B> {
+        match self.0.raw {
+            RawImage::Swapchain(ref image) => ImageView::from_raw(image.borrow()),
+            RawImage::Owned(_) => unreachable!(),
+        }
+    }
+}
+
+fn entry_point<'a, B: Backend>(entry: &EntryPoint<'a, Validation<B>>) -> EntryPoint<'a, B> {
+    EntryPoint {
+        entry: entry.entry,
+        module: entry.module,
+        specialization: entry.specialization.clone(),
+    }
+}
+
+fn shader_group<'a, B: Backend>(
+    group: &pso::ShaderGroup<'a, Validation<B>>,
+) -> pso::ShaderGroup<'a, B> {
+    match *group {
+        pso::ShaderGroup::RayGeneration(ref entry) => {
+            pso::ShaderGroup::RayGeneration(entry_point(entry))
+        }
+        pso::ShaderGroup::Miss(ref entry) => pso::ShaderGroup::Miss(entry_point(entry)),
+        pso::ShaderGroup::Callable(ref entry) => pso::ShaderGroup::Callable(entry_point(entry)),
+        pso::ShaderGroup::TrianglesHitGroup {
+            ref closest_hit,
+            ref any_hit,
+        } => pso::ShaderGroup::TrianglesHitGroup {
+            closest_hit: closest_hit.as_ref().map(entry_point),
+            any_hit: any_hit.as_ref().map(entry_point),
+        },
+        pso::ShaderGroup::ProceduralHitGroup {
+            ref intersection,
+            ref closest_hit,
+            ref any_hit,
+        } => pso::ShaderGroup::ProceduralHitGroup {
+            intersection: entry_point(intersection),
+            closest_hit: closest_hit.as_ref().map(entry_point),
+            any_hit: any_hit.as_ref().map(entry_point),
+        },
+    }
+}
+
+fn shader_binding_table<'a, B: Backend>(
+    table: &pso::ShaderBindingTable<'a, Validation<B>>,
+) -> pso::ShaderBindingTable<'a, B> {
+    pso::ShaderBindingTable {
+        buffer: &table.buffer.raw,
+        offset: table.offset,
+        stride: table.stride,
+        size: table.size,
+    }
+}
+
+fn geometry_descs<'a, B: Backend>(
+    geometries: &[accel::GeometryDesc<'a, Validation<B>>],
+) -> Vec<accel::GeometryDesc<'a, B>> {
+    geometries
+        .iter()
+        .map(|desc| accel::GeometryDesc {
+            geometry: match desc.geometry {
+                accel::Geometry::Triangles(ref triangles) => {
+                    accel::Geometry::Triangles(accel::Triangles {
+                        vertex_format: triangles.vertex_format,
+                        vertex_buffer: (&triangles.vertex_buffer.0.raw, triangles.vertex_buffer.1),
+                        vertex_stride: triangles.vertex_stride,
+                        vertex_count: triangles.vertex_count,
+                        index_buffer: triangles
+                            .index_buffer
+                            .map(|(buffer, offset, ty)| (&buffer.raw, offset, ty)),
+                        transform: triangles
+                            .transform
+                            .map(|(buffer, offset)| (&buffer.raw, offset)),
+                    })
+                }
+                accel::Geometry::Aabbs { buffer, stride } => accel::Geometry::Aabbs {
+                    buffer: (&buffer.0.raw, buffer.1),
+                    stride,
+                },
+                accel::Geometry::Instances { buffer } => accel::Geometry::Instances {
+                    buffer: (&buffer.0.raw, buffer.1),
+                },
+            },
+            primitive_count: desc.primitive_count,
+            flags: desc.flags,
+        })
+        .collect()
+}
+
+fn subpass<'a, B: Backend>(subpass: &Subpass<'a, Validation<B>>) -> Subpass<'a, B> {
+    Subpass {
+        index: subpass.index,
+        main_pass: subpass.main_pass,
+    }
+}
+
+fn base_pipeline<'a, P, R>(
+    base: &pso::BasePipeline<'a, P>,
+    raw: impl Fn(&'a P) -> &'a R,
+) -> pso::BasePipeline<'a, R> {
+    match *base {
+        pso::BasePipeline::Pipeline(pipeline) => pso::BasePipeline::Pipeline(raw(pipeline)),
+        pso::BasePipeline::Index(index) => pso::BasePipeline::Index(index),
+        pso::BasePipeline::None => pso::BasePipeline::None,
+    }
+}
+
+fn descriptor<B: Backend>(descriptor: Descriptor<Validation<B>>) -> Descriptor<B> {
+    match descriptor {
+        Descriptor::Sampler(sampler) => Descriptor::Sampler(sampler),
+        Descriptor::Image(view, layout) => Descriptor::Image(&view.raw, layout),
+        Descriptor::CombinedImageSampler(view, layout, sampler) => {
+            Descriptor::CombinedImageSampler(&view.raw, layout, sampler)
+        }
+        Descriptor::Buffer(buffer, range) => Descriptor::Buffer(&buffer.raw, range),
+        Descriptor::TexelBuffer(view) => Descriptor::TexelBuffer(view),
+        Descriptor::AccelerationStructure(accel) => Descriptor::AccelerationStructure(accel),
+    }
+}
+
+fn barrier<B: Backend>(barrier: Barrier<Validation<B>>) -> Barrier<B> {
+    match barrier {
+        Barrier::AllBuffers(access) => Barrier::AllBuffers(access),
+        Barrier::AllImages(access) => Barrier::AllImages(access),
+        Barrier::Buffer {
+            states,
+            target,
+            range,
+            families,
+        } => Barrier::Buffer {
+            states,
+            target: &target.raw,
+            range,
+            families,
+        },
+        Barrier::Image {
+            states,
+            target,
+            range,
+            families,
+        } => Barrier::Image {
+            states,
+            target: target.raw(),
+            range,
+            families,
+        },
+        Barrier::Aliasing { before, after } => Barrier::Aliasing {
+            before: before.map(aliased),
+            after: after.map(aliased),
+        },
+    }
+}
+
+fn aliased<B: Backend>(resource: Aliased<Validation<B>>) -> Aliased<B> {
+    match resource {
+        Aliased::Buffer(buffer) => Aliased::Buffer(&buffer.raw),
+        Aliased::Image(image) => Aliased::Image(image.raw()),
+    }
+}
+
+fn query<B: Backend>(query: query::Query<Validation<B>>) -> query::Query<B> {
+    query::Query {
+        pool: query.pool,
+        id: query.id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(
+        binding: pso::DescriptorBinding,
+        dynamic_offset: bool,
+        count: pso::DescriptorArrayIndex,
+        flags: pso::DescriptorBindingFlags,
+    ) -> DescriptorSetLayoutBinding {
+        DescriptorSetLayoutBinding {
+            binding,
+            ty: pso::DescriptorType::Buffer {
+                ty: pso::BufferDescriptorType::Uniform,
+                format: pso::BufferDescriptorFormat::Structured { dynamic_offset },
+            },
+            count,
+            stage_flags: pso::ShaderStageFlags::VERTEX,
+            immutable_samplers: false,
+            flags,
+        }
+    }
+
+    #[test]
+    fn set_layouts_count_the_dynamic_offsets() {
+        let none = pso::DescriptorBindingFlags::empty();
+        let layout = SetLayoutInfo::new(vec![
+            binding(2, true, 3, none),
+            binding(0, false, 4, none),
+            binding(1, true, 1, none),
+        ]);
+        assert_eq!(layout.dynamic_offsets(), 4);
+        assert_eq!(
+            layout
+                .bindings
+                .iter()
+                .map(|binding| binding.binding)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn set_layouts_have_the_variable_count_binding_last() {
+        let layout = SetLayoutInfo::new(vec![
+            binding(1, false, 16, pso::DescriptorBindingFlags::VARIABLE_COUNT),
+            binding(0, false, 1, pso::DescriptorBindingFlags::UPDATE_AFTER_BIND),
+        ]);
+        assert_eq!(
+            layout.variable_count().map(|binding| binding.binding),
+            Some(1)
+        );
+        assert!(layout.update_after_bind());
+
+        let layout = SetLayoutInfo::new(vec![
+            binding(0, false, 16, pso::DescriptorBindingFlags::VARIABLE_COUNT),
+            binding(1, false, 1, pso::DescriptorBindingFlags::empty()),
+        ]);
+        assert!(layout.variable_count().is_none());
+        assert!(!layout.update_after_bind());
+    }
+
+    #[test]
+    fn pipeline_layouts_are_compatible_up_to_the_set() {
+        let none = pso::DescriptorBindingFlags::empty();
+        let first = Arc::new(SetLayoutInfo::new(vec![binding(0, false, 1, none)]));
+        let second = Arc::new(SetLayoutInfo::new(vec![binding(0, true, 1, none)]));
+        let layout = |sets: Vec<Arc<SetLayoutInfo>>, push_constants| PipelineLayoutInfo {
+            sets,
+            push_constants,
+        };
+        let constants = vec![(pso::ShaderStageFlags::VERTEX, 0..16)];
+
+        let bound = layout(vec![first.clone(), second.clone()], Vec::new());
+        let other = layout(vec![first.clone(), first.clone()], Vec::new());
+        assert!(bound.is_compatible_for_set(&other, 0));
+        assert!(!bound.is_compatible_for_set(&other, 1));
+        assert!(!bound.is_compatible_for_set(&other, 2));
+
+        // The push constant ranges have to match for all the sets.
+        let other = layout(vec![first, second], constants);
+        assert!(!bound.is_compatible_for_set(&other, 0));
+    }
+
+    #[test]
+    fn node_masks_contain_their_nodes() {
+        assert!(has_node(0b101, 0));
+        assert!(!has_node(0b101, 1));
+        assert!(has_node(0b101, 2));
+        assert!(!has_node(!0, 32));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Use of a resource by a submitted command, recorded by the usage timeline.
+///
+/// See [`Device::enable_usage_timeline`][super::Device::enable_usage_timeline].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Touch {
+    /// Frame the command was submitted in, the first frame being `0`.
+    pub frame: u64,
+    /// Name of the command buffer, as given to `set_command_buffer_name`.
+    pub tag: Option<String>,
+    /// Command using the resource, e.g. `copy_buffer (destination)`.
+    pub operation: &'static str,
+}
+
+/// Identifier of a buffer or image, never reused within the process.
+pub(super) type Id = u64;
+
+/// Register a newly created buffer or image.
+///
+/// The resources can't be identified by their address, which changes when they
+/// are moved and is reused by the later ones.
+pub(super) fn register() -> Id {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Number of frames the touches are kept for.
+    frames: u64,
+    frame: u64,
+    touches: HashMap<Id, VecDeque<Touch>>,
+}
+
+/// Touches of the resources within the last frames, shared by a device,
+/// its queues and its command buffers.
+#[derive(Debug, Default)]
+pub(super) struct Timeline {
+    enabled: AtomicBool,
+    state: Mutex<State>,
+}
+
+impl Timeline {
+    pub(super) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn enable(&self, frames: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.frames = frames.max(1) as u64;
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    /// Record the touches of a submitted command buffer.
+    pub(super) fn commit(&self, tag: Option<&str>, touches: &[(Id, &'static str)]) {
+        if touches.is_empty() || !self.is_enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let frame = state.frame;
+        for &(id, operation) in touches {
+            state.touches.entry(id).or_default().push_back(Touch {
+                frame,
+                tag: tag.map(str::to_owned),
+                operation,
+            });
+        }
+    }
+
+    /// Start a new frame, forgetting the touches that are too old.
+    pub(super) fn end_frame(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.frame += 1;
+        let oldest = state.frame.saturating_sub(state.frames - 1);
+        state.touches.retain(|_, touches| {
+            while let Some(touch) = touches.front() {
+                if touch.frame >= oldest {
+                    break;
+                }
+                touches.pop_front();
+            }
+            !touches.is_empty()
+        });
+    }
+
+    pub(super) fn history(&self, id: Id) -> Vec<Touch> {
+        let state = self.state.lock().unwrap();
+        state
+            .touches
+            .get(&id)
+            .map_or_else(Vec::new, |touches| touches.iter().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_never_reused() {
+        let first = register();
+        assert!(register() > first);
+    }
+
+    #[test]
+    fn timeline_is_only_recorded_once_enabled() {
+        let timeline = Timeline::default();
+        timeline.commit(None, &[(0, "fill_buffer")]);
+        assert!(timeline.history(0).is_empty());
+
+        timeline.enable(1);
+        timeline.commit(Some("upload"), &[(0, "fill_buffer"), (1, "copy_buffer")]);
+        assert_eq!(
+            timeline.history(0),
+            vec![Touch {
+                frame: 0,
+                tag: Some("upload".to_string()),
+                operation: "fill_buffer",
+            }]
+        );
+        assert_eq!(timeline.history(1).len(), 1);
+    }
+
+    #[test]
+    fn timeline_forgets_the_old_frames() {
+        let timeline = Timeline::default();
+        timeline.enable(2);
+        timeline.commit(None, &[(0, "fill_buffer")]);
+        timeline.end_frame();
+        timeline.commit(None, &[(0, "copy_buffer"), (1, "copy_buffer")]);
+        assert_eq!(timeline.history(0).len(), 2);
+
+        timeline.end_frame();
+        let touches = timeline.history(0);
+        assert_eq!(touches.len(), 1);
+        assert_eq!((touches[0].frame, touches[0].operation), (1, "copy_buffer"));
+
+        timeline.end_frame();
+        assert!(timeline.history(0).is_empty());
+        assert!(timeline.history(1).is_empty());
+    }
+}
@@ -62,6 +62,7 @@ use std::{
     cmp::{max, min},
     fmt,
     ops::RangeInclusive,
+    time::Instant,
 };
 
 /// Default image usage for the swapchain.
@@ -92,6 +93,9 @@ pub enum SwapchainError {
     /// Accecssing the underlying NSView from wrong thread https://github.com/gfx-rs/gfx/issues/3704
     #[error("Accecssing NSView from wrong thread")]
     WrongThread,
+    /// The color space can't be presented with the format on this surface.
+    #[error("Color space is not supported with this format")]
+    UnsupportedColorSpace,
     /// Unknown error.
     #[error("Swapchain can't be created for an unknown reason")]
     Unknown,
@@ -241,6 +245,165 @@ pub trait Surface<B: Backend>: fmt::Debug + Any + Send + Sync {
     /// If `None` is returned then the surface has no preferred format and the
     /// application may use any desired format.
     fn supported_formats(&self, physical_device: &B::PhysicalDevice) -> Option<Vec<Format>>;
+
+    /// Query surface formats for this physical device, ranked from the most
+    /// recommended to the least one, see [`rank_surface_formats`].
+    ///
+    /// The first returned format is a portable choice for the swapchain.
+    fn ranked_formats(&self, physical_device: &B::PhysicalDevice) -> Vec<SurfaceFormat> {
+        rank_surface_formats(self.supported_formats(physical_device))
+    }
+
+    /// Query the HDR capabilities of the display showing the surface.
+    ///
+    /// Returns `None` if the display isn't in an HDR mode, or if the backend
+    /// can't query it.
+    fn hdr_capabilities(&self, _physical_device: &B::PhysicalDevice) -> Option<HdrCapabilities> {
+        None
+    }
+
+    /// Query the video modes of the display showing the surface with this format,
+    /// for [exclusive fullscreen][FullscreenMode::Exclusive].
+    ///
+    /// Returns an empty list if the backend has no control over the video mode.
+    fn video_modes(&self, _physical_device: &B::PhysicalDevice, _format: Format) -> Vec<VideoMode> {
+        Vec::new()
+    }
+}
+
+/// Statistics of the frames displayed by a swapchain,
+/// see [`PresentationSurface::present_statistics`].
+///
+/// The fields a backend can't report are `None`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PresentStatistics {
+    /// Number of frames presented, up to the last one that was displayed.
+    pub present_count: u64,
+    /// Time of the host at which the last displayed frame appeared on the display.
+    pub present_time: Option<Instant>,
+    /// Count of the vertical blanks of the display when the last displayed frame appeared.
+    pub vblank_count: Option<u64>,
+    /// Number of vertical blanks that showed a frame again because the next one
+    /// wasn't ready, since the statistics were first queried.
+    ///
+    /// Only counted when waiting for the vertical blank.
+    pub dropped_frames: Option<u64>,
+}
+
+/// Video mode of a display, see [`Surface::video_modes`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VideoMode {
+    /// Resolution of the display.
+    pub extent: Extent2D,
+    /// Refresh rate of the display, in millihertz.
+    pub refresh_rate: u32,
+}
+
+/// Fullscreen state of a swapchain, see [`PresentationSurface::set_fullscreen`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FullscreenMode {
+    /// The images are presented in the window, composited with the rest of the desktop.
+    Windowed,
+    /// The swapchain takes exclusive ownership of the display showing the window,
+    /// switching it to the closest supported video mode if one is given, or
+    /// keeping the current one otherwise.
+    Exclusive(Option<VideoMode>),
+}
+
+/// HDR capabilities of a display, see [`Surface::hdr_capabilities`].
+///
+/// The chromaticities are CIE 1931 `(x, y)` coordinates, and the luminances are in nits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HdrCapabilities {
+    /// Chromaticity of the red primary of the display.
+    pub red_primary: [f32; 2],
+    /// Chromaticity of the green primary of the display.
+    pub green_primary: [f32; 2],
+    /// Chromaticity of the blue primary of the display.
+    pub blue_primary: [f32; 2],
+    /// Chromaticity of the white point of the display.
+    pub white_point: [f32; 2],
+    /// Minimum luminance of the display.
+    pub min_luminance: f32,
+    /// Maximum luminance of the display, on a small area.
+    pub max_luminance: f32,
+    /// Maximum luminance of the display, on the whole screen.
+    pub max_full_frame_luminance: f32,
+}
+
+/// HDR metadata of the presented content, letting the display tone map it,
+/// see [`PresentationSurface::set_hdr_metadata`].
+///
+/// The chromaticities are CIE 1931 `(x, y)` coordinates, and the luminances are in nits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HdrMetadata {
+    /// Chromaticity of the red primary of the mastering display.
+    pub red_primary: [f32; 2],
+    /// Chromaticity of the green primary of the mastering display.
+    pub green_primary: [f32; 2],
+    /// Chromaticity of the blue primary of the mastering display.
+    pub blue_primary: [f32; 2],
+    /// Chromaticity of the white point of the mastering display.
+    pub white_point: [f32; 2],
+    /// Maximum luminance of the mastering display.
+    pub max_mastering_luminance: f32,
+    /// Minimum luminance of the mastering display.
+    pub min_mastering_luminance: f32,
+    /// Maximum content light level (MaxCLL), the luminance of the brightest pixel.
+    pub max_content_light_level: f32,
+    /// Maximum frame-average light level (MaxFALL), the average luminance
+    /// of the brightest frame.
+    pub max_frame_average_light_level: f32,
+}
+
+/// Surface format together with the color space to present it in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SurfaceFormat {
+    /// Format of the swapchain images.
+    pub format: Format,
+    /// Color space of the swapchain images.
+    pub color_space: ColorSpace,
+}
+
+/// Formats returned by [`rank_surface_formats`] when the surface has no preference.
+const RECOMMENDED_FORMATS: [Format; 6] = [
+    Format::Bgra8Srgb,
+    Format::Rgba8Srgb,
+    Format::A2r10g10b10Unorm,
+    Format::A2b10g10r10Unorm,
+    Format::Bgra8Unorm,
+    Format::Rgba8Unorm,
+];
+
+/// Rank the formats returned by [`Surface::supported_formats`].
+///
+/// 8-bit sRGB formats come first, since rendering to them is encoded for the
+/// display on every platform, followed by the 10-bit formats. The other formats
+/// keep their order. If the surface has no preferred format, the commonly
+/// supported ones are returned in this order.
+///
+/// All the formats are presented in [`ColorSpace::SrgbNonlinear`], which every
+/// backend supports. Backends supporting HDR output append the formats of the HDR
+/// color spaces to the ranked ones in [`Surface::ranked_formats`].
+pub fn rank_surface_formats(formats: Option<Vec<Format>>) -> Vec<SurfaceFormat> {
+    let mut formats = formats.unwrap_or_else(|| RECOMMENDED_FORMATS.to_vec());
+    formats.sort_by_key(|&format| match format {
+        Format::Bgra8Srgb | Format::Rgba8Srgb => 0,
+        Format::A2r10g10b10Unorm | Format::A2b10g10r10Unorm => 1,
+        _ => 2,
+    });
+    formats
+        .into_iter()
+        .map(|format| SurfaceFormat {
+            format,
+            color_space: ColorSpace::SrgbNonlinear,
+        })
+        .collect()
 }
 
 /// A surface trait that exposes the ability to present images on the
@@ -272,6 +435,60 @@ pub trait PresentationSurface<B: Backend>: Surface<B> {
         &mut self,
         timeout_ns: u64,
     ) -> Result<(Self::SwapchainImage, Option<Suboptimal>), AcquireError>;
+
+    /// Wait until the swapchain is ready to accept a new frame.
+    ///
+    /// This is used for frame pacing: waiting here before sampling the input and
+    /// recording a frame keeps at most [`SwapchainConfig::frame_latency`] frames
+    /// queued for presentation, with lower latency than waiting on a fence per frame.
+    /// The following call to `acquire_image` doesn't wait again for the same frame.
+    ///
+    /// Backends without a waitable presentation mechanism return immediately.
+    unsafe fn wait_for_available_frame(&mut self, _timeout_ns: u64) -> Result<(), AcquireError> {
+        Ok(())
+    }
+
+    /// Change the maximum number of frames queued for presentation by the
+    /// configured swapchain, without configuring it again.
+    ///
+    /// See [`SwapchainConfig::frame_latency`]. Without a configured swapchain, the
+    /// latency replaces the one of the next configuration. Backends without a
+    /// waitable presentation mechanism ignore it.
+    unsafe fn set_frame_latency(&mut self, _latency: u32) {}
+
+    /// Query the statistics of the frames displayed by the configured swapchain,
+    /// for adaptive frame pacing.
+    ///
+    /// Returns `None` if no frame was displayed yet, or if the backend can't
+    /// report them.
+    fn present_statistics(&mut self) -> Option<PresentStatistics> {
+        None
+    }
+
+    /// Set the HDR metadata of the content presented by the configured swapchain,
+    /// or clear it with `None`.
+    ///
+    /// This is meaningful with the HDR color spaces. Backends without HDR output
+    /// ignore the metadata.
+    unsafe fn set_hdr_metadata(&mut self, _metadata: Option<&HdrMetadata>) {}
+
+    /// Enter or leave exclusive fullscreen with the configured swapchain.
+    ///
+    /// Once the state changed, the next `acquire_image` reports the swapchain as
+    /// [`Suboptimal`], and it has to be configured again with the current extent
+    /// of the surface. Tearing is always allowed in exclusive fullscreen with
+    /// [`PresentMode::IMMEDIATE`].
+    ///
+    /// Backends with exclusive fullscreen return [`FullscreenError::NotConfigured`]
+    /// if the swapchain isn't configured. The others stay windowed, accepting
+    /// [`FullscreenMode::Windowed`] whether the swapchain is configured or not,
+    /// and returning [`FullscreenError::Unsupported`] otherwise.
+    unsafe fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), FullscreenError> {
+        match mode {
+            FullscreenMode::Windowed => Ok(()),
+            FullscreenMode::Exclusive(_) => Err(FullscreenError::Unsupported),
+        }
+    }
 }
 
 /// Index of an image in the swapchain.
@@ -287,6 +504,9 @@ bitflags!(
     #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     pub struct PresentMode: u32 {
         /// Don't ever wait for v-sync.
+        ///
+        /// Frames may tear, and displays with a variable refresh rate
+        /// refresh as soon as a frame is presented.
         const IMMEDIATE = 0x1;
         /// Wait for v-sync, overwrite the last rendered frame.
         const MAILBOX = 0x2;
@@ -327,6 +547,33 @@ bitflags!(
     }
 );
 
+/// Specifies how the presentation engine interprets the contents of the
+/// swapchain images.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ColorSpace {
+    /// The image contents are sRGB-encoded and presented as-is. Rendering
+    /// through an sRGB format encodes the written values, while rendering
+    /// through a linear format is expected to write encoded values already.
+    SrgbNonlinear,
+    /// The image contents are in linear light and get sRGB-encoded by the
+    /// presentation engine.
+    Linear,
+    /// scRGB: the image contents are in linear light with the BT.709 primaries,
+    /// 1.0 being the SDR white of 80 nits. Values beyond `0.0..=1.0` encode
+    /// brighter and wider gamut colors, so this is used with floating point formats.
+    ExtendedSrgbLinear,
+    /// HDR10: the image contents use the BT.2020 primaries, encoded with the
+    /// SMPTE ST 2084 (PQ) transfer function. This is used with 10-bit formats.
+    Hdr10St2084,
+}
+
+impl Default for ColorSpace {
+    fn default() -> Self {
+        ColorSpace::SrgbNonlinear
+    }
+}
+
 /// Contains all the data necessary to create a new `Swapchain`:
 /// color, depth, and number of images.
 ///
@@ -362,6 +609,18 @@ pub struct SwapchainConfig {
     pub image_layers: image::Layer,
     /// Image usage of the backbuffer images.
     pub image_usage: image::Usage,
+    /// Maximum number of frames queued for presentation, as waited for by
+    /// [`PresentationSurface::wait_for_available_frame`].
+    ///
+    /// `None` defaults to the number of images.
+    pub frame_latency: Option<u32>,
+    /// Color space of the backbuffer images.
+    ///
+    /// Backends without control over the presentation color space only support
+    /// [`ColorSpace::SrgbNonlinear`], returning [`SwapchainError::UnsupportedColorSpace`]
+    /// for the other ones. The HDR color spaces are only
+    /// supported with the formats returned along with them by [`Surface::ranked_formats`].
+    pub color_space: ColorSpace,
 }
 
 impl SwapchainConfig {
@@ -375,6 +634,8 @@ impl SwapchainConfig {
             image_count,
             image_layers: 1,
             image_usage: DEFAULT_USAGE,
+            frame_latency: None,
+            color_space: ColorSpace::SrgbNonlinear,
         }
     }
 
@@ -424,6 +685,8 @@ impl SwapchainConfig {
                 .min(*caps.image_count.end()),
             image_layers: 1,
             image_usage: DEFAULT_USAGE,
+            frame_latency: None,
+            color_space: ColorSpace::SrgbNonlinear,
         }
     }
 
@@ -451,6 +714,18 @@ impl SwapchainConfig {
         self
     }
 
+    /// Specify the maximum number of frames queued for presentation.
+    pub fn with_frame_latency(mut self, latency: u32) -> Self {
+        self.frame_latency = Some(latency);
+        self
+    }
+
+    /// Specify the color space of the backbuffer images.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
     // TODO: depth-only, stencil-only, swapchain size, present modes, etc.
 }
 
@@ -504,10 +779,37 @@ pub enum PresentError {
     DeviceLost(#[from] device::DeviceLost),
 }
 
+/// Error on changing the fullscreen state of a swapchain.
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum FullscreenError {
+    /// Out of either host or device memory.
+    #[error(transparent)]
+    OutOfMemory(#[from] device::OutOfMemory),
+    /// The backend or the display doesn't support exclusive fullscreen.
+    #[error("Exclusive fullscreen is not supported")]
+    Unsupported,
+    /// The display is owned by another application, or the window isn't focused.
+    #[error("Display is not currently available")]
+    Unavailable,
+    /// The swapchain of the surface isn't configured.
+    #[error("Swapchain is not configured")]
+    NotConfigured,
+}
+
 /// Error occurred during surface creation.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
 pub enum InitError {
     /// Window handle is not supported by the backend.
     #[error("Specified window handle is unsupported")]
     UnsupportedWindowHandle,
+    /// Window handle is null.
+    #[error("Specified window handle is invalid")]
+    InvalidWindowHandle,
+    /// The window system of the handle is supported by the backend, but not by
+    /// the instance, e.g. if its extension is missing or the instance is headless.
+    #[error("Window system is not available to the instance")]
+    WindowSystemUnavailable,
+    /// The window system failed to create the surface.
+    #[error("Surface can't be created for an unknown reason")]
+    Unknown,
 }
@@ -659,6 +659,8 @@ impl<B: hal::Backend> Scene<B> {
                         inputs: &t.2,
                         preserves: &t.3,
                         resolves: &t.4,
+                        view_mask: 0,
+                        shading_rate: None,
                     });
                     let raw_deps = dependencies.iter().map(|dep| hal::pass::SubpassDependency {
                         passes: subpass_ref(&dep.passes.start)..subpass_ref(&dep.passes.end),
@@ -953,6 +955,7 @@ impl<B: hal::Backend> Scene<B> {
                         },
                         flags: pso::PipelineCreationFlags::empty(),
                         parent: pso::BasePipeline::None,
+                        transform_feedback: None,
                     };
                     let pso = unsafe { device.create_graphics_pipeline(&desc, None) }.unwrap();
                     resources.graphics_pipelines.insert(name.clone(), pso);
@@ -1516,7 +1519,9 @@ impl<B: hal::Backend> Scene<B> {
             .chain(submits)
             .chain(iter::once(&self.finish_submit));
         unsafe {
-            self.queue_group.queues[0].submit(command_buffers, iter::empty(), iter::empty(), None);
+            self.queue_group.queues[0]
+                .submit(command_buffers, iter::empty(), iter::empty(), None)
+                .unwrap();
         }
     }
 
@@ -1603,12 +1608,14 @@ impl<B: hal::Backend> Scene<B> {
             .create_fence(false)
             .expect("Can't create copy-fence");
         unsafe {
-            self.queue_group.queues[0].submit(
-                iter::once(&cmd_buffer),
-                iter::empty(),
-                iter::empty(),
-                Some(&mut copy_fence),
-            );
+            self.queue_group.queues[0]
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::empty(),
+                    Some(&mut copy_fence),
+                )
+                .unwrap();
             self.device.wait_for_fence(&copy_fence, !0).unwrap();
             self.device.destroy_fence(copy_fence);
             self.device.destroy_command_pool(command_pool);
@@ -1755,12 +1762,14 @@ impl<B: hal::Backend> Scene<B> {
             .create_fence(false)
             .expect("Can't create copy-fence");
         unsafe {
-            self.queue_group.queues[0].submit(
-                iter::once(&cmd_buffer),
-                iter::empty(),
-                iter::empty(),
-                Some(&mut copy_fence),
-            );
+            self.queue_group.queues[0]
+                .submit(
+                    iter::once(&cmd_buffer),
+                    iter::empty(),
+                    iter::empty(),
+                    Some(&mut copy_fence),
+                )
+                .unwrap();
             self.device.wait_for_fence(&copy_fence, !0).unwrap();
             self.device.destroy_fence(copy_fence);
             self.device.destroy_command_pool(command_pool);